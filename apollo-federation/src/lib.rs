@@ -28,6 +28,7 @@ pub(crate) mod operation;
 pub mod query_graph;
 pub mod query_plan;
 pub mod schema;
+pub mod sources;
 pub mod subgraph;
 pub(crate) mod supergraph;
 pub(crate) mod utils;
@@ -43,7 +44,10 @@ use crate::error::FederationError;
 use crate::error::SingleFederationError;
 use crate::link::join_spec_definition::JoinSpecDefinition;
 use crate::link::link_spec_definition::LinkSpecDefinition;
+use crate::link::link_spec_definition::CORE_VERSIONS;
+use crate::link::link_spec_definition::LINK_VERSIONS;
 use crate::link::spec::Identity;
+use crate::link::spec::Version;
 use crate::link::spec_definition::SpecDefinitions;
 use crate::merge::merge_subgraphs;
 use crate::merge::MergeFailure;
@@ -90,6 +94,21 @@ pub(crate) fn validate_supergraph(
     Ok((link_spec_definition, join_spec_definition))
 }
 
+/// Returns the versions supported for the specs a supergraph needs to be planned: the link (or
+/// core) and join specs. Returns `None` for any other spec.
+pub fn supported_supergraph_spec_versions(identity: &Identity) -> Option<Vec<Version>> {
+    let versions = if *identity == Identity::join_identity() {
+        JOIN_VERSIONS.versions().cloned().collect()
+    } else if *identity == Identity::link_identity() {
+        LINK_VERSIONS.versions().cloned().collect()
+    } else if *identity == Identity::core_identity() {
+        CORE_VERSIONS.versions().cloned().collect()
+    } else {
+        return None;
+    };
+    Some(versions)
+}
+
 pub struct Supergraph {
     pub schema: ValidFederationSchema,
 }
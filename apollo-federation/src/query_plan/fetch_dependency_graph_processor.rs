@@ -5,6 +5,7 @@ use apollo_compiler::executable::VariableDefinition;
 use apollo_compiler::Name;
 use apollo_compiler::Node;
 
+use super::query_planner::QueryPlanStrategy;
 use super::query_planner::SubgraphOperationCompression;
 use crate::error::FederationError;
 use crate::operation::DirectiveList;
@@ -76,8 +77,40 @@ pub(crate) struct FetchDependencyGraphToQueryPlanProcessor {
 ///    it assumes that the networking and other query processing costs are much higher than
 ///    the cost of resolving a single field. Or to put it more concretely, it assumes that
 ///    a fetch of 5 fields is probably not too different from than of 2 fields.
+///
+/// The weights of fetches and sequences depend on the [`QueryPlanStrategy`].
 #[derive(Clone, Copy)]
-pub(crate) struct FetchDependencyGraphToCostProcessor;
+pub(crate) struct FetchDependencyGraphToCostProcessor {
+    strategy: QueryPlanStrategy,
+}
+
+impl FetchDependencyGraphToCostProcessor {
+    pub(crate) fn new(strategy: QueryPlanStrategy) -> Self {
+        Self { strategy }
+    }
+
+    /// The base cost of doing a fetch, see `FETCH_COST`.
+    fn fetch_cost(&self) -> QueryPlanCost {
+        match self.strategy {
+            QueryPlanStrategy::Balanced | QueryPlanStrategy::MaximizeParallelism => FETCH_COST,
+            // Fetches dominate the cost of fields even more, so that removing a fetch is always
+            // worth fetching more fields.
+            QueryPlanStrategy::MinimizeFetches => FETCH_COST * 10.0,
+        }
+    }
+
+    /// The multiplier of the cost of fetches made in sequences, see `PIPELINING_COST`.
+    fn pipelining_cost(&self) -> QueryPlanCost {
+        match self.strategy {
+            QueryPlanStrategy::Balanced => PIPELINING_COST,
+            // Stages of a sequence are not weighted by their depth (each one is multiplied by 1),
+            // so a fetch costs the same in a sequence as in parallel and the plan with the fewest
+            // fetches wins.
+            QueryPlanStrategy::MinimizeFetches => 0.0,
+            QueryPlanStrategy::MaximizeParallelism => PIPELINING_COST * 10.0,
+        }
+    }
+}
 
 /// Generic interface for "processing" a (reduced) dependency graph of fetch dependency nodes
 /// (a `FetchDependencyGraph`).
@@ -163,7 +196,7 @@ impl FetchDependencyGraphProcessor<QueryPlanCost, QueryPlanCost>
         node: &mut FetchDependencyGraphNode,
         _handled_conditions: &Conditions,
     ) -> Result<QueryPlanCost, FederationError> {
-        Ok(FETCH_COST + node.cost()?)
+        Ok(self.fetch_cost() + node.cost()?)
     }
 
     /// We don't take conditions into account in costing for now
@@ -196,7 +229,7 @@ impl FetchDependencyGraphProcessor<QueryPlanCost, QueryPlanCost>
         &mut self,
         values: impl IntoIterator<Item = QueryPlanCost>,
     ) -> QueryPlanCost {
-        sequence_cost(values)
+        sequence_cost(values, self.pipelining_cost())
     }
 
     /// This method exists so we can inject the necessary information for deferred block when
@@ -226,7 +259,10 @@ impl FetchDependencyGraphProcessor<QueryPlanCost, QueryPlanCost>
         _sub_selection: &SelectionSet,
         deferred_blocks: Vec<QueryPlanCost>,
     ) -> Result<QueryPlanCost, FederationError> {
-        Ok(sequence_cost([main, parallel_cost(deferred_blocks)]))
+        Ok(sequence_cost(
+            [main, parallel_cost(deferred_blocks)],
+            self.pipelining_cost(),
+        ))
     }
 }
 
@@ -234,11 +270,14 @@ fn parallel_cost(values: impl IntoIterator<Item = QueryPlanCost>) -> QueryPlanCo
     values.into_iter().sum()
 }
 
-fn sequence_cost(values: impl IntoIterator<Item = QueryPlanCost>) -> QueryPlanCost {
+fn sequence_cost(
+    values: impl IntoIterator<Item = QueryPlanCost>,
+    pipelining_cost: QueryPlanCost,
+) -> QueryPlanCost {
     values
         .into_iter()
         .enumerate()
-        .map(|(i, stage)| stage * (1.0f64).max(i as QueryPlanCost * PIPELINING_COST))
+        .map(|(i, stage)| stage * (1.0f64).max(i as QueryPlanCost * pipelining_cost))
         .sum()
 }
 
@@ -458,3 +497,52 @@ fn flat_wrap_nodes(
         NodeKind::Sequence => PlanNode::Sequence(SequenceNode { nodes }),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cost of a plan fetching `fields` fields in each of the given sequential stages of parallel
+    /// fetches.
+    fn plan_cost(
+        strategy: QueryPlanStrategy,
+        stages: &[usize],
+        fields: QueryPlanCost,
+    ) -> QueryPlanCost {
+        let mut processor = FetchDependencyGraphToCostProcessor::new(strategy);
+        let fetch = processor.fetch_cost() + fields;
+        let stages: Vec<_> = stages
+            .iter()
+            .map(|fetches| processor.reduce_parallel(std::iter::repeat(fetch).take(*fetches)))
+            .collect();
+        processor.reduce_sequence(stages)
+    }
+
+    #[test]
+    fn strategies_trade_fetches_for_parallelism() {
+        // 2 fetches in sequence, or 3 fetches in parallel
+        let sequential = [1, 1];
+        let parallel = [3];
+
+        assert!(
+            plan_cost(QueryPlanStrategy::Balanced, &sequential, 5.0)
+                > plan_cost(QueryPlanStrategy::Balanced, &parallel, 5.0)
+        );
+        assert!(
+            plan_cost(QueryPlanStrategy::MaximizeParallelism, &sequential, 5.0)
+                > plan_cost(QueryPlanStrategy::MaximizeParallelism, &parallel, 5.0)
+        );
+        assert!(
+            plan_cost(QueryPlanStrategy::MinimizeFetches, &sequential, 5.0)
+                < plan_cost(QueryPlanStrategy::MinimizeFetches, &parallel, 5.0)
+        );
+    }
+
+    #[test]
+    fn minimize_fetches_does_not_weigh_sequences() {
+        assert_eq!(
+            plan_cost(QueryPlanStrategy::MinimizeFetches, &[1, 1, 1], 5.0),
+            plan_cost(QueryPlanStrategy::MinimizeFetches, &[3], 5.0)
+        );
+    }
+}
@@ -91,6 +91,12 @@ pub struct QueryPlannerConfig {
     // support @stream, grouping the options here will make sense too.
     pub incremental_delivery: QueryPlanIncrementalDeliveryConfig,
 
+    /// How the query planner picks between the possible plans of an operation. This can be
+    /// overridden for a single operation with [`QueryPlanner::build_query_plan_with_strategy`].
+    ///
+    /// Defaults to [`QueryPlanStrategy::Balanced`].
+    pub strategy: QueryPlanStrategy,
+
     /// A sub-set of configurations that are meant for debugging or testing. All the configurations
     /// in this sub-set are provided without guarantees of stability (they may be dangerous) or
     /// continued support (they may be removed without warning).
@@ -104,11 +110,27 @@ impl Default for QueryPlannerConfig {
             subgraph_graphql_validation: false,
             generate_query_fragments: false,
             incremental_delivery: Default::default(),
+            strategy: Default::default(),
             debug: Default::default(),
         }
     }
 }
 
+/// The trade-off made by the query planner when it picks between the possible plans of an
+/// operation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum QueryPlanStrategy {
+    /// Weighs the number of subgraph fetches against the length of the sequences of fetches.
+    #[default]
+    Balanced,
+    /// Favors plans making fewer subgraph fetches, even if more of them are made in sequence.
+    /// This lowers the load on subgraphs.
+    MinimizeFetches,
+    /// Favors plans making fewer fetches in sequence, even if more fetches are made in parallel.
+    /// This lowers the latency of operations.
+    MaximizeParallelism,
+}
+
 #[derive(Debug, Clone, Default, Hash)]
 pub struct QueryPlanIncrementalDeliveryConfig {
     /// Enables @defer support by the query planner.
@@ -329,6 +351,17 @@ impl QueryPlanner {
         &self,
         document: &Valid<ExecutableDocument>,
         operation_name: Option<Name>,
+    ) -> Result<QueryPlan, FederationError> {
+        self.build_query_plan_with_strategy(document, operation_name, self.config.strategy)
+    }
+
+    /// Builds the query plan of an operation with the given strategy instead of the configured
+    /// one.
+    pub fn build_query_plan_with_strategy(
+        &self,
+        document: &Valid<ExecutableDocument>,
+        operation_name: Option<Name>,
+        strategy: QueryPlanStrategy,
     ) -> Result<QueryPlan, FederationError> {
         let operation = document
             .operations
@@ -471,7 +504,10 @@ impl QueryPlanner {
                 .abstract_types_with_inconsistent_runtime_types
                 .clone()
                 .into(),
-            config: self.config.clone(),
+            config: QueryPlannerConfig {
+                strategy,
+                ..self.config.clone()
+            },
             // PORT_NOTE: JS provides `override_conditions` here: see port note in `QueryPlanner::new`.
         };
 
@@ -784,7 +820,7 @@ fn compute_root_parallel_best_plan(
         selection,
         has_defers,
         parameters.operation.root_kind,
-        FetchDependencyGraphToCostProcessor,
+        FetchDependencyGraphToCostProcessor::new(parameters.config.strategy),
     )?;
 
     // Getting no plan means the query is essentially unsatisfiable (it's a valid query, but we can prove it will never return a result),
@@ -0,0 +1,279 @@
+//! Merging of the responses of the upstream calls made for a connector fetch
+//!
+//! A fetch resolved by a connector can issue several upstream requests: one per root field, or
+//! one per entity when the upstream API cannot batch them. Each response is mapped on its own, so
+//! that a failed request only produces an error at the path of its root field or entity, while
+//! the results of the other requests are still returned. Nulls are placed according to the
+//! nullability of the failed field.
+
+use serde_json_bytes::json;
+use serde_json_bytes::Map;
+use serde_json_bytes::Value as JSON;
+
+use super::json_selection::ApplyTo;
+use super::json_selection::JSONSelection;
+
+/// Error code of the errors for failed upstream requests
+const FETCH_ERROR_CODE: &str = "CONNECTOR_FETCH";
+/// Error code of the errors for upstream responses the selection could not be applied to
+const MAPPING_ERROR_CODE: &str = "CONNECTOR_MAPPING";
+const ENTITIES: &str = "_entities";
+
+/// The part of the fetch an upstream request was made for
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ResponseKey {
+    /// A root field of the operation
+    RootField {
+        /// Response key of the field, its alias or its name
+        name: String,
+        /// Whether the type of the field is non-null
+        non_null: bool,
+    },
+    /// An entity, at its index in the representations of the fetch
+    Entity { index: usize },
+}
+
+/// A failed upstream request
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConnectorError {
+    pub message: String,
+    /// HTTP status code of the response, if one was received
+    pub status: Option<u16>,
+}
+
+/// The result of an upstream request made for a connector fetch
+#[derive(Clone, Debug)]
+pub struct ConnectorResponse {
+    pub key: ResponseKey,
+    /// Body of the upstream response
+    pub result: Result<JSON, ConnectorError>,
+}
+
+/// The GraphQL response of a connector fetch
+#[derive(Clone, Debug, PartialEq)]
+pub struct MappedResponse {
+    /// Data of the response, `null` if a non-null root field failed
+    pub data: JSON,
+    /// GraphQL errors, with the path of the root field or entity they apply to
+    pub errors: Vec<JSON>,
+}
+
+/// Maps the responses of the upstream requests of a fetch with the selection of the connector,
+/// returning the results of the successful requests along with errors for the failed ones
+pub fn handle_responses(
+    selection: &JSONSelection,
+    responses: Vec<ConnectorResponse>,
+) -> MappedResponse {
+    let mut data = Map::new();
+    let mut entities: Vec<JSON> = Vec::new();
+    let mut has_entities = false;
+    let mut errors = Vec::new();
+    let mut null_data = false;
+
+    for response in responses {
+        let path = match &response.key {
+            ResponseKey::RootField { name, .. } => vec![JSON::String(name.as_str().into())],
+            ResponseKey::Entity { index } => {
+                vec![JSON::String(ENTITIES.into()), JSON::Number((*index).into())]
+            }
+        };
+        let value = match response.result {
+            Ok(body) => match selection.apply_to(&body) {
+                (Some(value), _) => Some(value),
+                (None, apply_errors) => {
+                    let message = apply_errors
+                        .first()
+                        .and_then(|error| error.message())
+                        .unwrap_or("the selection did not match the response")
+                        .to_string();
+                    errors.push(graphql_error(
+                        format!("could not map the connector response: {message}"),
+                        &path,
+                        MAPPING_ERROR_CODE,
+                        None,
+                    ));
+                    None
+                }
+            },
+            Err(error) => {
+                errors.push(graphql_error(
+                    error.message,
+                    &path,
+                    FETCH_ERROR_CODE,
+                    error.status,
+                ));
+                None
+            }
+        };
+
+        match response.key {
+            ResponseKey::RootField { name, non_null } => {
+                // a null non-null root field makes the whole data null
+                if value.is_none() && non_null {
+                    null_data = true;
+                }
+                data.insert(name, value.unwrap_or(JSON::Null));
+            }
+            // entities are nullable in `_entities: [_Any]!`
+            ResponseKey::Entity { index } => {
+                has_entities = true;
+                if entities.len() <= index {
+                    entities.resize(index + 1, JSON::Null);
+                }
+                entities[index] = value.unwrap_or(JSON::Null);
+            }
+        }
+    }
+
+    if has_entities {
+        data.insert(ENTITIES, JSON::Array(entities));
+    }
+
+    MappedResponse {
+        data: if null_data {
+            JSON::Null
+        } else {
+            JSON::Object(data)
+        },
+        errors,
+    }
+}
+
+fn graphql_error(message: String, path: &[JSON], code: &str, status: Option<u16>) -> JSON {
+    let mut extensions = Map::new();
+    extensions.insert("code", JSON::String(code.into()));
+    if let Some(status) = status {
+        extensions.insert("http", json!({ "status": status }));
+    }
+    json!({
+        "message": message,
+        "path": path,
+        "extensions": extensions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selection() -> JSONSelection {
+        JSONSelection::parse("id name").unwrap().1
+    }
+
+    fn fetch_error(status: u16) -> Result<JSON, ConnectorError> {
+        Err(ConnectorError {
+            message: "upstream request failed".to_string(),
+            status: Some(status),
+        })
+    }
+
+    #[test]
+    fn it_returns_the_entities_of_the_successful_requests() {
+        let response = handle_responses(
+            &selection(),
+            vec![
+                ConnectorResponse {
+                    key: ResponseKey::Entity { index: 0 },
+                    result: Ok(json!({ "id": "1", "name": "Table", "price": 10 })),
+                },
+                ConnectorResponse {
+                    key: ResponseKey::Entity { index: 1 },
+                    result: fetch_error(500),
+                },
+                ConnectorResponse {
+                    key: ResponseKey::Entity { index: 2 },
+                    result: Ok(json!({ "id": "3", "name": "Chair" })),
+                },
+            ],
+        );
+        assert_eq!(
+            response.data,
+            json!({
+                "_entities": [
+                    { "id": "1", "name": "Table" },
+                    null,
+                    { "id": "3", "name": "Chair" },
+                ]
+            })
+        );
+        assert_eq!(
+            response.errors,
+            vec![json!({
+                "message": "upstream request failed",
+                "path": ["_entities", 1],
+                "extensions": { "code": "CONNECTOR_FETCH", "http": { "status": 500 } },
+            })]
+        );
+    }
+
+    #[test]
+    fn it_nulls_failed_nullable_root_fields() {
+        let response = handle_responses(
+            &selection(),
+            vec![
+                ConnectorResponse {
+                    key: ResponseKey::RootField {
+                        name: "product".to_string(),
+                        non_null: false,
+                    },
+                    result: fetch_error(404),
+                },
+                ConnectorResponse {
+                    key: ResponseKey::RootField {
+                        name: "me".to_string(),
+                        non_null: true,
+                    },
+                    result: Ok(json!({ "id": "1", "name": "Ada" })),
+                },
+            ],
+        );
+        assert_eq!(
+            response.data,
+            json!({ "product": null, "me": { "id": "1", "name": "Ada" } })
+        );
+        assert_eq!(response.errors.len(), 1);
+        assert_eq!(response.errors[0]["path"], json!(["product"]));
+    }
+
+    #[test]
+    fn it_nulls_the_data_when_a_non_null_root_field_fails() {
+        let response = handle_responses(
+            &selection(),
+            vec![
+                ConnectorResponse {
+                    key: ResponseKey::RootField {
+                        name: "product".to_string(),
+                        non_null: true,
+                    },
+                    result: fetch_error(503),
+                },
+                ConnectorResponse {
+                    key: ResponseKey::RootField {
+                        name: "me".to_string(),
+                        non_null: false,
+                    },
+                    result: Ok(json!({ "id": "1", "name": "Ada" })),
+                },
+            ],
+        );
+        assert_eq!(response.data, JSON::Null);
+        assert_eq!(response.errors.len(), 1);
+    }
+
+    #[test]
+    fn it_reports_responses_the_selection_cannot_be_applied_to() {
+        let response = handle_responses(
+            &JSONSelection::parse(".data.product").unwrap().1,
+            vec![ConnectorResponse {
+                key: ResponseKey::Entity { index: 0 },
+                result: Ok(json!({ "errors": ["not found"] })),
+            }],
+        );
+        assert_eq!(response.data, json!({ "_entities": [null] }));
+        assert_eq!(
+            response.errors[0]["extensions"]["code"],
+            json!("CONNECTOR_MAPPING")
+        );
+        assert_eq!(response.errors[0]["path"], json!(["_entities", 0]));
+    }
+}
@@ -0,0 +1,385 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use apollo_compiler::ast::FieldDefinition;
+use apollo_compiler::ast::InputValueDefinition;
+use apollo_compiler::ast::Type;
+use apollo_compiler::schema::ExtendedType;
+use apollo_compiler::validation::Valid;
+use apollo_compiler::Node;
+use apollo_compiler::Schema;
+use serde::Serialize;
+use serde_json_bytes::Value as JSON;
+
+use super::json_selection::ApplyTo;
+use super::json_selection::JSONSelection;
+use super::json_selection::Key;
+use super::json_selection::NamedSelection;
+use super::json_selection::PathSelection;
+use super::json_selection::SubSelection;
+use super::url_path_template::URLPathTemplate;
+
+/// The variable referring to the arguments of the field in a body template.
+const ARGS_VAR: &str = "$args";
+
+/// The HTTP method of a connector request.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum HTTPMethod {
+    #[default]
+    Get,
+    Post,
+    Put,
+    Patch,
+    Delete,
+}
+
+impl HTTPMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HTTPMethod::Get => "GET",
+            HTTPMethod::Post => "POST",
+            HTTPMethod::Put => "PUT",
+            HTTPMethod::Patch => "PATCH",
+            HTTPMethod::Delete => "DELETE",
+        }
+    }
+
+    // GET requests are expected to be safe and cacheable, their body has no
+    // defined semantics.
+    fn accepts_body(&self) -> bool {
+        !matches!(self, HTTPMethod::Get)
+    }
+}
+
+impl FromStr for HTTPMethod {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.to_ascii_uppercase().as_str() {
+            "GET" => Ok(HTTPMethod::Get),
+            "POST" => Ok(HTTPMethod::Post),
+            "PUT" => Ok(HTTPMethod::Put),
+            "PATCH" => Ok(HTTPMethod::Patch),
+            "DELETE" => Ok(HTTPMethod::Delete),
+            _ => Err(format!("Unsupported HTTP method {}", input)),
+        }
+    }
+}
+
+impl Display for HTTPMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The HTTP request sent by a connector for a field: its method, its URL path,
+/// and for the methods accepting one, the template of its JSON body.
+///
+/// The body template is a JSONSelection applied to the arguments of the field,
+/// which are also available as the `$args` variable:
+///
+/// ```graphql
+/// type Mutation {
+///   updateProduct(id: ID!, input: ProductInput!): Product
+///   # PATCH /products/{id}
+///   # body: "name: $args.input.name dimensions: $args.input.dimensions { width height }"
+/// }
+/// ```
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct HTTPRequestTemplate {
+    pub method: HTTPMethod,
+    pub path: URLPathTemplate,
+    pub body: Option<JSONSelection>,
+}
+
+impl HTTPRequestTemplate {
+    pub fn parse(method: &str, path: &str, body: Option<&str>) -> Result<Self, String> {
+        let method = method.parse::<HTTPMethod>()?;
+        let path = URLPathTemplate::parse(path)?;
+        let body = body
+            .map(|body| {
+                JSONSelection::parse(body)
+                    .map(|(_, selection)| selection)
+                    .map_err(|e| format!("Invalid body template {}: {}", body, e))
+            })
+            .transpose()?;
+
+        if body.is_some() && !method.accepts_body() {
+            return Err(format!("{} requests cannot have a body", method));
+        }
+
+        Ok(Self { method, path, body })
+    }
+
+    // Given the arguments of the field, generate the JSON body of the request,
+    // if it has one.
+    pub fn generate_body(&self, args: &JSON) -> Result<Option<JSON>, String> {
+        let Some(body) = &self.body else {
+            return Ok(None);
+        };
+
+        let vars = [(ARGS_VAR.to_string(), args.clone())].into_iter().collect();
+        let (value, errors) = body.apply_with_vars(args, &vars);
+        if let Some(error) = errors.first() {
+            return Err(format!(
+                "Could not generate the request body: {}",
+                error.message().unwrap_or("unknown error")
+            ));
+        }
+        Ok(Some(value.unwrap_or(JSON::Null)))
+    }
+
+    // Check that the body template only selects arguments of the field (and
+    // fields of its input object arguments), so that mistakes are reported
+    // when the schema is loaded, rather than when the request is sent.
+    pub fn validate(
+        &self,
+        schema: &Valid<Schema>,
+        field: &FieldDefinition,
+    ) -> Result<(), Vec<String>> {
+        let Some(body) = &self.body else {
+            return Ok(());
+        };
+
+        let mut validator = BodyValidator {
+            schema,
+            field,
+            errors: vec![],
+        };
+        let root = Shape::Arguments(&field.arguments);
+        match body {
+            JSONSelection::Named(selection) => validator.sub_selection(selection, root),
+            JSONSelection::Path(path) => validator.path(path, root),
+        }
+
+        if validator.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(validator.errors)
+        }
+    }
+}
+
+/// The GraphQL shape of the value a body template is applied to.
+#[derive(Clone, Copy)]
+enum Shape<'a> {
+    Arguments(&'a [Node<InputValueDefinition>]),
+    Type(&'a Type),
+}
+
+struct BodyValidator<'a> {
+    schema: &'a Valid<Schema>,
+    field: &'a FieldDefinition,
+    errors: Vec<String>,
+}
+
+impl<'a> BodyValidator<'a> {
+    // Look up a key in the shape, returning the type of the selected value.
+    fn lookup(&mut self, shape: Shape<'a>, key: &str) -> Option<Shape<'a>> {
+        let found = match shape {
+            Shape::Arguments(arguments) => arguments
+                .iter()
+                .find(|argument| argument.name.as_str() == key)
+                .map(|argument| &*argument.ty),
+            Shape::Type(ty) => {
+                let schema = self.schema;
+                let type_name = ty.inner_named_type();
+                match schema.types.get(type_name) {
+                    Some(ExtendedType::InputObject(input_object)) => input_object
+                        .fields
+                        .get(key)
+                        .map(|input_field| &*input_field.ty),
+                    _ => {
+                        self.errors.push(format!(
+                            "Body template of field {} selects {} in {}, which is not an input object",
+                            self.field.name, key, type_name
+                        ));
+                        return None;
+                    }
+                }
+            }
+        };
+
+        if found.is_none() {
+            let parent = match shape {
+                Shape::Arguments(_) => "the arguments".to_string(),
+                Shape::Type(ty) => ty.inner_named_type().to_string(),
+            };
+            self.errors.push(format!(
+                "Body template of field {} selects {}, which is not defined in {}",
+                self.field.name, key, parent
+            ));
+        }
+        found.map(Shape::Type)
+    }
+
+    fn sub_selection(&mut self, selection: &SubSelection, shape: Shape<'a>) {
+        for named in selection.selections_iter() {
+            match named {
+                NamedSelection::Field(_, name, sub) | NamedSelection::Quoted(_, name, sub) => {
+                    if let Some(shape) = self.lookup(shape, name) {
+                        if let Some(sub) = sub {
+                            self.sub_selection(sub, shape);
+                        }
+                    }
+                }
+                NamedSelection::Path(_, path) => self.path(path, shape),
+                NamedSelection::Group(_, sub) => self.sub_selection(sub, shape),
+            }
+        }
+    }
+
+    fn path(&mut self, path: &PathSelection, shape: Shape<'a>) {
+        match path {
+            PathSelection::Var(var, tail) => {
+                if var == "$" {
+                    self.path(tail, shape);
+                } else if var == ARGS_VAR {
+                    let field = self.field;
+                    self.path(tail, Shape::Arguments(&field.arguments));
+                } else {
+                    self.errors.push(format!(
+                        "Body template of field {} uses the unknown variable {}",
+                        self.field.name, var
+                    ));
+                }
+            }
+            PathSelection::Key(key, tail) => {
+                let name = match key {
+                    Key::Field(name) | Key::Quoted(name) => name.as_str(),
+                    // List items have the type of the list
+                    Key::Index(_) => {
+                        self.path(tail, shape);
+                        return;
+                    }
+                };
+                if let Some(shape) = self.lookup(shape, name) {
+                    self.path(tail, shape);
+                }
+            }
+            PathSelection::Selection(sub) => self.sub_selection(sub, shape),
+            PathSelection::Empty => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json_bytes::json;
+
+    use super::*;
+
+    const SCHEMA: &str = r#"
+        type Query {
+            product(id: ID!): Product
+        }
+
+        type Mutation {
+            updateProduct(id: ID!, input: ProductInput!): Product
+            deleteProduct(id: ID!): Product
+        }
+
+        type Product {
+            id: ID!
+            name: String
+        }
+
+        input ProductInput {
+            name: String
+            dimensions: DimensionsInput
+            tags: [String!]
+        }
+
+        input DimensionsInput {
+            width: Int
+            height: Int
+        }
+    "#;
+
+    fn validate(template: &HTTPRequestTemplate, field_name: &str) -> Result<(), Vec<String>> {
+        let schema = Schema::parse_and_validate(SCHEMA, "schema.graphql").unwrap();
+        let field = schema.type_field("Mutation", field_name).unwrap();
+        template.validate(&schema, field)
+    }
+
+    #[test]
+    fn test_parse_method() {
+        assert_eq!("patch".parse::<HTTPMethod>(), Ok(HTTPMethod::Patch));
+        assert_eq!("DELETE".parse::<HTTPMethod>(), Ok(HTTPMethod::Delete));
+        assert!("TRACE".parse::<HTTPMethod>().is_err());
+    }
+
+    #[test]
+    fn test_get_without_body() {
+        assert_eq!(
+            HTTPRequestTemplate::parse("GET", "/products/{id}", Some("id")),
+            Err("GET requests cannot have a body".to_string()),
+        );
+        let template = HTTPRequestTemplate::parse("GET", "/products/{id}", None).unwrap();
+        assert_eq!(template.generate_body(&json!({ "id": 1 })), Ok(None));
+    }
+
+    #[test]
+    fn test_generate_body() {
+        let template = HTTPRequestTemplate::parse(
+            "PATCH",
+            "/products/{id}",
+            Some("name: $args.input.name size: $args.input.dimensions { width height } tags: input.tags"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            template.generate_body(&json!({
+                "id": "1",
+                "input": {
+                    "name": "table",
+                    "dimensions": { "width": 100, "height": 80, "depth": 60 },
+                    "tags": ["wood"],
+                },
+            })),
+            Ok(Some(json!({
+                "name": "table",
+                "size": { "width": 100, "height": 80 },
+                "tags": ["wood"],
+            }))),
+        );
+
+        assert!(template.generate_body(&json!({ "id": "1" })).is_err());
+    }
+
+    #[test]
+    fn test_validate_body() {
+        let template = HTTPRequestTemplate::parse(
+            "PUT",
+            "/products/{id}",
+            Some("id name: $args.input.name dimensions: input.dimensions { width height }"),
+        )
+        .unwrap();
+        assert_eq!(validate(&template, "updateProduct"), Ok(()));
+
+        let template = HTTPRequestTemplate::parse(
+            "PUT",
+            "/products/{id}",
+            Some("name: $args.input.title width: $args.id.width other: $this.id"),
+        )
+        .unwrap();
+        assert_eq!(
+            validate(&template, "updateProduct"),
+            Err(vec![
+                "Body template of field updateProduct selects title, which is not defined in ProductInput".to_string(),
+                "Body template of field updateProduct selects width in ID, which is not an input object".to_string(),
+                "Body template of field updateProduct uses the unknown variable $this".to_string(),
+            ]),
+        );
+
+        let template =
+            HTTPRequestTemplate::parse("DELETE", "/products/{id}", Some("reason")).unwrap();
+        assert_eq!(
+            validate(&template, "deleteProduct"),
+            Err(vec![
+                "Body template of field deleteProduct selects reason, which is not defined in the arguments".to_string(),
+            ]),
+        );
+    }
+}
@@ -1,8 +1,18 @@
 #![allow(unused_imports)]
 
+mod handle_responses;
+mod http_request;
 mod json_selection;
 mod url_path_template;
+mod validation;
 
+pub use handle_responses::handle_responses;
+pub use handle_responses::ConnectorError;
+pub use handle_responses::ConnectorResponse;
+pub use handle_responses::MappedResponse;
+pub use handle_responses::ResponseKey;
+pub use http_request::HTTPMethod;
+pub use http_request::HTTPRequestTemplate;
 pub use json_selection::ApplyTo;
 pub use json_selection::ApplyToError;
 pub use json_selection::JSONSelection;
@@ -10,3 +20,5 @@ pub use json_selection::Key;
 pub use json_selection::PathSelection;
 pub use json_selection::SubSelection;
 pub use url_path_template::URLPathTemplate;
+pub use validation::connect_identity;
+pub use validation::validate;
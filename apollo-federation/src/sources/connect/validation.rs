@@ -0,0 +1,163 @@
+use apollo_compiler::ast::Directive;
+use apollo_compiler::name;
+use apollo_compiler::schema::ExtendedType;
+use apollo_compiler::validation::Valid;
+use apollo_compiler::Name;
+use apollo_compiler::Schema;
+
+use super::http_request::HTTPRequestTemplate;
+use crate::link::spec::Identity;
+use crate::link::spec::APOLLO_SPEC_DOMAIN;
+use crate::link::Link;
+
+const CONNECT_DIRECTIVE_NAME: Name = name!("connect");
+const HTTP_ARGUMENT_NAME: &str = "http";
+const BODY_FIELD_NAME: &str = "body";
+
+/// The identity of the connect spec, linked by subgraphs declaring connectors.
+pub fn connect_identity() -> Identity {
+    Identity {
+        domain: APOLLO_SPEC_DOMAIN.to_string(),
+        name: name!("connect"),
+    }
+}
+
+/// Check the HTTP requests of the `@connect` directives of a subgraph: their
+/// method, their URL path and the template of their body, so that mistakes are
+/// reported when the subgraph is loaded.
+///
+/// ```graphql
+/// type Mutation {
+///   updateProduct(id: ID!, input: ProductInput!): Product
+///     @connect(http: { PATCH: "/products/{id}", body: "name: $args.input.name" })
+/// }
+/// ```
+pub fn validate(schema: &Valid<Schema>) -> Result<(), Vec<String>> {
+    let Some((link, _)) = Link::for_identity(schema, &connect_identity()) else {
+        return Ok(());
+    };
+    let directive_name = link.directive_name_in_schema(&CONNECT_DIRECTIVE_NAME);
+
+    let mut errors = vec![];
+    for (type_name, ty) in &schema.types {
+        let ExtendedType::Object(object) = ty else {
+            continue;
+        };
+        for (field_name, field) in &object.fields {
+            for directive in field.directives.get_all(&directive_name) {
+                let result = http_request(directive).and_then(|template| {
+                    template
+                        .validate(schema, field)
+                        .map_err(|errors| errors.join(", "))
+                });
+                if let Err(error) = result {
+                    errors.push(format!(
+                        "Invalid @{} on {}.{}: {}",
+                        directive_name, type_name, field_name, error
+                    ));
+                }
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+// The `http` argument holds the path under the name of the method, like
+// `{ GET: "/products/{id}" }`, and the optional body template.
+fn http_request(directive: &Directive) -> Result<HTTPRequestTemplate, String> {
+    let http = directive
+        .argument_by_name(HTTP_ARGUMENT_NAME)
+        .and_then(|http| http.as_object())
+        .ok_or_else(|| format!("the {} argument must be an object", HTTP_ARGUMENT_NAME))?;
+
+    let mut request = None;
+    let mut body = None;
+    for (name, value) in http {
+        let value = value
+            .as_str()
+            .ok_or_else(|| format!("{} must be a string", name))?;
+        if name == BODY_FIELD_NAME {
+            body = Some(value);
+        } else if request.replace((name.as_str(), value)).is_some() {
+            return Err("only one HTTP method can be set".to_string());
+        }
+    }
+
+    let (method, path) =
+        request.ok_or_else(|| "an HTTP method is required, like GET: \"/path\"".to_string())?;
+    HTTPRequestTemplate::parse(method, path, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema(fields: &str) -> Valid<Schema> {
+        let sdl = format!(
+            r#"
+            extend schema
+              @link(url: "https://specs.apollo.dev/connect/v0.1", import: ["@connect"])
+
+            directive @link(url: String, as: String, import: [link__Import]) repeatable on SCHEMA
+            directive @connect(http: ConnectHTTP) repeatable on FIELD_DEFINITION
+            scalar link__Import
+            input ConnectHTTP {{
+              GET: String
+              POST: String
+              PATCH: String
+              body: String
+            }}
+
+            type Query {{
+              product(id: ID!): Product
+            }}
+
+            type Mutation {{
+              {fields}
+            }}
+
+            type Product {{
+              id: ID!
+              name: String
+            }}
+
+            input ProductInput {{
+              name: String
+            }}
+            "#
+        );
+        Schema::parse_and_validate(sdl, "schema.graphql").unwrap()
+    }
+
+    #[test]
+    fn test_valid_connectors() {
+        assert_eq!(
+            validate(&schema(
+                r#"updateProduct(id: ID!, input: ProductInput!): Product
+                  @connect(http: { PATCH: "/products/{id}", body: "name: $args.input.name" })"#
+            )),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_invalid_connectors() {
+        assert_eq!(
+            validate(&schema(
+                r#"updateProduct(id: ID!, input: ProductInput!): Product
+                  @connect(http: { PATCH: "/products/{id}", body: "name: $args.input.title" })
+                deleteProduct(id: ID!): Product
+                  @connect(http: { GET: "/products/{id}", body: "id" })"#
+            )),
+            Err(vec![
+                "Invalid @connect on Mutation.updateProduct: Body template of field updateProduct selects title, which is not defined in ProductInput".to_string(),
+                "Invalid @connect on Mutation.deleteProduct: GET requests cannot have a body".to_string(),
+            ])
+        );
+    }
+}
@@ -14,6 +14,8 @@ use apollo_compiler::Schema;
 use indexmap::map::Entry;
 
 use crate::error::FederationError;
+use crate::error::MultipleFederationErrors;
+use crate::error::SingleFederationError;
 use crate::link::spec::Identity;
 use crate::link::Link;
 use crate::link::LinkError;
@@ -96,6 +98,14 @@ impl Subgraph {
             imported_link_definitions,
         )?;
         let schema = schema.validate()?;
+        // connectors are checked when the subgraph is loaded, rather than when their requests
+        // are sent
+        crate::sources::connect::validate(&schema).map_err(|errors| {
+            errors
+                .into_iter()
+                .map(|message| SingleFederationError::InvalidSubgraph { message })
+                .collect::<MultipleFederationErrors>()
+        })?;
         Ok(ValidSubgraph {
             name: name.to_owned(),
             url: url.to_owned(),
@@ -29,6 +29,7 @@ use apollo_compiler::Schema;
 use router_bridge::planner::ReferencedFieldsForType;
 use router_bridge::planner::UsageReporting;
 use serde::Serialize;
+use tower::BoxError;
 
 use crate::json_ext::Object;
 use crate::json_ext::Value as JsonValue;
@@ -36,6 +37,8 @@ use crate::plugins::telemetry::config::ApolloSignatureNormalizationAlgorithm;
 use crate::spec::Fragments;
 use crate::spec::Query;
 use crate::spec::Selection as SpecSelection;
+use crate::spec::SpecError;
+use crate::Configuration;
 
 /// The stats for a single execution of an input object field.
 #[derive(Clone, Default, Debug, Serialize)]
@@ -256,6 +259,76 @@ pub(crate) fn generate_usage_reporting(
     generator.generate_usage_reporting()
 }
 
+/// Computes the signatures of operations, as reported to GraphOS
+///
+/// The signature is the stable and anonymized form of an operation that identifies it in usage
+/// reports: literal values are removed, and fields, fragments and arguments are sorted. Plugins can
+/// use it to key operations the same way GraphOS does. During a request, the signature computed by
+/// the router is also available in the context under the `apollo_operation_signature` key, once
+/// the query is planned.
+pub struct OperationSignatures {
+    schema: Arc<crate::spec::Schema>,
+    normalization_algorithm: ApolloSignatureNormalizationAlgorithm,
+}
+
+impl OperationSignatures {
+    /// Creates a generator for the operations of a supergraph
+    pub fn new(supergraph_sdl: &str) -> Result<Self, BoxError> {
+        Ok(Self {
+            schema: Arc::new(crate::spec::Schema::parse(
+                supergraph_sdl,
+                &Configuration::default(),
+            )?),
+            normalization_algorithm: ApolloSignatureNormalizationAlgorithm::Legacy,
+        })
+    }
+
+    /// Uses the enhanced normalization algorithm, matching routers configured with
+    /// `experimental_apollo_signature_normalization_algorithm: enhanced`
+    pub fn with_enhanced_normalization(mut self) -> Self {
+        self.normalization_algorithm = ApolloSignatureNormalizationAlgorithm::Enhanced;
+        self
+    }
+
+    /// Returns the signature of an operation
+    ///
+    /// Operations that cannot be parsed or validated get the same error signature as in usage
+    /// reports, like `## GraphQLValidationFailure\n`.
+    pub fn signature(&self, query: &str, operation_name: Option<&str>) -> String {
+        let doc = match Query::parse_document(
+            query,
+            operation_name,
+            &self.schema,
+            &Configuration::default(),
+        ) {
+            Ok(doc) => doc,
+            Err(error) => return error.get_error_key().to_string(),
+        };
+
+        let operation_name = operation_name.map(|name| name.to_string());
+        if doc
+            .executable
+            .operations
+            .get(operation_name.as_deref())
+            .is_err()
+        {
+            return SpecError::UnknownOperation(operation_name.unwrap_or_default())
+                .get_error_key()
+                .to_string();
+        }
+
+        generate_usage_reporting(
+            &doc.executable,
+            &doc.executable,
+            &operation_name,
+            self.schema.supergraph_schema(),
+            &self.normalization_algorithm,
+        )
+        .result
+        .stats_report_key
+    }
+}
+
 pub(crate) fn generate_extended_references(
     doc: Arc<Valid<ExecutableDocument>>,
     operation_name: Option<String>,
@@ -975,3 +975,27 @@ async fn test_compare() {
         UsageReportingComparisonResult::BothNotEqual
     ));
 }
+
+#[test]
+fn test_operation_signatures() {
+    let signatures =
+        OperationSignatures::new(include_str!("testdata/schema_interop.graphql")).unwrap();
+    let query_str = include_str!("testdata/named_query.graphql");
+
+    assert_eq!(
+        signatures.signature(query_str, Some("MyQuery")),
+        "# MyQuery\nquery MyQuery{noInputQuery{id}}"
+    );
+    assert_eq!(
+        signatures.signature(query_str, Some("OtherQuery")),
+        "## GraphQLUnknownOperationName\n"
+    );
+    assert_eq!(
+        signatures.signature("query {", None),
+        "## GraphQLParseFailure\n"
+    );
+    assert_eq!(
+        signatures.signature("query { unknownField }", None),
+        "## GraphQLValidationFailure\n"
+    );
+}
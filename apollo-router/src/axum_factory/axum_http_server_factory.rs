@@ -47,6 +47,8 @@ use super::listeners::ensure_listenaddrs_consistency;
 use super::listeners::extra_endpoints;
 use super::listeners::ListenersAndRouters;
 use super::utils::PropagatingMakeSpan;
+use super::websocket;
+use super::websocket::CurrentRouterFactory;
 use super::ListenAddrAndRouter;
 use super::ENDPOINT_CALLBACK;
 use crate::axum_factory::compression::Compressor;
@@ -54,6 +56,7 @@ use crate::axum_factory::listeners::get_extra_listeners;
 use crate::axum_factory::listeners::serve_router_on_listen_addr;
 use crate::configuration::Configuration;
 use crate::configuration::ListenAddr;
+use crate::configuration::SupergraphWebSocket;
 use crate::graphql;
 use crate::http_server_factory::HttpServerFactory;
 use crate::http_server_factory::HttpServerHandle;
@@ -94,6 +97,7 @@ impl Drop for SessionCountGuard {
 pub(crate) struct AxumHttpServerFactory {
     live: Arc<AtomicBool>,
     ready: Arc<AtomicBool>,
+    router_factory: CurrentRouterFactory,
 }
 
 impl AxumHttpServerFactory {
@@ -120,6 +124,7 @@ struct Health {
 pub(crate) fn make_axum_router<RF>(
     live: Arc<AtomicBool>,
     ready: Arc<AtomicBool>,
+    current_factory: CurrentRouterFactory,
     service_factory: RF,
     configuration: &Configuration,
     mut endpoints: MultiMap<ListenAddr, Endpoint>,
@@ -195,6 +200,7 @@ where
     ensure_endpoints_consistency(configuration, &endpoints)?;
 
     let mut main_endpoint = main_endpoint(
+        current_factory,
         service_factory,
         configuration,
         endpoints
@@ -235,10 +241,12 @@ impl HttpServerFactory for AxumHttpServerFactory {
     {
         let live = self.live.clone();
         let ready = self.ready.clone();
+        let router_factory = self.router_factory.clone();
         Box::pin(async move {
             let all_routers = make_axum_router(
                 live.clone(),
                 ready.clone(),
+                router_factory,
                 service_factory,
                 &configuration,
                 extra_endpoints,
@@ -434,6 +442,7 @@ async fn decompression_error(_error: BoxError) -> axum::response::Response {
 }
 
 fn main_endpoint<RF>(
+    current_factory: CurrentRouterFactory,
     service_factory: RF,
     configuration: &Configuration,
     endpoints_on_main_listener: Vec<Endpoint>,
@@ -446,6 +455,8 @@ where
         ApolloRouterError::ServiceCreationError(format!("CORS configuration error: {e}").into())
     })?;
     let span_mode = span_mode(configuration);
+    // WebSocket connections execute operations with the factory of the running configuration
+    current_factory.set(service_factory.clone());
 
     let decompression = ServiceBuilder::new()
         .layer(HandleErrorLayer::<_, ()>::new(decompression_error))
@@ -462,6 +473,7 @@ where
             license_handler,
         ))
         .layer(Extension(service_factory))
+        .layer(Extension(current_factory))
         .layer(cors)
         // Telemetry layers MUST be last. This means that they will be hit first during execution of the pipeline
         // Adding layers after telemetry will cause us to lose metrics and spans.
@@ -557,12 +569,18 @@ where
 {
     let early_cancel = configuration.supergraph.early_cancel;
     let experimental_log_on_broken_pipe = configuration.supergraph.experimental_log_on_broken_pipe;
+    let websocket_config = Arc::new(configuration.supergraph.websocket.clone());
     let mut router = Router::new().route(
         &configuration.supergraph.sanitized_path(),
         get({
-            move |Extension(service): Extension<RF>, request: Request<DecompressionBody<Body>>| {
-                handle_graphql(
-                    service.create().boxed(),
+            let websocket_config = websocket_config.clone();
+            move |Extension(service): Extension<RF>,
+                  Extension(current_factory): Extension<CurrentRouterFactory>,
+                  request: Request<DecompressionBody<Body>>| {
+                handle_get(
+                    service,
+                    current_factory,
+                    websocket_config.clone(),
                     early_cancel,
                     experimental_log_on_broken_pipe,
                     request,
@@ -586,9 +604,12 @@ where
             "/",
             get({
                 move |Extension(service): Extension<RF>,
+                      Extension(current_factory): Extension<CurrentRouterFactory>,
                       request: Request<DecompressionBody<Body>>| {
-                    handle_graphql(
-                        service.create().boxed(),
+                    handle_get(
+                        service,
+                        current_factory,
+                        websocket_config.clone(),
                         early_cancel,
                         experimental_log_on_broken_pipe,
                         request,
@@ -612,6 +633,31 @@ where
     router
 }
 
+async fn handle_get<RF>(
+    service: RF,
+    current_factory: CurrentRouterFactory,
+    websocket_config: Arc<SupergraphWebSocket>,
+    early_cancel: bool,
+    experimental_log_on_broken_pipe: bool,
+    http_request: Request<DecompressionBody<Body>>,
+) -> Response
+where
+    RF: RouterFactory,
+{
+    if websocket_config.enabled && websocket::is_upgrade(&http_request) {
+        websocket::handle(current_factory, &websocket_config, http_request).await
+    } else {
+        handle_graphql(
+            service.create().boxed(),
+            early_cancel,
+            experimental_log_on_broken_pipe,
+            http_request,
+        )
+        .await
+        .into_response()
+    }
+}
+
 async fn handle_graphql(
     service: router::BoxService,
     early_cancel: bool,
@@ -1,3 +1,6 @@
+use std::time::Duration;
+use std::time::Instant;
+
 use brotli::enc::BrotliEncoderParams;
 use bytes::Bytes;
 use bytes::BytesMut;
@@ -23,6 +26,14 @@ pub(crate) mod util;
 
 const GZIP_HEADER_LEN: usize = 10;
 
+/// Bytes and time spent compressing a body
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct CompressionStats {
+    pub(crate) uncompressed_bytes: u64,
+    pub(crate) compressed_bytes: u64,
+    pub(crate) duration: Duration,
+}
+
 pub(crate) enum Compressor {
     Deflate(DeflateEncoder),
     Gzip(GzipEncoder),
@@ -68,12 +79,19 @@ impl Compressor {
         }
     }
 
-    pub(crate) fn process(
+    pub(crate) fn process(self, stream: RouterBody) -> impl Stream<Item = Result<Bytes, BoxError>> {
+        self.process_with_stats(stream, |_| {})
+    }
+
+    /// Compresses the body, then calls `on_finish` with the bytes and time spent compressing it.
+    /// `on_finish` is not called if the compression fails or the stream is dropped
+    pub(crate) fn process_with_stats(
         mut self,
         mut stream: RouterBody,
-    ) -> impl Stream<Item = Result<Bytes, BoxError>>
-where {
+        on_finish: impl FnOnce(CompressionStats) + Send + 'static,
+    ) -> impl Stream<Item = Result<Bytes, BoxError>> {
         let (tx, rx) = mpsc::channel(10);
+        let mut stats = CompressionStats::default();
 
         tokio::task::spawn(
             async move {
@@ -85,6 +103,8 @@ where {
                             }
                         }
                         Ok(data) => {
+                            stats.uncompressed_bytes += data.len() as u64;
+                            let start = Instant::now();
                             // the buffer needs at least 10 bytes for a gzip header if we use gzip, then more
                             // room to store the data itself
                             let mut buf = BytesMut::zeroed(GZIP_HEADER_LEN + data.len());
@@ -126,6 +146,8 @@ where {
                                     let len = partial_output.written().len();
                                     let _ = partial_output.into_inner();
                                     buf.resize(len, 0);
+                                    stats.duration += start.elapsed();
+                                    stats.compressed_bytes += len as u64;
 
                                     if (tx.send(Ok(buf.freeze())).await).is_err() {
                                         return;
@@ -138,19 +160,22 @@ where {
                 }
 
                 loop {
+                    let start = Instant::now();
                     let buf = BytesMut::zeroed(1024);
                     let mut partial_output = PartialBuffer::new(buf);
 
                     match self.finish(&mut partial_output) {
                         Err(e) => {
                             let _ = tx.send(Err(e.into())).await;
-                            break;
+                            return;
                         }
                         Ok(is_flushed) => {
                             let len = partial_output.written().len();
 
                             let mut buf = partial_output.into_inner();
                             buf.resize(len, 0);
+                            stats.duration += start.elapsed();
+                            stats.compressed_bytes += len as u64;
                             if (tx.send(Ok(buf.freeze())).await).is_err() {
                                 return;
                             }
@@ -160,6 +185,7 @@ where {
                         }
                     }
                 }
+                on_finish(stats);
             }
             .instrument(tracing::debug_span!("body_compression")),
         );
@@ -261,6 +287,26 @@ mod tests {
         assert!(stream.next().await.is_none());
     }
 
+    #[tokio::test]
+    async fn it_reports_compression_stats() {
+        let compressor = Compressor::new(["gzip"].into_iter()).unwrap();
+        let body: RouterBody = vec![0u8; 5000].into();
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let stream = compressor.process_with_stats(body, move |stats| {
+            let _ = tx.send(stats);
+        });
+        let compressed: Vec<Bytes> = stream.map(|buf| buf.unwrap()).collect().await;
+
+        let stats = rx.await.unwrap();
+        assert_eq!(stats.uncompressed_bytes, 5000);
+        assert_eq!(
+            stats.compressed_bytes,
+            compressed.iter().map(|buf| buf.len() as u64).sum::<u64>()
+        );
+        assert!(stats.compressed_bytes < stats.uncompressed_bytes);
+    }
+
     #[tokio::test]
     async fn gzip_header_writing() {
         let compressor = Compressor::new(["gzip"].into_iter()).unwrap();
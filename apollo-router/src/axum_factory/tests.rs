@@ -2311,7 +2311,9 @@ async fn test_supergraph_timeout() {
         .unwrap();
 
     let service = RouterCreator::new(
-        QueryAnalysisLayer::new(supergraph_creator.schema(), Arc::clone(&conf)).await,
+        QueryAnalysisLayer::new(supergraph_creator.schema(), Arc::clone(&conf))
+            .await
+            .unwrap(),
         Arc::new(PersistedQueryLayer::new(&conf).await.unwrap()),
         Arc::new(supergraph_creator),
         conf.clone(),
@@ -2353,3 +2355,118 @@ async fn test_supergraph_timeout() {
         })
     );
 }
+
+#[tokio::test]
+async fn it_executes_operations_over_websocket() -> Result<(), ApolloRouterError> {
+    use futures::SinkExt;
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let router_service = router::service::from_supergraph_mock_callback(|req| {
+        // the connection is authenticated by the upgrade request and the init payload
+        assert_eq!(
+            req.supergraph_request
+                .headers()
+                .get("authorization")
+                .unwrap(),
+            "Bearer token"
+        );
+        let init_payload = req
+            .context
+            .get::<_, serde_json_bytes::Value>(websocket::CONNECTION_INIT_PAYLOAD_CONTEXT_KEY)
+            .unwrap();
+        assert_eq!(init_payload, Some(json!({ "client": "realtime" }).into()));
+
+        Ok(SupergraphResponse::new_from_graphql_response(
+            graphql::Response::builder()
+                .data(json!({ "me": { "name": "Ada" } }))
+                .build(),
+            req.context,
+        ))
+    })
+    .await;
+
+    let conf = Configuration::fake_builder()
+        .supergraph(
+            Supergraph::fake_builder()
+                .websocket(crate::configuration::SupergraphWebSocket {
+                    enabled: true,
+                    allowed_origins: vec!["https://app.example.com".to_string()],
+                    ..Default::default()
+                })
+                .build(),
+        )
+        .build()
+        .unwrap();
+    let (server, _client) =
+        init_with_config(router_service, Arc::new(conf), MultiMap::new()).await?;
+    let url = server
+        .graphql_listen_address()
+        .as_ref()
+        .unwrap()
+        .to_string()
+        .replace("http://", "ws://");
+
+    let upgrade_request = |origin: &'static str| {
+        let mut request = url.as_str().into_client_request().unwrap();
+        request.headers_mut().insert(
+            "sec-websocket-protocol",
+            HeaderValue::from_static("graphql-transport-ws"),
+        );
+        request
+            .headers_mut()
+            .insert("authorization", HeaderValue::from_static("Bearer token"));
+        // operations go through the CSRF check with the headers of the upgrade request
+        request
+            .headers_mut()
+            .insert("apollo-require-preflight", HeaderValue::from_static("true"));
+        request
+            .headers_mut()
+            .insert("origin", HeaderValue::from_static(origin));
+        request
+    };
+
+    // browsers send the cookies of the router with upgrade requests from any website
+    match tokio_tungstenite::connect_async(upgrade_request("https://evil.example.com")).await {
+        Err(tokio_tungstenite::tungstenite::Error::Http(response)) => {
+            assert_eq!(response.status().as_u16(), 403)
+        }
+        result => panic!("unexpected result: {result:?}"),
+    }
+
+    let request = upgrade_request("https://app.example.com");
+    let (mut socket, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+
+    let messages = [
+        json!({ "type": "connection_init", "payload": { "client": "realtime" } }),
+        json!({ "type": "ping" }),
+        json!({ "type": "subscribe", "id": "1", "payload": { "query": "{ me { name } }" } }),
+    ];
+    for message in messages {
+        socket
+            .send(Message::Text(message.to_string()))
+            .await
+            .unwrap();
+    }
+
+    let mut received = Vec::new();
+    while received.len() < 4 {
+        match socket.next().await.unwrap().unwrap() {
+            Message::Text(text) => {
+                received.push(serde_json::from_str::<serde_json::Value>(&text).unwrap())
+            }
+            message => panic!("unexpected message: {message:?}"),
+        }
+    }
+    assert_eq!(
+        received,
+        vec![
+            json!({ "type": "connection_ack" }),
+            json!({ "type": "pong", "payload": null }),
+            json!({ "type": "next", "id": "1", "payload": { "data": { "me": { "name": "Ada" } } } }),
+            json!({ "type": "complete", "id": "1" }),
+        ]
+    );
+
+    server.shutdown().await
+}
@@ -0,0 +1,412 @@
+//! GraphQL operations over WebSocket
+//!
+//! When enabled, clients can open a WebSocket connection on the GraphQL endpoint with the
+//! `graphql-transport-ws` protocol, and execute queries and mutations over it. The headers of the
+//! upgrade request (like the `authorization` header) are added to every operation sent on the
+//! connection, and the `connection_init` payload is available in their context, so the connection
+//! is authenticated once instead of once per request.
+//!
+//! Browsers send cookies with upgrade requests from any website, so the `Origin` of upgrade
+//! requests is checked against the allowed origins. Browsers can't add headers to upgrade
+//! requests, so once the origin is checked, operations are considered preflighted by the CSRF
+//! check.
+//!
+//! Connections outlive configuration reloads, so each operation is executed with the router
+//! factory of the running configuration instead of the one of the upgrade request.
+//!
+//! Protocol reference: <https://github.com/enisdenjo/graphql-ws/blob/master/PROTOCOL.md>
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::ws::CloseFrame;
+use axum::extract::ws::Message;
+use axum::extract::ws::WebSocket;
+use axum::extract::ws::WebSocketUpgrade;
+use axum::extract::FromRequestParts;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use futures::stream::SplitSink;
+use futures::stream::SplitStream;
+use futures::SinkExt;
+use futures::StreamExt;
+use http::header::ACCEPT;
+use http::header::CONNECTION;
+use http::header::CONTENT_LENGTH;
+use http::header::ORIGIN;
+use http::header::SEC_WEBSOCKET_ACCEPT;
+use http::header::SEC_WEBSOCKET_EXTENSIONS;
+use http::header::SEC_WEBSOCKET_KEY;
+use http::header::SEC_WEBSOCKET_PROTOCOL;
+use http::header::SEC_WEBSOCKET_VERSION;
+use http::header::UPGRADE;
+use http::HeaderMap;
+use http::Method;
+use http::Request;
+use http::StatusCode;
+use http::Uri;
+use hyper::Body;
+use parking_lot::RwLock;
+use tokio::sync::mpsc;
+use tokio::task::AbortHandle;
+use tower::BoxError;
+use tower::ServiceExt;
+use tower_http::decompression::DecompressionBody;
+
+use crate::configuration::SupergraphWebSocket;
+use crate::graphql;
+use crate::protocols::websocket::ClientMessage;
+use crate::protocols::websocket::ServerError;
+use crate::protocols::websocket::ServerMessage;
+use crate::router_factory::RouterFactory;
+use crate::services::router;
+use crate::services::router::body::get_body_bytes;
+use crate::services::subgraph_service::APPLICATION_JSON_HEADER_VALUE;
+use crate::Context;
+
+/// Context key of the `connection_init` payload sent by the client
+pub(crate) const CONNECTION_INIT_PAYLOAD_CONTEXT_KEY: &str =
+    "apollo::websocket::connection_init_payload";
+
+const GRAPHQL_TRANSPORT_WS: &str = "graphql-transport-ws";
+const CONNECTION_INIT_TIMEOUT: Duration = Duration::from_secs(10);
+const MESSAGE_BUFFER_SIZE: usize = 16;
+
+// Close codes defined by the protocol
+const BAD_REQUEST: u16 = 4400;
+const UNAUTHORIZED: u16 = 4401;
+const SUBPROTOCOL_NOT_ACCEPTABLE: u16 = 4406;
+const CONNECTION_INIT_TIMED_OUT: u16 = 4408;
+const SUBSCRIBER_ALREADY_EXISTS: u16 = 4409;
+const TOO_MANY_INIT_REQUESTS: u16 = 4429;
+
+/// Returns true if the request asks to upgrade the connection to a WebSocket
+pub(super) fn is_upgrade<B>(request: &Request<B>) -> bool {
+    request
+        .headers()
+        .get(UPGRADE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.eq_ignore_ascii_case("websocket"))
+}
+
+/// Marks the requests of operations sent over WebSocket, whose JSON body is built by the router
+/// instead of being sent with a `content-type` by the client, and whose origin was checked when
+/// the connection was upgraded
+pub(crate) struct WebSocketOperation;
+
+type CreateRouterService = dyn Fn() -> router::BoxService + Send + Sync;
+
+/// The router factory of the running configuration, replaced when the configuration is reloaded
+#[derive(Clone, Default)]
+pub(crate) struct CurrentRouterFactory(Arc<RwLock<Option<Arc<CreateRouterService>>>>);
+
+impl CurrentRouterFactory {
+    pub(crate) fn set<RF>(&self, factory: RF)
+    where
+        RF: RouterFactory,
+    {
+        *self.0.write() = Some(Arc::new(move || factory.create().boxed()));
+    }
+
+    fn create(&self) -> Option<router::BoxService> {
+        let create = self.0.read().clone()?;
+        Some(create())
+    }
+}
+
+impl fmt::Debug for CurrentRouterFactory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CurrentRouterFactory").finish()
+    }
+}
+
+/// Upgrades the connection to a WebSocket executing the operations sent by the client
+pub(super) async fn handle(
+    factory: CurrentRouterFactory,
+    config: &SupergraphWebSocket,
+    request: Request<DecompressionBody<Body>>,
+) -> Response {
+    let (mut parts, _body) = request.into_parts();
+    if let Some(origin) = parts.headers.get(ORIGIN) {
+        let allowed = config
+            .allowed_origins
+            .iter()
+            .any(|allowed| allowed.as_bytes() == origin.as_bytes());
+        if !allowed {
+            tracing::warn!(
+                origin = ?origin,
+                "rejected a WebSocket connection from an origin that is not allowed"
+            );
+            return (StatusCode::FORBIDDEN, "origin not allowed").into_response();
+        }
+    }
+    let upgrade = match WebSocketUpgrade::from_request_parts(&mut parts, &()).await {
+        Ok(upgrade) => upgrade,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let connection = Connection {
+        factory,
+        uri: parts.uri,
+        headers: operation_headers(parts.headers),
+        max_operations: config.max_operations_per_connection,
+    };
+    upgrade
+        .protocols([GRAPHQL_TRANSPORT_WS])
+        .on_upgrade(move |socket| connection.run(socket))
+}
+
+/// Headers of the upgrade request that are added to the operations
+fn operation_headers(mut headers: HeaderMap) -> HeaderMap {
+    for name in [
+        CONNECTION,
+        UPGRADE,
+        CONTENT_LENGTH,
+        SEC_WEBSOCKET_ACCEPT,
+        SEC_WEBSOCKET_EXTENSIONS,
+        SEC_WEBSOCKET_KEY,
+        SEC_WEBSOCKET_PROTOCOL,
+        SEC_WEBSOCKET_VERSION,
+    ] {
+        headers.remove(name);
+    }
+    // the body is built by the router
+    headers.insert(ACCEPT, APPLICATION_JSON_HEADER_VALUE.clone());
+    headers
+}
+
+struct Connection {
+    factory: CurrentRouterFactory,
+    uri: Uri,
+    headers: HeaderMap,
+    max_operations: usize,
+}
+
+impl Connection {
+    async fn run(self, socket: WebSocket) {
+        let has_protocol = socket.protocol().is_some();
+        let (mut sink, mut stream) = socket.split();
+        if !has_protocol {
+            return close(
+                &mut sink,
+                SUBPROTOCOL_NOT_ACCEPTABLE,
+                "Subprotocol not acceptable",
+            )
+            .await;
+        }
+
+        let init_payload =
+            match tokio::time::timeout(CONNECTION_INIT_TIMEOUT, next_message(&mut stream)).await {
+                Err(_) => {
+                    return close(
+                        &mut sink,
+                        CONNECTION_INIT_TIMED_OUT,
+                        "Connection initialisation timeout",
+                    )
+                    .await
+                }
+                Ok(None) => return,
+                Ok(Some(Ok(ClientMessage::ConnectionInit { payload }))) => payload,
+                Ok(Some(Ok(_))) => return close(&mut sink, UNAUTHORIZED, "Unauthorized").await,
+                Ok(Some(Err(reason))) => return close(&mut sink, BAD_REQUEST, &reason).await,
+            };
+        if send(&mut sink, &ServerMessage::ConnectionAck)
+            .await
+            .is_err()
+        {
+            return;
+        }
+
+        let (sender, mut receiver) = mpsc::channel::<ServerMessage>(MESSAGE_BUFFER_SIZE);
+        let mut operations: HashMap<String, AbortHandle> = HashMap::new();
+        loop {
+            tokio::select! {
+                Some(message) = receiver.recv() => {
+                    if let ServerMessage::Error { id, .. } | ServerMessage::Complete { id } =
+                        &message
+                    {
+                        operations.remove(id);
+                    }
+                    if send(&mut sink, &message).await.is_err() {
+                        break;
+                    }
+                }
+                message = next_message(&mut stream) => match message {
+                    None | Some(Ok(ClientMessage::ConnectionTerminate)) => break,
+                    Some(Ok(ClientMessage::Subscribe { id, payload })) => {
+                        if operations.contains_key(&id) {
+                            let reason = format!("Subscriber for {id} already exists");
+                            close(&mut sink, SUBSCRIBER_ALREADY_EXISTS, &reason).await;
+                            break;
+                        }
+                        if operations.len() >= self.max_operations {
+                            let error = ServerMessage::Error {
+                                id,
+                                payload: ServerError::Error(
+                                    graphql::Error::builder()
+                                        .message("too many operations executing on this connection")
+                                        .extension_code("WEBSOCKET_TOO_MANY_OPERATIONS")
+                                        .build(),
+                                ),
+                            };
+                            if send(&mut sink, &error).await.is_err() {
+                                break;
+                            }
+                            continue;
+                        }
+                        let task = tokio::spawn(self.execute(
+                            id.clone(),
+                            payload,
+                            init_payload.clone(),
+                            sender.clone(),
+                        ));
+                        operations.insert(id, task.abort_handle());
+                    }
+                    Some(Ok(ClientMessage::Complete { id })) => {
+                        if let Some(operation) = operations.remove(&id) {
+                            operation.abort();
+                        }
+                    }
+                    Some(Ok(ClientMessage::ConnectionInit { .. })) => {
+                        let reason = "Too many initialisation requests";
+                        close(&mut sink, TOO_MANY_INIT_REQUESTS, reason).await;
+                        break;
+                    }
+                    Some(Ok(ClientMessage::Ping { payload })) => {
+                        let payload =
+                            payload.and_then(|payload| serde_json::to_value(payload).ok());
+                        if send(&mut sink, &ServerMessage::Pong { payload }).await.is_err() {
+                            break;
+                        }
+                    }
+                    Some(Ok(ClientMessage::Pong { .. })) => {}
+                    Some(Ok(_)) => {
+                        close(&mut sink, BAD_REQUEST, "Invalid message").await;
+                        break;
+                    }
+                    Some(Err(reason)) => {
+                        close(&mut sink, BAD_REQUEST, &reason).await;
+                        break;
+                    }
+                }
+            }
+        }
+
+        for operation in operations.into_values() {
+            operation.abort();
+        }
+    }
+
+    /// Executes an operation through the router pipeline, sending its result to the client
+    fn execute(
+        &self,
+        id: String,
+        request: graphql::Request,
+        init_payload: Option<serde_json_bytes::Value>,
+        sender: mpsc::Sender<ServerMessage>,
+    ) -> impl std::future::Future<Output = ()> + Send + 'static {
+        // look up the factory for each operation, the configuration may have been reloaded
+        let service = self.factory.create();
+        let uri = self.uri.clone();
+        let headers = self.headers.clone();
+
+        async move {
+            let messages = match execute(service, uri, headers, request, init_payload).await {
+                Ok(response) if response.data.is_none() && !response.errors.is_empty() => {
+                    vec![ServerMessage::Error {
+                        id,
+                        payload: ServerError::Errors(response.errors),
+                    }]
+                }
+                Ok(response) => vec![
+                    ServerMessage::Next {
+                        id: id.clone(),
+                        payload: response,
+                    },
+                    ServerMessage::Complete { id },
+                ],
+                Err(err) => {
+                    tracing::error!("could not execute the operation sent over WebSocket: {err}");
+                    vec![ServerMessage::Error {
+                        id,
+                        payload: ServerError::Error(
+                            graphql::Error::builder()
+                                .message("internal server error")
+                                .extension_code("INTERNAL_SERVER_ERROR")
+                                .build(),
+                        ),
+                    }]
+                }
+            };
+            for message in messages {
+                if sender.send(message).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+async fn execute(
+    service: Option<router::BoxService>,
+    uri: Uri,
+    headers: HeaderMap,
+    request: graphql::Request,
+    init_payload: Option<serde_json_bytes::Value>,
+) -> Result<graphql::Response, BoxError> {
+    let service = service.ok_or("the router is not serving a configuration")?;
+    let mut http_request = Request::builder()
+        .method(Method::POST)
+        .uri(uri)
+        .body(Body::from(serde_json::to_vec(&request)?))?;
+    *http_request.headers_mut() = headers;
+
+    let context = Context::new();
+    context
+        .extensions()
+        .with_lock(|mut lock| lock.insert(WebSocketOperation));
+    if let Some(payload) = init_payload {
+        context.insert(CONNECTION_INIT_PAYLOAD_CONTEXT_KEY, payload)?;
+    }
+
+    let response = service
+        .oneshot(router::Request::from((http_request, context)))
+        .await?;
+    let body = get_body_bytes(response.response.into_body()).await?;
+    Ok(graphql::Response::from_bytes("router", body)?)
+}
+
+/// Returns the next protocol message, or None if the connection is closed
+async fn next_message(
+    stream: &mut SplitStream<WebSocket>,
+) -> Option<Result<ClientMessage, String>> {
+    loop {
+        let message = match stream.next().await?.ok()? {
+            Message::Text(text) => serde_json::from_str(&text),
+            Message::Binary(bytes) => serde_json::from_slice(&bytes),
+            // ping frames are answered by axum
+            Message::Ping(_) | Message::Pong(_) => continue,
+            Message::Close(_) => return None,
+        };
+        return Some(message.map_err(|e| format!("Invalid message: {e}")));
+    }
+}
+
+async fn send(
+    sink: &mut SplitSink<WebSocket, Message>,
+    message: &ServerMessage,
+) -> Result<(), BoxError> {
+    let text = serde_json::to_string(message)?;
+    sink.send(Message::Text(text)).await?;
+    Ok(())
+}
+
+async fn close(sink: &mut SplitSink<WebSocket, Message>, code: u16, reason: &str) {
+    let frame = CloseFrame {
+        code,
+        reason: reason.to_string().into(),
+    };
+    // the client may already be gone
+    let _ = sink.send(Message::Close(Some(frame))).await;
+}
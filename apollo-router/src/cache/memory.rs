@@ -0,0 +1,56 @@
+//! Detection of the memory available to the router
+//!
+//! In a container, the memory available to the router is the memory limit of its cgroup, which
+//! is usually much lower than the memory of the host.
+
+use std::path::Path;
+
+/// cgroup v2 memory limit, `max` when there is no limit
+const CGROUP_V2_MEMORY_MAX: &str = "/sys/fs/cgroup/memory.max";
+/// cgroup v1 memory limit, a value close to `i64::MAX` when there is no limit
+const CGROUP_V1_MEMORY_LIMIT: &str = "/sys/fs/cgroup/memory/memory.limit_in_bytes";
+/// Limits above this value mean that the memory is not limited
+const UNLIMITED_THRESHOLD: u64 = 1 << 62;
+
+/// Returns the memory available to the router in bytes, re-evaluated on each call
+pub(crate) fn available_memory() -> Option<u64> {
+    let system = sys_info::mem_info()
+        .ok()
+        .map(|info| info.total.saturating_mul(1024));
+    match (cgroup_memory_limit(), system) {
+        (Some(cgroup), Some(system)) => Some(cgroup.min(system)),
+        (cgroup, system) => cgroup.or(system),
+    }
+}
+
+fn cgroup_memory_limit() -> Option<u64> {
+    read_limit(Path::new(CGROUP_V2_MEMORY_MAX))
+        .or_else(|| read_limit(Path::new(CGROUP_V1_MEMORY_LIMIT)))
+}
+
+fn read_limit(path: &Path) -> Option<u64> {
+    parse_limit(&std::fs::read_to_string(path).ok()?)
+}
+
+fn parse_limit(content: &str) -> Option<u64> {
+    content
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .filter(|limit| *limit > 0 && *limit < UNLIMITED_THRESHOLD)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_cgroup_memory_limits() {
+        assert_eq!(parse_limit("536870912\n"), Some(536870912));
+        // cgroup v2 without limit
+        assert_eq!(parse_limit("max\n"), None);
+        // cgroup v1 without limit
+        assert_eq!(parse_limit("9223372036854771712\n"), None);
+        assert_eq!(parse_limit(""), None);
+    }
+}
@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use std::num::NonZeroUsize;
 use std::sync::Arc;
+use std::time::Duration;
 
 use tokio::sync::broadcast;
 use tokio::sync::oneshot;
@@ -11,11 +12,13 @@ use self::storage::CacheStorage;
 use self::storage::InMemoryCache;
 use self::storage::KeyType;
 use self::storage::ValueType;
-use crate::configuration::RedisCache;
+use self::store::Store;
 
+mod memory;
 pub(crate) mod redis;
 mod size_estimation;
 pub(crate) mod storage;
+pub(crate) mod store;
 pub(crate) use size_estimation::estimate_size;
 
 type WaitMap<K, V> = Arc<Mutex<HashMap<K, broadcast::Sender<V>>>>;
@@ -38,12 +41,12 @@ where
 {
     pub(crate) async fn with_capacity(
         capacity: NonZeroUsize,
-        redis: Option<RedisCache>,
+        store: Option<Arc<dyn Store>>,
         caller: &'static str,
     ) -> Result<Self, BoxError> {
         Ok(Self {
             wait_map: Arc::new(Mutex::new(HashMap::new())),
-            storage: CacheStorage::new(capacity, redis, caller).await?,
+            storage: CacheStorage::new(capacity, store, caller).await?,
         })
     }
 
@@ -51,7 +54,36 @@ where
         config: &crate::configuration::Cache,
         caller: &'static str,
     ) -> Result<Self, BoxError> {
-        Self::with_capacity(config.in_memory.limit, config.redis.clone(), caller).await
+        let store =
+            store::from_configuration(config.redis.clone(), config.filesystem.clone(), caller)
+                .await?;
+        let mut storage = CacheStorage::new(config.in_memory.limit, store, caller).await?;
+        if let Some(percentage) = config.in_memory.memory_percentage {
+            if !(percentage > 0.0 && percentage <= 100.0) {
+                return Err(format!(
+                    "the memory percentage of the {caller} cache must be between 0 and 100"
+                )
+                .into());
+            }
+            // evaluated again when the cache is created on reload
+            match memory::available_memory() {
+                Some(available) => {
+                    let max_bytes = (available as f64 * percentage / 100.0) as u64;
+                    tracing::debug!(
+                        "the in memory {caller} cache is limited to {max_bytes} bytes ({percentage}% of {available} bytes)"
+                    );
+                    storage = storage.with_memory_limit(max_bytes);
+                }
+                None => tracing::warn!(
+                    "could not detect the available memory, the in memory {caller} cache is limited to {} entries",
+                    config.in_memory.limit
+                ),
+            }
+        }
+        Ok(Self {
+            wait_map: Arc::new(Mutex::new(HashMap::new())),
+            storage,
+        })
     }
 
     /// `init_from_redis` is called with values newly deserialized from Redis cache
@@ -126,6 +158,18 @@ where
         self.storage.insert_in_memory(key, value).await;
     }
 
+    pub(crate) async fn try_acquire_lock(
+        &self,
+        key: &str,
+        ttl: Duration,
+    ) -> Option<Result<bool, BoxError>> {
+        self.storage.try_acquire_lock(key, ttl).await
+    }
+
+    pub(crate) async fn release_lock(&self, key: &str, ttl: Duration) {
+        self.storage.release_lock(key, ttl).await
+    }
+
     async fn send(&self, sender: broadcast::Sender<V>, key: &K, value: V) {
         // Lock the wait map to prevent more subscribers racing with our send
         // notification
@@ -1,6 +1,4 @@
 use std::collections::HashMap;
-use std::fmt;
-use std::pin::Pin;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -9,28 +7,40 @@ use fred::interfaces::EventInterface;
 use fred::mocks::Mocks;
 use fred::prelude::ClientLike;
 use fred::prelude::KeysInterface;
+use fred::prelude::LuaInterface;
 use fred::prelude::RedisClient;
 use fred::prelude::RedisError;
 use fred::prelude::RedisErrorKind;
 use fred::types::ClusterRouting;
 use fred::types::Expiration;
-use fred::types::FromRedis;
 use fred::types::PerformanceConfig;
 use fred::types::ReconnectPolicy;
 use fred::types::RedisConfig;
-use fred::types::ScanResult;
+use fred::types::SetOptions;
 use fred::types::TlsConfig;
 use fred::types::TlsHostMapping;
+use futures::stream::BoxStream;
 use futures::FutureExt;
-use futures::Stream;
+use futures::StreamExt;
 use tower::BoxError;
 use url::Url;
 
-use super::KeyType;
-use super::ValueType;
+use super::store::Store;
 use crate::configuration::RedisCache;
 use crate::services::generate_tls_client_config;
 
+// FIXME: configurable batch size
+const SCAN_COUNT: u32 = 100;
+
+/// Increments the counter, and sets its expiration in milliseconds if this created it
+const INCREMENT_SCRIPT: &str = r#"
+local count = redis.call('INCR', KEYS[1])
+if count == 1 then
+    redis.call('PEXPIRE', KEYS[1], ARGV[1])
+end
+return count
+"#;
+
 const SUPPORTED_REDIS_SCHEMES: [&str; 6] = [
     "redis",
     "rediss",
@@ -40,108 +50,15 @@ const SUPPORTED_REDIS_SCHEMES: [&str; 6] = [
     "rediss-sentinel",
 ];
 
-#[derive(Clone, Debug, Eq, Hash, PartialEq)]
-pub(crate) struct RedisKey<K>(pub(crate) K)
-where
-    K: KeyType;
-
-#[derive(Clone, Debug)]
-pub(crate) struct RedisValue<V>(pub(crate) V)
-where
-    V: ValueType;
-
 #[derive(Clone)]
 pub(crate) struct RedisCacheStorage {
     inner: Arc<RedisClient>,
     namespace: Option<Arc<String>>,
-    pub(crate) ttl: Option<Duration>,
+    ttl: Option<Duration>,
     is_cluster: bool,
     reset_ttl: bool,
 }
 
-fn get_type_of<T>(_: &T) -> &'static str {
-    std::any::type_name::<T>()
-}
-
-impl<K> fmt::Display for RedisKey<K>
-where
-    K: KeyType,
-{
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.0)
-    }
-}
-
-impl<K> From<RedisKey<K>> for fred::types::RedisKey
-where
-    K: KeyType,
-{
-    fn from(val: RedisKey<K>) -> Self {
-        val.to_string().into()
-    }
-}
-
-impl<V> fmt::Display for RedisValue<V>
-where
-    V: ValueType,
-{
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}|{:?}", get_type_of(&self.0), self.0)
-    }
-}
-
-impl<V> FromRedis for RedisValue<V>
-where
-    V: ValueType,
-{
-    fn from_value(value: fred::types::RedisValue) -> Result<Self, RedisError> {
-        match value {
-            fred::types::RedisValue::Bytes(data) => {
-                serde_json::from_slice(&data).map(RedisValue).map_err(|e| {
-                    RedisError::new(
-                        RedisErrorKind::Parse,
-                        format!("can't deserialize from JSON: {e}"),
-                    )
-                })
-            }
-            fred::types::RedisValue::String(s) => {
-                serde_json::from_str(&s).map(RedisValue).map_err(|e| {
-                    RedisError::new(
-                        RedisErrorKind::Parse,
-                        format!("can't deserialize from JSON: {e}"),
-                    )
-                })
-            }
-            fred::types::RedisValue::Null => {
-                Err(RedisError::new(RedisErrorKind::NotFound, "not found"))
-            }
-            _res => Err(RedisError::new(
-                RedisErrorKind::Parse,
-                "the data is the wrong type",
-            )),
-        }
-    }
-}
-
-impl<V> TryInto<fred::types::RedisValue> for RedisValue<V>
-where
-    V: ValueType,
-{
-    type Error = RedisError;
-
-    fn try_into(self) -> Result<fred::types::RedisValue, Self::Error> {
-        let v = serde_json::to_vec(&self.0).map_err(|e| {
-            tracing::error!("couldn't serialize value to redis {}. This is a bug in the router, please file an issue: https://github.com/apollographql/router/issues/new", e);
-            RedisError::new(
-                RedisErrorKind::Parse,
-                format!("couldn't serialize value to redis {}", e),
-            )
-        })?;
-
-        Ok(fred::types::RedisValue::Bytes(v.into()))
-    }
-}
-
 impl RedisCacheStorage {
     pub(crate) async fn new(config: RedisCache) -> Result<Self, BoxError> {
         let url = Self::preprocess_urls(config.urls)?;
@@ -261,10 +178,6 @@ impl RedisCacheStorage {
         })
     }
 
-    pub(crate) fn ttl(&self) -> Option<Duration> {
-        self.ttl
-    }
-
     fn preprocess_urls(urls: Vec<Url>) -> Result<Url, RedisError> {
         let url_len = urls.len();
         let mut urls_iter = urls.into_iter();
@@ -358,106 +271,62 @@ impl RedisCacheStorage {
         self.ttl = ttl;
     }
 
-    fn make_key<K: KeyType>(&self, key: RedisKey<K>) -> String {
+    fn make_key(&self, key: &str) -> String {
         match &self.namespace {
             Some(namespace) => format!("{namespace}:{key}"),
             None => key.to_string(),
         }
     }
+}
 
-    pub(crate) async fn get<K: KeyType, V: ValueType>(
-        &self,
-        key: RedisKey<K>,
-    ) -> Option<RedisValue<V>> {
-        if self.reset_ttl && self.ttl.is_some() {
-            let pipeline: fred::clients::Pipeline<RedisClient> = self.inner.pipeline();
-            let key = self.make_key(key);
-            let res = pipeline
-                .get::<fred::types::RedisValue, _>(&key)
-                .await
-                .map_err(|e| {
-                    if !e.is_not_found() {
-                        tracing::error!(error = %e, "redis get error");
-                    }
-                    e
-                })
-                .ok()?;
-            if !res.is_queued() {
-                tracing::error!("could not queue GET command");
-                return None;
-            }
-            let res: fred::types::RedisValue = pipeline
-                .expire(
-                    &key,
-                    self.ttl
-                        .expect("we already checked the presence of ttl")
-                        .as_secs() as i64,
-                )
-                .await
-                .map_err(|e| {
-                    if !e.is_not_found() {
-                        tracing::error!(error = %e, "redis get error");
-                    }
-                    e
-                })
-                .ok()?;
-            if !res.is_queued() {
-                tracing::error!("could not queue EXPIRE command");
-                return None;
-            }
+fn into_bytes(value: fred::types::RedisValue) -> Result<Option<Vec<u8>>, BoxError> {
+    match value {
+        fred::types::RedisValue::Bytes(data) => Ok(Some(data.to_vec())),
+        fred::types::RedisValue::String(s) => Ok(Some(s.as_bytes().to_vec())),
+        fred::types::RedisValue::Null => Ok(None),
+        _res => Err(RedisError::new(RedisErrorKind::Parse, "the data is the wrong type").into()),
+    }
+}
 
-            let (first, _): (Option<RedisValue<V>>, bool) = pipeline
-                .all()
-                .await
-                .map_err(|e| {
-                    if !e.is_not_found() {
-                        tracing::error!(error = %e, "redis get error");
-                    }
-                    e
-                })
-                .ok()?;
-            first
-        } else {
-            self.inner
-                .get::<RedisValue<V>, _>(self.make_key(key))
-                .await
-                .map_err(|e| {
-                    if !e.is_not_found() {
-                        tracing::error!(error = %e, "redis get error");
-                    }
-                    e
-                })
-                .ok()
-        }
+#[async_trait::async_trait]
+impl Store for RedisCacheStorage {
+    fn name(&self) -> &'static str {
+        "redis"
     }
 
-    pub(crate) async fn get_multiple<K: KeyType, V: ValueType>(
-        &self,
-        mut keys: Vec<RedisKey<K>>,
-    ) -> Option<Vec<Option<RedisValue<V>>>> {
-        tracing::trace!("getting multiple values from redis: {:?}", keys);
+    fn ttl(&self) -> Option<Duration> {
+        self.ttl
+    }
 
-        if keys.len() == 1 {
-            let res = self
-                .inner
-                .get::<RedisValue<V>, _>(self.make_key(keys.remove(0)))
-                .await
-                .map_err(|e| {
-                    if !e.is_not_found() {
-                        tracing::error!("get error: {}", e);
-                    }
-                    e
-                })
-                .ok();
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, BoxError> {
+        let key = self.make_key(key);
+        let value: fred::types::RedisValue = match self.ttl.filter(|_| self.reset_ttl) {
+            Some(ttl) => {
+                let pipeline: fred::clients::Pipeline<RedisClient> = self.inner.pipeline();
+                let _: () = pipeline.get(&key).await?;
+                let _: () = pipeline.expire(&key, ttl.as_secs() as i64).await?;
+                let (value, _): (fred::types::RedisValue, bool) = pipeline.all().await?;
+                value
+            }
+            None => self.inner.get(&key).await?,
+        };
+        into_bytes(value)
+    }
+
+    async fn get_multiple(&self, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>, BoxError> {
+        tracing::trace!("getting multiple values from redis: {:?}", keys);
 
-            Some(vec![res])
+        let values: Vec<fred::types::RedisValue> = if keys.is_empty() {
+            Vec::new()
+        } else if keys.len() == 1 {
+            vec![self.inner.get(self.make_key(&keys[0])).await?]
         } else if self.is_cluster {
             // when using a cluster of redis nodes, the keys are hashed, and the hash number indicates which
             // node will store it. So first we have to group the keys by hash, because we cannot do a MGET
             // across multipe nodes (error: "ERR CROSSSLOT Keys in request don't hash to the same slot")
             let len = keys.len();
             let mut h: HashMap<u16, (Vec<usize>, Vec<String>)> = HashMap::new();
-            for (index, key) in keys.into_iter().enumerate() {
+            for (index, key) in keys.iter().enumerate() {
                 let key = self.make_key(key);
                 let hash = ClusterRouting::hash_key(key.as_bytes());
                 let entry = h.entry(hash).or_default();
@@ -467,9 +336,9 @@ impl RedisCacheStorage {
 
             // then we query all the key groups at the same time
             let results = futures::future::join_all(h.into_iter().map(|(_, (indexes, keys))| {
-                self.inner
-                    .mget(keys)
-                    .map(|values: Result<Vec<Option<RedisValue<V>>>, RedisError>| (indexes, values))
+                self.inner.mget(keys).map(
+                    |values: Result<Vec<fred::types::RedisValue>, RedisError>| (indexes, values),
+                )
             }))
             .await;
 
@@ -477,93 +346,107 @@ impl RedisCacheStorage {
             // the keys argument's order
             let mut res = Vec::with_capacity(len);
             for (indexes, result) in results.into_iter() {
-                match result {
-                    Err(e) => {
-                        tracing::error!("mget error: {}", e);
-                        return None;
-                    }
-                    Ok(values) => {
-                        for (index, value) in indexes.into_iter().zip(values.into_iter()) {
-                            res.push((index, value));
-                        }
-                    }
+                for (index, value) in indexes.into_iter().zip(result?.into_iter()) {
+                    res.push((index, value));
                 }
             }
             res.sort_by(|(i, _), (j, _)| i.cmp(j));
-            Some(res.into_iter().map(|(_, v)| v).collect())
+            res.into_iter().map(|(_, v)| v).collect()
         } else {
             self.inner
-                .mget(
-                    keys.into_iter()
-                        .map(|k| self.make_key(k))
-                        .collect::<Vec<_>>(),
-                )
-                .await
-                .map_err(|e| {
-                    if !e.is_not_found() {
-                        tracing::error!("mget error: {}", e);
-                    }
+                .mget(keys.iter().map(|k| self.make_key(k)).collect::<Vec<_>>())
+                .await?
+        };
 
-                    e
-                })
-                .ok()
-        }
+        values.into_iter().map(into_bytes).collect()
     }
 
-    pub(crate) async fn insert<K: KeyType, V: ValueType>(
-        &self,
-        key: RedisKey<K>,
-        value: RedisValue<V>,
-        ttl: Option<Duration>,
-    ) {
+    async fn set(&self, key: &str, value: Vec<u8>, ttl: Option<Duration>) -> Result<(), BoxError> {
         let key = self.make_key(key);
-        tracing::trace!("inserting into redis: {:?}, {:?}", key, value);
+        tracing::trace!("inserting into redis: {:?}", key);
         let expiration = ttl
-            .as_ref()
-            .or(self.ttl.as_ref())
+            .or(self.ttl)
             .map(|ttl| Expiration::EX(ttl.as_secs() as i64));
 
-        let r = self
-            .inner
-            .set::<(), _, _>(key, value, expiration, None, false)
-            .await;
-        tracing::trace!("insert result {:?}", r);
+        self.inner
+            .set::<(), _, _>(
+                key,
+                fred::types::RedisValue::Bytes(value.into()),
+                expiration,
+                None,
+                false,
+            )
+            .await?;
+        Ok(())
     }
 
-    pub(crate) async fn insert_multiple<K: KeyType, V: ValueType>(
+    async fn set_multiple(
         &self,
-        data: &[(RedisKey<K>, RedisValue<V>)],
+        entries: Vec<(String, Vec<u8>)>,
         ttl: Option<Duration>,
-    ) {
-        tracing::trace!("inserting into redis: {:#?}", data);
+    ) -> Result<(), BoxError> {
+        tracing::trace!("inserting {} entries into redis", entries.len());
+        let entries: Vec<(String, fred::types::RedisValue)> = entries
+            .into_iter()
+            .map(|(key, value)| {
+                (
+                    self.make_key(&key),
+                    fred::types::RedisValue::Bytes(value.into()),
+                )
+            })
+            .collect();
 
-        let r = match ttl.as_ref().or(self.ttl.as_ref()) {
-            None => self.inner.mset(data.to_owned()).await,
+        match ttl.or(self.ttl) {
+            None => self.inner.mset::<(), _>(entries).await?,
             Some(ttl) => {
                 let expiration = Some(Expiration::EX(ttl.as_secs() as i64));
                 let pipeline = self.inner.pipeline();
 
-                for (key, value) in data {
+                for (key, value) in entries {
                     let _ = pipeline
-                        .set::<(), _, _>(
-                            self.make_key(key.clone()),
-                            value.clone(),
-                            expiration.clone(),
-                            None,
-                            false,
-                        )
+                        .set::<(), _, _>(key, value, expiration.clone(), None, false)
                         .await;
                 }
 
-                pipeline.last().await
+                pipeline.last::<()>().await?
             }
         };
-        tracing::trace!("insert result {:?}", r);
+        Ok(())
+    }
+
+    async fn set_if_absent(
+        &self,
+        key: &str,
+        value: Vec<u8>,
+        ttl: Duration,
+    ) -> Result<bool, BoxError> {
+        let key = self.make_key(key);
+        let result = self
+            .inner
+            .set::<Option<String>, _, _>(
+                key,
+                fred::types::RedisValue::Bytes(value.into()),
+                Some(Expiration::PX(ttl.as_millis() as i64)),
+                Some(SetOptions::NX),
+                false,
+            )
+            .await?;
+        Ok(result.is_some())
+    }
+
+    async fn increment(&self, key: &str, ttl: Duration) -> Result<u64, BoxError> {
+        let key = self.make_key(key);
+        // in a script, so that a counter is never left without expiration
+        let count: u64 = self
+            .inner
+            .eval(INCREMENT_SCRIPT, key, ttl.as_millis() as i64)
+            .await?;
+        Ok(count)
     }
 
-    pub(crate) async fn delete<K: KeyType>(&self, keys: Vec<RedisKey<K>>) -> Option<u32> {
+    async fn delete(&self, keys: &[String]) -> Result<u32, BoxError> {
         let mut h: HashMap<u16, Vec<String>> = HashMap::new();
-        for key in keys.into_iter() {
+        for key in keys {
             let key = self.make_key(key);
             let hash = ClusterRouting::hash_key(key.as_bytes());
             let entry = h.entry(hash).or_default();
@@ -584,52 +467,48 @@ impl RedisCacheStorage {
             }
         }
 
-        Some(total)
+        Ok(total)
     }
 
-    pub(crate) fn scan(
-        &self,
-        pattern: String,
-        count: Option<u32>,
-    ) -> Pin<Box<dyn Stream<Item = Result<ScanResult, RedisError>> + Send>> {
-        if self.is_cluster {
-            Box::pin(self.inner.scan_cluster(pattern, count, None))
+    fn scan(&self, pattern: String) -> BoxStream<'static, Result<Vec<String>, BoxError>> {
+        let pattern = self.make_key(&pattern);
+        let results = if self.is_cluster {
+            self.inner
+                .scan_cluster(pattern, Some(SCAN_COUNT), None)
+                .boxed()
         } else {
-            Box::pin(self.inner.scan(pattern, count, None))
-        }
+            self.inner.scan(pattern, Some(SCAN_COUNT), None).boxed()
+        };
+
+        // the keys are returned without namespace, like the keys passed to the other methods
+        let prefix = self
+            .namespace
+            .as_ref()
+            .map(|namespace| format!("{namespace}:"));
+        results
+            .map(move |result| {
+                let result = result?;
+                let keys = result
+                    .results()
+                    .iter()
+                    .flatten()
+                    .filter_map(|key| key.as_str())
+                    .map(|key| match &prefix {
+                        Some(prefix) => key.strip_prefix(prefix.as_str()).unwrap_or(key),
+                        None => key,
+                    })
+                    .map(str::to_string)
+                    .collect();
+                Ok(keys)
+            })
+            .boxed()
     }
 }
 
 #[cfg(test)]
 mod test {
-    use std::time::SystemTime;
-
     use url::Url;
 
-    use crate::cache::storage::ValueType;
-
-    #[test]
-    fn ensure_invalid_payload_serialization_doesnt_fail() {
-        #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
-        struct Stuff {
-            time: SystemTime,
-        }
-        impl ValueType for Stuff {
-            fn estimated_size(&self) -> Option<usize> {
-                None
-            }
-        }
-
-        let invalid_json_payload = super::RedisValue(Stuff {
-            // this systemtime is invalid, serialization will fail
-            time: std::time::UNIX_EPOCH - std::time::Duration::new(1, 0),
-        });
-
-        let as_value: Result<fred::types::RedisValue, _> = invalid_json_payload.try_into();
-
-        assert!(as_value.is_err());
-    }
-
     #[test]
     fn it_preprocesses_redis_schemas_correctly() {
         // Base Format
@@ -1,10 +1,10 @@
-use std::fmt::Display;
 use std::fmt::{self};
 use std::hash::Hash;
 use std::num::NonZeroUsize;
 use std::sync::atomic::AtomicI64;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::Duration;
 
 use lru::LruCache;
 use opentelemetry::metrics::MeterProvider;
@@ -18,10 +18,14 @@ use tokio::sync::Mutex;
 use tokio::time::Instant;
 use tower::BoxError;
 
-use super::redis::*;
-use crate::configuration::RedisCache;
+use super::store::Store;
 use crate::metrics;
 use crate::plugins::telemetry::config_new::instruments::METER_NAME;
+use crate::status;
+use crate::status::CacheCounters;
+
+const LOCK_HELD: &[u8] = b"locked";
+const LOCK_RELEASED: &[u8] = b"released";
 
 pub(crate) trait KeyType:
     Clone + fmt::Debug + fmt::Display + Hash + Eq + Send + Sync
@@ -47,19 +51,20 @@ where
 
 pub(crate) type InMemoryCache<K, V> = Arc<Mutex<LruCache<K, V>>>;
 
-// placeholder storage module
-//
-// this will be replaced by the multi level (in memory + redis/memcached) once we find
-// a suitable implementation.
+/// Two level cache storage: entries are kept in memory, and in a store shared by the router
+/// instances if one is configured
 #[derive(Clone)]
 pub(crate) struct CacheStorage<K: KeyType, V: ValueType> {
     caller: String,
     inner: Arc<Mutex<LruCache<K, V>>>,
-    redis: Option<RedisCacheStorage>,
+    store: Option<Arc<dyn Store>>,
     cache_size: Arc<AtomicI64>,
     cache_estimated_storage: Arc<AtomicI64>,
+    /// When set, entries are also evicted once their estimated size exceeds this limit
+    max_estimated_storage: Option<i64>,
     _cache_size_gauge: ObservableGauge<i64>,
     _cache_estimated_storage_gauge: ObservableGauge<i64>,
+    counters: Arc<CacheCounters>,
 }
 
 impl<K, V> CacheStorage<K, V>
@@ -69,7 +74,7 @@ where
 {
     pub(crate) async fn new(
         max_capacity: NonZeroUsize,
-        config: Option<RedisCache>,
+        store: Option<Arc<dyn Store>>,
         caller: &'static str,
     ) -> Result<Self, BoxError> {
         // Because calculating the cache size is expensive we do this as we go rather than iterating. This means storing the values for the gauges
@@ -79,34 +84,25 @@ where
             Self::create_cache_estimated_storage_size_gauge(&meter, caller);
 
         Ok(Self {
+            counters: status::register_cache(caller, cache_size.clone()),
             _cache_size_gauge: cache_size_gauge,
             _cache_estimated_storage_gauge: cache_estimated_storage_gauge,
             cache_size,
             cache_estimated_storage,
+            max_estimated_storage: None,
             caller: caller.to_string(),
             inner: Arc::new(Mutex::new(LruCache::new(max_capacity))),
-            redis: if let Some(config) = config {
-                let required_to_start = config.required_to_start;
-                match RedisCacheStorage::new(config).await {
-                    Err(e) => {
-                        tracing::error!(
-                            cache = caller,
-                            e,
-                            "could not open connection to Redis for caching",
-                        );
-                        if required_to_start {
-                            return Err(e);
-                        }
-                        None
-                    }
-                    Ok(storage) => Some(storage),
-                }
-            } else {
-                None
-            },
+            store,
         })
     }
 
+    /// Bounds the in memory cache by the estimated size of its entries, in addition to their
+    /// number: entries without an estimated size would never be evicted otherwise
+    pub(crate) fn with_memory_limit(mut self, max_bytes: u64) -> Self {
+        self.max_estimated_storage = Some(i64::try_from(max_bytes).unwrap_or(i64::MAX));
+        self
+    }
+
     fn create_cache_size_gauge(
         meter: &Meter,
         caller: &'static str,
@@ -157,7 +153,7 @@ where
         (cache_estimated_storage, cache_estimated_storage_gauge)
     }
 
-    /// `init_from_redis` is called with values newly deserialized from Redis cache
+    /// `init_from_redis` is called with values newly deserialized from the shared store
     /// if an error is returned, the value is ignored and considered a cache miss.
     pub(crate) async fn get(
         &self,
@@ -169,16 +165,17 @@ where
 
         match res {
             Some(v) => {
+                self.counters.hit();
                 tracing::info!(
                     monotonic_counter.apollo_router_cache_hit_count = 1u64,
                     kind = %self.caller,
-                    storage = &tracing::field::display(CacheStorageName::Memory),
+                    storage = "memory",
                 );
                 let duration = instant_memory.elapsed().as_secs_f64();
                 tracing::info!(
                     histogram.apollo_router_cache_hit_time = duration,
                     kind = %self.caller,
-                    storage = &tracing::field::display(CacheStorageName::Memory),
+                    storage = "memory",
                 );
                 Some(v)
             }
@@ -187,61 +184,66 @@ where
                 tracing::info!(
                     histogram.apollo_router_cache_miss_time = duration,
                     kind = %self.caller,
-                    storage = &tracing::field::display(CacheStorageName::Memory),
+                    storage = "memory",
                 );
                 tracing::info!(
                     monotonic_counter.apollo_router_cache_miss_count = 1u64,
                     kind = %self.caller,
-                    storage = &tracing::field::display(CacheStorageName::Memory),
+                    storage = "memory",
                 );
 
-                let instant_redis = Instant::now();
-                if let Some(redis) = self.redis.as_ref() {
-                    let inner_key = RedisKey(key.clone());
-                    let redis_value =
-                        redis
-                            .get::<K, V>(inner_key)
+                let instant_store = Instant::now();
+                if let Some(store) = self.store.as_ref() {
+                    let store_value =
+                        store
+                            .get_json::<V>(&key.to_string())
                             .await
-                            .and_then(|mut v| match init_from_redis(&mut v.0) {
+                            .and_then(|mut v| match init_from_redis(&mut v) {
                                 Ok(()) => Some(v),
                                 Err(e) => {
-                                    tracing::error!("Invalid value from Redis cache: {e}");
+                                    tracing::error!(
+                                        "Invalid value from {} cache: {e}",
+                                        store.name()
+                                    );
                                     None
                                 }
                             });
-                    match redis_value {
+                    match store_value {
                         Some(v) => {
-                            self.insert_in_memory(key.clone(), v.0.clone()).await;
+                            self.counters.hit();
+                            self.insert_in_memory(key.clone(), v.clone()).await;
 
                             tracing::info!(
                                 monotonic_counter.apollo_router_cache_hit_count = 1u64,
                                 kind = %self.caller,
-                                storage = &tracing::field::display(CacheStorageName::Redis),
+                                storage = store.name(),
                             );
-                            let duration = instant_redis.elapsed().as_secs_f64();
+                            let duration = instant_store.elapsed().as_secs_f64();
                             tracing::info!(
                                 histogram.apollo_router_cache_hit_time = duration,
                                 kind = %self.caller,
-                                storage = &tracing::field::display(CacheStorageName::Redis),
+                                storage = store.name(),
                             );
-                            Some(v.0)
+                            Some(v)
                         }
                         None => {
+                            self.counters.miss();
                             tracing::info!(
                                 monotonic_counter.apollo_router_cache_miss_count = 1u64,
                                 kind = %self.caller,
-                                storage = &tracing::field::display(CacheStorageName::Redis),
+                                storage = store.name(),
                             );
-                            let duration = instant_redis.elapsed().as_secs_f64();
+                            let duration = instant_store.elapsed().as_secs_f64();
                             tracing::info!(
                                 histogram.apollo_router_cache_miss_time = duration,
                                 kind = %self.caller,
-                                storage = &tracing::field::display(CacheStorageName::Redis),
+                                storage = store.name(),
                             );
                             None
                         }
                     }
                 } else {
+                    self.counters.miss();
                     None
                 }
             }
@@ -249,15 +251,35 @@ where
     }
 
     pub(crate) async fn insert(&self, key: K, value: V) {
-        if let Some(redis) = self.redis.as_ref() {
-            redis
-                .insert(RedisKey(key.clone()), RedisValue(value.clone()), None)
-                .await;
+        if let Some(store) = self.store.as_ref() {
+            store.set_json(&key.to_string(), &value, None).await;
         }
 
         self.insert_in_memory(key, value).await;
     }
 
+    /// Tries to acquire a lock shared by all the instances using the same store. Returns `None`
+    /// if there is no store, and the error if the store cannot be reached
+    pub(crate) async fn try_acquire_lock(
+        &self,
+        key: &str,
+        ttl: Duration,
+    ) -> Option<Result<bool, BoxError>> {
+        let store = self.store.as_ref()?;
+        Some(store.set_if_absent(key, LOCK_HELD.to_vec(), ttl).await)
+    }
+
+    /// Releases a lock acquired with `try_acquire_lock`. The key is kept until it expires, so that
+    /// the lock is not acquired again
+    pub(crate) async fn release_lock(&self, key: &str, ttl: Duration) {
+        let Some(store) = self.store.as_ref() else {
+            return;
+        };
+        if let Err(e) = store.set(key, LOCK_RELEASED.to_vec(), Some(ttl)).await {
+            tracing::warn!(error = %e, "could not release the {} lock '{}'", store.name(), key);
+        }
+    }
+
     pub(crate) async fn insert_in_memory(&self, key: K, value: V)
     where
         V: ValueType,
@@ -266,10 +288,8 @@ where
         // This is cheaper than trying to estimate the cache storage size by iterating over the cache
         let new_value_size = value.estimated_size().unwrap_or(0) as i64;
 
-        let (old_value, length) = {
-            let mut in_memory = self.inner.lock().await;
-            (in_memory.push(key, value), in_memory.len())
-        };
+        let mut in_memory = self.inner.lock().await;
+        let old_value = in_memory.push(key, value);
 
         let size_delta = match old_value {
             Some((_, old_value)) => {
@@ -278,10 +298,27 @@ where
             }
             None => new_value_size,
         };
-        self.cache_estimated_storage
-            .fetch_add(size_delta, Ordering::SeqCst);
+        let mut estimated_storage = self
+            .cache_estimated_storage
+            .fetch_add(size_delta, Ordering::SeqCst)
+            + size_delta;
+
+        if let Some(max_estimated_storage) = self.max_estimated_storage {
+            // always keep the entry that was just inserted
+            while estimated_storage > max_estimated_storage && in_memory.len() > 1 {
+                let Some((_, evicted)) = in_memory.pop_lru() else {
+                    break;
+                };
+                let evicted_size = evicted.estimated_size().unwrap_or(0) as i64;
+                estimated_storage = self
+                    .cache_estimated_storage
+                    .fetch_sub(evicted_size, Ordering::SeqCst)
+                    - evicted_size;
+            }
+        }
 
-        self.cache_size.store(length as i64, Ordering::SeqCst);
+        self.cache_size
+            .store(in_memory.len() as i64, Ordering::SeqCst);
     }
 
     pub(crate) fn in_memory_cache(&self) -> InMemoryCache<K, V> {
@@ -294,20 +331,6 @@ where
     }
 }
 
-enum CacheStorageName {
-    Redis,
-    Memory,
-}
-
-impl Display for CacheStorageName {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            CacheStorageName::Redis => write!(f, "redis"),
-            CacheStorageName::Memory => write!(f, "memory"),
-        }
-    }
-}
-
 impl ValueType for String {
     fn estimated_size(&self) -> Option<usize> {
         Some(self.len())
@@ -329,12 +352,45 @@ impl ValueType for usize {
 #[cfg(test)]
 mod test {
     use std::num::NonZeroUsize;
+    use std::sync::Arc;
+    use std::time::Duration;
 
     use crate::cache::estimate_size;
     use crate::cache::storage::CacheStorage;
     use crate::cache::storage::ValueType;
+    use crate::cache::store::InMemoryStore;
+    use crate::cache::store::Store;
     use crate::metrics::FutureMetricsExt;
 
+    #[tokio::test]
+    async fn it_shares_locks_between_instances() {
+        let store: Arc<dyn Store> = Arc::new(InMemoryStore::new(None));
+        let leader: CacheStorage<String, String> =
+            CacheStorage::new(NonZeroUsize::new(10).unwrap(), Some(store.clone()), "test")
+                .await
+                .unwrap();
+        let follower: CacheStorage<String, String> =
+            CacheStorage::new(NonZeroUsize::new(10).unwrap(), Some(store), "test")
+                .await
+                .unwrap();
+        let ttl = Duration::from_secs(60);
+
+        assert!(leader.try_acquire_lock("lock", ttl).await.unwrap().unwrap());
+        assert!(!follower
+            .try_acquire_lock("lock", ttl)
+            .await
+            .unwrap()
+            .unwrap());
+
+        leader.release_lock("lock", ttl).await;
+        // the released lock cannot be acquired again until it expires
+        assert!(!follower
+            .try_acquire_lock("lock", ttl)
+            .await
+            .unwrap()
+            .unwrap());
+    }
+
     #[tokio::test]
     async fn test_metrics() {
         #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
@@ -487,4 +543,29 @@ mod test {
         .with_metrics()
         .await;
     }
+
+    #[tokio::test]
+    async fn test_memory_limit_eviction() {
+        let cache: CacheStorage<String, String> =
+            CacheStorage::new(NonZeroUsize::new(3).unwrap(), None, "test")
+                .await
+                .unwrap()
+                .with_memory_limit(10);
+
+        cache.insert("a".to_string(), "1234".to_string()).await;
+        cache.insert("b".to_string(), "1234".to_string()).await;
+        assert_eq!(cache.len().await, 2);
+
+        cache.insert("c".to_string(), "1234".to_string()).await;
+        assert_eq!(cache.len().await, 2);
+        assert!(cache.get(&"a".to_string(), |_| Ok(())).await.is_none());
+        assert!(cache.get(&"c".to_string(), |_| Ok(())).await.is_some());
+
+        // entries without an estimated size are bounded by the number of entries
+        for key in ["d", "e", "f", "g"] {
+            cache.insert(key.to_string(), String::new()).await;
+        }
+        assert_eq!(cache.len().await, 3);
+        assert!(cache.get(&"c".to_string(), |_| Ok(())).await.is_none());
+    }
 }
@@ -0,0 +1,838 @@
+//! Storage backends of the persistent features
+//!
+//! The caches of the router (APQ, query plans, entities) keep their shared entries in a [`Store`],
+//! a key value storage with expiration. Redis, in memory and filesystem backends are provided, and
+//! a new backend only has to implement this trait once to be usable by all these features.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use bytesize::ByteSize;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use parking_lot::Mutex;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde::Serialize;
+use sha2::Digest;
+use sha2::Sha256;
+use tokio::io::AsyncBufReadExt;
+use tokio::io::AsyncWriteExt;
+use tokio::time::Instant;
+use tower::BoxError;
+
+use super::redis::RedisCacheStorage;
+use crate::configuration::FileSystemCache;
+use crate::configuration::RedisCache;
+
+const DEFAULT_MAX_SIZE: ByteSize = ByteSize::gib(1);
+const DEFAULT_SWEEP_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// Age after which a temporary file is considered left by an interrupted write
+const TEMPORARY_FILE_LIFETIME: Duration = Duration::from_secs(60);
+
+/// Key value storage with expiration
+#[async_trait::async_trait]
+pub(crate) trait Store: Send + Sync + 'static {
+    /// Name of the backend, used in logs and metrics
+    fn name(&self) -> &'static str;
+
+    /// Expiration of the entries set without TTL
+    fn ttl(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Returns the value of the key, or None if it does not exist or expired
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, BoxError>;
+
+    /// Returns the values of the keys, in the same order
+    async fn get_multiple(&self, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>, BoxError> {
+        let mut values = Vec::with_capacity(keys.len());
+        for key in keys {
+            values.push(self.get(key).await?);
+        }
+        Ok(values)
+    }
+
+    /// Sets the value of the key. Without TTL, the default TTL of the store applies
+    async fn set(&self, key: &str, value: Vec<u8>, ttl: Option<Duration>) -> Result<(), BoxError>;
+
+    /// Sets the values of the keys. Without TTL, the default TTL of the store applies
+    async fn set_multiple(
+        &self,
+        entries: Vec<(String, Vec<u8>)>,
+        ttl: Option<Duration>,
+    ) -> Result<(), BoxError> {
+        for (key, value) in entries {
+            self.set(&key, value, ttl).await?;
+        }
+        Ok(())
+    }
+
+    /// Sets the key only if it does not exist yet. Returns true if this call created the key, so
+    /// it can be used as a lock shared by all the instances using the store
+    async fn set_if_absent(
+        &self,
+        key: &str,
+        value: Vec<u8>,
+        ttl: Duration,
+    ) -> Result<bool, BoxError>;
+
+    /// Increments the counter of the key and returns its new value. A missing counter starts at 1
+    /// and expires after the TTL, which the next increments do not extend
+    async fn increment(&self, key: &str, ttl: Duration) -> Result<u64, BoxError>;
+
+    /// Deletes the keys, and returns the number of deleted entries
+    async fn delete(&self, keys: &[String]) -> Result<u32, BoxError>;
+
+    /// Returns the keys matching the pattern, in batches. In the pattern, `*` matches any
+    /// sequence of characters, and `?` matches any single character
+    fn scan(&self, pattern: String) -> BoxStream<'static, Result<Vec<String>, BoxError>>;
+}
+
+impl dyn Store {
+    /// Returns the deserialized value of the key. Errors are logged and considered as a missing
+    /// entry
+    pub(crate) async fn get_json<V: DeserializeOwned>(&self, key: &str) -> Option<V> {
+        match self.get(key).await {
+            Ok(value) => value.and_then(|value| self.deserialize(key, &value)),
+            Err(e) => {
+                tracing::error!(store = self.name(), error = %e, "could not get the entry");
+                None
+            }
+        }
+    }
+
+    /// Returns the deserialized values of the keys, in the same order, or None if the store
+    /// could not be reached
+    pub(crate) async fn get_multiple_json<V: DeserializeOwned>(
+        &self,
+        keys: &[String],
+    ) -> Option<Vec<Option<V>>> {
+        match self.get_multiple(keys).await {
+            Ok(values) => Some(
+                keys.iter()
+                    .zip(values)
+                    .map(|(key, value)| value.and_then(|value| self.deserialize(key, &value)))
+                    .collect(),
+            ),
+            Err(e) => {
+                tracing::error!(store = self.name(), error = %e, "could not get the entries");
+                None
+            }
+        }
+    }
+
+    /// Serializes and sets the value of the key. Errors are logged
+    pub(crate) async fn set_json<V: Serialize>(&self, key: &str, value: &V, ttl: Option<Duration>) {
+        let Some(value) = serialize(value) else {
+            return;
+        };
+        if let Err(e) = self.set(key, value, ttl).await {
+            tracing::error!(store = self.name(), error = %e, "could not set the entry");
+        }
+    }
+
+    /// Serializes and sets the values of the keys. Errors are logged
+    pub(crate) async fn set_multiple_json<V: Serialize>(
+        &self,
+        entries: &[(String, V)],
+        ttl: Option<Duration>,
+    ) {
+        let entries = entries
+            .iter()
+            .filter_map(|(key, value)| Some((key.clone(), serialize(value)?)))
+            .collect();
+        if let Err(e) = self.set_multiple(entries, ttl).await {
+            tracing::error!(store = self.name(), error = %e, "could not set the entries");
+        }
+    }
+
+    fn deserialize<V: DeserializeOwned>(&self, key: &str, value: &[u8]) -> Option<V> {
+        serde_json::from_slice(value)
+            .map_err(|e| {
+                tracing::error!(
+                    store = self.name(),
+                    error = %e,
+                    "could not deserialize the entry '{}'",
+                    key
+                )
+            })
+            .ok()
+    }
+}
+
+fn serialize<V: Serialize>(value: &V) -> Option<Vec<u8>> {
+    serde_json::to_vec(value)
+        .map_err(|e| {
+            tracing::error!("couldn't serialize value to the store {}. This is a bug in the router, please file an issue: https://github.com/apollographql/router/issues/new", e);
+        })
+        .ok()
+}
+
+/// Creates the store configured for a cache. Returns None if no store is configured, or if Redis
+/// cannot be reached and is not required to start
+pub(crate) async fn from_configuration(
+    redis: Option<RedisCache>,
+    filesystem: Option<FileSystemCache>,
+    caller: &'static str,
+) -> Result<Option<Arc<dyn Store>>, BoxError> {
+    match (redis, filesystem) {
+        (Some(_), Some(_)) => Err(format!(
+            "the {caller} cache can be stored either in Redis or in the filesystem, not both"
+        )
+        .into()),
+        (Some(config), None) => {
+            let required_to_start = config.required_to_start;
+            match RedisCacheStorage::new(config).await {
+                Ok(storage) => Ok(Some(Arc::new(storage))),
+                Err(e) => {
+                    tracing::error!(
+                        cache = caller,
+                        e,
+                        "could not open connection to Redis for caching",
+                    );
+                    if required_to_start {
+                        Err(e)
+                    } else {
+                        Ok(None)
+                    }
+                }
+            }
+        }
+        (None, Some(config)) => Ok(Some(Arc::new(FileSystemStore::new(config).await?))),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Returns true if the key matches the glob style pattern
+fn matches_pattern(pattern: &str, key: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let key: Vec<char> = key.chars().collect();
+    let (mut p, mut k) = (0, 0);
+    // position of the last `*` in the pattern, and of the key when it was reached
+    let mut backtrack = None;
+    while k < key.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, k));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == key[k] => {
+                p += 1;
+                k += 1;
+            }
+            _ => match backtrack {
+                // the last `*` matches one more character
+                Some((star, star_k)) => {
+                    p = star + 1;
+                    k = star_k + 1;
+                    backtrack = Some((star, star_k + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+// counters are stored as decimal strings, like Redis does
+fn parse_counter(value: &[u8]) -> Result<u64, BoxError> {
+    Ok(std::str::from_utf8(value)?.parse()?)
+}
+
+fn expires_at(ttl: Option<Duration>) -> Option<Instant> {
+    ttl.map(|ttl| Instant::now() + ttl)
+}
+
+struct InMemoryEntry {
+    value: Vec<u8>,
+    expires_at: Option<Instant>,
+}
+
+impl InMemoryEntry {
+    fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= Instant::now())
+    }
+}
+
+/// Store keeping the entries in the memory of the router. The entries are not shared with other
+/// instances and are lost on restart
+#[derive(Clone)]
+pub(crate) struct InMemoryStore {
+    entries: Arc<Mutex<HashMap<String, InMemoryEntry>>>,
+    ttl: Option<Duration>,
+}
+
+impl InMemoryStore {
+    pub(crate) fn new(ttl: Option<Duration>) -> Self {
+        Self {
+            entries: Default::default(),
+            ttl,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Store for InMemoryStore {
+    fn name(&self) -> &'static str {
+        "in_memory"
+    }
+
+    fn ttl(&self) -> Option<Duration> {
+        self.ttl
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, BoxError> {
+        let mut entries = self.entries.lock();
+        if entries.get(key).is_some_and(InMemoryEntry::is_expired) {
+            entries.remove(key);
+            return Ok(None);
+        }
+        Ok(entries.get(key).map(|entry| entry.value.clone()))
+    }
+
+    async fn set(&self, key: &str, value: Vec<u8>, ttl: Option<Duration>) -> Result<(), BoxError> {
+        let entry = InMemoryEntry {
+            value,
+            expires_at: expires_at(ttl.or(self.ttl)),
+        };
+        self.entries.lock().insert(key.to_string(), entry);
+        Ok(())
+    }
+
+    async fn set_if_absent(
+        &self,
+        key: &str,
+        value: Vec<u8>,
+        ttl: Duration,
+    ) -> Result<bool, BoxError> {
+        let mut entries = self.entries.lock();
+        if entries.get(key).is_some_and(|entry| !entry.is_expired()) {
+            return Ok(false);
+        }
+        let entry = InMemoryEntry {
+            value,
+            expires_at: expires_at(Some(ttl)),
+        };
+        entries.insert(key.to_string(), entry);
+        Ok(true)
+    }
+
+    async fn increment(&self, key: &str, ttl: Duration) -> Result<u64, BoxError> {
+        let mut entries = self.entries.lock();
+        let (count, expires_at) = match entries.get(key) {
+            Some(entry) if !entry.is_expired() => {
+                (parse_counter(&entry.value)? + 1, entry.expires_at)
+            }
+            _ => (1, expires_at(Some(ttl))),
+        };
+        let entry = InMemoryEntry {
+            value: count.to_string().into_bytes(),
+            expires_at,
+        };
+        entries.insert(key.to_string(), entry);
+        Ok(count)
+    }
+
+    async fn delete(&self, keys: &[String]) -> Result<u32, BoxError> {
+        let mut entries = self.entries.lock();
+        Ok(keys
+            .iter()
+            .filter_map(|key| entries.remove(key))
+            .filter(|entry| !entry.is_expired())
+            .count() as u32)
+    }
+
+    fn scan(&self, pattern: String) -> BoxStream<'static, Result<Vec<String>, BoxError>> {
+        let keys = self
+            .entries
+            .lock()
+            .iter()
+            .filter(|(key, entry)| !entry.is_expired() && matches_pattern(&pattern, key))
+            .map(|(key, _)| key.clone())
+            .collect();
+        futures::stream::once(async move { Ok(keys) }).boxed()
+    }
+}
+
+/// Header of the files of the filesystem store, followed by a new line and the value
+#[derive(Serialize, Deserialize)]
+struct FileHeader {
+    key: String,
+    /// Expiration, in milliseconds since the UNIX epoch
+    expires_at: Option<u64>,
+}
+
+impl FileHeader {
+    fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= now_millis())
+    }
+}
+
+fn expires_at_millis(ttl: Option<Duration>) -> Option<u64> {
+    ttl.map(|ttl| now_millis() + ttl.as_millis() as u64)
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Store keeping the entries in files of a directory, one file per key. The entries survive
+/// restarts, and can be shared by the instances running on the same host
+#[derive(Clone)]
+pub(crate) struct FileSystemStore {
+    directory: Arc<PathBuf>,
+    ttl: Option<Duration>,
+    max_size: u64,
+    sweeper: Arc<Sweeper>,
+    /// Serializes the increments of the counters. Instances sharing the directory do not see
+    /// each other's increments in progress, so counters are only exact within an instance
+    counters: Arc<tokio::sync::Mutex<()>>,
+}
+
+/// State of the sweeps of the directory
+#[derive(Default)]
+struct Sweeper {
+    /// Size of the directory at the last sweep, plus the entries written since
+    size: AtomicU64,
+    running: AtomicBool,
+}
+
+impl FileSystemStore {
+    pub(crate) async fn new(config: FileSystemCache) -> Result<Self, BoxError> {
+        tokio::fs::create_dir_all(&config.path).await.map_err(|e| {
+            format!(
+                "could not create the cache directory {}: {e}",
+                config.path.display()
+            )
+        })?;
+        let sweep_interval = config.sweep_interval.unwrap_or(DEFAULT_SWEEP_INTERVAL);
+        let store = Self {
+            directory: Arc::new(config.path),
+            ttl: config.ttl,
+            max_size: config.max_size.unwrap_or(DEFAULT_MAX_SIZE).as_u64(),
+            sweeper: Default::default(),
+            counters: Default::default(),
+        };
+
+        // the first sweep measures the size of the directory, and the sweeps stop when the
+        // store is dropped
+        store.sweep_in_background();
+        let directory = store.directory.clone();
+        let max_size = store.max_size;
+        let sweeper = Arc::downgrade(&store.sweeper);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(sweep_interval);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                let Some(sweeper) = sweeper.upgrade() else {
+                    break;
+                };
+                Self::start_sweep(directory.clone(), max_size, sweeper);
+            }
+        });
+        Ok(store)
+    }
+
+    // keys can be longer than the file names allowed by the filesystem, so files are named
+    // after a hash of the key
+    fn path(&self, key: &str) -> PathBuf {
+        self.directory
+            .join(hex::encode(Sha256::digest(key.as_bytes())))
+    }
+
+    fn encode(key: &str, value: &[u8], expires_at: Option<u64>) -> Result<Vec<u8>, BoxError> {
+        let header = FileHeader {
+            key: key.to_string(),
+            expires_at,
+        };
+        let mut content = serde_json::to_vec(&header)?;
+        content.push(b'\n');
+        content.extend_from_slice(value);
+        Ok(content)
+    }
+
+    /// Reads the file of an entry, returning None if it does not exist or is not an entry
+    async fn read(path: &Path) -> Result<Option<(FileHeader, Vec<u8>)>, BoxError> {
+        let mut content = match tokio::fs::read(path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let Some(separator) = content.iter().position(|b| *b == b'\n') else {
+            return Ok(None);
+        };
+        let Ok(header) = serde_json::from_slice::<FileHeader>(&content[..separator]) else {
+            return Ok(None);
+        };
+        let value = content.split_off(separator + 1);
+        Ok(Some((header, value)))
+    }
+
+    /// Reads only the header of an entry, so that sweeps do not load the values
+    async fn read_header(path: &Path) -> Result<Option<FileHeader>, BoxError> {
+        let file = match tokio::fs::File::open(path).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut line = Vec::new();
+        tokio::io::BufReader::new(file)
+            .read_until(b'\n', &mut line)
+            .await?;
+        Ok(serde_json::from_slice(line.strip_suffix(b"\n").unwrap_or(&line)).ok())
+    }
+
+    async fn write(&self, path: &Path, content: Vec<u8>) -> Result<(), BoxError> {
+        let size = content.len() as u64;
+        // written to a temporary file first, so readers never see a partial entry
+        let temporary = path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+        tokio::fs::write(&temporary, content).await?;
+        tokio::fs::rename(&temporary, path).await?;
+
+        if self.sweeper.size.fetch_add(size, Ordering::Relaxed) + size > self.max_size {
+            self.sweep_in_background();
+        }
+        Ok(())
+    }
+
+    async fn remove(path: &Path) -> Result<bool, BoxError> {
+        match tokio::fs::remove_file(path).await {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn sweep_in_background(&self) {
+        Self::start_sweep(self.directory.clone(), self.max_size, self.sweeper.clone());
+    }
+
+    fn start_sweep(directory: Arc<PathBuf>, max_size: u64, sweeper: Arc<Sweeper>) {
+        if sweeper.running.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        tokio::spawn(async move {
+            match Self::sweep(&directory, max_size).await {
+                Ok(size) => sweeper.size.store(size, Ordering::Relaxed),
+                Err(e) => tracing::error!(
+                    directory = %directory.display(),
+                    error = %e,
+                    "could not sweep the cache directory"
+                ),
+            }
+            sweeper.running.store(false, Ordering::Release);
+        });
+    }
+
+    /// Deletes the expired entries and the temporary files left by interrupted writes, then the
+    /// entries modified the longest time ago until the directory fits in the maximum size.
+    /// Returns the size of the remaining entries
+    async fn sweep(directory: &Path, max_size: u64) -> Result<u64, BoxError> {
+        let mut files = Vec::new();
+        let mut size = 0;
+        let mut entries = tokio::fs::read_dir(directory).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            let metadata = match entry.metadata().await {
+                Ok(metadata) if metadata.is_file() => metadata,
+                // deleted by another instance in the meantime
+                _ => continue,
+            };
+            let modified = metadata.modified()?;
+            if path.extension().is_some() {
+                if modified.elapsed().unwrap_or_default() > TEMPORARY_FILE_LIFETIME {
+                    Self::remove(&path).await?;
+                }
+                continue;
+            }
+            match Self::read_header(&path).await? {
+                Some(header) if header.is_expired() => {
+                    Self::remove(&path).await?;
+                }
+                Some(_) => {
+                    size += metadata.len();
+                    files.push((modified, metadata.len(), path));
+                }
+                // not an entry of the store
+                None => {}
+            }
+        }
+
+        if size > max_size {
+            files.sort_by_key(|(modified, _, _)| *modified);
+            for (_, len, path) in files {
+                if size <= max_size {
+                    break;
+                }
+                Self::remove(&path).await?;
+                size -= len;
+            }
+        }
+        Ok(size)
+    }
+}
+
+#[async_trait::async_trait]
+impl Store for FileSystemStore {
+    fn name(&self) -> &'static str {
+        "filesystem"
+    }
+
+    fn ttl(&self) -> Option<Duration> {
+        self.ttl
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, BoxError> {
+        let path = self.path(key);
+        match Self::read(&path).await? {
+            Some((header, _)) if header.is_expired() => {
+                Self::remove(&path).await?;
+                Ok(None)
+            }
+            Some((header, value)) if header.key == key => Ok(Some(value)),
+            _ => Ok(None),
+        }
+    }
+
+    async fn set(&self, key: &str, value: Vec<u8>, ttl: Option<Duration>) -> Result<(), BoxError> {
+        let path = self.path(key);
+        let content = Self::encode(key, &value, expires_at_millis(ttl.or(self.ttl)))?;
+        self.write(&path, content).await
+    }
+
+    async fn set_if_absent(
+        &self,
+        key: &str,
+        value: Vec<u8>,
+        ttl: Duration,
+    ) -> Result<bool, BoxError> {
+        let path = self.path(key);
+        if let Some((header, _)) = Self::read(&path).await? {
+            if !header.is_expired() {
+                return Ok(false);
+            }
+            Self::remove(&path).await?;
+        }
+        let content = Self::encode(key, &value, expires_at_millis(Some(ttl)))?;
+        let mut file = match tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .await
+        {
+            Ok(file) => file,
+            // another instance created it first
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => return Ok(false),
+            Err(e) => return Err(e.into()),
+        };
+        file.write_all(&content).await?;
+        Ok(true)
+    }
+
+    async fn increment(&self, key: &str, ttl: Duration) -> Result<u64, BoxError> {
+        let _guard = self.counters.lock().await;
+        let path = self.path(key);
+        let (count, expires_at) = match Self::read(&path).await? {
+            Some((header, value)) if !header.is_expired() && header.key == key => {
+                (parse_counter(&value)? + 1, header.expires_at)
+            }
+            _ => (1, expires_at_millis(Some(ttl))),
+        };
+        let content = Self::encode(key, count.to_string().as_bytes(), expires_at)?;
+        self.write(&path, content).await?;
+        Ok(count)
+    }
+
+    async fn delete(&self, keys: &[String]) -> Result<u32, BoxError> {
+        let mut deleted = 0;
+        for key in keys {
+            if Self::remove(&self.path(key)).await? {
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+
+    fn scan(&self, pattern: String) -> BoxStream<'static, Result<Vec<String>, BoxError>> {
+        let directory = self.directory.clone();
+        futures::stream::once(async move {
+            let mut keys = Vec::new();
+            let mut entries = tokio::fs::read_dir(directory.as_path()).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                // skips the temporary files of the entries being written
+                if entry.path().extension().is_some() {
+                    continue;
+                }
+                if let Some((header, _)) = Self::read(&entry.path()).await? {
+                    if !header.is_expired() && matches_pattern(&pattern, &header.key) {
+                        keys.push(header.key);
+                    }
+                }
+            }
+            Ok(keys)
+        })
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_matches_patterns() {
+        assert!(matches_pattern(
+            "version:1.0:subgraph:*",
+            "version:1.0:subgraph:a:b"
+        ));
+        assert!(matches_pattern(
+            "*:type:Product:*",
+            "subgraph:a:type:Product:hash"
+        ));
+        assert!(matches_pattern("key?", "key1"));
+        assert!(matches_pattern("*", ""));
+        assert!(!matches_pattern("key?", "key"));
+        assert!(!matches_pattern(
+            "*:type:User:*",
+            "subgraph:a:type:Product:hash"
+        ));
+        assert!(!matches_pattern("version:2.0:*", "version:1.0:subgraph"));
+    }
+
+    async fn check_store(store: Arc<dyn Store>) {
+        assert_eq!(store.get_json::<String>("missing").await, None);
+
+        store.set_json("a:1", &"one", None).await;
+        store
+            .set_multiple_json(
+                &[("a:2".to_string(), "two"), ("b:1".to_string(), "three")],
+                None,
+            )
+            .await;
+        assert_eq!(
+            store.get_json::<String>("a:1").await,
+            Some("one".to_string())
+        );
+        assert_eq!(
+            store
+                .get_multiple_json::<String>(&["a:2".to_string(), "c".to_string()])
+                .await,
+            Some(vec![Some("two".to_string()), None])
+        );
+
+        let mut keys: Vec<String> = store
+            .scan("a:*".to_string())
+            .map(|batch| batch.unwrap())
+            .concat()
+            .await;
+        keys.sort();
+        assert_eq!(keys, vec!["a:1".to_string(), "a:2".to_string()]);
+
+        assert_eq!(store.delete(&keys).await.unwrap(), 2);
+        assert_eq!(store.get_json::<String>("a:1").await, None);
+        assert_eq!(
+            store.get_json::<String>("b:1").await,
+            Some("three".to_string())
+        );
+
+        let ttl = Duration::from_secs(60);
+        assert!(store.set_if_absent("lock", vec![1], ttl).await.unwrap());
+        assert!(!store.set_if_absent("lock", vec![1], ttl).await.unwrap());
+
+        store
+            .set_json("expired", &"value", Some(Duration::from_millis(1)))
+            .await;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(store.get_json::<String>("expired").await, None);
+        assert!(store.set_if_absent("expired", vec![1], ttl).await.unwrap());
+
+        assert_eq!(store.increment("counter", ttl).await.unwrap(), 1);
+        assert_eq!(store.increment("counter", ttl).await.unwrap(), 2);
+        let window = Duration::from_millis(1);
+        assert_eq!(store.increment("window", window).await.unwrap(), 1);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(store.increment("window", window).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn in_memory_store() {
+        check_store(Arc::new(InMemoryStore::new(None))).await;
+    }
+
+    #[tokio::test]
+    async fn filesystem_store() {
+        let directory = tempfile::tempdir().unwrap();
+        let store = FileSystemStore::new(FileSystemCache {
+            path: directory.path().join("cache"),
+            ttl: None,
+            max_size: None,
+            sweep_interval: None,
+        })
+        .await
+        .unwrap();
+        check_store(Arc::new(store)).await;
+    }
+
+    #[tokio::test]
+    async fn filesystem_store_sweeps_the_directory() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("cache");
+        let store = FileSystemStore::new(FileSystemCache {
+            path: path.clone(),
+            ttl: None,
+            max_size: Some(ByteSize::kib(10)),
+            sweep_interval: None,
+        })
+        .await
+        .unwrap();
+        let store: Arc<dyn Store> = Arc::new(store);
+
+        store
+            .set_json("expired", &"value", Some(Duration::from_millis(1)))
+            .await;
+        for key in ["a", "b", "c"] {
+            store.set_json(key, &"x".repeat(300), None).await;
+            // entries are evicted by modification time
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        std::fs::write(path.join("interrupted.tmp"), "partial").unwrap();
+
+        assert_eq!(FileSystemStore::sweep(&path, 700).await.unwrap(), 2 * 332);
+        assert_eq!(store.get_json::<String>("expired").await, None);
+        assert_eq!(store.get_json::<String>("a").await, None);
+        assert!(store.get_json::<String>("b").await.is_some());
+        assert!(store.get_json::<String>("c").await.is_some());
+        // too recent to be left by an interrupted write
+        assert!(path.join("interrupted.tmp").exists());
+    }
+
+    #[test]
+    fn invalid_payload_serialization_doesnt_fail() {
+        #[derive(Serialize)]
+        struct Stuff {
+            time: SystemTime,
+        }
+
+        // this systemtime is invalid, serialization will fail
+        let invalid_json_payload = Stuff {
+            time: UNIX_EPOCH - Duration::new(1, 0),
+        };
+
+        assert!(serialize(&invalid_json_payload).is_none());
+    }
+}
@@ -0,0 +1,583 @@
+//! Self-test of the request pipeline
+//!
+//! `router check` builds the full request pipeline from the supergraph schema and the
+//! configuration, then executes sample operations with the subgraph responses mocked from a
+//! fixtures file, without calling the subgraphs. The result of each operation is printed in a
+//! report, and the command fails if any of them failed, so that a schema and configuration can be
+//! validated in CI or in an init container before the router is deployed.
+//!
+//! The parts of the configuration connecting to other services are removed before the pipeline is
+//! built, see [`offline_configuration`] and [`disable_apollo_reporting`].
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use http::header::CONTENT_TYPE;
+use http::Method;
+use http::Uri;
+use serde::Deserialize;
+use serde_json_bytes::ByteString;
+use serde_json_bytes::Map as JsonMap;
+use serde_json_bytes::Value;
+use tower::BoxError;
+use tower::ServiceExt;
+
+use crate::configuration::Configuration;
+use crate::graphql;
+use crate::json_ext::Object;
+use crate::services::router;
+use crate::services::router::body::get_body_bytes;
+use crate::services::subgraph;
+use crate::services::supergraph;
+use crate::Context;
+use crate::TestHarness;
+
+const NO_MATCHING_FIXTURE_CODE: &str = "NO_MATCHING_FIXTURE";
+
+/// Sample operations and the subgraph responses they rely on
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct Fixtures {
+    /// Mocked responses of each subgraph, by subgraph name
+    subgraphs: HashMap<String, Vec<SubgraphFixture>>,
+    /// Operations executed by the check
+    operations: Vec<OperationFixture>,
+}
+
+/// A mocked subgraph response. The first fixture of a subgraph matching a request answers it
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SubgraphFixture {
+    /// Request answered by this fixture, or any request if absent
+    #[serde(default)]
+    request: Option<RequestMatcher>,
+    /// Response returned by the subgraph
+    response: graphql::Response,
+}
+
+/// Matches the requests sent to a subgraph
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+struct RequestMatcher {
+    /// Query of the request, compared without whitespace
+    query: Option<String>,
+    /// Variables of the request. Variables absent from the fixture are not compared
+    variables: Object,
+}
+
+/// An operation executed by the check
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OperationFixture {
+    /// Name of the operation in the report
+    name: String,
+    query: String,
+    #[serde(default)]
+    operation_name: Option<String>,
+    #[serde(default)]
+    variables: JsonMap<ByteString, Value>,
+    /// Headers of the client request
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    /// Data expected in the response. The response must not have errors in any case
+    #[serde(default)]
+    expected: Option<Value>,
+}
+
+impl FromStr for Fixtures {
+    type Err = BoxError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fixtures: Fixtures = serde_yaml::from_str(s)?;
+        if fixtures.operations.is_empty() {
+            return Err("the fixtures do not declare any operation".into());
+        }
+        Ok(fixtures)
+    }
+}
+
+impl RequestMatcher {
+    fn matches(&self, request: &graphql::Request) -> bool {
+        let query_matches = match (&self.query, &request.query) {
+            (None, _) => true,
+            (Some(expected), Some(query)) => {
+                without_whitespace(expected) == without_whitespace(query)
+            }
+            (Some(_), None) => false,
+        };
+        query_matches
+            && self
+                .variables
+                .iter()
+                .all(|(name, value)| request.variables.get(name) == Some(value))
+    }
+}
+
+fn without_whitespace(query: &str) -> String {
+    query.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+/// Subgraph requests that no fixture matched, stored in the context extensions
+#[derive(Default)]
+struct UnmatchedRequests(Vec<String>);
+
+/// Answers the subgraph requests with the fixtures of the subgraph
+fn fixture_service(
+    subgraph_name: &str,
+    fixtures: Arc<HashMap<String, Vec<SubgraphFixture>>>,
+) -> subgraph::BoxService {
+    let subgraph_name = subgraph_name.to_string();
+    tower::service_fn(move |request: subgraph::Request| {
+        let body = request.subgraph_request.body();
+        let fixture = fixtures.get(&subgraph_name).and_then(|fixtures| {
+            fixtures.iter().find(|fixture| {
+                fixture
+                    .request
+                    .as_ref()
+                    .map_or(true, |matcher| matcher.matches(body))
+            })
+        });
+        let response = match fixture {
+            Some(fixture) => fixture.response.clone(),
+            None => {
+                let message = format!(
+                    "no fixture of the subgraph '{subgraph_name}' matches the query: {}",
+                    body.query.as_deref().unwrap_or_default()
+                );
+                request.context.extensions().with_lock(|mut lock| {
+                    lock.get_or_default_mut::<UnmatchedRequests>()
+                        .0
+                        .push(message.clone())
+                });
+                graphql::Response::builder()
+                    .error(
+                        graphql::Error::builder()
+                            .message(message)
+                            .extension_code(NO_MATCHING_FIXTURE_CODE)
+                            .build(),
+                    )
+                    .build()
+            }
+        };
+        std::future::ready(Ok(subgraph::Response::new_from_response(
+            http::Response::new(response),
+            request.context,
+            subgraph_name.clone(),
+        )))
+    })
+    .boxed()
+}
+
+/// Result of the check
+#[derive(Debug, Default)]
+pub(crate) struct Report {
+    operations: Vec<OperationReport>,
+}
+
+#[derive(Debug)]
+struct OperationReport {
+    name: String,
+    failures: Vec<String>,
+}
+
+impl Report {
+    /// Number of failed operations
+    pub(crate) fn failed(&self) -> usize {
+        self.operations
+            .iter()
+            .filter(|operation| !operation.failures.is_empty())
+            .count()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for operation in &self.operations {
+            if operation.failures.is_empty() {
+                writeln!(f, "PASS {}", operation.name)?;
+            } else {
+                writeln!(f, "FAIL {}", operation.name)?;
+                for failure in &operation.failures {
+                    writeln!(f, "  - {failure}")?;
+                }
+            }
+        }
+        write!(
+            f,
+            "{} operations, {} passed, {} failed",
+            self.operations.len(),
+            self.operations.len() - self.failed(),
+            self.failed()
+        )
+    }
+}
+
+/// Runs the check with the files given on the command line
+pub(crate) async fn run(
+    config_path: Option<&Path>,
+    supergraph_path: Option<&Path>,
+    fixtures_path: &Path,
+) -> Result<Report, BoxError> {
+    let supergraph_path = supergraph_path
+        .ok_or("`router check` requires a supergraph schema, set with --supergraph")?;
+    let schema = std::fs::read_to_string(supergraph_path)
+        .map_err(|e| format!("could not read the supergraph schema: {e}"))?;
+    let mut configuration = match config_path {
+        Some(path) => {
+            let configuration = std::fs::read_to_string(path)
+                .map_err(|e| format!("could not read the configuration: {e}"))?;
+            // validates the file as it is, so that errors point at its lines
+            configuration.parse::<Configuration>()?;
+            offline_configuration(&configuration)?.parse()?
+        }
+        None => Configuration::default(),
+    };
+    disable_apollo_reporting(&mut configuration);
+    let fixtures = std::fs::read_to_string(fixtures_path)
+        .map_err(|e| format!("could not read the fixtures: {e}"))?
+        .parse::<Fixtures>()
+        .map_err(|e| format!("invalid fixtures: {e}"))?;
+
+    check(&schema, configuration, fixtures).await
+}
+
+/// Removes the parts of the configuration connecting to other services when the pipeline is
+/// built or when operations are executed:
+/// * Redis caches, locks and rate limit counters, replaced by their in-memory versions
+/// * coprocessors, which are not called
+/// * telemetry exporters, including the Prometheus endpoint
+/// * JWKS fetched over the network, only the `file://` ones are kept
+/// * subgraph authentication, which only applies to the mocked subgraph requests
+fn offline_configuration(configuration: &str) -> Result<String, BoxError> {
+    let mut yaml: serde_json::Value = serde_yaml::from_str(configuration)?;
+    let Some(root) = yaml.as_object_mut() else {
+        return Ok(configuration.to_string());
+    };
+
+    root.remove("coprocessor");
+    if let Some(telemetry) = root.get_mut("telemetry").and_then(|t| t.as_object_mut()) {
+        telemetry.remove("exporters");
+    }
+    if let Some(shaping) = root
+        .get_mut("traffic_shaping")
+        .and_then(|t| t.as_object_mut())
+    {
+        shaping.remove("rate_limit_store");
+    }
+    if let Some(authentication) = root
+        .get_mut("authentication")
+        .and_then(|a| a.as_object_mut())
+    {
+        authentication.remove("subgraph");
+    }
+    if let Some(jwks) = yaml
+        .pointer_mut("/authentication/router/jwt/jwks")
+        .and_then(|jwks| jwks.as_array_mut())
+    {
+        jwks.retain(|jwks| {
+            jwks.get("url")
+                .and_then(|url| url.as_str())
+                .map_or(false, |url| url.starts_with("file:"))
+        });
+    }
+    remove_redis(&mut yaml);
+
+    Ok(serde_yaml::to_string(&yaml)?)
+}
+
+/// Disables Apollo usage reporting, which the `APOLLO_KEY` and `APOLLO_GRAPH_REF` environment
+/// variables enable whatever the configuration file contains
+fn disable_apollo_reporting(configuration: &mut Configuration) {
+    let telemetry = configuration
+        .apollo_plugins
+        .plugins
+        .entry("telemetry")
+        .or_insert_with(|| serde_json::json!({}));
+    if let Some(apollo) = telemetry
+        .as_object_mut()
+        .map(|telemetry| {
+            telemetry
+                .entry("apollo")
+                .or_insert_with(|| serde_json::json!({}))
+        })
+        .and_then(|apollo| apollo.as_object_mut())
+    {
+        apollo.insert("apollo_key".to_string(), serde_json::Value::Null);
+    }
+}
+
+fn remove_redis(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(object) => {
+            object.remove("redis");
+            object.values_mut().for_each(remove_redis);
+        }
+        serde_json::Value::Array(array) => array.iter_mut().for_each(remove_redis),
+        _ => {}
+    }
+}
+
+/// Executes the operations of the fixtures through the pipeline
+pub(crate) async fn check(
+    schema: &str,
+    configuration: Configuration,
+    fixtures: Fixtures,
+) -> Result<Report, BoxError> {
+    let subgraph_fixtures = Arc::new(fixtures.subgraphs);
+    let service = TestHarness::builder()
+        .schema(schema)
+        .configuration(Arc::new(configuration))
+        .subgraph_hook(move |subgraph_name, _| {
+            fixture_service(subgraph_name, subgraph_fixtures.clone())
+        })
+        .build_router()
+        .await?;
+
+    let mut report = Report::default();
+    for operation in fixtures.operations {
+        let failures = match execute(service.clone(), &operation).await {
+            Ok((response, unmatched)) => check_response(&operation, response, unmatched),
+            Err(err) => vec![format!("the operation could not be executed: {err}")],
+        };
+        report.operations.push(OperationReport {
+            name: operation.name,
+            failures,
+        });
+    }
+    Ok(report)
+}
+
+async fn execute(
+    service: router::BoxCloneService,
+    operation: &OperationFixture,
+) -> Result<(graphql::Response, Vec<String>), BoxError> {
+    let context = Context::new();
+    let mut request = supergraph::Request::builder()
+        .query(operation.query.clone())
+        .and_operation_name(operation.operation_name.clone())
+        .variables(operation.variables.clone())
+        .context(context.clone())
+        .header(CONTENT_TYPE, "application/json")
+        .uri(Uri::from_static("http://localhost/"))
+        .method(Method::POST)
+        .build()?;
+    for (name, value) in &operation.headers {
+        request.supergraph_request.headers_mut().insert(
+            http::HeaderName::from_bytes(name.as_bytes())?,
+            http::HeaderValue::from_str(value)?,
+        );
+    }
+
+    let response = service.oneshot(router::Request::try_from(request)?).await?;
+    let body = get_body_bytes(response.response.into_body()).await?;
+    let response = graphql::Response::from_bytes("router", body)?;
+    let unmatched = context
+        .extensions()
+        .with_lock(|mut lock| lock.remove::<UnmatchedRequests>())
+        .unwrap_or_default();
+    Ok((response, unmatched.0))
+}
+
+fn check_response(
+    operation: &OperationFixture,
+    response: graphql::Response,
+    unmatched: Vec<String>,
+) -> Vec<String> {
+    let mut failures = unmatched;
+    failures.extend(
+        response
+            .errors
+            .iter()
+            // unmatched subgraph requests are already reported
+            .filter(|error| {
+                error.extensions.get("code").and_then(Value::as_str)
+                    != Some(NO_MATCHING_FIXTURE_CODE)
+            })
+            .map(|error| format!("the response has an error: {}", error.message)),
+    );
+    if let Some(expected) = &operation.expected {
+        let data = response.data.unwrap_or(Value::Null);
+        if &data != expected {
+            failures.push(format!(
+                "the data does not match the expected data: {}",
+                serde_json::to_string(&data).unwrap_or_default()
+            ));
+        }
+    }
+    failures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &str = include_str!("../testing_schema.graphql");
+
+    fn fixtures(yaml: &str) -> Fixtures {
+        yaml.parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn it_passes_operations_answered_by_the_fixtures() {
+        let report = check(
+            SCHEMA,
+            Configuration::default(),
+            fixtures(
+                r#"
+subgraphs:
+  products:
+    - request:
+        query: "{ topProducts { upc name } }"
+      response:
+        data:
+          topProducts:
+            - upc: "1"
+              name: Table
+operations:
+  - name: top products
+    query: "{ topProducts { upc name } }"
+    expected:
+      topProducts:
+        - upc: "1"
+          name: Table
+"#,
+            ),
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.failed(), 0, "{report}");
+    }
+
+    #[tokio::test]
+    async fn it_reports_failed_operations() {
+        let report = check(
+            SCHEMA,
+            Configuration::default(),
+            fixtures(
+                r#"
+subgraphs:
+  products:
+    - request:
+        query: "{ topProducts { upc } }"
+      response:
+        data:
+          topProducts:
+            - upc: "1"
+operations:
+  - name: unexpected data
+    query: "{ topProducts { upc } }"
+    expected:
+      topProducts:
+        - upc: "2"
+  - name: missing fixture
+    query: "{ topProducts { name } }"
+  - name: invalid query
+    query: "{ unknown }"
+"#,
+            ),
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.failed(), 3, "{report}");
+        let failures = |index: usize| &report.operations[index].failures;
+        assert!(failures(0)[0].starts_with("the data does not match"));
+        assert!(failures(1)[0].starts_with("no fixture of the subgraph 'products'"));
+        assert_eq!(failures(1).len(), 1);
+        assert!(failures(2)[0].starts_with("the response has an error"));
+    }
+
+    #[test]
+    fn it_matches_requests_ignoring_whitespace_and_extra_variables() {
+        let matcher: RequestMatcher = serde_yaml::from_str(
+            r#"
+query: "query($id: ID!) { product(id: $id) { upc } }"
+variables:
+  id: "1"
+"#,
+        )
+        .unwrap();
+        let request = graphql::Request::fake_builder()
+            .query("query($id:ID!){product(id:$id){upc}}")
+            .variable("id", "1")
+            .variable("other", true)
+            .build();
+        assert!(matcher.matches(&request));
+
+        let request = graphql::Request::fake_builder()
+            .query("query($id:ID!){product(id:$id){upc}}")
+            .variable("id", "2")
+            .build();
+        assert!(!matcher.matches(&request));
+    }
+
+    #[test]
+    fn it_removes_the_configuration_connecting_to_other_services() {
+        let configuration = offline_configuration(
+            r#"
+supergraph:
+  query_planning:
+    cache:
+      redis:
+        urls: ["redis://localhost:6379"]
+coprocessor:
+  url: http://localhost:8081
+telemetry:
+  exporters:
+    metrics:
+      prometheus:
+        enabled: true
+traffic_shaping:
+  rate_limit_store:
+    redis:
+      urls: ["redis://localhost:6379"]
+authentication:
+  router:
+    jwt:
+      jwks:
+        - url: https://idp.example.com/jwks.json
+        - url: file:///etc/router/jwks.json
+"#,
+        )
+        .unwrap();
+        let yaml: serde_json::Value = serde_yaml::from_str(&configuration).unwrap();
+        assert_eq!(
+            yaml,
+            serde_json::json!({
+                "supergraph": { "query_planning": { "cache": {} } },
+                "telemetry": {},
+                "traffic_shaping": {},
+                "authentication": {
+                    "router": { "jwt": { "jwks": [{ "url": "file:///etc/router/jwks.json" }] } }
+                }
+            })
+        );
+        configuration.parse::<Configuration>().unwrap();
+    }
+
+    #[test]
+    fn it_disables_apollo_reporting() {
+        let mut configuration = Configuration::default();
+        disable_apollo_reporting(&mut configuration);
+        let telemetry: crate::plugins::telemetry::config::Conf =
+            serde_json::from_value(configuration.apollo_plugins.plugins["telemetry"].clone())
+                .unwrap();
+        assert!(telemetry.apollo.apollo_key.is_none());
+
+        let mut configuration: Configuration = "telemetry:\n  apollo:\n    send_headers: all"
+            .parse()
+            .unwrap();
+        disable_apollo_reporting(&mut configuration);
+        assert_eq!(
+            configuration.apollo_plugins.plugins["telemetry"]["apollo"],
+            serde_json::json!({ "send_headers": "all", "apollo_key": null })
+        );
+    }
+
+    #[test]
+    fn it_requires_operations() {
+        assert!("subgraphs: {}".parse::<Fixtures>().is_err());
+    }
+}
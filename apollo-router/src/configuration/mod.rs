@@ -1,4 +1,5 @@
 //! Logic for loading configuration in to an object model
+use std::collections::HashMap;
 use std::fmt;
 use std::io;
 use std::io::BufReader;
@@ -6,16 +7,20 @@ use std::iter;
 use std::net::IpAddr;
 use std::net::SocketAddr;
 use std::num::NonZeroUsize;
+use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
 
+use bytesize::ByteSize;
 use derivative::Derivative;
 use displaydoc::Display;
 use itertools::Itertools;
 use once_cell::sync::Lazy;
 pub(crate) use persisted_queries::PersistedQueries;
 #[cfg(test)]
+pub(crate) use persisted_queries::PersistedQueriesManifestStore;
+#[cfg(test)]
 pub(crate) use persisted_queries::PersistedQueriesSafelist;
 use regex::Regex;
 use rustls::Certificate;
@@ -44,6 +49,7 @@ pub(crate) use self::schema::generate_upgrade;
 use self::subgraph::SubgraphConfiguration;
 use crate::cache::DEFAULT_CACHE_CAPACITY;
 use crate::configuration::schema::Mode;
+use crate::error::LocatedError;
 use crate::graphql;
 use crate::notification::Notify;
 use crate::plugin::plugins;
@@ -97,6 +103,11 @@ pub enum ConfigurationError {
         message: &'static str,
         error: String,
     },
+    /// configuration had errors: {report}
+    LocatedErrors {
+        report: String,
+        errors: Vec<LocatedError>,
+    },
     /// could not deserialize configuration: {0}
     DeserializeConfigError(serde_json::Error),
 
@@ -592,6 +603,19 @@ impl Configuration {
             });
         }
 
+        if self.experimental_query_planner_mode != QueryPlannerMode::New
+            && !self
+                .supergraph
+                .query_planning
+                .experimental_strategy
+                .is_balanced()
+        {
+            return Err(ConfigurationError::InvalidConfiguration {
+                message: "`supergraph.query_planning.experimental_strategy` requires `experimental_query_planner_mode: new`",
+                error: "either use the balanced strategy, or change to the new query planner".into()
+            });
+        }
+
         let apollo_telemetry_config = match self.apollo_plugins.plugins.get("telemetry") {
             Some(telemetry_config) => {
                 match serde_json::from_value::<crate::plugins::telemetry::config::Conf>(
@@ -747,6 +771,9 @@ pub(crate) struct Supergraph {
     /// Set to false to disable defer support
     pub(crate) defer_support: bool,
 
+    /// Limits on the deferred fragments honored in an operation
+    pub(crate) defer_limits: DeferLimits,
+
     /// Query planning options
     pub(crate) query_planning: QueryPlanning,
 
@@ -759,6 +786,90 @@ pub(crate) struct Supergraph {
     /// Log a message if the client closes the connection before the response is sent.
     /// Default: false.
     pub(crate) experimental_log_on_broken_pipe: bool,
+
+    /// Shape of the JSON responses, for compatibility with legacy clients
+    pub(crate) response_format: ResponseFormat,
+
+    /// Execution of queries and mutations over WebSocket connections
+    pub(crate) websocket: SupergraphWebSocket,
+
+    /// Load supergraphs linking newer minor versions of the join and link specs, or unknown
+    /// features for EXECUTION, instead of refusing them: newer versions are handled as the
+    /// latest version supported by the router if the supergraph only uses the directives and
+    /// arguments of that version, and unknown features are ignored. Features for SECURITY are
+    /// never ignored.
+    /// Default: false
+    pub(crate) experimental_spec_fallback: bool,
+
+    /// Record the latency and errors of the subgraph requests of each client request in its
+    /// context, under the `apollo_router::subgraph::statistics` key
+    /// Default: false
+    pub(crate) experimental_subgraph_statistics: bool,
+}
+
+/// Execution of queries and mutations over WebSocket connections opened on the GraphQL endpoint,
+/// with the `graphql-transport-ws` protocol. The headers of the upgrade request and the
+/// `connection_init` payload apply to all the operations sent on the connection
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct SupergraphWebSocket {
+    /// Accept WebSocket connections on the GraphQL endpoint
+    /// Default: false
+    pub(crate) enabled: bool,
+
+    /// Origins allowed to open connections, like `https://app.example.com`. Upgrade requests
+    /// with an `Origin` header that is not in this list are rejected, so that other websites
+    /// cannot open connections with the cookies of their visitors. Requests without an `Origin`
+    /// header, which are not sent by browsers, are accepted
+    /// Default: []
+    pub(crate) allowed_origins: Vec<String>,
+
+    /// Maximum number of operations executing at the same time on a connection
+    /// Default: 100
+    pub(crate) max_operations_per_connection: usize,
+}
+
+impl Default for SupergraphWebSocket {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            allowed_origins: Vec::new(),
+            max_operations_per_connection: 100,
+        }
+    }
+}
+
+/// Compatibility options for clients expecting a fixed shape of the JSON responses.
+/// They do not apply to multipart responses
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct ResponseFormat {
+    /// Always include the `data` field, set to null if the response has no data
+    /// Default: false
+    pub(crate) always_include_data: bool,
+
+    /// Place the `errors` field before the `data` field
+    /// Default: false
+    pub(crate) errors_before_data: bool,
+
+    /// Include the `extensions` field even if it is empty
+    /// Default: false
+    pub(crate) include_empty_extensions: bool,
+}
+
+/// Limits on the deferred fragments honored in an operation. Deferred fragments that are not
+/// honored are executed inline, as if they had no `@defer` directive
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct DeferLimits {
+    /// Maximum number of deferred fragments honored in an operation, in document order
+    /// Default: no limit
+    pub(crate) max_deferred_fragments: Option<usize>,
+
+    /// Names of the clients for which all deferred fragments are executed inline, as sent in the
+    /// client name header
+    /// Default: empty
+    pub(crate) disabled_for_clients: Vec<String>,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
@@ -792,17 +903,23 @@ impl Supergraph {
         path: Option<String>,
         introspection: Option<bool>,
         defer_support: Option<bool>,
+        defer_limits: Option<DeferLimits>,
         query_planning: Option<QueryPlanning>,
         reuse_query_fragments: Option<bool>,
         generate_query_fragments: Option<bool>,
         early_cancel: Option<bool>,
         experimental_log_on_broken_pipe: Option<bool>,
+        response_format: Option<ResponseFormat>,
+        websocket: Option<SupergraphWebSocket>,
+        experimental_spec_fallback: Option<bool>,
+        experimental_subgraph_statistics: Option<bool>,
     ) -> Self {
         Self {
             listen: listen.unwrap_or_else(default_graphql_listen),
             path: path.unwrap_or_else(default_graphql_path),
             introspection: introspection.unwrap_or_else(default_graphql_introspection),
             defer_support: defer_support.unwrap_or_else(default_defer_support),
+            defer_limits: defer_limits.unwrap_or_default(),
             query_planning: query_planning.unwrap_or_default(),
             reuse_query_fragments: generate_query_fragments.and_then(|v|
                 if v {
@@ -816,6 +933,11 @@ impl Supergraph {
             generate_query_fragments: generate_query_fragments.unwrap_or_default(),
             early_cancel: early_cancel.unwrap_or_default(),
             experimental_log_on_broken_pipe: experimental_log_on_broken_pipe.unwrap_or_default(),
+            response_format: response_format.unwrap_or_default(),
+            websocket: websocket.unwrap_or_default(),
+            experimental_spec_fallback: experimental_spec_fallback.unwrap_or_default(),
+            experimental_subgraph_statistics: experimental_subgraph_statistics
+                .unwrap_or_default(),
         }
     }
 }
@@ -829,17 +951,23 @@ impl Supergraph {
         path: Option<String>,
         introspection: Option<bool>,
         defer_support: Option<bool>,
+        defer_limits: Option<DeferLimits>,
         query_planning: Option<QueryPlanning>,
         reuse_query_fragments: Option<bool>,
         generate_query_fragments: Option<bool>,
         early_cancel: Option<bool>,
         experimental_log_on_broken_pipe: Option<bool>,
+        response_format: Option<ResponseFormat>,
+        websocket: Option<SupergraphWebSocket>,
+        experimental_spec_fallback: Option<bool>,
+        experimental_subgraph_statistics: Option<bool>,
     ) -> Self {
         Self {
             listen: listen.unwrap_or_else(test_listen),
             path: path.unwrap_or_else(default_graphql_path),
             introspection: introspection.unwrap_or_else(default_graphql_introspection),
             defer_support: defer_support.unwrap_or_else(default_defer_support),
+            defer_limits: defer_limits.unwrap_or_default(),
             query_planning: query_planning.unwrap_or_default(),
             reuse_query_fragments: generate_query_fragments.and_then(|v|
                 if v {
@@ -853,6 +981,11 @@ impl Supergraph {
             generate_query_fragments: generate_query_fragments.unwrap_or_default(),
             early_cancel: early_cancel.unwrap_or_default(),
             experimental_log_on_broken_pipe: experimental_log_on_broken_pipe.unwrap_or_default(),
+            response_format: response_format.unwrap_or_default(),
+            websocket: websocket.unwrap_or_default(),
+            experimental_spec_fallback: experimental_spec_fallback.unwrap_or_default(),
+            experimental_subgraph_statistics: experimental_subgraph_statistics
+                .unwrap_or_default(),
         }
     }
 }
@@ -918,12 +1051,26 @@ impl Apq {
 pub(crate) struct SubgraphApq {
     /// Enable
     pub(crate) enabled: bool,
+
+    /// Register the subgraph operations of the warmed up query plans with the subgraph when the
+    /// router reloads, so that the first requests for these operations only send the query hash.
+    /// Only query operations are registered, without variables
+    /// Default: false
+    pub(crate) pre_register: bool,
 }
 
 fn default_apq() -> bool {
     true
 }
 
+impl Apq {
+    /// Returns true if the operations sent to this subgraph are registered when the router reloads
+    pub(crate) fn subgraph_pre_register(&self, subgraph: &str) -> bool {
+        let apq = self.subgraph.get(subgraph);
+        apq.enabled && apq.pre_register
+    }
+}
+
 impl Default for Apq {
     fn default() -> Self {
         Self {
@@ -973,6 +1120,12 @@ pub(crate) struct QueryPlanning {
     /// the old schema, if it determines that the schema update does not affect the corresponding query
     pub(crate) experimental_reuse_query_plans: bool,
 
+    /// When the query plan cache is stored in Redis and shared by multiple router instances, only
+    /// the first instance to load a new schema warms up the cache, the other instances only load
+    /// the query plans already stored in Redis, without waiting. This avoids every instance
+    /// planning the same queries at the same time after a schema release
+    pub(crate) experimental_warm_up_leader_election: bool,
+
     /// Set the size of a pool of workers to enable query planning parallelism.
     /// Default: 1.
     pub(crate) experimental_parallelism: AvailableParallelism,
@@ -984,6 +1137,16 @@ pub(crate) struct QueryPlanning {
     /// the cache, this option can be used to deactivate it.
     /// Default: true
     pub(crate) legacy_introspection_caching: bool,
+
+    /// Selects how the query planner picks between the possible plans of an operation, for all
+    /// operations or by operation name. Requires `experimental_query_planner_mode: new`
+    pub(crate) experimental_strategy: QueryPlanStrategyConfig,
+
+    /// Adds `__typename` to the selections of interface and union types in the subgraph
+    /// operations of query plans, where the query planner did not select it, so that entities
+    /// and abstract types are merged with their concrete type. It is not added to the responses
+    /// of clients that did not select it
+    pub(crate) experimental_backfill_typename: bool,
 }
 
 impl Default for QueryPlanning {
@@ -995,7 +1158,10 @@ impl Default for QueryPlanning {
             experimental_parallelism: Default::default(),
             experimental_paths_limit: Default::default(),
             experimental_reuse_query_plans: Default::default(),
+            experimental_warm_up_leader_election: Default::default(),
             legacy_introspection_caching: default_legacy_introspection_caching(),
+            experimental_strategy: Default::default(),
+            experimental_backfill_typename: Default::default(),
         }
     }
 }
@@ -1013,6 +1179,68 @@ impl QueryPlanning {
     }
 }
 
+/// Query planning strategy configuration
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct QueryPlanStrategyConfig {
+    /// Strategy used for the operations without an override
+    pub(crate) default: QueryPlanStrategy,
+    /// Strategy overrides, by operation name
+    pub(crate) operations: HashMap<String, QueryPlanStrategy>,
+}
+
+impl QueryPlanStrategyConfig {
+    pub(crate) fn for_operation(&self, operation_name: Option<&str>) -> QueryPlanStrategy {
+        operation_name
+            .and_then(|name| self.operations.get(name))
+            .copied()
+            .unwrap_or(self.default)
+    }
+
+    fn is_balanced(&self) -> bool {
+        self.default == QueryPlanStrategy::Balanced
+            && self
+                .operations
+                .values()
+                .all(|strategy| *strategy == QueryPlanStrategy::Balanced)
+    }
+}
+
+/// How the query planner picks between the possible plans of an operation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum QueryPlanStrategy {
+    /// Weighs the number of subgraph fetches against the length of the sequences of fetches
+    #[default]
+    Balanced,
+    /// Favors plans making fewer subgraph fetches, even if more of them are made in sequence.
+    /// This lowers the load on subgraphs
+    MinimizeFetches,
+    /// Favors plans making fewer fetches in sequence, even if more fetches are made in parallel.
+    /// This lowers the latency of operations
+    MaximizeParallelism,
+}
+
+impl QueryPlanStrategy {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            QueryPlanStrategy::Balanced => "balanced",
+            QueryPlanStrategy::MinimizeFetches => "minimize_fetches",
+            QueryPlanStrategy::MaximizeParallelism => "maximize_parallelism",
+        }
+    }
+}
+
+impl From<QueryPlanStrategy> for apollo_federation::query_plan::query_planner::QueryPlanStrategy {
+    fn from(strategy: QueryPlanStrategy) -> Self {
+        match strategy {
+            QueryPlanStrategy::Balanced => Self::Balanced,
+            QueryPlanStrategy::MinimizeFetches => Self::MinimizeFetches,
+            QueryPlanStrategy::MaximizeParallelism => Self::MaximizeParallelism,
+        }
+    }
+}
+
 /// Cache configuration
 #[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields, default)]
@@ -1021,6 +1249,8 @@ pub(crate) struct QueryPlanCache {
     pub(crate) in_memory: InMemoryCache,
     /// Configures and activates the Redis cache
     pub(crate) redis: Option<QueryPlanRedisCache>,
+    /// Configures and activates the filesystem cache, for deployments without Redis
+    pub(crate) filesystem: Option<FileSystemCache>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
@@ -1077,6 +1307,8 @@ pub(crate) struct Cache {
     pub(crate) in_memory: InMemoryCache,
     /// Configures and activates the Redis cache
     pub(crate) redis: Option<RedisCache>,
+    /// Configures and activates the filesystem cache, for deployments without Redis
+    pub(crate) filesystem: Option<FileSystemCache>,
 }
 
 impl From<QueryPlanCache> for Cache {
@@ -1084,6 +1316,7 @@ impl From<QueryPlanCache> for Cache {
         Cache {
             in_memory: value.in_memory,
             redis: value.redis.map(Into::into),
+            filesystem: value.filesystem,
         }
     }
 }
@@ -1093,13 +1326,24 @@ impl From<QueryPlanCache> for Cache {
 /// In memory cache configuration
 pub(crate) struct InMemoryCache {
     /// Number of entries in the Least Recently Used cache
+    #[serde(default = "default_cache_capacity")]
     pub(crate) limit: NonZeroUsize,
+    /// Percentage of the memory available to the router (the cgroup memory limit in a
+    /// container, the system memory otherwise) used by the cache. When set, the cache is bounded
+    /// by the estimated size of its entries in addition to their number
+    #[serde(default)]
+    pub(crate) memory_percentage: Option<f64>,
+}
+
+fn default_cache_capacity() -> NonZeroUsize {
+    DEFAULT_CACHE_CAPACITY
 }
 
 impl Default for InMemoryCache {
     fn default() -> Self {
         Self {
             limit: DEFAULT_CACHE_CAPACITY,
+            memory_percentage: None,
         }
     }
 }
@@ -1166,6 +1410,31 @@ fn default_reset_ttl() -> bool {
     true
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+/// Filesystem cache configuration
+pub(crate) struct FileSystemCache {
+    /// Directory storing the cache entries, created if it does not exist. It can be shared by the
+    /// router instances running on the same host
+    pub(crate) path: PathBuf,
+
+    #[serde(deserialize_with = "humantime_serde::deserialize", default)]
+    #[schemars(with = "Option<String>", default)]
+    /// TTL for entries
+    pub(crate) ttl: Option<Duration>,
+
+    #[schemars(with = "Option<String>", default)]
+    /// Maximum size of the directory. Beyond it, the entries modified the longest time ago are
+    /// deleted (default: 1GiB)
+    pub(crate) max_size: Option<ByteSize>,
+
+    #[serde(deserialize_with = "humantime_serde::deserialize", default)]
+    #[schemars(with = "Option<String>", default)]
+    /// Interval between the sweeps deleting the expired entries and enforcing the maximum size
+    /// (default: 5m)
+    pub(crate) sweep_interval: Option<Duration>,
+}
+
 /// TLS related configuration options.
 #[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
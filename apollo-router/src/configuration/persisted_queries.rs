@@ -2,6 +2,9 @@ use schemars::JsonSchema;
 use serde::Deserialize;
 use serde::Serialize;
 
+use super::FileSystemCache;
+use super::RedisCache;
+
 /// Persisted Queries (PQ) configuration
 #[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields, default)]
@@ -20,6 +23,24 @@ pub struct PersistedQueries {
 
     /// Enables using a local copy of the persisted query manifest to safelist operations
     pub experimental_local_manifests: Option<Vec<String>>,
+
+    /// Accepts operations sent with only a `documentId`, like the persisted documents of Relay
+    /// or urql. Unknown document IDs are rejected, without falling back to APQ
+    pub trusted_documents: bool,
+
+    /// Keeps a copy of the persisted query list fetched from GraphOS, used when the router
+    /// starts while GraphOS cannot be reached
+    pub(crate) experimental_manifest_store: Option<PersistedQueriesManifestStore>,
+}
+
+/// Storage of the persisted query list
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct PersistedQueriesManifestStore {
+    /// Redis storing the list
+    pub(crate) redis: Option<RedisCache>,
+    /// Directory storing the list
+    pub(crate) filesystem: Option<FileSystemCache>,
 }
 
 #[cfg(test)]
@@ -32,6 +53,8 @@ impl PersistedQueries {
         safelist: Option<PersistedQueriesSafelist>,
         experimental_prewarm_query_plan_cache: Option<bool>,
         experimental_local_manifests: Option<Vec<String>>,
+        trusted_documents: Option<bool>,
+        experimental_manifest_store: Option<PersistedQueriesManifestStore>,
     ) -> Self {
         Self {
             enabled: enabled.unwrap_or_else(default_pq),
@@ -40,6 +63,8 @@ impl PersistedQueries {
             experimental_prewarm_query_plan_cache: experimental_prewarm_query_plan_cache
                 .unwrap_or_else(default_prewarm_query_plan_cache),
             experimental_local_manifests,
+            trusted_documents: trusted_documents.unwrap_or_else(default_trusted_documents),
+            experimental_manifest_store,
         }
     }
 }
@@ -75,6 +100,8 @@ impl Default for PersistedQueries {
             log_unknown: default_log_unknown(),
             experimental_prewarm_query_plan_cache: default_prewarm_query_plan_cache(),
             experimental_local_manifests: None,
+            trusted_documents: default_trusted_documents(),
+            experimental_manifest_store: None,
         }
     }
 }
@@ -107,3 +134,7 @@ const fn default_log_unknown() -> bool {
 const fn default_prewarm_query_plan_cache() -> bool {
     false
 }
+
+const fn default_trusted_documents() -> bool {
+    false
+}
@@ -28,9 +28,16 @@ use super::ConfigurationError;
 use super::APOLLO_PLUGIN_PREFIX;
 pub(crate) use crate::configuration::upgrade::generate_upgrade;
 pub(crate) use crate::configuration::upgrade::upgrade_configuration;
+use crate::error::LocatedError;
 
 const NUMBER_OF_PREVIOUS_LINES_TO_DISPLAY: usize = 5;
 
+const UNEXPECTED_PROPERTY_HINT: &str =
+    "remove the property or fix its name, `router config schema` prints the supported properties";
+
+const EXPANDED_VALUE_HINT: &str =
+    "the value is expanded from an environment variable or a file, check the expanded value";
+
 /// This needs to exist because Schemars incorrectly generates references with spaces in them.
 /// We just rename them.
 #[derive(Debug, Clone)]
@@ -151,6 +158,31 @@ pub(crate) fn validate_yaml_configuration(
         let yaml_split_by_lines = raw_yaml.split('\n').collect::<Vec<_>>();
 
         let mut errors = String::new();
+        let mut located = Vec::new();
+        let mut push_error = |idx: usize,
+                              marker: &Marker,
+                              lines: String,
+                              pointer: String,
+                              message: String,
+                              hint: Option<&str>| {
+            let help = hint
+                .map(|hint| format!("\n  help: {hint}"))
+                .unwrap_or_default();
+            let report = format!("{lines}\n{pointer} {message}{help}");
+            let _ = write!(
+                &mut errors,
+                "{}. at line {}\n\n{report}\n\n",
+                idx + 1,
+                marker.line(),
+            );
+            located.push(LocatedError {
+                message,
+                line: marker.line(),
+                column: marker.col() + 1,
+                hint: hint.map(str::to_string),
+                report,
+            });
+        };
 
         for (idx, mut e) in errors_it.enumerate() {
             if let Some(element) = parsed_yaml.get_element(&e.instance_path) {
@@ -175,14 +207,13 @@ pub(crate) fn validate_yaml_configuration(
                         // This guarantees that if the env variable contained a secret it won't be leaked.
                         e.instance = Cow::Owned(coerce(value));
 
-                        let _ = write!(
-                            &mut errors,
-                            "{}. at line {}\n\n{}\n{}^----- {}\n\n",
-                            idx + 1,
-                            start_marker.line(),
+                        push_error(
+                            idx,
+                            start_marker,
                             lines,
-                            " ".repeat(2 + marker.col()),
-                            e
+                            format!("{}^-----", " ".repeat(2 + marker.col())),
+                            e.to_string(),
+                            value.contains("${").then_some(EXPANDED_VALUE_HINT),
                         );
                     }
                     seq_element @ yaml::Value::Sequence(_, m) => {
@@ -190,13 +221,13 @@ pub(crate) fn validate_yaml_configuration(
 
                         let lines = context_lines(&yaml_split_by_lines, start_marker, end_marker);
 
-                        let _ = write!(
-                            &mut errors,
-                            "{}. at line {}\n\n{}\n└-----> {}\n\n",
-                            idx + 1,
-                            start_marker.line(),
+                        push_error(
+                            idx,
+                            start_marker,
                             lines,
-                            e
+                            "└----->".to_string(),
+                            e.to_string(),
+                            None,
                         );
                     }
                     map_value @ yaml::Value::Mapping(current_label, map, marker) => {
@@ -228,13 +259,13 @@ pub(crate) fn validate_yaml_configuration(
                                         unexpected: vec![key.clone()],
                                     };
 
-                                    let _ = write!(
-                                        &mut errors,
-                                        "{}. at line {}\n\n{}\n└-----> {}\n\n",
-                                        idx + 1,
-                                        start_marker.line(),
+                                    push_error(
+                                        idx,
+                                        start_marker,
                                         lines,
-                                        e
+                                        "└----->".to_string(),
+                                        e.to_string(),
+                                        Some(UNEXPECTED_PROPERTY_HINT),
                                     );
                                 }
                             }
@@ -250,13 +281,13 @@ pub(crate) fn validate_yaml_configuration(
                             let lines =
                                 context_lines(&yaml_split_by_lines, start_marker, end_marker);
 
-                            let _ = write!(
-                                &mut errors,
-                                "{}. at line {}\n\n{}\n└-----> {}\n\n",
-                                idx + 1,
-                                start_marker.line(),
+                            push_error(
+                                idx,
+                                start_marker,
                                 lines,
-                                e
+                                "└----->".to_string(),
+                                e.to_string(),
+                                None,
                             );
                         }
                     }
@@ -265,9 +296,9 @@ pub(crate) fn validate_yaml_configuration(
         }
 
         if !errors.is_empty() {
-            return Err(ConfigurationError::InvalidConfiguration {
-                message: "configuration had errors",
-                error: format!("\n{errors}"),
+            return Err(ConfigurationError::LocatedErrors {
+                report: format!("\n{errors}"),
+                errors: located,
             });
         }
     }
@@ -2,9 +2,12 @@ use schemars::JsonSchema;
 use serde::Deserialize;
 
 use crate::plugins::traffic_shaping::Http2Config;
+use crate::services::trust_dns_connector::DnsConfig;
 
 #[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields, default)]
 pub(crate) struct Client {
     pub(crate) experimental_http2: Option<Http2Config>,
+    /// DNS resolution configuration
+    pub(crate) dns: Option<DnsConfig>,
 }
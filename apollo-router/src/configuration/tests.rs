@@ -183,6 +183,7 @@ subgraphs:
 ┌ subgraphs:
 |   account: true
 └-----> Additional properties are not allowed ('subgraphs' was unexpected)
+  help: remove the property or fix its name, `router config schema` prints the supported properties
 
 "#
         )
@@ -210,12 +211,40 @@ unknown:
 ┌ unknown:
 |   foo: true
 └-----> Additional properties are not allowed ('unknown' was unexpected)
+  help: remove the property or fix its name, `router config schema` prints the supported properties
 
 "#
         )
     );
 }
 
+#[test]
+fn config_errors_are_located() {
+    let error = validate_yaml_configuration(
+        r#"
+supergraph:
+  introspection: maybe
+unknown:
+  foo: true
+  "#,
+        Expansion::default().unwrap(),
+        Mode::NoUpgrade,
+    )
+    .expect_err("should have resulted in an error");
+    let ConfigurationError::LocatedErrors { errors, .. } = error else {
+        panic!("errors must be located");
+    };
+    assert_eq!(errors.len(), 2);
+    let (introspection, unknown) = errors
+        .iter()
+        .partition::<Vec<_>, _>(|error| error.line == 3);
+    assert_eq!(introspection[0].column, 18);
+    assert!(introspection[0].hint.is_none());
+    assert_eq!(unknown[0].line, 4);
+    assert_eq!(unknown[0].column, 1);
+    assert!(unknown[0].hint.is_some());
+}
+
 #[test]
 fn empty_config() {
     validate_yaml_configuration(
@@ -1196,3 +1225,50 @@ fn it_requires_rust_apollo_metrics_generation_for_extended_references() {
         String::from("`experimental_apollo_metrics_reference_mode: extended` requires `experimental_apollo_metrics_generation_mode: new`: either change to the standard reference generation mode, or change to new metrics generation")
     );
 }
+
+#[test]
+fn it_selects_the_query_planning_strategy_by_operation_name() {
+    let strategy: QueryPlanStrategyConfig = serde_json::from_value(serde_json::json! {{
+        "default": "minimize_fetches",
+        "operations": {
+            "GetProduct": "maximize_parallelism"
+        }
+    }})
+    .unwrap();
+
+    assert_eq!(
+        strategy.for_operation(Some("GetProduct")),
+        QueryPlanStrategy::MaximizeParallelism
+    );
+    assert_eq!(
+        strategy.for_operation(Some("GetUser")),
+        QueryPlanStrategy::MinimizeFetches
+    );
+    assert_eq!(
+        strategy.for_operation(None),
+        QueryPlanStrategy::MinimizeFetches
+    );
+
+    let supergraph = Supergraph::builder()
+        .query_planning(QueryPlanning {
+            experimental_strategy: strategy,
+            ..Default::default()
+        })
+        .build();
+    Configuration::builder()
+        .supergraph(supergraph.clone())
+        .experimental_query_planner_mode(QueryPlannerMode::New)
+        .experimental_apollo_metrics_generation_mode(ApolloMetricsGenerationMode::New)
+        .build()
+        .expect("the new query planner supports strategies");
+
+    let error = Configuration::builder()
+        .supergraph(supergraph)
+        .experimental_query_planner_mode(QueryPlannerMode::Both)
+        .build()
+        .expect_err("Must have an error because the legacy query planner has no strategies");
+    assert_eq!(
+        error.to_string(),
+        String::from("`supergraph.query_planning.experimental_strategy` requires `experimental_query_planner_mode: new`: either use the balanced strategy, or change to the new query planner")
+    );
+}
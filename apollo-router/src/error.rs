@@ -1,4 +1,5 @@
 //! Router errors.
+use std::fmt::Write;
 use std::sync::Arc;
 
 use apollo_compiler::validation::DiagnosticList;
@@ -23,6 +24,7 @@ use crate::graphql::Location as ErrorLocation;
 use crate::graphql::Response;
 use crate::json_ext::Path;
 use crate::json_ext::Value;
+use crate::query_planner::plan_limits::PlanLimits;
 use crate::spec::operation_limits::OperationLimits;
 use crate::spec::SpecError;
 
@@ -109,6 +111,18 @@ pub(crate) enum FetchError {
         /// The reason batch processing failed.
         reason: String,
     },
+
+    /// request to '{service}' was not sent: its body of {size} bytes is over the limit of {limit} bytes
+    SubrequestBodyTooLarge {
+        /// The service the request was sent to.
+        service: String,
+
+        /// The size of the request body.
+        size: u64,
+
+        /// The maximum size of request bodies sent to the service.
+        limit: u64,
+    },
 }
 
 impl FetchError {
@@ -183,6 +197,7 @@ impl ErrorExtension for FetchError {
             FetchError::MalformedRequest { .. } => "MALFORMED_REQUEST",
             FetchError::MalformedResponse { .. } => "MALFORMED_RESPONSE",
             FetchError::SubrequestBatchingError { .. } => "SUBREQUEST_BATCHING_ERROR",
+            FetchError::SubrequestBodyTooLarge { .. } => "SUBREQUEST_BODY_TOO_LARGE",
         }
         .to_string()
     }
@@ -303,6 +318,9 @@ pub(crate) enum QueryPlannerError {
     /// complexity limit exceeded
     LimitExceeded(OperationLimits<bool>),
 
+    /// query plan size limit exceeded
+    PlanLimitExceeded(PlanLimits<bool>),
+
     /// Unauthorized field or type
     Unauthorized(Vec<Path>),
 
@@ -401,6 +419,39 @@ impl IntoGraphQLErrors for QueryPlannerError {
                 );
                 Ok(errors)
             }
+            QueryPlannerError::PlanLimitExceeded(PlanLimits {
+                fetches,
+                sequence_depth,
+                estimated_representations,
+            }) => {
+                let mut errors = Vec::new();
+                let mut build = |exceeded, code, message| {
+                    if exceeded {
+                        errors.push(
+                            Error::builder()
+                                .message(message)
+                                .extension_code(code)
+                                .build(),
+                        )
+                    }
+                };
+                build(
+                    fetches,
+                    "MAX_PLAN_FETCHES_LIMIT",
+                    "Maximum subgraph fetches limit exceeded in the query plan of this operation",
+                );
+                build(
+                    sequence_depth,
+                    "MAX_PLAN_SEQUENCE_DEPTH_LIMIT",
+                    "Maximum sequence depth limit exceeded in the query plan of this operation",
+                );
+                build(
+                    estimated_representations,
+                    "MAX_PLAN_ESTIMATED_REPRESENTATIONS_LIMIT",
+                    "Maximum estimated representations limit exceeded in the query plan of this operation",
+                );
+                Ok(errors)
+            }
             err => Err(err),
         }
     }
@@ -489,6 +540,12 @@ impl From<OperationLimits<bool>> for QueryPlannerError {
     }
 }
 
+impl From<PlanLimits<bool>> for QueryPlannerError {
+    fn from(error: PlanLimits<bool>) -> Self {
+        QueryPlannerError::PlanLimitExceeded(error)
+    }
+}
+
 impl From<QueryPlannerError> for Response {
     fn from(err: QueryPlannerError) -> Self {
         FetchError::from(err).to_response()
@@ -558,6 +615,50 @@ pub(crate) enum SchemaError {
     /// Api error(s): {0}
     #[from(ignore)]
     Api(String),
+    /// Unsupported feature: {0}
+    #[from(ignore)]
+    UnsupportedFeature(String),
+}
+
+impl SchemaError {
+    /// Returns the errors located in the schema source
+    pub(crate) fn located_errors(&self, sdl: &str) -> Vec<LocatedError> {
+        let hint = || {
+            Some(
+                "the supergraph schema must be composed from the subgraph schemas by Rover or GraphOS"
+                    .to_string(),
+            )
+        };
+        match self {
+            SchemaError::Parse(ParseErrors { errors }) => errors
+                .iter()
+                .filter_map(|diagnostic| {
+                    let location = diagnostic.line_column_range()?;
+                    Some(LocatedError::new(
+                        sdl,
+                        diagnostic.error.to_string(),
+                        location.start.line,
+                        location.start.column,
+                        hint(),
+                    ))
+                })
+                .collect(),
+            SchemaError::Validate(ValidationErrors { errors }) => errors
+                .iter()
+                .filter_map(|error| {
+                    let location = error.locations.first()?;
+                    Some(LocatedError::new(
+                        sdl,
+                        error.message.to_string(),
+                        location.line,
+                        location.column,
+                        hint(),
+                    ))
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
 }
 
 /// Collection of schema validation errors.
@@ -678,6 +779,83 @@ impl std::fmt::Display for ValidationErrors {
     }
 }
 
+/// Number of source lines displayed before the line of a located error
+const SNIPPET_CONTEXT_LINES: usize = 3;
+
+/// Error located in a source document, such as the configuration or the supergraph schema
+#[derive(Clone, Debug, Serialize)]
+pub struct LocatedError {
+    /// Error message
+    pub(crate) message: String,
+    /// Line of the error, starting at 1
+    pub(crate) line: usize,
+    /// Column of the error, starting at 1
+    pub(crate) column: usize,
+    /// How to fix the error
+    pub(crate) hint: Option<String>,
+    /// The lines of the source leading to the error and pointing at it, followed by the hint
+    #[serde(skip)]
+    pub(crate) report: String,
+}
+
+impl LocatedError {
+    /// Creates an error pointing at its line and column in the source
+    pub(crate) fn new(
+        source: &str,
+        message: String,
+        line: usize,
+        column: usize,
+        hint: Option<String>,
+    ) -> Self {
+        let lines = source.lines().collect::<Vec<_>>();
+        let end = line.clamp(1, lines.len().max(1));
+        let start = end.saturating_sub(SNIPPET_CONTEXT_LINES + 1);
+        let width = end.to_string().len();
+        let mut report = String::new();
+        for (index, text) in lines.iter().enumerate().take(end).skip(start) {
+            let _ = writeln!(report, "{:>width$} │ {text}", index + 1);
+        }
+        let _ = write!(
+            report,
+            "{:width$} · {}^----- {message}",
+            "",
+            " ".repeat(column.saturating_sub(1))
+        );
+        if let Some(hint) = &hint {
+            let _ = write!(report, "\n  help: {hint}");
+        }
+        Self {
+            message,
+            line,
+            column,
+            hint,
+            report,
+        }
+    }
+
+    /// Logs the error, its location and its hint are recorded as fields to be available in
+    /// structured logs
+    pub(crate) fn log(&self, document: &'static str) {
+        tracing::error!(
+            error.document = document,
+            error.message = %self.message,
+            error.line = self.line,
+            error.column = self.column,
+            error.hint = self.hint.as_deref(),
+            "invalid {document} at line {}, column {}\n\n{}\n",
+            self.line,
+            self.column,
+            self.report
+        );
+    }
+}
+
+impl std::fmt::Display for LocatedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.report)
+    }
+}
+
 /// Error during subgraph batch processing
 #[derive(Debug, Error, Display)]
 pub(crate) enum SubgraphBatchingError {
@@ -696,6 +874,26 @@ mod tests {
     use super::*;
     use crate::graphql;
 
+    #[test]
+    fn test_located_error_report() {
+        let sdl = "schema { query: Query }\ntype Query {\n  me: User\n}\n";
+        let error = LocatedError::new(
+            sdl,
+            "cannot find type `User` in this document".to_string(),
+            3,
+            7,
+            Some("define the `User` type".to_string()),
+        );
+        assert_eq!(
+            error.to_string(),
+            "1 │ schema { query: Query }\n\
+             2 │ type Query {\n\
+             3 │   me: User\n  \
+             ·       ^----- cannot find type `User` in this document\n  \
+             help: define the `User` type"
+        );
+    }
+
     #[test]
     fn test_into_graphql_error() {
         let error = FetchError::SubrequestHttpError {
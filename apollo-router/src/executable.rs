@@ -114,6 +114,16 @@ extern "C" fn drop_ad_hoc_profiler() {
 enum Commands {
     /// Configuration subcommands.
     Config(ConfigSubcommandArgs),
+
+    /// Execute sample operations through the router pipeline, with mocked subgraph responses, and report the results. Uses the --supergraph and --config options.
+    Check(CheckArgs),
+}
+
+#[derive(Args, Debug)]
+struct CheckArgs {
+    /// The location of the YAML file listing the operations to execute and the subgraph responses.
+    #[clap(value_parser, env = "APOLLO_ROUTER_CHECK_FIXTURES_PATH")]
+    fixtures_path: PathBuf,
 }
 
 #[derive(Args, Debug)]
@@ -199,6 +209,16 @@ pub struct Opt {
     #[clap(env = "APOLLO_ROUTER_SUPERGRAPH_URLS", value_delimiter = ',')]
     supergraph_urls: Option<Vec<Url>>,
 
+    /// Location of a YAML file listing local subgraphs, composed into the supergraph in development mode. Uses the format of `rover supergraph compose`, with a routing URL and a schema file for each subgraph.
+    #[clap(
+        long = "dev-subgraphs",
+        value_parser,
+        env = "APOLLO_ROUTER_DEV_SUBGRAPHS_PATH",
+        requires = "dev",
+        conflicts_with_all = ["supergraph_path", "supergraph_urls"]
+    )]
+    dev_subgraphs_path: Option<PathBuf>,
+
     /// Prints the configuration schema.
     #[clap(long, action(ArgAction::SetTrue), hide(true))]
     schema: bool,
@@ -445,6 +465,24 @@ impl Executable {
                 Discussed::new().print_preview();
                 Ok(())
             }
+            Some(Commands::Check(CheckArgs { fixtures_path })) => {
+                match crate::check::run(
+                    opt.config_path.as_deref(),
+                    opt.supergraph_path.as_deref(),
+                    fixtures_path,
+                )
+                .await
+                {
+                    Ok(report) => {
+                        println!("{report}");
+                        match report.failed() {
+                            0 => Ok(()),
+                            failed => Err(anyhow!("{failed} operations failed the check")),
+                        }
+                    }
+                    Err(e) => Err(anyhow!("router check failed: {e}")),
+                }
+            }
             None => Self::inner_start(shutdown, schema, config, license, opt).await,
         };
 
@@ -508,6 +546,30 @@ impl Executable {
 
         let apollo_router_msg = format!("Apollo Router v{} // (c) Apollo Graph, Inc. // Licensed as ELv2 (https://go.apollo.dev/elv2)", std::env!("CARGO_PKG_VERSION"));
 
+        // In development mode, local subgraphs are composed into the supergraph
+        let schema = match (schema, &opt.dev_subgraphs_path) {
+            (Some(_), Some(_)) => {
+                return Err(anyhow!(
+                    "--dev-subgraphs and APOLLO_ROUTER_DEV_SUBGRAPHS_PATH cannot be used when a custom schema source is in use"
+                ))
+            }
+            (_, Some(subgraphs_path)) => {
+                tracing::info!("{apollo_router_msg}");
+                tracing::info!("{apollo_telemetry_msg}");
+
+                let subgraphs_path = if subgraphs_path.is_relative() {
+                    current_directory.join(subgraphs_path)
+                } else {
+                    subgraphs_path.clone()
+                };
+                Some(SchemaSource::Subgraphs {
+                    path: subgraphs_path,
+                    watch: opt.hot_reload,
+                })
+            }
+            (schema, None) => schema,
+        };
+
         // Schema source will be in order of precedence:
         // 1. Cli --supergraph
         // 2. Env APOLLO_ROUTER_SUPERGRAPH_PATH
@@ -76,6 +76,15 @@ pub struct Request {
         deserialize_with = "deserialize_null_default"
     )]
     pub extensions: Object,
+
+    /// The (optional) ID of a [persisted document], sent instead of the `query`
+    /// by clients like Relay or urql.
+    ///
+    /// The `doc_id` name used by Relay is also accepted when deserializing.
+    ///
+    /// [persisted document]: https://github.com/graphql/graphql-over-http/blob/main/rfcs/PersistedDocuments.md
+    #[serde(skip_serializing_if = "Option::is_none", default, alias = "doc_id")]
+    pub document_id: Option<String>,
 }
 
 // NOTE: this deserialize helper is used to transform `null` to Default::default()
@@ -120,12 +129,14 @@ impl Request {
         // Skip the `Object` type alias in order to use buildstructor’s map special-casing
         variables: JsonMap<ByteString, Value>,
         extensions: JsonMap<ByteString, Value>,
+        document_id: Option<String>,
     ) -> Self {
         Self {
             query,
             operation_name,
             variables,
             extensions,
+            document_id,
         }
     }
 
@@ -145,12 +156,14 @@ impl Request {
         // Skip the `Object` type alias in order to use buildstructor’s map special-casing
         variables: JsonMap<ByteString, Value>,
         extensions: JsonMap<ByteString, Value>,
+        document_id: Option<String>,
     ) -> Self {
         Self {
             query,
             operation_name,
             variables,
             extensions,
+            document_id,
         }
     }
 
@@ -261,11 +274,19 @@ impl Request {
         let variables: Object = get_from_urlencoded_value(value, "variables")?.unwrap_or_default();
         let extensions: Object =
             get_from_urlencoded_value(value, "extensions")?.unwrap_or_default();
+        let document_id = ["documentId", "doc_id"].iter().find_map(|key| {
+            if let Some(serde_json::Value::String(document_id)) = value.get(key) {
+                Some(document_id.clone())
+            } else {
+                None
+            }
+        });
 
         let request_builder = Self::builder()
             .variables(variables)
             .and_operation_name(operation_name)
-            .extensions(extensions);
+            .extensions(extensions)
+            .and_document_id(document_id);
 
         let request = if let Some(query_str) = query {
             request_builder.query(query_str).build()
@@ -317,11 +338,19 @@ impl<'data, 'de> DeserializeSeed<'de> for RequestFromBytesSeed<'data> {
             OperationName,
             Variables,
             Extensions,
+            #[serde(alias = "doc_id")]
+            DocumentId,
             #[serde(other)]
             Other,
         }
 
-        const FIELDS: &[&str] = &["query", "operationName", "variables", "extensions"];
+        const FIELDS: &[&str] = &[
+            "query",
+            "operationName",
+            "variables",
+            "extensions",
+            "documentId",
+        ];
 
         struct RequestVisitor<'data>(&'data Bytes);
 
@@ -340,6 +369,7 @@ impl<'data, 'de> DeserializeSeed<'de> for RequestFromBytesSeed<'data> {
                 let mut operation_name = None;
                 let mut variables = None;
                 let mut extensions = None;
+                let mut document_id = None;
                 while let Some(key) = map.next_key()? {
                     match key {
                         Field::Query => {
@@ -370,6 +400,12 @@ impl<'data, 'de> DeserializeSeed<'de> for RequestFromBytesSeed<'data> {
                             let value = map.next_value_seed(seed)?;
                             extensions = Some(as_optional_object(value)?);
                         }
+                        Field::DocumentId => {
+                            if document_id.is_some() {
+                                return Err(Error::duplicate_field("documentId"));
+                            }
+                            document_id = Some(map.next_value()?);
+                        }
                         Field::Other => {
                             let _: serde::de::IgnoredAny = map.next_value()?;
                         }
@@ -380,6 +416,7 @@ impl<'data, 'de> DeserializeSeed<'de> for RequestFromBytesSeed<'data> {
                     operation_name: operation_name.unwrap_or_default(),
                     variables: variables.unwrap_or_default(),
                     extensions: extensions.unwrap_or_default(),
+                    document_id: document_id.unwrap_or_default(),
                 })
             }
         }
@@ -534,6 +571,32 @@ mod tests {
         insta::assert_yaml_snapshot!(expected_result);
     }
 
+    #[test]
+    fn test_document_id() {
+        let expected = Request::builder()
+            .document_id("5678")
+            .variables(bjson!({ "arg1": "me" }).as_object().unwrap().clone())
+            .build();
+        let result = check_deserialization(json!(
+        {
+          "documentId": "5678",
+          "variables": { "arg1": "me" }
+        }));
+        assert_eq!(result, expected);
+
+        // Relay names it `doc_id`
+        let result = check_deserialization(json!(
+        {
+          "doc_id": "5678",
+          "variables": { "arg1": "me" }
+        }));
+        assert_eq!(result, expected);
+
+        let query_string = "documentId=5678&variables=%7B%22arg1%22%3A%22me%22%7D".to_string();
+        let result = Request::from_urlencoded_query(query_string).unwrap();
+        assert_eq!(result, expected);
+    }
+
     fn check_deserialization(request: serde_json::Value) -> Request {
         // check that deserialize_from_bytes agrees with Deserialize impl
 
@@ -56,6 +56,7 @@ mod apollo_studio_interop;
 pub(crate) mod axum_factory;
 mod batching;
 mod cache;
+mod check;
 mod configuration;
 mod context;
 mod error;
@@ -77,10 +78,12 @@ mod router_factory;
 pub mod services;
 pub(crate) mod spec;
 mod state_machine;
+mod status;
 pub mod test_harness;
 pub mod tracer;
 mod uplink;
 
+pub use crate::apollo_studio_interop::OperationSignatures;
 pub use crate::axum_factory::unsupported_set_axum_router_callback;
 pub use crate::configuration::Configuration;
 pub use crate::configuration::ListenAddr;
@@ -17,6 +17,7 @@
 pub mod serde;
 #[macro_use]
 pub mod test;
+pub(crate) mod timing;
 
 use std::any::TypeId;
 use std::collections::HashMap;
@@ -0,0 +1,296 @@
+//! Time spent in each plugin
+//!
+//! The services of a plugin are wrapped to measure the time between the plugin receiving a
+//! request and returning its response, minus the time spent in the service the plugin wraps.
+//! The difference is the contribution of the plugin to the request latency, recorded per plugin
+//! and per stage in the `apollo.router.plugin.duration` histogram.
+
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+use std::time::Duration;
+use std::time::Instant;
+
+use futures::future::BoxFuture;
+use multimap::MultiMap;
+use tower::BoxError;
+use tower::Service;
+use tower::ServiceExt;
+
+use super::DynPlugin;
+use crate::router_factory::Endpoint;
+use crate::services::execution;
+use crate::services::router;
+use crate::services::subgraph;
+use crate::services::supergraph;
+use crate::ListenAddr;
+
+/// Time spent in the service wrapped by a plugin, in nanoseconds. Passed from the plugin service
+/// to the wrapped service in the extensions of the request
+#[derive(Clone, Default)]
+struct InnerDuration(Arc<AtomicU64>);
+
+trait StageRequest: Send + 'static {
+    fn http_extensions_mut(&mut self) -> &mut http::Extensions;
+}
+
+impl StageRequest for router::Request {
+    fn http_extensions_mut(&mut self) -> &mut http::Extensions {
+        self.router_request.extensions_mut()
+    }
+}
+
+impl StageRequest for supergraph::Request {
+    fn http_extensions_mut(&mut self) -> &mut http::Extensions {
+        self.supergraph_request.extensions_mut()
+    }
+}
+
+impl StageRequest for execution::Request {
+    fn http_extensions_mut(&mut self) -> &mut http::Extensions {
+        self.supergraph_request.extensions_mut()
+    }
+}
+
+impl StageRequest for subgraph::Request {
+    fn http_extensions_mut(&mut self) -> &mut http::Extensions {
+        self.subgraph_request.extensions_mut()
+    }
+}
+
+/// Service returned by a plugin, recording the time spent in the plugin
+struct PluginDuration<S> {
+    inner: S,
+    plugin: Arc<String>,
+    stage: &'static str,
+}
+
+impl<S, Request> Service<Request> for PluginDuration<S>
+where
+    S: Service<Request, Error = BoxError>,
+    S::Future: Send + 'static,
+    S::Response: Send + 'static,
+    Request: StageRequest,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request) -> Self::Future {
+        let inner_duration = InnerDuration::default();
+        request.http_extensions_mut().insert(inner_duration.clone());
+        let plugin = self.plugin.clone();
+        let stage = self.stage;
+        let start = Instant::now();
+        let future = self.inner.call(request);
+
+        Box::pin(async move {
+            let result = future.await;
+            let inner = Duration::from_nanos(inner_duration.0.load(Ordering::Relaxed));
+            f64_histogram!(
+                "apollo.router.plugin.duration",
+                "Time spent in a plugin, excluding the time spent in the service it wraps",
+                start.elapsed().saturating_sub(inner).as_secs_f64(),
+                "plugin" = plugin.to_string(),
+                "stage" = stage
+            );
+            result
+        })
+    }
+}
+
+/// Service wrapped by a plugin, measuring the time spent in it
+struct WrappedDuration<S> {
+    inner: S,
+}
+
+impl<S, Request> Service<Request> for WrappedDuration<S>
+where
+    S: Service<Request, Error = BoxError>,
+    S::Future: Send + 'static,
+    S::Response: Send + 'static,
+    Request: StageRequest,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request) -> Self::Future {
+        let inner_duration = request
+            .http_extensions_mut()
+            .get::<InnerDuration>()
+            .cloned();
+        let start = Instant::now();
+        let future = self.inner.call(request);
+
+        Box::pin(async move {
+            let result = future.await;
+            if let Some(inner_duration) = inner_duration {
+                let elapsed = u64::try_from(start.elapsed().as_nanos()).unwrap_or(u64::MAX);
+                inner_duration.0.fetch_add(elapsed, Ordering::Relaxed);
+            }
+            result
+        })
+    }
+}
+
+/// Plugin recording the time spent in the plugin it wraps
+pub(crate) struct TimedPlugin {
+    name: Arc<String>,
+    plugin: Box<dyn DynPlugin>,
+}
+
+impl TimedPlugin {
+    pub(crate) fn new(name: String, plugin: Box<dyn DynPlugin>) -> Self {
+        Self {
+            name: Arc::new(name),
+            plugin,
+        }
+    }
+
+    fn timed<S>(&self, stage: &'static str, service: S) -> PluginDuration<S> {
+        PluginDuration {
+            inner: service,
+            plugin: self.name.clone(),
+            stage,
+        }
+    }
+}
+
+impl DynPlugin for TimedPlugin {
+    fn router_service(&self, service: router::BoxService) -> router::BoxService {
+        let service = WrappedDuration { inner: service }.boxed();
+        self.timed("router", self.plugin.router_service(service))
+            .boxed()
+    }
+
+    fn supergraph_service(&self, service: supergraph::BoxService) -> supergraph::BoxService {
+        let service = WrappedDuration { inner: service }.boxed();
+        self.timed("supergraph", self.plugin.supergraph_service(service))
+            .boxed()
+    }
+
+    fn execution_service(&self, service: execution::BoxService) -> execution::BoxService {
+        let service = WrappedDuration { inner: service }.boxed();
+        self.timed("execution", self.plugin.execution_service(service))
+            .boxed()
+    }
+
+    fn subgraph_service(
+        &self,
+        subgraph_name: &str,
+        service: subgraph::BoxService,
+    ) -> subgraph::BoxService {
+        let service = WrappedDuration { inner: service }.boxed();
+        self.timed(
+            "subgraph",
+            self.plugin.subgraph_service(subgraph_name, service),
+        )
+        .boxed()
+    }
+
+    fn http_client_service(
+        &self,
+        subgraph_name: &str,
+        service: crate::services::http::BoxService,
+    ) -> crate::services::http::BoxService {
+        self.plugin.http_client_service(subgraph_name, service)
+    }
+
+    fn name(&self) -> &'static str {
+        self.plugin.name()
+    }
+
+    fn web_endpoints(&self) -> MultiMap<ListenAddr, Endpoint> {
+        self.plugin.web_endpoints()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self.plugin.as_any()
+    }
+
+    #[cfg(test)]
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self.plugin.as_any_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::sdk::metrics::data::Histogram;
+
+    use super::*;
+    use crate::metrics::FutureMetricsExt;
+    use crate::plugin::test::MockSupergraphService;
+
+    struct Sleep;
+
+    #[async_trait::async_trait]
+    impl crate::plugin::Plugin for Sleep {
+        type Config = ();
+
+        async fn new(_init: crate::plugin::PluginInit<()>) -> Result<Self, BoxError> {
+            Ok(Sleep)
+        }
+
+        fn supergraph_service(&self, service: supergraph::BoxService) -> supergraph::BoxService {
+            service
+                .map_future(|future| async move {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    future.await
+                })
+                .boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn it_records_the_time_spent_in_plugins() {
+        async {
+            let plugin = TimedPlugin::new("sleep".to_string(), Box::new(Sleep));
+
+            let mut mock_service = MockSupergraphService::new();
+            mock_service.expect_call().times(1).returning(|_| {
+                // time spent in the wrapped service is not attributed to the plugin
+                std::thread::sleep(Duration::from_millis(100));
+                Ok(supergraph::Response::fake_builder().build().unwrap())
+            });
+
+            plugin
+                .supergraph_service(mock_service.boxed())
+                .oneshot(supergraph::Request::fake_builder().build().unwrap())
+                .await
+                .unwrap();
+
+            assert_histogram_exists!(
+                "apollo.router.plugin.duration",
+                f64,
+                "plugin" = "sleep",
+                "stage" = "supergraph"
+            );
+            let metrics = crate::metrics::collect_metrics();
+            let histogram = metrics
+                .find("apollo.router.plugin.duration")
+                .and_then(|metric| metric.data.as_any().downcast_ref::<Histogram<f64>>())
+                .expect("the plugin duration is recorded");
+            let sum: f64 = histogram
+                .data_points
+                .iter()
+                .map(|data_point| data_point.sum)
+                .sum();
+            // the 20ms of the plugin are recorded, but not the 100ms of the wrapped service
+            assert!((0.02..0.1).contains(&sum), "{sum}");
+        }
+        .with_metrics()
+        .await;
+    }
+}
@@ -0,0 +1,203 @@
+//! Read-only admin API exposing the live status of the router
+
+use http::header::AUTHORIZATION;
+use http::header::CONTENT_TYPE;
+use http::HeaderValue;
+use http::Method;
+use http::StatusCode;
+use multimap::MultiMap;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde::Serialize;
+use subtle::ConstantTimeEq;
+use tower::service_fn;
+use tower::BoxError;
+use tower::ServiceExt;
+
+use crate::plugin::Plugin;
+use crate::plugin::PluginInit;
+use crate::register_plugin;
+use crate::services::router;
+use crate::status;
+use crate::Endpoint;
+use crate::ListenAddr;
+
+/// Read-only admin API exposing the schema, configuration, uplink, license, plugins and cache status
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct AdminApiConfig {
+    /// Enable the admin API (default: false)
+    #[serde(default)]
+    enabled: bool,
+    /// Listen address of the admin API (default: 127.0.0.1:8088)
+    #[serde(default = "default_listen")]
+    listen: ListenAddr,
+    /// Path of the status endpoint (default: /admin/status)
+    #[serde(default = "default_path")]
+    path: String,
+    /// Value expected in the `authorization` header of requests. Requests are not authenticated
+    /// if it is not set, which is only allowed when listening on a loopback address
+    shared_key: Option<String>,
+}
+
+fn default_listen() -> ListenAddr {
+    ListenAddr::SocketAddr("127.0.0.1:8088".parse().expect("valid ListenAddr"))
+}
+
+fn default_path() -> String {
+    String::from("/admin/status")
+}
+
+struct AdminApi {
+    config: AdminApiConfig,
+}
+
+#[async_trait::async_trait]
+impl Plugin for AdminApi {
+    type Config = AdminApiConfig;
+
+    async fn new(init: PluginInit<Self::Config>) -> Result<Self, BoxError> {
+        // the status exposes the configuration and the schema of the router
+        if init.config.enabled && init.config.shared_key.is_none() {
+            if let ListenAddr::SocketAddr(address) = &init.config.listen {
+                if !address.ip().is_loopback() {
+                    return Err(format!(
+                        "the admin API requires a shared_key to listen on {address}, which is not a loopback address"
+                    )
+                    .into());
+                }
+            }
+        }
+        Ok(Self {
+            config: init.config,
+        })
+    }
+
+    fn web_endpoints(&self) -> MultiMap<ListenAddr, Endpoint> {
+        let mut map = MultiMap::new();
+        if !self.config.enabled {
+            return map;
+        }
+
+        tracing::info!(
+            "Admin API exposed at {}{}",
+            self.config.listen,
+            self.config.path
+        );
+        let shared_key = self.config.shared_key.clone();
+        map.insert(
+            self.config.listen.clone(),
+            Endpoint::from_router_service(
+                self.config.path.clone(),
+                service_fn(move |request: router::Request| {
+                    let response = handle(shared_key.as_deref(), request);
+                    async move { response }
+                })
+                .boxed(),
+            ),
+        );
+        map
+    }
+}
+
+fn handle(
+    shared_key: Option<&str>,
+    request: router::Request,
+) -> Result<router::Response, BoxError> {
+    let authorized = match shared_key {
+        None => true,
+        Some(shared_key) => request
+            .router_request
+            .headers()
+            .get(AUTHORIZATION)
+            // constant-time comparison, to not leak the key through response times
+            .map(|value| bool::from(value.as_bytes().ct_eq(shared_key.as_bytes())))
+            .unwrap_or_default(),
+    };
+    let (status_code, body) = if !authorized {
+        (
+            StatusCode::UNAUTHORIZED,
+            "Invalid authorization header".into(),
+        )
+    } else if request.router_request.method() != Method::GET {
+        (StatusCode::METHOD_NOT_ALLOWED, String::new())
+    } else {
+        (StatusCode::OK, serde_json::to_string(&status::report())?)
+    };
+
+    let mut response = http::Response::builder()
+        .status(status_code)
+        .body(body.into())
+        .map_err(BoxError::from)?;
+    if status_code == StatusCode::OK {
+        response
+            .headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    }
+    Ok(router::Response {
+        response,
+        context: request.context,
+    })
+}
+
+register_plugin!("apollo", "admin_api", AdminApi);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Context;
+
+    async fn get(shared_key: Option<&str>, authorization: Option<&str>) -> (StatusCode, String) {
+        let mut request = http::Request::get("http://localhost/admin/status");
+        if let Some(authorization) = authorization {
+            request = request.header(AUTHORIZATION, authorization);
+        }
+        let request = (request.body(router::Body::empty()).unwrap(), Context::new()).into();
+        let response = handle(shared_key, request).unwrap().response;
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn it_reports_the_status() {
+        status::record_plugins(vec!["apollo.admin_api".to_string()]);
+        let (status, body) = get(None, None).await;
+        assert_eq!(status, StatusCode::OK);
+        let report: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert!(report["plugins"].is_array());
+        assert!(report["uplink"].is_object());
+        assert!(report["caches"].is_object());
+        assert!(report["unsupported_features"].is_array());
+    }
+
+    #[tokio::test]
+    async fn it_requires_a_shared_key_outside_of_loopback() {
+        let config = |listen: &str, shared_key: Option<&str>| {
+            serde_json::from_value::<AdminApiConfig>(serde_json::json!({
+                "enabled": true,
+                "listen": listen,
+                "shared_key": shared_key,
+            }))
+            .unwrap()
+        };
+        let new = |config: AdminApiConfig| {
+            AdminApi::new(PluginInit::fake_builder().config(config).build())
+        };
+
+        assert!(new(config("127.0.0.1:8088", None)).await.is_ok());
+        assert!(new(config("[::1]:8088", None)).await.is_ok());
+        assert!(new(config("0.0.0.0:8088", None)).await.is_err());
+        assert!(new(config("0.0.0.0:8088", Some("secret"))).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn it_checks_the_shared_key() {
+        let (status, _) = get(Some("secret"), None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = get(Some("secret"), Some("wrong")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = get(Some("secret"), Some("secret")).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
@@ -1667,6 +1667,7 @@ mod tests {
                 multipart_subscription: true,
                 json: true,
                 wildcard: true,
+                ..Default::default()
             })
         });
         let request = supergraph::Request::fake_builder()
@@ -0,0 +1,238 @@
+//! Error budget based subgraph degradation
+//!
+//! When the error rate of a subgraph crosses a configured threshold, the entity cache serves
+//! expired entries it still holds instead of calling the subgraph for them, until the subgraph
+//! recovers.
+
+use std::time::Duration;
+use std::time::Instant;
+
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde::Serialize;
+use tower::BoxError;
+
+use crate::services::subgraph;
+use crate::Context;
+
+/// Context key listing the subgraphs for which stale entity cache data was served
+pub(crate) const CONTEXT_STALE_SUBGRAPHS: &str = "apollo_entity_cache::stale_subgraphs";
+/// Response extension listing the subgraphs for which stale entity cache data was served
+pub(crate) const STALE_SUBGRAPHS_EXTENSION: &str = "staleEntityCacheSubgraphs";
+
+const DEFAULT_MIN_REQUESTS: u64 = 10;
+const DEFAULT_WINDOW: Duration = Duration::from_secs(10);
+
+/// Serve stale entity cache data when the error rate of the subgraph is too high
+#[derive(Clone, Debug, JsonSchema, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub(crate) struct Degradation {
+    /// error rate, between 0 and 1, above which the subgraph is considered degraded
+    pub(crate) error_rate_threshold: f64,
+
+    /// minimum number of requests in a window before the error rate is evaluated (default: 10)
+    pub(crate) min_requests: Option<u64>,
+
+    /// duration of the window over which the error rate is computed (default: 10s)
+    #[serde(deserialize_with = "humantime_serde::deserialize", default)]
+    #[schemars(with = "String", default)]
+    pub(crate) window: Option<Duration>,
+
+    /// how long entries are kept in the cache after they expired, so they can be served while
+    /// the subgraph is degraded
+    #[serde(deserialize_with = "humantime_serde::deserialize")]
+    #[schemars(with = "String")]
+    pub(crate) stale_ttl: Duration,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum HealthState {
+    Healthy,
+    Degraded,
+}
+
+impl HealthState {
+    fn as_str(&self) -> &'static str {
+        match self {
+            HealthState::Healthy => "healthy",
+            HealthState::Degraded => "degraded",
+        }
+    }
+}
+
+struct HealthWindow {
+    started: Instant,
+    requests: u64,
+    errors: u64,
+    state: HealthState,
+}
+
+/// Tracks the error rate of a subgraph over a sliding window
+pub(crate) struct SubgraphHealth {
+    subgraph_name: String,
+    error_rate_threshold: f64,
+    min_requests: u64,
+    window_duration: Duration,
+    window: Mutex<HealthWindow>,
+}
+
+impl SubgraphHealth {
+    pub(crate) fn new(subgraph_name: String, config: &Degradation) -> Self {
+        Self {
+            subgraph_name,
+            error_rate_threshold: config.error_rate_threshold,
+            min_requests: config.min_requests.unwrap_or(DEFAULT_MIN_REQUESTS),
+            window_duration: config.window.unwrap_or(DEFAULT_WINDOW),
+            window: Mutex::new(HealthWindow {
+                started: Instant::now(),
+                requests: 0,
+                errors: 0,
+                state: HealthState::Healthy,
+            }),
+        }
+    }
+
+    pub(crate) fn is_degraded(&self) -> bool {
+        self.window.lock().state == HealthState::Degraded
+    }
+
+    pub(crate) fn record(&self, result: &Result<subgraph::Response, BoxError>) {
+        let is_error = match result {
+            Ok(response) => {
+                response.response.status().is_server_error()
+                    || !response.response.body().errors.is_empty()
+            }
+            Err(_) => true,
+        };
+        self.record_at(is_error, Instant::now());
+    }
+
+    fn record_at(&self, is_error: bool, now: Instant) {
+        let mut window = self.window.lock();
+
+        // the subgraph is degraded as soon as the threshold is crossed, but it only recovers at
+        // the end of a window, to avoid flapping between states
+        if now.duration_since(window.started) >= self.window_duration {
+            let next = if window.requests >= self.min_requests
+                && error_rate(&window) >= self.error_rate_threshold
+            {
+                HealthState::Degraded
+            } else {
+                HealthState::Healthy
+            };
+            self.transition(&mut window, next);
+            window.started = now;
+            window.requests = 0;
+            window.errors = 0;
+        }
+
+        window.requests += 1;
+        if is_error {
+            window.errors += 1;
+        }
+
+        if window.state == HealthState::Healthy
+            && window.requests >= self.min_requests
+            && error_rate(&window) >= self.error_rate_threshold
+        {
+            self.transition(&mut window, HealthState::Degraded);
+        }
+    }
+
+    fn transition(&self, window: &mut HealthWindow, next: HealthState) {
+        if window.state == next {
+            return;
+        }
+
+        match next {
+            HealthState::Degraded => tracing::warn!(
+                subgraph = %self.subgraph_name,
+                error_rate = error_rate(window),
+                "subgraph error rate crossed the configured threshold, serving stale entity cache data"
+            ),
+            HealthState::Healthy => tracing::info!(
+                subgraph = %self.subgraph_name,
+                error_rate = error_rate(window),
+                "subgraph recovered, entity cache stopped serving stale data"
+            ),
+        }
+        u64_counter!(
+            "apollo.router.operations.entity.degradation",
+            "Entity cache subgraph degradation state transitions",
+            1u64,
+            "subgraph.name" = self.subgraph_name.clone(),
+            "state" = next.as_str()
+        );
+
+        window.state = next;
+    }
+}
+
+fn error_rate(window: &HealthWindow) -> f64 {
+    if window.requests == 0 {
+        0.0
+    } else {
+        window.errors as f64 / window.requests as f64
+    }
+}
+
+/// Records that stale data was served for this subgraph, to be reported in the response extensions
+pub(crate) fn mark_stale(context: &Context, subgraph_name: &str) {
+    let _ = context.upsert(CONTEXT_STALE_SUBGRAPHS, |mut subgraphs: Vec<String>| {
+        if !subgraphs.iter().any(|s| s == subgraph_name) {
+            subgraphs.push(subgraph_name.to_string());
+        }
+        subgraphs
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn health() -> SubgraphHealth {
+        SubgraphHealth::new(
+            "products".to_string(),
+            &Degradation {
+                error_rate_threshold: 0.5,
+                min_requests: Some(4),
+                window: Some(Duration::from_secs(1)),
+                stale_ttl: Duration::from_secs(60),
+            },
+        )
+    }
+
+    #[test]
+    fn it_degrades_when_the_threshold_is_crossed() {
+        let health = health();
+        let now = Instant::now();
+        health.record_at(false, now);
+        health.record_at(true, now);
+        health.record_at(true, now);
+        assert!(!health.is_degraded());
+        health.record_at(false, now);
+        assert!(health.is_degraded());
+    }
+
+    #[test]
+    fn it_recovers_at_the_end_of_a_healthy_window() {
+        let health = health();
+        let now = Instant::now();
+        for _ in 0..4 {
+            health.record_at(true, now);
+        }
+        assert!(health.is_degraded());
+
+        // the window with errors closes, the subgraph stays degraded
+        let next = now + Duration::from_secs(1);
+        for _ in 0..4 {
+            health.record_at(false, next);
+        }
+        assert!(health.is_degraded());
+
+        // the window without errors closes, the subgraph recovers
+        health.record_at(false, next + Duration::from_secs(1));
+        assert!(!health.is_degraded());
+    }
+}
@@ -8,6 +8,7 @@ use std::time::Duration;
 use http::header;
 use http::header::CACHE_CONTROL;
 use multimap::MultiMap;
+use parking_lot::Mutex;
 use schemars::JsonSchema;
 use serde::Deserialize;
 use serde::Serialize;
@@ -25,6 +26,11 @@ use tracing::Instrument;
 use tracing::Level;
 
 use super::cache_control::CacheControl;
+use super::degradation::mark_stale;
+use super::degradation::Degradation;
+use super::degradation::SubgraphHealth;
+use super::degradation::CONTEXT_STALE_SUBGRAPHS;
+use super::degradation::STALE_SUBGRAPHS_EXTENSION;
 use super::invalidation::Invalidation;
 use super::invalidation::InvalidationOrigin;
 use super::invalidation_endpoint::InvalidationEndpointConfig;
@@ -33,10 +39,8 @@ use super::invalidation_endpoint::SubgraphInvalidationConfig;
 use super::metrics::CacheMetricContextKey;
 use super::metrics::CacheMetricsService;
 use crate::batching::BatchQuery;
-use crate::cache::redis::RedisCacheStorage;
-use crate::cache::redis::RedisKey;
-use crate::cache::redis::RedisValue;
-use crate::cache::storage::ValueType;
+use crate::cache::store;
+use crate::cache::store::Store;
 use crate::configuration::subgraph::SubgraphConfiguration;
 use crate::configuration::RedisCache;
 use crate::error::FetchError;
@@ -45,6 +49,7 @@ use crate::graphql::Error;
 use crate::json_ext::Object;
 use crate::json_ext::Path;
 use crate::json_ext::PathElement;
+use crate::layers::ServiceBuilderExt;
 use crate::plugin::Plugin;
 use crate::plugin::PluginInit;
 use crate::plugins::authorization::CacheKeyMetadata;
@@ -74,16 +79,18 @@ pub(crate) struct EntityCache {
     enabled: bool,
     metrics: Metrics,
     private_queries: Arc<RwLock<HashSet<String>>>,
+    /// health of the subgraphs configured for degradation, kept across requests
+    health: Arc<Mutex<HashMap<String, Arc<SubgraphHealth>>>>,
     pub(crate) invalidation: Invalidation,
 }
 
 pub(crate) struct Storage {
-    all: Option<RedisCacheStorage>,
-    subgraphs: HashMap<String, RedisCacheStorage>,
+    all: Option<Arc<dyn Store>>,
+    subgraphs: HashMap<String, Arc<dyn Store>>,
 }
 
 impl Storage {
-    pub(crate) fn get(&self, subgraph: &str) -> Option<&RedisCacheStorage> {
+    pub(crate) fn get(&self, subgraph: &str) -> Option<&Arc<dyn Store>> {
         self.subgraphs.get(subgraph).or(self.all.as_ref())
     }
 }
@@ -125,6 +132,9 @@ pub(crate) struct Subgraph {
 
     /// Invalidation configuration
     pub(crate) invalidation: Option<SubgraphInvalidationConfig>,
+
+    /// Serve stale entries when the error rate of the subgraph crosses a threshold
+    pub(crate) degradation: Option<Degradation>,
 }
 
 impl Default for Subgraph {
@@ -135,6 +145,7 @@ impl Default for Subgraph {
             ttl: Default::default(),
             private_id: Default::default(),
             invalidation: Default::default(),
+            degradation: Default::default(),
         }
     }
 }
@@ -192,46 +203,19 @@ impl Plugin for EntityCache {
 
         if let Some(redis) = &init.config.subgraph.all.redis {
             let mut redis_config = redis.clone();
-            let required_to_start = redis_config.required_to_start;
             // we need to explicitely disable TTL reset because it is managed directly by this plugin
             redis_config.reset_ttl = false;
-            all = match RedisCacheStorage::new(redis_config).await {
-                Ok(storage) => Some(storage),
-                Err(e) => {
-                    tracing::error!(
-                        cache = "entity",
-                        e,
-                        "could not open connection to Redis for caching",
-                    );
-                    if required_to_start {
-                        return Err(e);
-                    }
-                    None
-                }
-            };
+            all = store::from_configuration(Some(redis_config), None, "entity").await?;
         }
         let mut subgraph_storages = HashMap::new();
         for (subgraph, config) in &init.config.subgraph.subgraphs {
             if let Some(redis) = &config.redis {
-                let required_to_start = redis.required_to_start;
                 // we need to explicitely disable TTL reset because it is managed directly by this plugin
                 let mut redis_config = redis.clone();
                 redis_config.reset_ttl = false;
-                let storage = match RedisCacheStorage::new(redis_config).await {
-                    Ok(storage) => Some(storage),
-                    Err(e) => {
-                        tracing::error!(
-                            cache = "entity",
-                            e,
-                            "could not open connection to Redis for caching",
-                        );
-                        if required_to_start {
-                            return Err(e);
-                        }
-                        None
-                    }
-                };
-                if let Some(storage) = storage {
+                if let Some(storage) =
+                    store::from_configuration(Some(redis_config), None, "entity").await?
+                {
                     subgraph_storages.insert(subgraph.clone(), storage);
                 }
             }
@@ -288,6 +272,7 @@ impl Plugin for EntityCache {
             subgraphs: Arc::new(init.config.subgraph),
             metrics: init.config.metrics,
             private_queries: Arc::new(RwLock::new(HashSet::new())),
+            health: Default::default(),
             invalidation,
         })
     }
@@ -305,6 +290,16 @@ impl Plugin for EntityCache {
 
                 response
             })
+            .map_first_graphql_response(|context, parts, mut response| {
+                if let Ok(Some(subgraphs)) = context.get::<_, Vec<String>>(CONTEXT_STALE_SUBGRAPHS)
+                {
+                    response
+                        .extensions
+                        .insert(STALE_SUBGRAPHS_EXTENSION, subgraphs.into());
+                }
+
+                (parts, response)
+            })
             .service(service)
             .boxed()
     }
@@ -343,6 +338,7 @@ impl Plugin for EntityCache {
         let subgraph_enabled =
             self.enabled && (self.subgraphs.all.enabled || self.subgraphs.get(name).enabled);
         let private_id = self.subgraphs.get(name).private_id.clone();
+        let degradation = self.subgraphs.get(name).degradation.clone();
 
         let name = name.to_string();
 
@@ -356,6 +352,25 @@ impl Plugin for EntityCache {
         }
 
         if subgraph_enabled {
+            // the subgraph services are created for each request, the error rate is tracked
+            // by the plugin
+            let health = degradation.as_ref().map(|degradation| {
+                self.health
+                    .lock()
+                    .entry(name.clone())
+                    .or_insert_with(|| Arc::new(SubgraphHealth::new(name.clone(), degradation)))
+                    .clone()
+            });
+            if let Some(health) = health.clone() {
+                service = ServiceBuilder::new()
+                    .map_result(move |result: Result<subgraph::Response, BoxError>| {
+                        health.record(&result);
+                        result
+                    })
+                    .service(service)
+                    .boxed();
+            }
+
             let private_queries = self.private_queries.clone();
             let inner = ServiceBuilder::new()
                 .map_response(move |response: subgraph::Response| {
@@ -377,6 +392,8 @@ impl Plugin for EntityCache {
                     private_queries,
                     private_id,
                     invalidation: self.invalidation.clone(),
+                    health,
+                    stale_ttl: degradation.map(|degradation| degradation.stale_ttl),
                 })));
             tower::util::BoxService::new(inner)
         } else {
@@ -434,7 +451,7 @@ impl Plugin for EntityCache {
 impl EntityCache {
     #[cfg(test)]
     pub(crate) async fn with_mocks(
-        storage: RedisCacheStorage,
+        storage: Arc<dyn Store>,
         subgraphs: HashMap<String, Subgraph>,
     ) -> Result<Self, BoxError>
     where
@@ -460,6 +477,7 @@ impl EntityCache {
             }),
             metrics: Metrics::default(),
             private_queries: Default::default(),
+            health: Default::default(),
             endpoint_config: Some(Arc::new(InvalidationEndpointConfig {
                 path: String::from("/invalidation"),
                 listen: ListenAddr::SocketAddr(SocketAddr::new(
@@ -477,11 +495,13 @@ struct InnerCacheService {
     service: subgraph::BoxService,
     name: String,
     entity_type: Option<String>,
-    storage: RedisCacheStorage,
+    storage: Arc<dyn Store>,
     subgraph_ttl: Option<Duration>,
     private_queries: Arc<RwLock<HashSet<String>>>,
     private_id: Option<String>,
     invalidation: Invalidation,
+    health: Option<Arc<SubgraphHealth>>,
+    stale_ttl: Option<Duration>,
 }
 
 impl Service<subgraph::Request> for CacheService {
@@ -532,6 +552,11 @@ impl InnerCacheService {
 
         let is_known_private = { self.private_queries.read().await.contains(&query) };
         let private_id = self.get_private_id(&request.context);
+        let serve_stale = self
+            .health
+            .as_ref()
+            .map(|health| health.is_degraded())
+            .unwrap_or_default();
 
         // the response will have a private scope but we don't have a way to differentiate users, so we know we will not get or store anything in the cache
         if is_known_private && private_id.is_none() {
@@ -552,6 +577,7 @@ impl InnerCacheService {
                     self.storage.clone(),
                     is_known_private,
                     private_id.as_deref(),
+                    serve_stale,
                     request,
                 )
                 .instrument(tracing::info_span!("cache.entity.lookup"))
@@ -580,7 +606,7 @@ impl InnerCacheService {
 
                         let cache_control =
                             if response.response.headers().contains_key(CACHE_CONTROL) {
-                                CacheControl::new(response.response.headers(), self.storage.ttl)?
+                                CacheControl::new(response.response.headers(), self.storage.ttl())?
                             } else {
                                 let mut c = CacheControl::default();
                                 c.no_store = true;
@@ -620,6 +646,7 @@ impl InnerCacheService {
                             cache_store_root_from_response(
                                 self.storage,
                                 self.subgraph_ttl,
+                                self.stale_ttl,
                                 &response,
                                 cache_control,
                                 root_cache_key,
@@ -653,6 +680,7 @@ impl InnerCacheService {
                 self.storage.clone(),
                 is_known_private,
                 private_id.as_deref(),
+                serve_stale,
                 request,
             )
             .instrument(tracing::info_span!("cache.entity.lookup"))
@@ -704,7 +732,7 @@ impl InnerCacheService {
 
                     let mut cache_control =
                         if response.response.headers().contains_key(CACHE_CONTROL) {
-                            CacheControl::new(response.response.headers(), self.storage.ttl)?
+                            CacheControl::new(response.response.headers(), self.storage.ttl())?
                         } else {
                             CacheControl::no_store()
                         };
@@ -733,6 +761,7 @@ impl InnerCacheService {
                     cache_store_entities_from_response(
                         self.storage,
                         self.subgraph_ttl,
+                        self.stale_ttl,
                         &mut response,
                         cache_control.clone(),
                         cache_result.0,
@@ -779,9 +808,10 @@ impl InnerCacheService {
 async fn cache_lookup_root(
     name: String,
     entity_type_opt: Option<&str>,
-    cache: RedisCacheStorage,
+    cache: Arc<dyn Store>,
     is_known_private: bool,
     private_id: Option<&str>,
+    serve_stale: bool,
     mut request: subgraph::Request,
 ) -> Result<ControlFlow<subgraph::Response, (subgraph::Request, String)>, BoxError> {
     let body = request.subgraph_request.body_mut();
@@ -797,28 +827,29 @@ async fn cache_lookup_root(
         private_id,
     );
 
-    let cache_result: Option<RedisValue<CacheEntry>> = cache.get(RedisKey(key.clone())).await;
+    let cache_result: Option<CacheEntry> = cache.get_json(&key).await;
 
     match cache_result {
         Some(value) => {
-            if value.0.control.can_use() {
-                let control = value.0.control.clone();
+            let is_stale = !value.control.can_use();
+            if !is_stale || (serve_stale && value.control.should_store()) {
+                if is_stale {
+                    mark_stale(&request.context, &name);
+                }
+                let control = value.control.clone();
                 request
                     .context
                     .extensions()
                     .with_lock(|mut lock| lock.insert(control));
 
                 let mut response = subgraph::Response::builder()
-                    .data(value.0.data)
+                    .data(value.data)
                     .extensions(Object::new())
                     .context(request.context)
                     .and_subgraph_name(request.subgraph_name.clone())
                     .build();
 
-                value
-                    .0
-                    .control
-                    .to_headers(response.response.headers_mut())?;
+                value.control.to_headers(response.response.headers_mut())?;
                 Ok(ControlFlow::Break(response))
             } else {
                 Ok(ControlFlow::Continue((request, key)))
@@ -832,9 +863,10 @@ struct EntityCacheResults(Vec<IntermediateResult>, Option<CacheControl>);
 
 async fn cache_lookup_entities(
     name: String,
-    cache: RedisCacheStorage,
+    cache: Arc<dyn Store>,
     is_known_private: bool,
     private_id: Option<&str>,
+    serve_stale: bool,
     mut request: subgraph::Request,
 ) -> Result<ControlFlow<subgraph::Response, (subgraph::Request, EntityCacheResults)>, BoxError> {
     let body = request.subgraph_request.body_mut();
@@ -850,15 +882,14 @@ async fn cache_lookup_entities(
     )?;
 
     let cache_result: Vec<Option<CacheEntry>> = cache
-        .get_multiple(keys.iter().map(|k| RedisKey(k.clone())).collect::<Vec<_>>())
+        .get_multiple_json::<CacheEntry>(&keys)
         .await
         .map(|res| {
             res.into_iter()
-                .map(|r| r.map(|v: RedisValue<CacheEntry>| v.0))
                 .map(|v| match v {
                     None => None,
                     Some(v) => {
-                        if v.control.can_use() {
+                        if v.control.can_use() || (serve_stale && v.control.should_store()) {
                             Some(v)
                         } else {
                             None
@@ -875,8 +906,14 @@ async fn cache_lookup_entities(
         .and_then(|value| value.as_array_mut())
         .expect("we already checked that representations exist");
     // remove from representations the entities we already obtained from the cache
-    let (new_representations, cache_result, cache_control) =
-        filter_representations(&name, representations, keys, cache_result, &request.context)?;
+    let (new_representations, cache_result, cache_control) = filter_representations(
+        &name,
+        representations,
+        keys,
+        cache_result,
+        serve_stale,
+        &request.context,
+    )?;
 
     if !new_representations.is_empty() {
         body.variables
@@ -927,38 +964,32 @@ struct CacheEntry {
     data: Value,
 }
 
-impl ValueType for CacheEntry {
-    fn estimated_size(&self) -> Option<usize> {
-        None
-    }
-}
-
 async fn cache_store_root_from_response(
-    cache: RedisCacheStorage,
+    cache: Arc<dyn Store>,
     subgraph_ttl: Option<Duration>,
+    stale_ttl: Option<Duration>,
     response: &subgraph::Response,
     cache_control: CacheControl,
     cache_key: String,
 ) -> Result<(), BoxError> {
     if let Some(data) = response.response.body().data.as_ref() {
+        // entries are kept past their expiration so they can be served while the subgraph is degraded
         let ttl: Option<Duration> = cache_control
             .ttl()
             .map(|secs| Duration::from_secs(secs as u64))
-            .or(subgraph_ttl);
+            .or(subgraph_ttl)
+            .map(|ttl| ttl + stale_ttl.unwrap_or_default());
 
         if response.response.body().errors.is_empty() && cache_control.should_store() {
             let span = tracing::info_span!("cache.entity.store");
             let data = data.clone();
             tokio::spawn(async move {
+                let entry = CacheEntry {
+                    control: cache_control,
+                    data,
+                };
                 cache
-                    .insert(
-                        RedisKey(cache_key),
-                        RedisValue(CacheEntry {
-                            control: cache_control,
-                            data,
-                        }),
-                        ttl,
-                    )
+                    .set_json(&cache_key, &entry, ttl)
                     .instrument(span)
                     .await;
             });
@@ -968,9 +999,11 @@ async fn cache_store_root_from_response(
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn cache_store_entities_from_response(
-    cache: RedisCacheStorage,
+    cache: Arc<dyn Store>,
     subgraph_ttl: Option<Duration>,
+    stale_ttl: Option<Duration>,
     response: &mut subgraph::Response,
     cache_control: CacheControl,
     mut result_from_cache: Vec<IntermediateResult>,
@@ -1002,6 +1035,7 @@ async fn cache_store_entities_from_response(
             &response.response.body().errors,
             cache,
             subgraph_ttl,
+            stale_ttl,
             cache_control,
             &mut result_from_cache,
             update_key_private,
@@ -1211,6 +1245,7 @@ fn filter_representations(
     representations: &mut Vec<Value>,
     keys: Vec<String>,
     mut cache_result: Vec<Option<CacheEntry>>,
+    serve_stale: bool,
     context: &Context,
 ) -> Result<(Vec<Value>, Vec<IntermediateResult>, Option<CacheControl>), BoxError> {
     let mut new_representations: Vec<Value> = Vec::new();
@@ -1232,9 +1267,18 @@ fn filter_representations(
 
         let typename = opt_type.as_str().unwrap_or("-").to_string();
 
-        // do not use that cache entry if it is stale
-        if let Some(false) = cache_entry.as_ref().map(|c| c.control.can_use()) {
-            cache_entry = None;
+        // do not use that cache entry if it is stale, unless the subgraph is degraded and the
+        // entry could be stored
+        if let Some(control) = cache_entry
+            .as_ref()
+            .map(|c| &c.control)
+            .filter(|control| !control.can_use())
+        {
+            if serve_stale && control.should_store() {
+                mark_stale(context, subgraph_name);
+            } else {
+                cache_entry = None;
+            }
         }
         match cache_entry.as_ref() {
             None => {
@@ -1274,8 +1318,9 @@ fn filter_representations(
 async fn insert_entities_in_result(
     entities: &mut Vec<Value>,
     errors: &[Error],
-    cache: RedisCacheStorage,
+    cache: Arc<dyn Store>,
     subgraph_ttl: Option<Duration>,
+    stale_ttl: Option<Duration>,
     cache_control: CacheControl,
     result: &mut Vec<IntermediateResult>,
     update_key_private: Option<String>,
@@ -1284,7 +1329,8 @@ async fn insert_entities_in_result(
     let ttl: Option<Duration> = cache_control
         .ttl()
         .map(|secs| Duration::from_secs(secs as u64))
-        .or(subgraph_ttl);
+        .or(subgraph_ttl)
+        .map(|ttl| ttl + stale_ttl.unwrap_or_default());
 
     let mut new_entities = Vec::new();
     let mut new_errors = Vec::new();
@@ -1346,11 +1392,11 @@ async fn insert_entities_in_result(
 
                 if !has_errors && cache_control.should_store() && should_cache_private {
                     to_insert.push((
-                        RedisKey(key),
-                        RedisValue(CacheEntry {
+                        key,
+                        CacheEntry {
                             control: cache_control.clone(),
                             data: value.clone(),
-                        }),
+                        },
                     ));
                 }
 
@@ -1364,7 +1410,7 @@ async fn insert_entities_in_result(
 
         tokio::spawn(async move {
             cache
-                .insert_multiple(&to_insert, ttl)
+                .set_multiple_json(&to_insert, ttl)
                 .instrument(span)
                 .await;
         });
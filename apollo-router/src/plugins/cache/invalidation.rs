@@ -1,8 +1,6 @@
 use std::sync::Arc;
 use std::time::Instant;
 
-use fred::error::RedisError;
-use fred::types::Scanner;
 use futures::SinkExt;
 use futures::StreamExt;
 use itertools::Itertools;
@@ -15,8 +13,7 @@ use tower::BoxError;
 use tracing::Instrument;
 
 use super::entity::Storage as EntityStorage;
-use crate::cache::redis::RedisCacheStorage;
-use crate::cache::redis::RedisKey;
+use crate::cache::store::Store;
 use crate::notification::Handle;
 use crate::notification::HandleStream;
 use crate::plugins::cache::entity::hash_entity_key;
@@ -38,8 +35,8 @@ pub(crate) struct Invalidation {
 
 #[derive(Error, Debug, Clone)]
 pub(crate) enum InvalidationError {
-    #[error("redis error")]
-    RedisError(#[from] RedisError),
+    #[error("store error: {0}")]
+    StoreError(String),
     #[error("several errors")]
     Errors(#[from] InvalidationErrors),
     #[cfg(test)]
@@ -149,7 +146,7 @@ async fn start(
 }
 
 async fn handle_request(
-    storage: &RedisCacheStorage,
+    storage: &Arc<dyn Store>,
     origin: &'static str,
     request: &InvalidationRequest,
 ) -> Result<u64, InvalidationError> {
@@ -160,8 +157,7 @@ async fn handle_request(
         key_prefix
     );
 
-    // FIXME: configurable batch size
-    let mut stream = storage.scan(key_prefix.clone(), Some(100));
+    let mut stream = storage.scan(key_prefix.clone());
     let mut count = 0u64;
     let mut error = None;
 
@@ -176,25 +172,20 @@ async fn handle_request(
                 error = Some(e);
                 break;
             }
-            Ok(scan_res) => {
-                if let Some(keys) = scan_res.results() {
-                    let keys = keys
-                        .iter()
-                        .filter_map(|k| k.as_str())
-                        .map(|k| RedisKey(k.to_string()))
-                        .collect::<Vec<_>>();
-                    if !keys.is_empty() {
-                        count += keys.len() as u64;
-                        storage.delete(keys).await;
-
-                        u64_counter!(
-                            "apollo.router.operations.entity.invalidation.entry",
-                            "Entity cache counter for invalidated entries",
-                            1u64,
-                            "origin" = origin,
-                            "subgraph.name" = subgraph.clone()
-                        );
+            Ok(keys) => {
+                if !keys.is_empty() {
+                    count += keys.len() as u64;
+                    if let Err(e) = storage.delete(&keys).await {
+                        tracing::error!(error = %e, "could not delete invalidated keys");
                     }
+
+                    u64_counter!(
+                        "apollo.router.operations.entity.invalidation.entry",
+                        "Entity cache counter for invalidated entries",
+                        1u64,
+                        "origin" = origin,
+                        "subgraph.name" = subgraph.clone()
+                    );
                 }
             }
         }
@@ -207,7 +198,7 @@ async fn handle_request(
     );
 
     match error {
-        Some(err) => Err(err.into()),
+        Some(err) => Err(InvalidationError::StoreError(err.to_string())),
         None => Ok(count),
     }
 }
@@ -1,4 +1,5 @@
 pub(crate) mod cache_control;
+pub(crate) mod degradation;
 pub(crate) mod entity;
 pub(crate) mod invalidation;
 pub(crate) mod invalidation_endpoint;
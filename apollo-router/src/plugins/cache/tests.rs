@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 
 use bytes::Bytes;
 use fred::error::RedisErrorKind;
@@ -7,13 +8,20 @@ use fred::mocks::MockCommand;
 use fred::mocks::Mocks;
 use fred::prelude::RedisError;
 use fred::prelude::RedisValue;
+use futures::TryStreamExt;
 use http::header::CACHE_CONTROL;
 use http::HeaderValue;
 use parking_lot::Mutex;
 use tower::ServiceExt;
 
+use super::degradation::Degradation;
+use super::degradation::STALE_SUBGRAPHS_EXTENSION;
 use super::entity::EntityCache;
+use super::invalidation::InvalidationOrigin;
+use super::invalidation::InvalidationRequest;
 use crate::cache::redis::RedisCacheStorage;
+use crate::cache::store::InMemoryStore;
+use crate::cache::store::Store;
 use crate::plugin::test::MockSubgraph;
 use crate::plugin::test::MockSubgraphService;
 use crate::plugins::cache::entity::Subgraph;
@@ -226,9 +234,11 @@ async fn insert() {
         ).with_header(CACHE_CONTROL, HeaderValue::from_static("public")).build())
     ].into_iter().collect());
 
-    let redis_cache = RedisCacheStorage::from_mocks(Arc::new(MockStore::new()))
-        .await
-        .unwrap();
+    let redis_cache = Arc::new(
+        RedisCacheStorage::from_mocks(Arc::new(MockStore::new()))
+            .await
+            .unwrap(),
+    );
     let map = [
         (
             "user".to_string(),
@@ -340,9 +350,11 @@ async fn no_cache_control() {
         ).build())
     ].into_iter().collect());
 
-    let redis_cache = RedisCacheStorage::from_mocks(Arc::new(MockStore::new()))
-        .await
-        .unwrap();
+    let redis_cache = Arc::new(
+        RedisCacheStorage::from_mocks(Arc::new(MockStore::new()))
+            .await
+            .unwrap(),
+    );
     let entity_cache = EntityCache::with_mocks(redis_cache.clone(), HashMap::new())
         .await
         .unwrap();
@@ -431,9 +443,11 @@ async fn private() {
         ).with_header(CACHE_CONTROL, HeaderValue::from_static("private")).build())
     ].into_iter().collect());
 
-    let redis_cache = RedisCacheStorage::from_mocks(Arc::new(MockStore::new()))
-        .await
-        .unwrap();
+    let redis_cache = Arc::new(
+        RedisCacheStorage::from_mocks(Arc::new(MockStore::new()))
+            .await
+            .unwrap(),
+    );
     let map = [
         (
             "user".to_string(),
@@ -587,9 +601,11 @@ async fn no_data() {
         ).with_header(CACHE_CONTROL, HeaderValue::from_static("public, max-age=3600")).build())
     ].into_iter().collect());
 
-    let redis_cache = RedisCacheStorage::from_mocks(Arc::new(MockStore::new()))
-        .await
-        .unwrap();
+    let redis_cache = Arc::new(
+        RedisCacheStorage::from_mocks(Arc::new(MockStore::new()))
+            .await
+            .unwrap(),
+    );
     let map = [
         (
             "user".to_string(),
@@ -751,9 +767,11 @@ async fn missing_entities() {
         ).with_header(CACHE_CONTROL, HeaderValue::from_static("public, max-age=3600")).build())
     ].into_iter().collect());
 
-    let redis_cache = RedisCacheStorage::from_mocks(Arc::new(MockStore::new()))
-        .await
-        .unwrap();
+    let redis_cache = Arc::new(
+        RedisCacheStorage::from_mocks(Arc::new(MockStore::new()))
+            .await
+            .unwrap(),
+    );
     let map = [
         (
             "user".to_string(),
@@ -863,6 +881,281 @@ async fn missing_entities() {
     insta::assert_json_snapshot!(response);
 }
 
+#[tokio::test]
+async fn invalidate_in_memory_store() {
+    let query = "query { currentUser { activeOrganization { id creatorUser { __typename id } } } }";
+
+    let subgraphs = MockedSubgraphs([
+        ("user", MockSubgraph::builder().with_json(
+                serde_json::json!{{"query":"{currentUser{activeOrganization{__typename id}}}"}},
+                serde_json::json!{{"data": {"currentUser": { "activeOrganization": {
+                    "__typename": "Organization",
+                    "id": "1"
+                } }}}}
+        ).with_header(CACHE_CONTROL, HeaderValue::from_static("public")).build()),
+        ("orga", MockSubgraph::builder().with_json(
+            serde_json::json!{{
+                "query": "query($representations:[_Any!]!){_entities(representations:$representations){...on Organization{creatorUser{__typename id}}}}",
+            "variables": {
+                "representations": [
+                    {
+                        "id": "1",
+                        "__typename": "Organization",
+                    }
+                ]
+            }}},
+            serde_json::json!{{"data": {
+                "_entities": [{
+                    "creatorUser": {
+                        "__typename": "User",
+                        "id": 2
+                    }
+                }]
+            }}}
+        ).with_header(CACHE_CONTROL, HeaderValue::from_static("public")).build())
+    ].into_iter().collect());
+
+    // the fred mocks do not answer SCAN commands, the in memory store supports them
+    let store: Arc<dyn Store> = Arc::new(InMemoryStore::new(None));
+    let entity_cache = EntityCache::with_mocks(store.clone(), HashMap::new())
+        .await
+        .unwrap();
+    let mut invalidation = entity_cache.invalidation.clone();
+
+    let service = TestHarness::builder()
+        .configuration_json(serde_json::json!({"include_subgraph_errors": { "all": true } }))
+        .unwrap()
+        .schema(SCHEMA)
+        .extra_plugin(entity_cache)
+        .extra_plugin(subgraphs)
+        .build_supergraph()
+        .await
+        .unwrap();
+
+    let request = supergraph::Request::fake_builder()
+        .query(query)
+        .context(Context::new())
+        .build()
+        .unwrap();
+    let mut response = service.oneshot(request).await.unwrap();
+    response.next_response().await.unwrap();
+
+    // the entries are stored in the background
+    let mut keys = Vec::new();
+    for _ in 0..100 {
+        keys = store.scan("*".to_string()).try_concat().await.unwrap();
+        if keys.len() == 2 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(keys.len(), 2);
+
+    let invalidated = invalidation
+        .invalidate(
+            InvalidationOrigin::Endpoint,
+            vec![InvalidationRequest::Subgraph {
+                subgraph: "orga".to_string(),
+            }],
+        )
+        .await
+        .unwrap();
+    assert_eq!(invalidated, 1);
+
+    let keys: Vec<String> = store.scan("*".to_string()).try_concat().await.unwrap();
+    assert_eq!(keys.len(), 1);
+    assert!(keys[0].contains(":subgraph:user:"));
+}
+
+#[tokio::test]
+async fn serve_stale_entries_when_degraded() {
+    let query = "query { currentUser { activeOrganization { id creatorUser { __typename id } } } }";
+    let user = MockSubgraph::builder()
+        .with_json(
+            serde_json::json! {{"query":"{currentUser{activeOrganization{__typename id}}}"}},
+            serde_json::json! {{"data": {"currentUser": { "activeOrganization": {
+                "__typename": "Organization",
+                "id": "1"
+            } }}}},
+        )
+        .with_header(CACHE_CONTROL, HeaderValue::from_static("public"))
+        .build();
+    let orga_request = serde_json::json! {{
+        "query": "query($representations:[_Any!]!){_entities(representations:$representations){...on Organization{creatorUser{__typename id}}}}",
+        "variables": {
+            "representations": [
+                {
+                    "id": "1",
+                    "__typename": "Organization",
+                }
+            ]
+        }
+    }};
+
+    let store: Arc<dyn Store> = Arc::new(InMemoryStore::new(None));
+    let map: HashMap<String, Subgraph> = [(
+        "orga".to_string(),
+        Subgraph {
+            enabled: true,
+            degradation: Some(Degradation {
+                error_rate_threshold: 0.5,
+                min_requests: Some(1),
+                window: None,
+                stale_ttl: Duration::from_secs(60),
+            }),
+            ..Default::default()
+        },
+    )]
+    .into_iter()
+    .collect();
+
+    // fill the cache while the subgraph is healthy
+    let subgraphs = MockedSubgraphs(
+        [
+            ("user", user.clone()),
+            (
+                "orga",
+                MockSubgraph::builder()
+                    .with_json(
+                        orga_request.clone(),
+                        serde_json::json! {{"data": {
+                            "_entities": [{
+                                "creatorUser": {
+                                    "__typename": "User",
+                                    "id": 2
+                                }
+                            }]
+                        }}},
+                    )
+                    .with_header(
+                        CACHE_CONTROL,
+                        HeaderValue::from_static("public, max-age=100"),
+                    )
+                    .build(),
+            ),
+        ]
+        .into_iter()
+        .collect(),
+    );
+    let service = TestHarness::builder()
+        .configuration_json(serde_json::json!({"include_subgraph_errors": { "all": true } }))
+        .unwrap()
+        .schema(SCHEMA)
+        .extra_plugin(
+            EntityCache::with_mocks(store.clone(), map.clone())
+                .await
+                .unwrap(),
+        )
+        .extra_plugin(subgraphs)
+        .build_supergraph()
+        .await
+        .unwrap();
+    let request = supergraph::Request::fake_builder()
+        .query(query)
+        .context(Context::new())
+        .build()
+        .unwrap();
+    service
+        .oneshot(request)
+        .await
+        .unwrap()
+        .next_response()
+        .await
+        .unwrap();
+
+    // the entries are stored in the background
+    let mut keys = Vec::new();
+    for _ in 0..100 {
+        keys = store
+            .scan("*:subgraph:orga:*".to_string())
+            .try_concat()
+            .await
+            .unwrap();
+        if keys.len() == 1 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(keys.len(), 1);
+    let orga_key = keys.remove(0);
+
+    // expires the cached entity by moving its creation past its max-age
+    let update_entry = |no_store: bool| {
+        let store = store.clone();
+        let orga_key = orga_key.clone();
+        async move {
+            let mut entry: serde_json::Value = store.get_json(&orga_key).await.unwrap();
+            let created = entry["control"]["created"].as_u64().unwrap();
+            entry["control"]["created"] = (created - 200).into();
+            entry["control"]["no_store"] = no_store.into();
+            store.set_json(&orga_key, &entry, None).await;
+        }
+    };
+    update_entry(false).await;
+
+    let subgraphs = MockedSubgraphs(
+        [
+            ("user", user),
+            (
+                "orga",
+                MockSubgraph::builder()
+                    .with_json(
+                        orga_request,
+                        serde_json::json! {{"errors": [{ "message": "subgraph unavailable" }]}},
+                    )
+                    .build(),
+            ),
+        ]
+        .into_iter()
+        .collect(),
+    );
+    let service = TestHarness::builder()
+        .configuration_json(serde_json::json!({"include_subgraph_errors": { "all": true } }))
+        .unwrap()
+        .schema(SCHEMA)
+        .extra_plugin(EntityCache::with_mocks(store.clone(), map).await.unwrap())
+        .extra_plugin(subgraphs)
+        .build_supergraph()
+        .await
+        .unwrap();
+    let execute = || {
+        let service = service.clone();
+        async move {
+            let request = supergraph::Request::fake_builder()
+                .query(query)
+                .context(Context::new())
+                .build()
+                .unwrap();
+            service
+                .oneshot(request)
+                .await
+                .unwrap()
+                .next_response()
+                .await
+                .unwrap()
+        }
+    };
+
+    // the subgraph is still healthy, the expired entry is not used and the subgraph fails
+    let response = execute().await;
+    assert!(!response.errors.is_empty());
+    assert!(response.extensions.get(STALE_SUBGRAPHS_EXTENSION).is_none());
+
+    // the subgraph is now degraded, the expired entry is served
+    let response = execute().await;
+    assert!(response.errors.is_empty());
+    assert_eq!(
+        response.extensions.get(STALE_SUBGRAPHS_EXTENSION),
+        Some(&serde_json_bytes::json!(["orga"]))
+    );
+
+    // entries that could not be stored are never served stale
+    update_entry(true).await;
+    let response = execute().await;
+    assert!(!response.errors.is_empty());
+    assert!(response.extensions.get(STALE_SUBGRAPHS_EXTENSION).is_none());
+}
+
 /*FIXME: reactivate test if we manage to make fred return the response to SCAN in mocks
 #[tokio::test(flavor = "multi_thread")]
 async fn invalidate() {
@@ -898,9 +1191,11 @@ async fn invalidate() {
         ).with_header(CACHE_CONTROL, HeaderValue::from_static("public")).build())
     ].into_iter().collect());
 
-    let redis_cache = RedisCacheStorage::from_mocks(Arc::new(MockStore::new()))
-        .await
-        .unwrap();
+    let redis_cache = Arc::new(
+        RedisCacheStorage::from_mocks(Arc::new(MockStore::new()))
+            .await
+            .unwrap(),
+    );
     let entity_cache = EntityCache::with_mocks(redis_cache.clone(), HashMap::new())
         .await
         .unwrap();
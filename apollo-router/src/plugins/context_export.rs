@@ -0,0 +1,370 @@
+//! Export of request context entries to subgraphs
+//!
+//! An allow-listed subset of the request context is serialized as a compact token, optionally
+//! signed, and sent to subgraphs in a header or in a request extension. Subgraphs can then use
+//! data computed by the router (client tier, experiment bucket...) without running a
+//! coprocessor of their own.
+//!
+//! The token is the base64url encoded JSON object holding the exported entries and the
+//! expiration of the token, as a UNIX timestamp in seconds:
+//! `{"entries": {...}, "exp": 1700000000}`. When a signing key is configured, it is followed by a
+//! `.` and the base64url encoded HMAC-SHA256 signature of the encoded object.
+
+use std::collections::HashSet;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine as _;
+use hmac::Hmac;
+use hmac::Mac;
+use http::header::HeaderName;
+use http::HeaderValue;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json_bytes::Map;
+use serde_json_bytes::Value;
+use tower::BoxError;
+use tower::ServiceExt;
+
+use crate::plugin::Plugin;
+use crate::plugin::PluginInit;
+use crate::register_plugin;
+use crate::services::subgraph;
+use crate::Context;
+
+type HmacSha256 = Hmac<sha2::Sha256>;
+
+const DEFAULT_NAME: &str = "apollo-router-context";
+const DEFAULT_TTL: Duration = Duration::from_secs(60);
+
+/// Export of request context entries to subgraphs
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct ContextExportConfig {
+    /// Keys of the context entries sent to subgraphs. Missing entries are skipped
+    pub(crate) keys: Vec<String>,
+    /// Where the exported entries are sent (default: header)
+    pub(crate) target: ExportTarget,
+    /// Name of the header or of the request extension (default: `apollo-router-context`)
+    pub(crate) name: Option<String>,
+    /// Key signing the exported entries with HMAC-SHA256, so subgraphs can check they were set
+    /// by the router
+    pub(crate) signing_key: Option<String>,
+    /// Subgraphs receiving the exported entries (default: all subgraphs)
+    pub(crate) subgraphs: Option<Vec<String>>,
+    /// Validity of the token, after which subgraphs should reject it (default: 60s)
+    #[serde(deserialize_with = "humantime_serde::deserialize", default)]
+    #[schemars(with = "Option<String>", default)]
+    pub(crate) ttl: Option<Duration>,
+}
+
+/// Where the exported context entries are sent
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ExportTarget {
+    /// A request header
+    #[default]
+    Header,
+    /// An entry of the `extensions` of the subgraph request
+    Extension,
+}
+
+#[derive(Clone)]
+enum Destination {
+    Header(HeaderName),
+    Extension(String),
+}
+
+#[derive(Clone)]
+struct Exporter {
+    keys: Vec<String>,
+    destination: Destination,
+    signing_key: Option<HmacSha256>,
+    ttl: Duration,
+}
+
+impl Exporter {
+    /// Returns the token of the exported entries, if any of them is in the context
+    fn token(&self, context: &Context) -> Result<Option<String>, BoxError> {
+        let entries: Map<_, _> = self
+            .keys
+            .iter()
+            .filter_map(|key| {
+                context
+                    .get_json_value(key.as_str())
+                    .map(|value| (key.as_str().into(), value))
+            })
+            .collect();
+        if entries.is_empty() {
+            return Ok(None);
+        }
+
+        let expires_at = (SystemTime::now() + self.ttl)
+            .duration_since(UNIX_EPOCH)?
+            .as_secs();
+        let payload = serde_json::json!({ "entries": entries, "exp": expires_at });
+        let mut token = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&payload)?);
+        if let Some(mac) = &self.signing_key {
+            let mut mac = mac.clone();
+            mac.update(token.as_bytes());
+            let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+            token.push('.');
+            token.push_str(&signature);
+        }
+        Ok(Some(token))
+    }
+
+    fn export(&self, request: &mut subgraph::Request) {
+        // clients can set the header or the extension themselves, and they can be propagated
+        // to subgraphs by other plugins: only the router sets them
+        match &self.destination {
+            Destination::Header(name) => {
+                request.subgraph_request.headers_mut().remove(name);
+            }
+            Destination::Extension(name) => {
+                request
+                    .subgraph_request
+                    .body_mut()
+                    .extensions
+                    .remove(name.as_str());
+            }
+        }
+
+        let token = match self.token(&request.context) {
+            Ok(Some(token)) => token,
+            Ok(None) => return,
+            Err(e) => {
+                tracing::error!("could not export the context to subgraph: {e}");
+                return;
+            }
+        };
+        match &self.destination {
+            Destination::Header(name) => match HeaderValue::try_from(token) {
+                Ok(value) => {
+                    request
+                        .subgraph_request
+                        .headers_mut()
+                        .insert(name.clone(), value);
+                }
+                Err(e) => tracing::error!("could not export the context to subgraph: {e}"),
+            },
+            Destination::Extension(name) => {
+                request
+                    .subgraph_request
+                    .body_mut()
+                    .extensions
+                    .insert(name.as_str(), Value::String(token.into()));
+            }
+        }
+    }
+}
+
+struct ContextExport {
+    exporter: Option<Exporter>,
+    subgraphs: Option<HashSet<String>>,
+}
+
+#[async_trait::async_trait]
+impl Plugin for ContextExport {
+    type Config = ContextExportConfig;
+
+    async fn new(init: PluginInit<Self::Config>) -> Result<Self, BoxError> {
+        let config = init.config;
+        if config.keys.is_empty() {
+            return Ok(Self {
+                exporter: None,
+                subgraphs: None,
+            });
+        }
+
+        let name = config.name.unwrap_or_else(|| DEFAULT_NAME.to_string());
+        let destination = match config.target {
+            ExportTarget::Header => Destination::Header(
+                HeaderName::try_from(name.as_str())
+                    .map_err(|e| format!("invalid context export header name '{name}': {e}"))?,
+            ),
+            ExportTarget::Extension => Destination::Extension(name),
+        };
+        let signing_key = config
+            .signing_key
+            .map(|key| HmacSha256::new_from_slice(key.as_bytes()))
+            .transpose()?;
+
+        Ok(Self {
+            exporter: Some(Exporter {
+                keys: config.keys,
+                destination,
+                signing_key,
+                ttl: config.ttl.unwrap_or(DEFAULT_TTL),
+            }),
+            subgraphs: config
+                .subgraphs
+                .map(|subgraphs| subgraphs.into_iter().collect()),
+        })
+    }
+
+    fn subgraph_service(&self, name: &str, service: subgraph::BoxService) -> subgraph::BoxService {
+        let Some(exporter) = self.exporter.clone() else {
+            return service;
+        };
+        if self
+            .subgraphs
+            .as_ref()
+            .is_some_and(|subgraphs| !subgraphs.contains(name))
+        {
+            return service;
+        }
+
+        service
+            .map_request(move |mut request: subgraph::Request| {
+                exporter.export(&mut request);
+                request
+            })
+            .boxed()
+    }
+}
+
+register_plugin!("apollo", "context_export", ContextExport);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::test::MockSubgraphService;
+
+    async fn context_export_plugin(config: serde_json::Value) -> Box<dyn crate::plugin::DynPlugin> {
+        crate::plugin::plugins()
+            .find(|factory| factory.name == "apollo.context_export")
+            .expect("Plugin not found")
+            .create_instance_without_schema(&config)
+            .await
+            .unwrap()
+    }
+
+    fn request() -> subgraph::Request {
+        let context = Context::new();
+        context.insert("client_tier", "gold".to_string()).unwrap();
+        context
+            .insert("secret", "do not export".to_string())
+            .unwrap();
+        subgraph::Request::fake_builder().context(context).build()
+    }
+
+    fn decode(payload: &str) -> serde_json::Value {
+        serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn it_exports_signed_context_entries_in_a_header() {
+        let plugin = context_export_plugin(serde_json::json!({
+            "keys": ["client_tier", "experiment_bucket"],
+            "signing_key": "router secret"
+        }))
+        .await;
+
+        let mut mock_service = MockSubgraphService::new();
+        mock_service.expect_call().times(1).returning(|request| {
+            let token = request
+                .subgraph_request
+                .headers()
+                .get(DEFAULT_NAME)
+                .unwrap()
+                .to_str()
+                .unwrap();
+            let (payload, signature) = token.split_once('.').unwrap();
+            let payload_json = decode(payload);
+            assert_eq!(
+                payload_json["entries"],
+                serde_json::json!({ "client_tier": "gold" })
+            );
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+            let expires_at = payload_json["exp"].as_u64().unwrap();
+            assert!(expires_at > now.as_secs() && expires_at <= now.as_secs() + 60);
+
+            let mut mac = HmacSha256::new_from_slice(b"router secret").unwrap();
+            mac.update(payload.as_bytes());
+            mac.verify_slice(&URL_SAFE_NO_PAD.decode(signature).unwrap())
+                .unwrap();
+            Ok(subgraph::Response::fake_builder().build())
+        });
+
+        plugin
+            .subgraph_service("products", mock_service.boxed())
+            .oneshot(request())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn it_exports_context_entries_in_an_extension() {
+        let plugin = context_export_plugin(serde_json::json!({
+            "keys": ["client_tier"],
+            "target": "extension",
+            "name": "routerContext",
+            "subgraphs": ["products"]
+        }))
+        .await;
+
+        let mut mock_service = MockSubgraphService::new();
+        mock_service.expect_call().times(1).returning(|request| {
+            let token = request.subgraph_request.body().extensions["routerContext"]
+                .as_str()
+                .unwrap();
+            assert_eq!(
+                decode(token)["entries"],
+                serde_json::json!({ "client_tier": "gold" })
+            );
+            Ok(subgraph::Response::fake_builder().build())
+        });
+        plugin
+            .subgraph_service("products", mock_service.boxed())
+            .oneshot(request())
+            .await
+            .unwrap();
+
+        // other subgraphs do not receive the context
+        let mut mock_service = MockSubgraphService::new();
+        mock_service.expect_call().times(1).returning(|request| {
+            assert!(request.subgraph_request.body().extensions.is_empty());
+            Ok(subgraph::Response::fake_builder().build())
+        });
+        plugin
+            .subgraph_service("reviews", mock_service.boxed())
+            .oneshot(request())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn it_strips_the_header_set_by_the_client() {
+        let plugin = context_export_plugin(serde_json::json!({
+            "keys": ["client_tier"],
+            "signing_key": "router secret"
+        }))
+        .await;
+
+        let mut mock_service = MockSubgraphService::new();
+        mock_service.expect_call().times(1).returning(|request| {
+            assert!(request
+                .subgraph_request
+                .headers()
+                .get(DEFAULT_NAME)
+                .is_none());
+            Ok(subgraph::Response::fake_builder().build())
+        });
+
+        // none of the exported entries is in the context
+        let mut request = subgraph::Request::fake_builder().build();
+        request
+            .subgraph_request
+            .headers_mut()
+            .insert(DEFAULT_NAME, HeaderValue::from_static("forged"));
+        plugin
+            .subgraph_service("products", mock_service.boxed())
+            .oneshot(request)
+            .await
+            .unwrap();
+    }
+}
@@ -68,6 +68,7 @@ impl ExecutionStage {
         service: execution::BoxService,
         coprocessor_url: String,
         sdl: Arc<String>,
+        on_failure: &FailureHandling,
     ) -> execution::BoxService
     where
         C: Service<
@@ -82,12 +83,14 @@ impl ExecutionStage {
     {
         let request_layer = (self.request != Default::default()).then_some({
             let request_config = self.request.clone();
+            let request_failure = on_failure.handler(PipelineStep::ExecutionRequest, None);
             let coprocessor_url = coprocessor_url.clone();
             let http_client = http_client.clone();
             let sdl = sdl.clone();
 
             OneShotAsyncCheckpointLayer::new(move |request: execution::Request| {
                 let request_config = request_config.clone();
+                let request_failure = request_failure.clone();
                 let coprocessor_url = coprocessor_url.clone();
                 let http_client = http_client.clone();
                 let sdl = sdl.clone();
@@ -100,6 +103,7 @@ impl ExecutionStage {
                         sdl,
                         request,
                         request_config,
+                        request_failure,
                     )
                     .await
                     .map_err(|error| {
@@ -124,12 +128,14 @@ impl ExecutionStage {
 
         let response_layer = (self.response != Default::default()).then_some({
             let response_config = self.response.clone();
+            let response_failure = on_failure.handler(PipelineStep::ExecutionResponse, None);
 
             MapFutureLayer::new(move |fut| {
                 let coprocessor_url = coprocessor_url.clone();
                 let sdl: Arc<String> = sdl.clone();
                 let http_client = http_client.clone();
                 let response_config = response_config.clone();
+                let response_failure = response_failure.clone();
 
                 async move {
                     let response: execution::Response = fut.await?;
@@ -141,6 +147,7 @@ impl ExecutionStage {
                         sdl,
                         response,
                         response_config,
+                        response_failure,
                     )
                     .await
                     .map_err(|error| {
@@ -188,6 +195,7 @@ async fn process_execution_request_stage<C>(
     sdl: Arc<String>,
     mut request: execution::Request,
     request_config: ExecutionRequestConf,
+    on_failure: StageFailureHandler,
 ) -> Result<ControlFlow<execution::Response, execution::Request>, BoxError>
 where
     C: Service<http::Request<RouterBody>, Response = http::Response<RouterBody>, Error = BoxError>
@@ -234,7 +242,9 @@ where
     tracing::debug!(?payload, "externalized output");
     let guard = request.context.enter_active_request();
     let start = Instant::now();
-    let co_processor_result = payload.call(http_client, &coprocessor_url).await;
+    let co_processor_result = on_failure
+        .call(payload, http_client, &coprocessor_url)
+        .await;
     let duration = start.elapsed().as_secs_f64();
     drop(guard);
     tracing::info!(
@@ -243,8 +253,7 @@ where
     );
 
     tracing::debug!(?co_processor_result, "co-processor returned");
-    let co_processor_output = co_processor_result?;
-    validate_coprocessor_output(&co_processor_output, PipelineStep::ExecutionRequest)?;
+    let co_processor_output = on_failure.output(co_processor_result, &request.context)?;
     // unwrap is safe here because validate_coprocessor_output made sure control is available
     let control = co_processor_output.control.expect("validated above; qed");
 
@@ -330,6 +339,7 @@ async fn process_execution_response_stage<C>(
     sdl: Arc<String>,
     response: execution::Response,
     response_config: ExecutionResponseConf,
+    on_failure: StageFailureHandler,
 ) -> Result<execution::Response, BoxError>
 where
     C: Service<http::Request<RouterBody>, Response = http::Response<RouterBody>, Error = BoxError>
@@ -380,7 +390,9 @@ where
     tracing::debug!(?payload, "externalized output");
     let guard = response.context.enter_active_request();
     let start = Instant::now();
-    let co_processor_result = payload.call(http_client.clone(), &coprocessor_url).await;
+    let co_processor_result = on_failure
+        .call(payload, http_client.clone(), &coprocessor_url)
+        .await;
     let duration = start.elapsed().as_secs_f64();
     drop(guard);
     tracing::info!(
@@ -389,9 +401,7 @@ where
     );
 
     tracing::debug!(?co_processor_result, "co-processor returned");
-    let co_processor_output = co_processor_result?;
-
-    validate_coprocessor_output(&co_processor_output, PipelineStep::ExecutionResponse)?;
+    let co_processor_output = on_failure.output(co_processor_result, &response.context)?;
 
     // Third, process our reply and act on the contents. Our processing logic is
     // that we replace "bits" of our incoming response with the updated bits if they
@@ -423,6 +433,7 @@ where
     let mapped_stream = rest
         .then(move |deferred_response| {
             let generator_client = http_client.clone();
+            let generator_on_failure = on_failure.clone();
             let generator_coprocessor_url = coprocessor_url.clone();
             let generator_map_context = map_context.clone();
             let generator_sdl_to_send = sdl_to_send.clone();
@@ -451,14 +462,13 @@ where
                 // Second, call our co-processor and get a reply.
                 tracing::debug!(?payload, "externalized output");
                 let guard = generator_map_context.enter_active_request();
-                let co_processor_result = payload
-                    .call(generator_client, &generator_coprocessor_url)
+                let co_processor_result = generator_on_failure
+                    .call(payload, generator_client, &generator_coprocessor_url)
                     .await;
                 drop(guard);
                 tracing::debug!(?co_processor_result, "co-processor returned");
-                let co_processor_output = co_processor_result?;
-
-                validate_coprocessor_output(&co_processor_output, PipelineStep::ExecutionResponse)?;
+                let co_processor_output =
+                    generator_on_failure.output(co_processor_result, &generator_map_context)?;
 
                 // Third, process our reply and act on the contents. Our processing logic is
                 // that we replace "bits" of our incoming response with the updated bits if they
@@ -680,6 +690,7 @@ mod tests {
             mock_execution_service.boxed(),
             "http://test".to_string(),
             Arc::new("".to_string()),
+            &Default::default(),
         );
 
         let request = execution::Request::fake_builder().build();
@@ -749,6 +760,7 @@ mod tests {
             mock_execution_service.boxed(),
             "http://test".to_string(),
             Arc::new("".to_string()),
+            &Default::default(),
         );
 
         let request = execution::Request::fake_builder().build();
@@ -875,6 +887,7 @@ mod tests {
             mock_execution_service.boxed(),
             "http://test".to_string(),
             Arc::new("".to_string()),
+            &Default::default(),
         );
 
         let request = execution::Request::fake_builder().build();
@@ -989,6 +1002,7 @@ mod tests {
             mock_execution_service.boxed(),
             "http://test".to_string(),
             Arc::new("".to_string()),
+            &Default::default(),
         );
 
         let request = execution::Request::fake_builder()
@@ -0,0 +1,387 @@
+//! Handling of coprocessor failures, per stage
+//!
+//! A stage fails when the coprocessor cannot be reached, times out or returns an invalid
+//! response. Depending on the policy of the stage, the request is then rejected, or continues
+//! unchanged as if the coprocessor had returned it as is.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde::Serialize;
+use tower::BoxError;
+use tower::Service;
+
+use super::validate_coprocessor_output;
+use crate::services::external::Externalizable;
+use crate::services::external::PipelineStep;
+use crate::services::router::body::RouterBody;
+use crate::Context;
+
+/// Context key listing the stages that were skipped because of a `degrade` failure policy
+pub(crate) const COPROCESSOR_DEGRADED_STAGES: &str = "apollo_coprocessor::degraded_stages";
+
+/// What happens to the request or response when the coprocessor of a stage fails
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub(super) enum FailurePolicy {
+    /// Reject the request with an error
+    #[default]
+    FailClosed,
+    /// Continue with the unchanged request or response
+    FailOpen,
+    /// Continue with the unchanged request or response, list the stage in the
+    /// `apollo_coprocessor::degraded_stages` context entry and count it in the
+    /// `apollo.router.operations.coprocessor.degraded` metric
+    Degrade,
+}
+
+/// Handling of coprocessor failures. A coprocessor fails when it cannot be reached, times out
+/// or returns an invalid response
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub(super) struct FailureConf {
+    /// Policy of the stages without a specific policy (default: fail_closed)
+    pub(super) default: FailurePolicy,
+    /// Policy of the router request stage
+    pub(super) router_request: Option<FailurePolicy>,
+    /// Policy of the router response stage
+    pub(super) router_response: Option<FailurePolicy>,
+    /// Policy of the supergraph request stage
+    pub(super) supergraph_request: Option<FailurePolicy>,
+    /// Policy of the supergraph response stage
+    pub(super) supergraph_response: Option<FailurePolicy>,
+    /// Policy of the execution request stage
+    pub(super) execution_request: Option<FailurePolicy>,
+    /// Policy of the execution response stage
+    pub(super) execution_response: Option<FailurePolicy>,
+    /// Policy of the subgraph request stage
+    pub(super) subgraph_request: Option<FailurePolicy>,
+    /// Policy of the subgraph response stage
+    pub(super) subgraph_response: Option<FailurePolicy>,
+    /// Stop calling the coprocessor of a stage after repeated failures
+    pub(super) circuit_breaker: Option<CircuitBreakerConf>,
+}
+
+/// Stops calling the coprocessor of a stage after consecutive failures. While the circuit is
+/// open, the failure policy of the stage applies without calling the coprocessor
+#[derive(Clone, Copy, Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(super) struct CircuitBreakerConf {
+    /// Number of consecutive failures opening the circuit
+    pub(super) failure_threshold: u32,
+    /// Time during which the coprocessor is not called once the circuit is open
+    #[serde(deserialize_with = "humantime_serde::deserialize")]
+    #[schemars(with = "String")]
+    pub(super) open_duration: Duration,
+}
+
+impl FailureConf {
+    fn policy(&self, stage: &PipelineStep) -> FailurePolicy {
+        let policy = match stage {
+            PipelineStep::RouterRequest => self.router_request,
+            PipelineStep::RouterResponse => self.router_response,
+            PipelineStep::SupergraphRequest => self.supergraph_request,
+            PipelineStep::SupergraphResponse => self.supergraph_response,
+            PipelineStep::ExecutionRequest => self.execution_request,
+            PipelineStep::ExecutionResponse => self.execution_response,
+            PipelineStep::SubgraphRequest => self.subgraph_request,
+            PipelineStep::SubgraphResponse => self.subgraph_response,
+        };
+        policy.unwrap_or(self.default)
+    }
+}
+
+/// Failure handling of the coprocessor stages. The services of the stages are created for each
+/// request, so the circuit breakers are kept here to count failures across requests
+#[derive(Debug, Default)]
+pub(super) struct FailureHandling {
+    conf: FailureConf,
+    /// Circuit breakers by stage, and by subgraph for the subgraph stages
+    breakers: Mutex<HashMap<(PipelineStep, Option<String>), Arc<CircuitBreaker>>>,
+}
+
+impl FailureHandling {
+    pub(super) fn new(conf: FailureConf) -> Self {
+        Self {
+            conf,
+            breakers: Default::default(),
+        }
+    }
+
+    /// Returns the failure handling of a stage, sharing its circuit breaker with the previous
+    /// requests
+    pub(super) fn handler(
+        &self,
+        stage: PipelineStep,
+        subgraph: Option<&str>,
+    ) -> StageFailureHandler {
+        let breaker = self.conf.circuit_breaker.map(|conf| {
+            self.breakers
+                .lock()
+                .entry((stage.clone(), subgraph.map(str::to_string)))
+                .or_insert_with(|| Arc::new(CircuitBreaker::new(conf)))
+                .clone()
+        });
+        StageFailureHandler {
+            policy: self.conf.policy(&stage),
+            breaker,
+            stage,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct CircuitBreaker {
+    conf: CircuitBreakerConf,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    fn new(conf: CircuitBreakerConf) -> Self {
+        Self {
+            conf,
+            state: Default::default(),
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        self.state
+            .lock()
+            .open_until
+            .is_some_and(|open_until| Instant::now() < open_until)
+    }
+
+    /// Records the outcome of a stage, returns true if the circuit was just opened
+    fn record(&self, succeeded: bool) -> bool {
+        let mut state = self.state.lock();
+        if succeeded {
+            *state = BreakerState::default();
+            return false;
+        }
+        state.consecutive_failures = state.consecutive_failures.saturating_add(1);
+        let now = Instant::now();
+        let is_open = state.open_until.is_some_and(|open_until| now < open_until);
+        // after the open duration, a single failure opens the circuit again
+        if !is_open && state.consecutive_failures >= self.conf.failure_threshold {
+            state.open_until = Some(now + self.conf.open_duration);
+            return true;
+        }
+        false
+    }
+}
+
+/// Failure handling of a stage
+#[derive(Clone)]
+pub(super) struct StageFailureHandler {
+    stage: PipelineStep,
+    policy: FailurePolicy,
+    breaker: Option<Arc<CircuitBreaker>>,
+}
+
+impl StageFailureHandler {
+    /// Calls the coprocessor, unless the circuit is open
+    pub(super) async fn call<C, T>(
+        &self,
+        payload: Externalizable<T>,
+        http_client: C,
+        coprocessor_url: &str,
+    ) -> Result<Externalizable<T>, BoxError>
+    where
+        C: Service<
+                http::Request<RouterBody>,
+                Response = http::Response<RouterBody>,
+                Error = BoxError,
+            > + Clone
+            + Send
+            + Sync
+            + 'static,
+        T: std::fmt::Debug + DeserializeOwned + Serialize + Send + Sync,
+    {
+        if self
+            .breaker
+            .as_ref()
+            .is_some_and(|breaker| breaker.is_open())
+        {
+            return Err(BoxError::from(format!(
+                "the circuit breaker of the {} stage is open",
+                self.stage
+            )));
+        }
+        payload.call(http_client, coprocessor_url).await
+    }
+
+    /// Validates the coprocessor output, and applies the failure policy if the coprocessor
+    /// failed
+    pub(super) fn output<T>(
+        &self,
+        result: Result<Externalizable<T>, BoxError>,
+        context: &Context,
+    ) -> Result<Externalizable<T>, BoxError>
+    where
+        T: std::fmt::Debug + DeserializeOwned + Serialize + Send + Sync,
+    {
+        let result = result.and_then(|output| {
+            validate_coprocessor_output(&output, self.stage.clone())?;
+            Ok(output)
+        });
+        if let Some(breaker) = &self.breaker {
+            if breaker.record(result.is_ok()) {
+                tracing::warn!(
+                    "external extensibility: opening the circuit breaker of the {} stage",
+                    self.stage
+                );
+                u64_counter!(
+                    "apollo.router.operations.coprocessor.circuit_opened",
+                    "Number of times a coprocessor stage stopped calling the coprocessor after repeated failures",
+                    1,
+                    "coprocessor.stage" = self.stage.clone()
+                );
+            }
+        }
+        let error = match result {
+            Ok(output) => return Ok(output),
+            Err(error) => error,
+        };
+
+        match self.policy {
+            FailurePolicy::FailClosed => Err(error),
+            FailurePolicy::FailOpen => {
+                tracing::warn!(
+                    "external extensibility: {} stage failed, continuing unchanged: {error}",
+                    self.stage
+                );
+                Ok(Externalizable::passthrough(self.stage.clone()))
+            }
+            FailurePolicy::Degrade => {
+                tracing::warn!(
+                    "external extensibility: {} stage failed, continuing in degraded mode: {error}",
+                    self.stage
+                );
+                let stage = self.stage.to_string();
+                let _ = context.upsert(COPROCESSOR_DEGRADED_STAGES, |mut stages: Vec<String>| {
+                    if !stages.contains(&stage) {
+                        stages.push(stage);
+                    }
+                    stages
+                });
+                u64_counter!(
+                    "apollo.router.operations.coprocessor.degraded",
+                    "Number of coprocessor stages skipped in degraded mode because the coprocessor failed",
+                    1,
+                    "coprocessor.stage" = self.stage.clone()
+                );
+                Ok(Externalizable::passthrough(self.stage.clone()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker(failure_threshold: u32) -> CircuitBreaker {
+        CircuitBreaker::new(CircuitBreakerConf {
+            failure_threshold,
+            open_duration: Duration::from_millis(50),
+        })
+    }
+
+    #[test]
+    fn it_opens_the_circuit_after_consecutive_failures() {
+        let breaker = breaker(2);
+        assert!(!breaker.record(false));
+        assert!(!breaker.record(true));
+        assert!(!breaker.record(false));
+        assert!(!breaker.is_open());
+        assert!(breaker.record(false));
+        assert!(breaker.is_open());
+
+        // a failure after the open duration opens the circuit again
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(!breaker.is_open());
+        assert!(breaker.record(false));
+        assert!(breaker.is_open());
+    }
+
+    #[test]
+    fn it_applies_the_stage_policy() {
+        let conf: FailureConf = serde_json::from_value(serde_json::json!({
+            "default": "fail_open",
+            "subgraph_request": "degrade"
+        }))
+        .unwrap();
+        let handling = FailureHandling::new(conf);
+        let context = Context::new();
+
+        let handler = handling.handler(PipelineStep::RouterRequest, None);
+        let output = handler
+            .output::<String>(Err("unreachable".into()), &context)
+            .unwrap();
+        assert_eq!(
+            output.control,
+            Some(crate::services::external::Control::Continue)
+        );
+        assert!(!context.contains_key(COPROCESSOR_DEGRADED_STAGES));
+
+        let handler = handling.handler(PipelineStep::SubgraphRequest, Some("products"));
+        handler
+            .output::<String>(Err("unreachable".into()), &context)
+            .unwrap();
+        assert_eq!(
+            context
+                .get::<_, Vec<String>>(COPROCESSOR_DEGRADED_STAGES)
+                .unwrap()
+                .unwrap(),
+            vec!["SubgraphRequest".to_string()]
+        );
+
+        let handler = FailureHandling::default().handler(PipelineStep::RouterResponse, None);
+        assert!(handler
+            .output::<String>(Err("unreachable".into()), &context)
+            .is_err());
+    }
+
+    #[test]
+    fn it_shares_the_circuit_breakers_across_requests() {
+        let conf: FailureConf = serde_json::from_value(serde_json::json!({
+            "default": "fail_open",
+            "circuit_breaker": {
+                "failure_threshold": 2,
+                "open_duration": "1m"
+            }
+        }))
+        .unwrap();
+        let handling = FailureHandling::new(conf);
+        let context = Context::new();
+
+        // each request creates the handlers of its stages
+        for _ in 0..2 {
+            handling
+                .handler(PipelineStep::SubgraphRequest, Some("products"))
+                .output::<String>(Err("unreachable".into()), &context)
+                .unwrap();
+        }
+        let is_open = |subgraph| {
+            handling
+                .handler(PipelineStep::SubgraphRequest, Some(subgraph))
+                .breaker
+                .unwrap()
+                .is_open()
+        };
+        assert!(is_open("products"));
+        assert!(!is_open("reviews"));
+    }
+}
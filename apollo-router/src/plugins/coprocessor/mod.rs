@@ -29,6 +29,9 @@ use tower::Service;
 use tower::ServiceBuilder;
 use tower::ServiceExt;
 
+use self::failure::FailureConf;
+use self::failure::FailureHandling;
+use self::failure::StageFailureHandler;
 use crate::configuration::shared::Client;
 use crate::error::Error;
 use crate::graphql;
@@ -60,6 +63,7 @@ use crate::services::trust_dns_connector::AsyncHyperResolver;
 mod test;
 
 mod execution;
+mod failure;
 mod supergraph;
 
 pub(crate) const EXTERNAL_SPAN_NAME: &str = "external_plugin";
@@ -78,7 +82,14 @@ impl Plugin for CoprocessorPlugin<HTTPClientService> {
     type Config = Conf;
 
     async fn new(init: PluginInit<Self::Config>) -> Result<Self, BoxError> {
-        let mut http_connector = new_async_http_connector()?;
+        let mut http_connector = new_async_http_connector(
+            &init
+                .config
+                .client
+                .as_ref()
+                .and_then(|client| client.dns.clone())
+                .unwrap_or_default(),
+        )?;
         http_connector.set_nodelay(true);
         http_connector.set_keepalive(Some(std::time::Duration::from_secs(60)));
         http_connector.enforce_http(false);
@@ -172,6 +183,7 @@ where
     http_client: C,
     configuration: Conf,
     sdl: Arc<String>,
+    on_failure: FailureHandling,
 }
 
 impl<C> CoprocessorPlugin<C>
@@ -186,6 +198,7 @@ where
     fn new(http_client: C, configuration: Conf, sdl: Arc<String>) -> Result<Self, BoxError> {
         Ok(Self {
             http_client,
+            on_failure: FailureHandling::new(configuration.on_failure.clone()),
             configuration,
             sdl,
         })
@@ -197,6 +210,7 @@ where
             service,
             self.configuration.url.clone(),
             self.sdl.clone(),
+            &self.on_failure,
         )
     }
 
@@ -209,6 +223,7 @@ where
             service,
             self.configuration.url.clone(),
             self.sdl.clone(),
+            &self.on_failure,
         )
     }
 
@@ -221,6 +236,7 @@ where
             service,
             self.configuration.url.clone(),
             self.sdl.clone(),
+            &self.on_failure,
         )
     }
 
@@ -230,6 +246,7 @@ where
             service,
             self.configuration.url.clone(),
             name.to_string(),
+            &self.on_failure,
         )
     }
 }
@@ -336,6 +353,9 @@ struct Conf {
     /// The subgraph stage request/response configuration
     #[serde(default)]
     subgraph: SubgraphStages,
+    /// Handling of coprocessor failures, per stage
+    #[serde(default)]
+    on_failure: FailureConf,
 }
 
 fn default_timeout() -> Duration {
@@ -358,6 +378,7 @@ impl RouterStage {
         service: router::BoxService,
         coprocessor_url: String,
         sdl: Arc<String>,
+        on_failure: &FailureHandling,
     ) -> router::BoxService
     where
         C: Service<
@@ -372,12 +393,14 @@ impl RouterStage {
     {
         let request_layer = (self.request != Default::default()).then_some({
             let request_config = self.request.clone();
+            let request_failure = on_failure.handler(PipelineStep::RouterRequest, None);
             let coprocessor_url = coprocessor_url.clone();
             let http_client = http_client.clone();
             let sdl = sdl.clone();
 
             OneShotAsyncCheckpointLayer::new(move |request: router::Request| {
                 let request_config = request_config.clone();
+                let request_failure = request_failure.clone();
                 let coprocessor_url = coprocessor_url.clone();
                 let http_client = http_client.clone();
                 let sdl = sdl.clone();
@@ -390,6 +413,7 @@ impl RouterStage {
                         sdl,
                         request,
                         request_config,
+                        request_failure,
                     )
                     .await
                     .map_err(|error| {
@@ -413,11 +437,13 @@ impl RouterStage {
 
         let response_layer = (self.response != Default::default()).then_some({
             let response_config = self.response.clone();
+            let response_failure = on_failure.handler(PipelineStep::RouterResponse, None);
             MapFutureLayer::new(move |fut| {
                 let sdl = sdl.clone();
                 let coprocessor_url = coprocessor_url.clone();
                 let http_client = http_client.clone();
                 let response_config = response_config.clone();
+                let response_failure = response_failure.clone();
 
                 async move {
                     let response: router::Response = fut.await?;
@@ -429,6 +455,7 @@ impl RouterStage {
                         sdl,
                         response,
                         response_config,
+                        response_failure,
                     )
                     .await
                     .map_err(|error| {
@@ -496,6 +523,7 @@ impl SubgraphStage {
         service: subgraph::BoxService,
         coprocessor_url: String,
         service_name: String,
+        on_failure: &FailureHandling,
     ) -> subgraph::BoxService
     where
         C: Service<
@@ -510,6 +538,8 @@ impl SubgraphStage {
     {
         let request_layer = (self.request != Default::default()).then_some({
             let request_config = self.request.clone();
+            let request_failure =
+                on_failure.handler(PipelineStep::SubgraphRequest, Some(service_name.as_str()));
             let http_client = http_client.clone();
             let coprocessor_url = coprocessor_url.clone();
             let service_name = service_name.clone();
@@ -518,6 +548,7 @@ impl SubgraphStage {
                 let coprocessor_url = coprocessor_url.clone();
                 let service_name = service_name.clone();
                 let request_config = request_config.clone();
+                let request_failure = request_failure.clone();
 
                 async move {
                     let mut succeeded = true;
@@ -527,6 +558,7 @@ impl SubgraphStage {
                         service_name,
                         request,
                         request_config,
+                        request_failure,
                     )
                     .await
                     .map_err(|error| {
@@ -550,11 +582,14 @@ impl SubgraphStage {
 
         let response_layer = (self.response != Default::default()).then_some({
             let response_config = self.response.clone();
+            let response_failure =
+                on_failure.handler(PipelineStep::SubgraphResponse, Some(service_name.as_str()));
 
             MapFutureLayer::new(move |fut| {
                 let http_client = http_client.clone();
                 let coprocessor_url = coprocessor_url.clone();
                 let response_config = response_config.clone();
+                let response_failure = response_failure.clone();
                 let service_name = service_name.clone();
 
                 async move {
@@ -567,6 +602,7 @@ impl SubgraphStage {
                         service_name,
                         response,
                         response_config,
+                        response_failure,
                     )
                     .await
                     .map_err(|error| {
@@ -614,6 +650,7 @@ async fn process_router_request_stage<C>(
     sdl: Arc<String>,
     mut request: router::Request,
     mut request_config: RouterRequestConf,
+    on_failure: StageFailureHandler,
 ) -> Result<ControlFlow<router::Response, router::Request>, BoxError>
 where
     C: Service<http::Request<RouterBody>, Response = http::Response<RouterBody>, Error = BoxError>
@@ -669,7 +706,9 @@ where
     tracing::debug!(?payload, "externalized output");
     let guard = request.context.enter_active_request();
     let start = Instant::now();
-    let co_processor_result = payload.call(http_client, &coprocessor_url).await;
+    let co_processor_result = on_failure
+        .call(payload, http_client, &coprocessor_url)
+        .await;
     let duration = start.elapsed().as_secs_f64();
     drop(guard);
     tracing::info!(
@@ -678,9 +717,7 @@ where
     );
 
     tracing::debug!(?co_processor_result, "co-processor returned");
-    let mut co_processor_output = co_processor_result?;
-
-    validate_coprocessor_output(&co_processor_output, PipelineStep::RouterRequest)?;
+    let mut co_processor_output = on_failure.output(co_processor_result, &request.context)?;
     // unwrap is safe here because validate_coprocessor_output made sure control is available
     let control = co_processor_output.control.expect("validated above; qed");
 
@@ -775,6 +812,7 @@ async fn process_router_response_stage<C>(
     sdl: Arc<String>,
     mut response: router::Response,
     response_config: RouterResponseConf,
+    on_failure: StageFailureHandler,
 ) -> Result<router::Response, BoxError>
 where
     C: Service<http::Request<RouterBody>, Response = http::Response<RouterBody>, Error = BoxError>
@@ -844,7 +882,9 @@ where
     tracing::debug!(?payload, "externalized output");
     let guard = response.context.enter_active_request();
     let start = Instant::now();
-    let co_processor_result = payload.call(http_client.clone(), &coprocessor_url).await;
+    let co_processor_result = on_failure
+        .call(payload, http_client.clone(), &coprocessor_url)
+        .await;
     let duration = start.elapsed().as_secs_f64();
     drop(guard);
     tracing::info!(
@@ -853,9 +893,7 @@ where
     );
 
     tracing::debug!(?co_processor_result, "co-processor returned");
-    let co_processor_output = co_processor_result?;
-
-    validate_coprocessor_output(&co_processor_output, PipelineStep::RouterResponse)?;
+    let co_processor_output = on_failure.output(co_processor_result, &response.context)?;
 
     // Third, process our reply and act on the contents. Our processing logic is
     // that we replace "bits" of our incoming response with the updated bits if they
@@ -897,6 +935,7 @@ where
         .map_err(BoxError::from)
         .and_then(move |deferred_response| {
             let generator_client = http_client.clone();
+            let generator_on_failure = on_failure.clone();
             let generator_coprocessor_url = coprocessor_url.clone();
             let generator_map_context = map_context.clone();
             let generator_sdl_to_send = sdl_to_send.clone();
@@ -926,14 +965,13 @@ where
                 // Second, call our co-processor and get a reply.
                 tracing::debug!(?payload, "externalized output");
                 let guard = generator_map_context.enter_active_request();
-                let co_processor_result = payload
-                    .call(generator_client, &generator_coprocessor_url)
+                let co_processor_result = generator_on_failure
+                    .call(payload, generator_client, &generator_coprocessor_url)
                     .await;
                 drop(guard);
                 tracing::debug!(?co_processor_result, "co-processor returned");
-                let co_processor_output = co_processor_result?;
-
-                validate_coprocessor_output(&co_processor_output, PipelineStep::RouterResponse)?;
+                let co_processor_output =
+                    generator_on_failure.output(co_processor_result, &generator_map_context)?;
 
                 // Third, process our reply and act on the contents. Our processing logic is
                 // that we replace "bits" of our incoming response with the updated bits if they
@@ -977,6 +1015,7 @@ async fn process_subgraph_request_stage<C>(
     service_name: String,
     mut request: subgraph::Request,
     mut request_config: SubgraphRequestConf,
+    on_failure: StageFailureHandler,
 ) -> Result<ControlFlow<subgraph::Response, subgraph::Request>, BoxError>
 where
     C: Service<http::Request<RouterBody>, Response = http::Response<RouterBody>, Error = BoxError>
@@ -1028,7 +1067,9 @@ where
     tracing::debug!(?payload, "externalized output");
     let guard = request.context.enter_active_request();
     let start = Instant::now();
-    let co_processor_result = payload.call(http_client, &coprocessor_url).await;
+    let co_processor_result = on_failure
+        .call(payload, http_client, &coprocessor_url)
+        .await;
     let duration = start.elapsed().as_secs_f64();
     drop(guard);
     tracing::info!(
@@ -1037,8 +1078,7 @@ where
     );
 
     tracing::debug!(?co_processor_result, "co-processor returned");
-    let co_processor_output = co_processor_result?;
-    validate_coprocessor_output(&co_processor_output, PipelineStep::SubgraphRequest)?;
+    let co_processor_output = on_failure.output(co_processor_result, &request.context)?;
     // unwrap is safe here because validate_coprocessor_output made sure control is available
     let control = co_processor_output.control.expect("validated above; qed");
 
@@ -1132,6 +1172,7 @@ async fn process_subgraph_response_stage<C>(
     service_name: String,
     mut response: subgraph::Response,
     response_config: SubgraphResponseConf,
+    on_failure: StageFailureHandler,
 ) -> Result<subgraph::Response, BoxError>
 where
     C: Service<http::Request<RouterBody>, Response = http::Response<RouterBody>, Error = BoxError>
@@ -1182,7 +1223,9 @@ where
     tracing::debug!(?payload, "externalized output");
     let guard = response.context.enter_active_request();
     let start = Instant::now();
-    let co_processor_result = payload.call(http_client, &coprocessor_url).await;
+    let co_processor_result = on_failure
+        .call(payload, http_client, &coprocessor_url)
+        .await;
     let duration = start.elapsed().as_secs_f64();
     drop(guard);
     tracing::info!(
@@ -1191,9 +1234,7 @@ where
     );
 
     tracing::debug!(?co_processor_result, "co-processor returned");
-    let co_processor_output = co_processor_result?;
-
-    validate_coprocessor_output(&co_processor_output, PipelineStep::SubgraphResponse)?;
+    let co_processor_output = on_failure.output(co_processor_result, &response.context)?;
 
     // Third, process our reply and act on the contents. Our processing logic is
     // that we replace "bits" of our incoming response with the updated bits if they
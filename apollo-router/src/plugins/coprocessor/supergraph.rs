@@ -74,6 +74,7 @@ impl SupergraphStage {
         service: supergraph::BoxService,
         coprocessor_url: String,
         sdl: Arc<String>,
+        on_failure: &FailureHandling,
     ) -> supergraph::BoxService
     where
         C: Service<
@@ -88,12 +89,14 @@ impl SupergraphStage {
     {
         let request_layer = (self.request != Default::default()).then_some({
             let request_config = self.request.clone();
+            let request_failure = on_failure.handler(PipelineStep::SupergraphRequest, None);
             let coprocessor_url = coprocessor_url.clone();
             let http_client = http_client.clone();
             let sdl = sdl.clone();
 
             OneShotAsyncCheckpointLayer::new(move |request: supergraph::Request| {
                 let request_config = request_config.clone();
+                let request_failure = request_failure.clone();
                 let coprocessor_url = coprocessor_url.clone();
                 let http_client = http_client.clone();
                 let sdl = sdl.clone();
@@ -106,6 +109,7 @@ impl SupergraphStage {
                         sdl,
                         request,
                         request_config,
+                        request_failure,
                     )
                     .await
                     .map_err(|error| {
@@ -129,12 +133,14 @@ impl SupergraphStage {
 
         let response_layer = (self.response != Default::default()).then_some({
             let response_config = self.response.clone();
+            let response_failure = on_failure.handler(PipelineStep::SupergraphResponse, None);
 
             MapFutureLayer::new(move |fut| {
                 let coprocessor_url = coprocessor_url.clone();
                 let sdl: Arc<String> = sdl.clone();
                 let http_client = http_client.clone();
                 let response_config = response_config.clone();
+                let response_failure = response_failure.clone();
 
                 async move {
                     let response: supergraph::Response = fut.await?;
@@ -146,6 +152,7 @@ impl SupergraphStage {
                         sdl,
                         response,
                         response_config,
+                        response_failure,
                     )
                     .await
                     .map_err(|error| {
@@ -192,6 +199,7 @@ async fn process_supergraph_request_stage<C>(
     sdl: Arc<String>,
     mut request: supergraph::Request,
     mut request_config: SupergraphRequestConf,
+    on_failure: StageFailureHandler,
 ) -> Result<ControlFlow<supergraph::Response, supergraph::Request>, BoxError>
 where
     C: Service<http::Request<RouterBody>, Response = http::Response<RouterBody>, Error = BoxError>
@@ -242,7 +250,9 @@ where
     tracing::debug!(?payload, "externalized output");
     let guard = request.context.enter_active_request();
     let start = Instant::now();
-    let co_processor_result = payload.call(http_client, &coprocessor_url).await;
+    let co_processor_result = on_failure
+        .call(payload, http_client, &coprocessor_url)
+        .await;
     let duration = start.elapsed().as_secs_f64();
     drop(guard);
     tracing::info!(
@@ -251,8 +261,7 @@ where
     );
 
     tracing::debug!(?co_processor_result, "co-processor returned");
-    let co_processor_output = co_processor_result?;
-    validate_coprocessor_output(&co_processor_output, PipelineStep::SupergraphRequest)?;
+    let co_processor_output = on_failure.output(co_processor_result, &request.context)?;
     // unwrap is safe here because validate_coprocessor_output made sure control is available
     let control = co_processor_output.control.expect("validated above; qed");
 
@@ -338,6 +347,7 @@ async fn process_supergraph_response_stage<C>(
     sdl: Arc<String>,
     response: supergraph::Response,
     response_config: SupergraphResponseConf,
+    on_failure: StageFailureHandler,
 ) -> Result<supergraph::Response, BoxError>
 where
     C: Service<http::Request<RouterBody>, Response = http::Response<RouterBody>, Error = BoxError>
@@ -396,7 +406,9 @@ where
     tracing::debug!(?payload, "externalized output");
     let guard = response.context.enter_active_request();
     let start = Instant::now();
-    let co_processor_result = payload.call(http_client.clone(), &coprocessor_url).await;
+    let co_processor_result = on_failure
+        .call(payload, http_client.clone(), &coprocessor_url)
+        .await;
     let duration = start.elapsed().as_secs_f64();
     drop(guard);
     tracing::info!(
@@ -405,9 +417,7 @@ where
     );
 
     tracing::debug!(?co_processor_result, "co-processor returned");
-    let co_processor_output = co_processor_result?;
-
-    validate_coprocessor_output(&co_processor_output, PipelineStep::SupergraphResponse)?;
+    let co_processor_output = on_failure.output(co_processor_result, &response.context)?;
 
     // Third, process our reply and act on the contents. Our processing logic is
     // that we replace "bits" of our incoming response with the updated bits if they
@@ -439,6 +449,7 @@ where
     let mapped_stream = rest
         .then(move |deferred_response| {
             let generator_client = http_client.clone();
+            let generator_on_failure = on_failure.clone();
             let generator_coprocessor_url = coprocessor_url.clone();
             let generator_map_context = map_context.clone();
             let generator_sdl_to_send = sdl_to_send.clone();
@@ -475,17 +486,13 @@ where
                 // Second, call our co-processor and get a reply.
                 tracing::debug!(?payload, "externalized output");
                 let guard = generator_map_context.enter_active_request();
-                let co_processor_result = payload
-                    .call(generator_client, &generator_coprocessor_url)
+                let co_processor_result = generator_on_failure
+                    .call(payload, generator_client, &generator_coprocessor_url)
                     .await;
                 drop(guard);
                 tracing::debug!(?co_processor_result, "co-processor returned");
-                let co_processor_output = co_processor_result?;
-
-                validate_coprocessor_output(
-                    &co_processor_output,
-                    PipelineStep::SupergraphResponse,
-                )?;
+                let co_processor_output =
+                    generator_on_failure.output(co_processor_result, &generator_map_context)?;
 
                 // Third, process our reply and act on the contents. Our processing logic is
                 // that we replace "bits" of our incoming response with the updated bits if they
@@ -707,6 +714,7 @@ mod tests {
             mock_supergraph_service.boxed(),
             "http://test".to_string(),
             Arc::new("".to_string()),
+            &Default::default(),
         );
 
         let request = supergraph::Request::fake_builder().build().unwrap();
@@ -785,6 +793,7 @@ mod tests {
             mock_supergraph_service.boxed(),
             "http://test".to_string(),
             Arc::new("".to_string()),
+            &Default::default(),
         );
 
         let request = supergraph::Request::fake_builder()
@@ -862,6 +871,7 @@ mod tests {
             mock_supergraph_service.boxed(),
             "http://test".to_string(),
             Arc::new("".to_string()),
+            &Default::default(),
         );
 
         let crate::services::supergraph::Response { context, .. } =
@@ -973,6 +983,7 @@ mod tests {
             mock_supergraph_service.boxed(),
             "http://test".to_string(),
             Arc::new("".to_string()),
+            &Default::default(),
         );
 
         let request = supergraph::Request::canned_builder().build().unwrap();
@@ -1088,6 +1099,7 @@ mod tests {
             mock_supergraph_service.boxed(),
             "http://test".to_string(),
             Arc::new("".to_string()),
+            &Default::default(),
         );
 
         let request = supergraph::Request::canned_builder()
@@ -1206,6 +1218,7 @@ mod tests {
             mock_supergraph_service.boxed(),
             "http://test".to_string(),
             Arc::new("".to_string()),
+            &Default::default(),
         );
 
         let request = supergraph::Request::canned_builder()
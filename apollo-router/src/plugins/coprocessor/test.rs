@@ -1,6 +1,8 @@
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
     use std::sync::Arc;
 
     use futures::future::BoxFuture;
@@ -141,6 +143,7 @@ mod tests {
             mock_router_service.boxed(),
             "http://test".to_string(),
             Arc::new("".to_string()),
+            &Default::default(),
         );
 
         let request = supergraph::Request::canned_builder().build().unwrap();
@@ -201,6 +204,7 @@ mod tests {
             mock_router_service.boxed(),
             "http://test".to_string(),
             Arc::new("".to_string()),
+            &Default::default(),
         );
 
         let request = supergraph::Request::canned_builder().build().unwrap();
@@ -260,6 +264,7 @@ mod tests {
             mock_router_service.boxed(),
             "http://test".to_string(),
             Arc::new("".to_string()),
+            &Default::default(),
         );
 
         let request = supergraph::Request::canned_builder().build().unwrap();
@@ -319,6 +324,7 @@ mod tests {
             mock_subgraph_service.boxed(),
             "http://test".to_string(),
             "my_subgraph_service_name".to_string(),
+            &Default::default(),
         );
 
         let request = subgraph::Request::fake_builder().build();
@@ -337,6 +343,86 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn coprocessor_subgraph_failure_policy() {
+        static COPROCESSOR_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+        let subgraph_stage = SubgraphStage {
+            request: SubgraphRequestConf {
+                condition: Default::default(),
+                headers: false,
+                context: false,
+                body: true,
+                uri: false,
+                method: false,
+                service_name: false,
+            },
+            response: Default::default(),
+        };
+        let on_failure: FailureConf = serde_json::from_value(json!({
+            "subgraph_request": "degrade",
+            "circuit_breaker": {
+                "failure_threshold": 1,
+                "open_duration": "1m"
+            }
+        }))
+        .unwrap();
+        // kept by the plugin across requests
+        let on_failure = FailureHandling::new(on_failure);
+
+        // the router creates the subgraph service for each request
+        let service = || {
+            let mut mock_subgraph_service = MockSubgraphService::new();
+            mock_subgraph_service
+                .expect_call()
+                .times(1)
+                .returning(|req: subgraph::Request| {
+                    Ok(subgraph::Response::builder()
+                        .data(json!({ "test": 1234_u32 }))
+                        .errors(Vec::new())
+                        .extensions(crate::json_ext::Object::new())
+                        .context(req.context)
+                        .build())
+                });
+
+            let mock_http_client = mock_with_callback(move |_: http::Request<RouterBody>| {
+                Box::pin(async {
+                    COPROCESSOR_CALLS.fetch_add(1, Ordering::SeqCst);
+                    Err(BoxError::from("coprocessor unreachable"))
+                })
+            });
+
+            subgraph_stage.as_service(
+                mock_http_client,
+                mock_subgraph_service.boxed(),
+                "http://test".to_string(),
+                "my_subgraph_service_name".to_string(),
+                &on_failure,
+            )
+        };
+
+        // the subgraph is called with the unchanged request, flagged as degraded
+        let request = subgraph::Request::fake_builder().build();
+        let context = request.context.clone();
+        let response = service().oneshot(request).await.unwrap();
+        assert_eq!(
+            response.response.body().data,
+            Some(serde_json_bytes::json!({ "test": 1234_u32 }))
+        );
+        assert_eq!(
+            context
+                .get::<_, Vec<String>>(failure::COPROCESSOR_DEGRADED_STAGES)
+                .unwrap()
+                .unwrap(),
+            vec!["SubgraphRequest".to_string()]
+        );
+
+        // the circuit opened during the previous request, the coprocessor is not called anymore
+        let request = subgraph::Request::fake_builder().build();
+        service().oneshot(request).await.unwrap();
+        assert_eq!(COPROCESSOR_CALLS.load(Ordering::SeqCst), 1);
+    }
+
     #[tokio::test]
     async fn external_plugin_subgraph_request() {
         let subgraph_stage = SubgraphStage {
@@ -450,6 +536,7 @@ mod tests {
             mock_subgraph_service.boxed(),
             "http://test".to_string(),
             "my_subgraph_service_name".to_string(),
+            &Default::default(),
         );
 
         let request = subgraph::Request::fake_builder().build();
@@ -532,6 +619,7 @@ mod tests {
             mock_subgraph_service.boxed(),
             "http://test".to_string(),
             "my_subgraph_service_name".to_string(),
+            &Default::default(),
         );
 
         let request = subgraph::Request::fake_builder().build();
@@ -599,6 +687,7 @@ mod tests {
             mock_subgraph_service.boxed(),
             "http://test".to_string(),
             "my_subgraph_service_name".to_string(),
+            &Default::default(),
         );
 
         let request = subgraph::Request::fake_builder().build();
@@ -659,6 +748,7 @@ mod tests {
             mock_subgraph_service.boxed(),
             "http://test".to_string(),
             "my_subgraph_service_name".to_string(),
+            &Default::default(),
         );
 
         let request = subgraph::Request::fake_builder().build();
@@ -768,6 +858,7 @@ mod tests {
             mock_subgraph_service.boxed(),
             "http://test".to_string(),
             "my_subgraph_service_name".to_string(),
+            &Default::default(),
         );
 
         let request = subgraph::Request::fake_builder().build();
@@ -889,6 +980,7 @@ mod tests {
             mock_subgraph_service.boxed(),
             "http://test".to_string(),
             "my_subgraph_service_name".to_string(),
+            &Default::default(),
         );
 
         let request = subgraph::Request::fake_builder().build();
@@ -966,6 +1058,7 @@ mod tests {
             mock_supergraph_service.boxed(),
             "http://test".to_string(),
             Arc::default(),
+            &Default::default(),
         );
 
         let request = supergraph::Request::fake_builder().build().unwrap();
@@ -1090,6 +1183,7 @@ mod tests {
             mock_router_service.boxed(),
             "http://test".to_string(),
             Arc::new("".to_string()),
+            &Default::default(),
         );
 
         let request = supergraph::Request::canned_builder().build().unwrap();
@@ -1202,6 +1296,7 @@ mod tests {
             mock_router_service.boxed(),
             "http://test".to_string(),
             Arc::new("".to_string()),
+            &Default::default(),
         );
 
         let request = supergraph::Request::canned_builder().build().unwrap();
@@ -1328,6 +1423,7 @@ mod tests {
             mock_router_service.boxed(),
             "http://test".to_string(),
             Arc::new("".to_string()),
+            &Default::default(),
         );
 
         let request = supergraph::Request::fake_builder()
@@ -1399,6 +1495,7 @@ mod tests {
             mock_router_service.boxed(),
             "http://test".to_string(),
             Arc::new("".to_string()),
+            &Default::default(),
         );
 
         let request = supergraph::Request::canned_builder().build().unwrap();
@@ -1478,6 +1575,7 @@ mod tests {
             mock_router_service.boxed(),
             "http://test".to_string(),
             Arc::new("".to_string()),
+            &Default::default(),
         );
 
         let request = supergraph::Request::canned_builder().build().unwrap();
@@ -1608,6 +1706,7 @@ mod tests {
             mock_router_service.boxed(),
             "http://test".to_string(),
             Arc::new("".to_string()),
+            &Default::default(),
         );
 
         let request = supergraph::Request::canned_builder().build().unwrap();
@@ -11,6 +11,7 @@ use tower::BoxError;
 use tower::ServiceBuilder;
 use tower::ServiceExt;
 
+use crate::axum_factory::websocket::WebSocketOperation;
 use crate::layers::ServiceBuilderExt;
 use crate::plugin::Plugin;
 use crate::plugin::PluginInit;
@@ -144,10 +145,20 @@ impl Plugin for Csrf {
 // - The only headers added by javascript code are part of the cors safelisted request headers (Accept,Accept-Language,Content-Language,Content-Type, and simple Range
 //
 // Given the first step is covered in our web browser, we'll take care of the two other steps below:
+//
+// Operations sent over WebSocket are not simple requests: the origin of the upgrade request was
+// checked against the allowed origins, and browsers can't add headers to upgrade requests anyway.
 fn is_preflighted(req: &supergraph::Request, required_headers: &[String]) -> bool {
     let headers = req.supergraph_request.headers();
     content_type_requires_preflight(headers)
         || recommended_header_is_provided(headers, required_headers)
+        || is_websocket_operation(req)
+}
+
+fn is_websocket_operation(req: &supergraph::Request) -> bool {
+    req.context
+        .extensions()
+        .with_lock(|lock| lock.contains_key::<WebSocketOperation>())
 }
 
 // Part two of the algorithm above:
@@ -265,6 +276,21 @@ mod csrf_tests {
         assert_rejected(config, non_preflighted_request).await
     }
 
+    #[tokio::test]
+    async fn it_lets_websocket_operations_pass_through() {
+        let config = CSRFConfig::default();
+        let mut websocket_operation = supergraph::Request::fake_builder().build().unwrap();
+        websocket_operation
+            .supergraph_request
+            .headers_mut()
+            .remove("content-type");
+        websocket_operation
+            .context
+            .extensions()
+            .with_lock(|mut lock| lock.insert(WebSocketOperation));
+        assert_accepted(config, websocket_operation).await
+    }
+
     #[tokio::test]
     async fn it_rejects_non_preflighted_content_type_request() {
         let config = CSRFConfig::default();
@@ -0,0 +1,347 @@
+//! Copy of client request extensions to the request context
+//!
+//! Clients can send hints (tracing flags, feature flags...) in the `extensions` of their GraphQL
+//! requests. The configured extensions are validated and copied to the request context, where
+//! other plugins, Rhai scripts and coprocessors can act on them.
+
+use std::ops::ControlFlow;
+
+use http::StatusCode;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json_bytes::Value;
+use tower::BoxError;
+use tower::ServiceBuilder;
+use tower::ServiceExt;
+
+use crate::context::OPERATION_KIND;
+use crate::context::OPERATION_NAME;
+use crate::graphql;
+use crate::layers::ServiceBuilderExt;
+use crate::plugin::Plugin;
+use crate::plugin::PluginInit;
+use crate::register_plugin;
+use crate::services::supergraph;
+
+const DEFAULT_MAX_SIZE: usize = 1024;
+
+/// Copy of request extensions to the request context
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct ExtensionsToContextConfig {
+    /// Extensions copied to the context
+    pub(crate) mappings: Vec<ExtensionMapping>,
+    /// Reject requests with an invalid extension, instead of ignoring the extension
+    pub(crate) reject_invalid: bool,
+}
+
+/// Copy of a request extension to the context
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ExtensionMapping {
+    /// Name of the extension in the request
+    pub(crate) extension: String,
+    /// Context key the extension is copied to (default: the name of the extension)
+    #[serde(default)]
+    pub(crate) context_key: Option<String>,
+    /// Expected type of the extension value (default: any)
+    #[serde(default, rename = "type")]
+    pub(crate) value_type: ExtensionType,
+    /// Maximum size of the JSON serialized value in bytes (default: 1024)
+    #[serde(default = "default_max_size")]
+    pub(crate) max_size: usize,
+}
+
+fn default_max_size() -> usize {
+    DEFAULT_MAX_SIZE
+}
+
+/// Type of an extension value
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ExtensionType {
+    /// Any JSON value
+    #[default]
+    Any,
+    /// A string
+    String,
+    /// A number
+    Number,
+    /// A boolean
+    Boolean,
+    /// A JSON object
+    Object,
+    /// A JSON array
+    Array,
+}
+
+impl ExtensionType {
+    fn matches(&self, value: &Value) -> bool {
+        match self {
+            ExtensionType::Any => true,
+            ExtensionType::String => value.is_string(),
+            ExtensionType::Number => value.is_number(),
+            ExtensionType::Boolean => value.is_boolean(),
+            ExtensionType::Object => value.is_object(),
+            ExtensionType::Array => value.is_array(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Mapping {
+    extension: String,
+    context_key: String,
+    value_type: ExtensionType,
+    max_size: usize,
+}
+
+impl Mapping {
+    /// Checks the extension value, returning the reason it is invalid
+    fn validate(&self, value: &Value) -> Result<(), &'static str> {
+        if !self.value_type.matches(value) {
+            return Err("invalid_type");
+        }
+        let size = serde_json::to_vec(value).map_or(usize::MAX, |bytes| bytes.len());
+        if size > self.max_size {
+            return Err("too_large");
+        }
+        Ok(())
+    }
+}
+
+struct ExtensionsToContext {
+    mappings: Vec<Mapping>,
+    reject_invalid: bool,
+}
+
+impl ExtensionsToContext {
+    /// Copies the valid extensions to the context, returns the name of the first invalid
+    /// extension if invalid extensions are rejected
+    fn copy(
+        mappings: &[Mapping],
+        reject_invalid: bool,
+        request: &supergraph::Request,
+    ) -> Option<String> {
+        let extensions = &request.supergraph_request.body().extensions;
+        for mapping in mappings {
+            let Some(value) = extensions.get(mapping.extension.as_str()) else {
+                continue;
+            };
+            if let Err(reason) = mapping.validate(value) {
+                u64_counter!(
+                    "apollo.router.extensions_to_context.invalid",
+                    "Number of request extensions that were not copied to the context because they are invalid",
+                    1,
+                    "reason" = reason
+                );
+                if reject_invalid {
+                    return Some(mapping.extension.clone());
+                }
+                tracing::debug!(
+                    "ignoring the '{}' request extension: {reason}",
+                    mapping.extension
+                );
+                continue;
+            }
+            request
+                .context
+                .insert_json_value(mapping.context_key.clone(), value.clone());
+        }
+        None
+    }
+}
+
+/// Returns true for the context keys of the entries set by the router
+fn is_reserved(context_key: &str) -> bool {
+    context_key.starts_with("apollo_")
+        || context_key.starts_with("apollo::")
+        || context_key == OPERATION_NAME
+        || context_key == OPERATION_KIND
+}
+
+#[async_trait::async_trait]
+impl Plugin for ExtensionsToContext {
+    type Config = ExtensionsToContextConfig;
+
+    async fn new(init: PluginInit<Self::Config>) -> Result<Self, BoxError> {
+        let config = init.config;
+        let mappings = config
+            .mappings
+            .into_iter()
+            .map(|mapping| {
+                let context_key = mapping
+                    .context_key
+                    .unwrap_or_else(|| mapping.extension.clone());
+                // clients must not be able to override the context entries set by the router
+                if is_reserved(&context_key) {
+                    return Err(format!(
+                        "extensions_to_context: context key '{context_key}' is reserved"
+                    ));
+                }
+                Ok(Mapping {
+                    extension: mapping.extension,
+                    context_key,
+                    value_type: mapping.value_type,
+                    max_size: mapping.max_size,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            mappings,
+            reject_invalid: config.reject_invalid,
+        })
+    }
+
+    fn supergraph_service(&self, service: supergraph::BoxService) -> supergraph::BoxService {
+        if self.mappings.is_empty() {
+            return service;
+        }
+        let mappings = self.mappings.clone();
+        let reject_invalid = self.reject_invalid;
+
+        ServiceBuilder::new()
+            .checkpoint(move |request: supergraph::Request| {
+                match Self::copy(&mappings, reject_invalid, &request) {
+                    None => Ok(ControlFlow::Continue(request)),
+                    Some(extension) => {
+                        let error = graphql::Error::builder()
+                            .message(format!("invalid request extension '{extension}'"))
+                            .extension_code("INVALID_EXTENSION")
+                            .build();
+                        let response = supergraph::Response::error_builder()
+                            .error(error)
+                            .status_code(StatusCode::BAD_REQUEST)
+                            .context(request.context)
+                            .build()?;
+                        Ok(ControlFlow::Break(response))
+                    }
+                }
+            })
+            .service(service)
+            .boxed()
+    }
+}
+
+register_plugin!("apollo", "extensions_to_context", ExtensionsToContext);
+
+#[cfg(test)]
+mod tests {
+    use serde_json_bytes::json;
+
+    use super::*;
+    use crate::plugin::test::MockSupergraphService;
+
+    async fn plugin(config: serde_json::Value) -> ExtensionsToContext {
+        ExtensionsToContext::new(PluginInit::fake_new(
+            serde_json::from_value(config).unwrap(),
+            Default::default(),
+        ))
+        .await
+        .unwrap()
+    }
+
+    fn request() -> supergraph::Request {
+        supergraph::Request::fake_builder()
+            .query("{ me { name } }")
+            .extension("traceHint", "verbose")
+            .extension("features", json!({ "newCheckout": true }))
+            .extension("experiment", json!(["a", "b"]))
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn it_copies_valid_extensions_to_the_context() {
+        let plugin = plugin(serde_json::json!({
+            "mappings": [
+                { "extension": "traceHint", "context_key": "trace_hint", "type": "string" },
+                { "extension": "features", "type": "object", "max_size": 4 },
+                { "extension": "experiment", "type": "string" },
+                { "extension": "missing" }
+            ]
+        }))
+        .await;
+
+        let mut mock_service = MockSupergraphService::new();
+        mock_service.expect_call().times(1).returning(|request| {
+            let context = &request.context;
+            assert_eq!(
+                context.get::<_, String>("trace_hint").unwrap(),
+                Some("verbose".to_string())
+            );
+            // too large
+            assert!(!context.contains_key("features"));
+            // invalid type
+            assert!(!context.contains_key("experiment"));
+            assert!(!context.contains_key("missing"));
+            Ok(supergraph::Response::fake_builder().build().unwrap())
+        });
+
+        plugin
+            .supergraph_service(mock_service.boxed())
+            .oneshot(request())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn it_rejects_invalid_extensions() {
+        let plugin = plugin(serde_json::json!({
+            "mappings": [{ "extension": "features", "type": "array" }],
+            "reject_invalid": true
+        }))
+        .await;
+
+        let mut response = plugin
+            .supergraph_service(MockSupergraphService::new().boxed())
+            .oneshot(request())
+            .await
+            .unwrap();
+        assert_eq!(response.response.status(), StatusCode::BAD_REQUEST);
+        let body = response.next_response().await.unwrap();
+        assert_eq!(
+            body.errors[0]
+                .extensions
+                .get("code")
+                .and_then(|code| code.as_str()),
+            Some("INVALID_EXTENSION")
+        );
+    }
+
+    #[tokio::test]
+    async fn it_refuses_reserved_context_keys() {
+        for context_key in [
+            "apollo_telemetry::client_name",
+            "apollo::supergraph::operation_id",
+            "operation_name",
+            "operation_kind",
+        ] {
+            let config = serde_json::from_value(serde_json::json!({
+                "mappings": [{ "extension": "client", "context_key": context_key }]
+            }))
+            .unwrap();
+            assert!(
+                ExtensionsToContext::new(PluginInit::fake_new(config, Default::default()))
+                    .await
+                    .is_err(),
+                "{context_key} must be reserved"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn it_refuses_reserved_extension_names_without_context_key() {
+        let config = serde_json::from_value(serde_json::json!({
+            "mappings": [{ "extension": "operation_name" }]
+        }))
+        .unwrap();
+        assert!(
+            ExtensionsToContext::new(PluginInit::fake_new(config, Default::default()))
+                .await
+                .is_err()
+        );
+    }
+}
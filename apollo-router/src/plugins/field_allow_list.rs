@@ -0,0 +1,729 @@
+//! Restricts the fields that the operations of each client can select
+//!
+//! This is a lighter weight alternative to publishing a contract variant for every partner: the
+//! schema coordinates allowed for a client are listed in the configuration, and operations are
+//! checked after persisted query expansion and before validation and query planning. Operations
+//! selecting other fields are rejected, or those fields are removed from the operation.
+//!
+//! Clients are identified by a claim of the JWT verified by the authentication plugin: the
+//! `apollographql-client-name` header can be set to any value by clients, so it can't be used to
+//! decide what they can see.
+
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use apollo_compiler::ast;
+use apollo_compiler::Name;
+use apollo_compiler::Schema;
+use lru::LruCache;
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde::Serialize;
+use tower::BoxError;
+
+use crate::graphql;
+use crate::plugin::Plugin;
+use crate::plugin::PluginInit;
+use crate::plugins::authentication::APOLLO_AUTHENTICATION_JWT_CLAIMS;
+use crate::plugins::limits::ParserLimits;
+use crate::register_plugin;
+use crate::spec::query::transform;
+use crate::Configuration;
+use crate::Context;
+
+const PLUGIN_NAME: &str = "field_allow_list";
+const FIELD_NOT_ALLOWED: &str = "FIELD_NOT_ALLOWED";
+/// Metric label of the clients that are not listed in the configuration
+const OTHER_CLIENTS: &str = "other";
+
+/// Restricts the fields that the operations of each client can select
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct FieldAllowListConfig {
+    /// What happens to operations selecting fields that are not allowed for their client
+    mode: FieldAllowListMode,
+    /// Claim of the JWT verified by the authentication plugin holding the name of the client.
+    /// Default: `client_name`
+    client_claim: String,
+    /// Rejects the operations of clients that are not listed in `clients`, and of requests
+    /// without a verified client claim. Default: false
+    default_deny: bool,
+    /// Schema coordinates that the operations of a client can select, by client name. A field
+    /// coordinate like `Product.name` allows the field on the type it is selected on, a type
+    /// coordinate like `Product` allows all the fields of the type. Clients that are not listed
+    /// are not restricted, unless `default_deny` is set
+    clients: HashMap<String, Vec<String>>,
+}
+
+impl Default for FieldAllowListConfig {
+    fn default() -> Self {
+        Self {
+            mode: FieldAllowListMode::default(),
+            client_claim: String::from("client_name"),
+            default_deny: false,
+            clients: HashMap::new(),
+        }
+    }
+}
+
+/// What happens to operations selecting fields that are not allowed
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum FieldAllowListMode {
+    /// Reject the operation with a validation error listing the fields that are not allowed
+    #[default]
+    Reject,
+    /// Remove the fields that are not allowed from the operation: they are absent from the
+    /// response. Operations where no field is left are rejected
+    Redact,
+}
+
+/// Result of checking an operation against the allow-list of a client
+#[derive(Clone)]
+struct Check {
+    /// the operation without the fields that are not allowed, if it still selects fields
+    redacted_query: Option<String>,
+    /// coordinates of the fields that are not allowed
+    disallowed: Vec<String>,
+}
+
+/// Checks operations against the allow-list of their client, keeping the result for operations
+/// already seen
+pub(crate) struct FieldAllowList {
+    mode: FieldAllowListMode,
+    client_claim: String,
+    default_deny: bool,
+    clients: HashMap<String, HashSet<String>>,
+    /// checks, by client name, query and parser limits
+    cache: Mutex<LruCache<(String, String, ParserLimits), Option<Check>>>,
+}
+
+impl FieldAllowList {
+    /// Creates the allow-list from the plugin configuration, if clients are restricted
+    pub(crate) fn from_configuration(
+        configuration: &Configuration,
+    ) -> Result<Option<Self>, BoxError> {
+        let Some(config) = configuration.apollo_plugins.plugins.get(PLUGIN_NAME) else {
+            return Ok(None);
+        };
+        let config: FieldAllowListConfig = serde_json::from_value(config.clone())
+            .map_err(|e| format!("invalid {PLUGIN_NAME} configuration: {e}"))?;
+        if config.clients.is_empty() && !config.default_deny {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            mode: config.mode,
+            client_claim: config.client_claim,
+            default_deny: config.default_deny,
+            clients: config
+                .clients
+                .into_iter()
+                .map(|(client, coordinates)| (client, coordinates.into_iter().collect()))
+                .collect(),
+            cache: Mutex::new(LruCache::new(
+                configuration
+                    .supergraph
+                    .query_planning
+                    .cache
+                    .in_memory
+                    .limit,
+            )),
+        }))
+    }
+
+    /// Returns the operation without the fields that are not allowed for the client in redact
+    /// mode, `None` if all its fields are allowed, or the errors if the operation is rejected.
+    /// The operation is parsed with the parser limits of the client
+    pub(crate) fn check(
+        &self,
+        schema: &Schema,
+        context: &Context,
+        query: &str,
+        parser_limits: ParserLimits,
+    ) -> Result<Option<String>, Vec<graphql::Error>> {
+        let client_name = self.client_name(context);
+        let Some((client_name, allowed)) = client_name
+            .as_ref()
+            .and_then(|client_name| self.clients.get_key_value(client_name))
+        else {
+            if !self.default_deny {
+                return Ok(None);
+            }
+            record(OTHER_CLIENTS, "rejected");
+            let message = match client_name {
+                Some(client_name) => format!("client `{client_name}` is not allowed"),
+                None => String::from("operations without an authenticated client are not allowed"),
+            };
+            return Err(vec![graphql::Error::builder()
+                .message(message)
+                .extension_code(FIELD_NOT_ALLOWED)
+                .build()]);
+        };
+
+        let key = (client_name.clone(), query.to_string(), parser_limits);
+        let cached = self.cache.lock().get(&key).cloned();
+        let check = match cached {
+            Some(check) => check,
+            None => {
+                let check = check_document(schema, allowed, query, parser_limits);
+                self.cache.lock().put(key, check.clone());
+                check
+            }
+        };
+        let Some(check) = check else {
+            return Ok(None);
+        };
+
+        match (self.mode, check.redacted_query) {
+            (FieldAllowListMode::Redact, Some(redacted_query)) => {
+                tracing::debug!(
+                    "removed fields not allowed for client {client_name}: {:?}",
+                    check.disallowed
+                );
+                record(client_name, "redacted");
+                Ok(Some(redacted_query))
+            }
+            _ => {
+                record(client_name, "rejected");
+                Err(check
+                    .disallowed
+                    .into_iter()
+                    .map(|coordinate| {
+                        graphql::Error::builder()
+                            .message(format!(
+                                "field `{coordinate}` is not allowed for client `{client_name}`"
+                            ))
+                            .extension_code(FIELD_NOT_ALLOWED)
+                            .build()
+                    })
+                    .collect())
+            }
+        }
+    }
+
+    /// Name of the client in the verified JWT claims
+    fn client_name(&self, context: &Context) -> Option<String> {
+        let claims = context.get_json_value(APOLLO_AUTHENTICATION_JWT_CLAIMS)?;
+        claims
+            .as_object()?
+            .get(self.client_claim.as_str())?
+            .as_str()
+            .map(str::to_string)
+    }
+}
+
+fn check_document(
+    schema: &Schema,
+    allowed: &HashSet<String>,
+    query: &str,
+    parser_limits: ParserLimits,
+) -> Option<Check> {
+    // invalid operations are left as is, they will be rejected by validation
+    let document = apollo_compiler::parser::Parser::new()
+        .recursion_limit(parser_limits.max_recursion)
+        .token_limit(parser_limits.max_tokens)
+        .parse_ast(query, "query.graphql")
+        .ok()?;
+
+    let mut visitor = AllowListVisitor {
+        schema,
+        allowed,
+        fragments: transform::collect_fragments(&document),
+        disallowed: BTreeSet::new(),
+    };
+    let mut redacted = transform::document(&mut visitor, &document).ok()?;
+    if visitor.disallowed.is_empty() {
+        return None;
+    }
+
+    // an operation is removed if none of its fields are allowed
+    let operations = |document: &ast::Document| {
+        document
+            .definitions
+            .iter()
+            .filter(|definition| matches!(definition, ast::Definition::OperationDefinition(_)))
+            .count()
+    };
+    let redacted_query = (operations(&redacted) == operations(&document)).then(|| {
+        remove_unused_variables(&mut redacted);
+        redacted.to_string()
+    });
+    Some(Check {
+        redacted_query,
+        disallowed: visitor.disallowed.into_iter().collect(),
+    })
+}
+
+fn record(client_name: &str, action: &'static str) {
+    u64_counter!(
+        "apollo.router.operations.field_allow_list",
+        "Number of operations selecting fields that are not allowed for their client",
+        1,
+        "client.name" = client_name.to_string(),
+        "action" = action
+    );
+}
+
+/// Removes the fields that are not allowed, and the selections left empty
+struct AllowListVisitor<'a> {
+    schema: &'a Schema,
+    allowed: &'a HashSet<String>,
+    fragments: HashMap<&'a Name, &'a ast::FragmentDefinition>,
+    disallowed: BTreeSet<String>,
+}
+
+impl<'a> AllowListVisitor<'a> {
+    fn is_allowed(&self, parent_type: &str, field_name: &str) -> bool {
+        // `__typename` is always allowed. The introspection fields `__schema` and `__type` must be
+        // allowed like other fields, and once they are, so are the fields of introspection types
+        field_name == "__typename"
+            || parent_type.starts_with("__")
+            || self.allowed.contains(parent_type)
+            || self
+                .allowed
+                .contains(&format!("{parent_type}.{field_name}"))
+    }
+}
+
+impl<'a> transform::Visitor for AllowListVisitor<'a> {
+    fn field(
+        &mut self,
+        parent_type: &str,
+        field_def: &ast::FieldDefinition,
+        node: &ast::Field,
+    ) -> Result<Option<ast::Field>, BoxError> {
+        if self.is_allowed(parent_type, &node.name) {
+            transform::field(self, field_def, node)
+        } else {
+            self.disallowed
+                .insert(format!("{parent_type}.{}", node.name));
+            Ok(None)
+        }
+    }
+
+    fn fragment_definition(
+        &mut self,
+        node: &ast::FragmentDefinition,
+    ) -> Result<Option<ast::FragmentDefinition>, BoxError> {
+        let res = transform::fragment_definition(self, node);
+        if let Ok(None) = res {
+            self.fragments.remove(&node.name);
+        }
+        res
+    }
+
+    fn fragment_spread(
+        &mut self,
+        node: &ast::FragmentSpread,
+    ) -> Result<Option<ast::FragmentSpread>, BoxError> {
+        if self.fragments.contains_key(&node.fragment_name) {
+            transform::fragment_spread(self, node)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn schema(&self) -> &Schema {
+        self.schema
+    }
+}
+
+/// Removes the variable definitions that are not used anymore once fields were removed, since
+/// unused variables fail validation
+fn remove_unused_variables(document: &mut ast::Document) {
+    let fragments: HashMap<Name, ast::FragmentDefinition> = transform::collect_fragments(document)
+        .into_iter()
+        .map(|(name, fragment)| (name.clone(), fragment.clone()))
+        .collect();
+    for definition in &mut document.definitions {
+        if let ast::Definition::OperationDefinition(operation) = definition {
+            let mut used = HashSet::new();
+            collect_directive_variables(&operation.directives, &mut used);
+            collect_selection_set_variables(
+                &operation.selection_set,
+                &fragments,
+                &mut HashSet::new(),
+                &mut used,
+            );
+            operation
+                .make_mut()
+                .variables
+                .retain(|variable| used.contains(&variable.name));
+        }
+    }
+}
+
+fn collect_selection_set_variables<'a>(
+    selection_set: &'a [ast::Selection],
+    fragments: &'a HashMap<Name, ast::FragmentDefinition>,
+    visited_fragments: &mut HashSet<&'a Name>,
+    used: &mut HashSet<Name>,
+) {
+    for selection in selection_set {
+        match selection {
+            ast::Selection::Field(field) => {
+                for argument in &field.arguments {
+                    collect_value_variables(&argument.value, used);
+                }
+                collect_directive_variables(&field.directives, used);
+                collect_selection_set_variables(
+                    &field.selection_set,
+                    fragments,
+                    visited_fragments,
+                    used,
+                );
+            }
+            ast::Selection::FragmentSpread(spread) => {
+                collect_directive_variables(&spread.directives, used);
+                if let Some((name, fragment)) = fragments.get_key_value(&spread.fragment_name) {
+                    if visited_fragments.insert(name) {
+                        collect_directive_variables(&fragment.directives, used);
+                        collect_selection_set_variables(
+                            &fragment.selection_set,
+                            fragments,
+                            visited_fragments,
+                            used,
+                        );
+                    }
+                }
+            }
+            ast::Selection::InlineFragment(fragment) => {
+                collect_directive_variables(&fragment.directives, used);
+                collect_selection_set_variables(
+                    &fragment.selection_set,
+                    fragments,
+                    visited_fragments,
+                    used,
+                );
+            }
+        }
+    }
+}
+
+fn collect_directive_variables(directives: &ast::DirectiveList, used: &mut HashSet<Name>) {
+    for directive in directives.iter() {
+        for argument in &directive.arguments {
+            collect_value_variables(&argument.value, used);
+        }
+    }
+}
+
+fn collect_value_variables(value: &ast::Value, used: &mut HashSet<Name>) {
+    match value {
+        ast::Value::Variable(name) => {
+            used.insert(name.clone());
+        }
+        ast::Value::List(items) => {
+            for item in items {
+                collect_value_variables(item, used);
+            }
+        }
+        ast::Value::Object(fields) => {
+            for (_, value) in fields {
+                collect_value_variables(value, used);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The checks happen in the query analysis layer, the plugin checks that the allowed coordinates
+/// match the schema when the router starts
+struct FieldAllowListPlugin;
+
+#[async_trait::async_trait]
+impl Plugin for FieldAllowListPlugin {
+    type Config = FieldAllowListConfig;
+
+    async fn new(init: PluginInit<Self::Config>) -> Result<Self, BoxError> {
+        for (client, coordinates) in &init.config.clients {
+            for coordinate in coordinates {
+                validate_coordinate(&init.supergraph_schema, coordinate)
+                    .map_err(|e| format!("field allow-list of client '{client}': {e}"))?;
+            }
+        }
+        Ok(FieldAllowListPlugin)
+    }
+}
+
+/// Checks that a type or field coordinate exists in the schema
+fn validate_coordinate(schema: &Schema, coordinate: &str) -> Result<(), String> {
+    match coordinate.split_once('.') {
+        Some((type_name, field_name)) => schema
+            .type_field(type_name, field_name)
+            .map(|_| ())
+            .map_err(|_| format!("no field `{field_name}` in type `{type_name}`")),
+        None if schema.types.contains_key(coordinate) => Ok(()),
+        None => Err(format!("no type `{coordinate}`")),
+    }
+}
+
+register_plugin!("apollo", "field_allow_list", FieldAllowListPlugin);
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use serde_json::json;
+
+    use super::*;
+
+    const SCHEMA: &str = r#"
+        type Query {
+            product(id: ID!): Product
+            products(first: Int): [Product]
+            orders: [Order]
+        }
+
+        type Product {
+            id: ID!
+            name: String
+            price(currency: String): Float
+        }
+
+        type Order {
+            id: ID!
+            total: Float
+        }
+    "#;
+
+    fn allow_list(mode: &str) -> FieldAllowList {
+        allow_list_with(json!({
+            "mode": mode,
+            "clients": {
+                "partner": ["Query.product", "Query.products", "Product.id", "Product.name"],
+                "internal": ["Query", "Product", "Order"]
+            }
+        }))
+    }
+
+    fn allow_list_with(config: serde_json::Value) -> FieldAllowList {
+        let configuration =
+            Configuration::from_str(&json!({ "field_allow_list": config }).to_string()).unwrap();
+        FieldAllowList::from_configuration(&configuration)
+            .unwrap()
+            .unwrap()
+    }
+
+    fn limits() -> ParserLimits {
+        Configuration::default().limits.parser_limits(None)
+    }
+
+    fn schema() -> Schema {
+        Schema::parse_and_validate(SCHEMA, "schema.graphql")
+            .unwrap()
+            .into_inner()
+    }
+
+    fn context(client_name: &str) -> Context {
+        let context = Context::new();
+        context
+            .insert(
+                APOLLO_AUTHENTICATION_JWT_CLAIMS,
+                json!({ "client_name": client_name }),
+            )
+            .unwrap();
+        context
+    }
+
+    #[test]
+    fn it_rejects_fields_not_allowed_for_the_client() {
+        let allow_list = allow_list("reject");
+        let schema = schema();
+        let query =
+            "{ product(id: 1) { id name ...P } orders { id } } fragment P on Product { price }";
+
+        let errors = allow_list
+            .check(&schema, &context("partner"), query, limits())
+            .unwrap_err();
+        assert_eq!(
+            errors
+                .iter()
+                .map(|error| error.message.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "field `Product.price` is not allowed for client `partner`",
+                "field `Query.orders` is not allowed for client `partner`"
+            ]
+        );
+        assert!(allow_list
+            .check(
+                &schema,
+                &context("partner"),
+                "{ products { id __typename } }",
+                limits()
+            )
+            .unwrap()
+            .is_none());
+
+        // type coordinates allow all fields, and unlisted clients are not restricted
+        assert!(allow_list
+            .check(&schema, &context("internal"), query, limits())
+            .unwrap()
+            .is_none());
+        assert!(allow_list
+            .check(&schema, &context("other"), query, limits())
+            .unwrap()
+            .is_none());
+        assert!(allow_list
+            .check(&schema, &Context::new(), query, limits())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn it_applies_the_allow_list_to_introspection() {
+        let schema = schema();
+        let query = "{ __schema { queryType { name } } __type(name: \"Product\") { name } }";
+
+        let errors = allow_list("reject")
+            .check(&schema, &context("partner"), query, limits())
+            .unwrap_err();
+        assert_eq!(
+            errors
+                .iter()
+                .map(|error| error.message.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "field `Query.__schema` is not allowed for client `partner`",
+                "field `Query.__type` is not allowed for client `partner`"
+            ]
+        );
+
+        let allow_list = allow_list_with(json!({
+            "clients": { "partner": ["Query.__schema", "Query.__type"] }
+        }));
+        assert!(allow_list
+            .check(&schema, &context("partner"), query, limits())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn it_redacts_fields_not_allowed_for_the_client() {
+        let allow_list = allow_list("redact");
+        let schema = schema();
+
+        let query = allow_list
+            .check(
+                &schema,
+                &context("partner"),
+                "query($currency: String) { product(id: 1) { id ...P } orders { id } }
+                fragment P on Product { price(currency: $currency) }",
+                limits(),
+            )
+            .unwrap()
+            .unwrap();
+        assert!(!query.contains("price"));
+        assert!(!query.contains("orders"));
+        assert!(!query.contains("$currency"));
+        assert!(!query.contains("fragment"));
+        assert!(query.contains("product(id: 1)"));
+
+        // operations where no field is left are rejected
+        assert!(allow_list
+            .check(&schema, &context("partner"), "{ orders { id } }", limits())
+            .is_err());
+    }
+
+    #[test]
+    fn it_identifies_clients_with_the_verified_claims() {
+        let allow_list = allow_list_with(json!({
+            "client_claim": "azp",
+            "clients": { "partner": ["Query.product", "Product.id"] }
+        }));
+        let schema = schema();
+        let query = "{ orders { id } }";
+
+        // the client name header is not trusted
+        let context = Context::new();
+        context
+            .insert(
+                crate::plugins::telemetry::CLIENT_NAME,
+                "partner".to_string(),
+            )
+            .unwrap();
+        assert!(allow_list
+            .check(&schema, &context, query, limits())
+            .unwrap()
+            .is_none());
+
+        let context = Context::new();
+        context
+            .insert(
+                APOLLO_AUTHENTICATION_JWT_CLAIMS,
+                json!({ "azp": "partner" }),
+            )
+            .unwrap();
+        assert!(allow_list
+            .check(&schema, &context, query, limits())
+            .is_err());
+    }
+
+    #[test]
+    fn it_rejects_unlisted_clients_by_default_if_configured() {
+        let allow_list = allow_list_with(json!({
+            "default_deny": true,
+            "clients": { "partner": ["Query.product", "Product.id"] }
+        }));
+        let schema = schema();
+        let query = "{ product(id: 1) { id } }";
+
+        assert!(allow_list
+            .check(&schema, &context("partner"), query, limits())
+            .unwrap()
+            .is_none());
+        let errors = allow_list
+            .check(&schema, &context("other"), query, limits())
+            .unwrap_err();
+        assert_eq!(errors[0].message, "client `other` is not allowed");
+        let errors = allow_list
+            .check(&schema, &Context::new(), query, limits())
+            .unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "operations without an authenticated client are not allowed"
+        );
+    }
+
+    #[test]
+    fn it_parses_operations_with_the_parser_limits_of_the_client() {
+        let allow_list = allow_list("reject");
+        let schema = schema();
+        let query = "{ product(id: 1) { id name price } }";
+
+        assert!(allow_list
+            .check(&schema, &context("partner"), query, limits())
+            .is_err());
+        // operations over the parser limits are left to validation, which rejects them
+        let limits = ParserLimits {
+            max_recursion: 500,
+            max_tokens: 5,
+        };
+        assert!(allow_list
+            .check(&schema, &context("partner"), query, limits)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn it_reports_invalid_configurations() {
+        let mut configuration = Configuration::default();
+        configuration.apollo_plugins.plugins.insert(
+            PLUGIN_NAME.to_string(),
+            json!({ "clients": { "partner": "Query.product" } }),
+        );
+        assert!(FieldAllowList::from_configuration(&configuration).is_err());
+    }
+
+    #[test]
+    fn it_checks_coordinates_against_the_schema() {
+        let schema = schema();
+        assert!(validate_coordinate(&schema, "Product").is_ok());
+        assert!(validate_coordinate(&schema, "Product.price").is_ok());
+        assert!(validate_coordinate(&schema, "Product.cost").is_err());
+        assert!(validate_coordinate(&schema, "Customer").is_err());
+    }
+}
@@ -0,0 +1,289 @@
+//! Restriction of the client variables forwarded to subgraphs
+//!
+//! Subgraph fetches carry the client variables used by the subgraph operation. Rhai scripts,
+//! coprocessors and plugins can add more variables to subgraph requests, and when a subgraph logs
+//! its inputs verbatim, those can include data it should never see. This plugin restricts the
+//! client variables sent to each subgraph, beyond the ones its operation uses, to an allow-list.
+//! Variables generated by the router, like the entity representations, are always forwarded.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde::Serialize;
+use tower::BoxError;
+use tower::ServiceExt;
+
+use crate::configuration::subgraph::SubgraphConfiguration;
+use crate::json_ext::Object;
+use crate::plugin::Plugin;
+use crate::plugin::PluginInit;
+use crate::register_plugin;
+use crate::services::subgraph;
+
+/// Forwarded variables configuration
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct ForwardedVariablesConfig {
+    /// Client variables forwarded to each subgraph
+    pub(crate) subgraph: SubgraphConfiguration<ForwardedVariables>,
+}
+
+/// Client variables forwarded to a subgraph
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct ForwardedVariables {
+    /// Names of the client variables that can be sent to the subgraph, in addition to the
+    /// variables its operation uses. Other client variables are removed from the subgraph
+    /// requests and logged. All variables are forwarded if absent
+    pub(crate) allowed: Option<Vec<String>>,
+}
+
+/// Variables generated by the router for subgraph requests, which are not client variables
+const ROUTER_VARIABLES: &[&str] = &["representations"];
+
+struct ForwardedVariablesPlugin {
+    config: ForwardedVariablesConfig,
+}
+
+#[async_trait::async_trait]
+impl Plugin for ForwardedVariablesPlugin {
+    type Config = ForwardedVariablesConfig;
+
+    async fn new(init: PluginInit<Self::Config>) -> Result<Self, BoxError> {
+        Ok(Self {
+            config: init.config,
+        })
+    }
+
+    fn subgraph_service(&self, name: &str, service: subgraph::BoxService) -> subgraph::BoxService {
+        let Some(allowed) = &self.config.subgraph.get(name).allowed else {
+            return service;
+        };
+        let allowed: Arc<HashSet<String>> = Arc::new(allowed.iter().cloned().collect());
+
+        let subgraph_name = name.to_string();
+        service
+            .map_request(move |mut request: subgraph::Request| {
+                let removed = remove_forbidden_variables(
+                    &request.supergraph_request.body().variables,
+                    &mut request.subgraph_request.body_mut().variables,
+                    request.variable_usages.as_deref(),
+                    &allowed,
+                );
+                if !removed.is_empty() {
+                    tracing::warn!(
+                        subgraph = %subgraph_name,
+                        variables = ?removed,
+                        "client variables not allowed for the subgraph were removed from its request"
+                    );
+                    u64_counter!(
+                        "apollo.router.operations.subgraph.forbidden_variables",
+                        "Number of client variables not allowed for a subgraph removed from its requests",
+                        removed.len() as u64,
+                        "subgraph.name" = subgraph_name.clone()
+                    );
+                }
+                request
+            })
+            .boxed()
+    }
+}
+
+/// Removes the client variables that are not allowed from the subgraph variables, unless the
+/// subgraph operation uses them, and returns their names. All variables are considered used if
+/// the variables used by the query plan node are unknown
+fn remove_forbidden_variables(
+    client_variables: &Object,
+    subgraph_variables: &mut Object,
+    variable_usages: Option<&[Arc<str>]>,
+    allowed: &HashSet<String>,
+) -> Vec<String> {
+    let Some(variable_usages) = variable_usages else {
+        return Vec::new();
+    };
+    let mut removed = Vec::new();
+    subgraph_variables.retain(|name, _| {
+        let name = name.as_str();
+        let forwarded = ROUTER_VARIABLES.contains(&name)
+            || !client_variables.contains_key(name)
+            || allowed.contains(name)
+            || variable_usages.iter().any(|usage| usage.as_ref() == name);
+        if !forwarded {
+            removed.push(name.to_string());
+        }
+        forwarded
+    });
+    removed
+}
+
+register_plugin!("apollo", "forwarded_variables", ForwardedVariablesPlugin);
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use serde_json_bytes::json;
+
+    use super::*;
+    use crate::graphql;
+    use crate::metrics::FutureMetricsExt;
+    use crate::plugin::test::MockSubgraphService;
+
+    /// Returns the variables sent to the subgraph
+    async fn call(
+        config: serde_json::Value,
+        subgraph_name: &str,
+        variable_usages: &[&str],
+    ) -> Object {
+        let plugin = ForwardedVariablesPlugin::new(PluginInit::fake_new(
+            serde_json::from_value(config).unwrap(),
+            Default::default(),
+        ))
+        .await
+        .unwrap();
+
+        let forwarded = Arc::new(Mutex::new(Object::new()));
+        let forwarded_in_mock = forwarded.clone();
+        let mut mock_service = MockSubgraphService::new();
+        mock_service.expect_call().returning(move |request| {
+            *forwarded_in_mock.lock().unwrap() = request.subgraph_request.body().variables.clone();
+            Ok(subgraph::Response::fake_builder().build())
+        });
+
+        let client_request = graphql::Request::fake_builder()
+            .query("query($id: ID!, $email: String, $locale: String) { user(id: $id, email: $email) { name(locale: $locale) } }")
+            .variable("id", "1")
+            .variable("email", "user@example.com")
+            .variable("locale", "fr")
+            .build();
+        // the variables of the subgraph request can be changed before the plugin, by a
+        // coprocessor for example
+        let mut request = subgraph::Request::fake_builder()
+            .supergraph_request(Arc::new(
+                http::Request::builder().body(client_request).unwrap(),
+            ))
+            .subgraph_request(
+                http::Request::builder()
+                    .body(
+                        graphql::Request::fake_builder()
+                            .variable("id", "1")
+                            .variable("email", "user@example.com")
+                            .variable("locale", "fr")
+                            .variable("representations", json!([{ "__typename": "User" }]))
+                            .build(),
+                    )
+                    .unwrap(),
+            )
+            .build();
+        request.variable_usages = Some(
+            variable_usages
+                .iter()
+                .map(|usage| (*usage).into())
+                .collect(),
+        );
+
+        plugin
+            .subgraph_service(subgraph_name, mock_service.boxed())
+            .oneshot(request)
+            .await
+            .unwrap();
+        let variables = forwarded.lock().unwrap().clone();
+        variables
+    }
+
+    #[tokio::test]
+    async fn it_removes_client_variables_absent_from_the_allow_list() {
+        async {
+            let config = serde_json::json!({
+                "subgraph": {
+                    "subgraphs": {
+                        "accounts": { "allowed": ["locale"] }
+                    }
+                }
+            });
+
+            let variables = call(config.clone(), "accounts", &["id"]).await;
+            assert!(variables.contains_key("id"));
+            assert!(variables.contains_key("locale"));
+            // variables generated by the router are always forwarded
+            assert!(variables.contains_key("representations"));
+            assert!(!variables.contains_key("email"));
+            assert_counter!(
+                "apollo.router.operations.subgraph.forbidden_variables",
+                1,
+                "subgraph.name" = "accounts"
+            );
+
+            let variables = call(config, "products", &[]).await;
+            assert!(variables.contains_key("email"));
+        }
+        .with_metrics()
+        .await;
+    }
+
+    #[tokio::test]
+    async fn it_forwards_the_variables_used_by_the_subgraph_operation() {
+        let variables = call(
+            serde_json::json!({
+                "subgraph": {
+                    "subgraphs": {
+                        "accounts": { "allowed": [] }
+                    }
+                }
+            }),
+            "accounts",
+            &["id", "email"],
+        )
+        .await;
+        assert!(variables.contains_key("id"));
+        assert!(variables.contains_key("email"));
+        assert!(!variables.contains_key("locale"));
+    }
+
+    #[tokio::test]
+    async fn it_applies_the_allow_list_of_all_subgraphs() {
+        let variables = call(
+            serde_json::json!({
+                "subgraph": {
+                    "all": { "allowed": [] },
+                    "subgraphs": {
+                        "accounts": { "allowed": ["email"] }
+                    }
+                }
+            }),
+            "products",
+            &[],
+        )
+        .await;
+        assert!(!variables.contains_key("id"));
+        assert!(!variables.contains_key("email"));
+        assert!(variables.contains_key("representations"));
+    }
+
+    #[test]
+    fn it_keeps_router_variables_and_variables_of_unknown_usage() {
+        // a client variable named like a router variable does not remove it
+        let client_variables = json!({ "email": "user@example.com", "representations": [] });
+        let mut subgraph_variables = client_variables.as_object().unwrap().clone();
+        let removed = remove_forbidden_variables(
+            client_variables.as_object().unwrap(),
+            &mut subgraph_variables,
+            Some(&[][..]),
+            &HashSet::new(),
+        );
+        assert_eq!(removed, vec!["email".to_string()]);
+        assert!(subgraph_variables.contains_key("representations"));
+
+        let mut subgraph_variables = client_variables.as_object().unwrap().clone();
+        let removed = remove_forbidden_variables(
+            client_variables.as_object().unwrap(),
+            &mut subgraph_variables,
+            None,
+            &HashSet::new(),
+        );
+        assert!(removed.is_empty());
+        assert_eq!(subgraph_variables.len(), 2);
+    }
+}
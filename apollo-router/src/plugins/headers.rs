@@ -38,6 +38,8 @@ use crate::plugin::serde::deserialize_option_header_value;
 use crate::plugin::serde::deserialize_regex;
 use crate::plugin::Plugin;
 use crate::plugin::PluginInit;
+use crate::plugins::telemetry::config_new::conditions::Condition;
+use crate::plugins::telemetry::config_new::selectors::SubgraphSelector;
 use crate::register_plugin;
 use crate::services::subgraph;
 use crate::services::SubgraphRequest;
@@ -51,6 +53,34 @@ struct HeadersLocation {
     request: Vec<Operation>,
     // Propagate/Insert/Remove headers from response
     // response: Option<Operation>
+    /// Condition to apply the rules (default: always applied)
+    #[serde(default)]
+    condition: Option<Condition<SubgraphSelector>>,
+}
+
+/// Header operations, applied to the requests matching the condition
+#[derive(Clone)]
+struct Rules {
+    condition: Option<Condition<SubgraphSelector>>,
+    operations: Vec<Operation>,
+}
+
+impl From<&HeadersLocation> for Rules {
+    fn from(location: &HeadersLocation) -> Self {
+        Self {
+            condition: location.condition.clone(),
+            operations: location.request.clone(),
+        }
+    }
+}
+
+impl Rules {
+    fn matches(&self, request: &SubgraphRequest) -> bool {
+        self.condition
+            .clone()
+            .map(|mut condition| condition.evaluate_request(request) == Some(true))
+            .unwrap_or(true)
+    }
 }
 
 #[derive(Clone, JsonSchema, Deserialize)]
@@ -191,8 +221,8 @@ struct Config {
 }
 
 struct Headers {
-    all_operations: Arc<Vec<Operation>>,
-    subgraph_operations: HashMap<String, Arc<Vec<Operation>>>,
+    all_rules: Arc<Vec<Rules>>,
+    subgraph_rules: HashMap<String, Arc<Vec<Rules>>>,
     reserved_headers: Arc<HashSet<&'static HeaderName>>,
 }
 
@@ -201,26 +231,21 @@ impl Plugin for Headers {
     type Config = Config;
 
     async fn new(init: PluginInit<Self::Config>) -> Result<Self, BoxError> {
-        let operations: Vec<Operation> = init
-            .config
-            .all
-            .as_ref()
-            .map(|a| a.request.clone())
-            .unwrap_or_default();
-        let subgraph_operations = init
+        let rules: Vec<Rules> = init.config.all.iter().map(Rules::from).collect();
+        let subgraph_rules = init
             .config
             .subgraphs
             .iter()
-            .map(|(subgraph_name, op)| {
-                let mut operations = operations.clone();
-                operations.append(&mut op.request.clone());
-                (subgraph_name.clone(), Arc::new(operations))
+            .map(|(subgraph_name, location)| {
+                let mut rules = rules.clone();
+                rules.push(location.into());
+                (subgraph_name.clone(), Arc::new(rules))
             })
             .collect();
 
         Ok(Headers {
-            all_operations: Arc::new(operations),
-            subgraph_operations,
+            all_rules: Arc::new(rules),
+            subgraph_rules,
             reserved_headers: Arc::new(RESERVED_HEADERS.iter().collect()),
         })
     }
@@ -228,10 +253,10 @@ impl Plugin for Headers {
     fn subgraph_service(&self, name: &str, service: subgraph::BoxService) -> subgraph::BoxService {
         ServiceBuilder::new()
             .layer(HeadersLayer::new(
-                self.subgraph_operations
+                self.subgraph_rules
                     .get(name)
                     .cloned()
-                    .unwrap_or_else(|| self.all_operations.clone()),
+                    .unwrap_or_else(|| self.all_rules.clone()),
                 self.reserved_headers.clone(),
             ))
             .service(service)
@@ -240,17 +265,14 @@ impl Plugin for Headers {
 }
 
 struct HeadersLayer {
-    operations: Arc<Vec<Operation>>,
+    rules: Arc<Vec<Rules>>,
     reserved_headers: Arc<HashSet<&'static HeaderName>>,
 }
 
 impl HeadersLayer {
-    fn new(
-        operations: Arc<Vec<Operation>>,
-        reserved_headers: Arc<HashSet<&'static HeaderName>>,
-    ) -> Self {
+    fn new(rules: Arc<Vec<Rules>>, reserved_headers: Arc<HashSet<&'static HeaderName>>) -> Self {
         Self {
-            operations,
+            rules,
             reserved_headers,
         }
     }
@@ -262,14 +284,14 @@ impl<S> Layer<S> for HeadersLayer {
     fn layer(&self, inner: S) -> Self::Service {
         HeadersService {
             inner,
-            operations: self.operations.clone(),
+            rules: self.rules.clone(),
             reserved_headers: self.reserved_headers.clone(),
         }
     }
 }
 struct HeadersService<S> {
     inner: S,
-    operations: Arc<Vec<Operation>>,
+    rules: Arc<Vec<Rules>>,
     reserved_headers: Arc<HashSet<&'static HeaderName>>,
 }
 
@@ -317,7 +339,13 @@ impl<S> HeadersService<S> {
     fn modify_request(&self, req: &mut SubgraphRequest) {
         let mut already_propagated: HashSet<&str> = HashSet::new();
 
-        for operation in &*self.operations {
+        let operations = self
+            .rules
+            .iter()
+            .filter(|rules| rules.matches(req))
+            .flat_map(|rules| rules.operations.iter())
+            .collect::<Vec<_>>();
+        for operation in operations {
             match operation {
                 Operation::Insert(insert_config) => match insert_config {
                     Insert::Static(static_insert) => {
@@ -590,7 +618,7 @@ mod test {
             .returning(example_response);
 
         let mut service = HeadersLayer::new(
-            Arc::new(vec![Operation::Insert(Insert::Static(InsertStatic {
+            rules(vec![Operation::Insert(Insert::Static(InsertStatic {
                 name: "c".try_into()?,
                 value: "d".try_into()?,
             }))]),
@@ -618,7 +646,7 @@ mod test {
             .returning(example_response);
 
         let mut service = HeadersLayer::new(
-            Arc::new(vec![Operation::Insert(Insert::FromContext(
+            rules(vec![Operation::Insert(Insert::FromContext(
                 InsertFromContext {
                     name: "header_from_context".try_into()?,
                     from_context: "my_key".to_string(),
@@ -648,7 +676,7 @@ mod test {
             .returning(example_response);
 
         let mut service = HeadersLayer::new(
-            Arc::new(vec![Operation::Insert(Insert::FromBody(InsertFromBody {
+            rules(vec![Operation::Insert(Insert::FromBody(InsertFromBody {
                 name: "header_from_request".try_into()?,
                 path: JSONQuery::parse(".operationName")?,
                 default: None,
@@ -670,7 +698,7 @@ mod test {
             .returning(example_response);
 
         let mut service = HeadersLayer::new(
-            Arc::new(vec![Operation::Remove(Remove::Named("aa".try_into()?))]),
+            rules(vec![Operation::Remove(Remove::Named("aa".try_into()?))]),
             Arc::new(RESERVED_HEADERS.iter().collect()),
         )
         .layer(mock);
@@ -688,7 +716,7 @@ mod test {
             .returning(example_response);
 
         let mut service = HeadersLayer::new(
-            Arc::new(vec![Operation::Remove(Remove::Matching(Regex::from_str(
+            rules(vec![Operation::Remove(Remove::Matching(Regex::from_str(
                 "a[ab]",
             )?))]),
             Arc::new(RESERVED_HEADERS.iter().collect()),
@@ -717,7 +745,7 @@ mod test {
             .returning(example_response);
 
         let mut service = HeadersLayer::new(
-            Arc::new(vec![Operation::Propagate(Propagate::Matching {
+            rules(vec![Operation::Propagate(Propagate::Matching {
                 matching: Regex::from_str("d[ab]")?,
             })]),
             Arc::new(RESERVED_HEADERS.iter().collect()),
@@ -744,7 +772,7 @@ mod test {
             .returning(example_response);
 
         let mut service = HeadersLayer::new(
-            Arc::new(vec![Operation::Propagate(Propagate::Named {
+            rules(vec![Operation::Propagate(Propagate::Named {
                 named: "da".try_into()?,
                 rename: None,
                 default: None,
@@ -773,7 +801,7 @@ mod test {
             .returning(example_response);
 
         let mut service = HeadersLayer::new(
-            Arc::new(vec![Operation::Propagate(Propagate::Named {
+            rules(vec![Operation::Propagate(Propagate::Named {
                 named: "da".try_into()?,
                 rename: Some("ea".try_into()?),
                 default: None,
@@ -802,7 +830,7 @@ mod test {
             .returning(example_response);
 
         let mut service = HeadersLayer::new(
-            Arc::new(vec![Operation::Propagate(Propagate::Named {
+            rules(vec![Operation::Propagate(Propagate::Named {
                 named: "ea".try_into()?,
                 rename: None,
                 default: Some("defaulted".try_into()?),
@@ -819,7 +847,7 @@ mod test {
     async fn test_propagate_reserved() -> Result<(), BoxError> {
         let service = HeadersService {
             inner: MockSubgraphService::new(),
-            operations: Arc::new(vec![Operation::Propagate(Propagate::Matching {
+            rules: rules(vec![Operation::Propagate(Propagate::Matching {
                 matching: Regex::from_str(".*")?,
             })]),
             reserved_headers: Arc::new(RESERVED_HEADERS.iter().collect()),
@@ -863,6 +891,7 @@ mod test {
             query_hash: Default::default(),
             authorization: Default::default(),
             executable_document: None,
+            variable_usages: None,
         };
         service.modify_request(&mut request);
         let headers = request
@@ -894,7 +923,7 @@ mod test {
     async fn test_propagate_multiple_matching_rules() -> Result<(), BoxError> {
         let service = HeadersService {
             inner: MockSubgraphService::new(),
-            operations: Arc::new(vec![
+            rules: rules(vec![
                 Operation::Propagate(Propagate::Named {
                     named: HeaderName::from_static("dc"),
                     rename: None,
@@ -935,6 +964,7 @@ mod test {
             query_hash: Default::default(),
             authorization: Default::default(),
             executable_document: None,
+            variable_usages: None,
         };
         service.modify_request(&mut request);
         let headers = request
@@ -951,6 +981,60 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_conditional_rules() -> Result<(), BoxError> {
+        let config = serde_yaml::from_str::<Config>(
+            r#"
+        all:
+            request:
+                - insert:
+                    name: "c"
+                    value: "d"
+            condition:
+                eq:
+                    - request_context: "my_key"
+                    - "my_value_from_context"
+        subgraphs:
+            test:
+                request:
+                    - insert:
+                        name: "e"
+                        value: "f"
+                condition:
+                    eq:
+                        - request_context: "my_key"
+                        - "other_value"
+        "#,
+        )?;
+        let plugin = Headers::new(PluginInit::fake_new(config, Default::default())).await?;
+
+        let mut mock = MockSubgraphService::new();
+        mock.expect_call()
+            .times(1)
+            .withf(|request| {
+                request.assert_headers(vec![
+                    ("aa", "vaa"),
+                    ("ab", "vab"),
+                    ("ac", "vac"),
+                    ("c", "d"),
+                ])
+            })
+            .returning(example_response);
+
+        plugin
+            .subgraph_service("test", mock.boxed())
+            .oneshot(example_request())
+            .await?;
+        Ok(())
+    }
+
+    fn rules(operations: Vec<Operation>) -> Arc<Vec<Rules>> {
+        Arc::new(vec![Rules {
+            condition: None,
+            operations,
+        }])
+    }
+
     fn example_response(req: SubgraphRequest) -> Result<SubgraphResponse, BoxError> {
         Ok(SubgraphResponse::new_from_response(
             http::Response::default(),
@@ -998,6 +1082,7 @@ mod test {
             query_hash: Default::default(),
             authorization: Default::default(),
             executable_document: None,
+            variable_usages: None,
         }
     }
 
@@ -1,18 +1,27 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 
+use futures::StreamExt;
 use schemars::JsonSchema;
 use serde::Deserialize;
+use serde_json_bytes::Value;
 use tower::BoxError;
 use tower::ServiceExt;
 
+use crate::graphql;
 use crate::json_ext::Object;
+use crate::layers::ServiceExt as _;
 use crate::plugin::Plugin;
 use crate::plugin::PluginInit;
 use crate::register_plugin;
+use crate::services::execution;
+use crate::services::execution::QueryPlan;
+use crate::services::execution::SubgraphOwnership;
 use crate::services::subgraph;
 use crate::services::SubgraphResponse;
 
 static REDACTED_ERROR_MESSAGE: &str = "Subgraph errors redacted";
+const SERVICE_EXTENSION: &str = "service";
 
 register_plugin!("apollo", "include_subgraph_errors", IncludeSubgraphErrors);
 
@@ -25,10 +34,21 @@ struct Config {
 
     /// Include errors from specific subgraphs
     subgraphs: HashMap<String, bool>,
+
+    /// Add the name of the subgraph that fetched the data at the path of each error to its
+    /// `service` extension, based on the query plan
+    attribution: bool,
+}
+
+impl Config {
+    /// Returns true if the errors of the subgraph are included, false if they are redacted
+    fn includes(&self, subgraph: &str) -> bool {
+        *self.subgraphs.get(subgraph).unwrap_or(&self.all)
+    }
 }
 
 struct IncludeSubgraphErrors {
-    config: Config,
+    config: Arc<Config>,
 }
 
 #[async_trait::async_trait]
@@ -37,14 +57,14 @@ impl Plugin for IncludeSubgraphErrors {
 
     async fn new(init: PluginInit<Self::Config>) -> Result<Self, BoxError> {
         Ok(IncludeSubgraphErrors {
-            config: init.config,
+            config: Arc::new(init.config),
         })
     }
 
     fn subgraph_service(&self, name: &str, service: subgraph::BoxService) -> subgraph::BoxService {
         // Search for subgraph in our configured subgraph map.
         // If we can't find it, use the "all" value
-        if !*self.config.subgraphs.get(name).unwrap_or(&self.config.all) {
+        if !self.config.includes(name) {
             let sub_name_response = name.to_string();
             let sub_name_error = name.to_string();
             return service
@@ -73,6 +93,61 @@ impl Plugin for IncludeSubgraphErrors {
         }
         service
     }
+
+    fn execution_service(&self, service: execution::BoxService) -> execution::BoxService {
+        if !self.config.attribution {
+            return service;
+        }
+        let config = self.config.clone();
+        service
+            .map_future_with_request_data(
+                |request: &execution::Request| request.query_plan.clone(),
+                move |query_plan: Arc<QueryPlan>, future| {
+                    let config = config.clone();
+                    async move {
+                        let mut response: execution::Response = future.await?;
+                        response.response = response.response.map(move |stream| {
+                            stream
+                                .map(move |mut response: graphql::Response| {
+                                    if response.errors.iter().any(|error| error.path.is_some()) {
+                                        // computed once per query plan, and kept in the plan cache
+                                        attribute_errors(
+                                            query_plan.subgraph_ownership(),
+                                            &config,
+                                            &mut response.errors,
+                                        );
+                                    }
+                                    response
+                                })
+                                .boxed()
+                        });
+                        Ok(response)
+                    }
+                },
+            )
+            .boxed()
+    }
+}
+
+/// Adds the name of the subgraph owning the path of each error to its `service` extension,
+/// keeping the extension when the error already has one, like subgraph request errors. Errors of
+/// subgraphs whose errors are redacted are not attributed, so that their names are not exposed
+fn attribute_errors(ownership: &SubgraphOwnership, config: &Config, errors: &mut [graphql::Error]) {
+    for error in errors {
+        if error.extensions.contains_key(SERVICE_EXTENSION) {
+            continue;
+        }
+        if let Some(subgraph) = error
+            .path
+            .as_ref()
+            .and_then(|path| ownership.subgraph(path))
+            .filter(|subgraph| config.includes(subgraph))
+        {
+            error
+                .extensions
+                .insert(SERVICE_EXTENSION, Value::String(subgraph.into()));
+        }
+    }
 }
 
 #[cfg(test)]
@@ -227,7 +302,9 @@ mod test {
         let supergraph_creator = builder.build().await.expect("should build");
 
         RouterCreator::new(
-            QueryAnalysisLayer::new(supergraph_creator.schema(), Default::default()).await,
+            QueryAnalysisLayer::new(supergraph_creator.schema(), Default::default())
+                .await
+                .unwrap(),
             Arc::new(PersistedQueryLayer::new(&Default::default()).await.unwrap()),
             Arc::new(supergraph_creator),
             Arc::new(Configuration::default()),
@@ -341,4 +418,52 @@ mod test {
         let router = build_mock_router(plugin).await;
         execute_router_test(ERROR_ACCOUNT_QUERY, &REDACTED_ACCOUNT_RESPONSE, router).await;
     }
+
+    #[test]
+    fn it_attributes_errors_to_the_fetching_subgraph() {
+        let root: crate::query_planner::PlanNode =
+            serde_json::from_str(include_str!("../query_planner/testdata/query_plan.json"))
+                .unwrap();
+        let query_plan = QueryPlan::fake_builder().root(root).build();
+
+        let error = |path: &str| {
+            graphql::Error::builder()
+                .message("error")
+                .path(crate::json_ext::Path::from(path))
+                .extension_code("ERROR")
+                .build()
+        };
+        let mut errors = vec![
+            error("topProducts/0/name"),
+            error("topProducts/1/title"),
+            error("me"),
+            graphql::Error::builder()
+                .message("error")
+                .path(crate::json_ext::Path::from("topProducts/0/name"))
+                .extension_code("ERROR")
+                .extension("service", "accounts")
+                .build(),
+        ];
+        let config = Config {
+            all: true,
+            subgraphs: [("books".to_string(), false)].into_iter().collect(),
+            attribution: true,
+        };
+        attribute_errors(query_plan.subgraph_ownership(), &config, &mut errors);
+
+        let services: Vec<Option<&Value>> = errors
+            .iter()
+            .map(|error| error.extensions.get("service"))
+            .collect();
+        assert_eq!(
+            services,
+            vec![
+                Some(&Value::from("product")),
+                // the errors of books are redacted
+                None,
+                None,
+                Some(&Value::from("accounts")),
+            ]
+        );
+    }
 }
@@ -0,0 +1,421 @@
+//! Strict validation of inbound request bodies
+//!
+//! The JSON parser of the router is lenient in ways other parsers are not: invalid UTF-8 in
+//! GET query strings is replaced, duplicate object keys keep the last value, and nesting is
+//! only limited to protect the stack. A subgraph or proxy parsing the same payload differently
+//! can see different variables than the router validated, so these can be rejected before the
+//! request is parsed.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use displaydoc::Display;
+use schemars::JsonSchema;
+use serde::de::DeserializeSeed;
+use serde::de::MapAccess;
+use serde::de::SeqAccess;
+use serde::de::Visitor;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+
+/// Strict validation of inbound request bodies
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct BodyValidation {
+    /// What to do with requests with invalid bodies (default: disabled).
+    /// Bodies, and GET query strings, are invalid if they are not valid UTF-8, if an object of
+    /// the variables has duplicate keys, or if they are nested deeper than `max_json_depth`
+    pub(crate) mode: BodyValidationMode,
+
+    /// Maximum nesting of JSON arrays and objects in a request, the request object counting as 1
+    pub(crate) max_json_depth: Option<usize>,
+}
+
+/// Handling of requests with invalid bodies
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum BodyValidationMode {
+    /// Do not validate bodies
+    #[default]
+    Disabled,
+    /// Log and count requests with invalid bodies, but let them through
+    LogOnly,
+    /// Reject requests with invalid bodies with a HTTP 400 Bad Request response
+    Reject,
+}
+
+#[derive(Debug, Display, PartialEq, Eq)]
+pub(crate) enum BodyViolation {
+    /// the request is not valid UTF-8
+    InvalidUtf8,
+    /// the variables contain an object with duplicate key '{0}'
+    DuplicateKey(String),
+    /// the request JSON is nested deeper than {0} levels
+    TooDeep(usize),
+}
+
+impl BodyViolation {
+    fn kind(&self) -> &'static str {
+        match self {
+            BodyViolation::InvalidUtf8 => "invalid_utf8",
+            BodyViolation::DuplicateKey(_) => "duplicate_key",
+            BodyViolation::TooDeep(_) => "json_depth",
+        }
+    }
+
+    /// Code of the GraphQL error returned when the request is rejected
+    pub(crate) fn extension_code(&self) -> &'static str {
+        match self {
+            BodyViolation::InvalidUtf8 => "INVALID_UTF8",
+            BodyViolation::DuplicateKey(_) => "DUPLICATE_VARIABLE_KEY",
+            BodyViolation::TooDeep(_) => "MAX_JSON_DEPTH_LIMIT",
+        }
+    }
+}
+
+impl BodyValidation {
+    /// Validates the body of a POST request, returning the violation if the request must be
+    /// rejected
+    pub(crate) fn validate_body(&self, body: &[u8]) -> Result<(), BodyViolation> {
+        if self.mode == BodyValidationMode::Disabled {
+            return Ok(());
+        }
+        self.report(self.check_body(body))
+    }
+
+    /// Validates the query string of a GET request, returning the violation if the request must
+    /// be rejected
+    pub(crate) fn validate_query_string(&self, query: &str) -> Result<(), BodyViolation> {
+        if self.mode == BodyValidationMode::Disabled {
+            return Ok(());
+        }
+        self.report(self.check_query_string(query))
+    }
+
+    fn report(&self, violation: Option<BodyViolation>) -> Result<(), BodyViolation> {
+        let Some(violation) = violation else {
+            return Ok(());
+        };
+        let rejected = self.mode == BodyValidationMode::Reject;
+        tracing::warn!(
+            violation = %violation,
+            rejected,
+            "inbound request has an invalid body"
+        );
+        u64_counter!(
+            "apollo.router.http.body_validation.violations",
+            "Number of inbound requests with invalid bodies",
+            1u64,
+            "violation" = violation.kind(),
+            "rejected" = rejected
+        );
+        if rejected {
+            Err(violation)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the first violation found in a request body. Bodies that are not valid JSON are
+    /// left to the request parser
+    pub(crate) fn check_body(&self, body: &[u8]) -> Option<BodyViolation> {
+        if std::str::from_utf8(body).is_err() {
+            return Some(BodyViolation::InvalidUtf8);
+        }
+        self.check_json(body, Level::Root)
+    }
+
+    /// Returns the first violation found in the parameters of a GET request
+    pub(crate) fn check_query_string(&self, query: &str) -> Option<BodyViolation> {
+        for pair in query.split('&') {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let (Ok(name), Ok(value)) = (
+                String::from_utf8(form_decode(name)),
+                String::from_utf8(form_decode(value)),
+            ) else {
+                return Some(BodyViolation::InvalidUtf8);
+            };
+            let level = match name.as_str() {
+                "variables" => Level::Variables,
+                "extensions" => Level::Other,
+                _ => continue,
+            };
+            if let Some(violation) = self.check_json(value.as_bytes(), level) {
+                return Some(violation);
+            }
+        }
+        None
+    }
+
+    fn check_json(&self, json: &[u8], level: Level) -> Option<BodyViolation> {
+        let mut violation = None;
+        let mut deserializer = serde_json::Deserializer::from_slice(json);
+        // the walk stops at the first violation, or at a syntax error the parser will report
+        let _ = Walker {
+            max_depth: self.max_depth(),
+            depth: 0,
+            level,
+            violation: &mut violation,
+        }
+        .deserialize(&mut deserializer);
+        violation
+    }
+
+    fn max_depth(&self) -> usize {
+        self.max_json_depth.unwrap_or(usize::MAX)
+    }
+}
+
+/// Decodes a component of a `application/x-www-form-urlencoded` query string, keeping invalid
+/// UTF-8 sequences instead of replacing them
+fn form_decode(input: &str) -> Vec<u8> {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'+' => decoded.push(b' '),
+            b'%' => match bytes
+                .get(index + 1..index + 3)
+                .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                Some(byte) => {
+                    decoded.push(byte);
+                    index += 2;
+                }
+                None => decoded.push(b'%'),
+            },
+            byte => decoded.push(byte),
+        }
+        index += 1;
+    }
+    decoded
+}
+
+/// Position of a JSON value in a request
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Level {
+    /// A request, or a batch of requests
+    Root,
+    /// A request of a batch
+    Request,
+    /// The variables of a request, or a value in them
+    Variables,
+    Other,
+}
+
+/// Walks a JSON value without building it, stopping at the first violation
+struct Walker<'a> {
+    max_depth: usize,
+    depth: usize,
+    level: Level,
+    violation: &'a mut Option<BodyViolation>,
+}
+
+impl<'a> Walker<'a> {
+    fn child(&mut self, level: Level) -> Walker<'_> {
+        Walker {
+            max_depth: self.max_depth,
+            depth: self.depth,
+            level,
+            violation: &mut *self.violation,
+        }
+    }
+
+    fn enter<E: serde::de::Error>(&mut self) -> Result<(), E> {
+        self.depth += 1;
+        if self.depth > self.max_depth {
+            return self.fail(BodyViolation::TooDeep(self.max_depth));
+        }
+        Ok(())
+    }
+
+    fn fail<E: serde::de::Error>(&mut self, violation: BodyViolation) -> Result<(), E> {
+        let error = E::custom(&violation);
+        *self.violation = Some(violation);
+        Err(error)
+    }
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for Walker<'a> {
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for Walker<'a> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a JSON value")
+    }
+
+    fn visit_bool<E>(self, _: bool) -> Result<(), E>
+    where
+        E: serde::de::Error,
+    {
+        Ok(())
+    }
+
+    fn visit_i64<E>(self, _: i64) -> Result<(), E>
+    where
+        E: serde::de::Error,
+    {
+        Ok(())
+    }
+
+    fn visit_u64<E>(self, _: u64) -> Result<(), E>
+    where
+        E: serde::de::Error,
+    {
+        Ok(())
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<(), E>
+    where
+        E: serde::de::Error,
+    {
+        Ok(())
+    }
+
+    fn visit_str<E>(self, _: &str) -> Result<(), E>
+    where
+        E: serde::de::Error,
+    {
+        Ok(())
+    }
+
+    fn visit_unit<E>(self) -> Result<(), E>
+    where
+        E: serde::de::Error,
+    {
+        Ok(())
+    }
+
+    fn visit_seq<A>(mut self, mut seq: A) -> Result<(), A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        self.enter()?;
+        let level = match self.level {
+            Level::Root => Level::Request,
+            Level::Variables => Level::Variables,
+            Level::Request | Level::Other => Level::Other,
+        };
+        while seq.next_element_seed(self.child(level))?.is_some() {}
+        Ok(())
+    }
+
+    fn visit_map<A>(mut self, mut map: A) -> Result<(), A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        self.enter()?;
+        let mut keys = HashSet::new();
+        while let Some(key) = map.next_key::<String>()? {
+            let level = match self.level {
+                Level::Root | Level::Request if key == "variables" => Level::Variables,
+                Level::Variables => Level::Variables,
+                _ => Level::Other,
+            };
+            if self.level == Level::Variables && !keys.insert(key.clone()) {
+                return self.fail(BodyViolation::DuplicateKey(key));
+            }
+            map.next_value_seed(self.child(level))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn validation(max_json_depth: Option<usize>) -> BodyValidation {
+        BodyValidation {
+            mode: BodyValidationMode::Reject,
+            max_json_depth,
+        }
+    }
+
+    #[test]
+    fn it_accepts_valid_bodies() {
+        let body = br#"{"query":"{ me }","variables":{"a":{"b":[1,{"c":"d"}]}},"extensions":{}}"#;
+        assert_eq!(validation(Some(4)).check_body(body), None);
+        assert_eq!(
+            validation(None).check_body(br#"[{"variables":{"a":1}},{"variables":{"a":2}}]"#),
+            None
+        );
+        // syntax errors are reported by the parser
+        assert_eq!(validation(None).check_body(b"{\"query\":"), None);
+    }
+
+    #[test]
+    fn it_rejects_invalid_utf8() {
+        assert_eq!(
+            validation(None).check_body(b"{\"query\":\"{ me \xff}\"}"),
+            Some(BodyViolation::InvalidUtf8)
+        );
+        assert_eq!(
+            validation(None)
+                .check_query_string("query=%7B%20me%20%7D&variables=%7B%22a%22%3A%22%FF%22%7D"),
+            Some(BodyViolation::InvalidUtf8)
+        );
+    }
+
+    #[test]
+    fn it_rejects_duplicate_keys_in_variables() {
+        assert_eq!(
+            validation(None).check_body(br#"{"variables":{"a":{"b":1,"b":2}}}"#),
+            Some(BodyViolation::DuplicateKey("b".to_string()))
+        );
+        assert_eq!(
+            validation(None).check_body(br#"[{"variables":{}},{"variables":{"a":1,"a":1}}]"#),
+            Some(BodyViolation::DuplicateKey("a".to_string()))
+        );
+        assert_eq!(
+            validation(None).check_query_string(
+                "query=%7B%20me%20%7D&variables=%7B%22a%22%3A1%2C%22a%22%3A2%7D"
+            ),
+            Some(BodyViolation::DuplicateKey("a".to_string()))
+        );
+        // only variables are checked
+        assert_eq!(
+            validation(None).check_body(br#"{"extensions":{"a":1,"a":2}}"#),
+            None
+        );
+    }
+
+    #[test]
+    fn it_rejects_deep_json() {
+        let body = br#"{"variables":{"a":[[{"b":1}]]}}"#;
+        assert_eq!(validation(Some(5)).check_body(body), None);
+        assert_eq!(
+            validation(Some(4)).check_body(body),
+            Some(BodyViolation::TooDeep(4))
+        );
+        assert_eq!(
+            validation(Some(2)).check_query_string("variables=%7B%22a%22%3A%5B%5B1%5D%5D%7D"),
+            Some(BodyViolation::TooDeep(2))
+        );
+    }
+
+    #[test]
+    fn it_only_logs_in_log_only_mode() {
+        let body = br#"{"variables":{"a":1,"a":2}}"#;
+        let mut validation = validation(None);
+        assert!(validation.validate_body(body).is_err());
+        validation.mode = BodyValidationMode::LogOnly;
+        assert!(validation.validate_body(body).is_ok());
+        validation.mode = BodyValidationMode::Disabled;
+        assert!(validation.validate_body(body).is_ok());
+    }
+}
@@ -0,0 +1,248 @@
+//! Strict validation of inbound HTTP headers
+//!
+//! Hyper already rejects most malformed requests while parsing them, but it accepts some
+//! ambiguous ones that can be interpreted differently by a proxy in front of the router,
+//! which is the basis of request smuggling attacks.
+
+use displaydoc::Display;
+use http::header;
+use http::HeaderMap;
+use http::StatusCode;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::graphql;
+use crate::services::router;
+use crate::Context;
+
+/// Strict validation of inbound HTTP headers
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct HeaderValidation {
+    /// What to do with requests with invalid headers (default: disabled)
+    pub(crate) mode: HeaderValidationMode,
+
+    /// Maximum number of headers in a request. Requests with more headers are considered invalid
+    pub(crate) max_headers: Option<usize>,
+}
+
+/// Handling of requests with invalid headers
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum HeaderValidationMode {
+    /// Do not validate headers
+    #[default]
+    Disabled,
+    /// Log and count requests with invalid headers, but let them through
+    LogOnly,
+    /// Reject requests with invalid headers with a HTTP 400 Bad Request response
+    Reject,
+}
+
+#[derive(Debug, Display, PartialEq, Eq)]
+pub(crate) enum HeaderViolation {
+    /// header '{0}' contains a line folding or a line break
+    ObsFold(String),
+    /// multiple content-length values
+    DuplicateContentLength,
+    /// content-length and transfer-encoding are both present
+    ContentLengthWithTransferEncoding,
+    /// the request contains {0} headers, the maximum is {1}
+    TooManyHeaders(usize, usize),
+}
+
+impl HeaderViolation {
+    fn kind(&self) -> &'static str {
+        match self {
+            HeaderViolation::ObsFold(_) => "obs_fold",
+            HeaderViolation::DuplicateContentLength => "duplicate_content_length",
+            HeaderViolation::ContentLengthWithTransferEncoding => {
+                "content_length_with_transfer_encoding"
+            }
+            HeaderViolation::TooManyHeaders(_, _) => "too_many_headers",
+        }
+    }
+}
+
+impl HeaderValidation {
+    /// Returns the first violation found in the headers
+    pub(crate) fn check(&self, headers: &HeaderMap) -> Option<HeaderViolation> {
+        if let Some(max_headers) = self.max_headers {
+            if headers.len() > max_headers {
+                return Some(HeaderViolation::TooManyHeaders(headers.len(), max_headers));
+            }
+        }
+
+        // hyper rejects folded header lines while parsing, but header values created with
+        // unchecked constructors can still contain line breaks
+        for (name, value) in headers {
+            if value
+                .as_bytes()
+                .iter()
+                .any(|byte| *byte == b'\r' || *byte == b'\n')
+            {
+                return Some(HeaderViolation::ObsFold(name.to_string()));
+            }
+        }
+
+        let mut content_lengths = 0;
+        for value in headers.get_all(header::CONTENT_LENGTH) {
+            content_lengths += value.as_bytes().split(|byte| *byte == b',').count();
+        }
+        if content_lengths > 1 {
+            return Some(HeaderViolation::DuplicateContentLength);
+        }
+        if content_lengths == 1 && headers.contains_key(header::TRANSFER_ENCODING) {
+            return Some(HeaderViolation::ContentLengthWithTransferEncoding);
+        }
+
+        None
+    }
+
+    /// Validates the request headers, returning an error response if the request must be rejected
+    pub(crate) fn validate(&self, request: &router::Request) -> Option<router::Response> {
+        if self.mode == HeaderValidationMode::Disabled {
+            return None;
+        }
+
+        let violation = self.check(request.router_request.headers())?;
+        let rejected = self.mode == HeaderValidationMode::Reject;
+        tracing::warn!(
+            violation = %violation,
+            rejected,
+            "inbound request has invalid HTTP headers"
+        );
+        u64_counter!(
+            "apollo.router.http.header_validation.violations",
+            "Number of inbound requests with invalid HTTP headers",
+            1u64,
+            "violation" = violation.kind(),
+            "rejected" = rejected
+        );
+
+        rejected.then(|| violation.into_response(request.context.clone()))
+    }
+}
+
+impl HeaderViolation {
+    fn into_response(self, ctx: Context) -> router::Response {
+        let status_code = match self {
+            HeaderViolation::TooManyHeaders(_, _) => StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+            _ => StatusCode::BAD_REQUEST,
+        };
+        router::Response::error_builder()
+            .error(
+                graphql::Error::builder()
+                    .message("Invalid HTTP headers")
+                    .extension_code("INVALID_HTTP_HEADERS")
+                    .extension("details", self.to_string())
+                    .build(),
+            )
+            .status_code(status_code)
+            .context(ctx)
+            .build()
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use http::HeaderValue;
+
+    use super::*;
+
+    fn validation(max_headers: Option<usize>) -> HeaderValidation {
+        HeaderValidation {
+            mode: HeaderValidationMode::Reject,
+            max_headers,
+        }
+    }
+
+    #[test]
+    fn it_accepts_valid_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_LENGTH, HeaderValue::from_static("10"));
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+        assert_eq!(validation(Some(2)).check(&headers), None);
+    }
+
+    #[test]
+    fn it_rejects_line_breaks_in_header_values() {
+        for value in ["a\r\n b", "a\nb", "a\rb"] {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+            // the checked constructors refuse line breaks, like hyper does while parsing
+            headers.insert("x-folded", unchecked_header_value(value));
+            assert_eq!(
+                validation(None).check(&headers),
+                Some(HeaderViolation::ObsFold("x-folded".to_string()))
+            );
+        }
+    }
+
+    fn unchecked_header_value(value: &'static str) -> HeaderValue {
+        assert!(HeaderValue::from_str(value).is_err());
+        // SAFETY: the value is only checked by the validation, never sent
+        unsafe { HeaderValue::from_maybe_shared_unchecked(value.as_bytes()) }
+    }
+
+    #[test]
+    fn it_rejects_duplicate_content_length() {
+        let mut headers = HeaderMap::new();
+        headers.append(header::CONTENT_LENGTH, HeaderValue::from_static("10"));
+        headers.append(header::CONTENT_LENGTH, HeaderValue::from_static("10"));
+        assert_eq!(
+            validation(None).check(&headers),
+            Some(HeaderViolation::DuplicateContentLength)
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_LENGTH, HeaderValue::from_static("10, 20"));
+        assert_eq!(
+            validation(None).check(&headers),
+            Some(HeaderViolation::DuplicateContentLength)
+        );
+    }
+
+    #[test]
+    fn it_rejects_content_length_with_transfer_encoding() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_LENGTH, HeaderValue::from_static("10"));
+        headers.insert(
+            header::TRANSFER_ENCODING,
+            HeaderValue::from_static("chunked"),
+        );
+        assert_eq!(
+            validation(None).check(&headers),
+            Some(HeaderViolation::ContentLengthWithTransferEncoding)
+        );
+    }
+
+    #[test]
+    fn it_rejects_too_many_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("a", HeaderValue::from_static("1"));
+        headers.insert("b", HeaderValue::from_static("2"));
+        headers.insert("c", HeaderValue::from_static("3"));
+        assert_eq!(
+            validation(Some(2)).check(&headers),
+            Some(HeaderViolation::TooManyHeaders(3, 2))
+        );
+    }
+
+    #[test]
+    fn it_only_logs_in_log_only_mode() {
+        let request = router::Request::fake_builder()
+            .header("content-length", "10")
+            .header("transfer-encoding", "chunked")
+            .build()
+            .unwrap();
+        let mut validation = validation(None);
+        assert!(validation.validate(&request).is_some());
+        validation.mode = HeaderValidationMode::LogOnly;
+        assert!(validation.validate(&request).is_none());
+        validation.mode = HeaderValidationMode::Disabled;
+        assert!(validation.validate(&request).is_none());
+    }
+}
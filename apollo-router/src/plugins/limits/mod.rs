@@ -1,10 +1,15 @@
+pub(crate) mod body_validation;
+mod header_validation;
 mod layer;
 mod limited;
 
+use std::collections::HashMap;
 use std::error::Error;
+use std::ops::ControlFlow;
 
 use async_trait::async_trait;
 use http::StatusCode;
+use http_body::Body as _;
 use schemars::JsonSchema;
 use serde::Deserialize;
 use serde::Serialize;
@@ -12,13 +17,18 @@ use tower::BoxError;
 use tower::ServiceBuilder;
 use tower::ServiceExt;
 
+use crate::configuration::subgraph::SubgraphConfiguration;
+use crate::error::FetchError;
 use crate::graphql;
 use crate::layers::ServiceBuilderExt;
 use crate::plugin::Plugin;
 use crate::plugin::PluginInit;
+use crate::plugins::limits::body_validation::BodyValidation;
+use crate::plugins::limits::header_validation::HeaderValidation;
 use crate::plugins::limits::layer::BodyLimitControl;
 use crate::plugins::limits::layer::BodyLimitError;
 use crate::plugins::limits::layer::RequestBodyLimitLayer;
+use crate::services::http::HttpRequest;
 use crate::services::router;
 use crate::services::router::BoxService;
 use crate::Context;
@@ -86,21 +96,107 @@ pub(crate) struct Config {
     /// `"extensions": {"code": "MAX_ALIASES_LIMIT"}`
     pub(crate) max_aliases: Option<u32>,
 
+    /// If set, requests with operations planned with more subgraph fetches than this maximum
+    /// are rejected with a HTTP 400 Bad Request response and GraphQL error with
+    /// `"extensions": {"code": "MAX_PLAN_FETCHES_LIMIT"}`
+    pub(crate) plan_max_fetches: Option<u64>,
+
+    /// If set, requests with operations planned with longer chains of subgraph fetches
+    /// executed one after the other than this maximum are rejected with a HTTP 400 Bad Request
+    /// response and GraphQL error with `"extensions": {"code": "MAX_PLAN_SEQUENCE_DEPTH_LIMIT"}`
+    pub(crate) plan_max_sequence_depth: Option<u64>,
+
+    /// If set, requests with operations planned with entity fetches sending more
+    /// representations in total than this maximum are rejected with a HTTP 400 Bad Request
+    /// response and GraphQL error with
+    /// `"extensions": {"code": "MAX_PLAN_ESTIMATED_REPRESENTATIONS_LIMIT"}`
+    ///
+    /// The representations are estimated from the query plan, assuming every list in the path
+    /// of an entity fetch has `plan_assumed_list_size` items
+    pub(crate) plan_max_estimated_representations: Option<u64>,
+
+    /// Number of items assumed for every list when estimating the representations sent by
+    /// a query plan. Default: 10
+    pub(crate) plan_assumed_list_size: u64,
+
     /// If set to true (which is the default is dev mode),
     /// requests that exceed a `max_*` limit are *not* rejected.
     /// Instead they are executed normally, and a warning is logged.
     pub(crate) warn_only: bool,
 
     /// Limit recursion in the GraphQL parser to protect against stack overflow.
+    /// Operations over the limit are rejected with a GraphQL error with
+    /// `"extensions": {"code": "PARSER_LIMIT_EXCEEDED"}`
     /// default: 500
     pub(crate) parser_max_recursion: usize,
 
     /// Limit the number of tokens the GraphQL parser processes before aborting.
+    /// Operations over the limit are rejected with a GraphQL error with
+    /// `"extensions": {"code": "PARSER_LIMIT_EXCEEDED"}`
     pub(crate) parser_max_tokens: usize,
 
+    /// Parser limits overridden for some clients, by client name as read from the client name
+    /// header into the `apollo_telemetry::client_name` context entry. This header is not
+    /// authenticated: any client sending the name gets its limits, unless a coprocessor or a
+    /// script overwrites the context entry from an authenticated identity at the router stage,
+    /// before the operation is parsed
+    pub(crate) clients: HashMap<String, ClientLimits>,
+
     /// Limit the size of incoming HTTP requests read from the network,
     /// to protect against running out of memory. Default: 2000000 (2 MB)
     pub(crate) http_max_request_bytes: usize,
+
+    /// Strict validation of the headers of incoming HTTP requests, to protect against
+    /// request smuggling. Disabled by default
+    pub(crate) http_header_validation: HeaderValidation,
+
+    /// Strict validation of the bodies of incoming HTTP requests, and of the query string
+    /// of GET requests, against parsing differences with subgraphs. Disabled by default
+    pub(crate) http_body_validation: BodyValidation,
+
+    /// Limits of the HTTP requests sent to subgraphs
+    pub(crate) subgraph: SubgraphConfiguration<SubgraphLimits>,
+}
+
+/// Parser limits overridden for a client
+#[derive(Debug, Clone, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct ClientLimits {
+    /// Overrides `parser_max_recursion` for the operations of the client
+    pub(crate) parser_max_recursion: Option<usize>,
+
+    /// Overrides `parser_max_tokens` for the operations of the client
+    pub(crate) parser_max_tokens: Option<usize>,
+}
+
+/// Limits of the GraphQL parser applied to an operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct ParserLimits {
+    pub(crate) max_recursion: usize,
+    pub(crate) max_tokens: usize,
+}
+
+/// Limits of the HTTP requests sent to a subgraph
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct SubgraphLimits {
+    /// If set, requests to the subgraph with a larger serialized body are not sent,
+    /// and fail with a GraphQL error with `"extensions": {"code": "SUBREQUEST_BODY_TOO_LARGE"}`
+    pub(crate) http_max_request_bytes: Option<usize>,
+
+    /// Share of `http_max_request_bytes` from which requests are counted in the
+    /// `apollo.router.limits.subgraph_request.near_limit` metric, greater than 0 and at most 1.
+    /// Default: 0.8
+    pub(crate) near_limit_ratio: f64,
+}
+
+impl Default for SubgraphLimits {
+    fn default() -> Self {
+        Self {
+            http_max_request_bytes: None,
+            near_limit_ratio: 0.8,
+        }
+    }
 }
 
 impl Default for Config {
@@ -111,14 +207,46 @@ impl Default for Config {
             max_height: None,
             max_root_fields: None,
             max_aliases: None,
+            plan_max_fetches: None,
+            plan_max_sequence_depth: None,
+            plan_max_estimated_representations: None,
+            plan_assumed_list_size: 10,
             warn_only: false,
             http_max_request_bytes: 2_000_000,
+            http_header_validation: HeaderValidation::default(),
+            http_body_validation: BodyValidation::default(),
+            subgraph: SubgraphConfiguration::default(),
             parser_max_tokens: 15_000,
 
             // This is `apollo-parser`’s default, which protects against stack overflow
             // but is still very high for "reasonable" queries.
             // https://github.com/apollographql/apollo-rs/blob/apollo-parser%400.7.3/crates/apollo-parser/src/parser/mod.rs#L93-L104
             parser_max_recursion: 500,
+            clients: HashMap::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Name of a client in metrics: only the clients with overridden limits are named, so that
+    /// the names sent by clients cannot create an unbounded number of time series
+    pub(crate) fn client_label(&self, client_name: Option<&str>) -> String {
+        client_name
+            .filter(|client_name| self.clients.contains_key(*client_name))
+            .unwrap_or("other")
+            .to_string()
+    }
+
+    /// Parser limits applied to the operations of a client
+    pub(crate) fn parser_limits(&self, client_name: Option<&str>) -> ParserLimits {
+        let client = client_name.and_then(|client_name| self.clients.get(client_name));
+        ParserLimits {
+            max_recursion: client
+                .and_then(|client| client.parser_max_recursion)
+                .unwrap_or(self.parser_max_recursion),
+            max_tokens: client
+                .and_then(|client| client.parser_max_tokens)
+                .unwrap_or(self.parser_max_tokens),
         }
     }
 }
@@ -135,6 +263,16 @@ impl Plugin for LimitsPlugin {
     where
         Self: Sized,
     {
+        let subgraph = &init.config.subgraph;
+        for limits in std::iter::once(&subgraph.all).chain(subgraph.subgraphs.values()) {
+            if !(limits.near_limit_ratio > 0.0 && limits.near_limit_ratio <= 1.0) {
+                return Err(format!(
+                    "limits.subgraph: near_limit_ratio must be greater than 0 and at most 1, got {}",
+                    limits.near_limit_ratio
+                )
+                .into());
+            }
+        }
         Ok(LimitsPlugin {
             config: init.config,
         })
@@ -143,7 +281,14 @@ impl Plugin for LimitsPlugin {
     fn router_service(&self, service: BoxService) -> BoxService {
         let control = BodyLimitControl::new(self.config.http_max_request_bytes);
         let control_for_context = control.clone();
+        let header_validation = self.config.http_header_validation.clone();
         ServiceBuilder::new()
+            .checkpoint(move |r: router::Request| {
+                Ok(match header_validation.validate(&r) {
+                    Some(response) => ControlFlow::Break(response),
+                    None => ControlFlow::Continue(r),
+                })
+            })
             .map_request(move |r: router::Request| {
                 let control_for_context = control_for_context.clone();
                 r.context
@@ -164,6 +309,51 @@ impl Plugin for LimitsPlugin {
             .service(service)
             .boxed()
     }
+
+    fn http_client_service(
+        &self,
+        subgraph_name: &str,
+        service: crate::services::http::BoxService,
+    ) -> crate::services::http::BoxService {
+        let limits = self.config.subgraph.get(subgraph_name);
+        let Some(limit) = limits.http_max_request_bytes else {
+            return service;
+        };
+        let near_limit = (limit as f64 * limits.near_limit_ratio) as u64;
+        let subgraph_name = subgraph_name.to_string();
+
+        ServiceBuilder::new()
+            .checkpoint(move |request: HttpRequest| {
+                // the body of subgraph requests is already serialized, except for file uploads
+                // which are streamed and not limited
+                let Some(size) = request.http_request.body().size_hint().exact() else {
+                    return Ok(ControlFlow::Continue(request));
+                };
+                if size < near_limit {
+                    return Ok(ControlFlow::Continue(request));
+                }
+
+                let rejected = size > limit as u64;
+                u64_counter!(
+                    "apollo.router.limits.subgraph_request.near_limit",
+                    "Number of subgraph requests with a body size close to or over the limit",
+                    1,
+                    "subgraph.name" = subgraph_name.clone(),
+                    "rejected" = rejected
+                );
+                if rejected {
+                    return Err(FetchError::SubrequestBodyTooLarge {
+                        service: subgraph_name.clone(),
+                        size,
+                        limit: limit as u64,
+                    }
+                    .into());
+                }
+                Ok(ControlFlow::Continue(request))
+            })
+            .service(service)
+            .boxed()
+    }
 }
 
 impl LimitsPlugin {
@@ -241,11 +431,21 @@ mod test {
     use http::StatusCode;
     use tower::BoxError;
 
+    use crate::error::FetchError;
+    use crate::metrics::FutureMetricsExt;
+    use crate::plugin::Plugin;
+    use crate::plugin::PluginInit;
     use crate::plugins::limits::layer::BodyLimitControl;
+    use crate::plugins::limits::Config;
     use crate::plugins::limits::LimitsPlugin;
+    use crate::plugins::limits::SubgraphLimits;
     use crate::plugins::test::PluginTestHarness;
+    use crate::services::http::HttpRequest;
+    use crate::services::http::HttpResponse;
     use crate::services::router;
     use crate::services::router::body::get_body_bytes;
+    use crate::services::router::body::RouterBody;
+    use crate::Context;
 
     #[tokio::test]
     async fn test_body_content_length_limit_exceeded() {
@@ -423,6 +623,102 @@ mod test {
         );
     }
 
+    #[tokio::test]
+    async fn test_subgraph_request_body_limit() {
+        async {
+            let plugin: PluginTestHarness<LimitsPlugin> = PluginTestHarness::new(
+                Some(
+                    r#"
+                limits:
+                  subgraph:
+                    subgraphs:
+                      products:
+                        http_max_request_bytes: 20
+                        near_limit_ratio: 0.5
+                "#,
+                ),
+                None,
+            )
+            .await;
+            let request = |body: &str| HttpRequest {
+                http_request: ::http::Request::new(RouterBody::from(body.to_string())),
+                context: Context::new(),
+            };
+            let response = |request: HttpRequest| HttpResponse {
+                http_response: ::http::Response::new(RouterBody::empty()),
+                context: request.context,
+            };
+
+            // other subgraphs are not limited
+            assert!(plugin
+                .call_http_client("reviews", request(&"a".repeat(100)), response)
+                .await
+                .is_ok());
+            assert!(plugin
+                .call_http_client("products", request("small"), response)
+                .await
+                .is_ok());
+            assert!(plugin
+                .call_http_client("products", request(&"a".repeat(15)), response)
+                .await
+                .is_ok());
+            assert_counter!(
+                "apollo.router.limits.subgraph_request.near_limit",
+                1,
+                "subgraph.name" = "products",
+                "rejected" = false
+            );
+
+            let error = plugin
+                .call_http_client("products", request(&"a".repeat(25)), response)
+                .await
+                .unwrap_err();
+            assert_eq!(
+                *error.downcast::<FetchError>().unwrap(),
+                FetchError::SubrequestBodyTooLarge {
+                    service: "products".to_string(),
+                    size: 25,
+                    limit: 20,
+                }
+            );
+            assert_counter!(
+                "apollo.router.limits.subgraph_request.near_limit",
+                1,
+                "subgraph.name" = "products",
+                "rejected" = true
+            );
+        }
+        .with_metrics()
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_subgraph_near_limit_ratio_validation() {
+        for ratio in [0.0, -0.5, 1.5, f64::NAN] {
+            let mut config = Config::default();
+            config.subgraph.subgraphs.insert(
+                "products".to_string(),
+                SubgraphLimits {
+                    http_max_request_bytes: Some(20),
+                    near_limit_ratio: ratio,
+                },
+            );
+            assert!(
+                LimitsPlugin::new(PluginInit::fake_new(config, Default::default()))
+                    .await
+                    .is_err(),
+                "{ratio} should be rejected"
+            );
+        }
+        let mut config = Config::default();
+        config.subgraph.all.near_limit_ratio = 1.0;
+        assert!(
+            LimitsPlugin::new(PluginInit::fake_new(config, Default::default()))
+                .await
+                .is_ok()
+        );
+    }
+
     async fn plugin() -> PluginTestHarness<LimitsPlugin> {
         let plugin: PluginTestHarness<LimitsPlugin> = PluginTestHarness::new(
             Some(include_str!("fixtures/content_length_limit.router.yaml")),
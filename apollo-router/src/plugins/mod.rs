@@ -20,22 +20,33 @@ macro_rules! schemar_fn {
     };
 }
 
+mod admin_api;
 pub(crate) mod authentication;
 pub(crate) mod authorization;
 pub(crate) mod cache;
+mod context_export;
 mod coprocessor;
 pub(crate) mod csrf;
 mod demand_control;
 mod expose_query_plan;
+mod extensions_to_context;
+pub(crate) mod field_allow_list;
 pub(crate) mod file_uploads;
 mod forbid_mutations;
+mod forwarded_variables;
 mod headers;
 mod include_subgraph_errors;
 pub(crate) mod limits;
+pub(crate) mod operation_rewrite;
 pub(crate) mod override_url;
 pub(crate) mod progressive_override;
 mod record_replay;
+mod response_assertions;
+pub(crate) mod response_sampling;
 pub(crate) mod rhai;
+mod schema_download;
+mod subgraph_identification;
+pub(crate) mod subgraph_response_validation;
 pub(crate) mod subscription;
 pub(crate) mod telemetry;
 #[cfg(test)]
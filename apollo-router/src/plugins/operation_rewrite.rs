@@ -0,0 +1,598 @@
+//! Rewrites incoming operations according to declarative rules
+//!
+//! This helps migrating schemas: a field can be renamed in the schema while older clients keep
+//! selecting it under its previous name. Rules match the selected fields with regular
+//! expressions, so one rule can rename a family of fields. Operations are rewritten after parsing
+//! and before validation and query planning, so the rest of the pipeline only sees the new schema.
+
+use std::collections::BTreeMap;
+
+use apollo_compiler::ast;
+use apollo_compiler::schema::ExtendedType;
+use apollo_compiler::Name;
+use apollo_compiler::Node;
+use apollo_compiler::Schema;
+use lru::LruCache;
+use parking_lot::Mutex;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde::Serialize;
+use tower::BoxError;
+
+use crate::plugin::Plugin;
+use crate::plugin::PluginInit;
+use crate::register_plugin;
+use crate::Configuration;
+
+const PLUGIN_NAME: &str = "operation_rewrite";
+
+/// Rewrites incoming operations, to rename schema fields without updating all clients at the same time
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct OperationRewriteConfig {
+    /// Rewrite rules, applied in order
+    rules: Vec<RewriteRule>,
+}
+
+/// Rewrite rule applied to a field selected on a type
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct RewriteRule {
+    /// Name of the rule, used in metrics
+    name: String,
+    /// Type on which the field is selected
+    #[serde(rename = "type")]
+    type_name: String,
+    /// Regular expression matching the whole name of the fields selected by clients
+    field: String,
+    /// New name of the field, which can refer to the groups captured by `field`, like `$1` or
+    /// `${name}`. The field is aliased to the name selected by the client, so the response keeps
+    /// the same shape
+    #[serde(default)]
+    rename_to: Option<String>,
+    /// Arguments added to the field when the client does not set them
+    #[serde(default)]
+    default_arguments: BTreeMap<String, serde_json::Value>,
+}
+
+struct Rule {
+    name: String,
+    type_name: Name,
+    field: Regex,
+    rename_to: Option<String>,
+    arguments: Vec<Node<ast::Argument>>,
+}
+
+impl Rule {
+    fn new(rule: &RewriteRule) -> Result<Self, BoxError> {
+        if let Some(rename_to) = &rule.rename_to {
+            // without references to captured groups, the new name is used as is
+            if !rename_to.contains('$') {
+                Name::new(rename_to)?;
+            }
+        }
+        Ok(Self {
+            name: rule.name.clone(),
+            type_name: Name::new(&rule.type_name)?,
+            field: Regex::new(&format!("^(?:{})$", rule.field))?,
+            rename_to: rule.rename_to.clone(),
+            arguments: rule
+                .default_arguments
+                .iter()
+                .map(|(name, value)| {
+                    Ok(ast::Argument {
+                        name: Name::new(name)?,
+                        value: Node::new(to_graphql_value(value)?),
+                    }
+                    .into())
+                })
+                .collect::<Result<_, BoxError>>()?,
+        })
+    }
+
+    /// Returns the new name of a selected field, if the rule renames it
+    fn renamed(&self, field: &str) -> Option<Name> {
+        let rename_to = self.rename_to.as_ref()?;
+        let captures = self.field.captures(field)?;
+        let mut renamed = String::new();
+        captures.expand(rename_to, &mut renamed);
+        Name::new(&renamed).ok()
+    }
+
+    /// Checks that the rewritten fields and their arguments exist in the schema. The names
+    /// built from captured groups are checked when operations are rewritten
+    fn validate(&self, schema: &Schema) -> Result<(), BoxError> {
+        let error = |message: String| format!("operation rewrite rule '{}': {message}", self.name);
+        let fields: Vec<&Name> = match &self.rename_to {
+            Some(rename_to) if rename_to.contains('$') => return Ok(()),
+            Some(rename_to) => vec![schema
+                .type_field(&self.type_name, rename_to)
+                .map(|definition| &definition.name)
+                .map_err(|_| {
+                    error(format!(
+                        "no field `{}` in type `{}`",
+                        rename_to, self.type_name
+                    ))
+                })?],
+            None => {
+                let fields = match schema.types.get(&self.type_name) {
+                    Some(ExtendedType::Object(object)) => object.fields.keys().collect::<Vec<_>>(),
+                    Some(ExtendedType::Interface(interface)) => interface.fields.keys().collect(),
+                    _ => Vec::new(),
+                };
+                let fields: Vec<_> = fields
+                    .into_iter()
+                    .filter(|field| self.field.is_match(field))
+                    .collect();
+                if fields.is_empty() {
+                    return Err(error(format!(
+                        "no field matching `{}` in type `{}`",
+                        self.field, self.type_name
+                    ))
+                    .into());
+                }
+                fields
+            }
+        };
+        for field_name in fields {
+            let definition = schema
+                .type_field(&self.type_name, field_name)
+                .map_err(|_| {
+                    error(format!(
+                        "no field `{}` in type `{}`",
+                        field_name, self.type_name
+                    ))
+                })?;
+            for argument in &self.arguments {
+                if definition.argument_by_name(&argument.name).is_none() {
+                    return Err(error(format!(
+                        "no argument `{}` on field `{}.{}`",
+                        argument.name, self.type_name, field_name
+                    ))
+                    .into());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn to_graphql_value(value: &serde_json::Value) -> Result<ast::Value, BoxError> {
+    Ok(match value {
+        serde_json::Value::Null => ast::Value::Null,
+        serde_json::Value::Bool(value) => (*value).into(),
+        serde_json::Value::Number(number) => {
+            match number.as_i64().and_then(|n| i32::try_from(n).ok()) {
+                Some(int) => int.into(),
+                None => number.as_f64().ok_or("invalid number")?.into(),
+            }
+        }
+        serde_json::Value::String(value) => value.as_str().into(),
+        serde_json::Value::Array(values) => ast::Value::List(
+            values
+                .iter()
+                .map(|value| Ok(Node::new(to_graphql_value(value)?)))
+                .collect::<Result<_, BoxError>>()?,
+        ),
+        serde_json::Value::Object(fields) => ast::Value::Object(
+            fields
+                .iter()
+                .map(|(name, value)| Ok((Name::new(name)?, Node::new(to_graphql_value(value)?))))
+                .collect::<Result<_, BoxError>>()?,
+        ),
+    })
+}
+
+/// Result of rewriting an operation
+#[derive(Clone)]
+struct Rewrite {
+    query: String,
+    rules: Vec<usize>,
+}
+
+/// Applies the rewrite rules to operations, keeping the result for operations already seen
+pub(crate) struct OperationRewriter {
+    rules: Vec<Rule>,
+    parser_max_recursion: usize,
+    parser_max_tokens: usize,
+    cache: Mutex<LruCache<String, Option<Rewrite>>>,
+}
+
+impl OperationRewriter {
+    /// Creates the rewriter from the plugin configuration, if rewrite rules are configured
+    pub(crate) fn from_configuration(
+        configuration: &Configuration,
+    ) -> Result<Option<Self>, BoxError> {
+        let Some(config) = configuration.apollo_plugins.plugins.get(PLUGIN_NAME) else {
+            return Ok(None);
+        };
+        let config: OperationRewriteConfig = serde_json::from_value(config.clone())
+            .map_err(|e| format!("invalid {PLUGIN_NAME} configuration: {e}"))?;
+        if config.rules.is_empty() {
+            return Ok(None);
+        }
+        let rules = config
+            .rules
+            .iter()
+            .map(|rule| {
+                Rule::new(rule)
+                    .map_err(|e| format!("operation rewrite rule '{}': {e}", rule.name).into())
+            })
+            .collect::<Result<_, BoxError>>()?;
+        Ok(Some(Self {
+            rules,
+            parser_max_recursion: configuration.limits.parser_max_recursion,
+            parser_max_tokens: configuration.limits.parser_max_tokens,
+            cache: Mutex::new(LruCache::new(
+                configuration
+                    .supergraph
+                    .query_planning
+                    .cache
+                    .in_memory
+                    .limit,
+            )),
+        }))
+    }
+
+    /// Returns the rewritten query, or `None` if no rule applies to it
+    pub(crate) fn rewrite(&self, schema: &Schema, query: &str) -> Option<String> {
+        if let Some(rewrite) = self.cache.lock().get(query) {
+            return rewrite.as_ref().map(|rewrite| rewrite.query.clone());
+        }
+
+        let rewrite = self.rewrite_document(schema, query);
+        // operations already rewritten are not counted again
+        for index in rewrite.iter().flat_map(|rewrite| &rewrite.rules) {
+            u64_counter!(
+                "apollo.router.operations.rewritten",
+                "Number of operations rewritten by an operation rewrite rule",
+                1,
+                "rule" = self.rules[*index].name.clone()
+            );
+        }
+        let query_rewritten = rewrite.as_ref().map(|rewrite| rewrite.query.clone());
+        self.cache.lock().put(query.to_string(), rewrite);
+        query_rewritten
+    }
+
+    fn rewrite_document(&self, schema: &Schema, query: &str) -> Option<Rewrite> {
+        // invalid operations are left as is, they will be rejected by validation
+        let mut document = apollo_compiler::parser::Parser::new()
+            .recursion_limit(self.parser_max_recursion)
+            .token_limit(self.parser_max_tokens)
+            .parse_ast(query, "query.graphql")
+            .ok()?;
+
+        let mut applied = Vec::new();
+        for definition in &mut document.definitions {
+            match definition {
+                ast::Definition::OperationDefinition(operation) => {
+                    let Some(root_type) = schema.root_operation(operation.operation_type) else {
+                        continue;
+                    };
+                    let root_type = root_type.clone();
+                    self.rewrite_selection_set(
+                        schema,
+                        &root_type,
+                        &mut operation.make_mut().selection_set,
+                        &mut applied,
+                    );
+                }
+                ast::Definition::FragmentDefinition(fragment) => {
+                    let fragment = fragment.make_mut();
+                    let type_condition = fragment.type_condition.clone();
+                    self.rewrite_selection_set(
+                        schema,
+                        &type_condition,
+                        &mut fragment.selection_set,
+                        &mut applied,
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        if applied.is_empty() {
+            return None;
+        }
+        applied.sort_unstable();
+        applied.dedup();
+        Some(Rewrite {
+            query: document.to_string(),
+            rules: applied,
+        })
+    }
+
+    fn rewrite_selection_set(
+        &self,
+        schema: &Schema,
+        parent_type: &Name,
+        selection_set: &mut [ast::Selection],
+        applied: &mut Vec<usize>,
+    ) {
+        for selection in selection_set {
+            match selection {
+                ast::Selection::Field(field) => {
+                    let field = field.make_mut();
+                    let selected_name = field.name.clone();
+                    for (index, rule) in self.rules.iter().enumerate() {
+                        if &rule.type_name != parent_type || !rule.field.is_match(&selected_name) {
+                            continue;
+                        }
+                        let mut changed = false;
+                        // fields renamed to a name missing from the schema are left as is, so
+                        // that validation reports the field the client selected
+                        if let Some(rename_to) = rule
+                            .renamed(&selected_name)
+                            .filter(|name| schema.type_field(parent_type, name).is_ok())
+                        {
+                            if field.alias.is_none() {
+                                field.alias = Some(selected_name.clone());
+                            }
+                            field.name = rename_to;
+                            changed = true;
+                        }
+                        for argument in &rule.arguments {
+                            if !field.arguments.iter().any(|a| a.name == argument.name) {
+                                field.arguments.push(argument.clone());
+                                changed = true;
+                            }
+                        }
+                        if changed {
+                            applied.push(index);
+                        }
+                    }
+
+                    if let Ok(definition) = schema.type_field(parent_type, &field.name) {
+                        let field_type = definition.ty.inner_named_type().clone();
+                        self.rewrite_selection_set(
+                            schema,
+                            &field_type,
+                            &mut field.selection_set,
+                            applied,
+                        );
+                    }
+                }
+                ast::Selection::InlineFragment(fragment) => {
+                    let fragment = fragment.make_mut();
+                    let fragment_type = fragment
+                        .type_condition
+                        .clone()
+                        .unwrap_or_else(|| parent_type.clone());
+                    self.rewrite_selection_set(
+                        schema,
+                        &fragment_type,
+                        &mut fragment.selection_set,
+                        applied,
+                    );
+                }
+                ast::Selection::FragmentSpread(_) => {}
+            }
+        }
+    }
+}
+
+/// The rewriting itself happens in the query analysis layer, the plugin checks that the rules
+/// match the schema when the router starts
+struct OperationRewrite;
+
+#[async_trait::async_trait]
+impl Plugin for OperationRewrite {
+    type Config = OperationRewriteConfig;
+
+    async fn new(init: PluginInit<Self::Config>) -> Result<Self, BoxError> {
+        for rule in &init.config.rules {
+            Rule::new(rule)
+                .map_err(|e| format!("operation rewrite rule '{}': {e}", rule.name))?
+                .validate(&init.supergraph_schema)?;
+        }
+        Ok(OperationRewrite)
+    }
+}
+
+register_plugin!("apollo", "operation_rewrite", OperationRewrite);
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::metrics::FutureMetricsExt;
+
+    const SCHEMA: &str = r#"
+        type Query {
+            user(id: ID!): User
+            users(first: Int, includeInactive: Boolean): [User]
+        }
+
+        type User {
+            id: ID!
+            name: String
+            friends: [User]
+        }
+    "#;
+
+    fn rewriter(rules: serde_json::Value) -> OperationRewriter {
+        let configuration = Configuration::from_str(
+            &json!({ "operation_rewrite": { "rules": rules } }).to_string(),
+        )
+        .unwrap();
+        OperationRewriter::from_configuration(&configuration)
+            .unwrap()
+            .unwrap()
+    }
+
+    fn schema() -> Schema {
+        Schema::parse_and_validate(SCHEMA, "schema.graphql")
+            .unwrap()
+            .into_inner()
+    }
+
+    #[test]
+    fn it_rejects_invalid_rules() {
+        let configuration = Configuration::from_str(
+            &json!({ "operation_rewrite": { "rules": [{
+                "name": "invalid",
+                "type": "User",
+                "field": "full(name"
+            }] } })
+            .to_string(),
+        )
+        .unwrap();
+        let error = OperationRewriter::from_configuration(&configuration)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(
+            error.starts_with("operation rewrite rule 'invalid'"),
+            "{error}"
+        );
+    }
+
+    #[tokio::test]
+    async fn it_renames_fields() {
+        async {
+            let rewriter = rewriter(json!([{
+                "name": "user_full_name",
+                "type": "User",
+                "field": "fullName",
+                "rename_to": "name"
+            }]));
+            let schema = schema();
+
+            let query = rewriter
+                .rewrite(
+                    &schema,
+                    "{ user(id: 1) { fullName friends { n: fullName } ...F } } fragment F on User { fullName }",
+                )
+                .unwrap();
+            insta::assert_snapshot!(query);
+            assert_counter!(
+                "apollo.router.operations.rewritten",
+                1,
+                "rule" = "user_full_name"
+            );
+
+            // the operations already rewritten are not counted again
+            rewriter
+                .rewrite(
+                    &schema,
+                    "{ user(id: 1) { fullName friends { n: fullName } ...F } } fragment F on User { fullName }",
+                )
+                .unwrap();
+            assert_counter!(
+                "apollo.router.operations.rewritten",
+                1,
+                "rule" = "user_full_name"
+            );
+
+            assert!(rewriter
+                .rewrite(&schema, "{ user(id: 1) { name } }")
+                .is_none());
+        }
+        .with_metrics()
+        .await;
+    }
+
+    #[test]
+    fn it_renames_fields_matching_a_pattern() {
+        let rewriter = rewriter(json!([{
+            "name": "legacy_fields",
+            "type": "User",
+            "field": "legacy_(?<field>[a-z]+)",
+            "rename_to": "${field}"
+        }]));
+        let schema = schema();
+
+        let query = rewriter
+            .rewrite(
+                &schema,
+                "{ user(id: 1) { legacy_name legacy_age legacy_friends_list } }",
+            )
+            .unwrap();
+        // `age` is not in the schema, and the whole name must match
+        insta::assert_snapshot!(query, @r###"
+        {
+          user(id: 1) {
+            legacy_name: name
+            legacy_age
+            legacy_friends_list
+          }
+        }
+        "###);
+    }
+
+    #[test]
+    fn it_adds_default_arguments() {
+        let rewriter = rewriter(json!([{
+            "name": "users_first",
+            "type": "Query",
+            "field": "users",
+            "default_arguments": { "first": 10, "includeInactive": false }
+        }]));
+        let schema = schema();
+
+        let query = rewriter
+            .rewrite(&schema, "{ users(includeInactive: true) { id } }")
+            .unwrap();
+        insta::assert_snapshot!(query);
+    }
+
+    #[test]
+    fn it_checks_rules_against_the_schema() {
+        let schema = schema();
+        let rule = |rule: serde_json::Value| {
+            Rule::new(&serde_json::from_value(rule).unwrap())
+                .unwrap()
+                .validate(&schema)
+        };
+
+        assert!(rule(json!({
+            "name": "a",
+            "type": "User",
+            "field": "fullName",
+            "rename_to": "name"
+        }))
+        .is_ok());
+        assert!(rule(json!({
+            "name": "b",
+            "type": "User",
+            "field": "fullName",
+            "rename_to": "surname"
+        }))
+        .is_err());
+        assert!(rule(json!({
+            "name": "c",
+            "type": "Query",
+            "field": "users",
+            "default_arguments": { "last": 1 }
+        }))
+        .is_err());
+        // the new names built from captured groups are checked on each operation
+        assert!(rule(json!({
+            "name": "d",
+            "type": "User",
+            "field": "legacy_(.*)",
+            "rename_to": "$1"
+        }))
+        .is_ok());
+        // the arguments are checked on every field of the schema matching the pattern
+        assert!(rule(json!({
+            "name": "e",
+            "type": "Query",
+            "field": "users?",
+            "default_arguments": { "id": 1 }
+        }))
+        .is_err());
+        assert!(rule(json!({
+            "name": "f",
+            "type": "Query",
+            "field": "accounts?",
+            "default_arguments": { "first": 1 }
+        }))
+        .is_err());
+    }
+}
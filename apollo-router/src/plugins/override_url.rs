@@ -16,11 +16,20 @@ use crate::register_plugin;
 use crate::services::subgraph;
 use crate::services::SubgraphRequest;
 
+pub(crate) const APOLLO_OVERRIDE_SUBGRAPH_URL: &str = "apollo.override_subgraph_url";
+
 #[derive(Debug, Clone)]
-struct OverrideSubgraphUrl {
+pub(crate) struct OverrideSubgraphUrl {
     urls: HashMap<String, Uri>,
 }
 
+impl OverrideSubgraphUrl {
+    /// Returns the URL configured for this subgraph, if it is overridden
+    pub(crate) fn url(&self, subgraph_name: &str) -> Option<&Uri> {
+        self.urls.get(subgraph_name)
+    }
+}
+
 /// Subgraph URL mappings
 #[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
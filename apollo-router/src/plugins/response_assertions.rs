@@ -0,0 +1,649 @@
+//! Assertions on the final responses
+//!
+//! Invariants declared in the configuration, like "the viewer id is the subject of the JWT" or
+//! "a list has at most 100 items", are checked on every response of every operation, including
+//! deferred payloads and subscription events. Paths are resolved by field name through the
+//! selections of the operation, so that aliases do not hide values from the assertions. A failed
+//! assertion is logged and counted, and can also replace the payload data with an error. This is
+//! a cheap safety net against authorization regressions in the subgraphs.
+
+use std::sync::Arc;
+
+use apollo_compiler::executable;
+use apollo_compiler::validation::Valid;
+use apollo_compiler::ExecutableDocument;
+use apollo_compiler::Schema;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json_bytes::Value;
+use tower::BoxError;
+use tower::ServiceBuilder;
+use tower::ServiceExt;
+
+use crate::context::OPERATION_NAME;
+use crate::graphql;
+use crate::json_ext::PathElement;
+use crate::plugin::Plugin;
+use crate::plugin::PluginInit;
+use crate::plugins::authentication::APOLLO_AUTHENTICATION_JWT_CLAIMS;
+use crate::register_plugin;
+use crate::services::supergraph;
+use crate::Context;
+
+const ASSERTION_FAILED_CODE: &str = "RESPONSE_ASSERTION_FAILED";
+
+/// Assertions checked on the final responses
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct ResponseAssertionsConfig {
+    /// Assertions checked on every response of every operation
+    pub(crate) assertions: Vec<Assertion>,
+}
+
+/// An assertion on the values found at a path of the response data
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Assertion {
+    /// Name of the assertion, used in logs, metrics and errors
+    pub(crate) name: String,
+    /// Path of the asserted values, as a root operation type followed by field names separated
+    /// by dots, like `Query.viewer.id`. Fields are matched by name whatever their alias. Lists on
+    /// the path are traversed, the rest of the path applies to each item. Responses without a
+    /// value at this path are not checked
+    pub(crate) path: String,
+    /// Condition the values must satisfy
+    pub(crate) condition: AssertionCondition,
+    /// What to do when the assertion fails (default: `log`)
+    #[serde(default)]
+    pub(crate) on_failure: FailureAction,
+}
+
+/// Condition on the values of an assertion
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields, rename_all = "snake_case")]
+pub(crate) enum AssertionCondition {
+    /// The values are equal to this claim of the JWT authenticating the request
+    EqualsClaim(String),
+    /// The values are equal to this entry of the request context
+    EqualsContext(String),
+    /// The values are lists with at most this number of items
+    MaxLength(usize),
+}
+
+/// Handling of a failed assertion
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum FailureAction {
+    /// Log the failure and count it in a metric
+    #[default]
+    Log,
+    /// Log and count the failure, and replace the data of the response with an error
+    Error,
+}
+
+struct CompiledAssertion {
+    name: String,
+    /// root operation type the path starts from
+    root: String,
+    /// field names of the path
+    fields: Vec<String>,
+    condition: AssertionCondition,
+    on_failure: FailureAction,
+}
+
+impl CompiledAssertion {
+    fn new(assertion: Assertion, schema: &Schema) -> Result<Self, BoxError> {
+        let invalid = |reason: String| -> BoxError {
+            format!(
+                "invalid path '{}' for the response assertion '{}': {reason}",
+                assertion.path, assertion.name
+            )
+            .into()
+        };
+        let mut segments = assertion.path.split('.').map(str::to_string);
+        let root = segments.next().unwrap_or_default();
+        let fields: Vec<String> = segments.collect();
+        if root.is_empty() || fields.is_empty() || fields.iter().any(String::is_empty) {
+            return Err(invalid(
+                "expected a root type followed by fields, like Query.viewer.id".to_string(),
+            ));
+        }
+        let mut type_name = root.clone();
+        for field in &fields {
+            let definition = schema
+                .type_field(&type_name, field)
+                .map_err(|_| invalid(format!("{type_name} has no field {field}")))?;
+            type_name = definition.ty.inner_named_type().to_string();
+        }
+
+        Ok(Self {
+            name: assertion.name,
+            root,
+            fields,
+            condition: assertion.condition,
+            on_failure: assertion.on_failure,
+        })
+    }
+
+    /// Returns true if the values at the path of the assertion, in the data of a payload located
+    /// at `payload_path`, satisfy its condition
+    fn check(
+        &self,
+        context: &Context,
+        operation: &Operation,
+        payload_path: &[PathElement],
+        data: &Value,
+    ) -> bool {
+        let Ok(operation_definition) = operation.document.operations.get(operation.name.as_deref())
+        else {
+            return true;
+        };
+        if operation_definition.object_type().as_str() != self.root {
+            return true;
+        }
+
+        let mut values = Vec::new();
+        for (selection_set, fields) in locate(
+            &operation.document,
+            &operation_definition.selection_set,
+            &self.fields,
+            payload_path,
+        ) {
+            select(
+                &operation.document,
+                selection_set,
+                fields,
+                data,
+                &mut values,
+            );
+        }
+        if values.is_empty() {
+            return true;
+        }
+
+        match &self.condition {
+            AssertionCondition::EqualsClaim(claim) => {
+                let expected = context
+                    .get_json_value(APOLLO_AUTHENTICATION_JWT_CLAIMS)
+                    .and_then(|claims| claims.as_object()?.get(claim.as_str()).cloned());
+                values_equal(&values, expected.as_ref())
+            }
+            AssertionCondition::EqualsContext(key) => {
+                let expected = context.get_json_value(key.as_str());
+                values_equal(&values, expected.as_ref())
+            }
+            AssertionCondition::MaxLength(max) => values.iter().all(|value| match value {
+                Value::Array(items) => items.len() <= *max,
+                _ => true,
+            }),
+        }
+    }
+}
+
+/// The operation the responses answer
+struct Operation {
+    document: Arc<Valid<ExecutableDocument>>,
+    name: Option<String>,
+}
+
+/// The fields selected in a selection set, through its fragments
+fn collect_fields<'a>(
+    document: &'a ExecutableDocument,
+    selection_set: &'a executable::SelectionSet,
+    fields: &mut Vec<&'a executable::Field>,
+) {
+    for selection in &selection_set.selections {
+        match selection {
+            executable::Selection::Field(field) => fields.push(field),
+            executable::Selection::FragmentSpread(spread) => {
+                if let Some(fragment) = document.fragments.get(&spread.fragment_name) {
+                    collect_fields(document, &fragment.selection_set, fields);
+                }
+            }
+            executable::Selection::InlineFragment(inline) => {
+                collect_fields(document, &inline.selection_set, fields);
+            }
+        }
+    }
+}
+
+/// Finds the selection sets of the data of a payload, like a deferred fragment, with the fields
+/// left on the path of the assertion. Payloads outside of the path have none
+fn locate<'a, 'f>(
+    document: &'a ExecutableDocument,
+    selection_set: &'a executable::SelectionSet,
+    fields: &'f [String],
+    payload_path: &[PathElement],
+) -> Vec<(&'a executable::SelectionSet, &'f [String])> {
+    let mut located = vec![(selection_set, fields)];
+    for element in payload_path {
+        let PathElement::Key(key, _) = element else {
+            continue;
+        };
+        let mut next = Vec::new();
+        for (selection_set, fields) in located {
+            let Some((field_name, rest)) = fields.split_first() else {
+                // the payload is within the asserted values
+                continue;
+            };
+            let mut selected = Vec::new();
+            collect_fields(document, selection_set, &mut selected);
+            for field in selected {
+                if field.response_key().as_str() == key && field.name.as_str() == field_name {
+                    next.push((&field.selection_set, rest));
+                }
+            }
+        }
+        located = next;
+    }
+    located
+}
+
+/// Collects the non null values at the fields of the path, following the selections of the
+/// operation and going through lists
+fn select<'a>(
+    document: &ExecutableDocument,
+    selection_set: &executable::SelectionSet,
+    fields: &[String],
+    value: &'a Value,
+    values: &mut Vec<&'a Value>,
+) {
+    match value {
+        Value::Null => {}
+        _ if fields.is_empty() => values.push(value),
+        Value::Array(items) => {
+            for item in items {
+                select(document, selection_set, fields, item, values);
+            }
+        }
+        Value::Object(object) => {
+            let mut selected = Vec::new();
+            collect_fields(document, selection_set, &mut selected);
+            for field in selected {
+                if field.name.as_str() != fields[0] {
+                    continue;
+                }
+                if let Some(value) = object.get(field.response_key().as_str()) {
+                    select(document, &field.selection_set, &fields[1..], value, values);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+// when the request has no expected value, like an unauthenticated request, any returned value
+// fails the assertion
+fn values_equal(values: &[&Value], expected: Option<&Value>) -> bool {
+    expected.is_some_and(|expected| values.iter().all(|value| *value == expected))
+}
+
+/// Checks the assertions on the data of a payload, logging and counting the failures, and
+/// returns the error replacing the data if a failed assertion requires it
+fn check_payload(
+    assertions: &[CompiledAssertion],
+    context: &Context,
+    operation: &Operation,
+    payload_path: &[PathElement],
+    data: &Value,
+) -> Option<graphql::Error> {
+    let failed: Vec<&CompiledAssertion> = assertions
+        .iter()
+        .filter(|assertion| !assertion.check(context, operation, payload_path, data))
+        .collect();
+
+    for assertion in &failed {
+        tracing::warn!(
+            assertion = %assertion.name,
+            operation_name = %operation.name.as_deref().unwrap_or_default(),
+            "the response failed an assertion"
+        );
+        u64_counter!(
+            "apollo.router.operations.response_assertions.failed",
+            "Number of responses that failed a response assertion",
+            1,
+            "assertion" = assertion.name.clone()
+        );
+    }
+
+    failed
+        .iter()
+        .find(|assertion| assertion.on_failure == FailureAction::Error)
+        .map(|assertion| {
+            graphql::Error::builder()
+                .message(format!(
+                    "the response failed the assertion '{}'",
+                    assertion.name
+                ))
+                .extension_code(ASSERTION_FAILED_CODE)
+                .build()
+        })
+}
+
+/// Checks the assertions on the primary data, or subscription event, and on the incremental
+/// payloads of the response, replacing the data of the payloads that failed an assertion if it
+/// requires it
+fn check_response(
+    assertions: &[CompiledAssertion],
+    context: &Context,
+    operation: &Operation,
+    response: &mut graphql::Response,
+) {
+    if let Some(data) = &response.data {
+        let path = response
+            .path
+            .as_ref()
+            .map(|path| path.0.as_slice())
+            .unwrap_or_default();
+        if let Some(error) = check_payload(assertions, context, operation, path, data) {
+            response.data = Some(Value::Null);
+            response.errors.push(error);
+        }
+    }
+    for incremental in &mut response.incremental {
+        let Some(data) = &incremental.data else {
+            continue;
+        };
+        let path = incremental
+            .path
+            .as_ref()
+            .map(|path| path.0.as_slice())
+            .unwrap_or_default();
+        if let Some(error) = check_payload(assertions, context, operation, path, data) {
+            incremental.data = None;
+            incremental.errors.push(error);
+        }
+    }
+}
+
+struct ResponseAssertions {
+    assertions: Arc<Vec<CompiledAssertion>>,
+}
+
+#[async_trait::async_trait]
+impl Plugin for ResponseAssertions {
+    type Config = ResponseAssertionsConfig;
+
+    async fn new(init: PluginInit<Self::Config>) -> Result<Self, BoxError> {
+        let assertions = init
+            .config
+            .assertions
+            .into_iter()
+            .map(|assertion| CompiledAssertion::new(assertion, &init.supergraph_schema))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            assertions: Arc::new(assertions),
+        })
+    }
+
+    fn supergraph_service(&self, service: supergraph::BoxService) -> supergraph::BoxService {
+        if self.assertions.is_empty() {
+            return service;
+        }
+        let assertions = self.assertions.clone();
+
+        ServiceBuilder::new()
+            .map_response(move |response: supergraph::Response| {
+                let context = response.context.clone();
+                let Some(document) = context.unsupported_executable_document() else {
+                    return response;
+                };
+                let operation = Operation {
+                    document,
+                    name: context.get::<_, String>(OPERATION_NAME).ok().flatten(),
+                };
+                let assertions = assertions.clone();
+                response.map_stream(move |mut response| {
+                    check_response(&assertions, &context, &operation, &mut response);
+                    response
+                })
+            })
+            .service(service)
+            .boxed()
+    }
+}
+
+register_plugin!("apollo", "response_assertions", ResponseAssertions);
+
+#[cfg(test)]
+mod tests {
+    use apollo_compiler::ast;
+    use serde_json_bytes::json;
+
+    use super::*;
+    use crate::json_ext::Path;
+    use crate::metrics::FutureMetricsExt;
+    use crate::plugin::test::MockSupergraphService;
+    use crate::services::layers::query_analysis::ParsedDocument;
+    use crate::services::layers::query_analysis::ParsedDocumentInner;
+
+    const SCHEMA: &str = r#"
+        type Query {
+            viewer: User
+        }
+
+        type User {
+            id: ID!
+            friends: [User]
+        }
+    "#;
+
+    fn schema() -> Arc<Valid<Schema>> {
+        Arc::new(Schema::parse_and_validate(SCHEMA, "schema.graphql").unwrap())
+    }
+
+    async fn assertions_plugin(config: serde_json::Value) -> Result<ResponseAssertions, BoxError> {
+        ResponseAssertions::new(
+            PluginInit::fake_builder()
+                .config(serde_json::from_value(config).unwrap())
+                .supergraph_schema(schema())
+                .build(),
+        )
+        .await
+    }
+
+    async fn call(
+        plugin: &ResponseAssertions,
+        subject: &str,
+        query: &str,
+        responses: Vec<graphql::Response>,
+    ) -> Vec<graphql::Response> {
+        let mut mock_service = MockSupergraphService::new();
+        mock_service.expect_call().returning(move |request| {
+            Ok(supergraph::Response::fake_stream_builder()
+                .responses(responses.clone())
+                .context(request.context)
+                .build()
+                .unwrap())
+        });
+
+        let request = supergraph::Request::fake_builder()
+            .query(query)
+            .build()
+            .unwrap();
+        let executable =
+            ExecutableDocument::parse_and_validate(&schema(), query, "query.graphql").unwrap();
+        request.context.extensions().with_lock(|mut lock| {
+            lock.insert::<ParsedDocument>(Arc::new(ParsedDocumentInner {
+                ast: ast::Document::parse(query, "query.graphql").unwrap(),
+                executable: Arc::new(executable),
+                hash: Default::default(),
+            }))
+        });
+        request
+            .context
+            .insert(
+                APOLLO_AUTHENTICATION_JWT_CLAIMS,
+                serde_json::json!({ "sub": subject }),
+            )
+            .unwrap();
+        let mut response = plugin
+            .supergraph_service(mock_service.boxed())
+            .oneshot(request)
+            .await
+            .unwrap();
+        let mut responses = Vec::new();
+        while let Some(response) = response.next_response().await {
+            responses.push(response);
+        }
+        responses
+    }
+
+    fn viewer(data: serde_json_bytes::Value) -> Vec<graphql::Response> {
+        vec![graphql::Response::builder().data(data).build()]
+    }
+
+    fn viewer_is_caller() -> serde_json::Value {
+        serde_json::json!({
+            "assertions": [{
+                "name": "viewer_is_caller",
+                "path": "Query.viewer.id",
+                "condition": { "equals_claim": "sub" },
+                "on_failure": "error"
+            }]
+        })
+    }
+
+    fn failed(response: &graphql::Response) -> bool {
+        response.errors.iter().any(|error| {
+            error.extensions.get("code").and_then(|code| code.as_str())
+                == Some(ASSERTION_FAILED_CODE)
+        })
+    }
+
+    #[tokio::test]
+    async fn it_replaces_data_when_an_assertion_fails() {
+        let plugin = assertions_plugin(viewer_is_caller()).await.unwrap();
+        let query = "{ viewer { id } }";
+
+        let responses = call(&plugin, "1", query, viewer(json!({"viewer": {"id": "1"}}))).await;
+        assert!(!failed(&responses[0]));
+        assert!(responses[0]
+            .data
+            .as_ref()
+            .is_some_and(|data| data != &Value::Null));
+
+        let responses = call(&plugin, "2", query, viewer(json!({"viewer": {"id": "1"}}))).await;
+        assert_eq!(responses[0].data, Some(Value::Null));
+        assert!(failed(&responses[0]));
+    }
+
+    #[tokio::test]
+    async fn it_resolves_aliases() {
+        let plugin = assertions_plugin(viewer_is_caller()).await.unwrap();
+
+        let responses = call(
+            &plugin,
+            "2",
+            "{ me: viewer { userId: id ... on User { id } } }",
+            viewer(json!({"me": {"userId": "1", "id": "1"}})),
+        )
+        .await;
+        assert!(failed(&responses[0]));
+
+        let responses = call(
+            &plugin,
+            "2",
+            "{ me: viewer { ...Ids } } fragment Ids on User { userId: id }",
+            viewer(json!({"me": {"userId": "1"}})),
+        )
+        .await;
+        assert!(failed(&responses[0]));
+    }
+
+    #[tokio::test]
+    async fn it_checks_deferred_payloads() {
+        let plugin = assertions_plugin(viewer_is_caller()).await.unwrap();
+
+        let responses = call(
+            &plugin,
+            "2",
+            "{ viewer { friends { id } ... @defer { userId: id } } }",
+            vec![
+                graphql::Response::builder()
+                    .data(json!({"viewer": {"friends": []}}))
+                    .has_next(true)
+                    .build(),
+                graphql::Response::builder()
+                    .incremental(vec![graphql::IncrementalResponse::builder()
+                        .data(json!({"userId": "1"}))
+                        .path(Path::from("viewer"))
+                        .build()])
+                    .has_next(false)
+                    .build(),
+            ],
+        )
+        .await;
+        assert!(!failed(&responses[0]));
+        assert_eq!(responses[1].incremental[0].data, None);
+        assert_eq!(
+            responses[1].incremental[0].errors[0]
+                .extensions
+                .get("code")
+                .and_then(|code| code.as_str()),
+            Some(ASSERTION_FAILED_CODE)
+        );
+    }
+
+    #[tokio::test]
+    async fn it_counts_failed_assertions() {
+        async {
+            let plugin = assertions_plugin(serde_json::json!({
+                "assertions": [{
+                    "name": "few_friends",
+                    "path": "Query.viewer.friends",
+                    "condition": { "max_length": 1 }
+                }, {
+                    "name": "known_friends",
+                    "path": "Query.viewer.friends.id",
+                    "condition": { "equals_context": "missing" }
+                }]
+            }))
+            .await
+            .unwrap();
+
+            let responses = call(
+                &plugin,
+                "1",
+                "{ viewer { id friends { id } } }",
+                viewer(json!({
+                    "viewer": { "id": "1", "friends": [{ "id": "2" }, { "id": "3" }] }
+                })),
+            )
+            .await;
+            // failures are only logged by default
+            assert!(responses[0].errors.is_empty());
+            assert_counter!(
+                "apollo.router.operations.response_assertions.failed",
+                1,
+                "assertion" = "few_friends"
+            );
+            assert_counter!(
+                "apollo.router.operations.response_assertions.failed",
+                1,
+                "assertion" = "known_friends"
+            );
+        }
+        .with_metrics()
+        .await;
+    }
+
+    #[tokio::test]
+    async fn it_rejects_invalid_paths() {
+        for path in ["viewer..id", "viewer.id", "Query", "Query.viewer.email"] {
+            assert!(
+                assertions_plugin(serde_json::json!({
+                    "assertions": [{
+                        "name": "invalid",
+                        "path": path,
+                        "condition": { "max_length": 1 }
+                    }]
+                }))
+                .await
+                .is_err(),
+                "{path} should be rejected"
+            );
+        }
+    }
+}
@@ -0,0 +1,789 @@
+//! Sampling of client requests and responses for offline analysis
+//!
+//! A configurable share of the operations is written, with their variables and the primary
+//! response, to a JSON Lines file. Values of sensitive fields are redacted before the sample
+//! leaves the request pipeline, and the samples are written by a background task so that the
+//! file system never slows down client requests.
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use apollo_compiler::ast;
+use apollo_compiler::executable;
+use apollo_compiler::schema::ExtendedType;
+use apollo_compiler::validation::Valid;
+use apollo_compiler::ExecutableDocument;
+use apollo_compiler::Schema;
+use rand::Rng;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json_bytes::Value;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+use tower::BoxError;
+use tower::ServiceBuilder;
+use tower::ServiceExt;
+
+use crate::graphql;
+use crate::json_ext::Object;
+use crate::layers::ServiceBuilderExt;
+use crate::plugin::Plugin;
+use crate::plugin::PluginInit;
+use crate::plugins::telemetry::CLIENT_NAME;
+use crate::register_plugin;
+use crate::services::supergraph;
+use crate::spec::query::literals::strip_literals;
+use crate::Context;
+
+const REDACTED: &str = "[REDACTED]";
+/// samples waiting to be written, new samples are dropped when the writer is that far behind
+const SAMPLES_QUEUE_SIZE: usize = 1024;
+
+/// Samples of client requests and responses, written to a file for offline analysis
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct ResponseSamplingConfig {
+    /// Enable response sampling
+    pub(crate) enabled: bool,
+    /// Number of sampled operations per thousand operations (default: 1)
+    pub(crate) per_mille: u16,
+    /// Path of the JSON Lines file the samples are appended to (default: `response_samples.jsonl`)
+    pub(crate) path: PathBuf,
+    /// Maximum size of a sample in bytes, larger samples are dropped (default: 65536)
+    pub(crate) max_sample_size: usize,
+    /// Maximum size of the samples file in bytes, new samples are dropped once it is reached
+    /// (default: 104857600)
+    pub(crate) max_file_size: u64,
+    /// Fields whose values are redacted, in variables and in response data, as schema
+    /// coordinates: `User.email` for an output field, `UserInput.email` for an input field and
+    /// `Query.users(email:)` for an argument. A bare name like `email` matches the fields,
+    /// arguments and variables of that name on any type
+    pub(crate) redacted_fields: Vec<String>,
+    /// Names of the clients whose operations are never sampled
+    pub(crate) excluded_clients: Vec<String>,
+}
+
+impl Default for ResponseSamplingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            per_mille: 1,
+            path: PathBuf::from("response_samples.jsonl"),
+            max_sample_size: 64 * 1024,
+            max_file_size: 100 * 1024 * 1024,
+            redacted_fields: Vec::new(),
+            excluded_clients: Vec::new(),
+        }
+    }
+}
+
+/// A sampled operation, written as one line of the samples file
+#[derive(Clone, Debug, Serialize)]
+struct Sample {
+    timestamp: String,
+    client_name: Option<String>,
+    operation_name: Option<String>,
+    /// query without its literals, which can hold personal data
+    query: Option<String>,
+    variables: Value,
+    data: Option<Value>,
+    /// error codes only, error messages can contain personal data
+    error_codes: Vec<String>,
+}
+
+/// A sample waiting for the response, with the operation it is redacted along
+struct PendingSample {
+    sample: Sample,
+    document: Option<Arc<Valid<ExecutableDocument>>>,
+}
+
+struct Sampler {
+    per_mille: u16,
+    max_sample_size: usize,
+    schema: Arc<Valid<Schema>>,
+    redacted_fields: HashSet<String>,
+    excluded_clients: HashSet<String>,
+    sender: mpsc::Sender<Vec<u8>>,
+}
+
+impl Sampler {
+    /// Decides whether the operation is sampled, and keeps its request in the context
+    fn on_request(&self, request: &supergraph::Request) {
+        let client_name = request.context.get::<_, String>(CLIENT_NAME).ok().flatten();
+        if client_name
+            .as_ref()
+            .is_some_and(|client_name| self.excluded_clients.contains(client_name))
+        {
+            return;
+        }
+        if rand::thread_rng().gen_range(0..1000) >= self.per_mille {
+            return;
+        }
+
+        let body = request.supergraph_request.body();
+        // the document is parsed by the router service, parse it again for requests that did
+        // not go through it
+        let document = request
+            .context
+            .unsupported_executable_document()
+            .or_else(|| {
+                let query = body.query.as_deref()?;
+                ExecutableDocument::parse_and_validate(&self.schema, query, "query.graphql")
+                    .ok()
+                    .map(Arc::new)
+            });
+        let operation = document.as_ref().and_then(|document| {
+            document
+                .operations
+                .get(body.operation_name.as_deref())
+                .ok()
+                .map(|operation| (document, operation))
+        });
+
+        let mut variables = body.variables.clone();
+        match operation {
+            Some((document, operation)) => {
+                self.redact_variables(document, operation, &mut variables)
+            }
+            // without the operation, the fields the variables go to are unknown
+            None => variables
+                .iter_mut()
+                .for_each(|(_, value)| *value = Value::String(REDACTED.into())),
+        }
+        let sample = Sample {
+            timestamp: humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+            client_name,
+            operation_name: body.operation_name.clone(),
+            query: body.query.as_deref().and_then(strip_literals),
+            variables: Value::Object(variables),
+            data: None,
+            error_codes: Vec::new(),
+        };
+        request
+            .context
+            .extensions()
+            .with_lock(|mut lock| lock.insert(PendingSample { sample, document }));
+    }
+
+    /// Completes the sample of the operation, if it was sampled, with its primary response
+    fn on_response(&self, context: &Context, response: &graphql::Response) {
+        let Some(PendingSample {
+            mut sample,
+            document,
+        }) = context
+            .extensions()
+            .with_lock(|mut lock| lock.remove::<PendingSample>())
+        else {
+            return;
+        };
+        let operation = document.as_ref().and_then(|document| {
+            document
+                .operations
+                .get(sample.operation_name.as_deref())
+                .ok()
+                .map(|operation| (document, operation))
+        });
+        // without the operation, the fields of the data cannot be redacted
+        if let Some((document, operation)) = operation {
+            sample.data = response.data.clone();
+            if let Some(data) = &mut sample.data {
+                self.redact_data(document, &operation.selection_set, data);
+            }
+        }
+        sample.error_codes = response
+            .errors
+            .iter()
+            .filter_map(|error| error.extensions.get("code"))
+            .filter_map(|code| code.as_str().map(str::to_string))
+            .collect();
+
+        let mut line = match serde_json::to_vec(&sample) {
+            Ok(line) => line,
+            Err(e) => {
+                tracing::error!("could not serialize the response sample: {e}");
+                return;
+            }
+        };
+        if line.len() > self.max_sample_size {
+            record_dropped("too_large");
+            return;
+        }
+        line.push(b'\n');
+        if self.sender.try_send(line).is_err() {
+            record_dropped("queue_full");
+        }
+    }
+
+    fn is_redacted(&self, name: &str, coordinate: String) -> bool {
+        self.redacted_fields.contains(name) || self.redacted_fields.contains(&coordinate)
+    }
+
+    /// Replaces the values of the redacted fields in the response data, following the selections
+    /// of the operation so that fields are redacted whatever their alias
+    fn redact_data(
+        &self,
+        document: &ExecutableDocument,
+        selection_set: &executable::SelectionSet,
+        value: &mut Value,
+    ) {
+        match value {
+            Value::Object(object) => {
+                for selection in &selection_set.selections {
+                    match selection {
+                        executable::Selection::Field(field) => {
+                            let Some(value) = object.get_mut(field.response_key().as_str()) else {
+                                continue;
+                            };
+                            if self.is_redacted(
+                                &field.name,
+                                format!("{}.{}", selection_set.ty, field.name),
+                            ) {
+                                *value = Value::String(REDACTED.into());
+                            } else {
+                                self.redact_data(document, &field.selection_set, value);
+                            }
+                        }
+                        executable::Selection::FragmentSpread(spread) => {
+                            if let Some(fragment) = document.fragments.get(&spread.fragment_name) {
+                                self.redact_data(document, &fragment.selection_set, value);
+                            }
+                        }
+                        executable::Selection::InlineFragment(inline) => {
+                            self.redact_data(document, &inline.selection_set, value);
+                        }
+                    }
+                }
+            }
+            Value::Array(values) => {
+                for value in values {
+                    self.redact_data(document, selection_set, value);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Replaces the values of the variables passed to redacted arguments or input fields, and the
+    /// values of the redacted input fields within variables
+    fn redact_variables(
+        &self,
+        document: &ExecutableDocument,
+        operation: &executable::Operation,
+        variables: &mut Object,
+    ) {
+        let mut redacted_variables = HashSet::new();
+        self.collect_redacted_variables(
+            document,
+            &operation.selection_set,
+            &mut redacted_variables,
+        );
+        for (name, value) in variables.iter_mut() {
+            match operation
+                .variables
+                .iter()
+                .find(|variable| variable.name.as_str() == name.as_str())
+            {
+                Some(variable)
+                    if !redacted_variables.contains(name.as_str())
+                        && !self.redacted_fields.contains(name.as_str()) =>
+                {
+                    self.redact_input(value, variable.ty.inner_named_type())
+                }
+                // variables the operation does not declare are not checked against its fields
+                _ => *value = Value::String(REDACTED.into()),
+            }
+        }
+    }
+
+    fn collect_redacted_variables(
+        &self,
+        document: &ExecutableDocument,
+        selection_set: &executable::SelectionSet,
+        redacted_variables: &mut HashSet<String>,
+    ) {
+        for selection in &selection_set.selections {
+            match selection {
+                executable::Selection::Field(field) => {
+                    for argument in &field.arguments {
+                        let redacted = self.is_redacted(
+                            &argument.name,
+                            format!("{}.{}({}:)", selection_set.ty, field.name, argument.name),
+                        );
+                        let type_name = field
+                            .definition
+                            .argument_by_name(&argument.name)
+                            .map(|definition| definition.ty.inner_named_type());
+                        self.collect_variables(
+                            &argument.value,
+                            type_name,
+                            redacted,
+                            redacted_variables,
+                        );
+                    }
+                    self.collect_redacted_variables(
+                        document,
+                        &field.selection_set,
+                        redacted_variables,
+                    );
+                }
+                executable::Selection::FragmentSpread(spread) => {
+                    if let Some(fragment) = document.fragments.get(&spread.fragment_name) {
+                        self.collect_redacted_variables(
+                            document,
+                            &fragment.selection_set,
+                            redacted_variables,
+                        );
+                    }
+                }
+                executable::Selection::InlineFragment(inline) => {
+                    self.collect_redacted_variables(
+                        document,
+                        &inline.selection_set,
+                        redacted_variables,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Collects the variables used in the value of a redacted argument or input field
+    fn collect_variables(
+        &self,
+        value: &ast::Value,
+        type_name: Option<&ast::NamedType>,
+        redacted: bool,
+        redacted_variables: &mut HashSet<String>,
+    ) {
+        match value {
+            ast::Value::Variable(name) if redacted => {
+                redacted_variables.insert(name.to_string());
+            }
+            ast::Value::List(values) => {
+                for value in values {
+                    self.collect_variables(value, type_name, redacted, redacted_variables);
+                }
+            }
+            ast::Value::Object(fields) => {
+                let input =
+                    type_name.and_then(|type_name| match self.schema.types.get(type_name) {
+                        Some(ExtendedType::InputObject(input)) => Some(input),
+                        _ => None,
+                    });
+                for (name, value) in fields {
+                    let redacted = redacted
+                        || self.is_redacted(
+                            name,
+                            format!("{}.{}", type_name.map(|t| t.as_str()).unwrap_or(""), name),
+                        );
+                    let field_type = input
+                        .and_then(|input| input.fields.get(name))
+                        .map(|field| field.ty.inner_named_type());
+                    self.collect_variables(value, field_type, redacted, redacted_variables);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Replaces the values of the redacted fields of an input value
+    fn redact_input(&self, value: &mut Value, type_name: &str) {
+        match value {
+            Value::Object(object) => {
+                let input = match self.schema.types.get(type_name) {
+                    Some(ExtendedType::InputObject(input)) => Some(input),
+                    _ => None,
+                };
+                for (key, value) in object.iter_mut() {
+                    let field_type = input
+                        .and_then(|input| input.fields.get(key.as_str()))
+                        .map(|field| field.ty.inner_named_type());
+                    match field_type {
+                        Some(field_type)
+                            if !self.is_redacted(
+                                key.as_str(),
+                                format!("{type_name}.{}", key.as_str()),
+                            ) =>
+                        {
+                            self.redact_input(value, field_type)
+                        }
+                        // unknown fields are redacted, the request is rejected anyway
+                        _ => *value = Value::String(REDACTED.into()),
+                    }
+                }
+            }
+            Value::Array(values) => {
+                for value in values {
+                    self.redact_input(value, type_name);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Adds to the redacted coordinates the fields of the interfaces the redacted fields implement,
+/// and of the types implementing them, so that the fields are redacted whatever the type they are
+/// selected on
+fn redacted_coordinates(schema: &Schema, redacted_fields: Vec<String>) -> HashSet<String> {
+    let mut coordinates: HashSet<String> = redacted_fields.into_iter().collect();
+    let mut implemented = Vec::new();
+    for (type_name, ty) in &schema.types {
+        let interfaces = match ty {
+            ExtendedType::Object(object) => &object.implements_interfaces,
+            ExtendedType::Interface(interface) => &interface.implements_interfaces,
+            _ => continue,
+        };
+        for interface in interfaces {
+            let interface = interface.to_string();
+            for coordinate in &coordinates {
+                let Some((parent, field)) = coordinate.split_once('.') else {
+                    continue;
+                };
+                if parent == type_name.as_str() {
+                    implemented.push(format!("{interface}.{field}"));
+                } else if parent == interface {
+                    implemented.push(format!("{type_name}.{field}"));
+                }
+            }
+        }
+    }
+    coordinates.extend(implemented);
+    coordinates
+}
+
+fn record_dropped(reason: &'static str) {
+    u64_counter!(
+        "apollo.router.response_sampling.dropped",
+        "Number of response samples that were not written",
+        1,
+        "reason" = reason
+    );
+}
+
+/// Appends the samples to the file, until it reaches its maximum size
+async fn write_samples(path: PathBuf, max_file_size: u64, mut receiver: mpsc::Receiver<Vec<u8>>) {
+    let mut file = match tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .await
+    {
+        Ok(file) => file,
+        Err(e) => {
+            tracing::error!("could not open the response samples file {path:?}: {e}");
+            return;
+        }
+    };
+    let mut size = file
+        .metadata()
+        .await
+        .map(|metadata| metadata.len())
+        .unwrap_or_default();
+
+    while let Some(line) = receiver.recv().await {
+        if size + line.len() as u64 > max_file_size {
+            record_dropped("file_full");
+            continue;
+        }
+        if let Err(e) = file.write_all(&line).await {
+            tracing::error!("could not write to the response samples file {path:?}: {e}");
+            continue;
+        }
+        size += line.len() as u64;
+        u64_counter!(
+            "apollo.router.response_sampling.written",
+            "Number of response samples written",
+            1
+        );
+    }
+    let _ = file.flush().await;
+}
+
+struct ResponseSampling {
+    sampler: Option<Arc<Sampler>>,
+}
+
+#[async_trait::async_trait]
+impl Plugin for ResponseSampling {
+    type Config = ResponseSamplingConfig;
+
+    async fn new(init: PluginInit<Self::Config>) -> Result<Self, BoxError> {
+        let config = init.config;
+        let schema = init.supergraph_schema;
+        if !config.enabled || config.per_mille == 0 {
+            return Ok(Self { sampler: None });
+        }
+        if config.per_mille > 1000 {
+            return Err("response_sampling.per_mille must be at most 1000".into());
+        }
+
+        // the writer task ends when the plugin and its services are dropped
+        let (sender, receiver) = mpsc::channel(SAMPLES_QUEUE_SIZE);
+        tokio::task::spawn(write_samples(config.path, config.max_file_size, receiver));
+
+        Ok(Self {
+            sampler: Some(Arc::new(Sampler {
+                per_mille: config.per_mille,
+                max_sample_size: config.max_sample_size,
+                redacted_fields: redacted_coordinates(&schema, config.redacted_fields),
+                schema,
+                excluded_clients: config.excluded_clients.into_iter().collect(),
+                sender,
+            })),
+        })
+    }
+
+    fn supergraph_service(&self, service: supergraph::BoxService) -> supergraph::BoxService {
+        let Some(sampler) = self.sampler.clone() else {
+            return service;
+        };
+        let response_sampler = sampler.clone();
+
+        ServiceBuilder::new()
+            .map_request(move |request: supergraph::Request| {
+                sampler.on_request(&request);
+                request
+            })
+            .map_first_graphql_response(move |context, parts, response| {
+                response_sampler.on_response(&context, &response);
+                (parts, response)
+            })
+            .service(service)
+            .boxed()
+    }
+}
+
+register_plugin!("apollo", "response_sampling", ResponseSampling);
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json_bytes::json;
+
+    use super::*;
+    use crate::metrics::FutureMetricsExt;
+    use crate::plugin::test::MockSupergraphService;
+
+    const SCHEMA: &str = r#"
+        type Query {
+          me: User
+          users(filter: UserFilter, email: String): [User]
+        }
+        interface Contact {
+          email: String
+        }
+        type User implements Contact {
+          name: String
+          email: String
+        }
+        input UserFilter {
+          name: String
+          email: String
+        }
+    "#;
+
+    async fn sampling_plugin(config: serde_json::Value) -> ResponseSampling {
+        ResponseSampling::new(
+            PluginInit::fake_builder()
+                .config(serde_json::from_value(config).unwrap())
+                .supergraph_schema(Arc::new(
+                    Schema::parse_and_validate(SCHEMA, "schema.graphql").unwrap(),
+                ))
+                .build(),
+        )
+        .await
+        .unwrap()
+    }
+
+    fn supergraph_service(data: serde_json_bytes::Value) -> supergraph::BoxService {
+        let mut mock_service = MockSupergraphService::new();
+        mock_service.expect_call().returning(move |request| {
+            Ok(supergraph::Response::fake_builder()
+                .data(data.clone())
+                .context(request.context)
+                .build()
+                .unwrap())
+        });
+        mock_service.boxed()
+    }
+
+    async fn call(
+        plugin: &ResponseSampling,
+        client_name: &str,
+        query: &str,
+        variables: serde_json_bytes::Value,
+        data: serde_json_bytes::Value,
+    ) {
+        let request = supergraph::Request::fake_builder()
+            .query(query)
+            .variables(variables.as_object().unwrap().clone())
+            .build()
+            .unwrap();
+        request
+            .context
+            .insert(CLIENT_NAME, client_name.to_string())
+            .unwrap();
+        let mut response = plugin
+            .supergraph_service(supergraph_service(data))
+            .oneshot(request)
+            .await
+            .unwrap();
+        response.next_response().await.unwrap();
+    }
+
+    async fn call_me(plugin: &ResponseSampling, client_name: &str) {
+        call(
+            plugin,
+            client_name,
+            "query Me($email: String) { me { name email } users(email: $email) { name } }",
+            json!({"email": "ada@example.com"}),
+            json!({"me": {"name": "Ada", "email": "ada@example.com"}, "users": []}),
+        )
+        .await
+    }
+
+    async fn read_samples(path: &std::path::Path, expected: usize) -> Vec<serde_json::Value> {
+        for _ in 0..50 {
+            let contents = tokio::fs::read_to_string(path).await.unwrap_or_default();
+            let samples: Vec<serde_json::Value> = contents
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect();
+            if samples.len() >= expected {
+                return samples;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("expected {expected} samples in {path:?}");
+    }
+
+    #[tokio::test]
+    async fn it_writes_redacted_samples() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("samples.jsonl");
+        let plugin = sampling_plugin(serde_json::json!({
+            "enabled": true,
+            "per_mille": 1000,
+            "path": path,
+            "redacted_fields": ["email"],
+            "excluded_clients": ["opted-out"]
+        }))
+        .await;
+
+        call_me(&plugin, "opted-out").await;
+        call_me(&plugin, "web").await;
+
+        let samples = read_samples(&path, 1).await;
+        assert_eq!(samples.len(), 1);
+        let sample = &samples[0];
+        assert_eq!(sample["client_name"], "web");
+        assert_eq!(sample["variables"]["email"], REDACTED);
+        assert_eq!(sample["data"]["me"]["name"], "Ada");
+        assert_eq!(sample["data"]["me"]["email"], REDACTED);
+    }
+
+    #[tokio::test]
+    async fn it_redacts_schema_coordinates_whatever_the_alias() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("samples.jsonl");
+        let plugin = sampling_plugin(serde_json::json!({
+            "enabled": true,
+            "per_mille": 1000,
+            "path": path,
+            "redacted_fields": ["User.email", "UserFilter.email", "Query.users(email:)"]
+        }))
+        .await;
+
+        call(
+            &plugin,
+            "web",
+            r#"query Users($filter: UserFilter, $byEmail: String, $name: String) {
+              me { name contact: email ... on Contact { other: email } }
+              byFilter: users(filter: $filter) { name }
+              byEmail: users(email: $byEmail) { name }
+              byName: users(filter: { name: $name, email: "ada@example.com" }) { name }
+            }"#,
+            json!({
+                "filter": {"name": "Ada", "email": "ada@example.com"},
+                "byEmail": "ada@example.com",
+                "name": "Ada"
+            }),
+            json!({
+                "me": {"name": "Ada", "contact": "ada@example.com", "other": "ada@example.com"},
+                "byFilter": [{"name": "Ada"}],
+                "byEmail": [{"name": "Ada"}],
+                "byName": [{"name": "Ada"}]
+            }),
+        )
+        .await;
+
+        let samples = read_samples(&path, 1).await;
+        let sample = &samples[0];
+        assert_eq!(sample["variables"]["filter"]["name"], "Ada");
+        assert_eq!(sample["variables"]["filter"]["email"], REDACTED);
+        assert_eq!(sample["variables"]["byEmail"], REDACTED);
+        assert_eq!(sample["variables"]["name"], "Ada");
+        assert_eq!(sample["data"]["me"]["name"], "Ada");
+        assert_eq!(sample["data"]["me"]["contact"], REDACTED);
+        assert_eq!(sample["data"]["me"]["other"], REDACTED);
+        assert_eq!(sample["data"]["byName"][0]["name"], "Ada");
+
+        let query = sample["query"].as_str().unwrap();
+        assert!(!query.contains("ada@example.com"));
+        assert!(query.contains("email: \"\""));
+    }
+
+    #[tokio::test]
+    async fn it_redacts_every_variable_of_unknown_operations() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("samples.jsonl");
+        let plugin = sampling_plugin(serde_json::json!({
+            "enabled": true,
+            "per_mille": 1000,
+            "path": path
+        }))
+        .await;
+
+        call(
+            &plugin,
+            "web",
+            "query Unknown($name: String) { unknown(name: $name) }",
+            json!({"name": "Ada"}),
+            json!({"unknown": "Ada"}),
+        )
+        .await;
+
+        let samples = read_samples(&path, 1).await;
+        assert_eq!(samples[0]["variables"]["name"], REDACTED);
+        assert_eq!(samples[0]["data"], serde_json::Value::Null);
+    }
+
+    #[tokio::test]
+    async fn it_drops_samples_over_the_size_limit() {
+        async {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("samples.jsonl");
+            let plugin = sampling_plugin(serde_json::json!({
+                "enabled": true,
+                "per_mille": 1000,
+                "path": path,
+                "max_sample_size": 10
+            }))
+            .await;
+
+            call_me(&plugin, "web").await;
+
+            assert_counter!(
+                "apollo.router.response_sampling.dropped",
+                1,
+                "reason" = "too_large"
+            );
+        }
+        .with_metrics()
+        .await;
+    }
+}
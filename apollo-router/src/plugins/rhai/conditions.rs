@@ -0,0 +1,94 @@
+//! Conditions to run the Rhai callbacks of a stage
+//!
+//! When a stage has a condition, the requests matching it go through the service wrapped by the
+//! Rhai callbacks, the others are sent directly to the next service, without running any script.
+
+use std::task::Context;
+use std::task::Poll;
+
+use futures::future::BoxFuture;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tower::buffer::Buffer;
+use tower::util::BoxService;
+use tower::BoxError;
+use tower::Service;
+use tower::ServiceBuilder;
+use tower::ServiceExt;
+
+use crate::layers::ServiceBuilderExt;
+use crate::plugins::telemetry::config_new::conditions::Condition;
+use crate::plugins::telemetry::config_new::selectors::RouterSelector;
+use crate::plugins::telemetry::config_new::selectors::SubgraphSelector;
+use crate::plugins::telemetry::config_new::selectors::SupergraphSelector;
+use crate::plugins::telemetry::config_new::Selector;
+
+/// Conditions to run the Rhai callbacks of a stage. The callbacks of a stage without condition
+/// always run
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct Conditions {
+    /// Condition to run the `router_service` callbacks
+    pub(super) router: Option<Condition<RouterSelector>>,
+    /// Condition to run the `supergraph_service` callbacks
+    pub(super) supergraph: Option<Condition<SupergraphSelector>>,
+    /// Condition to run the `subgraph_service` callbacks
+    pub(super) subgraph: Option<Condition<SubgraphSelector>>,
+}
+
+/// Wraps the service with the Rhai callbacks, only for the requests matching the condition
+pub(super) fn conditional<T, Request, Response>(
+    condition: Option<Condition<T>>,
+    service: BoxService<Request, Response, BoxError>,
+    wrap: impl FnOnce(
+        BoxService<Request, Response, BoxError>,
+    ) -> BoxService<Request, Response, BoxError>,
+) -> BoxService<Request, Response, BoxError>
+where
+    T: Selector<Request = Request> + Clone + Send + 'static,
+    Request: Send + 'static,
+    Response: Send + 'static,
+{
+    let Some(condition) = condition else {
+        return wrap(service);
+    };
+    let bypass = ServiceBuilder::new().buffered().service(service);
+    let matched = wrap(bypass.clone().boxed());
+    ConditionalService {
+        condition,
+        matched,
+        bypass,
+    }
+    .boxed()
+}
+
+struct ConditionalService<T, Request, Response> {
+    condition: Condition<T>,
+    matched: BoxService<Request, Response, BoxError>,
+    bypass: Buffer<BoxService<Request, Response, BoxError>, Request>,
+}
+
+impl<T, Request, Response> Service<Request> for ConditionalService<T, Request, Response>
+where
+    T: Selector<Request = Request> + Clone,
+    Request: Send + 'static,
+    Response: Send + 'static,
+{
+    type Response = Response;
+    type Error = BoxError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        futures::ready!(self.matched.poll_ready(cx))?;
+        self.bypass.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        // conditions keep the values computed on the request, evaluate a fresh copy each time
+        if self.condition.clone().evaluate_request(&request) == Some(true) {
+            self.matched.call(request)
+        } else {
+            Box::pin(self.bypass.call(request))
+        }
+    }
+}
@@ -48,6 +48,7 @@ use crate::plugins::authentication::APOLLO_AUTHENTICATION_JWT_CLAIMS;
 use crate::plugins::cache::entity::CONTEXT_CACHE_KEY;
 use crate::plugins::subscription::SUBSCRIPTION_WS_CUSTOM_CONNECTION_PARAMS;
 use crate::query_planner::APOLLO_OPERATION_ID;
+use crate::query_planner::APOLLO_OPERATION_SIGNATURE;
 use crate::Context;
 
 const CANNOT_ACCESS_HEADERS_ON_A_DEFERRED_RESPONSE: &str =
@@ -1777,6 +1778,10 @@ impl Rhai {
         );
         global_variables.insert("APOLLO_ENTITY_CACHE_KEY".into(), CONTEXT_CACHE_KEY.into());
         global_variables.insert("APOLLO_OPERATION_ID".into(), APOLLO_OPERATION_ID.into());
+        global_variables.insert(
+            "APOLLO_OPERATION_SIGNATURE".into(),
+            APOLLO_OPERATION_SIGNATURE.into(),
+        );
 
         let shared_globals = Arc::new(global_variables);
 
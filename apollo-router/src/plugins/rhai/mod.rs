@@ -1,9 +1,11 @@
 //! Customization via Rhai.
 
+use std::cell::RefCell;
 use std::fmt;
 use std::ops::ControlFlow;
 use std::path::PathBuf;
 use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::sync::Mutex;
@@ -33,11 +35,14 @@ use rhai::Shared;
 use rhai::AST;
 use schemars::JsonSchema;
 use serde::Deserialize;
+use tower::service_fn;
 use tower::util::BoxService;
 use tower::BoxError;
 use tower::ServiceBuilder;
 use tower::ServiceExt;
 
+use self::conditions::conditional;
+use self::conditions::Conditions;
 use self::engine::RhaiService;
 use self::engine::SharedMut;
 use crate::error::Error;
@@ -46,11 +51,23 @@ use crate::plugin::Plugin;
 use crate::plugin::PluginInit;
 use crate::plugins::rhai::engine::OptionDance;
 use crate::register_plugin;
+use crate::services::SupergraphRequest;
 
+mod conditions;
 mod engine;
 
 pub(crate) const RHAI_SPAN_NAME: &str = "rhai_plugin";
 
+/// Operation of the canned requests of the smoke test of reloaded scripts
+const SMOKE_TEST_QUERY: &str = "query SmokeTest { __typename }";
+/// Subgraph name passed to the `subgraph_service` callback by the smoke test
+const SMOKE_TEST_SUBGRAPH: &str = "smoke_test";
+
+tokio::task_local! {
+    /// Errors of the Rhai engine met while running the callbacks of a smoke test
+    static SMOKE_TEST_ERRORS: RefCell<Vec<String>>;
+}
+
 mod execution;
 mod router;
 mod subgraph;
@@ -105,6 +122,7 @@ struct Rhai {
     block: Arc<ArcSwap<EngineBlock>>,
     park_flag: Arc<AtomicBool>,
     watcher_handle: Option<std::thread::JoinHandle<()>>,
+    conditions: Conditions,
 }
 
 /// Configuration for the Rhai Plugin
@@ -115,6 +133,101 @@ pub(crate) struct Conf {
     scripts: Option<PathBuf>,
     /// The main entry point for Rhai script evaluation
     main: Option<String>,
+    /// Conditions to run the callbacks of the router, supergraph and subgraph stages
+    #[serde(default)]
+    conditions: Conditions,
+    /// Reloading of the scripts when their files change
+    #[serde(default)]
+    reload: Reload,
+}
+
+/// Reloading of the Rhai scripts
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct Reload {
+    /// Watch the files of the scripts directory, and reload the scripts when they change
+    /// without reloading the router configuration. Default: true
+    watch: bool,
+    /// Before replacing the running scripts, run the service callbacks of the new scripts on
+    /// canned requests. If the Rhai engine fails to run a callback, for example because it calls
+    /// a function that does not exist, the running scripts are kept. Errors thrown by the
+    /// scripts are not failures. Default: true
+    smoke_test: bool,
+}
+
+impl Default for Reload {
+    fn default() -> Self {
+        Self {
+            watch: true,
+            smoke_test: true,
+        }
+    }
+}
+
+/// Reloads the scripts in the watcher thread
+struct Reloader {
+    scripts: PathBuf,
+    main: PathBuf,
+    sdl: Arc<String>,
+    block: Arc<ArcSwap<EngineBlock>>,
+    smoke_test: bool,
+    runtime: tokio::runtime::Handle,
+    /// Number of reloads started, so that a reload finishing after a later one is not applied
+    generation: Arc<AtomicU64>,
+}
+
+impl Reloader {
+    /// Compiles the changed scripts and swaps them with the running ones. The running scripts
+    /// are kept if the new ones do not compile or fail the smoke test
+    fn reload(&self) {
+        let block = match EngineBlock::try_new(
+            Some(self.scripts.clone()),
+            self.main.clone(),
+            self.sdl.clone(),
+        ) {
+            Ok(block) => Arc::new(block),
+            Err(e) => {
+                tracing::warn!("could not create new rhai execution engine: {}", e);
+                Self::record("compile_error");
+                return;
+            }
+        };
+
+        // the callbacks run on the router runtime, the watcher thread is not part of it
+        let generation = self.generation.fetch_add(1, Ordering::AcqRel) + 1;
+        let latest = self.generation.clone();
+        let running = self.block.clone();
+        let smoke_test = self.smoke_test;
+        self.runtime.spawn(async move {
+            if smoke_test {
+                if let Err(e) = Rhai::smoke_test(block.clone()).await {
+                    tracing::warn!(
+                        "new rhai scripts failed the smoke test, keeping the running scripts: {}",
+                        e
+                    );
+                    Self::record("smoke_test_error");
+                    return;
+                }
+            }
+            if latest.load(Ordering::Acquire) != generation {
+                // the scripts changed again, the next reload applies them
+                return;
+            }
+
+            tracing::info!("updating rhai execution engine");
+            running.store(block);
+            Self::record("success");
+        });
+    }
+
+    fn record(outcome: &'static str) {
+        u64_counter!(
+            "apollo.router.rhai.reload",
+            "Number of reloads of the Rhai scripts after a change of their files",
+            1,
+            "outcome" = outcome
+        );
+    }
 }
 
 #[async_trait::async_trait]
@@ -135,22 +248,124 @@ impl Plugin for Rhai {
 
         let main = scripts_path.join(main_file);
 
-        let watched_path = scripts_path.clone();
-        let watched_main = main.clone();
-        let watched_sdl = sdl.clone();
-
         let block = Arc::new(ArcSwap::from_pointee(EngineBlock::try_new(
-            Some(scripts_path),
-            main,
-            sdl,
+            Some(scripts_path.clone()),
+            main.clone(),
+            sdl.clone(),
         )?));
-        let watched_block = block.clone();
 
         let park_flag = Arc::new(AtomicBool::new(false));
-        let watching_flag = park_flag.clone();
+        let watcher_handle = init.config.reload.watch.then(|| {
+            Self::watch(
+                Reloader {
+                    scripts: scripts_path,
+                    main,
+                    sdl,
+                    block: block.clone(),
+                    smoke_test: init.config.reload.smoke_test,
+                    runtime: tokio::runtime::Handle::current(),
+                    generation: Default::default(),
+                },
+                park_flag.clone(),
+            )
+        });
+
+        Ok(Self {
+            block,
+            park_flag,
+            watcher_handle,
+            conditions: init.config.conditions,
+        })
+    }
+
+    fn router_service(&self, service: router::BoxService) -> router::BoxService {
+        const FUNCTION_NAME_SERVICE: &str = "router_service";
+        if !self.ast_has_function(FUNCTION_NAME_SERVICE) {
+            return service;
+        }
+        tracing::debug!("router_service function found");
+        conditional(self.conditions.router.clone(), service, |service| {
+            let shared_service = Arc::new(Mutex::new(Some(service)));
+            if let Err(error) = self.run_rhai_service(
+                FUNCTION_NAME_SERVICE,
+                None,
+                ServiceStep::Router(shared_service.clone()),
+                self.block.load().scope.clone(),
+            ) {
+                tracing::error!("service callback failed: {error}");
+                record_smoke_test_error(error);
+            }
+            shared_service.take_unwrap()
+        })
+    }
+
+    fn supergraph_service(&self, service: supergraph::BoxService) -> supergraph::BoxService {
+        const FUNCTION_NAME_SERVICE: &str = "supergraph_service";
+        if !self.ast_has_function(FUNCTION_NAME_SERVICE) {
+            return service;
+        }
+        tracing::debug!("supergraph_service function found");
+        conditional(self.conditions.supergraph.clone(), service, |service| {
+            let shared_service = Arc::new(Mutex::new(Some(service)));
+            if let Err(error) = self.run_rhai_service(
+                FUNCTION_NAME_SERVICE,
+                None,
+                ServiceStep::Supergraph(shared_service.clone()),
+                self.block.load().scope.clone(),
+            ) {
+                tracing::error!("service callback failed: {error}");
+                record_smoke_test_error(error);
+            }
+            shared_service.take_unwrap()
+        })
+    }
+
+    fn execution_service(&self, service: execution::BoxService) -> execution::BoxService {
+        const FUNCTION_NAME_SERVICE: &str = "execution_service";
+        if !self.ast_has_function(FUNCTION_NAME_SERVICE) {
+            return service;
+        }
+        tracing::debug!("execution_service function found");
+        let shared_service = Arc::new(Mutex::new(Some(service)));
+        if let Err(error) = self.run_rhai_service(
+            FUNCTION_NAME_SERVICE,
+            None,
+            ServiceStep::Execution(shared_service.clone()),
+            self.block.load().scope.clone(),
+        ) {
+            tracing::error!("service callback failed: {error}");
+            record_smoke_test_error(error);
+        }
+        shared_service.take_unwrap()
+    }
+
+    fn subgraph_service(&self, name: &str, service: subgraph::BoxService) -> subgraph::BoxService {
+        const FUNCTION_NAME_SERVICE: &str = "subgraph_service";
+        if !self.ast_has_function(FUNCTION_NAME_SERVICE) {
+            return service;
+        }
+        tracing::debug!("subgraph_service function found");
+        conditional(self.conditions.subgraph.clone(), service, |service| {
+            let shared_service = Arc::new(Mutex::new(Some(service)));
+            if let Err(error) = self.run_rhai_service(
+                FUNCTION_NAME_SERVICE,
+                Some(name),
+                ServiceStep::Subgraph(shared_service.clone()),
+                self.block.load().scope.clone(),
+            ) {
+                tracing::error!("service callback failed: {error}");
+                record_smoke_test_error(error);
+            }
+            shared_service.take_unwrap()
+        })
+    }
+}
 
-        let watcher_handle = std::thread::spawn(move || {
-            let watching_path = watched_path.clone();
+impl Rhai {
+    /// Watches the scripts directory in a thread parked until this Rhai instance is dropped
+    fn watch(reloader: Reloader, park_flag: Arc<AtomicBool>) -> std::thread::JoinHandle<()> {
+        std::thread::spawn(move || {
+            let watched_path = reloader.scripts.clone();
             let config = Config::default()
                 .with_poll_interval(Duration::from_secs(3))
                 .with_compare_contents(true);
@@ -178,22 +393,7 @@ impl Plugin for Rhai {
                                 }
 
                                 if proceed {
-                                    match EngineBlock::try_new(
-                                        Some(watching_path.clone()),
-                                        watched_main.clone(),
-                                        watched_sdl.clone(),
-                                    ) {
-                                        Ok(eb) => {
-                                            tracing::info!("updating rhai execution engine");
-                                            watched_block.store(Arc::new(eb))
-                                        }
-                                        Err(e) => {
-                                            tracing::warn!(
-                                                "could not create new rhai execution engine: {}",
-                                                e
-                                            );
-                                        }
-                                    }
+                                    reloader.reload();
                                 }
                             }
                         }
@@ -208,91 +408,111 @@ impl Plugin for Rhai {
                 .unwrap_or_else(|_| panic!("could not watch: {watched_path:?}"));
             // Park the thread until this Rhai instance is dropped (see Drop impl)
             // We may actually unpark() before this code executes or exit from park() spuriously.
-            // Use the watching_flag to control a loop which waits from the flag to be updated
+            // Use the park_flag to control a loop which waits from the flag to be updated
             // from Drop.
-            while !watching_flag.load(Ordering::Acquire) {
+            while !park_flag.load(Ordering::Acquire) {
                 std::thread::park();
             }
-        });
-
-        Ok(Self {
-            block,
-            park_flag,
-            watcher_handle: Some(watcher_handle),
         })
     }
 
-    fn router_service(&self, service: router::BoxService) -> router::BoxService {
-        const FUNCTION_NAME_SERVICE: &str = "router_service";
-        if !self.ast_has_function(FUNCTION_NAME_SERVICE) {
-            return service;
-        }
-        tracing::debug!("router_service function found");
-        let shared_service = Arc::new(Mutex::new(Some(service)));
-        if let Err(error) = self.run_rhai_service(
-            FUNCTION_NAME_SERVICE,
-            None,
-            ServiceStep::Router(shared_service.clone()),
-            self.block.load().scope.clone(),
-        ) {
-            tracing::error!("service callback failed: {error}");
-        }
-        shared_service.take_unwrap()
+    /// Runs the service callbacks of the scripts on canned requests, with services returning
+    /// canned responses. Fails if the Rhai engine fails to run a callback. Errors thrown by the
+    /// scripts are not failures, since scripts may reject the canned requests
+    async fn smoke_test(block: Arc<EngineBlock>) -> Result<(), BoxError> {
+        SMOKE_TEST_ERRORS
+            .scope(RefCell::new(Vec::new()), Self::run_smoke_test(block))
+            .await
     }
 
-    fn supergraph_service(&self, service: supergraph::BoxService) -> supergraph::BoxService {
-        const FUNCTION_NAME_SERVICE: &str = "supergraph_service";
-        if !self.ast_has_function(FUNCTION_NAME_SERVICE) {
-            return service;
-        }
-        tracing::debug!("supergraph_service function found");
-        let shared_service = Arc::new(Mutex::new(Some(service)));
-        if let Err(error) = self.run_rhai_service(
-            FUNCTION_NAME_SERVICE,
-            None,
-            ServiceStep::Supergraph(shared_service.clone()),
-            self.block.load().scope.clone(),
-        ) {
-            tracing::error!("service callback failed: {error}");
-        }
-        shared_service.take_unwrap()
-    }
+    async fn run_smoke_test(block: Arc<EngineBlock>) -> Result<(), BoxError> {
+        let rhai = Rhai {
+            block: Arc::new(ArcSwap::new(block)),
+            park_flag: Arc::new(AtomicBool::new(false)),
+            watcher_handle: None,
+            conditions: Conditions::default(),
+        };
+        let request = || {
+            SupergraphRequest::fake_builder()
+                .query(SMOKE_TEST_QUERY)
+                .operation_name("SmokeTest")
+                .build()
+        };
+        let check = |stage: &str| {
+            SMOKE_TEST_ERRORS.with(|errors| match errors.borrow_mut().drain(..).next() {
+                Some(error) => Err(format!("{stage} failed: {error}")),
+                None => Ok(()),
+            })
+        };
 
-    fn execution_service(&self, service: execution::BoxService) -> execution::BoxService {
-        const FUNCTION_NAME_SERVICE: &str = "execution_service";
-        if !self.ast_has_function(FUNCTION_NAME_SERVICE) {
-            return service;
-        }
-        tracing::debug!("execution_service function found");
-        let shared_service = Arc::new(Mutex::new(Some(service)));
-        if let Err(error) = self.run_rhai_service(
-            FUNCTION_NAME_SERVICE,
-            None,
-            ServiceStep::Execution(shared_service.clone()),
-            self.block.load().scope.clone(),
-        ) {
-            tracing::error!("service callback failed: {error}");
-        }
-        shared_service.take_unwrap()
+        rhai.router_service(
+            service_fn(|request: router::Request| async move {
+                router::Response::fake_builder()
+                    .context(request.context)
+                    .build()
+            })
+            .boxed(),
+        )
+        .oneshot(request()?.try_into()?)
+        .await?;
+        check("router_service")?;
+
+        rhai.supergraph_service(
+            service_fn(|request: supergraph::Request| async move {
+                supergraph::Response::fake_builder()
+                    .context(request.context)
+                    .build()
+            })
+            .boxed(),
+        )
+        .oneshot(request()?)
+        .await?;
+        check("supergraph_service")?;
+
+        rhai.execution_service(
+            service_fn(|request: execution::Request| async move {
+                execution::Response::fake_builder()
+                    .context(request.context)
+                    .build()
+            })
+            .boxed(),
+        )
+        .oneshot(
+            execution::Request::fake_builder()
+                .supergraph_request(request()?.supergraph_request)
+                .build(),
+        )
+        .await?;
+        check("execution_service")?;
+
+        rhai.subgraph_service(
+            SMOKE_TEST_SUBGRAPH,
+            service_fn(|request: subgraph::Request| async move {
+                Ok::<_, BoxError>(
+                    subgraph::Response::fake_builder()
+                        .context(request.context)
+                        .subgraph_name(SMOKE_TEST_SUBGRAPH)
+                        .build(),
+                )
+            })
+            .boxed(),
+        )
+        .oneshot(
+            subgraph::Request::fake_builder()
+                .subgraph_request(request()?.supergraph_request)
+                .subgraph_name(SMOKE_TEST_SUBGRAPH)
+                .build(),
+        )
+        .await?;
+        check("subgraph_service")?;
+
+        Ok(())
     }
+}
 
-    fn subgraph_service(&self, name: &str, service: subgraph::BoxService) -> subgraph::BoxService {
-        const FUNCTION_NAME_SERVICE: &str = "subgraph_service";
-        if !self.ast_has_function(FUNCTION_NAME_SERVICE) {
-            return service;
-        }
-        tracing::debug!("subgraph_service function found");
-        let shared_service = Arc::new(Mutex::new(Some(service)));
-        if let Err(error) = self.run_rhai_service(
-            FUNCTION_NAME_SERVICE,
-            Some(name),
-            ServiceStep::Subgraph(shared_service.clone()),
-            self.block.load().scope.clone(),
-        ) {
-            tracing::error!("service callback failed: {error}");
-        }
-        shared_service.take_unwrap()
-    }
+/// Records an error of the Rhai engine for the smoke test running the callback, if any
+fn record_smoke_test_error(error: String) {
+    let _ = SMOKE_TEST_ERRORS.try_with(|errors| errors.borrow_mut().push(error));
 }
 
 impl Drop for Rhai {
@@ -793,6 +1013,10 @@ fn process_error(error: Box<EvalAltResult>) -> ErrorDetails {
     };
 
     let inner_error = error.unwrap_inner();
+    // errors thrown by the scripts are runtime errors, the other ones come from the engine
+    if !matches!(inner_error, EvalAltResult::ErrorRuntime(..)) {
+        record_smoke_test_error(inner_error.to_string());
+    }
     // We only want to process runtime errors
     if let EvalAltResult::ErrorRuntime(obj, pos) = inner_error {
         if let Ok(temp_error_details) = rhai::serde::from_dynamic::<ErrorDetails>(obj) {
@@ -20,6 +20,7 @@ use uuid::Uuid;
 
 use super::process_error;
 use super::subgraph;
+use super::EngineBlock;
 use super::PathBuf;
 use super::Rhai;
 use crate::graphql;
@@ -167,6 +168,58 @@ async fn rhai_plugin_supergraph_service() -> Result<(), BoxError> {
     Ok(())
 }
 
+#[tokio::test]
+async fn rhai_plugin_supergraph_service_with_condition() -> Result<(), BoxError> {
+    let dyn_plugin: Box<dyn DynPlugin> = crate::plugin::plugins()
+        .find(|factory| factory.name == "apollo.rhai")
+        .expect("Plugin not found")
+        .create_instance_without_schema(&serde_json::json!({
+            "scripts": "tests/fixtures",
+            "main": "test.rhai",
+            "conditions": {
+                "supergraph": { "eq": [{ "request_header": "x-run-rhai" }, "true"] }
+            }
+        }))
+        .await
+        .unwrap();
+
+    for run_rhai in [false, true] {
+        let mut mock_service = MockSupergraphService::new();
+        mock_service
+            .expect_call()
+            .times(1)
+            .returning(move |req: SupergraphRequest| {
+                Ok(SupergraphResponse::fake_builder()
+                    .context(req.context)
+                    .build()
+                    .unwrap())
+            });
+        let mut supergraph_service = dyn_plugin.supergraph_service(BoxService::new(mock_service));
+
+        let context = Context::new();
+        context.insert("test", 5i64).unwrap();
+        let supergraph_req = SupergraphRequest::fake_builder()
+            .header("x-run-rhai", run_rhai.to_string())
+            .context(context)
+            .build()?;
+        let supergraph_resp = supergraph_service
+            .ready()
+            .await?
+            .call(supergraph_req)
+            .await?;
+
+        let headers = supergraph_resp.response.headers();
+        assert_eq!(headers.contains_key("coming_from_entries"), run_rhai);
+        let test = supergraph_resp
+            .context
+            .get::<_, i64>("test")
+            .unwrap()
+            .unwrap();
+        assert_eq!(test, if run_rhai { 42i64 } else { 5i64 });
+    }
+    Ok(())
+}
+
 #[tokio::test]
 async fn rhai_plugin_execution_service_error() -> Result<(), BoxError> {
     let mut mock_service = MockExecutionService::new();
@@ -695,6 +748,35 @@ async fn it_mentions_source_when_syntax_error_occurs() {
     assert!(err.to_string().contains("syntax_errors.rhai"));
 }
 
+#[tokio::test]
+async fn it_smoke_tests_reloaded_scripts() {
+    let block = |main: &str| {
+        Arc::new(
+            EngineBlock::try_new(
+                Some(PathBuf::from("tests/fixtures")),
+                PathBuf::from("tests/fixtures").join(main),
+                Default::default(),
+            )
+            .unwrap(),
+        )
+    };
+
+    // errors thrown by the scripts, like rejecting the unauthenticated canned requests, are not
+    // failures
+    Rhai::smoke_test(block("require_authentication.rhai"))
+        .await
+        .unwrap();
+    Rhai::smoke_test(block("smoke_test_throw.rhai"))
+        .await
+        .unwrap();
+    let error = Rhai::smoke_test(block("smoke_test_failure.rhai"))
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(error.starts_with("supergraph_service failed: "), "{error}");
+    assert!(error.contains("validate_request"), "{error}");
+}
+
 #[test]
 #[should_panic(
     expected = "can use env: ErrorRuntime(\"could not expand variable: THIS_SHOULD_NOT_EXIST, environment variable not found\", none)"
@@ -0,0 +1,377 @@
+//! Graph of the types, fields and relations of the API schema, for visualization tooling
+//!
+//! The subgraphs owning each type and field are read from the join spec directives of the
+//! supergraph, which are not visible in the API schema.
+
+use std::collections::HashMap;
+use std::ops::Deref;
+
+use apollo_compiler::ast;
+use apollo_compiler::schema::ComponentName;
+use apollo_compiler::schema::ExtendedType;
+use apollo_compiler::schema::FieldDefinition;
+use apollo_compiler::Name;
+use apollo_compiler::Schema;
+use serde::Serialize;
+
+use crate::plugins::progressive_override::JOIN_FIELD_DIRECTIVE_NAME;
+use crate::spec::query::change::JOIN_TYPE_DIRECTIVE_NAME;
+
+const JOIN_GRAPH_ENUM_NAME: &str = "join__Graph";
+const JOIN_GRAPH_DIRECTIVE_NAME: &str = "join__graph";
+
+#[derive(Debug, Serialize)]
+pub(super) struct SchemaGraph {
+    /// names of the subgraphs
+    subgraphs: Vec<String>,
+    types: Vec<TypeNode>,
+    relations: Vec<Relation>,
+}
+
+#[derive(Debug, Serialize)]
+struct TypeNode {
+    name: String,
+    kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    /// subgraphs defining the type
+    subgraphs: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    fields: Vec<FieldNode>,
+}
+
+#[derive(Debug, Serialize)]
+struct FieldNode {
+    name: String,
+    #[serde(rename = "type")]
+    ty: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    arguments: Vec<ArgumentNode>,
+    /// subgraphs that can resolve the field
+    subgraphs: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ArgumentNode {
+    name: String,
+    #[serde(rename = "type")]
+    ty: String,
+}
+
+/// Edge between two types of the graph
+#[derive(Debug, Serialize)]
+struct Relation {
+    from: String,
+    to: String,
+    kind: RelationKind,
+    /// field returning the target type, for `field` relations
+    #[serde(skip_serializing_if = "Option::is_none")]
+    field: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum RelationKind {
+    /// a field of the source type returns the target type
+    Field,
+    /// the source type implements the target interface
+    Implements,
+    /// the source union has the target type as member
+    Member,
+}
+
+impl SchemaGraph {
+    /// Builds the graph of the API schema, with ownership from the supergraph schema
+    pub(super) fn new(supergraph: &Schema, api: &Schema) -> Self {
+        // subgraph names, by join__Graph enum value
+        let graphs: HashMap<&Name, String> = supergraph
+            .get_enum(JOIN_GRAPH_ENUM_NAME)
+            .map(|join_enum| {
+                join_enum
+                    .values
+                    .iter()
+                    .filter_map(|(value, definition)| {
+                        let name = definition
+                            .directives
+                            .get(JOIN_GRAPH_DIRECTIVE_NAME)?
+                            .argument_by_name("name")?
+                            .as_str()?;
+                        Some((value, name.to_string()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let mut builder = Builder {
+            supergraph,
+            api,
+            graphs,
+            relations: Vec::new(),
+        };
+
+        let mut types: Vec<TypeNode> = api
+            .types
+            .iter()
+            .filter(|(_, ty)| !ty.is_built_in())
+            .map(|(name, ty)| builder.type_node(name, ty))
+            .collect();
+        types.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut subgraphs: Vec<String> = builder.graphs.into_values().collect();
+        subgraphs.sort();
+        Self {
+            subgraphs,
+            types,
+            relations: builder.relations,
+        }
+    }
+}
+
+struct Builder<'a> {
+    supergraph: &'a Schema,
+    api: &'a Schema,
+    /// subgraph names, by join__Graph enum value
+    graphs: HashMap<&'a Name, String>,
+    relations: Vec<Relation>,
+}
+
+impl<'a> Builder<'a> {
+    fn type_node(&mut self, name: &Name, ty: &ExtendedType) -> TypeNode {
+        let subgraphs = self
+            .supergraph
+            .types
+            .get(name)
+            .map(|ty| self.subgraphs(ty.directives().get_all(JOIN_TYPE_DIRECTIVE_NAME)))
+            .unwrap_or_default();
+
+        let fields = match ty {
+            ExtendedType::Object(object) => {
+                self.implements(name, &object.implements_interfaces);
+                object
+                    .fields
+                    .iter()
+                    .map(|(field_name, field)| self.field_node(name, field_name, field, &subgraphs))
+                    .collect()
+            }
+            ExtendedType::Interface(interface) => {
+                self.implements(name, &interface.implements_interfaces);
+                interface
+                    .fields
+                    .iter()
+                    .map(|(field_name, field)| self.field_node(name, field_name, field, &subgraphs))
+                    .collect()
+            }
+            ExtendedType::Union(union) => {
+                for member in &union.members {
+                    self.relations.push(Relation {
+                        from: name.to_string(),
+                        to: member.to_string(),
+                        kind: RelationKind::Member,
+                        field: None,
+                    });
+                }
+                Vec::new()
+            }
+            ExtendedType::InputObject(input) => input
+                .fields
+                .iter()
+                .map(|(field_name, field)| FieldNode {
+                    name: field_name.to_string(),
+                    ty: field.ty.to_string(),
+                    description: field.description.as_ref().map(|d| d.to_string()),
+                    arguments: Vec::new(),
+                    subgraphs: subgraphs.clone(),
+                })
+                .collect(),
+            ExtendedType::Scalar(_) | ExtendedType::Enum(_) => Vec::new(),
+        };
+
+        TypeNode {
+            name: name.to_string(),
+            kind: kind(ty),
+            description: description(ty),
+            subgraphs,
+            fields,
+        }
+    }
+
+    fn implements<'b>(
+        &mut self,
+        name: &Name,
+        interfaces: impl IntoIterator<Item = &'b ComponentName>,
+    ) {
+        for interface in interfaces {
+            self.relations.push(Relation {
+                from: name.to_string(),
+                to: interface.to_string(),
+                kind: RelationKind::Implements,
+                field: None,
+            });
+        }
+    }
+
+    fn field_node(
+        &mut self,
+        type_name: &Name,
+        name: &Name,
+        field: &FieldDefinition,
+        type_subgraphs: &[String],
+    ) -> FieldNode {
+        let target = field.ty.inner_named_type();
+        if self
+            .api
+            .types
+            .get(target)
+            .is_some_and(|target| target.is_object() || target.is_interface() || target.is_union())
+        {
+            self.relations.push(Relation {
+                from: type_name.to_string(),
+                to: target.to_string(),
+                kind: RelationKind::Field,
+                field: Some(name.to_string()),
+            });
+        }
+
+        // without join__field directives with a graph, the field is resolved by all the
+        // subgraphs defining the type
+        let subgraphs = self
+            .supergraph
+            .type_field(type_name, name)
+            .map(|definition| {
+                self.subgraphs(
+                    definition
+                        .directives
+                        .get_all(JOIN_FIELD_DIRECTIVE_NAME)
+                        .filter(|directive| {
+                            !is_true(directive, "external") && !is_true(directive, "usedOverridden")
+                        }),
+                )
+            })
+            .unwrap_or_default();
+        let subgraphs = if subgraphs.is_empty() {
+            type_subgraphs.to_vec()
+        } else {
+            subgraphs
+        };
+
+        FieldNode {
+            name: name.to_string(),
+            ty: field.ty.to_string(),
+            description: field.description.as_ref().map(|d| d.to_string()),
+            arguments: field
+                .arguments
+                .iter()
+                .map(|argument| ArgumentNode {
+                    name: argument.name.to_string(),
+                    ty: argument.ty.to_string(),
+                })
+                .collect(),
+            subgraphs,
+        }
+    }
+
+    /// Names of the subgraphs in the `graph` argument of join directives, sorted
+    fn subgraphs<'b, D: Deref<Target = ast::Directive> + 'b>(
+        &self,
+        directives: impl Iterator<Item = &'b D>,
+    ) -> Vec<String> {
+        let mut names: Vec<String> = directives
+            .filter_map(|directive| {
+                let value = directive.argument_by_name("graph")?.as_enum()?;
+                self.graphs.get(value).cloned()
+            })
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+}
+
+fn kind(ty: &ExtendedType) -> &'static str {
+    match ty {
+        ExtendedType::Scalar(_) => "scalar",
+        ExtendedType::Object(_) => "object",
+        ExtendedType::Interface(_) => "interface",
+        ExtendedType::Union(_) => "union",
+        ExtendedType::Enum(_) => "enum",
+        ExtendedType::InputObject(_) => "input_object",
+    }
+}
+
+fn description(ty: &ExtendedType) -> Option<String> {
+    let description = match ty {
+        ExtendedType::Scalar(ty) => &ty.description,
+        ExtendedType::Object(ty) => &ty.description,
+        ExtendedType::Interface(ty) => &ty.description,
+        ExtendedType::Union(ty) => &ty.description,
+        ExtendedType::Enum(ty) => &ty.description,
+        ExtendedType::InputObject(ty) => &ty.description,
+    };
+    description.as_ref().map(|d| d.to_string())
+}
+
+fn is_true(directive: &ast::Directive, argument: &str) -> bool {
+    directive
+        .argument_by_name(argument)
+        .and_then(|value| value.to_bool())
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn it_builds_the_graph_with_subgraph_ownership() {
+        let schema = crate::spec::Schema::parse(
+            include_str!("../../testdata/supergraph.graphql"),
+            &Default::default(),
+        )
+        .unwrap();
+        let graph = serde_json::to_value(SchemaGraph::new(
+            schema.supergraph_schema(),
+            schema.api_schema(),
+        ))
+        .unwrap();
+
+        assert_eq!(
+            graph["subgraphs"],
+            json!(["accounts", "inventory", "products", "reviews"])
+        );
+        let types = graph["types"].as_array().unwrap();
+        assert!(types
+            .iter()
+            .all(|ty| !ty["name"].as_str().unwrap().starts_with("join__")));
+
+        let product = types.iter().find(|ty| ty["name"] == "Product").unwrap();
+        assert_eq!(product["kind"], "object");
+        assert_eq!(
+            product["subgraphs"],
+            json!(["inventory", "products", "reviews"])
+        );
+        let field = |name: &str| {
+            product["fields"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|field| field["name"] == name)
+                .unwrap()
+                .clone()
+        };
+        assert_eq!(field("inStock")["subgraphs"], json!(["inventory"]));
+        assert_eq!(field("reviews")["type"], "[Review]");
+        assert_eq!(field("reviews")["subgraphs"], json!(["reviews"]));
+
+        let relations = graph["relations"].as_array().unwrap();
+        assert!(relations.contains(&json!({
+            "from": "Product",
+            "to": "Review",
+            "kind": "field",
+            "field": "reviews"
+        })));
+        assert!(!relations.iter().any(|relation| relation["field"] == "name"));
+    }
+}
@@ -0,0 +1,335 @@
+//! Authenticated endpoint to download the schemas served by the router
+//!
+//! Tooling can use it to verify exactly which supergraph a running router has loaded, without
+//! relying on introspection, which only exposes the API schema and can be disabled. It also serves
+//! a graph of the API schema types and their relations, with the subgraphs owning each type and
+//! field, for visualization tooling.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use apollo_federation::ApiSchemaOptions;
+use apollo_federation::Supergraph;
+use http::header::AUTHORIZATION;
+use http::header::CONTENT_TYPE;
+use http::HeaderValue;
+use http::Method;
+use http::StatusCode;
+use multimap::MultiMap;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde::Serialize;
+use subtle::ConstantTimeEq;
+use tower::service_fn;
+use tower::BoxError;
+use tower::ServiceExt;
+
+use crate::plugin::Plugin;
+use crate::plugin::PluginInit;
+use crate::register_plugin;
+use crate::services::router;
+use crate::spec::Schema;
+use crate::Endpoint;
+use crate::ListenAddr;
+
+mod graph;
+
+const SCHEMA_ID_HEADER: &str = "apollo-schema-id";
+const SDL_HASH_HEADER: &str = "apollo-sdl-hash";
+
+/// Authenticated endpoint to download the supergraph, API and subgraph schemas loaded by the router
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct SchemaDownloadConfig {
+    /// Enable the endpoint (default: false)
+    #[serde(default)]
+    enabled: bool,
+    /// Listen address of the endpoint (default: 127.0.0.1:8088)
+    #[serde(default = "default_listen")]
+    listen: ListenAddr,
+    /// Path prefix of the endpoint (default: /schema)
+    #[serde(default = "default_path")]
+    path: String,
+    /// Value expected in the `authorization` header of requests
+    #[serde(default)]
+    shared_key: String,
+}
+
+fn default_listen() -> ListenAddr {
+    ListenAddr::SocketAddr("127.0.0.1:8088".parse().expect("valid ListenAddr"))
+}
+
+fn default_path() -> String {
+    String::from("/schema")
+}
+
+/// Schemas served by the endpoint, printed when the plugin is created
+struct Documents {
+    schema_id: String,
+    supergraph: String,
+    api: String,
+    /// JSON graph of the API schema
+    graph: String,
+    subgraphs: HashMap<String, String>,
+}
+
+struct SchemaDownload {
+    config: SchemaDownloadConfig,
+    documents: Option<Arc<Documents>>,
+}
+
+#[async_trait::async_trait]
+impl Plugin for SchemaDownload {
+    type Config = SchemaDownloadConfig;
+
+    async fn new(init: PluginInit<Self::Config>) -> Result<Self, BoxError> {
+        if !init.config.enabled {
+            return Ok(Self {
+                config: init.config,
+                documents: None,
+            });
+        }
+        if init.config.shared_key.is_empty() {
+            return Err("the schema download endpoint requires a shared_key".into());
+        }
+
+        // the API schema is generated with `@defer` support, as in the router's default configuration
+        let api_schema =
+            Supergraph::new(&init.supergraph_sdl)?.to_api_schema(ApiSchemaOptions {
+                include_defer: true,
+                ..Default::default()
+            })?;
+        // plugins are created again when the schema is reloaded, so the graph always matches the
+        // schema being served
+        let graph = serde_json::to_string(&graph::SchemaGraph::new(
+            &init.supergraph_schema,
+            api_schema.schema(),
+        ))?;
+        let documents = Documents {
+            schema_id: Schema::schema_id(&init.supergraph_sdl),
+            supergraph: init.supergraph_sdl.to_string(),
+            api: api_schema.schema().to_string(),
+            graph,
+            subgraphs: init
+                .subgraph_schemas
+                .iter()
+                .map(|(name, schema)| (name.clone(), schema.to_string()))
+                .collect(),
+        };
+
+        Ok(Self {
+            config: init.config,
+            documents: Some(Arc::new(documents)),
+        })
+    }
+
+    fn web_endpoints(&self) -> MultiMap<ListenAddr, Endpoint> {
+        let mut map = MultiMap::new();
+        let Some(documents) = &self.documents else {
+            return map;
+        };
+
+        let path = Arc::new(self.config.path.trim_end_matches('/').to_string());
+        let shared_key = Arc::new(self.config.shared_key.clone());
+        tracing::info!(
+            "Schema download endpoint exposed at {}{}",
+            self.config.listen,
+            path
+        );
+        for route in [format!("{path}/:schema"), format!("{path}/subgraphs/:name")] {
+            let documents = documents.clone();
+            let path = path.clone();
+            let shared_key = shared_key.clone();
+            map.insert(
+                self.config.listen.clone(),
+                Endpoint::from_router_service(
+                    route,
+                    service_fn(move |request: router::Request| {
+                        let response = handle(&documents, &path, &shared_key, request);
+                        async move { response }
+                    })
+                    .boxed(),
+                ),
+            );
+        }
+        map
+    }
+}
+
+fn handle(
+    documents: &Documents,
+    path: &str,
+    shared_key: &str,
+    request: router::Request,
+) -> Result<router::Response, BoxError> {
+    let context = request.context;
+    let request = request.router_request;
+    let respond = |status: StatusCode, body: String| -> Result<router::Response, BoxError> {
+        Ok(router::Response {
+            response: http::Response::builder()
+                .status(status)
+                .body(body.into())
+                .map_err(BoxError::from)?,
+            context: context.clone(),
+        })
+    };
+
+    match request.headers().get(AUTHORIZATION) {
+        None => {
+            return respond(
+                StatusCode::UNAUTHORIZED,
+                "Missing authorization header".into(),
+            )
+        }
+        // constant-time comparison, to not leak the key through response times
+        Some(value) if !bool::from(value.as_bytes().ct_eq(shared_key.as_bytes())) => {
+            return respond(
+                StatusCode::UNAUTHORIZED,
+                "Invalid authorization header".into(),
+            )
+        }
+        Some(_) => {}
+    }
+    if request.method() != Method::GET {
+        return respond(StatusCode::METHOD_NOT_ALLOWED, String::new());
+    }
+
+    let target = request
+        .uri()
+        .path()
+        .strip_prefix(path)
+        .unwrap_or_default()
+        .trim_matches('/');
+    let sdl = match target.split_once('/') {
+        None if target == "supergraph" => &documents.supergraph,
+        None if target == "api" => &documents.api,
+        None if target == "graph" => {
+            let mut response = respond(StatusCode::OK, documents.graph.clone())?;
+            let headers = response.response.headers_mut();
+            headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            headers.insert(
+                SCHEMA_ID_HEADER,
+                HeaderValue::from_str(&documents.schema_id)?,
+            );
+            return Ok(response);
+        }
+        None if target == "subgraphs" => {
+            let mut names: Vec<_> = documents.subgraphs.keys().collect();
+            names.sort();
+            let mut response = respond(StatusCode::OK, serde_json::to_string(&names)?)?;
+            response
+                .response
+                .headers_mut()
+                .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            return Ok(response);
+        }
+        Some(("subgraphs", name)) => match documents.subgraphs.get(name) {
+            Some(sdl) => sdl,
+            None => return respond(StatusCode::NOT_FOUND, format!("unknown subgraph '{name}'")),
+        },
+        _ => return respond(StatusCode::NOT_FOUND, String::new()),
+    };
+
+    let mut response = respond(StatusCode::OK, sdl.clone())?;
+    let headers = response.response.headers_mut();
+    headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("text/plain; charset=utf-8"),
+    );
+    headers.insert(
+        SCHEMA_ID_HEADER,
+        HeaderValue::from_str(&documents.schema_id)?,
+    );
+    headers.insert(
+        SDL_HASH_HEADER,
+        HeaderValue::from_str(&Schema::schema_id(sdl))?,
+    );
+    Ok(response)
+}
+
+register_plugin!("apollo", "schema_download", SchemaDownload);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Context;
+
+    fn documents() -> Documents {
+        Documents {
+            schema_id: "supergraph-id".to_string(),
+            supergraph: "supergraph sdl".to_string(),
+            api: "api sdl".to_string(),
+            graph: r#"{"subgraphs":[],"types":[],"relations":[]}"#.to_string(),
+            subgraphs: [
+                ("products".to_string(), "products sdl".to_string()),
+                ("accounts".to_string(), "accounts sdl".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+        }
+    }
+
+    async fn get(uri: &str, authorization: Option<&str>) -> http::Response<String> {
+        let mut request = http::Request::get(uri);
+        if let Some(authorization) = authorization {
+            request = request.header(AUTHORIZATION, authorization);
+        }
+        let request = (request.body(router::Body::empty()).unwrap(), Context::new()).into();
+        let response = handle(&documents(), "/schema", "secret", request)
+            .unwrap()
+            .response;
+        let (parts, body) = response.into_parts();
+        let body = hyper::body::to_bytes(body).await.unwrap();
+        http::Response::from_parts(parts, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn it_requires_the_shared_key() {
+        let response = get("http://localhost/schema/supergraph", None).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = get("http://localhost/schema/supergraph", Some("wrong")).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn it_serves_the_schemas() {
+        let response = get("http://localhost/schema/supergraph", Some("secret")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.body(), "supergraph sdl");
+        assert_eq!(
+            response.headers().get(SCHEMA_ID_HEADER).unwrap(),
+            "supergraph-id"
+        );
+        assert_eq!(
+            response.headers().get(SDL_HASH_HEADER).unwrap(),
+            Schema::schema_id("supergraph sdl").as_str()
+        );
+
+        let response = get("http://localhost/schema/api", Some("secret")).await;
+        assert_eq!(response.body(), "api sdl");
+
+        let response = get("http://localhost/schema/subgraphs/products", Some("secret")).await;
+        assert_eq!(response.body(), "products sdl");
+        assert_eq!(
+            response.headers().get(SCHEMA_ID_HEADER).unwrap(),
+            "supergraph-id"
+        );
+
+        let response = get("http://localhost/schema/graph", Some("secret")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get(CONTENT_TYPE).unwrap(),
+            "application/json"
+        );
+        assert_eq!(
+            response.body(),
+            r#"{"subgraphs":[],"types":[],"relations":[]}"#
+        );
+
+        let response = get("http://localhost/schema/subgraphs", Some("secret")).await;
+        assert_eq!(response.body(), r#"["accounts","products"]"#);
+
+        let response = get("http://localhost/schema/subgraphs/unknown", Some("secret")).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
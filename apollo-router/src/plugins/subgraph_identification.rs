@@ -0,0 +1,178 @@
+//! Identification headers sent by the router to subgraphs
+//!
+//! Some upstream services (WAFs, API gateways) apply policies based on the `user-agent` or
+//! the client identification headers, this plugin sets them for all requests to a subgraph.
+
+use std::collections::HashMap;
+
+use http::header::HeaderName;
+use http::header::USER_AGENT;
+use http::HeaderValue;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde::Serialize;
+use tower::BoxError;
+use tower::ServiceExt;
+
+use crate::configuration::subgraph::SubgraphConfiguration;
+use crate::plugin::Plugin;
+use crate::plugin::PluginInit;
+use crate::register_plugin;
+use crate::services::subgraph;
+use crate::spec::Schema;
+
+static CLIENT_NAME: HeaderName = HeaderName::from_static("apollographql-client-name");
+static CLIENT_VERSION: HeaderName = HeaderName::from_static("apollographql-client-version");
+
+/// Identification headers sent to subgraphs
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct SubgraphIdentificationConfig {
+    /// Identification headers, per subgraph
+    pub(crate) subgraph: SubgraphConfiguration<Identification>,
+}
+
+/// Identification headers sent to a subgraph.
+///
+/// Values can contain the `{router_version}` and `{schema_id}` variables, replaced with the
+/// version of the router and the hash of the supergraph schema.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct Identification {
+    /// Value of the `user-agent` header
+    pub(crate) user_agent: Option<String>,
+    /// Value of the `apollographql-client-name` header
+    pub(crate) client_name: Option<String>,
+    /// Value of the `apollographql-client-version` header
+    pub(crate) client_version: Option<String>,
+}
+
+impl Identification {
+    fn headers(&self, schema_id: &str) -> Result<Vec<(HeaderName, HeaderValue)>, BoxError> {
+        [
+            (&USER_AGENT, &self.user_agent),
+            (&CLIENT_NAME, &self.client_name),
+            (&CLIENT_VERSION, &self.client_version),
+        ]
+        .into_iter()
+        .filter_map(|(name, template)| {
+            template.as_ref().map(|template| {
+                let value = render(template, schema_id);
+                HeaderValue::try_from(value.as_str())
+                    .map(|value| (name.clone(), value))
+                    .map_err(|e| {
+                        BoxError::from(format!("invalid value '{value}' for header '{name}': {e}"))
+                    })
+            })
+        })
+        .collect()
+    }
+}
+
+fn render(template: &str, schema_id: &str) -> String {
+    template
+        .replace("{router_version}", std::env!("CARGO_PKG_VERSION"))
+        .replace("{schema_id}", schema_id)
+}
+
+struct SubgraphIdentification {
+    all: Vec<(HeaderName, HeaderValue)>,
+    subgraphs: HashMap<String, Vec<(HeaderName, HeaderValue)>>,
+}
+
+#[async_trait::async_trait]
+impl Plugin for SubgraphIdentification {
+    type Config = SubgraphIdentificationConfig;
+
+    async fn new(init: PluginInit<Self::Config>) -> Result<Self, BoxError> {
+        let schema_id = Schema::schema_id(&init.supergraph_sdl);
+        let config = init.config.subgraph;
+        let subgraphs = config
+            .subgraphs
+            .keys()
+            .map(|name| Ok((name.clone(), config.get(name).headers(&schema_id)?)))
+            .collect::<Result<_, BoxError>>()?;
+
+        Ok(Self {
+            all: config.all.headers(&schema_id)?,
+            subgraphs,
+        })
+    }
+
+    fn subgraph_service(&self, name: &str, service: subgraph::BoxService) -> subgraph::BoxService {
+        let headers = self.subgraphs.get(name).unwrap_or(&self.all).clone();
+        if headers.is_empty() {
+            return service;
+        }
+
+        service
+            .map_request(move |mut request: subgraph::Request| {
+                for (name, value) in &headers {
+                    request
+                        .subgraph_request
+                        .headers_mut()
+                        .insert(name.clone(), value.clone());
+                }
+                request
+            })
+            .boxed()
+    }
+}
+
+register_plugin!("apollo", "subgraph_identification", SubgraphIdentification);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::test::MockSubgraphService;
+
+    #[tokio::test]
+    async fn it_sets_identification_headers() {
+        let config = serde_json::json!({
+            "subgraph": {
+                "all": {
+                    "user_agent": "router/{router_version}",
+                    "client_name": "router"
+                },
+                "subgraphs": {
+                    "products": {
+                        "client_version": "{schema_id}"
+                    }
+                }
+            }
+        });
+        let plugin = crate::plugin::plugins()
+            .find(|factory| factory.name == "apollo.subgraph_identification")
+            .expect("Plugin not found")
+            .create_instance_without_schema(&config)
+            .await
+            .unwrap();
+
+        let mut mock_service = MockSubgraphService::new();
+        mock_service.expect_call().times(1).returning(|request| {
+            let headers = request.subgraph_request.headers();
+            assert_eq!(
+                headers.get(USER_AGENT).unwrap(),
+                format!("router/{}", std::env!("CARGO_PKG_VERSION")).as_str()
+            );
+            assert_eq!(headers.get(&CLIENT_NAME).unwrap(), "router");
+            assert!(headers.get(&CLIENT_VERSION).is_some());
+            Ok(subgraph::Response::fake_builder().build())
+        });
+
+        plugin
+            .subgraph_service("products", mock_service.boxed())
+            .oneshot(subgraph::Request::fake_builder().build())
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn it_rejects_invalid_header_values() {
+        let identification = Identification {
+            user_agent: Some("invalid\nagent".to_string()),
+            ..Default::default()
+        };
+        assert!(identification.headers("schema").is_err());
+    }
+}
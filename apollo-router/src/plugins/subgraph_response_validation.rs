@@ -0,0 +1,349 @@
+//! Detection of fields returned by subgraphs without being requested
+//!
+//! A subgraph returning more data than what was requested in the fetch is harmless for the
+//! final response, but it usually points at a subgraph leaking data. This plugin can report
+//! and remove those fields. Rhai scripts, coprocessors and native plugins handle the subgraph
+//! response before it is validated, so they still see the unrequested fields.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use apollo_compiler::executable;
+use apollo_compiler::validation::Valid;
+use apollo_compiler::ExecutableDocument;
+use apollo_compiler::Name;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json_bytes::Value;
+use tower::BoxError;
+use tower::ServiceBuilder;
+use tower::ServiceExt;
+
+use crate::configuration::subgraph::SubgraphConfiguration;
+use crate::layers::ServiceBuilderExt;
+use crate::plugin::Plugin;
+use crate::plugin::PluginInit;
+use crate::register_plugin;
+use crate::services::subgraph;
+use crate::Configuration;
+
+const PLUGIN_NAME: &str = "subgraph_response_validation";
+
+/// Subgraph response validation configuration
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct SubgraphResponseValidationConfig {
+    /// Validation of subgraph responses, per subgraph
+    pub(crate) subgraph: SubgraphConfiguration<SubgraphResponseValidation>,
+}
+
+/// Validation of a subgraph's responses
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct SubgraphResponseValidation {
+    /// What to do with fields present in the subgraph response that were not requested in the fetch
+    pub(crate) unrequested_fields: UnrequestedFieldsMode,
+}
+
+/// Handling of fields present in a subgraph response that were not requested in the fetch
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum UnrequestedFieldsMode {
+    /// Keep unrequested fields, without looking for them
+    #[default]
+    Allow,
+    /// Keep unrequested fields, but log them and count them in a metric
+    Log,
+    /// Remove unrequested fields from the response, log them and count them in a metric
+    Strip,
+}
+
+/// Returns true if the responses of at least one subgraph are validated, in which case the
+/// parsed subgraph operation is added to subgraph requests
+pub(crate) fn is_enabled(configuration: &Configuration) -> bool {
+    let Some(config) = configuration.apollo_plugins.plugins.get(PLUGIN_NAME) else {
+        return false;
+    };
+    let Ok(config) = serde_json::from_value::<SubgraphResponseValidationConfig>(config.clone())
+    else {
+        return false;
+    };
+    std::iter::once(&config.subgraph.all)
+        .chain(config.subgraph.subgraphs.values())
+        .any(|validation| validation.unrequested_fields != UnrequestedFieldsMode::Allow)
+}
+
+struct SubgraphResponseValidationPlugin {
+    config: SubgraphResponseValidationConfig,
+}
+
+#[async_trait::async_trait]
+impl Plugin for SubgraphResponseValidationPlugin {
+    type Config = SubgraphResponseValidationConfig;
+
+    async fn new(init: PluginInit<Self::Config>) -> Result<Self, BoxError> {
+        Ok(Self {
+            config: init.config,
+        })
+    }
+
+    fn subgraph_service(&self, name: &str, service: subgraph::BoxService) -> subgraph::BoxService {
+        let mode = self.config.subgraph.get(name).unrequested_fields;
+        if mode == UnrequestedFieldsMode::Allow {
+            return service;
+        }
+
+        let subgraph_name = name.to_string();
+        ServiceBuilder::new()
+            .map_future_with_request_data(
+                |req: &subgraph::Request| {
+                    (
+                        req.executable_document.clone(),
+                        req.subgraph_request.body().operation_name.clone(),
+                    )
+                },
+                move |(document, operation_name): (
+                    Option<Arc<Valid<ExecutableDocument>>>,
+                    Option<String>,
+                ),
+                      fut| {
+                    let subgraph_name = subgraph_name.clone();
+                    async move {
+                        let mut response: subgraph::Response = fut.await?;
+                        let strip = mode == UnrequestedFieldsMode::Strip;
+                        if let (Some(document), Some(data)) =
+                            (document, response.response.body_mut().data.as_mut())
+                        {
+                            let unrequested = find_unrequested_fields(
+                                &document,
+                                operation_name.as_deref(),
+                                data,
+                                strip,
+                            );
+                            if !unrequested.is_empty() {
+                                tracing::warn!(
+                                    subgraph = %subgraph_name,
+                                    fields = ?unrequested,
+                                    stripped = strip,
+                                    "subgraph response contains fields that were not requested"
+                                );
+                                u64_counter!(
+                                    "apollo.router.operations.subgraph.unrequested_fields",
+                                    "Number of fields returned by a subgraph without being requested",
+                                    unrequested.len() as u64,
+                                    "subgraph.name" = subgraph_name
+                                );
+                            }
+                        }
+
+                        Ok(response)
+                    }
+                },
+            )
+            .service(service)
+            .boxed()
+    }
+}
+
+/// Looks for fields of the response data that are not part of the operation's selections,
+/// returns their names and removes them from the data if `strip` is set
+pub(crate) fn find_unrequested_fields(
+    document: &ExecutableDocument,
+    operation_name: Option<&str>,
+    data: &mut Value,
+    strip: bool,
+) -> Vec<String> {
+    let mut unrequested = Vec::new();
+    if let Ok(operation) = document.operations.get(operation_name) {
+        visit(
+            document,
+            &[&operation.selection_set],
+            data,
+            strip,
+            &mut unrequested,
+        );
+    }
+    unrequested
+}
+
+fn visit(
+    document: &ExecutableDocument,
+    selection_sets: &[&executable::SelectionSet],
+    value: &mut Value,
+    strip: bool,
+    unrequested: &mut Vec<String>,
+) {
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                visit(document, selection_sets, item, strip, unrequested);
+            }
+        }
+        Value::Object(object) => {
+            let mut fields = HashMap::new();
+            let mut visited_fragments = HashSet::new();
+            for selection_set in selection_sets {
+                collect_fields(document, selection_set, &mut fields, &mut visited_fragments);
+            }
+
+            let unrequested_keys: Vec<_> = object
+                .keys()
+                .filter(|key| !fields.contains_key(key.as_str()))
+                .cloned()
+                .collect();
+            for key in unrequested_keys {
+                if strip {
+                    object.remove(&key);
+                }
+                unrequested.push(key.as_str().to_string());
+            }
+
+            for (key, value) in object.iter_mut() {
+                if let Some(sub_selection_sets) = fields.get(key.as_str()) {
+                    // leaf fields can contain arbitrary JSON objects for custom scalars
+                    if !sub_selection_sets.is_empty() {
+                        visit(document, sub_selection_sets, value, strip, unrequested);
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Collects the response keys of a selection set, along with the selection sets of each field.
+/// Type conditions are not evaluated, so every fragment is considered as applying: this
+/// can only let unrequested fields through, never remove requested ones.
+fn collect_fields<'a>(
+    document: &'a ExecutableDocument,
+    selection_set: &'a executable::SelectionSet,
+    fields: &mut HashMap<&'a str, Vec<&'a executable::SelectionSet>>,
+    visited_fragments: &mut HashSet<&'a Name>,
+) {
+    for selection in &selection_set.selections {
+        match selection {
+            executable::Selection::Field(field) => {
+                let response_key = field.alias.as_ref().unwrap_or(&field.name);
+                let sub_selection_sets = fields.entry(response_key.as_str()).or_default();
+                if !field.selection_set.selections.is_empty() {
+                    sub_selection_sets.push(&field.selection_set);
+                }
+            }
+            executable::Selection::InlineFragment(fragment) => {
+                collect_fields(document, &fragment.selection_set, fields, visited_fragments);
+            }
+            executable::Selection::FragmentSpread(spread) => {
+                if visited_fragments.insert(&spread.fragment_name) {
+                    if let Some(fragment) = document.fragments.get(&spread.fragment_name) {
+                        collect_fields(
+                            document,
+                            &fragment.selection_set,
+                            fields,
+                            visited_fragments,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+register_plugin!(
+    "apollo",
+    "subgraph_response_validation",
+    SubgraphResponseValidationPlugin
+);
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use apollo_compiler::Schema;
+    use serde_json_bytes::json;
+
+    use super::*;
+
+    const SCHEMA: &str = r#"
+        type Query {
+            me: User
+            _entities(representations: [_Any!]!): [_Entity]!
+        }
+        scalar _Any
+        union _Entity = User
+        type User {
+            id: ID!
+            name: String
+            email: String
+            friends: [User]
+        }
+    "#;
+
+    fn check(query: &str, mut data: Value, strip: bool) -> (Vec<String>, Value) {
+        let schema = Schema::parse_and_validate(SCHEMA, "schema.graphql").unwrap();
+        let document =
+            ExecutableDocument::parse_and_validate(&schema, query, "query.graphql").unwrap();
+        let unrequested = find_unrequested_fields(&document, None, &mut data, strip);
+        (unrequested, data)
+    }
+
+    #[test]
+    fn it_keeps_requested_fields() {
+        let data = json!({"me": {"id": "1", "n": "Ada", "friends": [{"id": "2"}]}});
+        let (unrequested, result) = check(
+            "{ me { id n: name ...F } } fragment F on User { friends { id } }",
+            data.clone(),
+            true,
+        );
+        assert!(unrequested.is_empty());
+        assert_eq!(result, data);
+    }
+
+    #[test]
+    fn it_logs_unrequested_fields() {
+        let data = json!({"me": {"id": "1", "email": "ada@example.com"}});
+        let (unrequested, result) = check("{ me { id } }", data.clone(), false);
+        assert_eq!(unrequested, vec!["email".to_string()]);
+        assert_eq!(result, data);
+    }
+
+    #[test]
+    fn it_strips_unrequested_fields() {
+        let (unrequested, result) = check(
+            "query($representations: [_Any!]!) { _entities(representations: $representations) { ... on User { name } } }",
+            json!({"_entities": [
+                {"name": "Ada", "email": "ada@example.com"},
+                {"name": "Alan", "friends": [{"id": "1"}]}
+            ]}),
+            true,
+        );
+        assert_eq!(
+            unrequested,
+            vec!["email".to_string(), "friends".to_string()]
+        );
+        assert_eq!(
+            result,
+            json!({"_entities": [{"name": "Ada"}, {"name": "Alan"}]})
+        );
+    }
+
+    #[test]
+    fn it_is_enabled_when_a_subgraph_is_validated() {
+        let enabled = |config: serde_json::Value| {
+            let configuration = Configuration::from_str(
+                &serde_json::json!({ "subgraph_response_validation": config }).to_string(),
+            )
+            .unwrap();
+            is_enabled(&configuration)
+        };
+        assert!(!is_enabled(&Configuration::default()));
+        assert!(!enabled(serde_json::json!({})));
+        assert!(enabled(
+            serde_json::json!({ "subgraph": { "all": { "unrequested_fields": "log" } } })
+        ));
+        assert!(enabled(serde_json::json!({
+            "subgraph": { "subgraphs": { "accounts": { "unrequested_fields": "strip" } } }
+        })));
+    }
+}
@@ -30,6 +30,9 @@ use url::Url;
 use super::apollo::Report;
 use super::apollo::SingleReport;
 use super::config::ApolloMetricsReferenceMode;
+use super::dropped::record_dropped;
+use super::dropped::DropReason;
+use super::dropped::DroppedKind;
 use crate::plugins::telemetry::tracing::BatchProcessorConfig;
 
 const BACKOFF_INCREMENT: Duration = Duration::from_millis(50);
@@ -71,11 +74,21 @@ impl Sender {
         match &self {
             Sender::Noop => {}
             Sender::Apollo(channel) => {
-                if let Err(err) = channel.to_owned().try_send(report) {
-                    tracing::warn!(
-                        "could not send metrics to telemetry, metric will be dropped: {}",
-                        err
-                    );
+                let kind = match report {
+                    SingleReport::Stats(_) => DroppedKind::Metrics,
+                    SingleReport::Traces(_) => DroppedKind::Traces,
+                };
+                match channel.to_owned().try_send(report) {
+                    Ok(()) => {}
+                    Err(mpsc::error::TrySendError::Full(_)) => {
+                        record_dropped("apollo", kind, DropReason::QueueFull, 1)
+                    }
+                    Err(err) => {
+                        tracing::warn!(
+                            "could not send metrics to telemetry, metric will be dropped: {}",
+                            err
+                        );
+                    }
                 }
             }
         }
@@ -68,6 +68,9 @@ pub(crate) struct Conf {
 
     /// Instrumentation configuration
     pub(crate) instrumentation: Instrumentation,
+
+    /// Redaction of argument and variable values in usage reports, spans and logs
+    pub(crate) redaction: super::redaction::Redaction,
 }
 
 /// Exporter configuration
@@ -129,6 +132,12 @@ pub(crate) struct MetricsCommon {
     pub(crate) buckets: Vec<f64>,
     /// Views applied on metrics
     pub(crate) views: Vec<MetricView>,
+    /// Human readable release label, set as the `apollo.router.release` resource attribute and
+    /// as the `release` attribute of the `apollo.router.reload` metric
+    pub(crate) release: Option<String>,
+    /// Set the hash of the supergraph schema as the `apollo.router.schema.id` resource attribute.
+    /// Prometheus metrics are reset when the schema changes
+    pub(crate) schema_id_resource: bool,
 }
 
 impl Default for MetricsCommon {
@@ -139,6 +148,8 @@ impl Default for MetricsCommon {
             service_namespace: None,
             resource: BTreeMap::new(),
             views: Vec::with_capacity(0),
+            release: None,
+            schema_id_resource: false,
             buckets: vec![
                 0.001, 0.005, 0.015, 0.05, 0.1, 0.2, 0.3, 0.4, 0.5, 1.0, 5.0, 10.0,
             ],
@@ -753,6 +764,27 @@ impl Conf {
         }
     }
 
+    pub(crate) fn plugin_duration_enabled(configuration: &Configuration) -> bool {
+        configuration
+            .apollo_plugins
+            .plugins
+            .get("telemetry")
+            .and_then(|telemetry_config| {
+                serde_json::from_value::<Conf>(telemetry_config.clone()).ok()
+            })
+            .is_some_and(|conf| conf.instrumentation.instruments.plugin_duration)
+    }
+
+    pub(crate) fn metrics_release(configuration: &Configuration) -> Option<String> {
+        let telemetry_config = configuration.apollo_plugins.plugins.get("telemetry")?;
+        serde_json::from_value::<Conf>(telemetry_config.clone())
+            .ok()?
+            .exporters
+            .metrics
+            .common
+            .release
+    }
+
     pub(crate) fn signature_normalization_algorithm(
         configuration: &Configuration,
     ) -> ApolloSignatureNormalizationAlgorithm {
@@ -44,6 +44,7 @@ use crate::plugins::telemetry::config_new::DefaultForLevel;
 use crate::plugins::telemetry::config_new::Selectors;
 use crate::plugins::telemetry::otel::OpenTelemetrySpanExt;
 use crate::plugins::telemetry::otlp::TelemetryDataKind;
+use crate::plugins::telemetry::redaction::redact_document;
 use crate::services::router;
 use crate::services::router::Request;
 use crate::services::subgraph;
@@ -936,7 +937,10 @@ impl Selectors for SupergraphAttributes {
         let mut attrs = Vec::new();
         if let Some(true) = &self.graphql_document {
             if let Some(query) = &request.supergraph_request.body().query {
-                attrs.push(KeyValue::new(GRAPHQL_DOCUMENT, query.clone()));
+                attrs.push(KeyValue::new(
+                    GRAPHQL_DOCUMENT,
+                    redact_document(&request.context, query).into_owned(),
+                ));
             }
         }
         if let Some(true) = &self.graphql_operation_name {
@@ -997,7 +1001,10 @@ impl Selectors for SubgraphAttributes {
         let mut attrs = Vec::new();
         if let Some(true) = &self.graphql_document {
             if let Some(query) = &request.subgraph_request.body().query {
-                attrs.push(KeyValue::new(SUBGRAPH_GRAPHQL_DOCUMENT, query.clone()));
+                attrs.push(KeyValue::new(
+                    SUBGRAPH_GRAPHQL_DOCUMENT,
+                    redact_document(&request.context, query).into_owned(),
+                ));
             }
         }
         if let Some(true) = &self.graphql_operation_name {
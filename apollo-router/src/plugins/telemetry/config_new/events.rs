@@ -24,6 +24,9 @@ use crate::plugins::telemetry::config_new::selectors::RouterSelector;
 use crate::plugins::telemetry::config_new::selectors::SubgraphSelector;
 use crate::plugins::telemetry::config_new::selectors::SupergraphSelector;
 use crate::plugins::telemetry::dynamic_attribute::EventDynAttribute;
+use crate::plugins::telemetry::redaction::is_redacted;
+use crate::plugins::telemetry::redaction::redact_request;
+use crate::plugins::telemetry::redaction::REDACTED;
 use crate::services::router;
 use crate::services::subgraph;
 use crate::services::supergraph;
@@ -248,9 +251,15 @@ impl Instrumented
                     format!("{:?}", request.router_request.version()).into(),
                 ),
             ));
+            // the raw body is logged before the variables to redact are known
+            let body = if is_redacted(&request.context) {
+                REDACTED.to_string()
+            } else {
+                format!("{:?}", request.router_request.body())
+            };
             attrs.push(KeyValue::new(
                 Key::from_static_str("http.request.body"),
-                opentelemetry::Value::String(format!("{:?}", request.router_request.body()).into()),
+                opentelemetry::Value::String(body.into()),
             ));
             log_event(self.request.level(), "router.request", attrs, "");
         }
@@ -383,9 +392,12 @@ impl Instrumented
             attrs.push(KeyValue::new(
                 Key::from_static_str("http.request.body"),
                 opentelemetry::Value::String(
-                    serde_json::to_string(request.supergraph_request.body())
-                        .unwrap_or_default()
-                        .into(),
+                    serde_json::to_string(&redact_request(
+                        &request.context,
+                        request.supergraph_request.body(),
+                    ))
+                    .unwrap_or_default()
+                    .into(),
                 ),
             ));
             log_event(self.request.level(), "supergraph.request", attrs, "");
@@ -91,6 +91,8 @@ pub(crate) struct InstrumentsConfig {
         CacheInstrumentsConfig,
         Instrument<CacheAttributes, SubgraphSelector, SubgraphValue>,
     >,
+    /// Record the time spent in each plugin, per pipeline stage, in the `apollo.router.plugin.duration` histogram.
+    pub(crate) plugin_duration: bool,
 }
 
 const HTTP_SERVER_REQUEST_DURATION_METRIC: &str = "http.server.request.duration";
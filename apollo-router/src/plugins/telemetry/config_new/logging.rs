@@ -24,6 +24,9 @@ use crate::plugins::telemetry::config_new::experimental_when_header::HeaderLoggi
 use crate::plugins::telemetry::resource::ConfigResource;
 use crate::services::SupergraphRequest;
 
+const DEFAULT_TRACE_ID_FIELD_NAME: &str = "trace_id";
+const DEFAULT_SPAN_ID_FIELD_NAME: &str = "span_id";
+
 /// Logging configuration.
 #[derive(Deserialize, JsonSchema, Clone, Default, Debug)]
 #[serde(deny_unknown_fields, default)]
@@ -340,6 +343,10 @@ pub(crate) struct JsonFormat {
     pub(crate) display_trace_id: DisplayTraceIdFormat,
     /// Include the span id (if any) with the log event. (default: true)
     pub(crate) display_span_id: bool,
+    /// Name of the field containing the trace id. (default: trace_id)
+    pub(crate) trace_id_field_name: String,
+    /// Name of the field containing the span id. (default: span_id)
+    pub(crate) span_id_field_name: String,
     /// List of span attributes to attach to the json log object
     pub(crate) span_attributes: HashSet<String>,
 }
@@ -392,6 +399,8 @@ impl Default for JsonFormat {
             display_resource: true,
             display_trace_id: DisplayTraceIdFormat::Bool(true),
             display_span_id: true,
+            trace_id_field_name: DEFAULT_TRACE_ID_FIELD_NAME.to_string(),
+            span_id_field_name: DEFAULT_SPAN_ID_FIELD_NAME.to_string(),
             span_attributes: HashSet::new(),
         }
     }
@@ -430,6 +439,10 @@ pub(crate) struct TextFormat {
     pub(crate) display_trace_id: DisplayTraceIdFormat,
     /// Include the span id (if any) with the log event. (default: false)
     pub(crate) display_span_id: bool,
+    /// Name of the field containing the trace id. (default: trace_id)
+    pub(crate) trace_id_field_name: String,
+    /// Name of the field containing the span id. (default: span_id)
+    pub(crate) span_id_field_name: String,
 }
 
 impl Default for TextFormat {
@@ -450,6 +463,8 @@ impl Default for TextFormat {
             display_span_list: true,
             display_trace_id: DisplayTraceIdFormat::Bool(false),
             display_span_id: false,
+            trace_id_field_name: DEFAULT_TRACE_ID_FIELD_NAME.to_string(),
+            span_id_field_name: DEFAULT_SPAN_ID_FIELD_NAME.to_string(),
         }
     }
 }
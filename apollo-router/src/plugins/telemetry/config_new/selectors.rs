@@ -25,6 +25,7 @@ use crate::plugins::telemetry::config_new::instruments::Standard;
 use crate::plugins::telemetry::config_new::trace_id;
 use crate::plugins::telemetry::config_new::Selector;
 use crate::plugins::telemetry::config_new::ToOtelValue;
+use crate::plugins::telemetry::redaction::redact_variable;
 use crate::query_planner::APOLLO_OPERATION_ID;
 use crate::services::router;
 use crate::services::subgraph;
@@ -918,6 +919,7 @@ impl Selector for SupergraphSelector {
                 .body()
                 .variables
                 .get(&ByteString::from(query_variable.as_str()))
+                .and_then(|v| redact_variable(&request.context, query_variable, v))
                 .and_then(|v| v.maybe_to_otel_value())
                 .or_else(|| default.maybe_to_otel_value()),
             SupergraphSelector::RequestContext {
@@ -1388,6 +1390,7 @@ impl Selector for SubgraphSelector {
                 .body()
                 .variables
                 .get(&ByteString::from(subgraph_query_variable.as_str()))
+                .and_then(|v| redact_variable(&request.context, subgraph_query_variable, v))
                 .and_then(|v| v.maybe_to_otel_value())
                 .or_else(|| default.maybe_to_otel_value()),
 
@@ -1400,6 +1403,7 @@ impl Selector for SubgraphSelector {
                 .body()
                 .variables
                 .get(&ByteString::from(supergraph_query_variable.as_str()))
+                .and_then(|v| redact_variable(&request.context, supergraph_query_variable, v))
                 .and_then(|v| v.maybe_to_otel_value())
                 .or_else(|| default.maybe_to_otel_value()),
             SubgraphSelector::SubgraphRequestHeader {
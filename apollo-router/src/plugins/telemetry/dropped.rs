@@ -0,0 +1,198 @@
+//! Accounting of telemetry data dropped before reaching an exporter
+//!
+//! Exporters drop data when their queue is full, and spans lose attributes, events and links
+//! over the span limits, without any trace of it. Each drop is counted in the
+//! `apollo.router.telemetry.dropped` metric, and logged at most once per period for each
+//! exporter, kind of data and reason.
+
+use std::cell::Cell;
+use std::time::Duration;
+use std::time::Instant;
+
+use dashmap::DashMap;
+use once_cell::sync::OnceCell;
+
+#[cfg(not(test))]
+const WARNING_PERIOD: Duration = Duration::from_secs(10);
+#[cfg(test)]
+const WARNING_PERIOD: Duration = Duration::from_millis(100);
+
+/// Kind of telemetry data dropped
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum DroppedKind {
+    Spans,
+    SpanAttributes,
+    SpanEvents,
+    SpanLinks,
+    Metrics,
+    /// Apollo traces reports
+    Traces,
+}
+
+impl DroppedKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            DroppedKind::Spans => "spans",
+            DroppedKind::SpanAttributes => "span_attributes",
+            DroppedKind::SpanEvents => "span_events",
+            DroppedKind::SpanLinks => "span_links",
+            DroppedKind::Metrics => "metrics",
+            DroppedKind::Traces => "traces",
+        }
+    }
+}
+
+/// Reason telemetry data was dropped
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum DropReason {
+    /// The export queue is full
+    QueueFull,
+    /// The span limits of `telemetry.exporters.tracing.common` are exceeded
+    SpanLimits,
+}
+
+impl DropReason {
+    fn as_str(&self) -> &'static str {
+        match self {
+            DropReason::QueueFull => "queue_full",
+            DropReason::SpanLimits => "span_limits",
+        }
+    }
+
+    fn hint(&self) -> &'static str {
+        match self {
+            DropReason::QueueFull => {
+                "the export queue is full, the exporter cannot keep up or its endpoint is unavailable"
+            }
+            DropReason::SpanLimits => "the span limits of the tracing configuration are exceeded",
+        }
+    }
+}
+
+/// Drops not logged yet, and when they were last logged
+struct Pending {
+    last_logged: Option<Instant>,
+    count: u64,
+}
+
+static PENDING_WARNINGS: OnceCell<DashMap<(&'static str, DroppedKind, DropReason), Pending>> =
+    OnceCell::new();
+
+/// Counts telemetry data dropped for an exporter, and logs a warning if none was logged for it
+/// during the last period
+pub(crate) fn record_dropped(
+    exporter: &'static str,
+    kind: DroppedKind,
+    reason: DropReason,
+    count: u64,
+) {
+    if count == 0 {
+        return;
+    }
+    u64_counter!(
+        "apollo.router.telemetry.dropped",
+        "Number of spans, span attributes, events and links, metrics and traces dropped before export",
+        count,
+        "exporter" = exporter,
+        "kind" = kind.as_str(),
+        "reason" = reason.as_str()
+    );
+
+    let pending = PENDING_WARNINGS.get_or_init(DashMap::new);
+    let mut entry = pending.entry((exporter, kind, reason)).or_insert(Pending {
+        last_logged: None,
+        count: 0,
+    });
+    entry.count += count;
+    if entry
+        .last_logged
+        .is_some_and(|last_logged| last_logged.elapsed() < WARNING_PERIOD)
+    {
+        return;
+    }
+    let count = std::mem::take(&mut entry.count);
+    entry.last_logged = Some(Instant::now());
+    // do not log while holding the shard lock, logs can be exported too
+    drop(entry);
+    ::tracing::warn!(
+        exporter,
+        kind = kind.as_str(),
+        reason = reason.as_str(),
+        count,
+        "telemetry data dropped by the {exporter} exporter: {count} {}, {}",
+        kind.as_str(),
+        reason.hint()
+    );
+}
+
+thread_local! {
+    /// Exporter a span is being handed to on this thread, and whether the span was dropped
+    static CURRENT_EXPORTER: Cell<Option<(&'static str, bool)>> = const { Cell::new(None) };
+}
+
+/// Hands a span to the batch span processor of `exporter` with `f`, returning whether the span
+/// was dropped because the queue of the processor is full.
+///
+/// Batch span processors report a full queue to the global OpenTelemetry error handler, on the
+/// thread that ends the span, without naming the exporter
+pub(crate) fn with_exporter(exporter: &'static str, f: impl FnOnce()) -> bool {
+    let previous = CURRENT_EXPORTER.with(|current| current.replace(Some((exporter, false))));
+    f();
+    let current = CURRENT_EXPORTER.with(|current| current.replace(previous));
+    current.is_some_and(|(_, dropped)| dropped)
+}
+
+/// Records a span dropped by the full queue of the exporter it is handed to on this thread
+pub(crate) fn record_span_dropped_by_full_queue() {
+    let exporter = CURRENT_EXPORTER.with(|current| {
+        let (exporter, _) = current.get()?;
+        current.set(Some((exporter, true)));
+        Some(exporter)
+    });
+    record_dropped(
+        exporter.unwrap_or("unknown"),
+        DroppedKind::Spans,
+        DropReason::QueueFull,
+        1,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::FutureMetricsExt;
+
+    #[tokio::test]
+    async fn it_counts_dropped_telemetry() {
+        async {
+            record_dropped("otlp", DroppedKind::Spans, DropReason::QueueFull, 2);
+            record_dropped("otlp", DroppedKind::Spans, DropReason::QueueFull, 3);
+            record_dropped("zipkin", DroppedKind::SpanEvents, DropReason::SpanLimits, 1);
+            assert_counter!(
+                "apollo.router.telemetry.dropped",
+                5,
+                "exporter" = "otlp",
+                "kind" = "spans",
+                "reason" = "queue_full"
+            );
+            assert_counter!(
+                "apollo.router.telemetry.dropped",
+                1,
+                "exporter" = "zipkin",
+                "kind" = "span_events",
+                "reason" = "span_limits"
+            );
+        }
+        .with_metrics()
+        .await;
+    }
+
+    #[test]
+    fn it_tracks_spans_dropped_by_full_queues() {
+        assert!(!with_exporter("zipkin", || {}));
+        assert!(with_exporter("zipkin", record_span_dropped_by_full_queue));
+        assert!(!with_exporter("zipkin", || {
+            assert!(with_exporter("otlp", record_span_dropped_by_full_queue))
+        }));
+    }
+}
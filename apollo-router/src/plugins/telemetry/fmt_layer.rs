@@ -526,6 +526,48 @@ subgraph:
         insta::assert_snapshot!(buff.to_string());
     }
 
+    #[tokio::test]
+    async fn test_json_logging_outside_of_spans_with_custom_field_names() {
+        use opentelemetry::trace::SpanContext;
+        use opentelemetry::trace::TraceContextExt;
+        use opentelemetry::trace::TraceFlags;
+        use opentelemetry::trace::TraceState;
+        use opentelemetry_api::trace::SpanId;
+        use opentelemetry_api::trace::TraceId;
+
+        let buff = LogBuffer::default();
+        let json_format = JsonFormat {
+            trace_id_field_name: "traceId".to_string(),
+            span_id_field_name: "spanId".to_string(),
+            ..Default::default()
+        };
+        let format = Json::new(Default::default(), json_format);
+        let fmt_layer = FmtLayer::new(
+            FilteringFormatter::new(format, filter_metric_events, &RateLimit::default()),
+            buff.clone(),
+        )
+        .boxed();
+
+        ::tracing::subscriber::with_default(fmt::Subscriber::new().with(fmt_layer), || {
+            // events logged in spawned tasks have no span, only the OpenTelemetry context
+            let span_context = SpanContext::new(
+                TraceId::from_u128(1),
+                SpanId::from_u64(2),
+                TraceFlags::SAMPLED,
+                false,
+                TraceState::default(),
+            );
+            let _guard = opentelemetry::Context::current()
+                .with_remote_span_context(span_context)
+                .attach();
+            error!("Hello from a task");
+        });
+
+        let log = buff.to_string();
+        assert!(log.contains(r#""traceId":"00000000000000000000000000000001""#));
+        assert!(log.contains(r#""spanId":"0000000000000002""#));
+    }
+
     #[tokio::test]
     async fn test_text_logging_without_span_list() {
         let buff = LogBuffer::default();
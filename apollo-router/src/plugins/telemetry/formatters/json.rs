@@ -18,7 +18,7 @@ use tracing_subscriber::layer::Context;
 use tracing_subscriber::registry::LookupSpan;
 use tracing_subscriber::registry::SpanRef;
 
-use super::get_trace_and_span_id;
+use super::get_event_trace_and_span_id;
 use super::EventFormatter;
 use super::APOLLO_PRIVATE_PREFIX;
 use super::EXCLUDED_ATTRIBUTES;
@@ -229,36 +229,37 @@ where
                 .and_then(|id| ctx.span(id))
                 .or_else(|| ctx.lookup_current());
 
-            if let Some(ref span) = current_span {
-                if let Some((trace_id, span_id)) = get_trace_and_span_id(span) {
-                    let trace_id = match self.config.display_trace_id {
-                        DisplayTraceIdFormat::Bool(true)
-                        | DisplayTraceIdFormat::TraceIdFormat(TraceIdFormat::Hexadecimal)
-                        | DisplayTraceIdFormat::TraceIdFormat(TraceIdFormat::OpenTelemetry) => {
-                            Some(TraceIdFormat::Hexadecimal.format(trace_id))
-                        }
-                        DisplayTraceIdFormat::TraceIdFormat(TraceIdFormat::Decimal) => {
-                            Some(TraceIdFormat::Decimal.format(trace_id))
-                        }
-                        DisplayTraceIdFormat::TraceIdFormat(TraceIdFormat::Datadog) => {
-                            Some(TraceIdFormat::Datadog.format(trace_id))
-                        }
-                        DisplayTraceIdFormat::TraceIdFormat(TraceIdFormat::Uuid) => {
-                            Some(TraceIdFormat::Uuid.format(trace_id))
-                        }
-                        DisplayTraceIdFormat::Bool(false) => None,
-                    };
-                    if let Some(trace_id) = trace_id {
-                        serializer
-                            .serialize_entry("trace_id", &trace_id)
-                            .unwrap_or(());
+            if let Some((trace_id, span_id)) = get_event_trace_and_span_id(current_span.as_ref()) {
+                let trace_id = match self.config.display_trace_id {
+                    DisplayTraceIdFormat::Bool(true)
+                    | DisplayTraceIdFormat::TraceIdFormat(TraceIdFormat::Hexadecimal)
+                    | DisplayTraceIdFormat::TraceIdFormat(TraceIdFormat::OpenTelemetry) => {
+                        Some(TraceIdFormat::Hexadecimal.format(trace_id))
+                    }
+                    DisplayTraceIdFormat::TraceIdFormat(TraceIdFormat::Decimal) => {
+                        Some(TraceIdFormat::Decimal.format(trace_id))
                     }
-                    if self.config.display_span_id {
-                        serializer
-                            .serialize_entry("span_id", &span_id.to_string())
-                            .unwrap_or(());
+                    DisplayTraceIdFormat::TraceIdFormat(TraceIdFormat::Datadog) => {
+                        Some(TraceIdFormat::Datadog.format(trace_id))
                     }
+                    DisplayTraceIdFormat::TraceIdFormat(TraceIdFormat::Uuid) => {
+                        Some(TraceIdFormat::Uuid.format(trace_id))
+                    }
+                    DisplayTraceIdFormat::Bool(false) => None,
                 };
+                if let Some(trace_id) = trace_id {
+                    serializer
+                        .serialize_entry(&self.config.trace_id_field_name, &trace_id)
+                        .unwrap_or(());
+                }
+                if self.config.display_span_id {
+                    serializer
+                        .serialize_entry(&self.config.span_id_field_name, &span_id.to_string())
+                        .unwrap_or(());
+                }
+            }
+
+            if let Some(ref span) = current_span {
                 let event_attributes = {
                     let mut extensions = span.extensions_mut();
                     let otel_data = extensions.get_mut::<OtelData>();
@@ -4,15 +4,19 @@ pub(crate) mod text;
 
 use std::collections::HashMap;
 use std::fmt;
+use std::future::Future;
 use std::time::Instant;
 
 use opentelemetry::sdk::Resource;
+use opentelemetry::trace::FutureExt;
+use opentelemetry::trace::WithContext;
 use opentelemetry_api::trace::SpanId;
 use opentelemetry_api::trace::TraceContextExt;
 use opentelemetry_api::trace::TraceId;
 use opentelemetry_api::KeyValue;
 use parking_lot::Mutex;
 use serde_json::Number;
+use tracing::Span;
 use tracing::Subscriber;
 use tracing_core::callsite::Identifier;
 use tracing_subscriber::fmt::format::Writer;
@@ -29,6 +33,7 @@ use crate::metrics::layer::METRIC_PREFIX_COUNTER;
 use crate::metrics::layer::METRIC_PREFIX_HISTOGRAM;
 use crate::metrics::layer::METRIC_PREFIX_MONOTONIC_COUNTER;
 use crate::metrics::layer::METRIC_PREFIX_VALUE;
+use crate::plugins::telemetry::otel::OpenTelemetrySpanExt;
 use crate::plugins::telemetry::otel::OtelData;
 
 pub(crate) const APOLLO_PRIVATE_PREFIX: &str = "apollo_private.";
@@ -294,6 +299,29 @@ pub(crate) trait EventFormatter<S> {
         W: std::fmt::Write;
 }
 
+/// Returns the trace and span ids of an event, from its span or, for the events logged outside of
+/// any span, like in tasks spawned without instrumentation, from the active OpenTelemetry context
+pub(crate) fn get_event_trace_and_span_id<S>(span: Option<&SpanRef<S>>) -> Option<(TraceId, SpanId)>
+where
+    S: Subscriber + for<'lookup> LookupSpan<'lookup>,
+{
+    span.and_then(get_trace_and_span_id).or_else(|| {
+        let context = opentelemetry::Context::current();
+        let span = context.span();
+        let span_context = span.span_context();
+        span_context
+            .is_valid()
+            .then(|| (span_context.trace_id(), span_context.span_id()))
+    })
+}
+
+/// Attaches the OpenTelemetry context of the current span to a future spawned in its own task, so
+/// that the events it logs keep the trace and span ids of the request, without keeping the span
+/// open until the task ends
+pub(crate) fn in_current_otel_context<F: Future>(future: F) -> WithContext<F> {
+    future.with_context(Span::current().context())
+}
+
 #[inline]
 pub(crate) fn get_trace_and_span_id<S>(span: &SpanRef<S>) -> Option<(TraceId, SpanId)>
 where
@@ -10,6 +10,7 @@ use opentelemetry::sdk::metrics::reader::AggregationSelector;
 use opentelemetry::sdk::metrics::Aggregation;
 use opentelemetry::sdk::metrics::InstrumentKind;
 use opentelemetry::sdk::Resource;
+use opentelemetry::KeyValue;
 use regex::Regex;
 use schemars::JsonSchema;
 use serde::Serialize;
@@ -36,6 +37,9 @@ pub(crate) mod otlp;
 pub(crate) mod prometheus;
 pub(crate) mod span_metrics_exporter;
 
+const SCHEMA_ID_RESOURCE: &str = "apollo.router.schema.id";
+const RELEASE_RESOURCE: &str = "apollo.router.release";
+
 #[derive(Debug, Clone, Deserialize, JsonSchema, Default)]
 #[serde(deny_unknown_fields, default)]
 /// Configuration to add custom attributes/labels on metrics
@@ -437,8 +441,18 @@ pub(crate) struct MetricsBuilder {
 }
 
 impl MetricsBuilder {
-    pub(crate) fn new(config: &Conf) -> Self {
-        let resource = config.exporters.metrics.common.to_resource();
+    pub(crate) fn new(config: &Conf, schema_id: &str) -> Self {
+        let common = &config.exporters.metrics.common;
+        let mut release_attributes = Vec::new();
+        if common.schema_id_resource {
+            release_attributes.push(KeyValue::new(SCHEMA_ID_RESOURCE, schema_id.to_string()));
+        }
+        if let Some(release) = &common.release {
+            release_attributes.push(KeyValue::new(RELEASE_RESOURCE, release.clone()));
+        }
+        let resource = common
+            .to_resource()
+            .merge(&Resource::new(release_attributes));
 
         Self {
             resource: resource.clone(),
@@ -32,11 +32,13 @@ use opentelemetry::propagation::text_map_propagator::FieldIter;
 use opentelemetry::propagation::Extractor;
 use opentelemetry::propagation::Injector;
 use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry::runtime::TrySendError;
 use opentelemetry::sdk::propagation::TextMapCompositePropagator;
 use opentelemetry::sdk::trace::Builder;
 use opentelemetry::trace::SpanContext;
 use opentelemetry::trace::SpanId;
 use opentelemetry::trace::TraceContextExt;
+use opentelemetry::trace::TraceError;
 use opentelemetry::trace::TraceFlags;
 use opentelemetry::trace::TraceState;
 use opentelemetry::trace::TracerProvider;
@@ -157,6 +159,7 @@ pub(crate) mod apollo_otlp_exporter;
 pub(crate) mod config;
 pub(crate) mod config_new;
 pub(crate) mod consts;
+pub(crate) mod dropped;
 pub(crate) mod dynamic_attribute;
 mod endpoint;
 mod fmt_layer;
@@ -166,6 +169,7 @@ pub(crate) mod metrics;
 /// Opentelemetry utils
 pub(crate) mod otel;
 mod otlp;
+pub(crate) mod redaction;
 pub(crate) mod reload;
 mod resource;
 mod span_factory;
@@ -173,7 +177,7 @@ pub(crate) mod tracing;
 pub(crate) mod utils;
 
 // Tracing consts
-const CLIENT_NAME: &str = "apollo_telemetry::client_name";
+pub(crate) const CLIENT_NAME: &str = "apollo_telemetry::client_name";
 const CLIENT_VERSION: &str = "apollo_telemetry::client_version";
 const SUBGRAPH_FTV1: &str = "apollo_telemetry::subgraph_ftv1";
 pub(crate) const STUDIO_EXCLUDE: &str = "apollo_telemetry::studio::exclude";
@@ -290,13 +294,15 @@ impl Plugin for Telemetry {
         config.instrumentation.spans.update_defaults();
         config.instrumentation.instruments.update_defaults();
         config.exporters.logging.validate()?;
+        config.redaction.validate()?;
         if let Err(err) = config.instrumentation.validate() {
             ::tracing::warn!("Potential configuration error for 'instrumentation': {err}, please check the documentation on https://www.apollographql.com/docs/router/configuration/telemetry/instrumentation/events");
         }
 
         let field_level_instrumentation_ratio =
             config.calculate_field_level_instrumentation_ratio()?;
-        let metrics_builder = Self::create_metrics_builder(&config)?;
+        let schema_id = crate::spec::Schema::schema_id(&init.supergraph_sdl);
+        let metrics_builder = Self::create_metrics_builder(&config, &schema_id)?;
 
         let (sampling_filter_ratio, tracer_provider) = Self::create_tracer_provider(&config)?;
 
@@ -446,6 +452,7 @@ impl Plugin for Telemetry {
                         .new_router_instruments(static_router_instruments.clone());
                     custom_instruments.on_request(request);
 
+                    config_request.redaction.on_router_request(&request.context);
                     let custom_events: RouterEvents =
                         config_request.instrumentation.events.new_router_events();
                     custom_events.on_request(request);
@@ -572,7 +579,12 @@ impl Plugin for Telemetry {
         let field_level_instrumentation_ratio = self.field_level_instrumentation_ratio;
         let static_supergraph_instruments = self.supergraph_custom_instruments.read().clone();
         let static_graphql_instruments = self.graphql_custom_instruments.read().clone();
+        let redaction = self.config.redaction.clone();
         ServiceBuilder::new()
+            .map_request(move |supergraph_req: SupergraphRequest| {
+                redaction.on_request(&supergraph_req);
+                supergraph_req
+            })
             .instrument(move |supergraph_req: &SupergraphRequest| span_mode.create_supergraph(
                 &config_instrument.apollo,
                 supergraph_req,
@@ -982,10 +994,13 @@ impl Telemetry {
         Ok((sampler, tracer_provider))
     }
 
-    fn create_metrics_builder(config: &config::Conf) -> Result<MetricsBuilder, BoxError> {
+    fn create_metrics_builder(
+        config: &config::Conf,
+        schema_id: &str,
+    ) -> Result<MetricsBuilder, BoxError> {
         let metrics_config = &config.exporters.metrics;
         let metrics_common_config = &metrics_config.common;
-        let mut builder = MetricsBuilder::new(config);
+        let mut builder = MetricsBuilder::new(config, schema_id);
         builder = setup_metrics_exporter(builder, &config.apollo, metrics_common_config)?;
         builder =
             setup_metrics_exporter(builder, &metrics_config.prometheus, metrics_common_config)?;
@@ -1152,7 +1167,7 @@ impl Telemetry {
             let _ = req.context.insert(LOGGING_DISPLAY_HEADERS, true);
         }
         if should_log_body {
-            ::tracing::info!(http.request.body = ?req.supergraph_request.body(), "Supergraph request body");
+            ::tracing::info!(http.request.body = ?redaction::redact_request(&req.context, req.supergraph_request.body()), "Supergraph request body");
 
             let _ = req.context.insert(LOGGING_DISPLAY_BODY, true);
         }
@@ -1939,6 +1954,14 @@ fn handle_error_internal<T: Into<opentelemetry::global::Error>>(
             return;
         }
     }
+    // Batch span processors report spans dropped because their queue is full as errors,
+    // they are counted and logged per exporter instead
+    if let opentelemetry::global::Error::Trace(TraceError::Other(err)) = &err {
+        if let Some(TrySendError::ChannelFull) = err.downcast_ref::<TrySendError>() {
+            dropped::record_span_dropped_by_full_queue();
+            return;
+        }
+    }
     // Copy here so that we don't retain a mutable reference into the dashmap and lock the shard
     let now = Instant::now();
     let last_logged = *last_logged_map
@@ -2136,6 +2159,8 @@ mod tests {
     use http::StatusCode;
     use insta::assert_snapshot;
     use itertools::Itertools;
+    use opentelemetry::runtime::TrySendError;
+    use opentelemetry::trace::TraceError;
     use opentelemetry_api::propagation::Injector;
     use opentelemetry_api::propagation::TextMapPropagator;
     use opentelemetry_api::trace::SpanContext;
@@ -2160,6 +2185,7 @@ mod tests {
     use tracing_subscriber::Layer;
 
     use super::apollo::ForwardHeaders;
+    use super::dropped;
     use super::CustomTraceIdPropagator;
     use super::Telemetry;
     use crate::error::FetchError;
@@ -3207,6 +3233,45 @@ mod tests {
         test_layer.assert_log_entry_count("other error", 2);
     }
 
+    #[tokio::test]
+    async fn test_handle_error_counts_spans_dropped_by_full_queues() {
+        async {
+            let error_map = DashMap::new();
+            // the error reported by the batch span processor of the SDK
+            let dropped = dropped::with_exporter("otlp", || {
+                handle_error_internal(
+                    opentelemetry::global::Error::Trace(TraceError::Other(
+                        TrySendError::ChannelFull.into(),
+                    )),
+                    &error_map,
+                )
+            });
+            assert!(dropped);
+            assert_counter!(
+                "apollo.router.telemetry.dropped",
+                1,
+                "exporter" = "otlp",
+                "kind" = "spans",
+                "reason" = "queue_full"
+            );
+            // counted and logged per exporter, not as an error
+            assert!(error_map.is_empty());
+
+            // other errors with the same message are still logged
+            handle_error_internal(
+                opentelemetry::global::Error::Trace(
+                    "cannot send message to batch processor as the channel is full"
+                        .to_string()
+                        .into(),
+                ),
+                &error_map,
+            );
+            assert!(!error_map.is_empty());
+        }
+        .with_metrics()
+        .await;
+    }
+
     #[tokio::test]
     async fn test_custom_trace_id_propagator_strip_dashes_in_trace_id() {
         let header = String::from("x-trace-id");
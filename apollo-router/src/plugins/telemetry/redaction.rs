@@ -0,0 +1,469 @@
+//! Redaction of argument and variable values in telemetry
+//!
+//! The values of the configured arguments, by schema coordinate, and variables, by name, are
+//! hashed or dropped from the telemetry data: the variables sent in Apollo traces, the query
+//! variable selectors of spans, instruments and events, and the logged request bodies. Literals
+//! are stripped from the documents in `graphql.document` attributes and logged bodies, and the
+//! raw router request bodies are not logged. The variables to redact are computed once per
+//! request, when the supergraph request enters the telemetry plugin, and stored in the context
+//! extensions.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use apollo_compiler::ast;
+use apollo_compiler::executable;
+use apollo_compiler::ExecutableDocument;
+use apollo_compiler::Name;
+use hmac::Hmac;
+use hmac::Mac;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json_bytes::Value;
+use tower::BoxError;
+
+use crate::graphql;
+use crate::json_ext::Object;
+use crate::services::layers::query_analysis::ParsedDocument;
+use crate::services::SupergraphRequest;
+use crate::spec::query::literals::strip_literals;
+use crate::Context;
+
+type HmacSha256 = Hmac<sha2::Sha256>;
+
+pub(crate) const REDACTED: &str = "[REDACTED]";
+
+/// Redaction of argument and variable values in telemetry
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct Redaction {
+    /// Arguments whose values are redacted, by schema coordinate, like `Query.user(email:)`. The
+    /// values of the variables passed to these arguments are redacted
+    pub(crate) arguments: HashMap<String, RedactionAction>,
+    /// Variables whose values are redacted, by name
+    pub(crate) variables: HashMap<String, RedactionAction>,
+    /// Secret key of the HMAC of hashed values, required to hash values. Without a secret, the
+    /// values of small domains, like phone numbers, could be found from their hash
+    pub(crate) hash_secret: Option<String>,
+}
+
+/// Redaction of a value
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum RedactionAction {
+    /// Replace the value with the hexadecimal HMAC-SHA256 of its JSON representation, keyed with
+    /// the `hash_secret`
+    Hash,
+    /// Remove the value
+    Drop,
+}
+
+/// Redaction of a request, stored in the context extensions
+#[derive(Clone)]
+struct RequestRedaction {
+    variables: Arc<HashMap<String, RedactionAction>>,
+    hash_key: Option<HmacSha256>,
+}
+
+impl RequestRedaction {
+    fn redact<'a>(&self, name: &str, value: &'a Value) -> Option<Cow<'a, Value>> {
+        match self.variables.get(name) {
+            None => Some(Cow::Borrowed(value)),
+            Some(RedactionAction::Hash) => self.hash(value).map(Cow::Owned),
+            Some(RedactionAction::Drop) => None,
+        }
+    }
+
+    /// The hash of the value, or None without a hash key to drop the value
+    fn hash(&self, value: &Value) -> Option<Value> {
+        let mut mac = self.hash_key.clone()?;
+        mac.update(&serde_json::to_vec(value).unwrap_or_default());
+        Some(Value::String(
+            hex::encode(mac.finalize().into_bytes()).into(),
+        ))
+    }
+}
+
+impl Redaction {
+    fn is_enabled(&self) -> bool {
+        !self.arguments.is_empty() || !self.variables.is_empty()
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), BoxError> {
+        let hashes = self
+            .arguments
+            .values()
+            .chain(self.variables.values())
+            .any(|action| *action == RedactionAction::Hash);
+        if hashes && self.hash_secret.as_deref().unwrap_or_default().is_empty() {
+            return Err("telemetry.redaction.hash_secret is required to hash values".into());
+        }
+        Ok(())
+    }
+
+    fn request_redaction(&self, variables: HashMap<String, RedactionAction>) -> RequestRedaction {
+        RequestRedaction {
+            variables: Arc::new(variables),
+            hash_key: self.hash_secret.as_ref().map(|secret| {
+                HmacSha256::new_from_slice(secret.as_bytes())
+                    .expect("HMAC can take a key of any size")
+            }),
+        }
+    }
+
+    /// Marks the request as redacted before its body is parsed, so that the raw body is not
+    /// logged
+    pub(crate) fn on_router_request(&self, context: &Context) {
+        if self.is_enabled() {
+            let redaction = self.request_redaction(self.variables.clone());
+            context
+                .extensions()
+                .with_lock(|mut lock| lock.insert(redaction));
+        }
+    }
+
+    /// Computes the variables redacted for the request and stores them in its context
+    pub(crate) fn on_request(&self, request: &SupergraphRequest) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let mut redacted = self.variables.clone();
+        if !self.arguments.is_empty() {
+            let document = request
+                .context
+                .extensions()
+                .with_lock(|lock| lock.get::<ParsedDocument>().cloned());
+            let operation_name = request.supergraph_request.body().operation_name.as_deref();
+            if let Some(document) = document {
+                if let Ok(operation) = document.executable.operations.get(operation_name) {
+                    self.visit_selection_set(
+                        &document.executable,
+                        &operation.selection_set,
+                        &mut redacted,
+                        &mut HashSet::new(),
+                    );
+                }
+            }
+        }
+
+        let redaction = self.request_redaction(redacted);
+        request
+            .context
+            .extensions()
+            .with_lock(|mut lock| lock.insert(redaction));
+    }
+
+    fn visit_selection_set<'a>(
+        &self,
+        document: &'a ExecutableDocument,
+        selection_set: &'a executable::SelectionSet,
+        redacted: &mut HashMap<String, RedactionAction>,
+        visited_fragments: &mut HashSet<&'a Name>,
+    ) {
+        for selection in &selection_set.selections {
+            match selection {
+                executable::Selection::Field(field) => {
+                    for argument in &field.arguments {
+                        let coordinate =
+                            format!("{}.{}({}:)", selection_set.ty, field.name, argument.name);
+                        if let Some(action) = self.arguments.get(&coordinate) {
+                            collect_variables(&argument.value, *action, redacted);
+                        }
+                    }
+                    self.visit_selection_set(
+                        document,
+                        &field.selection_set,
+                        redacted,
+                        visited_fragments,
+                    );
+                }
+                executable::Selection::InlineFragment(fragment) => {
+                    self.visit_selection_set(
+                        document,
+                        &fragment.selection_set,
+                        redacted,
+                        visited_fragments,
+                    );
+                }
+                executable::Selection::FragmentSpread(spread) => {
+                    if visited_fragments.insert(&spread.fragment_name) {
+                        if let Some(fragment) = document.fragments.get(&spread.fragment_name) {
+                            self.visit_selection_set(
+                                document,
+                                &fragment.selection_set,
+                                redacted,
+                                visited_fragments,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Collects the variables used in an argument value, dropping wins over hashing
+fn collect_variables(
+    value: &ast::Value,
+    action: RedactionAction,
+    redacted: &mut HashMap<String, RedactionAction>,
+) {
+    match value {
+        ast::Value::Variable(name) => {
+            let current = redacted.entry(name.to_string()).or_insert(action);
+            if action == RedactionAction::Drop {
+                *current = RedactionAction::Drop;
+            }
+        }
+        ast::Value::List(items) => {
+            for item in items {
+                collect_variables(item, action, redacted);
+            }
+        }
+        ast::Value::Object(fields) => {
+            for (_, value) in fields {
+                collect_variables(value, action, redacted);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn request_redaction(context: &Context) -> Option<RequestRedaction> {
+    context
+        .extensions()
+        .with_lock(|lock| lock.get::<RequestRedaction>().cloned())
+}
+
+/// Whether the telemetry of the request is redacted
+pub(crate) fn is_redacted(context: &Context) -> bool {
+    context
+        .extensions()
+        .with_lock(|lock| lock.contains_key::<RequestRedaction>())
+}
+
+/// Returns the value of a variable as it can appear in telemetry, or None if it is dropped
+pub(crate) fn redact_variable<'a>(
+    context: &Context,
+    name: &str,
+    value: &'a Value,
+) -> Option<Cow<'a, Value>> {
+    match request_redaction(context) {
+        None => Some(Cow::Borrowed(value)),
+        Some(redaction) => redaction.redact(name, value),
+    }
+}
+
+/// Returns the variables as they can appear in telemetry
+pub(crate) fn redact_variables<'a>(context: &Context, variables: &'a Object) -> Cow<'a, Object> {
+    let Some(redaction) = request_redaction(context) else {
+        return Cow::Borrowed(variables);
+    };
+    if !variables
+        .keys()
+        .any(|name| redaction.variables.contains_key(name.as_str()))
+    {
+        return Cow::Borrowed(variables);
+    }
+
+    Cow::Owned(
+        variables
+            .iter()
+            .filter_map(|(name, value)| {
+                redaction
+                    .redact(name.as_str(), value)
+                    .map(|value| (name.clone(), value.into_owned()))
+            })
+            .collect(),
+    )
+}
+
+/// Returns the document as it can appear in telemetry, without its literals if the request is
+/// redacted
+pub(crate) fn redact_document<'a>(context: &Context, document: &'a str) -> Cow<'a, str> {
+    if !is_redacted(context) {
+        return Cow::Borrowed(document);
+    }
+    // documents that cannot be parsed are not executed
+    strip_literals(document)
+        .map(Cow::Owned)
+        .unwrap_or(Cow::Borrowed(REDACTED))
+}
+
+/// Returns the GraphQL request as it can appear in telemetry
+pub(crate) fn redact_request<'a>(
+    context: &Context,
+    request: &'a graphql::Request,
+) -> Cow<'a, graphql::Request> {
+    if !is_redacted(context) {
+        return Cow::Borrowed(request);
+    }
+    let mut request = request.clone();
+    request.variables = redact_variables(context, &request.variables).into_owned();
+    request.query = request
+        .query
+        .as_deref()
+        .map(|query| redact_document(context, query).into_owned());
+    Cow::Owned(request)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json_bytes::json;
+
+    use super::*;
+    use crate::services::layers::query_analysis::ParsedDocumentInner;
+
+    const SCHEMA: &str = r#"
+        type Query {
+            user(email: String, filter: UserFilter): User
+            users(ids: [ID!]): [User]
+        }
+
+        type User {
+            name: String
+        }
+
+        input UserFilter {
+            phone: String
+        }
+    "#;
+
+    fn request(query: &str, variables: Value) -> SupergraphRequest {
+        let schema = apollo_compiler::Schema::parse_and_validate(SCHEMA, "schema.graphql").unwrap();
+        let executable =
+            ExecutableDocument::parse_and_validate(&schema, query, "query.graphql").unwrap();
+        let ast = ast::Document::parse(query, "query.graphql").unwrap();
+        let request = SupergraphRequest::fake_builder()
+            .query(query)
+            .variables(variables.as_object().unwrap().clone())
+            .build()
+            .unwrap();
+        request.context.extensions().with_lock(|mut lock| {
+            lock.insert::<ParsedDocument>(Arc::new(ParsedDocumentInner {
+                ast,
+                executable: Arc::new(executable),
+                hash: Default::default(),
+            }))
+        });
+        request
+    }
+
+    fn redaction(config: serde_json::Value) -> Redaction {
+        serde_json::from_value(config).unwrap()
+    }
+
+    #[test]
+    fn it_redacts_variables_passed_to_arguments() {
+        let request = request(
+            "query($email: String, $phone: String, $ids: [ID!], $name: String) {
+                user(email: $email, filter: { phone: $phone }) { ...UserFields }
+                users(ids: $ids) { name }
+                ... on Query { other: user(email: $name) { name } }
+            }
+            fragment UserFields on User { name }",
+            json!({
+                "email": "user@example.com",
+                "phone": "555",
+                "ids": ["1"],
+                "name": "user"
+            }),
+        );
+        redaction(serde_json::json!({
+            "arguments": {
+                "Query.user(email:)": "hash",
+                "Query.user(filter:)": "drop"
+            },
+            "variables": { "ids": "drop" },
+            "hash_secret": "telemetry secret"
+        }))
+        .on_request(&request);
+
+        let variables = redact_variables(
+            &request.context,
+            &request.supergraph_request.body().variables,
+        );
+        assert_eq!(
+            variables.get("email"),
+            Some(&json!(
+                "ffcefd5673ddbee33f62c5f53bbeb70903049fce760d4f533d481ab4be0894d3"
+            ))
+        );
+        assert!(variables
+            .get("name")
+            .is_some_and(|name| name != &json!("user")));
+        assert!(!variables.contains_key("phone"));
+        assert!(!variables.contains_key("ids"));
+
+        assert!(redact_variable(&request.context, "phone", &json!("555")).is_none());
+        let request_body = redact_request(&request.context, request.supergraph_request.body());
+        assert!(!request_body.variables.contains_key("ids"));
+    }
+
+    #[test]
+    fn it_keeps_requests_without_redacted_variables() {
+        let request = request(
+            "query($ids: [ID!]) { users(ids: $ids) { name } }",
+            json!({ "ids": ["1"] }),
+        );
+        redaction(serde_json::json!({
+            "arguments": { "Query.user(email:)": "drop" }
+        }))
+        .on_request(&request);
+
+        let body = request.supergraph_request.body();
+        let redacted = redact_request(&request.context, body);
+        assert_eq!(redacted.variables, body.variables);
+        assert!(redacted
+            .query
+            .as_deref()
+            .unwrap()
+            .contains("users(ids: $ids)"));
+    }
+
+    #[test]
+    fn it_strips_literals_of_redacted_requests() {
+        let query = r#"{ user(email: "user@example.com") { name } }"#;
+        let request = request(query, json!({}));
+        assert_eq!(redact_document(&request.context, query), query);
+
+        redaction(serde_json::json!({
+            "variables": { "token": "drop" }
+        }))
+        .on_request(&request);
+
+        assert!(is_redacted(&request.context));
+        assert!(!redact_document(&request.context, query).contains("user@example.com"));
+        assert!(
+            !redact_request(&request.context, request.supergraph_request.body())
+                .query
+                .as_deref()
+                .unwrap()
+                .contains("user@example.com")
+        );
+    }
+
+    #[test]
+    fn it_requires_a_secret_to_hash_values() {
+        assert!(redaction(serde_json::json!({
+            "arguments": { "Query.user(email:)": "hash" }
+        }))
+        .validate()
+        .is_err());
+        assert!(redaction(serde_json::json!({
+            "arguments": { "Query.user(email:)": "hash" },
+            "hash_secret": "telemetry secret"
+        }))
+        .validate()
+        .is_ok());
+        assert!(redaction(serde_json::json!({
+            "arguments": { "Query.user(email:)": "drop" }
+        }))
+        .validate()
+        .is_ok());
+    }
+}
@@ -8,6 +8,7 @@ use crate::plugins::telemetry::consts::REQUEST_SPAN_NAME;
 use crate::plugins::telemetry::consts::ROUTER_SPAN_NAME;
 use crate::plugins::telemetry::consts::SUBGRAPH_SPAN_NAME;
 use crate::plugins::telemetry::consts::SUPERGRAPH_SPAN_NAME;
+use crate::plugins::telemetry::redaction::redact_variables;
 use crate::plugins::telemetry::Telemetry;
 use crate::services::SubgraphRequest;
 use crate::services::SupergraphRequest;
@@ -136,7 +137,10 @@ impl SpanMode {
                         field_level_instrumentation_ratio,
                     apollo_private.operation_signature = ::tracing::field::Empty,
                     apollo_private.graphql.variables = Telemetry::filter_variables_values(
-                        &request.supergraph_request.body().variables,
+                        &redact_variables(
+                            &request.context,
+                            &request.supergraph_request.body().variables
+                        ),
                         &send_variable_values,
                     ),
                 );
@@ -159,7 +163,10 @@ impl SpanMode {
                         field_level_instrumentation_ratio,
                     apollo_private.operation_signature = ::tracing::field::Empty,
                     apollo_private.graphql.variables = Telemetry::filter_variables_values(
-                        &request.supergraph_request.body().variables,
+                        &redact_variables(
+                            &request.context,
+                            &request.supergraph_request.body().variables
+                        ),
                         &send_variable_values,
                     )
                 )
@@ -10,6 +10,8 @@ use crate::plugins::telemetry::config;
 use crate::plugins::telemetry::config_new::spans::Spans;
 use crate::plugins::telemetry::span_factory::SpanMode;
 use crate::plugins::telemetry::tracing::apollo_telemetry;
+use crate::plugins::telemetry::tracing::ExporterQueue;
+use crate::plugins::telemetry::tracing::SpanProcessorExt;
 use crate::plugins::telemetry::tracing::TracingConfigurator;
 
 impl TracingConfigurator for Config {
@@ -47,10 +49,12 @@ impl TracingConfigurator for Config {
             .use_legacy_request_span(matches!(spans_config.mode, SpanMode::Deprecated))
             .metrics_reference_mode(self.experimental_apollo_metrics_reference_mode)
             .build()?;
+        let queue = ExporterQueue::new("apollo", &self.batch_processor);
         Ok(builder.with_span_processor(
-            BatchSpanProcessor::builder(exporter, opentelemetry::runtime::Tokio)
+            BatchSpanProcessor::builder(queue.tracking(exporter), opentelemetry::runtime::Tokio)
                 .with_batch_config(self.batch_processor.clone().into())
-                .build(),
+                .build()
+                .counting_drops(queue),
         ))
     }
 }
@@ -41,6 +41,7 @@ use crate::plugins::telemetry::endpoint::UriEndpoint;
 use crate::plugins::telemetry::tracing::datadog_exporter;
 use crate::plugins::telemetry::tracing::datadog_exporter::DatadogTraceState;
 use crate::plugins::telemetry::tracing::BatchProcessorConfig;
+use crate::plugins::telemetry::tracing::ExporterQueue;
 use crate::plugins::telemetry::tracing::SpanProcessorExt;
 use crate::plugins::telemetry::tracing::TracingConfigurator;
 
@@ -210,17 +211,20 @@ impl TracingConfigurator for Config {
         let mut span_metrics = default_span_metrics();
         span_metrics.extend(self.span_metrics.clone());
 
+        let queue = ExporterQueue::new("datadog", &self.batch_processor);
+
         Ok(builder.with_span_processor(
             BatchSpanProcessor::builder(
-                ExporterWrapper {
+                queue.tracking(ExporterWrapper {
                     delegate: exporter,
                     span_metrics,
-                },
+                }),
                 opentelemetry::runtime::Tokio,
             )
             .with_batch_config(self.batch_processor.clone().into())
             .build()
-            .filtered(),
+            .filtered()
+            .counting_drops(queue),
         ))
     }
 }
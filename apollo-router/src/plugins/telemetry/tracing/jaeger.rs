@@ -16,6 +16,7 @@ use crate::plugins::telemetry::config_new::spans::Spans;
 use crate::plugins::telemetry::endpoint::SocketEndpoint;
 use crate::plugins::telemetry::endpoint::UriEndpoint;
 use crate::plugins::telemetry::tracing::BatchProcessorConfig;
+use crate::plugins::telemetry::tracing::ExporterQueue;
 use crate::plugins::telemetry::tracing::SpanProcessorExt;
 use crate::plugins::telemetry::tracing::TracingConfigurator;
 
@@ -104,11 +105,16 @@ impl TracingConfigurator for Config {
                     .with_trace_config(common.into())
                     .with(&agent.endpoint.to_socket(), |b, s| b.with_endpoint(s))
                     .build_async_agent_exporter(opentelemetry::runtime::Tokio)?;
+                let queue = ExporterQueue::new("jaeger", batch_processor);
                 Ok(builder.with_span_processor(
-                    BatchSpanProcessor::builder(exporter, opentelemetry::runtime::Tokio)
-                        .with_batch_config(batch_processor.clone().into())
-                        .build()
-                        .filtered(),
+                    BatchSpanProcessor::builder(
+                        queue.tracking(exporter),
+                        opentelemetry::runtime::Tokio,
+                    )
+                    .with_batch_config(batch_processor.clone().into())
+                    .build()
+                    .filtered()
+                    .counting_drops(queue),
                 ))
             }
             Config::Collector {
@@ -136,10 +142,12 @@ impl TracingConfigurator for Config {
                     .with_reqwest()
                     .with_batch_processor_config(batch_processor.clone().into())
                     .build_collector_exporter::<runtime::Tokio>()?;
+                let queue = ExporterQueue::new("jaeger", batch_processor);
                 Ok(builder.with_span_processor(
-                    BatchSpanProcessor::builder(exporter, runtime::Tokio)
+                    BatchSpanProcessor::builder(queue.tracking(exporter), runtime::Tokio)
                         .with_batch_config(batch_processor.clone().into())
-                        .build(),
+                        .build()
+                        .counting_drops(queue),
                 ))
             }
             _ => Ok(builder),
@@ -1,8 +1,17 @@
+use std::fmt::Debug;
 use std::fmt::Display;
 use std::fmt::Formatter;
+use std::sync::atomic::AtomicI64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
 use std::time::Duration;
 
+use futures::future::BoxFuture;
+use opentelemetry::metrics::MeterProvider;
+use opentelemetry::metrics::ObservableGauge;
+use opentelemetry::sdk::export::trace::ExportResult;
 use opentelemetry::sdk::export::trace::SpanData;
+use opentelemetry::sdk::export::trace::SpanExporter;
 use opentelemetry::sdk::trace::BatchConfig;
 use opentelemetry::sdk::trace::Builder;
 use opentelemetry::sdk::trace::EvictedHashMap;
@@ -11,12 +20,18 @@ use opentelemetry::sdk::trace::SpanProcessor;
 use opentelemetry::trace::TraceResult;
 use opentelemetry::Context;
 use opentelemetry::KeyValue;
+use parking_lot::Mutex;
 use schemars::JsonSchema;
 use serde::Deserialize;
 use tower::BoxError;
 
+use super::config_new::instruments::METER_NAME;
 use super::config_new::spans::Spans;
+use super::dropped;
+use super::dropped::DropReason;
+use super::dropped::DroppedKind;
 use super::formatters::APOLLO_PRIVATE_PREFIX;
+use crate::metrics;
 use crate::plugins::telemetry::config::TracingCommon;
 
 pub(crate) mod apollo;
@@ -86,11 +101,159 @@ impl<T: SpanProcessor> SpanProcessor for ApolloFilterSpanProcessor<T> {
     }
 }
 
+/// Counts the spans, and the span attributes, events and links, dropped before reaching an
+/// exporter, and the spans waiting in the queue of its batch span processor
+#[derive(Debug)]
+struct DropCountingSpanProcessor<T: SpanProcessor> {
+    queue: ExporterQueue,
+    delegate: T,
+}
+
+impl<T: SpanProcessor> SpanProcessor for DropCountingSpanProcessor<T> {
+    fn on_start(&self, span: &mut Span, cx: &Context) {
+        self.delegate.on_start(span, cx);
+    }
+
+    fn on_end(&self, span: SpanData) {
+        let exporter = self.queue.exporter;
+        for (kind, count) in [
+            (DroppedKind::SpanAttributes, span.attributes.dropped_count()),
+            (DroppedKind::SpanEvents, span.events.dropped_count()),
+            (DroppedKind::SpanLinks, span.links.dropped_count()),
+        ] {
+            dropped::record_dropped(exporter, kind, DropReason::SpanLimits, count as u64);
+        }
+        // batch span processors only queue sampled spans
+        let sampled = span.span_context.is_sampled();
+        // spans dropped by a full batch queue are reported to the error handler, during this call
+        let dropped = dropped::with_exporter(exporter, || self.delegate.on_end(span));
+        if sampled && !dropped {
+            self.queue.queued.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn force_flush(&self) -> TraceResult<()> {
+        self.delegate.force_flush()
+    }
+
+    fn shutdown(&mut self) -> TraceResult<()> {
+        let result = self.delegate.shutdown();
+        self.queue.shutdown();
+        result
+    }
+}
+
+/// Queue of the batch span processor of an exporter.
+///
+/// Each exporter has its own batch span processor, with its own queue and export task, so an
+/// unavailable endpoint only fills the queue of its exporter. The spans waiting in the queue are
+/// reported by the `apollo.router.telemetry.batch_processor.queue.size` and
+/// `apollo.router.telemetry.batch_processor.queue.saturation` gauges.
+///
+/// The batch span processor does not report when it takes spans out of its queue, so spans are
+/// counted as queued until their batch is passed to the exporter. The gauges are an
+/// approximation: they include the spans of the batch being built, up to `max_export_batch_size`.
+#[derive(Clone)]
+pub(crate) struct ExporterQueue {
+    exporter: &'static str,
+    queued: Arc<AtomicI64>,
+    /// Dropping the gauges unregisters their callbacks
+    gauges: Arc<Mutex<Option<(ObservableGauge<i64>, ObservableGauge<f64>)>>>,
+}
+
+impl ExporterQueue {
+    pub(crate) fn new(exporter: &'static str, config: &BatchProcessorConfig) -> Self {
+        let queued = Arc::new(AtomicI64::new(0));
+        let meter = metrics::meter_provider().meter(METER_NAME);
+        let size = {
+            let queued = queued.clone();
+            meter
+                .i64_observable_gauge("apollo.router.telemetry.batch_processor.queue.size")
+                .with_description("Number of spans waiting in the queue of the batch span processor of an exporter")
+                .with_callback(move |gauge| {
+                    gauge.observe(
+                        queued.load(Ordering::Relaxed),
+                        &[KeyValue::new("exporter", exporter)],
+                    );
+                })
+                .init()
+        };
+        let max_queue_size = config.max_queue_size.max(1) as f64;
+        let saturation = {
+            let queued = queued.clone();
+            meter
+                .f64_observable_gauge("apollo.router.telemetry.batch_processor.queue.saturation")
+                .with_description("Fraction of the queue of the batch span processor of an exporter in use, from 0 to 1")
+                .with_callback(move |gauge| {
+                    gauge.observe(
+                        (queued.load(Ordering::Relaxed).max(0) as f64 / max_queue_size).min(1.0),
+                        &[KeyValue::new("exporter", exporter)],
+                    );
+                })
+                .init()
+        };
+        ExporterQueue {
+            exporter,
+            queued,
+            gauges: Arc::new(Mutex::new(Some((size, saturation)))),
+        }
+    }
+
+    /// Unregisters the gauges once the batch span processor is shut down, so they are not
+    /// reported next to the gauges of the processor replacing it on reload. The spans still
+    /// queued at this point are discarded
+    fn shutdown(&self) {
+        self.gauges.lock().take();
+        self.queued.store(0, Ordering::Relaxed);
+    }
+
+    /// Wraps the exporter of the batch span processor, to count the spans leaving the queue
+    pub(crate) fn tracking<E: SpanExporter>(&self, exporter: E) -> QueueTrackingExporter<E> {
+        QueueTrackingExporter {
+            queued: self.queued.clone(),
+            delegate: exporter,
+        }
+    }
+}
+
+impl Debug for ExporterQueue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ExporterQueue")
+            .field("exporter", &self.exporter)
+            .field("queued", &self.queued)
+            .finish()
+    }
+}
+
+/// Span exporter counting the spans taken out of the queue of its batch span processor
+#[derive(Debug)]
+pub(crate) struct QueueTrackingExporter<E> {
+    queued: Arc<AtomicI64>,
+    delegate: E,
+}
+
+impl<E: SpanExporter> SpanExporter for QueueTrackingExporter<E> {
+    fn export(&mut self, batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+        self.queued.fetch_sub(batch.len() as i64, Ordering::Relaxed);
+        self.delegate.export(batch)
+    }
+
+    fn shutdown(&mut self) {
+        self.delegate.shutdown()
+    }
+
+    fn force_flush(&mut self) -> BoxFuture<'static, ExportResult> {
+        self.delegate.force_flush()
+    }
+}
+
 trait SpanProcessorExt
 where
     Self: Sized + SpanProcessor,
 {
     fn filtered(self) -> ApolloFilterSpanProcessor<Self>;
+
+    fn counting_drops(self, queue: ExporterQueue) -> DropCountingSpanProcessor<Self>;
 }
 
 impl<T: SpanProcessor> SpanProcessorExt for T
@@ -100,6 +263,13 @@ where
     fn filtered(self) -> ApolloFilterSpanProcessor<Self> {
         ApolloFilterSpanProcessor { delegate: self }
     }
+
+    fn counting_drops(self, queue: ExporterQueue) -> DropCountingSpanProcessor<Self> {
+        DropCountingSpanProcessor {
+            queue,
+            delegate: self,
+        }
+    }
 }
 
 /// Batch processor configuration
@@ -191,3 +361,113 @@ impl Default for BatchProcessorConfig {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::time::SystemTime;
+
+    use opentelemetry::sdk::trace::EvictedQueue;
+    use opentelemetry::sdk::Resource;
+    use opentelemetry::trace::SpanContext;
+    use opentelemetry::trace::SpanId;
+    use opentelemetry::trace::SpanKind;
+    use opentelemetry::trace::Status;
+    use opentelemetry::trace::TraceFlags;
+    use opentelemetry::trace::TraceId;
+    use opentelemetry::trace::TraceState;
+    use opentelemetry::InstrumentationLibrary;
+
+    use super::*;
+    use crate::metrics::FutureMetricsExt;
+
+    #[derive(Debug)]
+    struct Noop;
+
+    impl SpanProcessor for Noop {
+        fn on_start(&self, _span: &mut Span, _cx: &Context) {}
+
+        fn on_end(&self, _span: SpanData) {}
+
+        fn force_flush(&self) -> TraceResult<()> {
+            Ok(())
+        }
+
+        fn shutdown(&mut self) -> TraceResult<()> {
+            Ok(())
+        }
+    }
+
+    impl SpanExporter for Noop {
+        fn export(&mut self, _batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    fn span(trace_flags: TraceFlags) -> SpanData {
+        SpanData {
+            span_context: SpanContext::new(
+                TraceId::from_u128(1),
+                SpanId::from_u64(1),
+                trace_flags,
+                false,
+                TraceState::default(),
+            ),
+            parent_span_id: SpanId::INVALID,
+            span_kind: SpanKind::Internal,
+            name: "span".into(),
+            start_time: SystemTime::UNIX_EPOCH,
+            end_time: SystemTime::UNIX_EPOCH,
+            attributes: EvictedHashMap::new(128, 0),
+            events: EvictedQueue::new(128),
+            links: EvictedQueue::new(128),
+            status: Status::Unset,
+            resource: Cow::Owned(Resource::empty()),
+            instrumentation_lib: InstrumentationLibrary::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_reports_the_saturation_of_exporter_queues() {
+        async {
+            let config = BatchProcessorConfig {
+                max_queue_size: 4,
+                ..Default::default()
+            };
+            let queue = ExporterQueue::new("otlp", &config);
+            let mut processor = Noop.counting_drops(queue.clone());
+            processor.on_end(span(TraceFlags::SAMPLED));
+            processor.on_end(span(TraceFlags::SAMPLED));
+            // unsampled spans are not queued
+            processor.on_end(span(TraceFlags::default()));
+            assert_gauge!(
+                "apollo.router.telemetry.batch_processor.queue.size",
+                2,
+                "exporter" = "otlp"
+            );
+            assert_gauge!(
+                "apollo.router.telemetry.batch_processor.queue.saturation",
+                0.5,
+                "exporter" = "otlp"
+            );
+
+            queue
+                .tracking(Noop)
+                .export(vec![span(TraceFlags::SAMPLED)])
+                .await
+                .unwrap();
+            assert_gauge!(
+                "apollo.router.telemetry.batch_processor.queue.size",
+                1,
+                "exporter" = "otlp"
+            );
+
+            // the gauges are unregistered with the processor, before a reload registers new ones
+            processor.shutdown().unwrap();
+            assert!(queue.gauges.lock().is_none());
+            assert_eq!(queue.queued.load(Ordering::Relaxed), 0);
+        }
+        .with_metrics()
+        .await;
+    }
+}
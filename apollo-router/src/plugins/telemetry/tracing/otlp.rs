@@ -9,6 +9,7 @@ use tower::BoxError;
 use crate::plugins::telemetry::config::TracingCommon;
 use crate::plugins::telemetry::config_new::spans::Spans;
 use crate::plugins::telemetry::otlp::TelemetryDataKind;
+use crate::plugins::telemetry::tracing::ExporterQueue;
 use crate::plugins::telemetry::tracing::SpanProcessorExt;
 use crate::plugins::telemetry::tracing::TracingConfigurator;
 
@@ -26,14 +27,17 @@ impl TracingConfigurator for super::super::otlp::Config {
         tracing::info!("Configuring Otlp tracing: {}", self.batch_processor);
         let exporter: SpanExporterBuilder = self.exporter(TelemetryDataKind::Traces)?;
 
+        let queue = ExporterQueue::new("otlp", &self.batch_processor);
+
         Ok(builder.with_span_processor(
             BatchSpanProcessor::builder(
-                exporter.build_span_exporter()?,
+                queue.tracking(exporter.build_span_exporter()?),
                 opentelemetry::runtime::Tokio,
             )
             .with_batch_config(self.batch_processor.clone().into())
             .build()
-            .filtered(),
+            .filtered()
+            .counting_drops(queue),
         ))
     }
 }
@@ -14,6 +14,7 @@ use crate::plugins::telemetry::config::TracingCommon;
 use crate::plugins::telemetry::config_new::spans::Spans;
 use crate::plugins::telemetry::endpoint::UriEndpoint;
 use crate::plugins::telemetry::tracing::BatchProcessorConfig;
+use crate::plugins::telemetry::tracing::ExporterQueue;
 use crate::plugins::telemetry::tracing::SpanProcessorExt;
 use crate::plugins::telemetry::tracing::TracingConfigurator;
 
@@ -64,11 +65,14 @@ impl TracingConfigurator for Config {
             .with_trace_config(common)
             .init_exporter()?;
 
+        let queue = ExporterQueue::new("zipkin", &self.batch_processor);
+
         Ok(builder.with_span_processor(
-            BatchSpanProcessor::builder(exporter, opentelemetry::runtime::Tokio)
+            BatchSpanProcessor::builder(queue.tracking(exporter), opentelemetry::runtime::Tokio)
                 .with_batch_config(self.batch_processor.clone().into())
                 .build()
-                .filtered(),
+                .filtered()
+                .counting_drops(queue),
         ))
     }
 }
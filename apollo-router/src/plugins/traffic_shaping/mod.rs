@@ -5,17 +5,23 @@
 //! * Timeout
 //! * Compression
 //! * Rate limiting
+//! * Retry-After handling
 //!
 mod deduplication;
 pub(crate) mod rate;
 mod retry;
+mod retry_after;
 pub(crate) mod timeout;
 
 use std::collections::HashMap;
+use std::io;
 use std::num::NonZeroU64;
+use std::sync::Arc;
 use std::sync::Mutex;
 use std::time::Duration;
+use std::time::Instant;
 
+use bytesize::ByteSize;
 use futures::future::BoxFuture;
 use futures::FutureExt;
 use http::header::CONTENT_ENCODING;
@@ -33,18 +39,28 @@ use self::deduplication::QueryDeduplicationLayer;
 use self::rate::RateLimitLayer;
 use self::rate::RateLimited;
 pub(crate) use self::retry::RetryPolicy;
+use self::retry_after::ClientRetryAfter;
+use self::retry_after::RetryAfterConf;
+use self::retry_after::RetryAfterLayer;
 use self::timeout::Elapsed;
 use self::timeout::TimeoutLayer;
+use crate::cache::store;
+use crate::cache::store::InMemoryStore;
+use crate::cache::store::Store;
+use crate::configuration::RedisCache;
 use crate::error::ConfigurationError;
 use crate::graphql;
 use crate::layers::ServiceBuilderExt;
 use crate::plugin::Plugin;
 use crate::plugin::PluginInit;
 use crate::register_plugin;
+use crate::services::http::proxy::ProxyConfig;
 use crate::services::http::service::Compression;
 use crate::services::subgraph;
 use crate::services::supergraph;
+use crate::services::trust_dns_connector::DnsConfig;
 use crate::services::SubgraphRequest;
+use crate::Context;
 
 const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
 pub(crate) const APOLLO_TRAFFIC_SHAPING: &str = "apollo.traffic_shaping";
@@ -61,6 +77,10 @@ struct Shaping {
     deduplicate_query: Option<bool>,
     /// Enable compression for subgraphs (available compressions are deflate, br, gzip)
     compression: Option<Compression>,
+    /// Only compress entity fetches whose representations are larger than this size, in bytes.
+    /// Other requests are sent uncompressed (default: all requests are compressed)
+    #[schemars(with = "Option<String>", default)]
+    compression_threshold: Option<ByteSize>,
     /// Enable global rate limiting
     global_rate_limit: Option<RateLimitConf>,
     #[serde(deserialize_with = "humantime_serde::deserialize", default)]
@@ -72,6 +92,20 @@ struct Shaping {
     experimental_retry: Option<RetryConfig>,
     /// Enable HTTP2 for subgraphs
     experimental_http2: Option<Http2Config>,
+    /// Establish a connection to subgraphs when the router starts or reloads, before it serves
+    /// traffic, by sending them a `{ __typename }` query
+    experimental_connection_warmup: Option<bool>,
+    #[serde(deserialize_with = "humantime_serde::deserialize", default)]
+    #[schemars(with = "String", default)]
+    /// Keep idle connections to subgraphs open for this duration (default: 5s)
+    experimental_pool_idle_timeout: Option<Duration>,
+    /// Send requests to subgraphs through a HTTP or SOCKS5 proxy
+    proxy: Option<ProxyConfig>,
+    /// DNS resolution of subgraph host names
+    dns: Option<DnsConfig>,
+    /// Handling of `429 Too Many Requests` and `503 Service Unavailable` responses with a
+    /// `Retry-After` header
+    retry_after: Option<RetryAfterConf>,
 }
 
 #[derive(PartialEq, Default, Debug, Clone, Deserialize, JsonSchema)]
@@ -93,6 +127,9 @@ impl Merge for Shaping {
             Some(fallback) => Shaping {
                 deduplicate_query: self.deduplicate_query.or(fallback.deduplicate_query),
                 compression: self.compression.or(fallback.compression),
+                compression_threshold: self
+                    .compression_threshold
+                    .or(fallback.compression_threshold),
                 timeout: self.timeout.or(fallback.timeout),
                 global_rate_limit: self
                     .global_rate_limit
@@ -102,13 +139,22 @@ impl Merge for Shaping {
                 experimental_retry: self
                     .experimental_retry
                     .as_ref()
-                    .or(fallback.experimental_retry.as_ref())
-                    .cloned(),
+                    .map(|retry| retry.merge(fallback.experimental_retry.as_ref()))
+                    .or_else(|| fallback.experimental_retry.clone()),
                 experimental_http2: self
                     .experimental_http2
                     .as_ref()
                     .or(fallback.experimental_http2.as_ref())
                     .cloned(),
+                experimental_connection_warmup: self
+                    .experimental_connection_warmup
+                    .or(fallback.experimental_connection_warmup),
+                experimental_pool_idle_timeout: self
+                    .experimental_pool_idle_timeout
+                    .or(fallback.experimental_pool_idle_timeout),
+                proxy: self.proxy.as_ref().or(fallback.proxy.as_ref()).cloned(),
+                dns: self.dns.as_ref().or(fallback.dns.as_ref()).cloned(),
+                retry_after: self.retry_after.or(fallback.retry_after),
             },
         }
     }
@@ -132,8 +178,20 @@ struct RetryConfig {
     /// is 0.2
     retry_percent: Option<f32>,
     /// allows request retries on mutations. This should only be activated if mutations
-    /// are idempotent. Disabled by default
+    /// are idempotent. Disabled by default. When disabled, only query fetches belonging to
+    /// a query operation are retried, fetches following a mutation are never retried
     retry_mutations: Option<bool>,
+    #[serde(deserialize_with = "humantime_serde::deserialize", default)]
+    #[schemars(with = "String", default)]
+    /// delay before the first retry, doubled on each following attempt. The default value
+    /// is 0, which retries immediately
+    min_backoff: Option<Duration>,
+    #[serde(deserialize_with = "humantime_serde::deserialize", default)]
+    #[schemars(with = "String", default)]
+    /// maximum delay between two retries, default value is 1 second
+    max_backoff: Option<Duration>,
+    /// maximum number of retries for a single subgraph request, default value is 3
+    max_attempts: Option<u32>,
 }
 
 impl Merge for RetryConfig {
@@ -145,6 +203,9 @@ impl Merge for RetryConfig {
                 min_per_sec: self.min_per_sec.or(fallback.min_per_sec),
                 retry_percent: self.retry_percent.or(fallback.retry_percent),
                 retry_mutations: self.retry_mutations.or(fallback.retry_mutations),
+                min_backoff: self.min_backoff.or(fallback.min_backoff),
+                max_backoff: self.max_backoff.or(fallback.max_backoff),
+                max_attempts: self.max_attempts.or(fallback.max_attempts),
             },
         }
     }
@@ -178,6 +239,49 @@ struct RouterShaping {
     #[schemars(with = "String", default)]
     /// Enable timeout for incoming requests
     timeout: Option<Duration>,
+    /// How requests that timed out are reported to clients
+    timeout_response: Option<TimeoutResponseConf>,
+}
+
+/// How requests that timed out are reported to clients
+#[derive(PartialEq, Debug, Clone, Copy, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+struct TimeoutResponseConf {
+    /// HTTP status code of the response when the router timeout is reached
+    status_code: TimeoutStatusCode,
+    /// Add a `timeout` extension to timeout errors, with the stage that timed out (`router` or
+    /// `subgraph`), its budget and the time it ran for, in milliseconds
+    include_details: bool,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum TimeoutStatusCode {
+    /// Respond with a 504 Gateway Timeout status code
+    #[default]
+    GatewayTimeout,
+    /// Respond with a 200 OK status code, the timeout is only reported in the GraphQL errors
+    Ok,
+}
+
+impl From<TimeoutStatusCode> for StatusCode {
+    fn from(status_code: TimeoutStatusCode) -> Self {
+        match status_code {
+            TimeoutStatusCode::GatewayTimeout => StatusCode::GATEWAY_TIMEOUT,
+            TimeoutStatusCode::Ok => StatusCode::OK,
+        }
+    }
+}
+
+impl TimeoutResponseConf {
+    fn error(&self, stage: &str, budget: Duration, elapsed: Duration) -> graphql::Error {
+        let error = Elapsed::new();
+        if self.include_details {
+            error.to_graphql_error_with_details(stage, budget, elapsed)
+        } else {
+            error.to_graphql_error()
+        }
+    }
 }
 
 #[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
@@ -194,6 +298,18 @@ pub(crate) struct Config {
     subgraphs: HashMap<String, SubgraphShaping>,
     /// DEPRECATED, now always enabled: Enable variable deduplication optimization when sending requests to subgraphs (https://github.com/apollographql/router/issues/87)
     deduplicate_variables: Option<bool>,
+    /// Storage of the rate limit counters. By default, they are kept in memory and each router
+    /// instance enforces the rate limits on its own
+    rate_limit_store: Option<RateLimitStore>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+/// Storage of the rate limit counters
+struct RateLimitStore {
+    /// Redis storing the counters, so that the router instances using it enforce the rate
+    /// limits together
+    redis: RedisCache,
 }
 
 #[derive(PartialEq, Debug, Clone, Deserialize, JsonSchema)]
@@ -224,7 +340,8 @@ impl Merge for RateLimitConf {
 pub(crate) struct TrafficShaping {
     config: Config,
     rate_limit_router: Option<RateLimitLayer>,
-    rate_limit_subgraphs: Mutex<HashMap<String, RateLimitLayer>>,
+    rate_limit_store: Arc<dyn Store>,
+    retry_after_subgraphs: Mutex<HashMap<String, RetryAfterLayer>>,
 }
 
 #[async_trait::async_trait]
@@ -232,6 +349,14 @@ impl Plugin for TrafficShaping {
     type Config = Config;
 
     async fn new(init: PluginInit<Self::Config>) -> Result<Self, BoxError> {
+        let rate_limit_store = match &init.config.rate_limit_store {
+            Some(config) => {
+                store::from_configuration(Some(config.redis.clone()), None, "rate limit").await?
+            }
+            None => None,
+        }
+        .unwrap_or_else(|| Arc::new(InMemoryStore::new(None)));
+
         let rate_limit_router = init
             .config
             .router
@@ -250,6 +375,8 @@ impl Plugin for TrafficShaping {
                     Ok(RateLimitLayer::new(
                         router_rate_limit_conf.capacity,
                         router_rate_limit_conf.interval,
+                        rate_limit_store.clone(),
+                        String::from("rate_limit:router"),
                     ))
                 }
             })
@@ -259,7 +386,8 @@ impl Plugin for TrafficShaping {
             Ok(Self {
                 config: init.config,
                 rate_limit_router,
-                rate_limit_subgraphs: Mutex::new(HashMap::new()),
+                rate_limit_store,
+                retry_after_subgraphs: Mutex::new(HashMap::new()),
             })
         }
     }
@@ -302,17 +430,34 @@ impl TrafficShaping {
             + 'static,
         <S as Service<supergraph::Request>>::Future: std::marker::Send,
     {
+        let timeout = self
+            .config
+            .router
+            .as_ref()
+            .and_then(|r| r.timeout)
+            .unwrap_or(DEFAULT_TIMEOUT);
+        let timeout_response = self.timeout_response();
         ServiceBuilder::new()
             .map_future_with_request_data(
-                |req: &supergraph::Request| req.context.clone(),
-                move |ctx, future| {
-                    async {
+                |req: &supergraph::Request| (req.context.clone(), Instant::now()),
+                move |(ctx, start): (Context, Instant), future| {
+                    async move {
                         let response: Result<supergraph::Response, BoxError> = future.await;
                         match response {
                             Err(error) if error.is::<Elapsed>() => {
+                                u64_counter!(
+                                    "apollo.router.operations.timeout",
+                                    "Number of requests that timed out, per stage",
+                                    1u64,
+                                    "stage" = "router"
+                                );
                                 supergraph::Response::error_builder()
-                                    .status_code(StatusCode::GATEWAY_TIMEOUT)
-                                    .error::<graphql::Error>(Elapsed::new().into())
+                                    .status_code(timeout_response.status_code.into())
+                                    .error(timeout_response.error(
+                                        "router",
+                                        timeout,
+                                        start.elapsed(),
+                                    ))
                                     .context(ctx)
                                     .build()
                             }
@@ -323,19 +468,17 @@ impl TrafficShaping {
                                     .context(ctx)
                                     .build()
                             }
+                            Ok(mut response) => {
+                                ClientRetryAfter::apply(&ctx, &mut response.response);
+                                Ok(response)
+                            }
                             _ => response,
                         }
                     }
                     .boxed()
                 },
             )
-            .layer(TimeoutLayer::new(
-                self.config
-                    .router
-                    .as_ref()
-                    .and_then(|r| r.timeout)
-                    .unwrap_or(DEFAULT_TIMEOUT),
-            ))
+            .layer(TimeoutLayer::new(timeout))
             .option_layer(self.rate_limit_router.clone())
             .service(service)
     }
@@ -372,14 +515,12 @@ impl TrafficShaping {
                 .global_rate_limit
                 .as_ref()
                 .map(|rate_limit_conf| {
-                    self.rate_limit_subgraphs
-                        .lock()
-                        .unwrap()
-                        .entry(name.to_string())
-                        .or_insert_with(|| {
-                            RateLimitLayer::new(rate_limit_conf.capacity, rate_limit_conf.interval)
-                        })
-                        .clone()
+                    RateLimitLayer::new(
+                        rate_limit_conf.capacity,
+                        rate_limit_conf.interval,
+                        self.rate_limit_store.clone(),
+                        format!("rate_limit:subgraph:{name}"),
+                    )
                 });
 
             let retry = config.shaping.experimental_retry.as_ref().map(|config| {
@@ -388,26 +529,50 @@ impl TrafficShaping {
                     config.min_per_sec,
                     config.retry_percent,
                     config.retry_mutations,
+                    config.min_backoff,
+                    config.max_backoff,
+                    config.max_attempts,
                     name.to_string(),
                 );
                 tower::retry::RetryLayer::new(retry_policy)
             });
 
+            let retry_after = config.shaping.retry_after.map(|retry_after_conf| {
+                self.retry_after_subgraphs
+                    .lock()
+                    .unwrap()
+                    .entry(name.to_string())
+                    .or_insert_with(|| RetryAfterLayer::new(retry_after_conf, name.to_string()))
+                    .clone()
+            });
+
+            let timeout = config.shaping.timeout.unwrap_or(DEFAULT_TIMEOUT);
+            let timeout_response = self.timeout_response();
+            let subgraph_name = name.to_string();
+
             Either::A(ServiceBuilder::new()
 
                 .option_layer(config.shaping.deduplicate_query.unwrap_or_default().then(
                   QueryDeduplicationLayer::default
                 ))
                     .map_future_with_request_data(
-                        |req: &subgraph::Request| req.context.clone(),
-                        move |ctx, future| {
-                            async {
+                        |req: &subgraph::Request| (req.context.clone(), Instant::now()),
+                        move |(ctx, start): (Context, Instant), future| {
+                            let subgraph_name = subgraph_name.clone();
+                            async move {
                                 let response: Result<subgraph::Response, BoxError> = future.await;
                                 match response {
                                     Err(error) if error.is::<Elapsed>() => {
+                                        u64_counter!(
+                                            "apollo.router.operations.timeout",
+                                            "Number of requests that timed out, per stage",
+                                            1u64,
+                                            "stage" = "subgraph",
+                                            "subgraph.name" = subgraph_name
+                                        );
                                         subgraph::Response::error_builder()
                                             .status_code(StatusCode::GATEWAY_TIMEOUT)
-                                            .error::<graphql::Error>(Elapsed::new().into())
+                                            .error(timeout_response.error("subgraph", timeout, start.elapsed()))
                                             .context(ctx)
                                             .build()
                                     }
@@ -423,16 +588,17 @@ impl TrafficShaping {
                             }.boxed()
                         },
                     )
-                    .layer(TimeoutLayer::new(
-                        config.shaping
-                        .timeout
-                        .unwrap_or(DEFAULT_TIMEOUT),
-                    ))
+                    .option_layer(retry_after)
+                    .layer(TimeoutLayer::new(timeout))
                     .option_layer(retry)
                     .option_layer(rate_limit)
                 .service(service)
                 .map_request(move |mut req: SubgraphRequest| {
-                    if let Some(compression) = config.shaping.compression {
+                    if let Some(compression) = config
+                        .shaping
+                        .compression
+                        .filter(|_| exceeds_compression_threshold(&req, config.shaping.compression_threshold))
+                    {
                         let compression_header_val = HeaderValue::from_str(&compression.to_string()).expect("compression is manually implemented and already have the right values; qed");
                         req.subgraph_request.headers_mut().insert(CONTENT_ENCODING, compression_header_val);
                     }
@@ -444,6 +610,14 @@ impl TrafficShaping {
         }
     }
 
+    fn timeout_response(&self) -> TimeoutResponseConf {
+        self.config
+            .router
+            .as_ref()
+            .and_then(|r| r.timeout_response)
+            .unwrap_or_default()
+    }
+
     pub(crate) fn enable_subgraph_http2(&self, service_name: &str) -> Http2Config {
         Self::merge_config(
             self.config.all.as_ref(),
@@ -452,6 +626,72 @@ impl TrafficShaping {
         .and_then(|config| config.shaping.experimental_http2)
         .unwrap_or(Http2Config::Enable)
     }
+
+    pub(crate) fn enable_subgraph_connection_warmup(&self, service_name: &str) -> bool {
+        Self::merge_config(
+            self.config.all.as_ref(),
+            self.config.subgraphs.get(service_name),
+        )
+        .and_then(|config| config.shaping.experimental_connection_warmup)
+        .unwrap_or_default()
+    }
+
+    pub(crate) fn subgraph_pool_idle_timeout(&self, service_name: &str) -> Option<Duration> {
+        Self::merge_config(
+            self.config.all.as_ref(),
+            self.config.subgraphs.get(service_name),
+        )
+        .and_then(|config| config.shaping.experimental_pool_idle_timeout)
+    }
+
+    pub(crate) fn subgraph_proxy(&self, service_name: &str) -> Option<ProxyConfig> {
+        Self::merge_config(
+            self.config.all.as_ref(),
+            self.config.subgraphs.get(service_name),
+        )
+        .and_then(|config| config.shaping.proxy)
+    }
+
+    pub(crate) fn subgraph_dns(&self, service_name: &str) -> DnsConfig {
+        Self::merge_config(
+            self.config.all.as_ref(),
+            self.config.subgraphs.get(service_name),
+        )
+        .and_then(|config| config.shaping.dns)
+        .unwrap_or_default()
+    }
+}
+
+/// Returns true if the request should be compressed: without a threshold, all requests are
+/// compressed, otherwise only entity fetches with large representations are
+fn exceeds_compression_threshold(request: &SubgraphRequest, threshold: Option<ByteSize>) -> bool {
+    let Some(threshold) = threshold else {
+        return true;
+    };
+    let Some(representations) = request
+        .subgraph_request
+        .body()
+        .variables
+        .get("representations")
+    else {
+        return false;
+    };
+    // count the serialized bytes without allocating, representations can reach megabytes
+    let mut counter = ByteCounter(0);
+    serde_json::to_writer(&mut counter, representations).is_ok() && counter.0 > threshold.as_u64()
+}
+
+struct ByteCounter(u64);
+
+impl io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
 }
 
 register_plugin!("apollo", "traffic_shaping", TrafficShaping);
@@ -603,7 +843,9 @@ mod test {
         let supergraph_creator = builder.build().await.expect("should build");
 
         RouterCreator::new(
-            QueryAnalysisLayer::new(supergraph_creator.schema(), Default::default()).await,
+            QueryAnalysisLayer::new(supergraph_creator.schema(), Default::default())
+                .await
+                .unwrap(),
             Arc::new(PersistedQueryLayer::new(&Default::default()).await.unwrap()),
             Arc::new(supergraph_creator),
             Arc::new(Configuration::default()),
@@ -674,6 +916,65 @@ mod test {
             .unwrap();
     }
 
+    #[tokio::test]
+    async fn it_compresses_only_large_representations() {
+        let config = serde_yaml::from_str::<serde_json::Value>(
+            r#"
+        subgraphs:
+            test:
+                compression: gzip
+                compression_threshold: 50 B
+        "#,
+        )
+        .unwrap();
+        let plugin = get_traffic_shaping_plugin(&config).await;
+
+        let representations = |count: usize| {
+            crate::graphql::Request::fake_builder()
+                .query("query($representations:[_Any!]!){_entities(representations:$representations){...on Product{name}}}")
+                .variable(
+                    "representations",
+                    (0..count)
+                        .map(|upc| json!({"__typename": "Product", "upc": upc.to_string()}))
+                        .collect::<Vec<_>>(),
+                )
+                .build()
+        };
+        for (request, compressed) in [
+            (
+                crate::graphql::Request::fake_builder()
+                    .query("{ me { name } }")
+                    .build(),
+                false,
+            ),
+            (representations(1), false),
+            (representations(10), true),
+        ] {
+            let request = SubgraphRequest::fake_builder()
+                .subgraph_request(http::Request::new(request))
+                .build();
+            let test_service =
+                MockSubgraph::new(HashMap::new()).map_request(move |req: SubgraphRequest| {
+                    assert_eq!(
+                        req.subgraph_request
+                            .headers()
+                            .contains_key(&CONTENT_ENCODING),
+                        compressed
+                    );
+                    req
+                });
+
+            let _response = plugin
+                .as_any()
+                .downcast_ref::<TrafficShaping>()
+                .unwrap()
+                .subgraph_service_internal("test", test_service)
+                .oneshot(request)
+                .await
+                .unwrap();
+        }
+    }
+
     #[test]
     fn test_merge_config() {
         let config = serde_yaml::from_str::<Config>(
@@ -704,6 +1005,35 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_merge_retry_config() {
+        let config = serde_yaml::from_str::<Config>(
+            r#"
+        all:
+          experimental_retry:
+            retry_percent: 0.5
+            min_backoff: 10ms
+            max_attempts: 2
+        subgraphs:
+          products:
+            experimental_retry:
+              max_attempts: 5
+        "#,
+        )
+        .unwrap();
+
+        let merged =
+            TrafficShaping::merge_config(config.all.as_ref(), config.subgraphs.get("products"))
+                .unwrap()
+                .shaping
+                .experimental_retry
+                .unwrap();
+
+        assert_eq!(merged.retry_percent, Some(0.5));
+        assert_eq!(merged.min_backoff, Some(Duration::from_millis(10)));
+        assert_eq!(merged.max_attempts, Some(5));
+    }
+
     #[test]
     fn test_merge_http2_all() {
         let config = serde_yaml::from_str::<Config>(
@@ -773,6 +1103,35 @@ mod test {
         assert!(shaping_config.enable_subgraph_http2("this_doesnt_exist") == Http2Config::Disable);
     }
 
+    #[tokio::test]
+    async fn test_subgraph_proxy() {
+        let config = serde_yaml::from_str::<Config>(
+            r#"
+        all:
+          proxy:
+            url: http://proxy.example.com:3128
+        subgraphs:
+          products:
+            proxy:
+              url: socks5://proxy.example.com
+              no_proxy:
+                - internal.example.com
+        "#,
+        )
+        .unwrap();
+
+        let shaping_config = TrafficShaping::new(PluginInit::fake_builder().config(config).build())
+            .await
+            .unwrap();
+
+        let all = shaping_config.subgraph_proxy("reviews");
+        let products = shaping_config.subgraph_proxy("products");
+        assert!(all.is_some());
+        assert!(products.is_some());
+        assert_ne!(all, products);
+        assert_eq!(all, shaping_config.subgraph_proxy("this_doesnt_exist"));
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn it_rate_limit_subgraph_requests() {
         let config = serde_yaml::from_str::<serde_json::Value>(
@@ -849,6 +1208,48 @@ mod test {
             .is_empty());
     }
 
+    #[tokio::test]
+    async fn it_reports_router_timeouts() {
+        let config = serde_yaml::from_str::<serde_json::Value>(
+            r#"
+        router:
+            timeout: 10ms
+            timeout_response:
+                status_code: ok
+                include_details: true
+        "#,
+        )
+        .unwrap();
+
+        let plugin = get_traffic_shaping_plugin(&config).await;
+        let service = tower::service_fn(|_request: SupergraphRequest| async {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            SupergraphResponse::fake_builder().build()
+        });
+
+        let mut response = plugin
+            .as_any()
+            .downcast_ref::<TrafficShaping>()
+            .unwrap()
+            .supergraph_service_internal(service)
+            .oneshot(SupergraphRequest::fake_builder().build().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.response.status(), StatusCode::OK);
+
+        let error = response.next_response().await.unwrap().errors.remove(0);
+        assert_eq!(error.extensions.get("code").unwrap(), "REQUEST_TIMEOUT");
+        let timeout = error
+            .extensions
+            .get("timeout")
+            .unwrap()
+            .as_object()
+            .unwrap();
+        assert_eq!(timeout.get("stage").unwrap(), "router");
+        assert_eq!(timeout.get("budgetMs").unwrap(), &json!(10));
+        assert!(timeout.get("elapsedMs").unwrap().as_u64().unwrap() >= 10);
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn it_rate_limit_router_requests() {
         let config = serde_yaml::from_str::<serde_json::Value>(
@@ -1,39 +1,32 @@
 use std::num::NonZeroU64;
-use std::sync::atomic::AtomicU64;
-use std::sync::atomic::AtomicUsize;
 use std::sync::Arc;
+use std::sync::Mutex;
 use std::time::Duration;
-use std::time::SystemTime;
-use std::time::UNIX_EPOCH;
 
 use tower::Layer;
 
+use super::service::State;
 use super::Rate;
 use super::RateLimit;
+use crate::cache::store::Store;
+
 /// Enforces a rate limit on the number of requests the underlying
 /// service can handle over a period of time.
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub(crate) struct RateLimitLayer {
     rate: Rate,
-    window_start: Arc<AtomicU64>,
-    previous_nb_requests: Arc<AtomicUsize>,
-    current_nb_requests: Arc<AtomicUsize>,
+    store: Arc<dyn Store>,
+    key: Arc<String>,
 }
 
 impl RateLimitLayer {
-    /// Create new rate limit layer.
-    pub(crate) fn new(num: NonZeroU64, per: Duration) -> Self {
+    /// Create new rate limit layer, counting the requests under the key prefix in the store.
+    pub(crate) fn new(num: NonZeroU64, per: Duration, store: Arc<dyn Store>, key: String) -> Self {
         let rate = Rate::new(num, per);
         RateLimitLayer {
             rate,
-            window_start: Arc::new(AtomicU64::new(
-                SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .expect("system time must be after EPOCH")
-                    .as_millis() as u64,
-            )),
-            previous_nb_requests: Arc::default(),
-            current_nb_requests: Arc::new(AtomicUsize::new(1)),
+            store,
+            key: Arc::new(key),
         }
     }
 }
@@ -45,9 +38,9 @@ impl<S> Layer<S> for RateLimitLayer {
         RateLimit {
             inner: service,
             rate: self.rate,
-            window_start: self.window_start.clone(),
-            previous_nb_requests: self.previous_nb_requests.clone(),
-            current_nb_requests: self.current_nb_requests.clone(),
+            store: self.store.clone(),
+            key: self.key.clone(),
+            state: Mutex::new(State::Idle),
         }
     }
 }
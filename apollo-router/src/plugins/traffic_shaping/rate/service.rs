@@ -1,29 +1,52 @@
-use std::sync::atomic::AtomicU64;
-use std::sync::atomic::AtomicUsize;
-use std::sync::atomic::Ordering;
+use std::future::Future;
 use std::sync::Arc;
+use std::sync::Mutex;
 use std::task::Context;
 use std::task::Poll;
 use std::time::SystemTime;
 use std::time::UNIX_EPOCH;
 
+use futures::future::BoxFuture;
 use futures::ready;
+use futures::FutureExt;
+use tower::BoxError;
 use tower::Service;
 
 use super::future::ResponseFuture;
 use super::Rate;
+use crate::cache::store::Store;
 use crate::plugins::traffic_shaping::rate::error::RateLimited;
 
-#[derive(Debug, Clone)]
+/// Sliding window rate limit. The requests are counted in the store per window of the interval
+/// of the rate, and the number of requests of the last interval is estimated from the counts of
+/// the current and previous windows
 pub(crate) struct RateLimit<T> {
     pub(crate) inner: T,
     pub(crate) rate: Rate,
-    /// We're using an atomic u64 because it's basically a timestamp in milliseconds for the start of the window
-    /// Instead of using an Instant which is not thread safe we're using an atomic u64
-    /// It's ok to have an u64 because we just care about milliseconds for this use case
-    pub(crate) window_start: Arc<AtomicU64>,
-    pub(crate) previous_nb_requests: Arc<AtomicUsize>,
-    pub(crate) current_nb_requests: Arc<AtomicUsize>,
+    pub(crate) store: Arc<dyn Store>,
+    /// Prefix of the keys of the counters in the store
+    pub(crate) key: Arc<String>,
+    /// Started by `poll_ready`, and reset once the request is called.
+    /// The mutex is never locked, it only makes the service `Sync`
+    pub(crate) state: Mutex<State>,
+}
+
+pub(crate) enum State {
+    Idle,
+    Checking(BoxFuture<'static, Result<bool, BoxError>>),
+    Allowed,
+}
+
+impl<T: Clone> Clone for RateLimit<T> {
+    fn clone(&self) -> Self {
+        RateLimit {
+            inner: self.inner.clone(),
+            rate: self.rate,
+            store: self.store.clone(),
+            key: self.key.clone(),
+            state: Mutex::new(State::Idle),
+        }
+    }
 }
 
 impl<S, Request> Service<Request> for RateLimit<S>
@@ -36,48 +59,114 @@ where
     type Future = ResponseFuture<S::Future>;
 
     fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        let time_unit = self.rate.per().as_millis() as u64;
-
-        let updated =
-            self.window_start
-                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |window_start| {
-                    let duration_now = SystemTime::now()
-                        .duration_since(UNIX_EPOCH)
-                        .expect("system time must be after EPOCH")
-                        .as_millis() as u64;
-                    if duration_now - window_start > self.rate.per().as_millis() as u64 {
-                        Some(duration_now)
-                    } else {
-                        None
+        let state = self
+            .state
+            .get_mut()
+            .expect("the rate limit state is never locked");
+        loop {
+            match state {
+                State::Idle => {
+                    let store = self.store.clone();
+                    let key = self.key.clone();
+                    let rate = self.rate;
+                    *state = State::Checking(
+                        async move { is_limited(store.as_ref(), &key, rate).await }.boxed(),
+                    );
+                }
+                State::Checking(check) => {
+                    let limited = ready!(check.as_mut().poll(cx));
+                    match limited {
+                        Ok(true) => {
+                            *state = State::Idle;
+                            tracing::trace!("rate limit exceeded; sleeping.");
+                            return Poll::Ready(Err(RateLimited::new().into()));
+                        }
+                        Ok(false) => {}
+                        // an unavailable store must not take the router down with it
+                        Err(e) => tracing::error!(
+                            store = self.store.name(),
+                            error = %e,
+                            "could not count the request for the rate limit, letting it through"
+                        ),
                     }
-                });
-        // If it has been updated
-        if let Ok(_updated_window_start) = updated {
-            self.previous_nb_requests.swap(
-                self.current_nb_requests.load(Ordering::SeqCst),
-                Ordering::SeqCst,
-            );
-            self.current_nb_requests.swap(1, Ordering::SeqCst);
+                    *state = State::Allowed;
+                }
+                State::Allowed => {
+                    return Poll::Ready(ready!(self.inner.poll_ready(cx)).map_err(Into::into))
+                }
+            }
         }
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        *self
+            .state
+            .get_mut()
+            .expect("the rate limit state is never locked") = State::Idle;
+        ResponseFuture::new(self.inner.call(request))
+    }
+}
 
-        let estimated_cap = (self.previous_nb_requests.load(Ordering::SeqCst)
-            * (time_unit
-                .checked_sub(self.window_start.load(Ordering::SeqCst))
-                .unwrap_or_default()
-                / time_unit) as usize)
-            + self.current_nb_requests.load(Ordering::SeqCst);
+/// Returns true if the request exceeds the rate, and counts it otherwise
+async fn is_limited(store: &dyn Store, key: &str, rate: Rate) -> Result<bool, BoxError> {
+    let time_unit = rate.per().as_millis().max(1) as u64;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time must be after EPOCH")
+        .as_millis() as u64;
+    let window = now / time_unit;
+    let current_key = format!("{key}:{window}");
+    let previous_key = format!("{key}:{}", window.saturating_sub(1));
 
-        if estimated_cap as u64 > self.rate.num() {
-            tracing::trace!("rate limit exceeded; sleeping.");
-            return Poll::Ready(Err(RateLimited::new().into()));
-        }
+    let counts = store
+        .get_multiple(&[previous_key, current_key.clone()])
+        .await?
+        .into_iter()
+        .map(|count| match count {
+            Some(count) => Ok(std::str::from_utf8(&count)?.parse::<u64>()?),
+            None => Ok(0),
+        })
+        .collect::<Result<Vec<_>, BoxError>>()?;
+    let (previous, current) = (counts[0], counts[1]);
+
+    if estimated_requests(previous, current, now % time_unit, time_unit) >= rate.num() as f64 {
+        return Ok(true);
+    }
+    // the counter of the current window is still read as the previous one during the next window
+    store.increment(&current_key, rate.per() * 2).await?;
+    Ok(false)
+}
 
-        self.current_nb_requests.fetch_add(1, Ordering::SeqCst);
+/// Estimates the number of requests in the last interval, considering that the requests of the
+/// previous window were evenly spread
+fn estimated_requests(previous: u64, current: u64, elapsed: u64, time_unit: u64) -> f64 {
+    let previous_weight = time_unit.saturating_sub(elapsed) as f64 / time_unit as f64;
+    previous as f64 * previous_weight + current as f64
+}
 
-        Poll::Ready(ready!(self.inner.poll_ready(cx)).map_err(Into::into))
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU64;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::cache::store::InMemoryStore;
+
+    #[test]
+    fn it_weighs_the_previous_window_by_its_overlap_with_the_interval() {
+        assert_eq!(estimated_requests(10, 2, 0, 100), 12.0);
+        assert_eq!(estimated_requests(10, 2, 25, 100), 9.5);
+        assert_eq!(estimated_requests(10, 2, 99, 100), 2.1);
     }
 
-    fn call(&mut self, request: Request) -> Self::Future {
-        ResponseFuture::new(self.inner.call(request))
+    #[tokio::test]
+    async fn it_counts_only_the_requests_let_through() {
+        let store: Arc<dyn Store> = Arc::new(InMemoryStore::new(None));
+        let rate = Rate::new(NonZeroU64::new(2).unwrap(), Duration::from_secs(60));
+        let mut limited = Vec::new();
+        for _ in 0..4 {
+            limited.push(is_limited(store.as_ref(), "test", rate).await.unwrap());
+        }
+        assert_eq!(limited, [false, false, true, true]);
     }
 }
@@ -1,28 +1,43 @@
-use std::future;
 use std::sync::Arc;
 use std::time::Duration;
 
+use futures::future::BoxFuture;
+use futures::FutureExt;
 use tower::retry::budget::Budget;
 use tower::retry::Policy;
 
+use crate::context::OPERATION_KIND;
 use crate::query_planner::OperationKind;
 use crate::services::subgraph;
 
+const DEFAULT_MIN_BACKOFF: Duration = Duration::from_millis(0);
+const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(1);
+const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+
 #[derive(Clone, Default)]
 pub(crate) struct RetryPolicy {
     budget: Arc<Budget>,
     retry_mutations: bool,
+    min_backoff: Duration,
+    max_backoff: Duration,
+    max_attempts: u32,
+    attempts: u32,
     subgraph_name: String,
 }
 
 impl RetryPolicy {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         duration: Option<Duration>,
         min_per_sec: Option<u32>,
         retry_percent: Option<f32>,
         retry_mutations: Option<bool>,
+        min_backoff: Option<Duration>,
+        max_backoff: Option<Duration>,
+        max_attempts: Option<u32>,
         subgraph_name: String,
     ) -> Self {
+        let min_backoff = min_backoff.unwrap_or(DEFAULT_MIN_BACKOFF);
         Self {
             budget: Arc::new(Budget::new(
                 duration.unwrap_or_else(|| Duration::from_secs(10)),
@@ -30,13 +45,44 @@ impl RetryPolicy {
                 retry_percent.unwrap_or(0.2),
             )),
             retry_mutations: retry_mutations.unwrap_or(false),
+            min_backoff,
+            max_backoff: max_backoff.unwrap_or(DEFAULT_MAX_BACKOFF).max(min_backoff),
+            max_attempts: max_attempts.unwrap_or(DEFAULT_MAX_ATTEMPTS),
+            attempts: 0,
             subgraph_name,
         }
     }
+
+    /// A fetch is only considered idempotent if it is a query fetch that belongs to a query
+    /// operation: fetches issued for a mutation operation (including the entity fetches that
+    /// follow a mutation in a sequence) are never retried unless mutation retries are enabled.
+    fn is_idempotent(&self, req: &subgraph::Request) -> bool {
+        if self.retry_mutations {
+            return req.operation_kind != OperationKind::Subscription;
+        }
+
+        if req.operation_kind != OperationKind::Query {
+            return false;
+        }
+
+        // if the operation kind is missing from the context, we cannot know if this fetch
+        // happens after a mutation, so we do not retry it
+        matches!(
+            req.context.get::<_, OperationKind>(OPERATION_KIND),
+            Ok(Some(OperationKind::Query))
+        )
+    }
+
+    /// Exponential backoff starting at `min_backoff` and capped at `max_backoff`
+    fn backoff(&self) -> Duration {
+        self.min_backoff
+            .saturating_mul(1 << self.attempts.min(31))
+            .min(self.max_backoff)
+    }
 }
 
 impl<Res, E> Policy<subgraph::Request, Res, E> for RetryPolicy {
-    type Future = future::Ready<Self>;
+    type Future = BoxFuture<'static, Self>;
 
     fn retry(&self, req: &subgraph::Request, result: Result<&Res, &E>) -> Option<Self::Future> {
         match result {
@@ -47,7 +93,17 @@ impl<Res, E> Policy<subgraph::Request, Res, E> for RetryPolicy {
                 None
             }
             Err(_e) => {
-                if req.operation_kind == OperationKind::Mutation && !self.retry_mutations {
+                if !self.is_idempotent(req) {
+                    return None;
+                }
+
+                if self.attempts >= self.max_attempts {
+                    tracing::info!(
+                        monotonic_counter.apollo_router_http_request_retry_total = 1u64,
+                        status = "exhausted",
+                        subgraph = %self.subgraph_name,
+                    );
+
                     return None;
                 }
 
@@ -67,7 +123,19 @@ impl<Res, E> Policy<subgraph::Request, Res, E> for RetryPolicy {
                     subgraph = %self.subgraph_name,
                 );
 
-                Some(future::ready(self.clone()))
+                let backoff = self.backoff();
+                let mut next = self.clone();
+                next.attempts += 1;
+
+                Some(
+                    async move {
+                        if !backoff.is_zero() {
+                            tokio::time::sleep(backoff).await;
+                        }
+                        next
+                    }
+                    .boxed(),
+                )
             }
         }
     }
@@ -76,3 +144,60 @@ impl<Res, E> Policy<subgraph::Request, Res, E> for RetryPolicy {
         Some(req.clone())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Context;
+
+    fn request(operation_kind: OperationKind, supergraph_kind: OperationKind) -> subgraph::Request {
+        let context = Context::new();
+        context.insert(OPERATION_KIND, supergraph_kind).unwrap();
+        subgraph::Request::fake_builder()
+            .operation_kind(operation_kind)
+            .context(context)
+            .build()
+    }
+
+    fn policy(retry_mutations: bool) -> RetryPolicy {
+        RetryPolicy::new(
+            None,
+            None,
+            None,
+            Some(retry_mutations),
+            Some(Duration::from_millis(10)),
+            Some(Duration::from_millis(50)),
+            None,
+            "test".to_string(),
+        )
+    }
+
+    #[test]
+    fn it_only_retries_query_fetches_of_query_operations() {
+        let policy = policy(false);
+        assert!(policy.is_idempotent(&request(OperationKind::Query, OperationKind::Query)));
+        assert!(!policy.is_idempotent(&request(OperationKind::Mutation, OperationKind::Mutation)));
+        // entity fetch after a mutation in a sequence
+        assert!(!policy.is_idempotent(&request(OperationKind::Query, OperationKind::Mutation)));
+        assert!(!policy.is_idempotent(&request(
+            OperationKind::Subscription,
+            OperationKind::Subscription
+        )));
+
+        let policy = self::policy(true);
+        assert!(policy.is_idempotent(&request(OperationKind::Query, OperationKind::Mutation)));
+        assert!(policy.is_idempotent(&request(OperationKind::Mutation, OperationKind::Mutation)));
+    }
+
+    #[test]
+    fn it_computes_exponential_backoff() {
+        let mut policy = policy(false);
+        assert_eq!(policy.backoff(), Duration::from_millis(10));
+        policy.attempts = 1;
+        assert_eq!(policy.backoff(), Duration::from_millis(20));
+        policy.attempts = 2;
+        assert_eq!(policy.backoff(), Duration::from_millis(40));
+        policy.attempts = 3;
+        assert_eq!(policy.backoff(), Duration::from_millis(50));
+    }
+}
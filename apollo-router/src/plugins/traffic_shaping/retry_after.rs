@@ -0,0 +1,298 @@
+//! Handle `Retry-After` headers of subgraph `429 Too Many Requests` and `503 Service Unavailable`
+//! responses. Implemented as a tower Layer.
+
+use std::sync::Arc;
+use std::task::Poll;
+use std::time::Duration;
+use std::time::Instant;
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use http::header::RETRY_AFTER;
+use http::HeaderValue;
+use http::StatusCode;
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tower::BoxError;
+use tower::Layer;
+use tower::Service;
+
+use crate::graphql;
+use crate::services::subgraph;
+use crate::Context;
+
+const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// Handling of subgraph `429 Too Many Requests` and `503 Service Unavailable` responses with a
+/// `Retry-After` header
+#[derive(PartialEq, Debug, Clone, Copy, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct RetryAfterConf {
+    /// Respond to the client with the status code and the `Retry-After` header of the subgraph
+    pub(crate) propagate: bool,
+    /// Stop sending requests to the subgraph until the `Retry-After` delay has elapsed, and
+    /// respond with an error instead
+    pub(crate) pause_requests: bool,
+    /// Maximum delay taken from a `Retry-After` header (default: 60s)
+    #[serde(deserialize_with = "humantime_serde::deserialize", default)]
+    #[schemars(with = "String", default)]
+    pub(crate) max_delay: Option<Duration>,
+}
+
+/// Status code and delay sent to the client, the longest delay of all subgraphs is kept
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ClientRetryAfter {
+    status: StatusCode,
+    delay: Duration,
+}
+
+impl ClientRetryAfter {
+    fn record(self, context: &Context) {
+        context.extensions().with_lock(|mut lock| {
+            let longest = match lock.get::<ClientRetryAfter>() {
+                Some(previous) if previous.delay >= self.delay => *previous,
+                _ => self,
+            };
+            lock.insert(longest);
+        });
+    }
+
+    /// Sets the status code and `Retry-After` header of the client response
+    pub(crate) fn apply<T>(context: &Context, response: &mut http::Response<T>) {
+        let Some(retry_after) = context
+            .extensions()
+            .with_lock(|lock| lock.get::<ClientRetryAfter>().copied())
+        else {
+            return;
+        };
+        *response.status_mut() = retry_after.status;
+        response.headers_mut().insert(
+            RETRY_AFTER,
+            HeaderValue::from(retry_after.delay.as_secs_f64().ceil() as u64),
+        );
+    }
+}
+
+/// Reads the delay of a `Retry-After` header. Only delays in seconds are supported, HTTP dates
+/// are ignored
+fn retry_after(response: &subgraph::Response, max_delay: Duration) -> Option<Duration> {
+    let status = response.response.status();
+    if status != StatusCode::TOO_MANY_REQUESTS && status != StatusCode::SERVICE_UNAVAILABLE {
+        return None;
+    }
+    let seconds: u64 = response
+        .response
+        .headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(Duration::from_secs(seconds).min(max_delay))
+}
+
+#[derive(Clone)]
+pub(crate) struct RetryAfterLayer {
+    config: RetryAfterConf,
+    subgraph_name: String,
+    /// end of the pause requested by the subgraph, with the status code it responded with
+    paused_until: Arc<Mutex<Option<(Instant, StatusCode)>>>,
+}
+
+impl RetryAfterLayer {
+    pub(crate) fn new(config: RetryAfterConf, subgraph_name: String) -> Self {
+        Self {
+            config,
+            subgraph_name,
+            paused_until: Default::default(),
+        }
+    }
+}
+
+impl<S> Layer<S> for RetryAfterLayer {
+    type Service = RetryAfterService<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        RetryAfterService {
+            inner: service,
+            layer: self.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct RetryAfterService<S> {
+    inner: S,
+    layer: RetryAfterLayer,
+}
+
+impl<S> Service<subgraph::Request> for RetryAfterService<S>
+where
+    S: Service<subgraph::Request, Response = subgraph::Response, Error = BoxError>,
+    S::Future: Send + 'static,
+{
+    type Response = subgraph::Response;
+    type Error = BoxError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut std::task::Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: subgraph::Request) -> Self::Future {
+        let config = self.layer.config;
+        let now = Instant::now();
+        let paused = *self.layer.paused_until.lock();
+        if let Some((until, status)) = paused.filter(|(until, _)| *until > now) {
+            let delay = until - now;
+            if config.propagate {
+                ClientRetryAfter { status, delay }.record(&request.context);
+            }
+            u64_counter!(
+                "apollo.router.operations.subgraph.paused",
+                "Number of subgraph requests not sent because the subgraph asked to retry later",
+                1u64,
+                "subgraph.name" = self.layer.subgraph_name.clone()
+            );
+            let response = subgraph::Response::error_builder()
+                .status_code(status)
+                .error(
+                    graphql::Error::builder()
+                        .message(format!(
+                            "subgraph '{}' asked to retry after {}s",
+                            self.layer.subgraph_name,
+                            delay.as_secs_f64().ceil() as u64
+                        ))
+                        .extension_code("SUBGRAPH_RETRY_AFTER")
+                        .build(),
+                )
+                .context(request.context)
+                .subgraph_name(self.layer.subgraph_name.clone())
+                .build();
+            return std::future::ready(response).boxed();
+        }
+
+        let paused_until = self.layer.paused_until.clone();
+        let future = self.inner.call(request);
+        async move {
+            let response = future.await?;
+            if let Some(delay) =
+                retry_after(&response, config.max_delay.unwrap_or(DEFAULT_MAX_DELAY))
+            {
+                let status = response.response.status();
+                if config.pause_requests {
+                    *paused_until.lock() = Some((Instant::now() + delay, status));
+                }
+                if config.propagate {
+                    ClientRetryAfter { status, delay }.record(&response.context);
+                }
+            }
+            Ok(response)
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tower::ServiceExt;
+
+    use super::*;
+
+    fn subgraph_service(
+        status: StatusCode,
+        retry_after: &'static str,
+    ) -> impl Service<
+        subgraph::Request,
+        Response = subgraph::Response,
+        Error = BoxError,
+        Future = BoxFuture<'static, Result<subgraph::Response, BoxError>>,
+    > + Clone {
+        tower::service_fn(move |request: subgraph::Request| {
+            async move {
+                let mut response = subgraph::Response::fake_builder()
+                    .status_code(status)
+                    .context(request.context)
+                    .build();
+                response
+                    .response
+                    .headers_mut()
+                    .insert(RETRY_AFTER, HeaderValue::from_static(retry_after));
+                Ok(response)
+            }
+            .boxed()
+        })
+    }
+
+    #[tokio::test]
+    async fn it_propagates_retry_after_to_the_client() {
+        let layer = RetryAfterLayer::new(
+            RetryAfterConf {
+                propagate: true,
+                ..Default::default()
+            },
+            "test".to_string(),
+        );
+        let context = Context::new();
+        let response = layer
+            .layer(subgraph_service(StatusCode::TOO_MANY_REQUESTS, "5"))
+            .oneshot(
+                subgraph::Request::fake_builder()
+                    .context(context.clone())
+                    .build(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        let mut client_response = http::Response::new(());
+        ClientRetryAfter::apply(&context, &mut client_response);
+        assert_eq!(client_response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(client_response.headers().get(RETRY_AFTER).unwrap(), "5");
+    }
+
+    #[tokio::test]
+    async fn it_pauses_requests() {
+        let layer = RetryAfterLayer::new(
+            RetryAfterConf {
+                pause_requests: true,
+                max_delay: Some(Duration::from_millis(100)),
+                ..Default::default()
+            },
+            "test".to_string(),
+        );
+
+        let response = layer
+            .layer(subgraph_service(StatusCode::SERVICE_UNAVAILABLE, "3600"))
+            .oneshot(subgraph::Request::fake_builder().build())
+            .await
+            .unwrap();
+        assert!(response.response.body().errors.is_empty());
+
+        // the subgraph is not called during the pause
+        let response = layer
+            .layer(subgraph_service(StatusCode::OK, "0"))
+            .oneshot(subgraph::Request::fake_builder().build())
+            .await
+            .unwrap();
+        assert_eq!(response.response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            response.response.body().errors[0]
+                .extensions
+                .get("code")
+                .unwrap(),
+            "SUBGRAPH_RETRY_AFTER"
+        );
+
+        // the delay is capped by max_delay
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        let response = layer
+            .layer(subgraph_service(StatusCode::OK, "0"))
+            .oneshot(subgraph::Request::fake_builder().build())
+            .await
+            .unwrap();
+        assert_eq!(response.response.status(), StatusCode::OK);
+    }
+}
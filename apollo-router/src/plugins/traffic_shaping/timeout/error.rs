@@ -2,6 +2,9 @@
 
 use std::error;
 use std::fmt;
+use std::time::Duration;
+
+use serde_json_bytes::json;
 
 use crate::graphql;
 
@@ -16,6 +19,36 @@ impl Elapsed {
     }
 }
 
+impl Elapsed {
+    /// Converts the error to a GraphQL error
+    pub(crate) fn to_graphql_error(&self) -> graphql::Error {
+        graphql::Error::builder()
+            .message(String::from("Request timed out"))
+            .extension_code("REQUEST_TIMEOUT")
+            .build()
+    }
+
+    /// Converts the error to a GraphQL error with a `timeout` extension, describing which stage
+    /// of the request timed out, its budget and how long it ran
+    pub(crate) fn to_graphql_error_with_details(
+        &self,
+        stage: &str,
+        budget: Duration,
+        elapsed: Duration,
+    ) -> graphql::Error {
+        let mut error = self.to_graphql_error();
+        error.extensions.insert(
+            "timeout",
+            json!({
+                "stage": stage,
+                "budgetMs": budget.as_millis() as u64,
+                "elapsedMs": elapsed.as_millis() as u64,
+            }),
+        );
+        error
+    }
+}
+
 impl fmt::Display for Elapsed {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.pad("request timed out")
@@ -23,12 +56,34 @@ impl fmt::Display for Elapsed {
 }
 
 impl From<Elapsed> for graphql::Error {
-    fn from(_: Elapsed) -> Self {
-        graphql::Error::builder()
-            .message(String::from("Request timed out"))
-            .extension_code("REQUEST_TIMEOUT")
-            .build()
+    fn from(elapsed: Elapsed) -> Self {
+        elapsed.to_graphql_error()
     }
 }
 
 impl error::Error for Elapsed {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_adds_timeout_details() {
+        let error = Elapsed::new().to_graphql_error_with_details(
+            "subgraph",
+            Duration::from_secs(1),
+            Duration::from_millis(1003),
+        );
+        assert_eq!(
+            serde_json_bytes::Value::Object(error.extensions),
+            json!({
+                "code": "REQUEST_TIMEOUT",
+                "timeout": {
+                    "stage": "subgraph",
+                    "budgetMs": 1000,
+                    "elapsedMs": 1003,
+                }
+            })
+        );
+    }
+}
@@ -0,0 +1,149 @@
+//! Incremental delivery of deferred responses for clients that cannot parse `multipart/mixed`
+//!
+//! The payloads are the same as the multipart ones, sent either as the elements of a single JSON
+//! array or as JSON Lines.
+
+use std::pin::Pin;
+use std::task::Poll;
+
+use bytes::Bytes;
+use futures::Stream;
+use futures::StreamExt;
+
+use crate::graphql;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum JsonStreamMode {
+    /// All payloads are elements of one JSON array
+    Array,
+    /// One payload per line
+    Lines,
+}
+
+pub(crate) struct JsonStream {
+    stream: Pin<Box<dyn Stream<Item = graphql::Response> + Send>>,
+    is_first_chunk: bool,
+    is_terminated: bool,
+    mode: JsonStreamMode,
+}
+
+impl JsonStream {
+    pub(crate) fn new<S>(stream: S, mode: JsonStreamMode) -> Self
+    where
+        S: Stream<Item = graphql::Response> + Send + 'static,
+    {
+        Self {
+            stream: stream.boxed(),
+            is_first_chunk: true,
+            is_terminated: false,
+            mode,
+        }
+    }
+}
+
+impl Stream for JsonStream {
+    type Item = Result<Bytes, serde_json::Error>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        if self.is_terminated {
+            return Poll::Ready(None);
+        }
+        match self.stream.as_mut().poll_next(cx) {
+            Poll::Ready(Some(response)) => {
+                let mut buf = Vec::new();
+                if self.mode == JsonStreamMode::Array {
+                    buf.push(if self.is_first_chunk { b'[' } else { b',' });
+                }
+                self.is_first_chunk = false;
+                serde_json::to_writer(&mut buf, &response)?;
+
+                let is_still_open = response.has_next.unwrap_or(false);
+                match self.mode {
+                    JsonStreamMode::Array if !is_still_open => buf.push(b']'),
+                    JsonStreamMode::Array => {}
+                    JsonStreamMode::Lines => buf.push(b'\n'),
+                }
+                if !is_still_open {
+                    self.is_terminated = true;
+                }
+
+                Poll::Ready(Some(Ok(buf.into())))
+            }
+            Poll::Ready(None) => {
+                // the stream ended without a payload with `hasNext: false`
+                self.is_terminated = true;
+                match self.mode {
+                    JsonStreamMode::Array if self.is_first_chunk => {
+                        Poll::Ready(Some(Ok(Bytes::from_static(b"[]"))))
+                    }
+                    JsonStreamMode::Array => Poll::Ready(Some(Ok(Bytes::from_static(b"]")))),
+                    JsonStreamMode::Lines => Poll::Ready(None),
+                }
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::stream;
+
+    use super::*;
+
+    fn responses() -> Vec<graphql::Response> {
+        vec![
+            graphql::Response::builder()
+                .data(serde_json_bytes::json!({"me": {"id": "1"}}))
+                .has_next(true)
+                .build(),
+            graphql::Response::builder().has_next(false).build(),
+        ]
+    }
+
+    async fn collect(stream: JsonStream) -> String {
+        let chunks: Vec<_> = stream.map(|chunk| chunk.unwrap()).collect().await;
+        String::from_utf8(chunks.concat()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn it_sends_a_json_array() {
+        let body = collect(JsonStream::new(
+            stream::iter(responses()),
+            JsonStreamMode::Array,
+        ))
+        .await;
+        assert_eq!(
+            body,
+            r#"[{"data":{"me":{"id":"1"}},"hasNext":true},{"hasNext":false}]"#
+        );
+        let payloads: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(payloads.as_array().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn it_sends_json_lines() {
+        let body = collect(JsonStream::new(
+            stream::iter(responses()),
+            JsonStreamMode::Lines,
+        ))
+        .await;
+        assert_eq!(
+            body,
+            "{\"data\":{\"me\":{\"id\":\"1\"}},\"hasNext\":true}\n{\"hasNext\":false}\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn it_closes_the_array_when_the_stream_ends() {
+        let body = collect(JsonStream::new(
+            stream::iter(responses().into_iter().take(1)),
+            JsonStreamMode::Array,
+        ))
+        .await;
+        assert_eq!(body, r#"[{"data":{"me":{"id":"1"}},"hasNext":true}]"#);
+    }
+}
@@ -1,2 +1,3 @@
+pub(crate) mod json_stream;
 pub(crate) mod multipart;
 pub(crate) mod websocket;
@@ -1,6 +1,7 @@
 use std::pin::Pin;
 use std::task::Poll;
 use std::time::Duration;
+use std::time::Instant;
 
 use bytes::Bytes;
 use futures::stream::select;
@@ -49,6 +50,61 @@ pub(crate) struct Multipart {
     is_first_chunk: bool,
     is_terminated: bool,
     mode: ProtocolMode,
+    defer_metrics: Option<DeferMetrics>,
+}
+
+/// Latency and size of the payloads of a deferred response
+pub(crate) struct DeferMetrics {
+    operation_name: String,
+    start: Instant,
+    patches: u64,
+}
+
+impl DeferMetrics {
+    pub(crate) fn new(operation_name: String, start: Instant) -> Self {
+        Self {
+            operation_name,
+            start,
+            patches: 0,
+        }
+    }
+
+    fn record(&mut self, is_initial_payload: bool, size: usize) {
+        let elapsed = self.start.elapsed().as_secs_f64();
+        if is_initial_payload {
+            f64_histogram!(
+                "apollo.router.operations.defer.initial_payload.duration",
+                "Time from the start of the request until the initial payload of a deferred response is sent",
+                elapsed,
+                "graphql.operation.name" = self.operation_name.clone()
+            );
+        } else {
+            self.patches += 1;
+            f64_histogram!(
+                "apollo.router.operations.defer.patch.duration",
+                "Time from the start of the request until a deferred patch is sent",
+                elapsed,
+                "graphql.operation.name" = self.operation_name.clone()
+            );
+            u64_histogram!(
+                "apollo.router.operations.defer.patch.size",
+                "Size in bytes of a deferred patch",
+                size as u64,
+                "graphql.operation.name" = self.operation_name.clone()
+            );
+        }
+    }
+}
+
+impl Drop for DeferMetrics {
+    fn drop(&mut self) {
+        u64_histogram!(
+            "apollo.router.operations.defer.patches",
+            "Number of deferred patches sent for a request",
+            self.patches,
+            "graphql.operation.name" = self.operation_name.clone()
+        );
+    }
 }
 
 impl Multipart {
@@ -73,8 +129,15 @@ impl Multipart {
             is_first_chunk: true,
             is_terminated: false,
             mode,
+            defer_metrics: None,
         }
     }
+
+    /// Records the latency and size of each payload of a deferred response
+    pub(crate) fn with_defer_metrics(mut self, defer_metrics: DeferMetrics) -> Self {
+        self.defer_metrics = Some(defer_metrics);
+        self
+    }
 }
 
 impl Stream for Multipart {
@@ -107,6 +170,7 @@ impl Stream for Multipart {
                 Some(MessageKind::Message(mut response)) => {
                     let is_still_open =
                         response.has_next.unwrap_or(false) || response.subscribed.unwrap_or(false);
+                    let is_first_chunk = self.is_first_chunk;
                     let mut buf = if self.is_first_chunk {
                         self.is_first_chunk = false;
                         Vec::from(&b"\r\n--graphql\r\ncontent-type: application/json\r\n\r\n"[..])
@@ -139,7 +203,12 @@ impl Stream for Multipart {
                             }
                         }
                         ProtocolMode::Defer => {
+                            let header_len = buf.len();
                             serde_json::to_writer(&mut buf, &response)?;
+                            let size = buf.len() - header_len;
+                            if let Some(defer_metrics) = self.defer_metrics.as_mut() {
+                                defer_metrics.record(is_first_chunk, size);
+                            }
                         }
                     }
 
@@ -184,6 +253,7 @@ mod tests {
     use serde_json_bytes::ByteString;
 
     use super::*;
+    use crate::metrics::FutureMetricsExt;
 
     #[tokio::test]
     async fn test_heartbeat_and_boundaries() {
@@ -291,4 +361,45 @@ mod tests {
             }
         }
     }
+
+    #[tokio::test]
+    async fn test_defer_metrics() {
+        async {
+            let responses = vec![
+                graphql::Response::builder()
+                    .data(serde_json_bytes::json!({"foo": "bar"}))
+                    .has_next(true)
+                    .build(),
+                graphql::Response::builder().has_next(true).build(),
+                graphql::Response::builder().has_next(false).build(),
+            ];
+            let protocol = Multipart::new(stream::iter(responses), ProtocolMode::Defer)
+                .with_defer_metrics(DeferMetrics::new("TestQuery".to_string(), Instant::now()));
+            let chunks: Vec<_> = protocol.collect().await;
+            assert_eq!(chunks.len(), 3);
+
+            assert_histogram_exists!(
+                "apollo.router.operations.defer.initial_payload.duration",
+                f64,
+                "graphql.operation.name" = "TestQuery"
+            );
+            assert_histogram_exists!(
+                "apollo.router.operations.defer.patch.duration",
+                f64,
+                "graphql.operation.name" = "TestQuery"
+            );
+            assert_histogram_sum!(
+                "apollo.router.operations.defer.patch.size",
+                33,
+                "graphql.operation.name" = "TestQuery"
+            );
+            assert_histogram_sum!(
+                "apollo.router.operations.defer.patches",
+                2,
+                "graphql.operation.name" = "TestQuery"
+            );
+        }
+        .with_metrics()
+        .await;
+    }
 }
@@ -25,6 +25,7 @@ use tokio_tungstenite::tungstenite::Message;
 use tokio_tungstenite::WebSocketStream;
 
 use crate::graphql;
+use crate::plugins::telemetry::formatters::in_current_otel_context;
 
 const CONNECTION_ACK_TIMEOUT: Duration = Duration::from_secs(5);
 
@@ -423,7 +424,7 @@ where
         let (mut sink, inner_stream) = InnerStream::new(stream, id, protocol).split();
         let (close_signal, close_sentinel) = tokio::sync::oneshot::channel::<()>();
 
-        tokio::task::spawn(async move {
+        tokio::task::spawn(in_current_otel_context(async move {
             if let (WebSocketProtocol::GraphqlWs, Some(duration)) = (protocol, heartbeat_interval) {
                 let mut interval =
                     tokio::time::interval_at(tokio::time::Instant::now() + duration, duration);
@@ -457,7 +458,7 @@ where
             if let Err(err) = sink.close().await {
                 tracing::trace!("cannot close the websocket stream: {err:?}");
             }
-        });
+        }));
 
         Self {
             inner_stream,
@@ -30,6 +30,7 @@ use super::QueryKey;
 use crate::apollo_studio_interop::generate_usage_reporting;
 use crate::apollo_studio_interop::UsageReportingComparisonResult;
 use crate::configuration::ApolloMetricsGenerationMode;
+use crate::configuration::QueryPlanStrategy;
 use crate::configuration::QueryPlannerMode;
 use crate::error::PlanErrors;
 use crate::error::QueryPlannerError;
@@ -125,7 +126,7 @@ impl PlannerMode {
         Ok(match configuration.experimental_query_planner_mode {
             QueryPlannerMode::New => Self::Rust {
                 js_for_api_schema_and_introspection_and_operation_signature: Self::js(
-                    &schema.raw_sdl,
+                    schema.planner_sdl(),
                     configuration,
                     old_planner,
                 )
@@ -134,10 +135,10 @@ impl PlannerMode {
                     .expect("expected Rust QP instance for `experimental_query_planner_mode: new`"),
             },
             QueryPlannerMode::Legacy => {
-                Self::Js(Self::js(&schema.raw_sdl, configuration, old_planner).await?)
+                Self::Js(Self::js(schema.planner_sdl(), configuration, old_planner).await?)
             }
             QueryPlannerMode::Both => Self::Both {
-                js: Self::js(&schema.raw_sdl, configuration, old_planner).await?,
+                js: Self::js(schema.planner_sdl(), configuration, old_planner).await?,
                 rust: rust_planner.expect(
                     "expected Rust QP instance for `experimental_query_planner_mode: both`",
                 ),
@@ -145,11 +146,11 @@ impl PlannerMode {
             QueryPlannerMode::BothBestEffort => {
                 if let Some(rust) = rust_planner {
                     Self::Both {
-                        js: Self::js(&schema.raw_sdl, configuration, old_planner).await?,
+                        js: Self::js(schema.planner_sdl(), configuration, old_planner).await?,
                         rust,
                     }
                 } else {
-                    Self::Js(Self::js(&schema.raw_sdl, configuration, old_planner).await?)
+                    Self::Js(Self::js(schema.planner_sdl(), configuration, old_planner).await?)
                 }
             }
         })
@@ -189,6 +190,12 @@ impl PlannerMode {
                 apollo_federation::query_plan::query_planner::QueryPlanIncrementalDeliveryConfig {
                     enable_defer: configuration.supergraph.defer_support,
                 },
+            strategy: configuration
+                .supergraph
+                .query_planning
+                .experimental_strategy
+                .default
+                .into(),
             debug: Default::default(),
         };
         let result = QueryPlanner::new(schema.federation_supergraph(), config);
@@ -257,6 +264,7 @@ impl PlannerMode {
         filtered_query: String,
         operation: Option<String>,
         plan_options: PlanOptions,
+        strategy: QueryPlanStrategy,
         // Initialization code that needs mutable access to the plan,
         // before we potentially share it in Arc with a background thread
         // for "both" mode.
@@ -289,7 +297,13 @@ impl PlannerMode {
                     .as_deref()
                     .map(|n| Name::new(n).map_err(FederationError::from))
                     .transpose()
-                    .and_then(|operation| rust.build_query_plan(&doc.executable, operation))
+                    .and_then(|operation| {
+                        rust.build_query_plan_with_strategy(
+                            &doc.executable,
+                            operation,
+                            strategy.into(),
+                        )
+                    })
                     .map_err(|e| QueryPlannerError::FederationError(e.to_string()));
 
                 metric_query_planning_plan_duration(RUST_QP_MODE, start);
@@ -512,6 +526,12 @@ impl BridgeQueryPlanner {
         doc: &ParsedDocument,
         query_metrics: OperationLimits<u32>,
     ) -> Result<QueryPlannerContent, QueryPlannerError> {
+        let strategy = self
+            .configuration
+            .supergraph
+            .query_planning
+            .experimental_strategy
+            .for_operation(operation.as_deref());
         let plan_success = self
             .planner
             .plan(
@@ -519,7 +539,16 @@ impl BridgeQueryPlanner {
                 filtered_query.clone(),
                 operation.clone(),
                 plan_options,
+                strategy,
                 |root_node| {
+                    if self
+                        .configuration
+                        .supergraph
+                        .query_planning
+                        .experimental_backfill_typename
+                    {
+                        root_node.backfill_typename(&self.subgraph_schemas);
+                    }
                     root_node.init_parsed_operations_and_hash_subqueries(
                         &self.subgraph_schemas,
                         &self.schema.raw_sdl,
@@ -561,6 +590,13 @@ impl BridgeQueryPlanner {
                     usage_reporting.stats_report_key = sig;
                 }
 
+                super::plan_limits::check(
+                    &self.configuration,
+                    &node,
+                    &filtered_query,
+                    operation.as_deref(),
+                )?;
+
                 if matches!(
                     self.configuration
                         .experimental_apollo_metrics_generation_mode,
@@ -671,6 +707,7 @@ impl BridgeQueryPlanner {
                         query: Arc::new(selections),
                         query_metrics,
                         estimated_size: Default::default(),
+                        subgraph_ownership: Default::default(),
                     }),
                 })
             }
@@ -3,6 +3,7 @@ use std::hash::Hash;
 use std::ops::Deref;
 use std::sync::Arc;
 use std::task;
+use std::time::Duration;
 
 use apollo_compiler::validation::Valid;
 use futures::future::BoxFuture;
@@ -28,6 +29,8 @@ use crate::cache::estimate_size;
 use crate::cache::storage::InMemoryCache;
 use crate::cache::storage::ValueType;
 use crate::cache::DeduplicatingCache;
+use crate::configuration::QueryPlanStrategy;
+use crate::configuration::QueryPlanStrategyConfig;
 use crate::error::CacheResolverError;
 use crate::error::QueryPlannerError;
 use crate::plugins::authorization::AuthorizationPlugin;
@@ -55,6 +58,9 @@ pub(crate) type Plugins = IndexMap<String, Box<dyn QueryPlannerPlugin>>;
 pub(crate) type InMemoryCachePlanner =
     InMemoryCache<CachingQueryKey, Result<QueryPlannerContent, Arc<QueryPlannerError>>>;
 pub(crate) const APOLLO_OPERATION_ID: &str = "apollo_operation_id";
+pub(crate) const APOLLO_OPERATION_SIGNATURE: &str = "apollo_operation_signature";
+/// Expiration of the warm up lock, after which another instance can warm up the cache for the same schema
+const WARM_UP_LOCK_TTL: Duration = Duration::from_secs(5 * 60);
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize)]
 pub(crate) enum ConfigMode {
@@ -80,6 +86,8 @@ pub(crate) struct CachingQueryPlanner<T: Clone> {
     plugins: Arc<Plugins>,
     enable_authorization_directives: bool,
     config_mode: ConfigMode,
+    strategy: Arc<QueryPlanStrategyConfig>,
+    backfill_typename: bool,
     introspection: bool,
     legacy_introspection_caching: bool,
 }
@@ -148,6 +156,17 @@ where
             plugins: Arc::new(plugins),
             enable_authorization_directives,
             config_mode,
+            strategy: Arc::new(
+                configuration
+                    .supergraph
+                    .query_planning
+                    .experimental_strategy
+                    .clone(),
+            ),
+            backfill_typename: configuration
+                .supergraph
+                .query_planning
+                .experimental_backfill_typename,
             introspection: configuration.supergraph.introspection,
             legacy_introspection_caching: configuration
                 .supergraph
@@ -156,10 +175,16 @@ where
         })
     }
 
+    /// Strategy used to plan an operation
+    pub(crate) fn strategy(&self, operation_name: Option<&str>) -> QueryPlanStrategy {
+        self.strategy.for_operation(operation_name)
+    }
+
     pub(crate) fn previous_cache(&self) -> InMemoryCachePlanner {
         self.cache.in_memory_cache()
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub(crate) async fn warm_up(
         &mut self,
         query_analysis: &QueryAnalysisLayer,
@@ -167,6 +192,7 @@ where
         previous_cache: Option<InMemoryCachePlanner>,
         count: Option<usize>,
         experimental_reuse_query_plans: bool,
+        experimental_warm_up_leader_election: bool,
         experimental_pql_prewarm: bool,
     ) {
         let _timer = Timer::new(|duration| {
@@ -201,6 +227,8 @@ where
                                 metadata,
                                 plan_options,
                                 config_mode: _,
+                                strategy: _,
+                                backfill_typename: _,
                                 schema_id: _,
                                 introspection: _,
                             },
@@ -265,6 +293,31 @@ where
 
         all_cache_keys.extend(cache_keys.into_iter());
 
+        // with a shared Redis cache, only the first instance to load this schema plans the queries,
+        // the other ones only load the query plans already stored in Redis, without waiting
+        let lock_key = format!("warm_up_leader:{}", self.schema.schema_id);
+        let leader = if experimental_warm_up_leader_election {
+            match self
+                .cache
+                .try_acquire_lock(&lock_key, WARM_UP_LOCK_TTL)
+                .await
+            {
+                None | Some(Ok(true)) => true,
+                Some(Ok(false)) => {
+                    tracing::info!(
+                        "another router instance warms up the query plan cache for this schema, only loading the query plans already stored in the shared cache"
+                    );
+                    false
+                }
+                Some(Err(e)) => {
+                    tracing::warn!(error = %e, "could not elect the router instance warming up the query plan cache, skipping warm up");
+                    return;
+                }
+            }
+        } else {
+            true
+        };
+
         let mut count = 0usize;
         let mut reused = 0usize;
         for WarmUpCachingQueryKey {
@@ -294,6 +347,8 @@ where
                 metadata,
                 plan_options,
                 config_mode: self.config_mode.clone(),
+                strategy: self.strategy(operation.as_deref()),
+                backfill_typename: self.backfill_typename,
                 introspection: self.introspection,
             };
 
@@ -321,6 +376,10 @@ where
                     init_query_plan_from_redis(&self.subgraph_schemas, v)
                 })
                 .await;
+            if entry.is_first() && !leader {
+                // dropping the entry lets the query be planned on its first execution
+                continue;
+            }
             if entry.is_first() {
                 let doc = match query_analysis
                     .parse_document(&query, operation.as_deref())
@@ -378,6 +437,10 @@ where
         }
 
         tracing::debug!("warmed up the query planner cache with {count} queries planned and {reused} queries reused");
+
+        if experimental_warm_up_leader_election && leader {
+            self.cache.release_lock(&lock_key, WARM_UP_LOCK_TTL).await;
+        }
     }
 }
 
@@ -425,7 +488,7 @@ where
                         stats_report_key_hash(usage_reporting.stats_report_key.as_str()),
                     );
                     let _ = response.context.insert(
-                        "apollo_operation_signature",
+                        APOLLO_OPERATION_SIGNATURE,
                         usage_reporting.stats_report_key.clone(),
                     );
                 }
@@ -491,6 +554,8 @@ where
             metadata,
             plan_options,
             config_mode: self.config_mode.clone(),
+            strategy: self.strategy(request.operation_name.as_deref()),
+            backfill_typename: self.backfill_typename,
             introspection: self.introspection,
         };
 
@@ -638,6 +703,8 @@ pub(crate) struct CachingQueryKey {
     pub(crate) metadata: CacheKeyMetadata,
     pub(crate) plan_options: PlanOptions,
     pub(crate) config_mode: ConfigMode,
+    pub(crate) strategy: QueryPlanStrategy,
+    pub(crate) backfill_typename: bool,
     pub(crate) introspection: bool,
 }
 
@@ -660,6 +727,14 @@ impl std::fmt::Display for CachingQueryKey {
             .update(serde_json::to_vec(&self.config_mode).expect("serialization should not fail"));
         hasher.update(&*self.schema_id);
         hasher.update([self.introspection as u8]);
+        // the default strategy is not hashed, to keep the keys of the plans cached before
+        // strategies were introduced
+        if self.strategy != QueryPlanStrategy::Balanced {
+            hasher.update(self.strategy.as_str());
+        }
+        if self.backfill_typename {
+            hasher.update("backfill_typename");
+        }
         let metadata = hex::encode(hasher.finalize());
 
         write!(
@@ -678,6 +753,8 @@ impl Hash for CachingQueryKey {
         self.metadata.hash(state);
         self.plan_options.hash(state);
         self.config_mode.hash(state);
+        self.strategy.hash(state);
+        self.backfill_typename.hash(state);
         self.introspection.hash(state);
     }
 }
@@ -856,6 +933,7 @@ mod tests {
                     query: Arc::new(Query::empty()),
                     query_metrics: Default::default(),
                     estimated_size: Default::default(),
+                    subgraph_ownership: Default::default(),
                 };
                 let qp_content = QueryPlannerContent::Plan {
                     plan: Arc::new(query_plan),
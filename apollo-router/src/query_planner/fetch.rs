@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::fmt::Display;
 use std::sync::Arc;
@@ -293,24 +294,27 @@ impl Variables {
                     .map(|(variable_key, value)| (variable_key.clone(), value.clone()))
             }));
 
+            // entities appearing at multiple paths are sent once, along with their contextual
+            // arguments, and the response for each of them is merged at all of its paths
             let mut inverted_paths: Vec<Vec<Path>> = Vec::new();
-            let mut values: IndexSet<Value> = IndexSet::default();
+            let mut values: IndexSet<(Value, BTreeMap<String, Value>)> = IndexSet::default();
             data.select_values_and_paths(schema, current_dir, |path, value| {
-                // first get contextual values that are required
-                if let Some(context) = subgraph_context.as_mut() {
-                    context.execute_on_path(path);
-                }
-
                 let mut value = execute_selection_set(value, requires, schema, None);
                 if value.as_object().map(|o| !o.is_empty()).unwrap_or(false) {
                     rewrites::apply_rewrites(schema, &mut value, input_rewrites);
-                    match values.get_index_of(&value) {
+                    // get the contextual values that are required
+                    let arguments = subgraph_context
+                        .as_ref()
+                        .map(|context| context.arguments_on_path(path))
+                        .unwrap_or_default();
+                    let entry = (value, arguments);
+                    match values.get_index_of(&entry) {
                         Some(index) => {
                             inverted_paths[index].push(path.clone());
                         }
                         None => {
                             inverted_paths.push(vec![path.clone()]);
-                            values.insert(value);
+                            values.insert(entry);
                             debug_assert!(inverted_paths.len() == values.len());
                         }
                     }
@@ -321,9 +325,13 @@ impl Variables {
                 return None;
             }
 
-            let representations = Value::Array(Vec::from_iter(values));
+            let (representations, named_args): (Vec<_>, Vec<_>) = values.into_iter().unzip();
+            let representations = Value::Array(representations);
             let contextual_arguments = match subgraph_context.as_mut() {
-                Some(context) => context.add_variables_and_get_args(&mut variables),
+                Some(context) => {
+                    context.named_args = named_args;
+                    context.add_variables_and_get_args(&mut variables)
+                }
                 None => None,
             };
 
@@ -401,6 +409,14 @@ impl FetchNode {
             }
         };
 
+        // the parsed operation only matches the subgraph query if it was not rewritten with aliases
+        let executable_document =
+            if parameters.service_factory.executable_documents && contextual_arguments.is_none() {
+                operation.as_parsed().ok().cloned()
+            } else {
+                None
+            };
+
         let alias_query_string; // this exists outside the if block to allow the as_str() to be longer lived
         let aliased_operation = if let Some(ctx_arg) = contextual_arguments {
             if let Some(subgraph_schema) =
@@ -462,6 +478,8 @@ impl FetchNode {
             .build();
         subgraph_request.query_hash = self.schema_aware_hash.clone();
         subgraph_request.authorization = self.authorization.clone();
+        subgraph_request.executable_document = executable_document;
+        subgraph_request.variable_usages = Some(self.variable_usages.clone());
 
         let service = parameters
             .service_factory
@@ -671,6 +689,17 @@ impl FetchNode {
         &self.operation_kind
     }
 
+    /// Adds `__typename` to the selection sets of abstract types of the operation, before it
+    /// is parsed and hashed
+    pub(crate) fn backfill_typename(&mut self, subgraph_schemas: &SubgraphSchemas) {
+        let schema = &subgraph_schemas[self.service_name.as_ref()];
+        if let Some(operation) =
+            super::typename::backfill_typename(schema, self.operation.as_serialized())
+        {
+            self.operation = SubgraphOperation::from_string(operation);
+        }
+    }
+
     pub(crate) fn init_parsed_operation(
         &mut self,
         subgraph_schemas: &SubgraphSchemas,
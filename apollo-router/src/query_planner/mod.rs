@@ -5,6 +5,7 @@
 pub(crate) use bridge_query_planner::*;
 pub(crate) use bridge_query_planner_pool::*;
 pub(crate) use caching_query_planner::*;
+pub use ownership::SubgraphOwnership;
 pub use plan::QueryPlan;
 pub(crate) use plan::*;
 
@@ -18,11 +19,14 @@ pub(crate) mod dual_query_planner;
 mod execution;
 pub(crate) mod fetch;
 mod labeler;
+mod ownership;
 mod plan;
+pub(crate) mod plan_limits;
 pub(crate) mod rewrites;
 mod selection;
 mod subgraph_context;
 pub(crate) mod subscription;
+mod typename;
 
 pub(crate) const FETCH_SPAN_NAME: &str = "fetch";
 pub(crate) const SUBSCRIBE_SPAN_NAME: &str = "subscribe";
@@ -0,0 +1,275 @@
+//! Attribution of response paths to the subgraphs that fetched them
+//!
+//! Each fetch of a query plan writes the fields selected by its subgraph operation at the path
+//! where it is merged: the root of the response for root fetches, or the path of a flatten node
+//! for entity fetches. A response path, like the path of an error, is attributed to the fetch
+//! whose selections match the most of its keys.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use apollo_compiler::ast;
+use apollo_compiler::Name;
+
+use super::PlanNode;
+use super::QueryPlan;
+use crate::json_ext::Path;
+use crate::json_ext::PathElement;
+
+/// Subgraphs that fetched each part of the response of a query plan
+#[derive(Debug, Default)]
+pub struct SubgraphOwnership {
+    fetches: Vec<FetchSelections>,
+}
+
+/// Response keys selected by a fetch, below the path where it is merged
+#[derive(Debug)]
+struct FetchSelections {
+    service_name: Arc<str>,
+    merge_at: Path,
+    selections: Selections,
+}
+
+/// Tree of the response keys selected by an operation
+#[derive(Debug, Default)]
+struct Selections(HashMap<String, Selections>);
+
+impl QueryPlan {
+    /// Returns the subgraphs owning the parts of the response, computed from the fetches of the
+    /// plan the first time it is called
+    pub fn subgraph_ownership(&self) -> &SubgraphOwnership {
+        self.subgraph_ownership.get_or_init(|| {
+            let mut ownership = SubgraphOwnership::default();
+            ownership.collect(&self.root, &Path::default(), false);
+            ownership
+        })
+    }
+}
+
+impl SubgraphOwnership {
+    /// Returns the name of the subgraph that fetched the data at this response path, if a fetch
+    /// of the plan selects it
+    pub fn subgraph(&self, path: &Path) -> Option<&str> {
+        let mut owner = None;
+        let mut best_depth = 0;
+        for fetch in &self.fetches {
+            let Some((merge_depth, rest)) = match_merge_path(&fetch.merge_at, path) else {
+                continue;
+            };
+            let depth = fetch.selections.matching_depth(rest);
+            // later fetches write over the keys selected by earlier ones
+            if depth > 0 && merge_depth + depth >= best_depth {
+                best_depth = merge_depth + depth;
+                owner = Some(&*fetch.service_name);
+            }
+        }
+        owner
+    }
+
+    fn collect(&mut self, node: &PlanNode, merge_at: &Path, is_entity_fetch: bool) {
+        match node {
+            PlanNode::Sequence { nodes } | PlanNode::Parallel { nodes } => {
+                for node in nodes {
+                    self.collect(node, merge_at, is_entity_fetch);
+                }
+            }
+            PlanNode::Fetch(fetch) => self.push(
+                &fetch.service_name,
+                fetch.operation.as_serialized(),
+                merge_at,
+                is_entity_fetch,
+            ),
+            PlanNode::Flatten(flatten) => {
+                // flatten paths are relative to the enclosing flatten node, as in execution
+                let merge_at = merge_at.join(flatten.path.remove_empty_key_root());
+                self.collect(&flatten.node, &merge_at, true)
+            }
+            PlanNode::Defer { primary, deferred } => {
+                if let Some(node) = &primary.node {
+                    self.collect(node, merge_at, is_entity_fetch);
+                }
+                for node in deferred
+                    .iter()
+                    .filter_map(|deferred| deferred.node.as_ref())
+                {
+                    self.collect(node, merge_at, is_entity_fetch);
+                }
+            }
+            PlanNode::Subscription { primary, rest } => {
+                self.push(
+                    &primary.service_name,
+                    primary.operation.as_serialized(),
+                    merge_at,
+                    is_entity_fetch,
+                );
+                if let Some(node) = rest {
+                    self.collect(node, merge_at, is_entity_fetch);
+                }
+            }
+            PlanNode::Condition {
+                if_clause,
+                else_clause,
+                ..
+            } => {
+                for node in [if_clause, else_clause].into_iter().flatten() {
+                    self.collect(node, merge_at, is_entity_fetch);
+                }
+            }
+        }
+    }
+
+    fn push(
+        &mut self,
+        service_name: &Arc<str>,
+        operation: &str,
+        merge_at: &Path,
+        is_entity_fetch: bool,
+    ) {
+        // operations generated by the query planner are valid
+        let Ok(document) = ast::Document::parse(operation, "operation.graphql") else {
+            return;
+        };
+        let fragments: HashMap<&Name, &ast::FragmentDefinition> = document
+            .definitions
+            .iter()
+            .filter_map(|definition| match definition {
+                ast::Definition::FragmentDefinition(fragment) => {
+                    Some((&fragment.name, fragment.as_ref()))
+                }
+                _ => None,
+            })
+            .collect();
+        let Some(operation) = document
+            .definitions
+            .iter()
+            .find_map(|definition| match definition {
+                ast::Definition::OperationDefinition(operation) => Some(operation),
+                _ => None,
+            })
+        else {
+            return;
+        };
+
+        let mut selections = Selections::default();
+        selections.add(&operation.selection_set, &fragments, 0);
+        // entity fetches select the fields merged at the flatten path under `_entities`
+        if is_entity_fetch {
+            selections = selections.0.remove("_entities").unwrap_or_default();
+        }
+        self.fetches.push(FetchSelections {
+            service_name: service_name.clone(),
+            merge_at: merge_at.clone(),
+            selections,
+        });
+    }
+}
+
+/// Maximum nesting of fragment spreads, the planner does not generate recursive fragments
+const MAX_FRAGMENT_DEPTH: usize = 32;
+
+impl Selections {
+    fn add(
+        &mut self,
+        selection_set: &[ast::Selection],
+        fragments: &HashMap<&Name, &ast::FragmentDefinition>,
+        fragment_depth: usize,
+    ) {
+        for selection in selection_set {
+            match selection {
+                ast::Selection::Field(field) => {
+                    let key = field.alias.as_ref().unwrap_or(&field.name).to_string();
+                    self.0.entry(key).or_default().add(
+                        &field.selection_set,
+                        fragments,
+                        fragment_depth,
+                    );
+                }
+                ast::Selection::InlineFragment(fragment) => {
+                    self.add(&fragment.selection_set, fragments, fragment_depth)
+                }
+                ast::Selection::FragmentSpread(spread) => {
+                    if fragment_depth < MAX_FRAGMENT_DEPTH {
+                        if let Some(fragment) = fragments.get(&spread.fragment_name) {
+                            self.add(&fragment.selection_set, fragments, fragment_depth + 1)
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Number of keys of the path selected, list indexes are skipped
+    fn matching_depth(&self, path: &[PathElement]) -> usize {
+        let mut selections = self;
+        let mut depth = 0;
+        for element in path {
+            match element {
+                PathElement::Key(key, _) => match selections.0.get(key) {
+                    Some(next) => {
+                        selections = next;
+                        depth += 1;
+                    }
+                    None => break,
+                },
+                PathElement::Index(_) | PathElement::Flatten(_) | PathElement::Fragment(_) => {}
+            }
+        }
+        depth
+    }
+}
+
+/// Matches a response path against the path where a fetch is merged, returning the number of
+/// keys in the merge path and the rest of the response path
+fn match_merge_path<'a>(merge_at: &Path, path: &'a Path) -> Option<(usize, &'a [PathElement])> {
+    let mut rest = path.0.as_slice();
+    let mut depth = 0;
+    for element in &merge_at.0 {
+        match element {
+            PathElement::Key(key, _) => match rest.split_first() {
+                Some((PathElement::Key(other, _), tail)) if key == other => {
+                    rest = tail;
+                    depth += 1;
+                }
+                _ => return None,
+            },
+            // `@` matches any index of a list
+            PathElement::Flatten(_) => match rest.split_first() {
+                Some((PathElement::Index(_) | PathElement::Flatten(_), tail)) => rest = tail,
+                _ => return None,
+            },
+            PathElement::Index(index) => match rest.split_first() {
+                Some((PathElement::Index(other), tail)) if index == other => rest = tail,
+                _ => return None,
+            },
+            // type conditions do not appear in response paths
+            PathElement::Fragment(_) => {}
+        }
+    }
+    Some((depth, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_attributes_paths_to_the_fetching_subgraph() {
+        let root: PlanNode =
+            serde_json::from_str(include_str!("testdata/query_plan.json")).unwrap();
+        let query_plan = QueryPlan::fake_builder().root(root).build();
+        let ownership = query_plan.subgraph_ownership();
+
+        let subgraph = |path: &str| ownership.subgraph(&Path::from(path));
+        assert_eq!(subgraph("topProducts"), Some("product"));
+        assert_eq!(subgraph("topProducts/0/title"), Some("books"));
+        // later fetches write over the fields selected by earlier ones
+        assert_eq!(subgraph("topProducts/1/isbn"), Some("books"));
+        assert_eq!(subgraph("topProducts/1/name"), Some("product"));
+        assert_eq!(subgraph("product/year"), Some("books"));
+        assert_eq!(subgraph("me"), None);
+        assert_eq!(subgraph(""), None);
+
+        // the ownership is computed once per plan
+        assert!(std::ptr::eq(ownership, query_plan.subgraph_ownership()));
+    }
+}
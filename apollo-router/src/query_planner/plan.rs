@@ -1,6 +1,7 @@
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::sync::OnceLock;
 
 use apollo_compiler::validation::Valid;
 use router_bridge::planner::PlanOptions;
@@ -11,6 +12,7 @@ use serde::Serialize;
 pub(crate) use self::fetch::OperationKind;
 use super::fetch;
 use super::subscription::SubscriptionNode;
+use super::SubgraphOwnership;
 use crate::cache::estimate_size;
 use crate::configuration::Batching;
 use crate::error::CacheResolverError;
@@ -49,6 +51,10 @@ pub struct QueryPlan {
     /// The estimated size in bytes of the query plan
     #[serde(default)]
     pub(crate) estimated_size: Arc<AtomicUsize>,
+
+    /// The subgraphs owning the parts of the response, computed on first use
+    #[serde(skip)]
+    pub(crate) subgraph_ownership: Arc<OnceLock<SubgraphOwnership>>,
 }
 
 /// This default impl is useful for test users
@@ -72,6 +78,7 @@ impl QueryPlan {
             query: Arc::new(Query::empty()),
             query_metrics: Default::default(),
             estimated_size: Default::default(),
+            subgraph_ownership: Default::default(),
         }
     }
 }
@@ -303,6 +310,45 @@ impl PlanNode {
         Ok(query_hashes)
     }
 
+    /// Collects the fetch nodes of the plan, in all the branches of conditions and in deferred
+    /// fragments
+    pub(crate) fn fetch_nodes<'a>(&'a self, fetches: &mut Vec<&'a fetch::FetchNode>) {
+        match self {
+            PlanNode::Sequence { nodes } | PlanNode::Parallel { nodes } => {
+                for node in nodes {
+                    node.fetch_nodes(fetches);
+                }
+            }
+            PlanNode::Fetch(node) => fetches.push(node),
+            PlanNode::Flatten(node) => node.node.fetch_nodes(fetches),
+            PlanNode::Defer { primary, deferred } => {
+                if let Some(node) = &primary.node {
+                    node.fetch_nodes(fetches);
+                }
+                for node in deferred
+                    .iter()
+                    .filter_map(|deferred| deferred.node.as_ref())
+                {
+                    node.fetch_nodes(fetches);
+                }
+            }
+            PlanNode::Subscription { rest, .. } => {
+                if let Some(node) = rest {
+                    node.fetch_nodes(fetches);
+                }
+            }
+            PlanNode::Condition {
+                if_clause,
+                else_clause,
+                ..
+            } => {
+                for node in [if_clause, else_clause].into_iter().flatten() {
+                    node.fetch_nodes(fetches);
+                }
+            }
+        }
+    }
+
     pub(crate) fn subgraph_fetches(&self) -> usize {
         match self {
             PlanNode::Sequence { nodes } => nodes.iter().map(|n| n.subgraph_fetches()).sum(),
@@ -389,6 +435,47 @@ impl PlanNode {
         Ok(())
     }
 
+    /// Adds `__typename` to the selection sets of abstract types of the subgraph operations
+    pub(crate) fn backfill_typename(&mut self, subgraph_schemas: &SubgraphSchemas) {
+        match self {
+            PlanNode::Fetch(fetch_node) => fetch_node.backfill_typename(subgraph_schemas),
+            PlanNode::Sequence { nodes } | PlanNode::Parallel { nodes } => {
+                for node in nodes {
+                    node.backfill_typename(subgraph_schemas);
+                }
+            }
+            PlanNode::Flatten(flatten) => flatten.node.backfill_typename(subgraph_schemas),
+            PlanNode::Defer { primary, deferred } => {
+                if let Some(node) = primary.node.as_mut() {
+                    node.backfill_typename(subgraph_schemas);
+                }
+                for deferred_node in deferred {
+                    if let Some(node) = &mut deferred_node.node {
+                        Arc::make_mut(node).backfill_typename(subgraph_schemas);
+                    }
+                }
+            }
+            PlanNode::Subscription { primary, rest } => {
+                primary.backfill_typename(subgraph_schemas);
+                if let Some(node) = rest.as_mut() {
+                    node.backfill_typename(subgraph_schemas);
+                }
+            }
+            PlanNode::Condition {
+                condition: _,
+                if_clause,
+                else_clause,
+            } => {
+                if let Some(node) = if_clause.as_mut() {
+                    node.backfill_typename(subgraph_schemas);
+                }
+                if let Some(node) = else_clause.as_mut() {
+                    node.backfill_typename(subgraph_schemas);
+                }
+            }
+        }
+    }
+
     pub(crate) fn init_parsed_operations_and_hash_subqueries(
         &mut self,
         subgraph_schemas: &SubgraphSchemas,
@@ -626,6 +713,10 @@ pub(crate) struct Depends {
 
 #[cfg(test)]
 mod test {
+    use std::sync::Arc;
+
+    use super::PlanNode;
+    use crate::query_planner::fetch::SubgraphSchemas;
     use crate::query_planner::QueryPlan;
 
     #[test]
@@ -636,4 +727,133 @@ mod test {
         assert!(size1 > 0);
         assert_eq!(size1, size2);
     }
+
+    #[test]
+    fn it_collects_the_fetch_nodes_of_every_branch() {
+        let operations = |root: &PlanNode| {
+            let mut fetches = Vec::new();
+            root.fetch_nodes(&mut fetches);
+            fetches
+                .into_iter()
+                .map(|fetch| {
+                    (
+                        fetch.service_name.to_string(),
+                        fetch.operation.as_serialized().to_string(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // sequences, parallel nodes and flatten nodes
+        let root: PlanNode =
+            serde_json::from_str(include_str!("testdata/query_plan.json")).unwrap();
+        let services = operations(&root)
+            .into_iter()
+            .map(|(service_name, _)| service_name)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            services,
+            ["product", "books", "product", "books", "product"]
+        );
+
+        // both clauses of a condition, the primary and deferred parts of a defer node
+        let root: PlanNode =
+            serde_json::from_str(include_str!("testdata/defer_clause_plan.json")).unwrap();
+        let queries = operations(&root)
+            .into_iter()
+            .map(|(_, operation)| operation)
+            .collect::<Vec<_>>();
+        assert_eq!(queries.len(), 3);
+        assert!(queries[0].starts_with("query Me__accounts__0"));
+        assert!(queries[1].starts_with("query Me__accounts__1"));
+        assert!(queries[2].starts_with("query Me__accounts__2"));
+
+        // the fetches following the subscription, but not the subscription itself
+        let root: PlanNode = serde_json::from_value(serde_json::json!({
+            "kind": "Subscription",
+            "primary": {
+                "serviceName": "reviews",
+                "variableUsages": [],
+                "operation": "subscription{reviewAdded{body product{__typename upc}}}",
+                "operationKind": "subscription"
+            },
+            "rest": {
+                "kind": "Flatten",
+                "path": ["reviewAdded", "product"],
+                "node": {
+                    "kind": "Fetch",
+                    "serviceName": "products",
+                    "requires": [],
+                    "variableUsages": [],
+                    "operation": "query($representations:[_Any!]!){_entities(representations:$representations){...on Product{name}}}",
+                    "operationKind": "query"
+                }
+            }
+        }))
+        .unwrap();
+        let services = operations(&root)
+            .into_iter()
+            .map(|(service_name, _)| service_name)
+            .collect::<Vec<_>>();
+        assert_eq!(services, ["products"]);
+    }
+
+    #[test]
+    fn it_backfills_typename_in_subscriptions() {
+        let schema = |sdl: &str| {
+            Arc::new(apollo_compiler::Schema::parse_and_validate(sdl, "schema.graphql").unwrap())
+        };
+        let subgraph_schemas: SubgraphSchemas = [
+            (
+                "reviews".to_string(),
+                schema(
+                    "type Query { me: String } type Subscription { reviewAdded: Review } \
+                     interface Review { body: String } type ProductReview implements Review { body: String }",
+                ),
+            ),
+            (
+                "products".to_string(),
+                schema(
+                    "type Query { _entities(representations: [_Any!]!): [_Entity]! } \
+                     union _Entity = Product type Product { upc: String name: String } scalar _Any",
+                ),
+            ),
+        ]
+        .into_iter()
+        .collect();
+        let mut root: PlanNode = serde_json::from_value(serde_json::json!({
+            "kind": "Subscription",
+            "primary": {
+                "serviceName": "reviews",
+                "variableUsages": [],
+                "operation": "subscription{reviewAdded{body}}",
+                "operationKind": "subscription"
+            },
+            "rest": {
+                "kind": "Fetch",
+                "serviceName": "products",
+                "requires": [],
+                "variableUsages": [],
+                "operation": "query($representations:[_Any!]!){_entities(representations:$representations){...on Product{name}}}",
+                "operationKind": "query"
+            }
+        }))
+        .unwrap();
+
+        root.backfill_typename(&subgraph_schemas);
+        let PlanNode::Subscription { primary, rest } = &root else {
+            panic!("expected a subscription node");
+        };
+        assert_eq!(
+            primary.operation.as_serialized(),
+            "subscription { reviewAdded { __typename body } }"
+        );
+        let Some(PlanNode::Fetch(fetch)) = rest.as_deref() else {
+            panic!("expected a fetch node");
+        };
+        assert_eq!(
+            fetch.operation.as_serialized(),
+            "query($representations: [_Any!]!) { _entities(representations: $representations) { __typename ... on Product { name } } }"
+        );
+    }
 }
@@ -0,0 +1,249 @@
+//! Limits on the size of query plans
+//!
+//! Some operations can make the query planner generate plans with a very large number of
+//! subgraph fetches, long chains of fetches executed in sequence, or entity fetches nested
+//! under many lists. These limits reject, or only log, operations whose plan goes over them,
+//! as a backstop against planner blow-ups.
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::PlanNode;
+use crate::json_ext::PathElement;
+use crate::Configuration;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct PlanLimits<T> {
+    /// Number of subgraph fetches
+    pub(crate) fetches: T,
+    /// Length of the longest chain of fetches executed one after the other
+    pub(crate) sequence_depth: T,
+    /// Number of entity representations sent to subgraphs, estimated with an assumed size
+    /// for all lists
+    pub(crate) estimated_representations: T,
+}
+
+impl PlanLimits<bool> {
+    fn any(&self) -> bool {
+        // make the compile warn if we forget one
+        let Self {
+            fetches,
+            sequence_depth,
+            estimated_representations,
+        } = *self;
+        fetches || sequence_depth || estimated_representations
+    }
+}
+
+/// Returns which limits are exceeded by the plan of an operation, if any
+pub(crate) fn check(
+    configuration: &Configuration,
+    root: &PlanNode,
+    query: &str,
+    operation_name: Option<&str>,
+) -> Result<(), PlanLimits<bool>> {
+    let config_limits = &configuration.limits;
+    let max = PlanLimits {
+        fetches: config_limits.plan_max_fetches,
+        sequence_depth: config_limits.plan_max_sequence_depth,
+        estimated_representations: config_limits.plan_max_estimated_representations,
+    };
+    if max.fetches.is_none()
+        && max.sequence_depth.is_none()
+        && max.estimated_representations.is_none()
+    {
+        return Ok(());
+    }
+
+    let measured = measure(root, config_limits.plan_assumed_list_size);
+    let over = |max: Option<u64>, measured: u64| max.is_some_and(|max| measured > max);
+    let exceeded = PlanLimits {
+        fetches: over(max.fetches, measured.fetches),
+        sequence_depth: over(max.sequence_depth, measured.sequence_depth),
+        estimated_representations: over(
+            max.estimated_representations,
+            measured.estimated_representations,
+        ),
+    };
+    if !exceeded.any() {
+        return Ok(());
+    }
+
+    let action = if config_limits.warn_only {
+        "warned"
+    } else {
+        "rejected"
+    };
+    let mut messages = Vec::new();
+    for (limit, exceeded, max, measured) in [
+        ("fetches", exceeded.fetches, max.fetches, measured.fetches),
+        (
+            "sequence_depth",
+            exceeded.sequence_depth,
+            max.sequence_depth,
+            measured.sequence_depth,
+        ),
+        (
+            "estimated_representations",
+            exceeded.estimated_representations,
+            max.estimated_representations,
+            measured.estimated_representations,
+        ),
+    ] {
+        if exceeded {
+            messages.push(format!(
+                "{limit}: {measured}, plan_max_{limit}: {}",
+                max.unwrap_or_default()
+            ));
+            u64_counter!(
+                "apollo.router.query_planning.plan.limit_exceeded",
+                "Number of query plans going over a limit of the size of plans",
+                1,
+                "limit" = limit,
+                "action" = action
+            );
+        }
+    }
+    let message = messages.join(", ");
+    tracing::warn!(
+        "query plan exceeded size limits: {message}, \
+        query: {query:?}, operation name: {operation_name:?}"
+    );
+    if config_limits.warn_only {
+        Ok(())
+    } else {
+        Err(exceeded)
+    }
+}
+
+/// Measures a plan against each limit
+pub(crate) fn measure(root: &PlanNode, assumed_list_size: u64) -> PlanLimits<u64> {
+    PlanLimits {
+        fetches: root.subgraph_fetches() as u64,
+        sequence_depth: sequence_depth(root),
+        estimated_representations: estimated_representations(root, assumed_list_size, 0),
+    }
+}
+
+fn sequence_depth(node: &PlanNode) -> u64 {
+    match node {
+        PlanNode::Sequence { nodes } => nodes.iter().map(sequence_depth).sum(),
+        PlanNode::Parallel { nodes } => nodes.iter().map(sequence_depth).max().unwrap_or(0),
+        PlanNode::Fetch(_) => 1,
+        PlanNode::Flatten(flatten) => sequence_depth(&flatten.node),
+        // deferred parts start once the primary part is done
+        PlanNode::Defer { primary, deferred } => {
+            primary.node.as_deref().map_or(0, sequence_depth)
+                + deferred
+                    .iter()
+                    .map(|deferred| deferred.node.as_deref().map_or(0, sequence_depth))
+                    .max()
+                    .unwrap_or(0)
+        }
+        PlanNode::Subscription { rest, .. } => 1 + rest.as_deref().map_or(0, sequence_depth),
+        PlanNode::Condition {
+            if_clause,
+            else_clause,
+            ..
+        } => std::cmp::max(
+            if_clause.as_deref().map_or(0, sequence_depth),
+            else_clause.as_deref().map_or(0, sequence_depth),
+        ),
+    }
+}
+
+/// An entity fetch sends a representation for each object at its flatten path, which are
+/// estimated to `assumed_list_size` to the power of the number of lists in the path
+fn estimated_representations(node: &PlanNode, assumed_list_size: u64, lists: u32) -> u64 {
+    let recurse = |node: &PlanNode| estimated_representations(node, assumed_list_size, lists);
+    match node {
+        PlanNode::Sequence { nodes } | PlanNode::Parallel { nodes } => {
+            nodes.iter().map(recurse).fold(0, u64::saturating_add)
+        }
+        // root fetches do not send representations
+        PlanNode::Fetch(_) => 0,
+        PlanNode::Flatten(flatten) => {
+            let lists = lists
+                + flatten
+                    .path
+                    .iter()
+                    .filter(|element| matches!(element, PathElement::Flatten(_)))
+                    .count() as u32;
+            match &*flatten.node {
+                PlanNode::Fetch(_) => assumed_list_size.saturating_pow(lists),
+                node => estimated_representations(node, assumed_list_size, lists),
+            }
+        }
+        PlanNode::Defer { primary, deferred } => primary
+            .node
+            .as_deref()
+            .into_iter()
+            .chain(
+                deferred
+                    .iter()
+                    .filter_map(|deferred| deferred.node.as_deref()),
+            )
+            .map(recurse)
+            .fold(0, u64::saturating_add),
+        PlanNode::Subscription { rest, .. } => rest.as_deref().map_or(0, recurse),
+        PlanNode::Condition {
+            if_clause,
+            else_clause,
+            ..
+        } => std::cmp::max(
+            if_clause.as_deref().map_or(0, recurse),
+            else_clause.as_deref().map_or(0, recurse),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_measures_plans() {
+        let fetch = |service: &str| {
+            serde_json::json!({
+                "kind": "Fetch",
+                "serviceName": service,
+                "variableUsages": [],
+                "operation": "{__typename}",
+                "operationKind": "query"
+            })
+        };
+        let flatten = |path: serde_json::Value, node: serde_json::Value| serde_json::json!({ "kind": "Flatten", "path": path, "node": node });
+        let root: PlanNode = serde_json::from_value(serde_json::json!({
+            "kind": "Sequence",
+            "nodes": [
+                fetch("products"),
+                {
+                    "kind": "Parallel",
+                    "nodes": [
+                        flatten(serde_json::json!(["topProducts", "@"]), fetch("inventory")),
+                        {
+                            "kind": "Sequence",
+                            "nodes": [
+                                flatten(serde_json::json!(["topProducts", "@"]), fetch("reviews")),
+                                flatten(
+                                    serde_json::json!(["topProducts", "@", "reviews", "@", "author"]),
+                                    fetch("accounts")
+                                )
+                            ]
+                        }
+                    ]
+                }
+            ]
+        }))
+        .unwrap();
+
+        assert_eq!(
+            measure(&root, 10),
+            PlanLimits {
+                fetches: 4,
+                sequence_depth: 3,
+                estimated_representations: 120,
+            }
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::collections::HashSet;
 
@@ -34,7 +35,7 @@ pub(crate) struct SubgraphContext<'a> {
     pub(crate) data: &'a Value,
     pub(crate) schema: &'a Schema,
     pub(crate) context_rewrites: &'a Vec<DataRewrite>,
-    pub(crate) named_args: Vec<HashMap<String, Value>>,
+    pub(crate) named_args: Vec<BTreeMap<String, Value>>,
 }
 
 // context_path is a non-standard relative path which may navigate up the tree
@@ -99,13 +100,13 @@ impl<'a> SubgraphContext<'a> {
         None
     }
 
-    // For each of the rewrites, start collecting data for the data at path.
+    // For each of the rewrites, collect the data for the data at path.
     // Once we find a Value for a given variable, skip additional rewrites that
-    // reference the same variable
-    pub(crate) fn execute_on_path(&mut self, path: &Path) {
+    // reference the same variable. The returned arguments are added to `named_args`
+    // by the caller, once per distinct entity representation
+    pub(crate) fn arguments_on_path(&self, path: &Path) -> BTreeMap<String, Value> {
         let mut found_rewrites: HashSet<String> = HashSet::new();
-        let hash_map: HashMap<String, Value> = self
-            .context_rewrites
+        self.context_rewrites
             .iter()
             .filter_map(|rewrite| {
                 match rewrite {
@@ -148,8 +149,7 @@ impl<'a> SubgraphContext<'a> {
                     DataRewrite::ValueSetter(_) => None,
                 }
             })
-            .collect();
-        self.named_args.push(hash_map);
+            .collect()
     }
 
     // Once all a value has been extracted for every variable, go ahead and add all
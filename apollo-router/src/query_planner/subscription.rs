@@ -12,6 +12,8 @@ use tower::ServiceExt;
 use tracing_futures::Instrument;
 
 use super::execution::ExecutionParameters;
+use super::fetch::SubgraphOperation;
+use super::fetch::SubgraphSchemas;
 use super::fetch::Variables;
 use super::rewrites;
 use super::OperationKind;
@@ -216,7 +218,7 @@ impl SubscriptionNode {
             }
         };
 
-        let subgraph_request = SubgraphRequest::builder()
+        let mut subgraph_request = SubgraphRequest::builder()
             .supergraph_request(parameters.supergraph_request.clone())
             .subgraph_request(
                 http_ext::Request::builder()
@@ -248,6 +250,7 @@ impl SubscriptionNode {
             .subscription_stream(tx_gql)
             .and_connection_closed_signal(parameters.subscription_handle.as_ref().map(|s| s.closed_signal.resubscribe()))
             .build();
+        subgraph_request.variable_usages = Some(self.variable_usages.clone());
 
         let service = parameters
             .service_factory
@@ -272,4 +275,15 @@ impl SubscriptionNode {
 
         Ok(response.errors)
     }
+
+    /// Adds `__typename` to the selection sets of abstract types of the operation, before it
+    /// is parsed and hashed
+    pub(crate) fn backfill_typename(&mut self, subgraph_schemas: &SubgraphSchemas) {
+        let schema = &subgraph_schemas[self.service_name.as_ref()];
+        if let Some(operation) =
+            super::typename::backfill_typename(schema, self.operation.as_serialized())
+        {
+            self.operation = SubgraphOperation::from_string(operation);
+        }
+    }
 }
@@ -26,6 +26,9 @@ use crate::plugin::test::MockSubgraph;
 use crate::query_planner;
 use crate::query_planner::fetch::FetchNode;
 use crate::query_planner::fetch::SubgraphOperation;
+use crate::query_planner::fetch::Variables;
+use crate::query_planner::rewrites::DataKeyRenamer;
+use crate::query_planner::rewrites::DataRewrite;
 use crate::services::subgraph_service::MakeSubgraphService;
 use crate::services::supergraph;
 use crate::services::SubgraphResponse;
@@ -88,6 +91,7 @@ async fn mock_subgraph_service_withf_panics_should_be_reported_as_service_closed
         }
         .into(),
         estimated_size: Default::default(),
+        subgraph_ownership: Default::default(),
     };
 
     let mut mock_products_service = plugin::test::MockSubgraphService::new();
@@ -109,6 +113,8 @@ async fn mock_subgraph_service_withf_panics_should_be_reported_as_service_closed
             Arc::new(mock_products_service) as Arc<dyn MakeSubgraphService>,
         )])),
         plugins: Default::default(),
+        statistics: false,
+        executable_documents: false,
     });
 
     let result = query_plan
@@ -144,6 +150,7 @@ async fn fetch_includes_operation_name() {
         query: Arc::new(Query::empty()),
         query_metrics: Default::default(),
         estimated_size: Default::default(),
+        subgraph_ownership: Default::default(),
     };
 
     let succeeded: Arc<AtomicBool> = Default::default();
@@ -173,6 +180,8 @@ async fn fetch_includes_operation_name() {
             Arc::new(mock_products_service) as Arc<dyn MakeSubgraphService>,
         )])),
         plugins: Default::default(),
+        statistics: false,
+        executable_documents: false,
     });
 
     let _response = query_plan
@@ -205,6 +214,7 @@ async fn fetch_makes_post_requests() {
         query: Arc::new(Query::empty()),
         query_metrics: Default::default(),
         estimated_size: Default::default(),
+        subgraph_ownership: Default::default(),
     };
 
     let succeeded: Arc<AtomicBool> = Default::default();
@@ -234,6 +244,8 @@ async fn fetch_makes_post_requests() {
             Arc::new(mock_products_service) as Arc<dyn MakeSubgraphService>,
         )])),
         plugins: Default::default(),
+        statistics: false,
+        executable_documents: false,
     });
 
     let _response = query_plan
@@ -334,6 +346,7 @@ async fn defer() {
             query: Arc::new(Query::empty()),
             query_metrics: Default::default(),
             estimated_size: Default::default(),
+            subgraph_ownership: Default::default(),
         };
 
     let mut mock_x_service = plugin::test::MockSubgraphService::new();
@@ -389,6 +402,8 @@ async fn defer() {
             ),
         ])),
         plugins: Default::default(),
+        statistics: false,
+        executable_documents: false,
     });
 
     let response = query_plan
@@ -465,6 +480,7 @@ async fn defer_if_condition() {
         formatted_query_plan: None,
         query_metrics: Default::default(),
         estimated_size: Default::default(),
+        subgraph_ownership: Default::default(),
     };
 
     let mocked_accounts = MockSubgraph::builder()
@@ -492,6 +508,8 @@ async fn defer_if_condition() {
             Arc::new(mocked_accounts) as Arc<dyn MakeSubgraphService>,
         )])),
         plugins: Default::default(),
+        statistics: false,
+        executable_documents: false,
     });
     let defer_primary_response = query_plan
         .execute(
@@ -648,6 +666,7 @@ async fn dependent_mutations() {
         query: Arc::new(Query::empty()),
         query_metrics: Default::default(),
         estimated_size: Default::default(),
+        subgraph_ownership: Default::default(),
     };
 
     let mut mock_a_service = plugin::test::MockSubgraphService::new();
@@ -677,6 +696,8 @@ async fn dependent_mutations() {
             ),
         ])),
         plugins: Default::default(),
+        statistics: false,
+        executable_documents: false,
     });
 
     let (sender, _) = tokio::sync::mpsc::channel(10);
@@ -1833,6 +1854,7 @@ fn broken_plan_does_not_panic() {
         query: Arc::new(Query::empty()),
         query_metrics: Default::default(),
         estimated_size: Default::default(),
+        subgraph_ownership: Default::default(),
     };
     let subgraph_schema = apollo_compiler::Schema::parse_and_validate(subgraph_schema, "").unwrap();
     let mut subgraph_schemas = HashMap::new();
@@ -1844,3 +1866,103 @@ fn broken_plan_does_not_panic() {
         r#"[1:3] Cannot query field "invalid" on type "Query"."#
     );
 }
+
+#[test]
+fn entity_representations_are_deduplicated() {
+    let schema = Schema::parse(test_schema!(), &Default::default()).unwrap();
+    let requires = vec![query_planner::selection::Selection::InlineFragment(
+        query_planner::selection::InlineFragment {
+            type_condition: Some(name!("Furniture")),
+            selections: vec![
+                query_planner::selection::Selection::Field(query_planner::selection::Field {
+                    alias: None,
+                    name: name!("__typename"),
+                    selections: None,
+                }),
+                query_planner::selection::Selection::Field(query_planner::selection::Field {
+                    alias: None,
+                    name: name!("upc"),
+                    selections: None,
+                }),
+            ],
+        },
+    )];
+    let data = json!({
+        "topProducts": [
+            { "__typename": "Furniture", "upc": "1", "name": "Table" },
+            { "__typename": "Furniture", "upc": "2", "name": "Chair" },
+            { "__typename": "Furniture", "upc": "1", "name": "Table" },
+            { "__typename": "Furniture", "upc": "1", "name": "Desk" }
+        ]
+    });
+    let current_dir = Path::from("topProducts/@");
+    let request = Arc::new(http::Request::new(graphql::Request::default()));
+
+    let variables = Variables::new(
+        &requires,
+        &[],
+        &data,
+        &current_dir,
+        &request,
+        &schema,
+        &None,
+        &None,
+    )
+    .unwrap();
+    assert_eq!(
+        variables.variables.get("representations").unwrap(),
+        &json!([
+            { "__typename": "Furniture", "upc": "1" },
+            { "__typename": "Furniture", "upc": "2" }
+        ])
+    );
+    assert_eq!(
+        variables.inverted_paths,
+        vec![
+            vec![
+                Path::from("topProducts/0"),
+                Path::from("topProducts/2"),
+                Path::from("topProducts/3")
+            ],
+            vec![Path::from("topProducts/1")],
+        ]
+    );
+
+    // entities with the same key but different contextual arguments are sent separately
+    let context_rewrites = Some(vec![DataRewrite::KeyRenamer(DataKeyRenamer {
+        path: Path::from("name"),
+        rename_key_to: name!("contextualArgument_1_0"),
+    })]);
+    let variables = Variables::new(
+        &requires,
+        &[],
+        &data,
+        &current_dir,
+        &request,
+        &schema,
+        &None,
+        &context_rewrites,
+    )
+    .unwrap();
+    assert_eq!(
+        variables.variables.get("representations").unwrap(),
+        &json!([
+            { "__typename": "Furniture", "upc": "1" },
+            { "__typename": "Furniture", "upc": "2" },
+            { "__typename": "Furniture", "upc": "1" }
+        ])
+    );
+    assert_eq!(
+        variables.inverted_paths,
+        vec![
+            vec![Path::from("topProducts/0"), Path::from("topProducts/2")],
+            vec![Path::from("topProducts/1")],
+            vec![Path::from("topProducts/3")],
+        ]
+    );
+    assert_eq!(variables.contextual_arguments.unwrap().count, 3);
+    assert_eq!(
+        variables.variables.get("contextualArgument_1_0_2").unwrap(),
+        &json!("Desk")
+    );
+}
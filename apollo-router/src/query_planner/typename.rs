@@ -0,0 +1,151 @@
+//! Backfilling of `__typename` in subgraph operations
+//!
+//! Merging the response of a subgraph fetch with the data already fetched, and formatting
+//! the response to the client, relies on `__typename` to resolve the concrete type of the
+//! objects returned by fields of interface and union types. The query planner does not always
+//! select it, so it can be added to the selection sets of abstract types of the subgraph
+//! operations. The client response only contains the fields selected by the client operation,
+//! so the added `__typename` does not reach clients that did not ask for it.
+
+use apollo_compiler::ast;
+use apollo_compiler::name;
+use apollo_compiler::Node;
+use apollo_compiler::Schema;
+use tower::BoxError;
+
+use crate::spec::query::transform;
+use crate::spec::query::transform::Visitor;
+use crate::spec::TYPENAME;
+
+/// Adds `__typename` to the selection sets of abstract types of a subgraph operation.
+///
+/// Returns `None` if the operation already selects it everywhere it is needed
+pub(crate) fn backfill_typename(schema: &Schema, operation: &str) -> Option<String> {
+    let document = ast::Document::parse(operation, "operation.graphql").ok()?;
+    let mut visitor = TypenameBackfill {
+        schema,
+        added: false,
+    };
+    match transform::document(&mut visitor, &document) {
+        Ok(document) if visitor.added => Some(document.serialize().no_indent().to_string()),
+        Ok(_) => None,
+        Err(error) => {
+            tracing::debug!("could not add __typename to a subgraph operation: {error}");
+            None
+        }
+    }
+}
+
+struct TypenameBackfill<'a> {
+    schema: &'a Schema,
+    added: bool,
+}
+
+impl<'a> Visitor for TypenameBackfill<'a> {
+    fn field(
+        &mut self,
+        _parent_type: &str,
+        field_def: &ast::FieldDefinition,
+        def: &ast::Field,
+    ) -> Result<Option<ast::Field>, BoxError> {
+        let Some(mut field) = transform::field(self, field_def, def)? else {
+            return Ok(None);
+        };
+        let is_abstract = self
+            .schema
+            .types
+            .get(field_def.ty.inner_named_type())
+            .is_some_and(|ty| ty.is_interface() || ty.is_union());
+        if is_abstract && !selects_typename(&field.selection_set) {
+            field.selection_set.insert(
+                0,
+                ast::Selection::Field(Node::new(ast::Field {
+                    alias: None,
+                    name: name!("__typename"),
+                    arguments: Vec::new(),
+                    directives: Default::default(),
+                    selection_set: Vec::new(),
+                })),
+            );
+            self.added = true;
+        }
+        Ok(Some(field))
+    }
+
+    fn schema(&self) -> &Schema {
+        self.schema
+    }
+}
+
+/// Whether the selection set has `__typename` in its own response keys. Fragments are not
+/// considered, their selections can be skipped by their type condition
+fn selects_typename(selection_set: &[ast::Selection]) -> bool {
+    selection_set.iter().any(|selection| match selection {
+        ast::Selection::Field(field) => {
+            field.alias.as_ref().unwrap_or(&field.name).as_str() == TYPENAME
+        }
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &str = r#"
+        type Query {
+            node(id: ID!): Node
+            search: [SearchResult]
+            user: User
+            _entities(representations: [_Any!]!): [_Entity]!
+        }
+
+        interface Node {
+            id: ID!
+        }
+
+        type User implements Node {
+            id: ID!
+            name: String
+            friends: [Node]
+        }
+
+        type Product implements Node {
+            id: ID!
+            upc: String
+        }
+
+        union SearchResult = User | Product
+        union _Entity = User | Product
+        scalar _Any
+    "#;
+
+    fn backfill(operation: &str) -> Option<String> {
+        let schema = Schema::parse(SCHEMA, "schema.graphql").unwrap();
+        backfill_typename(&schema, operation)
+    }
+
+    #[test]
+    fn it_adds_typename_to_abstract_selections() {
+        assert_eq!(
+            backfill("{node(id:\"1\"){id ...on User{name friends{id}}} search{...on Product{upc}}}")
+                .as_deref(),
+            Some(
+                "{ node(id: \"1\") { __typename id ... on User { name friends { __typename id } } } search { __typename ... on Product { upc } } }"
+            )
+        );
+        assert_eq!(
+            backfill("query($representations:[_Any!]!){_entities(representations:$representations){...on User{name}}}")
+                .as_deref(),
+            Some(
+                "query($representations: [_Any!]!) { _entities(representations: $representations) { __typename ... on User { name } } }"
+            )
+        );
+    }
+
+    #[test]
+    fn it_keeps_operations_selecting_typename() {
+        assert_eq!(backfill("{node(id:\"1\"){__typename id} user{name}}"), None);
+        assert_eq!(backfill("{user{name}}"), None);
+    }
+}
@@ -0,0 +1,219 @@
+//! Composition of local subgraphs in development mode
+//!
+//! The subgraphs are listed in a YAML file using the format of `rover supergraph compose`, with
+//! the routing URL and the schema file of each subgraph:
+//!
+//! ```yaml
+//! subgraphs:
+//!   products:
+//!     routing_url: http://localhost:4001/graphql
+//!     schema:
+//!       file: ./products.graphql
+//! ```
+//!
+//! The supergraph is composed in process, and composed again when the list of subgraphs or one
+//! of the schema files changes.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::path::PathBuf;
+
+use apollo_federation::subgraph::Subgraph;
+use apollo_federation::Supergraph;
+use futures::prelude::*;
+use futures::stream::BoxStream;
+use serde::Deserialize;
+use tower::BoxError;
+
+/// The local subgraphs
+#[derive(Debug, Deserialize)]
+struct SubgraphsConfig {
+    subgraphs: BTreeMap<String, SubgraphConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubgraphConfig {
+    routing_url: String,
+    schema: SubgraphSchema,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SubgraphSchema {
+    /// Path of the schema file, relative to the subgraphs file
+    file: PathBuf,
+}
+
+impl SubgraphsConfig {
+    fn read(path: &Path) -> Result<Self, BoxError> {
+        let mut config: Self = serde_yaml::from_str(&std::fs::read_to_string(path)?)?;
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+        for subgraph in config.subgraphs.values_mut() {
+            subgraph.schema.file = directory.join(&subgraph.schema.file);
+        }
+        Ok(config)
+    }
+
+    fn compose(&self) -> Result<String, BoxError> {
+        let subgraphs = self
+            .subgraphs
+            .iter()
+            .map(|(name, subgraph)| {
+                let file = &subgraph.schema.file;
+                let sdl = std::fs::read_to_string(file).map_err(|e| {
+                    format!(
+                        "could not read the schema of subgraph '{name}' at {}: {e}",
+                        file.display()
+                    )
+                })?;
+                Subgraph::parse_and_expand(name, &subgraph.routing_url, &sdl)
+                    .map_err(|e| format!("invalid schema for subgraph '{name}': {e}").into())
+            })
+            .collect::<Result<Vec<_>, BoxError>>()?;
+        let supergraph = Supergraph::compose(subgraphs.iter().collect())
+            .map_err(|failure| format!("composition failed: {}", failure.errors.join(", ")))?;
+        Ok(supergraph.schema.schema().to_string())
+    }
+}
+
+/// Composes the supergraph, returns it along with the files it was composed from
+fn compose(path: &Path) -> (Option<String>, Vec<PathBuf>) {
+    let mut files = vec![path.to_path_buf()];
+    let result = SubgraphsConfig::read(path).and_then(|config| {
+        files.extend(
+            config
+                .subgraphs
+                .values()
+                .map(|subgraph| subgraph.schema.file.clone()),
+        );
+        config.compose()
+    });
+    match result {
+        Ok(schema) => (Some(schema), files),
+        Err(e) => {
+            tracing::error!("could not compose the local subgraphs: {e}");
+            (None, files)
+        }
+    }
+}
+
+fn watch_files(files: &[PathBuf]) -> BoxStream<'static, ()> {
+    // Missing files cannot be watched, they will be once the subgraphs file changes
+    stream::select_all(
+        files
+            .iter()
+            .filter(|file| file.exists())
+            // the first event of a watch only tells to read the file
+            .map(|file| crate::files::watch(file).skip(1).boxed()),
+    )
+    .boxed()
+}
+
+/// Stream of the supergraphs composed from the subgraphs listed in a file. When watching, the
+/// supergraph is composed again on changes, keeping the previous one if composition fails.
+pub(super) fn stream(path: PathBuf, watch: bool) -> BoxStream<'static, String> {
+    let (schema, files) = compose(&path);
+    let first = stream::iter(schema);
+    if !watch {
+        return first.boxed();
+    }
+
+    let changes = watch_files(&files);
+    let updates = stream::unfold(
+        (path, files, changes),
+        |(path, mut files, mut changes)| async move {
+            loop {
+                changes.next().await?;
+                let (schema, new_files) = {
+                    let path = path.clone();
+                    tokio::task::spawn_blocking(move || compose(&path))
+                        .await
+                        .ok()?
+                };
+                // the subgraphs file changed, the schema files may be different
+                if new_files != files {
+                    changes = watch_files(&new_files);
+                    files = new_files;
+                }
+                if let Some(schema) = schema {
+                    return Some((schema, (path, files, changes)));
+                }
+            }
+        },
+    );
+    first.chain(updates).boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::files::tests::create_temp_file;
+    use crate::files::tests::write_and_flush;
+
+    const PRODUCTS: &str = r#"
+        extend schema @link(url: "https://specs.apollo.dev/federation/v2.0", import: ["@key"])
+        type Query { topProducts: [Product] }
+        type Product @key(fields: "upc") { upc: String! name: String }
+    "#;
+
+    const REVIEWS: &str = r#"
+        extend schema @link(url: "https://specs.apollo.dev/federation/v2.0", import: ["@key"])
+        type Product @key(fields: "upc") { upc: String! reviews: [Review] }
+        type Review { body: String }
+    "#;
+
+    #[tokio::test]
+    async fn it_composes_local_subgraphs_on_changes() {
+        let (products_path, mut products) = create_temp_file();
+        write_and_flush(&mut products, PRODUCTS).await;
+        let (reviews_path, mut reviews) = create_temp_file();
+        write_and_flush(&mut reviews, REVIEWS).await;
+        let (path, mut file) = create_temp_file();
+        let subgraphs = format!(
+            "subgraphs:
+  products:
+    routing_url: http://localhost:4001/graphql
+    schema:
+      file: {}
+  reviews:
+    routing_url: http://localhost:4002/graphql
+    schema:
+      file: {}
+",
+            products_path.display(),
+            reviews_path.display()
+        );
+        write_and_flush(&mut file, &subgraphs).await;
+
+        let mut stream = stream(path, true);
+        let schema = stream.next().await.unwrap();
+        assert!(schema.contains("http://localhost:4001/graphql"));
+        assert!(schema.contains("reviews: [Review]"));
+
+        // invalid schemas are not composed
+        write_and_flush(&mut reviews, "type Review {").await;
+        write_and_flush(
+            &mut reviews,
+            &REVIEWS.replace("body: String", "body: String rating: Int"),
+        )
+        .await;
+        let schema = stream.next().await.unwrap();
+        assert!(schema.contains("rating: Int"));
+    }
+
+    #[tokio::test]
+    async fn it_does_not_compose_missing_schemas() {
+        let (path, mut file) = create_temp_file();
+        write_and_flush(
+            &mut file,
+            "subgraphs:
+  products:
+    routing_url: http://localhost:4001/graphql
+    schema:
+      file: missing.graphql
+",
+        )
+        .await;
+        assert!(stream(path, false).next().await.is_none());
+    }
+}
@@ -8,6 +8,7 @@ use derive_more::Display;
 use derive_more::From;
 use futures::prelude::*;
 
+use crate::configuration::ConfigurationError;
 use crate::router::Event;
 use crate::router::Event::NoMoreConfiguration;
 use crate::router::Event::UpdateConfiguration;
@@ -103,7 +104,7 @@ impl ConfigurationSource {
                                                     Some(UpdateConfiguration(configuration))
                                                 }
                                                 Err(err) => {
-                                                    tracing::error!("{}", err);
+                                                    err.log();
                                                     None
                                                 }
                                             }
@@ -117,7 +118,7 @@ impl ConfigurationSource {
                             }
                         }
                         Err(err) => {
-                            tracing::error!("Failed to read configuration: {}", err);
+                            err.log();
                             stream::empty().boxed()
                         }
                     }
@@ -143,7 +144,21 @@ enum ReadConfigError {
     /// could not read configuration: {0}
     Io(std::io::Error),
     /// {0}
-    Validation(crate::configuration::ConfigurationError),
+    Validation(ConfigurationError),
+}
+
+impl ReadConfigError {
+    fn log(&self) {
+        match self {
+            // log each located error on its own, with its location as structured fields
+            ReadConfigError::Validation(ConfigurationError::LocatedErrors { errors, .. }) => {
+                for error in errors {
+                    error.log("configuration");
+                }
+            }
+            _ => tracing::error!("Failed to read configuration: {}", self),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -1,3 +1,4 @@
+mod composition;
 mod configuration;
 mod license;
 mod reload;
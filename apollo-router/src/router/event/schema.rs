@@ -59,6 +59,16 @@ pub enum SchemaSource {
         /// When watching, the delay to wait between each poll.
         period: Duration,
     },
+
+    /// A YAML file listing local subgraphs, composed into the supergraph.
+    #[display(fmt = "Subgraphs")]
+    Subgraphs {
+        /// The path of the subgraphs file.
+        path: PathBuf,
+
+        /// `true` to watch the subgraphs file and the subgraph schemas for changes and hot apply them.
+        watch: bool,
+    },
 }
 
 impl From<&'_ str> for SchemaSource {
@@ -172,6 +182,11 @@ impl SchemaSource {
                     .boxed()
                 }
             }
+            SchemaSource::Subgraphs { path, watch } => {
+                super::composition::stream(path, watch)
+                    .map(UpdateSchema)
+                    .boxed()
+            }
         }
         .chain(stream::iter(vec![NoMoreSchema]))
         .boxed()
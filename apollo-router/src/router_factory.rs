@@ -1,10 +1,14 @@
 use std::collections::HashMap;
 use std::io;
 use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
 
 use apollo_compiler::validation::Valid;
 use axum::response::IntoResponse;
+use futures::future::join_all;
 use http::StatusCode;
+use http::Uri;
 use indexmap::IndexMap;
 use multimap::MultiMap;
 use rustls::RootCertStore;
@@ -21,18 +25,23 @@ use crate::configuration::Configuration;
 use crate::configuration::ConfigurationError;
 use crate::configuration::TlsClient;
 use crate::configuration::APOLLO_PLUGIN_PREFIX;
+use crate::plugin::timing::TimedPlugin;
 use crate::plugin::DynPlugin;
 use crate::plugin::Handler;
 use crate::plugin::PluginFactory;
 use crate::plugin::PluginInit;
+use crate::plugins::override_url::OverrideSubgraphUrl;
+use crate::plugins::override_url::APOLLO_OVERRIDE_SUBGRAPH_URL;
 use crate::plugins::subscription::Subscription;
 use crate::plugins::subscription::APOLLO_SUBSCRIPTION_PLUGIN;
+use crate::plugins::telemetry::config::Conf as TelemetryConfig;
 use crate::plugins::telemetry::reload::apollo_opentelemetry_initialized;
 use crate::plugins::traffic_shaping::TrafficShaping;
 use crate::plugins::traffic_shaping::APOLLO_TRAFFIC_SHAPING;
 use crate::query_planner::BridgeQueryPlannerPool;
 use crate::services::apollo_graph_reference;
 use crate::services::apollo_key;
+use crate::services::http::HttpClientService;
 use crate::services::http::HttpClientServiceFactory;
 use crate::services::layers::persisted_queries::PersistedQueryLayer;
 use crate::services::layers::query_analysis::QueryAnalysisLayer;
@@ -51,6 +60,7 @@ use crate::spec::Schema;
 use crate::ListenAddr;
 
 pub(crate) const STARTING_SPAN_NAME: &str = "starting";
+const SUBGRAPH_WARMUP_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[derive(Clone)]
 /// A path and a handler to be exposed as a web_endpoint for plugins
@@ -236,7 +246,8 @@ impl YamlRouterFactory {
 
         // Instantiate the parser here so we can use it to warm up the planner below
         let query_analysis_layer =
-            QueryAnalysisLayer::new(supergraph_creator.schema(), Arc::clone(&configuration)).await;
+            QueryAnalysisLayer::new(supergraph_creator.schema(), Arc::clone(&configuration))
+                .await?;
 
         let persisted_query_layer = Arc::new(PersistedQueryLayer::new(&configuration).await?);
 
@@ -253,6 +264,10 @@ impl YamlRouterFactory {
                         .supergraph
                         .query_planning
                         .experimental_reuse_query_plans,
+                    configuration
+                        .supergraph
+                        .query_planning
+                        .experimental_warm_up_leader_election,
                     configuration
                         .persisted_queries
                         .experimental_prewarm_query_plan_cache,
@@ -269,12 +284,17 @@ impl YamlRouterFactory {
                         .supergraph
                         .query_planning
                         .experimental_reuse_query_plans,
+                    configuration
+                        .supergraph
+                        .query_planning
+                        .experimental_warm_up_leader_election,
                     configuration
                         .persisted_queries
                         .experimental_prewarm_query_plan_cache,
                 )
                 .await;
         };
+        supergraph_creator.pre_register_subgraph_operations();
         RouterCreator::new(
             query_analysis_layer,
             persisted_query_layer,
@@ -374,7 +394,9 @@ impl YamlRouterFactory {
             }
 
             // Final creation after this line we must NOT fail to go live with the new router from this point as some plugins may interact with globals.
+            let plugin_names = plugins.keys().cloned().collect();
             let supergraph_creator = builder.with_plugins(plugins).build().await?;
+            crate::status::record_plugins(plugin_names);
 
             Ok(supergraph_creator)
         }
@@ -424,15 +446,32 @@ pub(crate) async fn create_subgraph_services(
         .and_then(|plugin| (*plugin.1).as_any().downcast_ref::<TrafficShaping>())
         .expect("traffic shaping should always be part of the plugin list");
 
+    let override_url = plugins
+        .iter()
+        .find(|i| i.0.as_str() == APOLLO_OVERRIDE_SUBGRAPH_URL)
+        .and_then(|plugin| (*plugin.1).as_any().downcast_ref::<OverrideSubgraphUrl>());
+
     let mut subgraph_services = IndexMap::default();
-    for (name, _) in schema.subgraphs() {
+    let mut warmups = Vec::new();
+    for (name, url) in schema.subgraphs() {
         let http_service = crate::services::http::HttpClientService::from_config(
             name,
             configuration,
             &tls_root_store,
             shaping.enable_subgraph_http2(name),
+            shaping.subgraph_proxy(name),
+            shaping.subgraph_dns(name),
+            shaping.subgraph_pool_idle_timeout(name),
         )?;
 
+        if shaping.enable_subgraph_connection_warmup(name) {
+            let url = override_url
+                .and_then(|plugin| plugin.url(name))
+                .unwrap_or(url)
+                .clone();
+            warmups.push(warm_up_subgraph(name.clone(), url, http_service.clone()));
+        }
+
         let http_service_factory = HttpClientServiceFactory::new(http_service, plugins.clone());
 
         let subgraph_service = shaping.subgraph_service_internal(
@@ -447,9 +486,31 @@ pub(crate) async fn create_subgraph_services(
         subgraph_services.insert(name.clone(), subgraph_service);
     }
 
+    // the router is not serving traffic yet, so it waits for the connections to be established
+    join_all(warmups).await;
+
     Ok(subgraph_services)
 }
 
+async fn warm_up_subgraph(name: String, url: Uri, http_service: HttpClientService) {
+    let start = Instant::now();
+    match tokio::time::timeout(SUBGRAPH_WARMUP_TIMEOUT, http_service.warm_up(url)).await {
+        Ok(Ok(())) => tracing::debug!(
+            subgraph = %name,
+            duration = ?start.elapsed(),
+            "connection to the subgraph warmed up"
+        ),
+        Ok(Err(err)) => tracing::warn!(
+            subgraph = %name,
+            "could not warm up the connection to the subgraph: {err}"
+        ),
+        Err(_) => tracing::warn!(
+            subgraph = %name,
+            "warming up the connection to the subgraph timed out"
+        ),
+    }
+}
+
 impl TlsClient {
     pub(crate) fn create_certificate_store(
         &self,
@@ -674,6 +735,7 @@ pub(crate) async fn create_plugins(
     add_mandatory_apollo_plugin!("include_subgraph_errors");
     add_mandatory_apollo_plugin!("csrf");
     add_mandatory_apollo_plugin!("headers");
+    add_optional_apollo_plugin!("subgraph_identification");
     if apollo_telemetry_plugin_mandatory {
         match initial_telemetry_plugin {
             None => {
@@ -696,12 +758,23 @@ pub(crate) async fn create_plugins(
     add_optional_apollo_plugin!("preview_file_uploads");
     add_optional_apollo_plugin!("preview_entity_cache");
     add_mandatory_apollo_plugin!("progressive_override");
+    add_optional_apollo_plugin!("subgraph_response_validation");
+    add_optional_apollo_plugin!("schema_download");
+    add_optional_apollo_plugin!("admin_api");
+    add_optional_apollo_plugin!("operation_rewrite");
+    add_optional_apollo_plugin!("field_allow_list");
+    add_optional_apollo_plugin!("response_sampling");
+    add_optional_apollo_plugin!("response_assertions");
+    add_optional_apollo_plugin!("context_export");
+    add_optional_apollo_plugin!("extensions_to_context");
 
     // This relative ordering is documented in `docs/source/customizations/native.mdx`:
     add_optional_apollo_plugin!("rhai");
     add_optional_apollo_plugin!("coprocessor");
     add_optional_apollo_plugin!("demand_control");
     add_user_plugins!();
+    // Handles subgraph requests after every customization, to remove the variables they add
+    add_optional_apollo_plugin!("forwarded_variables");
 
     // Macros above remove from `apollo_plugin_factories`, so anything left at the end
     // indicates a missing macro call.
@@ -734,6 +807,14 @@ pub(crate) async fn create_plugins(
             "there were {} configuration errors",
             errors.len()
         )))
+    } else if TelemetryConfig::plugin_duration_enabled(configuration) {
+        Ok(plugin_instances
+            .into_iter()
+            .map(|(name, plugin)| {
+                let plugin: Box<dyn DynPlugin> = Box::new(TimedPlugin::new(name.clone(), plugin));
+                (name, plugin)
+            })
+            .collect())
     } else {
         Ok(plugin_instances)
     }
@@ -19,6 +19,7 @@ pub type ServiceResult = Result<Response, BoxError>;
 // Reachable from Request
 use super::SubscriptionTaskParams;
 pub use crate::query_planner::QueryPlan;
+pub use crate::query_planner::SubgraphOwnership;
 
 assert_impl_all!(Request: Send);
 #[non_exhaustive]
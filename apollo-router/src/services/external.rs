@@ -32,7 +32,7 @@ pub(crate) const DEFAULT_EXTERNALIZATION_TIMEOUT: Duration = Duration::from_secs
 /// Version of our externalised data. Rev this if it changes
 pub(crate) const EXTERNALIZABLE_VERSION: u8 = 1;
 
-#[derive(Clone, Debug, Display, Deserialize, PartialEq, Serialize, JsonSchema)]
+#[derive(Clone, Debug, Display, Deserialize, PartialEq, Eq, Hash, Serialize, JsonSchema)]
 pub(crate) enum PipelineStep {
     RouterRequest,
     RouterResponse,
@@ -266,6 +266,31 @@ where
         }
     }
 
+    /// Output of a stage leaving the request or response unchanged, used in place of the
+    /// coprocessor output when a failing coprocessor is skipped
+    pub(crate) fn passthrough(stage: PipelineStep) -> Self {
+        let stage = stage.to_string();
+        // request stages must always have a control
+        let control = stage.ends_with("Request").then_some(Control::Continue);
+        Externalizable {
+            version: EXTERNALIZABLE_VERSION,
+            stage,
+            control,
+            id: None,
+            headers: None,
+            body: None,
+            context: None,
+            status_code: None,
+            sdl: None,
+            uri: None,
+            path: None,
+            method: None,
+            service_name: None,
+            has_next: None,
+            query_plan: None,
+        }
+    }
+
     pub(crate) async fn call<C>(self, mut client: C, uri: &str) -> Result<Self, BoxError>
     where
         C: Service<
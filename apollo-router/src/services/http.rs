@@ -10,6 +10,8 @@ use super::Plugins;
 use crate::Context;
 
 pub(crate) mod body_stream;
+pub(crate) mod compression_metrics;
+pub(crate) mod proxy;
 pub(crate) mod service;
 #[cfg(test)]
 mod tests;
@@ -56,6 +58,9 @@ impl HttpClientServiceFactory {
             configuration,
             &rustls::RootCertStore::empty(),
             http2,
+            None,
+            Default::default(),
+            None,
         )
         .unwrap();
 
@@ -0,0 +1,196 @@
+//! Bytes and time spent compressing subgraph requests and decompressing subgraph responses
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Poll;
+use std::time::Duration;
+use std::time::Instant;
+
+use bytes::Bytes;
+use futures::Stream;
+use http::header::CONTENT_ENCODING;
+use http::HeaderMap;
+use http_body::SizeHint;
+use pin_project_lite::pin_project;
+use tower::BoxError;
+use tower_http::decompression::DecompressionBody;
+
+use crate::axum_factory::compression::CompressionStats;
+
+/// Records the compression of a subgraph request body
+pub(crate) fn record_compression(subgraph_name: &str, encoding: &str, stats: CompressionStats) {
+    u64_histogram!(
+        "apollo.router.subgraph.request.compression.uncompressed_size",
+        "Size in bytes of subgraph request bodies before compression",
+        stats.uncompressed_bytes,
+        "subgraph.name" = subgraph_name.to_string(),
+        "compression" = encoding.to_string()
+    );
+    u64_histogram!(
+        "apollo.router.subgraph.request.compression.compressed_size",
+        "Size in bytes of subgraph request bodies after compression",
+        stats.compressed_bytes,
+        "subgraph.name" = subgraph_name.to_string(),
+        "compression" = encoding.to_string()
+    );
+    f64_histogram!(
+        "apollo.router.subgraph.request.compression.duration",
+        "Time spent compressing subgraph request bodies",
+        stats.duration.as_secs_f64(),
+        "subgraph.name" = subgraph_name.to_string(),
+        "compression" = encoding.to_string()
+    );
+}
+
+fn record_decompression(subgraph_name: &str, encoding: &str, stats: CompressionStats) {
+    u64_histogram!(
+        "apollo.router.subgraph.response.decompression.compressed_size",
+        "Size in bytes of subgraph response bodies before decompression",
+        stats.compressed_bytes,
+        "subgraph.name" = subgraph_name.to_string(),
+        "compression" = encoding.to_string()
+    );
+    u64_histogram!(
+        "apollo.router.subgraph.response.decompression.uncompressed_size",
+        "Size in bytes of subgraph response bodies after decompression",
+        stats.uncompressed_bytes,
+        "subgraph.name" = subgraph_name.to_string(),
+        "compression" = encoding.to_string()
+    );
+    f64_histogram!(
+        "apollo.router.subgraph.response.decompression.duration",
+        "Time spent decompressing subgraph response bodies",
+        stats.duration.as_secs_f64(),
+        "subgraph.name" = subgraph_name.to_string(),
+        "compression" = encoding.to_string()
+    );
+}
+
+/// Returns the encoding of a compressed body
+fn content_encoding(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(CONTENT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty() && value != "identity")
+}
+
+pin_project! {
+    /// Subgraph response body counting the bytes received, before they are decompressed
+    pub(crate) struct CompressedBody {
+        #[pin]
+        inner: hyper::Body,
+        encoding: Option<String>,
+        bytes: u64,
+    }
+}
+
+impl CompressedBody {
+    /// Wraps the body of a response, before the decompression layer removes its
+    /// `content-encoding` header
+    pub(crate) fn wrap(response: http::Response<hyper::Body>) -> http::Response<CompressedBody> {
+        let encoding = content_encoding(response.headers());
+        response.map(|inner| CompressedBody {
+            inner,
+            encoding,
+            bytes: 0,
+        })
+    }
+}
+
+impl http_body::Body for CompressedBody {
+    type Data = Bytes;
+    type Error = hyper::Error;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.project();
+        let poll = this.inner.poll_data(cx);
+        if let Poll::Ready(Some(Ok(data))) = &poll {
+            *this.bytes += data.len() as u64;
+        }
+        poll
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        self.project().inner.poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+pin_project! {
+    /// Decompressed subgraph response body, recording the decompression metrics once it is
+    /// entirely read
+    pub(crate) struct DecompressedBodyStream {
+        #[pin]
+        inner: DecompressionBody<CompressedBody>,
+        subgraph_name: Arc<String>,
+        uncompressed_bytes: u64,
+        duration: Duration,
+        recorded: bool,
+    }
+}
+
+impl DecompressedBodyStream {
+    pub(crate) fn new(
+        inner: DecompressionBody<CompressedBody>,
+        subgraph_name: Arc<String>,
+    ) -> Self {
+        Self {
+            inner,
+            subgraph_name,
+            uncompressed_bytes: 0,
+            duration: Duration::ZERO,
+            recorded: false,
+        }
+    }
+}
+
+impl Stream for DecompressedBodyStream {
+    type Item = Result<Bytes, BoxError>;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        use hyper::body::HttpBody;
+
+        let mut this = self.project();
+        let start = Instant::now();
+        let poll = this.inner.as_mut().poll_data(cx);
+        *this.duration += start.elapsed();
+
+        match &poll {
+            Poll::Ready(Some(Ok(data))) => *this.uncompressed_bytes += data.len() as u64,
+            Poll::Ready(None) if !*this.recorded => {
+                *this.recorded = true;
+                let compressed: &CompressedBody = DecompressionBody::get_ref(&this.inner);
+                if let Some(encoding) = &compressed.encoding {
+                    record_decompression(
+                        this.subgraph_name.as_str(),
+                        encoding,
+                        CompressionStats {
+                            uncompressed_bytes: *this.uncompressed_bytes,
+                            compressed_bytes: compressed.bytes,
+                            duration: *this.duration,
+                        },
+                    );
+                }
+            }
+            _ => {}
+        }
+        poll
+    }
+}
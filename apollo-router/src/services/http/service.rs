@@ -11,9 +11,11 @@ use futures::TryFutureExt;
 use global::get_text_map_propagator;
 use http::header::ACCEPT_ENCODING;
 use http::header::CONTENT_ENCODING;
+use http::header::CONTENT_TYPE;
 use http::HeaderValue;
+use http::Method;
 use http::Request;
-use hyper::client::HttpConnector;
+use http::Uri;
 use hyper_rustls::HttpsConnector;
 #[cfg(unix)]
 use hyperlocal::UnixConnector;
@@ -23,14 +25,21 @@ use rustls::ClientConfig;
 use rustls::RootCertStore;
 use schemars::JsonSchema;
 use tower::util::Either;
+use tower::util::MapResponse;
 use tower::BoxError;
 use tower::Service;
 use tower::ServiceBuilder;
+use tower::ServiceExt;
 use tower_http::decompression::Decompression;
 use tower_http::decompression::DecompressionBody;
 use tower_http::decompression::DecompressionLayer;
 use tracing::Instrument;
 
+use super::compression_metrics::record_compression;
+use super::compression_metrics::CompressedBody;
+use super::compression_metrics::DecompressedBodyStream;
+use super::proxy::ProxyConfig;
+use super::proxy::ProxyConnector;
 use super::HttpRequest;
 use super::HttpResponse;
 use crate::axum_factory::compression::Compressor;
@@ -43,16 +52,21 @@ use crate::plugins::telemetry::reload::prepare_context;
 use crate::plugins::telemetry::LOGGING_DISPLAY_BODY;
 use crate::plugins::telemetry::LOGGING_DISPLAY_HEADERS;
 use crate::plugins::traffic_shaping::Http2Config;
+use crate::services::router::body::get_body_bytes;
 use crate::services::router::body::RouterBody;
 use crate::services::trust_dns_connector::new_async_http_connector;
-use crate::services::trust_dns_connector::AsyncHyperResolver;
+use crate::services::trust_dns_connector::DnsConfig;
 use crate::Configuration;
 use crate::Context;
 
-type HTTPClient =
-    Decompression<hyper::Client<HttpsConnector<HttpConnector<AsyncHyperResolver>>, RouterBody>>;
+/// Counts the bytes of compressed responses before they are decompressed
+type CountingClient<C> = MapResponse<
+    hyper::Client<C, RouterBody>,
+    fn(http::Response<hyper::Body>) -> http::Response<CompressedBody>,
+>;
+type HTTPClient = Decompression<CountingClient<HttpsConnector<ProxyConnector>>>;
 #[cfg(unix)]
-type UnixHTTPClient = Decompression<hyper::Client<UnixConnector, RouterBody>>;
+type UnixHTTPClient = Decompression<CountingClient<UnixConnector>>;
 #[cfg(unix)]
 type MixedClient = Either<HTTPClient, UnixHTTPClient>;
 #[cfg(not(unix))]
@@ -62,6 +76,7 @@ type MixedClient = HTTPClient;
 #[allow(clippy::declare_interior_mutable_const)]
 static ACCEPTED_ENCODINGS: HeaderValue = HeaderValue::from_static("gzip, br, deflate");
 const POOL_IDLE_TIMEOUT_DURATION: Option<Duration> = Some(Duration::from_secs(5));
+const WARMUP_QUERY: &str = r#"{"query":"query ApolloRouterWarmup { __typename }"}"#;
 
 #[derive(PartialEq, Debug, Clone, Deserialize, JsonSchema, Copy)]
 #[serde(rename_all = "lowercase")]
@@ -104,6 +119,9 @@ impl HttpClientService {
         configuration: &Configuration,
         tls_root_store: &RootCertStore,
         http2: Http2Config,
+        proxy: Option<ProxyConfig>,
+        dns: DnsConfig,
+        pool_idle_timeout: Option<Duration>,
     ) -> Result<Self, BoxError> {
         let name: String = service.into();
         let tls_cert_store = configuration
@@ -131,18 +149,29 @@ impl HttpClientService {
 
         let tls_client_config = generate_tls_client_config(tls_cert_store, client_cert_config)?;
 
-        HttpClientService::new(name, http2, tls_client_config)
+        HttpClientService::new(
+            name,
+            http2,
+            tls_client_config,
+            proxy,
+            dns,
+            pool_idle_timeout,
+        )
     }
 
     pub(crate) fn new(
         service: impl Into<String>,
         http2: Http2Config,
         tls_config: ClientConfig,
+        proxy: Option<ProxyConfig>,
+        dns: DnsConfig,
+        pool_idle_timeout: Option<Duration>,
     ) -> Result<Self, BoxError> {
-        let mut http_connector = new_async_http_connector()?;
+        let mut http_connector = new_async_http_connector(&dns)?;
         http_connector.set_nodelay(true);
         http_connector.set_keepalive(Some(std::time::Duration::from_secs(60)));
         http_connector.enforce_http(false);
+        let http_connector = ProxyConnector::new(http_connector, proxy.as_ref())?;
 
         let builder = hyper_rustls::HttpsConnectorBuilder::new()
             .with_tls_config(tls_config)
@@ -156,21 +185,46 @@ impl HttpClientService {
         };
 
         let http_client = hyper::Client::builder()
-            .pool_idle_timeout(POOL_IDLE_TIMEOUT_DURATION)
+            .pool_idle_timeout(pool_idle_timeout.or(POOL_IDLE_TIMEOUT_DURATION))
             .http2_only(http2 == Http2Config::Http2Only)
             .build(connector);
         Ok(Self {
             http_client: ServiceBuilder::new()
                 .layer(DecompressionLayer::new())
+                .map_response(CompressedBody::wrap as fn(_) -> _)
                 .service(http_client),
             #[cfg(unix)]
             unix_client: ServiceBuilder::new()
                 .layer(DecompressionLayer::new())
+                .map_response(CompressedBody::wrap as fn(_) -> _)
                 .service(hyper::Client::builder().build(UnixConnector)),
             service: Arc::new(service.into()),
         })
     }
 
+    /// Sends a `{ __typename }` query to the subgraph, so that the DNS resolution, the TCP
+    /// connection and the TLS handshake are done before the first client request.
+    ///
+    /// The response is ignored: the connection is established even if the subgraph rejects
+    /// the query.
+    pub(crate) async fn warm_up(&self, uri: Uri) -> Result<(), BoxError> {
+        let http_request = http::Request::builder()
+            .method(Method::POST)
+            .uri(uri)
+            .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
+            .body(RouterBody::from(WARMUP_QUERY))?;
+        let response = self
+            .clone()
+            .oneshot(HttpRequest {
+                http_request,
+                context: Context::new(),
+            })
+            .await?;
+        // the body must be read for the connection to go back to the pool
+        get_body_bytes(response.http_response.into_body()).await?;
+        Ok(())
+    }
+
     pub(crate) fn native_roots_store() -> RootCertStore {
         let mut roots = rustls::RootCertStore::empty();
         let mut valid_count = 0;
@@ -285,7 +339,13 @@ impl tower::Service<HttpRequest> for HttpClientService {
 
         let body = match opt_compressor {
             None => body,
-            Some(compressor) => RouterBody::wrap_stream(compressor.process(body)),
+            Some(compressor) => {
+                let subgraph_name = service_name.clone();
+                let encoding = compressor.content_encoding();
+                RouterBody::wrap_stream(compressor.process_with_stats(body, move |stats| {
+                    record_compression(&subgraph_name, encoding, stats)
+                }))
+            }
         };
         let mut http_request = http::Request::from_parts(parts, body);
 
@@ -335,7 +395,7 @@ impl tower::Service<HttpRequest> for HttpClientService {
 async fn do_fetch(
     mut client: MixedClient,
     context: &Context,
-    service_name: &str,
+    service_name: &Arc<String>,
     request: Request<RouterBody>,
 ) -> Result<http::Response<RouterBody>, FetchError> {
     let _active_request_guard = context.enter_active_request();
@@ -353,7 +413,7 @@ async fn do_fetch(
         .into_parts();
     Ok(http::Response::from_parts(
         parts,
-        RouterBody::wrap_stream(BodyStream { inner: body }),
+        RouterBody::wrap_stream(DecompressedBodyStream::new(body, service_name.clone())),
     ))
 }
 
@@ -39,6 +39,7 @@ use crate::configuration::load_key;
 use crate::configuration::TlsClient;
 use crate::configuration::TlsClientAuth;
 use crate::graphql::Response;
+use crate::metrics::FutureMetricsExt;
 use crate::plugin::PluginInit;
 use crate::plugin::PluginPrivate;
 use crate::plugins::traffic_shaping::Http2Config;
@@ -117,6 +118,8 @@ async fn tls_self_signed() {
         &config,
         &rustls::RootCertStore::empty(),
         Http2Config::Enable,
+        None,
+        Default::default(),
     )
     .unwrap();
 
@@ -174,6 +177,8 @@ async fn tls_custom_root() {
         &config,
         &rustls::RootCertStore::empty(),
         Http2Config::Enable,
+        None,
+        Default::default(),
     )
     .unwrap();
 
@@ -284,6 +289,8 @@ async fn tls_client_auth() {
         &config,
         &rustls::RootCertStore::empty(),
         Http2Config::Enable,
+        None,
+        Default::default(),
     )
     .unwrap();
 
@@ -348,6 +355,9 @@ async fn test_subgraph_h2c() {
             .with_safe_defaults()
             .with_native_roots()
             .with_no_client_auth(),
+        None,
+        Default::default(),
+        None,
     )
     .expect("can create a HttpService");
 
@@ -424,6 +434,9 @@ async fn test_compressed_request_response_body() {
             .with_safe_defaults()
             .with_native_roots()
             .with_no_client_auth(),
+        None,
+        Default::default(),
+        None,
     )
     .expect("can create a HttpService");
 
@@ -452,6 +465,65 @@ async fn test_compressed_request_response_body() {
     );
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_decompression_metrics() {
+    async {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let socket_addr = listener.local_addr().unwrap();
+        tokio::task::spawn(emulate_subgraph_compressed_response(listener));
+        let subgraph_service = HttpClientService::new(
+            "test",
+            Http2Config::Http2Only,
+            rustls::ClientConfig::builder()
+                .with_safe_defaults()
+                .with_native_roots()
+                .with_no_client_auth(),
+            None,
+            Default::default(),
+            None,
+        )
+        .expect("can create a HttpService");
+
+        let url = Uri::from_str(&format!("http://{socket_addr}")).unwrap();
+        let response = subgraph_service
+            .oneshot(HttpRequest {
+                http_request: http::Request::builder()
+                    .uri(url)
+                    .header(CONTENT_TYPE, APPLICATION_JSON.essence_str())
+                    .header(CONTENT_ENCODING, "gzip")
+                    .body(r#"{"query":"{ me { name username } }"#.into())
+                    .unwrap(),
+                context: Context::new(),
+            })
+            .await
+            .unwrap();
+        get_body_bytes(response.http_response.into_body())
+            .await
+            .unwrap();
+
+        assert_histogram_sum!(
+            "apollo.router.subgraph.response.decompression.uncompressed_size",
+            r#"{"data":"test"}"#.len(),
+            "subgraph.name" = "test",
+            "compression" = "gzip"
+        );
+        assert_histogram_exists!(
+            "apollo.router.subgraph.response.decompression.compressed_size",
+            u64,
+            "subgraph.name" = "test",
+            "compression" = "gzip"
+        );
+        assert_histogram_exists!(
+            "apollo.router.subgraph.response.decompression.duration",
+            f64,
+            "subgraph.name" = "test",
+            "compression" = "gzip"
+        );
+    }
+    .with_metrics()
+    .await;
+}
+
 const SCHEMA: &str = r#"schema
         @core(feature: "https://specs.apollo.dev/core/v0.1")
         @core(feature: "https://specs.apollo.dev/join/v0.1")
@@ -636,3 +708,56 @@ async fn test_unix_socket() {
         .unwrap();
     insta::assert_json_snapshot!(response);
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_warm_up() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let socket_addr = listener.local_addr().unwrap();
+    let received = Arc::new(AtomicBool::new(false));
+    let received_2 = received.clone();
+    tokio::task::spawn(async move {
+        let make_svc = make_service_fn(move |_conn| {
+            let received = received_2.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request: http::Request<Body>| {
+                    let received = received.clone();
+                    async move {
+                        let body = get_body_bytes(request.into_body()).await.unwrap();
+                        assert!(std::str::from_utf8(&body).unwrap().contains("__typename"));
+                        received.store(true, Ordering::SeqCst);
+                        Ok::<_, Infallible>(
+                            http::Response::builder()
+                                .status(StatusCode::BAD_REQUEST)
+                                .body(Body::empty())
+                                .unwrap(),
+                        )
+                    }
+                }))
+            }
+        });
+        Server::from_tcp(listener)
+            .unwrap()
+            .serve(make_svc)
+            .await
+            .unwrap();
+    });
+    let subgraph_service = HttpClientService::new(
+        "test",
+        Http2Config::Enable,
+        rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_native_roots()
+            .with_no_client_auth(),
+        None,
+        Default::default(),
+        None,
+    )
+    .expect("can create a HttpService");
+
+    // the response status is ignored, only the connection matters
+    subgraph_service
+        .warm_up(Uri::from_str(&format!("http://{socket_addr}")).unwrap())
+        .await
+        .unwrap();
+    assert!(received.load(Ordering::SeqCst));
+}
@@ -13,6 +13,7 @@ use sha2::Digest;
 use sha2::Sha256;
 
 use crate::cache::DeduplicatingCache;
+use crate::plugins::telemetry::formatters::in_current_otel_context;
 use crate::services::SupergraphRequest;
 use crate::services::SupergraphResponse;
 
@@ -90,9 +91,9 @@ async fn apq_request(
                 let _ = request.context.insert("persisted_query_register", true);
                 let query = query.to_owned();
                 let cache = cache.clone();
-                tokio::spawn(async move {
+                tokio::spawn(in_current_otel_context(async move {
                     cache.insert(redis_key(&query_hash), query).await;
-                });
+                }));
                 Ok(request)
             } else {
                 tracing::debug!("apq: graphql request doesn't match provided sha256Hash");
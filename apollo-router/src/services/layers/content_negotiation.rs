@@ -18,10 +18,13 @@ use tower::Layer;
 use tower::Service;
 use tower::ServiceExt;
 
+use crate::axum_factory::websocket::WebSocketOperation;
 use crate::graphql;
 use crate::layers::sync_checkpoint::CheckpointService;
 use crate::layers::ServiceExt as _;
 use crate::services::router;
+use crate::services::router::service::JSON_ARRAY_DEFER_CONTENT_TYPE_HEADER_VALUE;
+use crate::services::router::service::JSON_LINES_DEFER_CONTENT_TYPE_HEADER_VALUE;
 use crate::services::router::service::MULTIPART_DEFER_CONTENT_TYPE_HEADER_VALUE;
 use crate::services::router::service::MULTIPART_SUBSCRIPTION_CONTENT_TYPE_HEADER_VALUE;
 use crate::services::router::ClientRequestAccepts;
@@ -49,7 +52,13 @@ where
     fn layer(&self, service: S) -> Self::Service {
         CheckpointService::new(
             move |req| {
+                // the body of operations sent over WebSocket is built by the router
+                let from_websocket = req
+                    .context
+                    .extensions()
+                    .with_lock(|lock| lock.contains_key::<WebSocketOperation>());
                 if req.router_request.method() != Method::GET
+                    && !from_websocket
                     && !content_type_is_json(req.router_request.headers())
                 {
                     let response: http::Response<crate::services::router::Body> = http::Response::builder()
@@ -89,7 +98,7 @@ where
                 let accepts = parse_accept(req.router_request.headers());
 
                 if accepts.wildcard
-                    || accepts.multipart_defer
+                    || accepts.accepts_defer()
                     || accepts.multipart_subscription
                     || accepts.json
                 {
@@ -145,6 +154,8 @@ where
                     json: accepts_json,
                     multipart_defer: accepts_multipart_defer,
                     multipart_subscription: accepts_multipart_subscription,
+                    json_array_defer: accepts_json_array_defer,
+                    json_lines_defer: accepts_json_lines_defer,
                 } = context.extensions().with_lock(|lock| {
                     lock.get::<ClientRequestAccepts>()
                         .cloned()
@@ -165,6 +176,16 @@ where
                         CONTENT_TYPE,
                         MULTIPART_SUBSCRIPTION_CONTENT_TYPE_HEADER_VALUE.clone(),
                     );
+                } else if accepts_json_lines_defer {
+                    parts.headers.insert(
+                        CONTENT_TYPE,
+                        JSON_LINES_DEFER_CONTENT_TYPE_HEADER_VALUE.clone(),
+                    );
+                } else if accepts_json_array_defer {
+                    parts.headers.insert(
+                        CONTENT_TYPE,
+                        JSON_ARRAY_DEFER_CONTENT_TYPE_HEADER_VALUE.clone(),
+                    );
                 }
                 (parts, res)
             })
@@ -217,14 +238,23 @@ fn parse_accept(headers: &HeaderMap) -> ClientRequestAccepts {
                     if !accepts.wildcard && (mime.ty == _STAR && mime.subty == _STAR) {
                         accepts.wildcard = true
                     }
-                    if !accepts.multipart_defer && (mime.ty == MULTIPART && mime.subty == MIXED) {
-                        let parameter = mediatype::Name::new(MULTIPART_DEFER_SPEC_PARAMETER)
-                            .expect("valid name");
-                        let value =
-                            mediatype::Value::new(MULTIPART_DEFER_SPEC_VALUE).expect("valid value");
-                        if mime.get_param(parameter) == Some(value) {
-                            accepts.multipart_defer = true
-                        }
+                    if !accepts.multipart_defer
+                        && (mime.ty == MULTIPART && mime.subty == MIXED)
+                        && accepts_defer_spec(&mime)
+                    {
+                        accepts.multipart_defer = true
+                    }
+                    if !accepts.json_array_defer
+                        && (mime.ty == APPLICATION && mime.subty == JSON)
+                        && accepts_defer_spec(&mime)
+                    {
+                        accepts.json_array_defer = true
+                    }
+                    if !accepts.json_lines_defer
+                        && (mime.ty == APPLICATION && mime.subty.as_str() == "jsonl")
+                        && accepts_defer_spec(&mime)
+                    {
+                        accepts.json_lines_defer = true
                     }
                     if !accepts.multipart_subscription
                         && (mime.ty == MULTIPART && mime.subty == MIXED)
@@ -247,11 +277,20 @@ fn parse_accept(headers: &HeaderMap) -> ClientRequestAccepts {
     accepts
 }
 
+/// Returns true if the media type has the supported `deferSpec` parameter
+fn accepts_defer_spec(mime: &mediatype::MediaType<'_>) -> bool {
+    let parameter = mediatype::Name::new(MULTIPART_DEFER_SPEC_PARAMETER).expect("valid name");
+    let value = mediatype::Value::new(MULTIPART_DEFER_SPEC_VALUE).expect("valid value");
+    mime.get_param(parameter) == Some(value)
+}
+
 #[cfg(test)]
 mod tests {
     use http::HeaderValue;
 
     use super::*;
+    use crate::services::JSON_ARRAY_DEFER_ACCEPT;
+    use crate::services::JSON_LINES_DEFER_ACCEPT;
 
     #[test]
     fn it_checks_accept_header() {
@@ -293,5 +332,23 @@ mod tests {
         default_headers.append(ACCEPT, HeaderValue::from_static(MULTIPART_DEFER_ACCEPT));
         let accepts = parse_accept(&default_headers);
         assert!(accepts.multipart_defer);
+
+        let mut default_headers = HeaderMap::new();
+        default_headers.insert(ACCEPT, HeaderValue::from_static(JSON_ARRAY_DEFER_ACCEPT));
+        let accepts = parse_accept(&default_headers);
+        assert!(accepts.json);
+        assert!(accepts.json_array_defer);
+        assert!(!accepts.multipart_defer);
+
+        let mut default_headers = HeaderMap::new();
+        default_headers.insert(ACCEPT, HeaderValue::from_static(JSON_LINES_DEFER_ACCEPT));
+        let accepts = parse_accept(&default_headers);
+        assert!(accepts.json_lines_defer);
+        assert!(accepts.accepts_defer());
+
+        let mut default_headers = HeaderMap::new();
+        default_headers.insert(ACCEPT, HeaderValue::from_static("application/jsonl"));
+        let accepts = parse_accept(&default_headers);
+        assert!(!accepts.json_lines_defer);
     }
 }
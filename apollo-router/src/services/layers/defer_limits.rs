@@ -0,0 +1,201 @@
+//! Limits on the `@defer` directives honored in an operation
+//!
+//! Deferred fragments over the configured maximum, and all deferred fragments of operations sent
+//! by clients for which defer is disabled, are executed inline: their `@defer` directive is
+//! removed from the operation before it is parsed and planned.
+
+use apollo_compiler::ast;
+use lru::LruCache;
+use parking_lot::Mutex;
+
+use crate::plugins::telemetry::CLIENT_NAME;
+use crate::spec::query::subselections::DEFER_DIRECTIVE_NAME;
+use crate::Configuration;
+use crate::Context;
+
+/// Removes the `@defer` directives that are not honored, keeping the result for operations
+/// already seen
+pub(crate) struct DeferLimiter {
+    max_deferred_fragments: Option<usize>,
+    disabled_for_clients: Vec<String>,
+    parser_max_recursion: usize,
+    parser_max_tokens: usize,
+    /// rewritten operations, by query and by whether all deferred fragments are inlined
+    cache: Mutex<LruCache<(String, bool), Option<String>>>,
+}
+
+impl DeferLimiter {
+    /// Creates the limiter from the supergraph configuration, if defer limits are configured
+    pub(crate) fn from_configuration(configuration: &Configuration) -> Option<Self> {
+        let supergraph = &configuration.supergraph;
+        let limits = &supergraph.defer_limits;
+        if !supergraph.defer_support
+            || (limits.max_deferred_fragments.is_none() && limits.disabled_for_clients.is_empty())
+        {
+            return None;
+        }
+        Some(Self {
+            max_deferred_fragments: limits.max_deferred_fragments,
+            disabled_for_clients: limits.disabled_for_clients.clone(),
+            parser_max_recursion: configuration.limits.parser_max_recursion,
+            parser_max_tokens: configuration.limits.parser_max_tokens,
+            cache: Mutex::new(LruCache::new(
+                supergraph.query_planning.cache.in_memory.limit,
+            )),
+        })
+    }
+
+    /// Returns the operation without the `@defer` directives that are not honored, or `None` if
+    /// all of them are honored
+    pub(crate) fn limit(&self, context: &Context, query: &str) -> Option<String> {
+        // cheap check for the vast majority of operations
+        if !query.contains("@defer") {
+            return None;
+        }
+        let inline_all = context
+            .get::<_, String>(CLIENT_NAME)
+            .ok()
+            .flatten()
+            .is_some_and(|client_name| self.disabled_for_clients.contains(&client_name));
+        let max = if inline_all {
+            0
+        } else {
+            self.max_deferred_fragments?
+        };
+
+        let key = (query.to_string(), inline_all);
+        let cached = self.cache.lock().get(&key).cloned();
+        let limited = match cached {
+            Some(limited) => limited,
+            None => {
+                let limited = self.limit_document(query, max);
+                self.cache.lock().put(key, limited.clone());
+                limited
+            }
+        };
+        if limited.is_some() {
+            u64_counter!(
+                "apollo.router.operations.defer.inlined",
+                "Number of operations with deferred fragments executed inline because of defer limits",
+                1,
+                "inline_all" = inline_all
+            );
+        }
+        limited
+    }
+
+    fn limit_document(&self, query: &str, max: usize) -> Option<String> {
+        // invalid operations are left as is, they will be rejected by validation
+        let mut document = apollo_compiler::parser::Parser::new()
+            .recursion_limit(self.parser_max_recursion)
+            .token_limit(self.parser_max_tokens)
+            .parse_ast(query, "query.graphql")
+            .ok()?;
+
+        let mut seen = 0;
+        let mut removed = false;
+        for definition in &mut document.definitions {
+            let selection_set = match definition {
+                ast::Definition::OperationDefinition(operation) => {
+                    &mut operation.make_mut().selection_set
+                }
+                ast::Definition::FragmentDefinition(fragment) => {
+                    &mut fragment.make_mut().selection_set
+                }
+                _ => continue,
+            };
+            limit_selection_set(selection_set, max, &mut seen, &mut removed);
+        }
+
+        removed.then(|| document.to_string())
+    }
+}
+
+/// Counts the deferred fragments in document order, and removes the `@defer` directive of those
+/// over the maximum
+fn limit_selection_set(
+    selection_set: &mut [ast::Selection],
+    max: usize,
+    seen: &mut usize,
+    removed: &mut bool,
+) {
+    for selection in selection_set {
+        let (directives, selection_set) = match selection {
+            ast::Selection::Field(field) => {
+                limit_selection_set(&mut field.make_mut().selection_set, max, seen, removed);
+                continue;
+            }
+            ast::Selection::FragmentSpread(spread) => (&mut spread.make_mut().directives, None),
+            ast::Selection::InlineFragment(fragment) => {
+                let fragment = fragment.make_mut();
+                (&mut fragment.directives, Some(&mut fragment.selection_set))
+            }
+        };
+        if directives.get(DEFER_DIRECTIVE_NAME).is_some() {
+            *seen += 1;
+            if *seen > max {
+                directives.retain(|directive| directive.name != DEFER_DIRECTIVE_NAME);
+                *removed = true;
+            }
+        }
+        if let Some(selection_set) = selection_set {
+            limit_selection_set(selection_set, max, seen, removed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::DeferLimits;
+    use crate::configuration::Supergraph;
+
+    fn limiter(defer_limits: DeferLimits) -> DeferLimiter {
+        let configuration = Configuration::builder()
+            .supergraph(
+                Supergraph::fake_builder()
+                    .defer_limits(defer_limits)
+                    .build(),
+            )
+            .build()
+            .unwrap();
+        DeferLimiter::from_configuration(&configuration).unwrap()
+    }
+
+    const QUERY: &str = "query { me { id ... @defer { name } ...Reviews @defer } } fragment Reviews on User { reviews { ... @defer { body } } }";
+
+    #[test]
+    fn it_inlines_deferred_fragments_over_the_limit() {
+        let limited = limiter(DeferLimits {
+            max_deferred_fragments: Some(1),
+            ..Default::default()
+        })
+        .limit(&Context::new(), QUERY)
+        .unwrap();
+        // only the first deferred fragment in document order is kept
+        assert_eq!(limited.matches("@defer").count(), 1);
+        assert!(limited.find("@defer") < limited.find("name"));
+
+        let limiter = limiter(DeferLimits {
+            max_deferred_fragments: Some(3),
+            ..Default::default()
+        });
+        assert!(limiter.limit(&Context::new(), QUERY).is_none());
+    }
+
+    #[test]
+    fn it_disables_defer_for_clients() {
+        let limiter = limiter(DeferLimits {
+            disabled_for_clients: vec!["legacy-ios".to_string()],
+            ..Default::default()
+        });
+        assert!(limiter.limit(&Context::new(), QUERY).is_none());
+
+        let context = Context::new();
+        context
+            .insert(CLIENT_NAME, "legacy-ios".to_string())
+            .unwrap();
+        let limited = limiter.limit(&context, QUERY).unwrap();
+        assert!(!limited.contains("@defer"));
+    }
+}
@@ -2,6 +2,7 @@
 pub(crate) mod allow_only_http_post_mutations;
 pub(crate) mod apq;
 pub(crate) mod content_negotiation;
+pub(crate) mod defer_limits;
 pub(crate) mod persisted_queries;
 pub(crate) mod query_analysis;
 pub(crate) mod static_page;
@@ -10,6 +10,10 @@ impl PersistedQueryIdExtractor {
     pub(crate) fn extract_id(request: &SupergraphRequest) -> Option<String> {
         PersistedQuery::maybe_from_request(request).map(|pq| pq.sha256hash)
     }
+
+    pub(crate) fn extract_document_id(request: &SupergraphRequest) -> Option<String> {
+        request.supergraph_request.body().document_id.clone()
+    }
 }
 
 #[cfg(test)]
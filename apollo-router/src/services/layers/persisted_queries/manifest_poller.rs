@@ -14,6 +14,8 @@ use tokio::fs::read_to_string;
 use tokio::sync::mpsc;
 use tower::BoxError;
 
+use crate::cache::store;
+use crate::cache::store::Store;
 use crate::uplink::persisted_queries_manifest_stream::MaybePersistedQueriesManifestChunks;
 use crate::uplink::persisted_queries_manifest_stream::PersistedQueriesManifestChunk;
 use crate::uplink::persisted_queries_manifest_stream::PersistedQueriesManifestQuery;
@@ -231,7 +233,7 @@ impl PersistedQueryManifestPoller {
                             .into()
                         })?;
 
-                let manifest_file: SignedUrlChunk =
+                let manifest_file: LocalManifest =
                     serde_json::from_str(&local_manifest).map_err(|e| -> BoxError {
                         format!(
                             "could not parse local persisted query list file {}: {}",
@@ -241,16 +243,23 @@ impl PersistedQueryManifestPoller {
                         .into()
                     })?;
 
-                if manifest_file.format != "apollo-persisted-query-manifest" {
-                    return Err("chunk format is not 'apollo-persisted-query-manifest'".into());
-                }
+                match manifest_file {
+                    LocalManifest::Apollo(manifest_file) => {
+                        if manifest_file.format != "apollo-persisted-query-manifest" {
+                            return Err(
+                                "chunk format is not 'apollo-persisted-query-manifest'".into()
+                            );
+                        }
 
-                if manifest_file.version != 1 {
-                    return Err("persisted query manifest chunk version is not 1".into());
-                }
+                        if manifest_file.version != 1 {
+                            return Err("persisted query manifest chunk version is not 1".into());
+                        }
 
-                for operation in manifest_file.operations {
-                    manifest.insert(operation.id, operation.body);
+                        for operation in manifest_file.operations {
+                            manifest.insert(operation.id, operation.body);
+                        }
+                    }
+                    LocalManifest::Documents(documents) => manifest.extend(documents),
                 }
             }
 
@@ -308,6 +317,19 @@ impl PersistedQueryManifestPoller {
                 ).into()
             })?;
 
+            let manifest_store = match config.persisted_queries.experimental_manifest_store.clone()
+            {
+                Some(manifest_store) => {
+                    store::from_configuration(
+                        manifest_store.redis,
+                        manifest_store.filesystem,
+                        "persisted query list",
+                    )
+                    .await?
+                }
+                None => None,
+            };
+
             let (_drop_signal, drop_receiver) = mpsc::channel::<()>(1);
             let (ready_sender, mut ready_receiver) =
                 mpsc::channel::<ManifestPollResultOnStartup>(1);
@@ -320,6 +342,7 @@ impl PersistedQueryManifestPoller {
                 ready_sender,
                 drop_receiver,
                 http_client,
+                manifest_store,
             ));
 
             // wait for the uplink poller to report its first success and continue
@@ -414,7 +437,12 @@ async fn poll_uplink(
     ready_sender: mpsc::Sender<ManifestPollResultOnStartup>,
     mut drop_receiver: mpsc::Receiver<()>,
     http_client: Client,
+    manifest_store: Option<Arc<dyn Store>>,
 ) {
+    let manifest_key = format!(
+        "persisted_queries:manifest:{}",
+        uplink_config.apollo_graph_ref
+    );
     let http_client = http_client.clone();
     let mut uplink_executor = stream::select_all(vec![
         stream_from_uplink_transforming_new_response::<
@@ -466,39 +494,12 @@ async fn poll_uplink(
     while let Some(event) = uplink_executor.next().await {
         match event {
             ManifestPollEvent::NewManifest(new_manifest) => {
-                let freeform_graphql_behavior = if config.persisted_queries.safelist.enabled {
-                    if config.persisted_queries.safelist.require_id {
-                        FreeformGraphQLBehavior::DenyAll {
-                            log_unknown: config.persisted_queries.log_unknown,
-                        }
-                    } else {
-                        FreeformGraphQLBehavior::AllowIfInSafelist {
-                            safelist: FreeformGraphQLSafelist::new(&new_manifest),
-                            log_unknown: config.persisted_queries.log_unknown,
-                        }
-                    }
-                } else if config.persisted_queries.log_unknown {
-                    FreeformGraphQLBehavior::LogUnlessInSafelist {
-                        safelist: FreeformGraphQLSafelist::new(&new_manifest),
-                        apq_enabled: config.apq.enabled,
-                    }
-                } else {
-                    FreeformGraphQLBehavior::AllowAll {
-                        apq_enabled: config.apq.enabled,
-                    }
-                };
-
-                let new_state = PersistedQueryManifestPollerState {
-                    persisted_query_manifest: new_manifest,
-                    freeform_graphql_behavior,
-                };
-
-                state
-                    .write()
-                    .map(|mut locked_state| {
-                        *locked_state = new_state;
-                    })
-                    .expect("could not acquire write lock on persisted query manifest state");
+                if let Some(manifest_store) = &manifest_store {
+                    manifest_store
+                        .set_json(&manifest_key, &new_manifest, None)
+                        .await;
+                }
+                update_state(&state, &config, new_manifest);
 
                 send_startup_event_or_log_error(
                     &mut ready_sender_once,
@@ -507,11 +508,36 @@ async fn poll_uplink(
                 .await;
             }
             ManifestPollEvent::Err(e) => {
-                send_startup_event_or_log_error(
-                    &mut ready_sender_once,
-                    ManifestPollResultOnStartup::Err(e),
-                )
-                .await
+                // without GraphOS at startup, the router can start with the last list it stored
+                let stored_manifest = match (&manifest_store, &ready_sender_once) {
+                    (Some(manifest_store), Some(_)) => {
+                        manifest_store
+                            .get_json::<PersistedQueryManifest>(&manifest_key)
+                            .await
+                    }
+                    _ => None,
+                };
+                match stored_manifest {
+                    Some(stored_manifest) => {
+                        tracing::warn!(
+                            "could not fetch the persisted query list, starting with the stored one: {}",
+                            e
+                        );
+                        update_state(&state, &config, stored_manifest);
+                        send_startup_event_or_log_error(
+                            &mut ready_sender_once,
+                            ManifestPollResultOnStartup::LoadedOperations,
+                        )
+                        .await
+                    }
+                    None => {
+                        send_startup_event_or_log_error(
+                            &mut ready_sender_once,
+                            ManifestPollResultOnStartup::Err(e),
+                        )
+                        .await
+                    }
+                }
             }
             ManifestPollEvent::NoPersistedQueryList { graph_ref } => {
                 send_startup_event_or_log_error(
@@ -554,6 +580,46 @@ async fn poll_uplink(
     }
 }
 
+fn update_state(
+    state: &RwLock<PersistedQueryManifestPollerState>,
+    config: &Configuration,
+    new_manifest: PersistedQueryManifest,
+) {
+    let freeform_graphql_behavior = if config.persisted_queries.safelist.enabled {
+        if config.persisted_queries.safelist.require_id {
+            FreeformGraphQLBehavior::DenyAll {
+                log_unknown: config.persisted_queries.log_unknown,
+            }
+        } else {
+            FreeformGraphQLBehavior::AllowIfInSafelist {
+                safelist: FreeformGraphQLSafelist::new(&new_manifest),
+                log_unknown: config.persisted_queries.log_unknown,
+            }
+        }
+    } else if config.persisted_queries.log_unknown {
+        FreeformGraphQLBehavior::LogUnlessInSafelist {
+            safelist: FreeformGraphQLSafelist::new(&new_manifest),
+            apq_enabled: config.apq.enabled,
+        }
+    } else {
+        FreeformGraphQLBehavior::AllowAll {
+            apq_enabled: config.apq.enabled,
+        }
+    };
+
+    let new_state = PersistedQueryManifestPollerState {
+        persisted_query_manifest: new_manifest,
+        freeform_graphql_behavior,
+    };
+
+    state
+        .write()
+        .map(|mut locked_state| {
+            *locked_state = new_state;
+        })
+        .expect("could not acquire write lock on persisted query manifest state");
+}
+
 async fn manifest_from_chunks(
     new_chunks: Vec<PersistedQueriesManifestChunk>,
     http_client: Client,
@@ -672,6 +738,17 @@ pub(crate) struct SignedUrlChunk {
     pub(crate) operations: Vec<Operation>,
 }
 
+/// The format of a local persisted query list file.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum LocalManifest {
+    /// A manifest generated by the Apollo tooling
+    Apollo(SignedUrlChunk),
+    /// A map of document IDs to operation bodies, as written by the Relay compiler
+    /// (`persistConfig.file`) or the persisted documents of GraphQL Code Generator
+    Documents(HashMap<String, String>),
+}
+
 /// A single operation containing an ID and a body,
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub(crate) struct Operation {
@@ -685,7 +762,9 @@ mod tests {
 
     use super::*;
     use crate::configuration::Apq;
+    use crate::configuration::FileSystemCache;
     use crate::configuration::PersistedQueries;
+    use crate::configuration::PersistedQueriesManifestStore;
     use crate::configuration::PersistedQueriesSafelist;
     use crate::test_harness::mocks::persisted_queries::*;
     use crate::uplink::Endpoints;
@@ -720,6 +799,50 @@ mod tests {
         .is_err());
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn poller_starts_with_the_stored_manifest_without_uplink_connection() {
+        let directory = tempfile::tempdir().unwrap();
+        let manifest_store = PersistedQueriesManifestStore {
+            redis: None,
+            filesystem: Some(FileSystemCache {
+                path: directory.path().to_path_buf(),
+                ttl: None,
+                max_size: None,
+                sweep_interval: None,
+            }),
+        };
+        let persisted_queries = PersistedQueries::builder()
+            .enabled(true)
+            .experimental_manifest_store(manifest_store)
+            .build();
+
+        let (id, body, manifest) = fake_manifest();
+        let (_mock_guard, uplink_config) = mock_pq_uplink(&manifest).await;
+        PersistedQueryManifestPoller::new(
+            Configuration::fake_builder()
+                .uplink(uplink_config)
+                .persisted_query(persisted_queries.clone())
+                .build()
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+        let uplink_endpoint = Url::parse("https://definitely.not.uplink").unwrap();
+        let manifest_manager = PersistedQueryManifestPoller::new(
+            Configuration::fake_builder()
+                .uplink(UplinkConfig::for_tests(Endpoints::fallback(vec![
+                    uplink_endpoint,
+                ])))
+                .persisted_query(persisted_queries)
+                .build()
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(manifest_manager.get_operation_body(&id), Some(body))
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn poller_fails_over_on_gcs_failure() {
         let (_mock_server1, url1) = mock_pq_uplink_bad_gcs().await;
@@ -791,6 +914,7 @@ mod tests {
                     Some(vec![
                         "tests/fixtures/persisted-queries-manifest.json".to_string()
                     ]),
+                    Some(false),
                 ))
                 .build()
                 .unwrap(),
@@ -799,4 +923,34 @@ mod tests {
         .unwrap();
         assert_eq!(manifest_manager.get_operation_body(&id), Some(body))
     }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn uses_local_relay_manifest() {
+        let manifest_manager = PersistedQueryManifestPoller::new(
+            Configuration::fake_builder()
+                .apq(Apq::fake_new(Some(false)))
+                .persisted_query(
+                    PersistedQueries::builder()
+                        .enabled(true)
+                        .trusted_documents(true)
+                        .experimental_local_manifests(vec![
+                            "tests/fixtures/persisted-queries-manifest.json".to_string(),
+                            "tests/fixtures/relay-persisted-documents.json".to_string(),
+                        ])
+                        .build(),
+                )
+                .build()
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            manifest_manager.get_operation_body("5678"),
+            Some("query { typename }".to_string())
+        );
+        assert_eq!(
+            manifest_manager.get_operation_body("a1b2c3"),
+            Some("query ViewerQuery { me { id } }".to_string())
+        );
+    }
 }
@@ -28,6 +28,7 @@ pub(crate) struct PersistedQueryLayer {
     /// value of the manifest and projected safelist. None if the layer is disabled.
     pub(crate) manifest_poller: Option<PersistedQueryManifestPoller>,
     introspection_enabled: bool,
+    trusted_documents: bool,
 }
 
 impl PersistedQueryLayer {
@@ -40,18 +41,20 @@ impl PersistedQueryLayer {
                     PersistedQueryManifestPoller::new(configuration.clone()).await?,
                 ),
                 introspection_enabled: configuration.supergraph.introspection,
+                trusted_documents: configuration.persisted_queries.trusted_documents,
             })
         } else {
             Ok(Self {
                 manifest_poller: None,
                 introspection_enabled: configuration.supergraph.introspection,
+                trusted_documents: configuration.persisted_queries.trusted_documents,
             })
         }
     }
 
     /// Run a request through the layer.
     /// Takes care of:
-    /// 1) resolving a persisted query ID to a query body
+    /// 1) resolving a trusted document ID or a persisted query ID to a query body
     /// 2) matching a freeform GraphQL request against persisted queries, optionally rejecting it based on configuration
     /// 3) continuing to the next stage of the router
     pub(crate) fn supergraph_request(
@@ -59,7 +62,15 @@ impl PersistedQueryLayer {
         request: SupergraphRequest,
     ) -> Result<SupergraphRequest, SupergraphResponse> {
         if let Some(manifest_poller) = &self.manifest_poller {
-            if let Some(persisted_query_id) = PersistedQueryIdExtractor::extract_id(&request) {
+            let document_id = if self.trusted_documents {
+                PersistedQueryIdExtractor::extract_document_id(&request)
+            } else {
+                None
+            };
+            if let Some(document_id) = document_id {
+                self.replace_document_id_with_operation_body(request, manifest_poller, &document_id)
+            } else if let Some(persisted_query_id) = PersistedQueryIdExtractor::extract_id(&request)
+            {
                 self.replace_query_id_with_operation_body(
                     request,
                     manifest_poller,
@@ -144,6 +155,48 @@ impl PersistedQueryLayer {
         }
     }
 
+    /// Places the operation body of a trusted document on a [`SupergraphRequest`].
+    ///
+    /// Unlike persisted query IDs, document IDs are never handed over to APQ: clients sending
+    /// them only ever expect the operations of the manifest, so unknown IDs are rejected.
+    fn replace_document_id_with_operation_body(
+        &self,
+        mut request: SupergraphRequest,
+        manifest_poller: &PersistedQueryManifestPoller,
+        document_id: &str,
+    ) -> Result<SupergraphRequest, SupergraphResponse> {
+        if request.supergraph_request.body().query.is_some() {
+            record_trusted_document("rejected");
+            return Err(supergraph_err(
+                graphql_err_cannot_send_id_and_body(),
+                request,
+                ErrorCacheStrategy::DontCache,
+                StatusCode::BAD_REQUEST,
+            ));
+        }
+
+        // the GraphQL over HTTP specification prefixes the IDs with their hash algorithm
+        let operation_body = manifest_poller.get_operation_body(document_id).or_else(|| {
+            document_id
+                .strip_prefix("sha256:")
+                .and_then(|id| manifest_poller.get_operation_body(id))
+        });
+        if let Some(operation_body) = operation_body {
+            let body = request.supergraph_request.body_mut();
+            body.query = Some(operation_body);
+            body.document_id = None;
+            request
+                .context
+                .extensions()
+                .with_lock(|mut lock| lock.insert(UsedQueryIdFromManifest));
+            record_trusted_document("found");
+            Ok(request)
+        } else {
+            record_trusted_document("not_found");
+            Err(supergraph_err_operation_not_found(request, document_id))
+        }
+    }
+
     pub(crate) async fn supergraph_request_with_analyzed_query(
         &self,
         request: SupergraphRequest,
@@ -247,6 +300,15 @@ impl PersistedQueryLayer {
     }
 }
 
+fn record_trusted_document(result: &'static str) {
+    u64_counter!(
+        "apollo.router.operations.persisted_queries.trusted_documents",
+        "Number of operations sent with a trusted document ID",
+        1,
+        "result" = result
+    );
+}
+
 fn log_unknown_operation(operation_body: &str) {
     tracing::warn!(message = "unknown operation", operation_body);
 }
@@ -380,6 +442,7 @@ mod tests {
     use crate::configuration::PersistedQueries;
     use crate::configuration::PersistedQueriesSafelist;
     use crate::configuration::Supergraph;
+    use crate::metrics::FutureMetricsExt;
     use crate::services::layers::persisted_queries::manifest_poller::FreeformGraphQLBehavior;
     use crate::services::layers::query_analysis::QueryAnalysisLayer;
     use crate::spec::Schema;
@@ -707,7 +770,9 @@ mod tests {
             .unwrap(),
         );
 
-        let query_analysis_layer = QueryAnalysisLayer::new(schema, Arc::new(config)).await;
+        let query_analysis_layer = QueryAnalysisLayer::new(schema, Arc::new(config))
+            .await
+            .unwrap();
 
         // A random query is blocked.
         denied_by_safelist(
@@ -1072,4 +1137,107 @@ mod tests {
             .expect("could not get response from pq layer");
         assert_eq!(response.errors, vec![graphql_err_cannot_send_id_and_body()]);
     }
+
+    fn document_request(document_id: &str) -> SupergraphRequest {
+        let mut request = SupergraphRequest::fake_builder().build().unwrap();
+        request.supergraph_request.body_mut().document_id = Some(document_id.to_string());
+        request
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn pq_layer_resolves_trusted_document_ids() {
+        async {
+            let (id, body, manifest) = fake_manifest();
+            let (_mock_guard, uplink_config) = mock_pq_uplink(&manifest).await;
+            let pq_layer = PersistedQueryLayer::new(
+                &Configuration::fake_builder()
+                    .persisted_query(
+                        PersistedQueries::builder()
+                            .enabled(true)
+                            .trusted_documents(true)
+                            .build(),
+                    )
+                    // unknown document IDs must not be handed over to APQ
+                    .apq(Apq::fake_builder().enabled(true).build())
+                    .uplink(uplink_config)
+                    .build()
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+            let request = pq_layer
+                .supergraph_request(document_request(&id))
+                .ok()
+                .expect("pq layer returned response instead of putting the query on the request");
+            assert_eq!(request.supergraph_request.body().query, Some(body.clone()));
+            assert_eq!(request.supergraph_request.body().document_id, None);
+
+            let request = pq_layer
+                .supergraph_request(document_request(&format!("sha256:{id}")))
+                .ok()
+                .expect("pq layer returned response instead of putting the query on the request");
+            assert_eq!(request.supergraph_request.body().query, Some(body.clone()));
+
+            let invalid_id = "this-id-is-invalid";
+            let mut supergraph_response = pq_layer
+                .supergraph_request(document_request(invalid_id))
+                .expect_err("pq layer returned request instead of returning an error response");
+            assert_eq!(supergraph_response.response.status(), 404);
+            let response = supergraph_response
+                .next_response()
+                .await
+                .expect("could not get response from pq layer");
+            assert_eq!(
+                response.errors,
+                vec![graphql_err_operation_not_found(invalid_id)]
+            );
+
+            let mut request = document_request(&id);
+            request.supergraph_request.body_mut().query = Some(body);
+            let supergraph_response = pq_layer
+                .supergraph_request(request)
+                .expect_err("pq layer returned request instead of returning an error response");
+            assert_eq!(supergraph_response.response.status(), 400);
+
+            assert_counter!(
+                "apollo.router.operations.persisted_queries.trusted_documents",
+                2,
+                "result" = "found"
+            );
+            assert_counter!(
+                "apollo.router.operations.persisted_queries.trusted_documents",
+                1,
+                "result" = "not_found"
+            );
+            assert_counter!(
+                "apollo.router.operations.persisted_queries.trusted_documents",
+                1,
+                "result" = "rejected"
+            );
+        }
+        .with_metrics()
+        .await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn pq_layer_ignores_document_ids_without_trusted_documents() {
+        let (id, _body, manifest) = fake_manifest();
+        let (_mock_guard, uplink_config) = mock_pq_uplink(&manifest).await;
+        let pq_layer = PersistedQueryLayer::new(
+            &Configuration::fake_builder()
+                .persisted_query(PersistedQueries::builder().enabled(true).build())
+                .uplink(uplink_config)
+                .build()
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+        let request = pq_layer
+            .supergraph_request(document_request(&id))
+            .ok()
+            .expect("pq layer returned response instead of continuing to the next stage");
+        assert!(request.supergraph_request.body().query.is_none());
+    }
 }
@@ -12,6 +12,7 @@ use lru::LruCache;
 use router_bridge::planner::UsageReporting;
 use tokio::sync::Mutex;
 use tokio::task;
+use tower::BoxError;
 
 use crate::apollo_studio_interop::generate_extended_references;
 use crate::apollo_studio_interop::ExtendedReferenceStats;
@@ -21,11 +22,17 @@ use crate::graphql::Error;
 use crate::graphql::ErrorExtension;
 use crate::graphql::IntoGraphQLErrors;
 use crate::plugins::authorization::AuthorizationPlugin;
+use crate::plugins::field_allow_list::FieldAllowList;
+use crate::plugins::limits;
+use crate::plugins::limits::ParserLimits;
+use crate::plugins::operation_rewrite::OperationRewriter;
 use crate::plugins::telemetry::config::ApolloMetricsReferenceMode;
 use crate::plugins::telemetry::config::Conf as TelemetryConfig;
 use crate::plugins::telemetry::consts::QUERY_PARSING_SPAN_NAME;
+use crate::plugins::telemetry::CLIENT_NAME;
 use crate::query_planner::fetch::QueryHash;
 use crate::query_planner::OperationKind;
+use crate::services::layers::defer_limits::DeferLimiter;
 use crate::services::SupergraphRequest;
 use crate::services::SupergraphResponse;
 use crate::spec::Query;
@@ -43,21 +50,32 @@ pub(crate) struct QueryAnalysisLayer {
     cache: Arc<Mutex<LruCache<QueryAnalysisKey, Result<(Context, ParsedDocument), SpecError>>>>,
     enable_authorization_directives: bool,
     metrics_reference_mode: ApolloMetricsReferenceMode,
+    operation_rewriter: Option<Arc<OperationRewriter>>,
+    defer_limiter: Option<Arc<DeferLimiter>>,
+    field_allow_list: Option<Arc<FieldAllowList>>,
 }
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 struct QueryAnalysisKey {
     query: String,
     operation_name: Option<String>,
+    parser_limits: ParserLimits,
 }
 
 impl QueryAnalysisLayer {
-    pub(crate) async fn new(schema: Arc<Schema>, configuration: Arc<Configuration>) -> Self {
+    pub(crate) async fn new(
+        schema: Arc<Schema>,
+        configuration: Arc<Configuration>,
+    ) -> Result<Self, BoxError> {
         let enable_authorization_directives =
             AuthorizationPlugin::enable_directives(&configuration, &schema).unwrap_or(false);
         let metrics_reference_mode = TelemetryConfig::metrics_reference_mode(&configuration);
+        let operation_rewriter =
+            OperationRewriter::from_configuration(&configuration)?.map(Arc::new);
+        let defer_limiter = DeferLimiter::from_configuration(&configuration).map(Arc::new);
+        let field_allow_list = FieldAllowList::from_configuration(&configuration)?.map(Arc::new);
 
-        Self {
+        Ok(Self {
             schema,
             cache: Arc::new(Mutex::new(LruCache::new(
                 configuration
@@ -70,18 +88,31 @@ impl QueryAnalysisLayer {
             enable_authorization_directives,
             configuration,
             metrics_reference_mode,
-        }
+            operation_rewriter,
+            defer_limiter,
+            field_allow_list,
+        })
     }
 
     pub(crate) async fn parse_document(
         &self,
         query: &str,
         operation_name: Option<&str>,
+    ) -> Result<ParsedDocument, SpecError> {
+        let parser_limits = self.configuration.limits.parser_limits(None);
+        self.parse_document_with_limits(query, operation_name, parser_limits)
+            .await
+    }
+
+    async fn parse_document_with_limits(
+        &self,
+        query: &str,
+        operation_name: Option<&str>,
+        parser_limits: ParserLimits,
     ) -> Result<ParsedDocument, SpecError> {
         let query = query.to_string();
         let operation_name = operation_name.map(|o| o.to_string());
         let schema = self.schema.clone();
-        let conf = self.configuration.clone();
 
         // Must be created *outside* of the spawn_blocking or the span is not connected to the
         // parent
@@ -89,11 +120,11 @@ impl QueryAnalysisLayer {
 
         task::spawn_blocking(move || {
             span.in_scope(|| {
-                Query::parse_document(
+                Query::parse_document_with_limits(
                     &query,
                     operation_name.as_deref(),
                     schema.as_ref(),
-                    conf.as_ref(),
+                    parser_limits,
                 )
             })
         })
@@ -103,7 +134,7 @@ impl QueryAnalysisLayer {
 
     pub(crate) async fn supergraph_request(
         &self,
-        request: SupergraphRequest,
+        mut request: SupergraphRequest,
     ) -> Result<SupergraphRequest, SupergraphResponse> {
         let query = request.supergraph_request.body().query.as_ref();
 
@@ -129,12 +160,56 @@ impl QueryAnalysisLayer {
         }
 
         let op_name = request.supergraph_request.body().operation_name.clone();
-        let query = request
+        let mut query = request
             .supergraph_request
             .body()
             .query
             .clone()
             .expect("query presence was already checked");
+        // operations are rewritten before validation, since they can select fields that are not in the schema anymore
+        if let Some(rewriter) = &self.operation_rewriter {
+            if let Some(rewritten) = rewriter.rewrite(self.schema.api_schema(), &query) {
+                request.supergraph_request.body_mut().query = Some(rewritten.clone());
+                query = rewritten;
+            }
+        }
+        // deferred fragments that are not honored are inlined before parsing, so that they are
+        // planned as regular fragments
+        if let Some(limiter) = &self.defer_limiter {
+            if let Some(limited) = limiter.limit(&request.context, &query) {
+                request.supergraph_request.body_mut().query = Some(limited.clone());
+                query = limited;
+            }
+        }
+        let client_name = request.context.get::<_, String>(CLIENT_NAME).ok().flatten();
+        let parser_limits = self
+            .configuration
+            .limits
+            .parser_limits(client_name.as_deref());
+        // fields that are not allowed for the client are checked before validation, so that
+        // redacted operations are validated and planned without them
+        if let Some(allow_list) = &self.field_allow_list {
+            match allow_list.check(
+                self.schema.api_schema(),
+                &request.context,
+                &query,
+                parser_limits,
+            ) {
+                Ok(None) => {}
+                Ok(Some(redacted)) => {
+                    request.supergraph_request.body_mut().query = Some(redacted.clone());
+                    query = redacted;
+                }
+                Err(errors) => {
+                    return Err(SupergraphResponse::builder()
+                        .errors(errors)
+                        .status_code(StatusCode::BAD_REQUEST)
+                        .context(request.context)
+                        .build()
+                        .expect("response is valid"));
+                }
+            }
+        }
         let entry = self
             .cache
             .lock()
@@ -142,17 +217,27 @@ impl QueryAnalysisLayer {
             .get(&QueryAnalysisKey {
                 query: query.clone(),
                 operation_name: op_name.clone(),
+                parser_limits,
             })
             .cloned();
 
         let res = match entry {
             None => {
-                match self.parse_document(&query, op_name.as_deref()).await {
+                match self
+                    .parse_document_with_limits(&query, op_name.as_deref(), parser_limits)
+                    .await
+                {
                     Err(errors) => {
+                        record_parser_limit_exceeded(
+                            &errors,
+                            &self.configuration.limits,
+                            client_name.as_deref(),
+                        );
                         (*self.cache.lock().await).put(
                             QueryAnalysisKey {
                                 query,
                                 operation_name: op_name,
+                                parser_limits,
                             },
                             Err(errors.clone()),
                         );
@@ -202,6 +287,7 @@ impl QueryAnalysisLayer {
                             QueryAnalysisKey {
                                 query,
                                 operation_name: op_name.clone(),
+                                parser_limits,
                             },
                             Ok((context.clone(), doc.clone())),
                         );
@@ -244,6 +330,11 @@ impl QueryAnalysisLayer {
                 })
             }
             Err(errors) => {
+                record_parser_limit_exceeded(
+                    &errors,
+                    &self.configuration.limits,
+                    client_name.as_deref(),
+                );
                 request.context.extensions().with_lock(|mut lock| {
                     lock.insert(Arc::new(UsageReporting {
                         stats_report_key: errors.get_error_key().to_string(),
@@ -261,6 +352,23 @@ impl QueryAnalysisLayer {
     }
 }
 
+/// Counts the operations rejected by a parser limit, which can be attempts to probe the limits
+fn record_parser_limit_exceeded(
+    error: &SpecError,
+    limits: &limits::Config,
+    client_name: Option<&str>,
+) {
+    if let SpecError::ParserLimitExceeded(limit) = error {
+        u64_counter!(
+            "apollo.router.limits.parser.exceeded",
+            "Number of operations rejected because they exceed a limit of the GraphQL parser",
+            1,
+            "limit" = limit.to_string(),
+            "client.name" = limits.client_label(client_name)
+        );
+    }
+}
+
 pub(crate) type ParsedDocument = Arc<ParsedDocumentInner>;
 
 #[derive(Debug)]
@@ -82,3 +82,7 @@ pub(crate) const MULTIPART_SUBSCRIPTION_CONTENT_TYPE: &str =
     "multipart/mixed;boundary=\"graphql\";subscriptionSpec=1.0";
 pub(crate) const MULTIPART_SUBSCRIPTION_SPEC_PARAMETER: &str = "subscriptionSpec";
 pub(crate) const MULTIPART_SUBSCRIPTION_SPEC_VALUE: &str = "1.0";
+
+// incremental delivery of `@defer` responses for clients that cannot parse multipart responses
+pub(crate) const JSON_ARRAY_DEFER_ACCEPT: &str = "application/json;deferSpec=20220824";
+pub(crate) const JSON_LINES_DEFER_ACCEPT: &str = "application/jsonl;deferSpec=20220824";
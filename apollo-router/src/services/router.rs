@@ -115,6 +115,29 @@ impl Request {
     }
 }
 
+impl Request {
+    /// Reads the whole request body and returns its raw bytes, along with the request, whose
+    /// body is replaced with the same bytes so it can still be read by the rest of the pipeline.
+    ///
+    /// This is meant for plugins that need the exact bytes sent by the client before they are
+    /// parsed as JSON, to verify a signature for example. The bytes are not copied: the new body
+    /// shares the buffer with the returned `Bytes`.
+    ///
+    /// The body size limits still apply, as the body is read through the same stream.
+    pub async fn buffer_body(self) -> Result<(Request, Bytes), BoxError> {
+        let (parts, body) = self.router_request.into_parts();
+        let bytes = hyper::body::to_bytes(body).await?;
+        let router_request = http::Request::from_parts(parts, Body::from(bytes.clone()));
+        Ok((
+            Request {
+                router_request,
+                context: self.context,
+            },
+            bytes,
+        ))
+    }
+}
+
 use displaydoc::Display;
 use thiserror::Error;
 
@@ -383,6 +406,15 @@ pub(crate) struct ClientRequestAccepts {
     pub(crate) multipart_subscription: bool,
     pub(crate) json: bool,
     pub(crate) wildcard: bool,
+    pub(crate) json_array_defer: bool,
+    pub(crate) json_lines_defer: bool,
+}
+
+impl ClientRequestAccepts {
+    /// Returns true if the client accepts one of the incremental delivery transports of `@defer`
+    pub(crate) fn accepts_defer(&self) -> bool {
+        self.multipart_defer || self.json_array_defer || self.json_lines_defer
+    }
 }
 
 impl<T> From<http::Response<T>> for Response
@@ -3,6 +3,7 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::task::Poll;
+use std::time::Instant;
 
 use axum::body::StreamBody;
 use axum::response::*;
@@ -42,11 +43,20 @@ use crate::batching::BatchQuery;
 use crate::cache::DeduplicatingCache;
 use crate::configuration::Batching;
 use crate::configuration::BatchingMode;
+use crate::configuration::ResponseFormat;
 use crate::context::CONTAINS_GRAPHQL_ERROR;
+use crate::context::OPERATION_NAME;
 use crate::graphql;
 use crate::http_ext;
+use crate::json_ext::Object;
+use crate::json_ext::Value;
 #[cfg(test)]
 use crate::plugin::test::MockSupergraphService;
+use crate::plugins::limits::body_validation::BodyValidation;
+use crate::plugins::limits::body_validation::BodyViolation;
+use crate::protocols::json_stream::JsonStream;
+use crate::protocols::json_stream::JsonStreamMode;
+use crate::protocols::multipart::DeferMetrics;
 use crate::protocols::multipart::Multipart;
 use crate::protocols::multipart::ProtocolMode;
 use crate::query_planner::InMemoryCachePlanner;
@@ -72,6 +82,8 @@ use crate::services::SupergraphCreator;
 use crate::services::SupergraphRequest;
 use crate::services::SupergraphResponse;
 use crate::services::APPLICATION_JSON_HEADER_VALUE;
+use crate::services::JSON_ARRAY_DEFER_ACCEPT;
+use crate::services::JSON_LINES_DEFER_ACCEPT;
 use crate::services::MULTIPART_DEFER_ACCEPT;
 use crate::services::MULTIPART_DEFER_CONTENT_TYPE;
 use crate::services::MULTIPART_SUBSCRIPTION_ACCEPT;
@@ -85,6 +97,10 @@ pub(crate) static MULTIPART_DEFER_CONTENT_TYPE_HEADER_VALUE: HeaderValue =
     HeaderValue::from_static(MULTIPART_DEFER_CONTENT_TYPE);
 pub(crate) static MULTIPART_SUBSCRIPTION_CONTENT_TYPE_HEADER_VALUE: HeaderValue =
     HeaderValue::from_static(MULTIPART_SUBSCRIPTION_CONTENT_TYPE);
+pub(crate) static JSON_ARRAY_DEFER_CONTENT_TYPE_HEADER_VALUE: HeaderValue =
+    HeaderValue::from_static(JSON_ARRAY_DEFER_ACCEPT);
+pub(crate) static JSON_LINES_DEFER_CONTENT_TYPE_HEADER_VALUE: HeaderValue =
+    HeaderValue::from_static(JSON_LINES_DEFER_ACCEPT);
 static ACCEL_BUFFERING_HEADER_NAME: HeaderName = HeaderName::from_static("x-accel-buffering");
 static ACCEL_BUFFERING_HEADER_VALUE: HeaderValue = HeaderValue::from_static("no");
 static ORIGIN_HEADER_VALUE: HeaderValue = HeaderValue::from_static("origin");
@@ -97,6 +113,8 @@ pub(crate) struct RouterService {
     persisted_query_layer: Arc<PersistedQueryLayer>,
     query_analysis_layer: QueryAnalysisLayer,
     batching: Batching,
+    response_format: ResponseFormat,
+    body_validation: BodyValidation,
 }
 
 impl RouterService {
@@ -106,6 +124,8 @@ impl RouterService {
         persisted_query_layer: Arc<PersistedQueryLayer>,
         query_analysis_layer: QueryAnalysisLayer,
         batching: Batching,
+        response_format: ResponseFormat,
+        body_validation: BodyValidation,
     ) -> Self {
         RouterService {
             supergraph_creator,
@@ -113,6 +133,8 @@ impl RouterService {
             persisted_query_layer,
             query_analysis_layer,
             batching,
+            response_format,
+            body_validation,
         }
     }
 }
@@ -148,7 +170,9 @@ pub(crate) async fn from_supergraph_mock_callback_and_configuration(
         .unwrap();
 
     RouterCreator::new(
-        QueryAnalysisLayer::new(supergraph_creator.schema(), Arc::clone(&configuration)).await,
+        QueryAnalysisLayer::new(supergraph_creator.schema(), Arc::clone(&configuration))
+            .await
+            .unwrap(),
         Arc::new(PersistedQueryLayer::new(&configuration).await.unwrap()),
         Arc::new(supergraph_creator),
         configuration,
@@ -198,7 +222,9 @@ pub(crate) async fn empty() -> impl Service<
         .unwrap();
 
     RouterCreator::new(
-        QueryAnalysisLayer::new(supergraph_creator.schema(), Default::default()).await,
+        QueryAnalysisLayer::new(supergraph_creator.schema(), Default::default())
+            .await
+            .unwrap(),
         Arc::new(PersistedQueryLayer::new(&Default::default()).await.unwrap()),
         Arc::new(supergraph_creator),
         Arc::new(Configuration::default()),
@@ -232,6 +258,7 @@ impl RouterService {
         &self,
         supergraph_request: SupergraphRequest,
     ) -> Result<router::Response, BoxError> {
+        let start = Instant::now();
         let mut request_res = self
             .persisted_query_layer
             .supergraph_request(supergraph_request);
@@ -260,6 +287,8 @@ impl RouterService {
             json: accepts_json,
             multipart_defer: accepts_multipart_defer,
             multipart_subscription: accepts_multipart_subscription,
+            json_array_defer: accepts_json_array_defer,
+            json_lines_defer: accepts_json_lines_defer,
         } = context
             .extensions()
             .with_lock(|lock| lock.get().cloned())
@@ -303,7 +332,7 @@ impl RouterService {
                         .headers
                         .insert(CONTENT_TYPE, APPLICATION_JSON_HEADER_VALUE.clone());
                     tracing::trace_span!("serialize_response").in_scope(|| {
-                        let body = serde_json::to_string(&response)?;
+                        let body = serialize_response(&response, &self.response_format)?;
                         Ok(router::Response {
                             response: http::Response::from_parts(
                                 parts,
@@ -312,6 +341,46 @@ impl RouterService {
                             context,
                         })
                     })
+                } else if !response.subscribed.unwrap_or(false)
+                    && !accepts_multipart_defer
+                    && (accepts_json_lines_defer || accepts_json_array_defer)
+                {
+                    // incremental delivery for clients that cannot parse multipart responses
+                    let (content_type, mode) = if accepts_json_lines_defer {
+                        (
+                            JSON_LINES_DEFER_CONTENT_TYPE_HEADER_VALUE.clone(),
+                            JsonStreamMode::Lines,
+                        )
+                    } else {
+                        (
+                            JSON_ARRAY_DEFER_CONTENT_TYPE_HEADER_VALUE.clone(),
+                            JsonStreamMode::Array,
+                        )
+                    };
+                    parts.headers.insert(CONTENT_TYPE, content_type);
+                    parts.headers.insert(
+                        ACCEL_BUFFERING_HEADER_NAME.clone(),
+                        ACCEL_BUFFERING_HEADER_VALUE.clone(),
+                    );
+                    if !response.errors.is_empty() {
+                        Self::count_errors(&response.errors);
+                    }
+
+                    let json_stream = JsonStream::new(
+                        once(ready(response)).chain(body.inspect(|response| {
+                            if !response.errors.is_empty() {
+                                Self::count_errors(&response.errors);
+                            }
+                        })),
+                        mode,
+                    );
+                    Ok(RouterResponse {
+                        response: http::Response::from_parts(
+                            parts,
+                            RouterBody::wrap_stream(json_stream).into_inner(),
+                        ),
+                        context,
+                    })
                 } else if accepts_multipart_defer || accepts_multipart_subscription {
                     if accepts_multipart_defer {
                         parts.headers.insert(
@@ -343,14 +412,24 @@ impl RouterService {
                             }),
                             ProtocolMode::Subscription,
                         )),
-                        _ => StreamBody::new(Multipart::new(
-                            once(ready(response)).chain(body.inspect(|response| {
-                                if !response.errors.is_empty() {
-                                    Self::count_errors(&response.errors);
-                                }
-                            })),
-                            ProtocolMode::Defer,
-                        )),
+                        _ => {
+                            let operation_name = context
+                                .get::<_, String>(OPERATION_NAME)
+                                .ok()
+                                .flatten()
+                                .unwrap_or_default();
+                            StreamBody::new(
+                                Multipart::new(
+                                    once(ready(response)).chain(body.inspect(|response| {
+                                        if !response.errors.is_empty() {
+                                            Self::count_errors(&response.errors);
+                                        }
+                                    })),
+                                    ProtocolMode::Defer,
+                                )
+                                .with_defer_metrics(DeferMetrics::new(operation_name, start)),
+                            )
+                        }
                     };
                     let response = (parts, multipart_stream).into_response().map(|body| {
                         // Axum makes this `body` have type:
@@ -773,12 +852,21 @@ impl RouterService {
     ) -> Result<Result<(Vec<graphql::Request>, bool), TranslateError>, BoxError> {
         let graphql_requests: Result<(Vec<graphql::Request>, bool), TranslateError> =
             if parts.method == Method::GET {
-                self.translate_query_request(parts).await
+                match self
+                    .body_validation
+                    .validate_query_string(parts.uri.query().unwrap_or_default())
+                {
+                    Ok(()) => self.translate_query_request(parts).await,
+                    Err(violation) => Err(TranslateError::from_body_violation(violation)),
+                }
             } else {
                 let bytes = get_body_bytes(body)
                     .instrument(tracing::debug_span!("receive_body"))
                     .await?;
-                self.translate_bytes_request(&bytes)
+                match self.body_validation.validate_body(&bytes) {
+                    Ok(()) => self.translate_bytes_request(&bytes),
+                    Err(violation) => Err(TranslateError::from_body_violation(violation)),
+                }
             };
         Ok(graphql_requests)
     }
@@ -814,6 +902,72 @@ struct TranslateError<'a> {
     extension_details: String,
 }
 
+impl TranslateError<'static> {
+    fn from_body_violation(violation: BodyViolation) -> Self {
+        TranslateError {
+            status: StatusCode::BAD_REQUEST,
+            error: "invalid request body",
+            extension_code: violation.extension_code(),
+            extension_details: violation.to_string(),
+        }
+    }
+}
+
+/// Keys of the JSON responses, in the order they are serialized
+const RESPONSE_KEYS: [&str; 7] = [
+    "label",
+    "data",
+    "path",
+    "errors",
+    "extensions",
+    "hasNext",
+    "incremental",
+];
+
+/// Serializes a JSON response, applying the compatibility options of the response format
+pub(crate) fn serialize_response(
+    response: &graphql::Response,
+    format: &ResponseFormat,
+) -> Result<String, serde_json::Error> {
+    if !format.always_include_data && !format.errors_before_data && !format.include_empty_extensions
+    {
+        return serde_json::to_string(response);
+    }
+
+    let mut object = match serde_json_bytes::to_value(response)? {
+        Value::Object(object) => object,
+        other => return serde_json::to_string(&other),
+    };
+    // `errors` moves before the other keys, which keep their order
+    let errors_first = format.errors_before_data.then_some("errors");
+    let keys = errors_first.into_iter().chain(
+        RESPONSE_KEYS
+            .iter()
+            .copied()
+            .filter(|key| Some(*key) != errors_first),
+    );
+    let mut ordered = Object::with_capacity(object.len() + 2);
+    for key in keys {
+        match object.remove(key) {
+            Some(value) => {
+                ordered.insert(key, value);
+            }
+            None if key == "data" && format.always_include_data => {
+                ordered.insert(key, Value::Null);
+            }
+            None if key == "extensions" && format.include_empty_extensions => {
+                ordered.insert(key, Value::Object(Object::new()));
+            }
+            None => {}
+        }
+    }
+    // keys added to responses later are kept
+    for (key, value) in object {
+        ordered.insert(key, value);
+    }
+    serde_json::to_string(&ordered)
+}
+
 // Process the headers to make sure that `VARY` is set correctly
 pub(crate) fn process_vary_header(headers: &mut HeaderMap<HeaderValue>) {
     if headers.get(VARY).is_none() {
@@ -831,6 +985,8 @@ pub(crate) struct RouterCreator {
     pub(crate) persisted_query_layer: Arc<PersistedQueryLayer>,
     query_analysis_layer: QueryAnalysisLayer,
     batching: Batching,
+    response_format: ResponseFormat,
+    body_validation: BodyValidation,
 }
 
 impl ServiceFactory<router::Request> for RouterCreator {
@@ -881,6 +1037,8 @@ impl RouterCreator {
             query_analysis_layer,
             persisted_query_layer,
             batching: configuration.batching.clone(),
+            response_format: configuration.supergraph.response_format.clone(),
+            body_validation: configuration.limits.http_body_validation.clone(),
         })
     }
 
@@ -898,6 +1056,8 @@ impl RouterCreator {
             self.persisted_query_layer.clone(),
             self.query_analysis_layer.clone(),
             self.batching.clone(),
+            self.response_format.clone(),
+            self.body_validation.clone(),
         ));
 
         ServiceBuilder::new()
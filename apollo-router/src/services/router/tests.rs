@@ -13,15 +13,19 @@ use serde_json_bytes::json;
 use tower::ServiceExt;
 use tower_service::Service;
 
+use crate::configuration::ResponseFormat;
 use crate::graphql;
+use crate::layers::ServiceBuilderExt;
 use crate::services::router;
 use crate::services::router::body::get_body_bytes;
 use crate::services::router::service::from_supergraph_mock_callback;
 use crate::services::router::service::process_vary_header;
+use crate::services::router::service::serialize_response;
 use crate::services::subgraph;
 use crate::services::supergraph;
 use crate::services::SupergraphRequest;
 use crate::services::SupergraphResponse;
+use crate::services::JSON_ARRAY_DEFER_ACCEPT;
 use crate::services::MULTIPART_DEFER_CONTENT_TYPE;
 use crate::test_harness::make_fake_batch;
 use crate::Context;
@@ -62,6 +66,59 @@ fn it_leaves_varys_alone_if_there_are_more_than_one() {
     }
 }
 
+#[test]
+fn it_applies_the_response_format() {
+    let response = graphql::Response::builder()
+        .error(
+            graphql::Error::builder()
+                .message("error")
+                .extension_code("ERR")
+                .build(),
+        )
+        .build();
+    let default = serialize_response(&response, &ResponseFormat::default()).unwrap();
+    assert_eq!(
+        default,
+        r#"{"errors":[{"message":"error","extensions":{"code":"ERR"}}]}"#
+    );
+
+    let legacy = ResponseFormat {
+        always_include_data: true,
+        errors_before_data: true,
+        include_empty_extensions: true,
+    };
+    assert_eq!(
+        serialize_response(&response, &legacy).unwrap(),
+        r#"{"errors":[{"message":"error","extensions":{"code":"ERR"}}],"data":null,"extensions":{}}"#
+    );
+
+    let response = graphql::Response::builder()
+        .data(json!({"a": 1}))
+        .error(
+            graphql::Error::builder()
+                .message("error")
+                .extension_code("ERR")
+                .build(),
+        )
+        .extension("b", json!(2))
+        .build();
+    assert_eq!(
+        serialize_response(
+            &response,
+            &ResponseFormat {
+                always_include_data: true,
+                ..Default::default()
+            }
+        )
+        .unwrap(),
+        serialize_response(&response, &ResponseFormat::default()).unwrap()
+    );
+    assert_eq!(
+        serialize_response(&response, &legacy).unwrap(),
+        r#"{"errors":[{"message":"error","extensions":{"code":"ERR"}}],"data":{"a":1},"extensions":{"b":2}}"#
+    );
+}
+
 #[tokio::test]
 async fn it_extracts_query_and_operation_name() {
     let query = "query";
@@ -230,6 +287,60 @@ async fn test_http_max_request_bytes() {
     assert_eq!(response.status(), http::StatusCode::PAYLOAD_TOO_LARGE);
 }
 
+#[tokio::test]
+async fn it_rejects_invalid_bodies_with_body_validation() {
+    async fn with_config(mode: &str, body: &'static str) -> router::Response {
+        let http_request = http::Request::builder()
+            .method(Method::POST)
+            .uri("http://example.com/")
+            .header(CONTENT_TYPE, "application/json")
+            .body(hyper::Body::from(body))
+            .unwrap();
+        let config = serde_json::json!({
+            "limits": {
+                "http_body_validation": {
+                    "mode": mode,
+                    "max_json_depth": 4
+                }
+            }
+        });
+        crate::TestHarness::builder()
+            .configuration_json(config)
+            .unwrap()
+            .build_router()
+            .await
+            .unwrap()
+            .oneshot(router::Request::from(http_request))
+            .await
+            .unwrap()
+    }
+    async fn error_code(response: router::Response) -> serde_json::Value {
+        assert_eq!(response.response.status(), http::StatusCode::BAD_REQUEST);
+        let body: serde_json::Value =
+            serde_json::from_slice(&get_body_bytes(response.response.into_body()).await.unwrap())
+                .unwrap();
+        body["errors"][0]["extensions"]["code"].clone()
+    }
+
+    let duplicate_key = r#"{"query":"{ me { name } }","variables":{"id":"1","id":"2"}}"#;
+    let too_deep = r#"{"query":"{ me { name } }","variables":{"a":[[[1]]]}}"#;
+    assert_eq!(
+        error_code(with_config("reject", duplicate_key).await).await,
+        "DUPLICATE_VARIABLE_KEY"
+    );
+    assert_eq!(
+        error_code(with_config("reject", too_deep).await).await,
+        "MAX_JSON_DEPTH_LIMIT"
+    );
+    assert_ne!(
+        with_config("log_only", duplicate_key)
+            .await
+            .response
+            .status(),
+        http::StatusCode::BAD_REQUEST
+    );
+}
+
 #[tokio::test]
 async fn it_only_accepts_batch_http_link_mode_for_query_batch() {
     let expected_response: serde_json::Value = serde_json::from_str(include_str!(
@@ -453,6 +564,52 @@ async fn it_will_process_a_non_batched_defered_query() {
     assert_eq!(expected_response, data);
 }
 
+#[tokio::test]
+async fn it_sends_deferred_responses_as_a_json_array() {
+    let query = "
+        query TopProducts($first: Int) {
+            topProducts(first: $first) {
+                upc
+                reviews {
+                    ... @defer {
+                    id
+                    }
+                }
+            }
+        }
+    ";
+    let http_request = supergraph::Request::canned_builder()
+        .header(http::header::ACCEPT, JSON_ARRAY_DEFER_ACCEPT)
+        .query(query)
+        .build()
+        .unwrap()
+        .supergraph_request
+        .map(|req: graphql::Request| {
+            let bytes = serde_json::to_vec(&req).unwrap();
+            hyper::Body::from(bytes)
+        });
+    let response = crate::TestHarness::builder()
+        .build_router()
+        .await
+        .unwrap()
+        .oneshot(router::Request::from(http_request))
+        .await
+        .unwrap()
+        .response;
+    assert_eq!(response.status(), http::StatusCode::OK);
+    assert_eq!(
+        response.headers().get(CONTENT_TYPE).unwrap(),
+        JSON_ARRAY_DEFER_ACCEPT
+    );
+    let bytes = get_body_bytes(response.into_body()).await.unwrap();
+    let payloads: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    let payloads = payloads.as_array().unwrap();
+    assert_eq!(payloads.len(), 2);
+    assert_eq!(payloads[0]["hasNext"], true);
+    assert_eq!(payloads[1]["hasNext"], false);
+    assert_eq!(payloads[1]["incremental"].as_array().unwrap().len(), 3);
+}
+
 #[tokio::test]
 async fn it_will_not_process_a_batched_deferred_query() {
     let expected_response = "[\r\n--graphql\r\ncontent-type: application/json\r\n\r\n{\"errors\":[{\"message\":\"Deferred responses and subscriptions aren't supported in batches\",\"extensions\":{\"code\":\"BATCHING_DEFER_UNSUPPORTED\"}}]}\r\n--graphql--\r\n, \r\n--graphql\r\ncontent-type: application/json\r\n\r\n{\"errors\":[{\"message\":\"Deferred responses and subscriptions aren't supported in batches\",\"extensions\":{\"code\":\"BATCHING_DEFER_UNSUPPORTED\"}}]}\r\n--graphql--\r\n]";
@@ -571,3 +728,44 @@ async fn escaped_quotes_in_string_literal() {
     // The string literal made it through unchanged:
     assert!(subgraph_query.contains(r#"reviewsForAuthor(authorID:"\"1\"")"#));
 }
+
+#[tokio::test]
+async fn it_gives_access_to_the_raw_request_body() {
+    let http_request = supergraph::Request::canned_builder()
+        .build()
+        .unwrap()
+        .supergraph_request
+        .map(|body| hyper::Body::from(serde_json::to_vec(&body).unwrap()));
+    let expected_bytes = serde_json::to_vec(http_request.body()).unwrap();
+    let raw_bytes = Arc::new(Mutex::new(None));
+    let raw_bytes_2 = raw_bytes.clone();
+
+    let response = crate::TestHarness::builder()
+        .router_hook(move |service| {
+            let raw_bytes = raw_bytes_2.clone();
+            tower::ServiceBuilder::new()
+                .oneshot_checkpoint_async(move |request: router::Request| {
+                    let raw_bytes = raw_bytes.clone();
+                    async move {
+                        let (request, bytes) = request.buffer_body().await?;
+                        *raw_bytes.lock().unwrap() = Some(bytes);
+                        Ok(std::ops::ControlFlow::Continue(request))
+                    }
+                })
+                .service(service)
+                .boxed()
+        })
+        .build_router()
+        .await
+        .unwrap()
+        .oneshot(router::Request::from(http_request))
+        .await
+        .unwrap();
+
+    // the body was still available to the router service after being read by the hook
+    assert_eq!(response.response.status(), http::StatusCode::OK);
+    assert_eq!(
+        raw_bytes.lock().unwrap().as_deref(),
+        Some(expected_bytes.as_slice())
+    );
+}
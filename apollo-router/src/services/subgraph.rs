@@ -1,12 +1,16 @@
 #![allow(missing_docs)] // FIXME
 
+use std::collections::HashMap;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
 
 use apollo_compiler::validation::Valid;
 use http::StatusCode;
 use http::Version;
 use multimap::MultiMap;
+use serde::Deserialize;
+use serde::Serialize;
 use serde_json_bytes::ByteString;
 use serde_json_bytes::Map as JsonMap;
 use serde_json_bytes::Value;
@@ -62,6 +66,9 @@ pub struct Request {
     pub(crate) authorization: Arc<CacheKeyMetadata>,
 
     pub(crate) executable_document: Option<Arc<Valid<apollo_compiler::ExecutableDocument>>>,
+
+    /// Client variables used by the query plan node sending this request
+    pub(crate) variable_usages: Option<Vec<Arc<str>>>,
 }
 
 #[buildstructor::buildstructor]
@@ -90,6 +97,7 @@ impl Request {
             query_hash: Default::default(),
             authorization: Default::default(),
             executable_document: None,
+            variable_usages: None,
         }
     }
 
@@ -154,6 +162,7 @@ impl Clone for Request {
             query_hash: self.query_hash.clone(),
             authorization: self.authorization.clone(),
             executable_document: self.executable_document.clone(),
+            variable_usages: self.variable_usages.clone(),
         }
     }
 }
@@ -374,3 +383,84 @@ impl Request {
         hex::encode(hasher.finalize())
     }
 }
+
+/// Context key of the [`SubgraphStatistics`] of a client request
+pub const SUBGRAPH_STATISTICS_CONTEXT_KEY: &str = "apollo_router::subgraph::statistics";
+
+/// Latency and errors of the subgraph requests already made for a client request, stored in the
+/// context under [`SUBGRAPH_STATISTICS_CONTEXT_KEY`] as each subgraph response is received, when
+/// `supergraph.experimental_subgraph_statistics` is enabled.
+///
+/// Fetches executed after others in the query plan can use them to adapt, like skipping optional
+/// subgraph requests when the client request is already over its latency budget.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct SubgraphStatistics {
+    /// Time between the start of the client request and the last subgraph response, in milliseconds
+    pub elapsed_ms: f64,
+    /// Statistics of the requests to each subgraph, by subgraph name
+    pub subgraphs: HashMap<String, FetchStatistics>,
+}
+
+/// Statistics of the requests to one subgraph
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct FetchStatistics {
+    /// Number of requests
+    pub requests: u64,
+    /// Number of requests that failed, returned GraphQL errors or a 5xx status code
+    pub errors: u64,
+    /// Sum of the latencies of the requests, in milliseconds
+    pub total_latency_ms: f64,
+    /// Highest latency of the requests, in milliseconds
+    pub max_latency_ms: f64,
+}
+
+impl SubgraphStatistics {
+    /// Reads the statistics of the client request from its context, they are empty before the
+    /// first subgraph response
+    pub fn from_context(context: &Context) -> Self {
+        context
+            .get(SUBGRAPH_STATISTICS_CONTEXT_KEY)
+            .ok()
+            .flatten()
+            .unwrap_or_default()
+    }
+
+    /// Adds a subgraph response to the statistics in the context
+    pub(crate) fn record(context: &Context, subgraph_name: &str, latency: Duration, error: bool) {
+        let elapsed_ms = context.created_at.elapsed().as_secs_f64() * 1000.0;
+        let latency_ms = latency.as_secs_f64() * 1000.0;
+        let result = context.upsert(
+            SUBGRAPH_STATISTICS_CONTEXT_KEY,
+            |mut statistics: SubgraphStatistics| {
+                statistics.elapsed_ms = statistics.elapsed_ms.max(elapsed_ms);
+                let subgraph = statistics
+                    .subgraphs
+                    .entry(subgraph_name.to_string())
+                    .or_default();
+                subgraph.requests += 1;
+                subgraph.errors += error as u64;
+                subgraph.total_latency_ms += latency_ms;
+                subgraph.max_latency_ms = subgraph.max_latency_ms.max(latency_ms);
+                statistics
+            },
+        );
+        if let Err(error) = result {
+            tracing::debug!("could not record subgraph statistics in the context: {error}");
+        }
+    }
+}
+
+impl FetchStatistics {
+    /// Mean latency of the requests, in milliseconds
+    pub fn mean_latency_ms(&self) -> f64 {
+        if self.requests == 0 {
+            0.0
+        } else {
+            self.total_latency_ms / self.requests as f64
+        }
+    }
+}
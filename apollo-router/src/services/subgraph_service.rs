@@ -5,6 +5,7 @@ use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering::Relaxed;
 use std::sync::Arc;
 use std::task::Poll;
+use std::time::Instant;
 
 use bytes::Bytes;
 use futures::future::BoxFuture;
@@ -55,6 +56,7 @@ use crate::error::FetchError;
 use crate::error::SubgraphBatchingError;
 use crate::graphql;
 use crate::json_ext::Object;
+use crate::layers::ServiceExt as _;
 use crate::plugins::authentication::subgraph::SigningParamsConfig;
 use crate::plugins::file_uploads;
 use crate::plugins::subscription::create_verifier;
@@ -67,12 +69,16 @@ use crate::plugins::telemetry::config_new::events::log_event;
 use crate::plugins::telemetry::config_new::events::SubgraphEventRequest;
 use crate::plugins::telemetry::config_new::events::SubgraphEventResponse;
 use crate::plugins::telemetry::consts::SUBGRAPH_REQUEST_SPAN_NAME;
+use crate::plugins::telemetry::formatters::in_current_otel_context;
+use crate::plugins::telemetry::redaction::is_redacted;
+use crate::plugins::telemetry::redaction::redact_request;
 use crate::plugins::telemetry::LOGGING_DISPLAY_BODY;
 use crate::plugins::telemetry::LOGGING_DISPLAY_HEADERS;
 use crate::protocols::websocket::convert_websocket_stream;
 use crate::protocols::websocket::GraphqlWebSocket;
 use crate::query_planner::OperationKind;
 use crate::services::layers::apq;
+use crate::services::subgraph::SubgraphStatistics;
 use crate::services::SubgraphRequest;
 use crate::services::SubgraphResponse;
 use crate::Configuration;
@@ -98,6 +104,11 @@ pub(crate) static APPLICATION_JSON_HEADER_VALUE: HeaderValue =
 static ACCEPT_GRAPHQL_JSON: HeaderValue =
     HeaderValue::from_static("application/json, application/graphql-response+json");
 
+/// Marks the subgraph requests registering an operation with the subgraph when the router
+/// reloads: they send the query along with its hash, instead of trying the hash first
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ApqRegistration;
+
 enum APQError {
     PersistedQueryNotSupported,
     PersistedQueryNotFound,
@@ -396,6 +407,7 @@ impl tower::Service<SubgraphRequest> for SubgraphService {
                 operation_name,
                 variables,
                 extensions,
+                ..
             } = body.clone();
 
             let hash_value = apq::calculate_hash_for_query(query.as_deref().unwrap_or_default());
@@ -413,8 +425,30 @@ impl tower::Service<SubgraphRequest> for SubgraphService {
                 operation_name,
                 variables,
                 extensions: extensions_with_apq,
+                document_id: None,
             };
 
+            if context
+                .extensions()
+                .with_lock(|lock| lock.contains_key::<ApqRegistration>())
+            {
+                apq_body.query = query;
+                let response = call_http(
+                    request,
+                    apq_body,
+                    context,
+                    client_factory.clone(),
+                    &service_name,
+                )
+                .await?;
+                if let APQError::PersistedQueryNotSupported =
+                    get_apq_error(response.response.body())
+                {
+                    apq_enabled.store(false, Relaxed);
+                }
+                return Ok(response);
+            }
+
             let response = call_http(
                 request.clone(),
                 apq_body.clone(),
@@ -577,7 +611,7 @@ async fn call_websocket(
         attrs.push(KeyValue::new(
             Key::from_static_str("http.request.body"),
             opentelemetry::Value::String(
-                serde_json::to_string(request.body())
+                serde_json::to_string(&redact_request(&context, request.body()))
                     .unwrap_or_default()
                     .into(),
             ),
@@ -599,7 +633,7 @@ async fn call_websocket(
     }
 
     if display_body {
-        tracing::info!(http.request.body = ?request.body(), apollo.subgraph.name = %service_name, "Websocket request body to subgraph {service_name:?}");
+        tracing::info!(http.request.body = ?redact_request(&context, request.body()), apollo.subgraph.name = %service_name, "Websocket request body to subgraph {service_name:?}");
     }
 
     let uri = request.uri();
@@ -678,7 +712,7 @@ async fn call_websocket(
 
     let (handle_sink, handle_stream) = handle.split();
 
-    tokio::task::spawn(async move {
+    tokio::task::spawn(in_current_otel_context(async move {
         match connection_closed_signal {
             Some(mut connection_closed_signal) => select! {
                 // We prefer to specify the order of checks within the select
@@ -699,7 +733,7 @@ async fn call_websocket(
                     .await;
             }
         }
-    });
+    }));
 
     subscription_stream_tx.send(Box::pin(handle_stream)).await?;
 
@@ -1232,8 +1266,15 @@ pub(crate) async fn call_single_http(
         .unwrap_or_default();
 
     let (parts, _) = subgraph_request.into_parts();
+    // the raw body of a redacted request is not logged, only the redacted request
+    let redacted_body = (log_request_level.is_some() && is_redacted(&context))
+        .then(|| serde_json::to_string(&redact_request(&context, &body)).unwrap_or_default());
+    // the arguments are only evaluated when debug logs are enabled
+    tracing::debug!(
+        "our JSON body: {:?}",
+        serde_json::to_string(&redact_request(&context, &body)).unwrap_or_default()
+    );
     let body = serde_json::to_string(&body)?;
-    tracing::debug!("our JSON body: {body:?}");
     let mut request = http::Request::from_parts(parts, RouterBody::from(body));
 
     request
@@ -1292,7 +1333,11 @@ pub(crate) async fn call_single_http(
         ));
         attrs.push(KeyValue::new(
             Key::from_static_str("http.request.body"),
-            opentelemetry::Value::String(format!("{:?}", request.body()).into()),
+            opentelemetry::Value::String(
+                redacted_body
+                    .unwrap_or_else(|| format!("{:?}", request.body()))
+                    .into(),
+            ),
         ));
         attrs.push(KeyValue::new(
             Key::from_static_str("subgraph.name"),
@@ -1470,6 +1515,12 @@ async fn do_fetch(
         })
         .map_err(|err| {
             tracing::error!(fetch_error = ?err);
+            // requests rejected by the subgraph request limits keep their error code
+            if let Some(err @ FetchError::SubrequestBodyTooLarge { .. }) =
+                err.downcast_ref::<FetchError>()
+            {
+                return err.clone();
+            }
             FetchError::SubrequestHttpError {
                 status_code: None,
                 service: service_name.to_string(),
@@ -1609,6 +1660,10 @@ fn get_apq_error(gql_response: &graphql::Response) -> APQError {
 pub(crate) struct SubgraphServiceFactory {
     pub(crate) services: Arc<HashMap<String, Arc<dyn MakeSubgraphService>>>,
     pub(crate) plugins: Arc<Plugins>,
+    /// Records the [`SubgraphStatistics`] of client requests in their context
+    pub(crate) statistics: bool,
+    /// Adds the parsed subgraph operation to subgraph requests, to validate subgraph responses
+    pub(crate) executable_documents: bool,
 }
 
 impl SubgraphServiceFactory {
@@ -1619,21 +1674,71 @@ impl SubgraphServiceFactory {
         SubgraphServiceFactory {
             services: Arc::new(services.into_iter().collect()),
             plugins,
+            statistics: false,
+            executable_documents: false,
         }
     }
 
+    pub(crate) fn with_statistics(mut self, statistics: bool) -> Self {
+        self.statistics = statistics;
+        self
+    }
+
+    pub(crate) fn with_executable_documents(mut self, executable_documents: bool) -> Self {
+        self.executable_documents = executable_documents;
+        self
+    }
+
     pub(crate) fn create(
         &self,
         name: &str,
     ) -> Option<BoxService<SubgraphRequest, SubgraphResponse, BoxError>> {
         self.services.get(name).map(|service| {
-            let service = service.make();
+            let service = if self.statistics {
+                Self::recording_statistics(name, service.make())
+            } else {
+                service.make()
+            };
             self.plugins
                 .iter()
                 .rev()
                 .fold(service, |acc, (_, e)| e.subgraph_service(name, acc))
         })
     }
+
+    // measured under the plugins, so that responses they return without calling the subgraph,
+    // like cache hits, are not counted
+    fn recording_statistics(
+        name: &str,
+        service: BoxService<SubgraphRequest, SubgraphResponse, BoxError>,
+    ) -> BoxService<SubgraphRequest, SubgraphResponse, BoxError> {
+        let subgraph_name: Arc<str> = name.into();
+        service
+            .map_future_with_request_data(
+                |request: &SubgraphRequest| (request.context.clone(), Instant::now()),
+                move |(context, start): (Context, Instant), future| {
+                    let subgraph_name = subgraph_name.clone();
+                    async move {
+                        let result: Result<SubgraphResponse, BoxError> = future.await;
+                        let error = match &result {
+                            Ok(response) => {
+                                !response.response.body().errors.is_empty()
+                                    || response.response.status().is_server_error()
+                            }
+                            Err(_) => true,
+                        };
+                        SubgraphStatistics::record(
+                            &context,
+                            &subgraph_name,
+                            start.elapsed(),
+                            error,
+                        );
+                        result
+                    }
+                },
+            )
+            .boxed()
+    }
 }
 
 /// make new instances of the subgraph service
@@ -1701,6 +1806,7 @@ mod tests {
     use crate::protocols::websocket::WebSocketProtocol;
     use crate::query_planner::fetch::OperationKind;
     use crate::services::router::body::get_body_bytes;
+    use crate::services::subgraph::SUBGRAPH_STATISTICS_CONTEXT_KEY;
     use crate::Context;
 
     // starts a local server emulating a subgraph returning status code 400
@@ -2150,6 +2256,49 @@ mod tests {
         server.await.unwrap();
     }
 
+    // starts a local server emulating a subgraph registering persisted queries,
+    // and panics if the request does not have both the query and its hash.
+    async fn emulate_apq_registration(listener: TcpListener) {
+        async fn handle(request: http::Request<Body>) -> Result<http::Response<Body>, Infallible> {
+            let (_, body) = request.into_parts();
+            let graphql_request: Result<graphql::Request, &str> = get_body_bytes(body)
+                .await
+                .map_err(|_| ())
+                .and_then(|bytes| serde_json::from_reader(bytes.reader()).map_err(|_| ()))
+                .map_err(|_| "failed to parse the request body as JSON");
+
+            match graphql_request {
+                Ok(request) => {
+                    if request.query.is_none()
+                        || !request.extensions.contains_key(PERSISTED_QUERY_KEY)
+                    {
+                        panic!("the query and its hash are expected when registering an operation")
+                    }
+
+                    return Ok(http::Response::builder()
+                        .header(CONTENT_TYPE, APPLICATION_JSON.essence_str())
+                        .status(StatusCode::OK)
+                        .body(
+                            serde_json::to_string(&Response {
+                                data: Some(Value::String(ByteString::from("test"))),
+                                ..Response::default()
+                            })
+                            .expect("always valid")
+                            .into(),
+                        )
+                        .unwrap());
+                }
+                Err(_) => {
+                    panic!("invalid graphql request recieved")
+                }
+            }
+        }
+
+        let make_svc = make_service_fn(|_conn| async { Ok::<_, Infallible>(service_fn(handle)) });
+        let server = Server::from_tcp(listener).unwrap().serve(make_svc);
+        server.await.unwrap();
+    }
+
     // starts a local server emulating a subgraph returning a response to request without apq
     // and panics if it finds a persistedQuery.
     async fn emulate_expected_apq_disabled_configuration(listener: TcpListener) {
@@ -3133,6 +3282,51 @@ mod tests {
         assert_eq!(resp.response.body(), &expected_resp);
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_apq_registration() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let socket_addr = listener.local_addr().unwrap();
+        tokio::task::spawn(emulate_apq_registration(listener));
+        let subgraph_service = SubgraphService::new(
+            "test",
+            true,
+            None,
+            Notify::default(),
+            HttpClientServiceFactory::from_config(
+                "test",
+                &Configuration::default(),
+                Http2Config::Enable,
+            ),
+        )
+        .expect("can create a SubgraphService");
+
+        let context = Context::new();
+        context
+            .extensions()
+            .with_lock(|mut lock| lock.insert(ApqRegistration));
+        let url = Uri::from_str(&format!("http://{socket_addr}")).unwrap();
+        let resp = subgraph_service
+            .clone()
+            .oneshot(
+                SubgraphRequest::builder()
+                    .supergraph_request(supergraph_request("query"))
+                    .subgraph_request(subgraph_http_request(url, "query"))
+                    .operation_kind(OperationKind::Query)
+                    .subgraph_name(String::from("test"))
+                    .context(context)
+                    .build(),
+            )
+            .await
+            .unwrap();
+
+        let expected_resp = Response {
+            data: Some(Value::String(ByteString::from("test"))),
+            ..Response::default()
+        };
+
+        assert_eq!(resp.response.body(), &expected_resp);
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn test_apq_disabled_subgraph_configuration() {
         let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
@@ -3331,4 +3525,77 @@ mod tests {
             .build();
         assert_eq!(actual, expected);
     }
+
+    #[tokio::test]
+    async fn it_records_subgraph_statistics_in_the_context() {
+        let subgraph = service_fn(|request: SubgraphRequest| async move {
+            let errors = if request.subgraph_request.body().query.as_deref() == Some("{ fail }") {
+                vec![Error::builder()
+                    .message("failed")
+                    .extension_code("FAILED")
+                    .build()]
+            } else {
+                Vec::new()
+            };
+            Ok::<_, BoxError>(
+                SubgraphResponse::fake_builder()
+                    .errors(errors)
+                    .context(request.context)
+                    .build(),
+            )
+        });
+        let factory = SubgraphServiceFactory::new(
+            vec![(
+                "products".to_string(),
+                Arc::new(subgraph) as Arc<dyn MakeSubgraphService>,
+            )],
+            Default::default(),
+        );
+
+        // statistics are only recorded when enabled
+        let context = Context::new();
+        factory
+            .create("products")
+            .unwrap()
+            .oneshot(
+                SubgraphRequest::fake_builder()
+                    .context(context.clone())
+                    .build(),
+            )
+            .await
+            .unwrap();
+        assert!(!context.contains_key(SUBGRAPH_STATISTICS_CONTEXT_KEY));
+
+        let factory = factory.with_statistics(true);
+        let context = Context::new();
+        assert_eq!(
+            SubgraphStatistics::from_context(&context),
+            SubgraphStatistics::default()
+        );
+        for query in ["{ me }", "{ fail }"] {
+            factory
+                .create("products")
+                .unwrap()
+                .oneshot(
+                    SubgraphRequest::fake_builder()
+                        .subgraph_request(
+                            http::Request::builder()
+                                .body(Request::builder().query(query).build())
+                                .unwrap(),
+                        )
+                        .context(context.clone())
+                        .build(),
+                )
+                .await
+                .unwrap();
+        }
+
+        let statistics = SubgraphStatistics::from_context(&context);
+        assert_eq!(statistics.subgraphs.len(), 1);
+        let products = &statistics.subgraphs["products"];
+        assert_eq!(products.requests, 2);
+        assert_eq!(products.errors, 1);
+        assert!(products.max_latency_ms <= products.total_latency_ms);
+        assert!(statistics.elapsed_ms >= products.max_latency_ms);
+    }
 }
@@ -3,13 +3,16 @@
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::task::Poll;
+use std::time::Duration;
 use std::time::Instant;
 
 use futures::future::BoxFuture;
+use futures::stream;
 use futures::stream::StreamExt;
 use futures::TryFutureExt;
 use http::StatusCode;
 use indexmap::IndexMap;
+use indexmap::IndexSet;
 use opentelemetry::Key;
 use opentelemetry::KeyValue;
 use router_bridge::planner::Planner;
@@ -27,17 +30,21 @@ use tracing::Span;
 use tracing_futures::Instrument;
 
 use crate::batching::BatchQuery;
+use crate::configuration::Apq;
 use crate::configuration::Batching;
 use crate::context::OPERATION_NAME;
 use crate::error::CacheResolverError;
 use crate::graphql;
 use crate::graphql::IntoGraphQLErrors;
 use crate::graphql::Response;
+use crate::http_ext;
 use crate::plugin::DynPlugin;
+use crate::plugins::subgraph_response_validation;
 use crate::plugins::subscription::SubscriptionConfig;
 use crate::plugins::telemetry::config_new::events::log_event;
 use crate::plugins::telemetry::config_new::events::SupergraphEventResponse;
 use crate::plugins::telemetry::consts::QUERY_PLANNING_SPAN_NAME;
+use crate::plugins::telemetry::formatters::in_current_otel_context;
 use crate::plugins::telemetry::tracing::apollo_telemetry::APOLLO_PRIVATE_DURATION_NS;
 use crate::plugins::telemetry::Telemetry;
 use crate::plugins::telemetry::LOGGING_DISPLAY_BODY;
@@ -61,14 +68,17 @@ use crate::services::new_service::ServiceFactory;
 use crate::services::query_planner;
 use crate::services::router::ClientRequestAccepts;
 use crate::services::subgraph::BoxGqlStream;
+use crate::services::subgraph_service::ApqRegistration;
 use crate::services::subgraph_service::MakeSubgraphService;
 use crate::services::subgraph_service::SubgraphServiceFactory;
 use crate::services::supergraph;
 use crate::services::ExecutionRequest;
 use crate::services::ExecutionResponse;
 use crate::services::ExecutionServiceFactory;
+use crate::services::OperationKind;
 use crate::services::QueryPlannerContent;
 use crate::services::QueryPlannerResponse;
+use crate::services::SubgraphRequest;
 use crate::services::SupergraphRequest;
 use crate::services::SupergraphResponse;
 use crate::spec::operation_limits::OperationLimits;
@@ -78,6 +88,9 @@ use crate::Context;
 use crate::Notify;
 
 pub(crate) const FIRST_EVENT_CONTEXT_KEY: &str = "apollo_router::supergraph::first_event";
+/// Maximum number of operations registered with the subgraphs in parallel
+const APQ_PRE_REGISTRATION_CONCURRENCY: usize = 8;
+const APQ_PRE_REGISTRATION_TIMEOUT: Duration = Duration::from_secs(60);
 
 /// An [`IndexMap`] of available plugins.
 pub(crate) type Plugins = IndexMap<String, Box<dyn DynPlugin>>;
@@ -275,17 +288,13 @@ async fn service_call(
                 }
             }
 
-            let ClientRequestAccepts {
-                multipart_defer: accepts_multipart_defer,
-                multipart_subscription: accepts_multipart_subscription,
-                ..
-            } = context
+            let accepts: ClientRequestAccepts = context
                 .extensions()
                 .with_lock(|lock| lock.get().cloned())
                 .unwrap_or_default();
             let mut subscription_tx = None;
-            if (is_deferred && !accepts_multipart_defer)
-                || (is_subscription && !accepts_multipart_subscription)
+            if (is_deferred && !accepts.accepts_defer())
+                || (is_subscription && !accepts.multipart_subscription)
             {
                 let (error_message, error_code) = if is_deferred {
                     (String::from("the router received a query with the @defer directive but the client does not accept multipart/mixed HTTP responses. To enable @defer support, add the HTTP header 'Accept: multipart/mixed;deferSpec=20220824'"), "DEFER_BAD_HEADER")
@@ -316,17 +325,14 @@ async fn service_call(
                     let cloned_supergraph_req =
                         clone_supergraph_request(&req.supergraph_request, context.clone());
                     // Spawn task for subscription
-                    tokio::spawn(async move {
-                        subscription_task(
-                            execution_service_factory_cloned,
-                            ctx,
-                            query_plan,
-                            subs_rx,
-                            notify,
-                            cloned_supergraph_req,
-                        )
-                        .await;
-                    });
+                    tokio::spawn(in_current_otel_context(subscription_task(
+                        execution_service_factory_cloned,
+                        ctx,
+                        query_plan,
+                        subs_rx,
+                        notify,
+                        cloned_supergraph_req,
+                    )));
                     subscription_tx = subs_tx.into();
                 }
 
@@ -455,6 +461,7 @@ async fn subscription_task(
                 query: query_plan.query.clone(),
                 query_metrics: query_plan.query_metrics,
                 estimated_size: Default::default(),
+                subgraph_ownership: Default::default(),
             })
         }),
         _ => {
@@ -575,7 +582,7 @@ async fn subscription_task(
                         schema: execution_service_factory.schema.clone(),
                         subgraph_schemas: execution_service_factory.subgraph_schemas.clone(),
                         plugins: plugins.clone(),
-                        subgraph_service_factory: Arc::new(SubgraphServiceFactory::new(subgraph_services.into_iter().map(|(k, v)| (k, Arc::new(v) as Arc<dyn MakeSubgraphService>)).collect(), plugins.clone())),
+                        subgraph_service_factory: Arc::new(SubgraphServiceFactory::new(subgraph_services.into_iter().map(|(k, v)| (k, Arc::new(v) as Arc<dyn MakeSubgraphService>)).collect(), plugins.clone()).with_statistics(conf.supergraph.experimental_subgraph_statistics).with_executable_documents(subgraph_response_validation::is_enabled(&conf))),
 
                     };
                 }
@@ -699,6 +706,7 @@ async fn plan_query(
         });
     }
 
+    let strategy = planning.strategy(operation_name.as_deref());
     let qpr = planning
         .call(
             query_planner::CachingRequest::builder()
@@ -709,7 +717,8 @@ async fn plan_query(
         )
         .instrument(tracing::info_span!(
             QUERY_PLANNING_SPAN_NAME,
-            "otel.kind" = "INTERNAL"
+            "otel.kind" = "INTERNAL",
+            "query_planning.strategy" = strategy.as_str()
         ))
         .await?;
 
@@ -822,13 +831,17 @@ impl PluggableSupergraphServiceBuilder {
             }
         }*/
 
-        let subgraph_service_factory = Arc::new(SubgraphServiceFactory::new(
-            self.subgraph_services
-                .into_iter()
-                .map(|(name, service)| (name, service.into()))
-                .collect(),
-            self.plugins.clone(),
-        ));
+        let subgraph_service_factory = Arc::new(
+            SubgraphServiceFactory::new(
+                self.subgraph_services
+                    .into_iter()
+                    .map(|(name, service)| (name, service.into()))
+                    .collect(),
+                self.plugins.clone(),
+            )
+            .with_statistics(configuration.supergraph.experimental_subgraph_statistics)
+            .with_executable_documents(subgraph_response_validation::is_enabled(&configuration)),
+        );
 
         Ok(SupergraphCreator {
             query_planner_service,
@@ -938,6 +951,7 @@ impl SupergraphCreator {
         self.query_planner_service.planners()
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub(crate) async fn warm_up_query_planner(
         &mut self,
         query_parser: &QueryAnalysisLayer,
@@ -945,6 +959,7 @@ impl SupergraphCreator {
         previous_cache: Option<InMemoryCachePlanner>,
         count: Option<usize>,
         experimental_reuse_query_plans: bool,
+        experimental_warm_up_leader_election: bool,
         experimental_pql_prewarm: bool,
     ) {
         self.query_planner_service
@@ -954,8 +969,132 @@ impl SupergraphCreator {
                 previous_cache,
                 count,
                 experimental_reuse_query_plans,
+                experimental_warm_up_leader_election,
                 experimental_pql_prewarm,
             )
             .await
     }
+
+    /// Registers the subgraph operations of the cached query plans with the subgraphs configured
+    /// with `apq.subgraph.pre_register`, so that the first requests for these operations only
+    /// send the query hash. The registration requests go through the subgraph plugins, so they
+    /// get the URL overrides, static header rules and subgraph authentication of other subgraph
+    /// requests, but no headers propagated from a client request, as there is none. It runs in
+    /// the background so that slow subgraphs do not delay the router, and is abandoned after a
+    /// timeout
+    pub(crate) fn pre_register_subgraph_operations(&self) {
+        let cache = self.previous_cache();
+        let config = self.config.clone();
+        let schema = self.schema.clone();
+        let subgraph_service_factory = self.subgraph_service_factory.clone();
+        tokio::task::spawn(async move {
+            let operations = {
+                let cache = cache.lock().await;
+                operations_to_pre_register(
+                    cache.iter().filter_map(|(_, content)| match content {
+                        Ok(QueryPlannerContent::Plan { plan }) => Some(plan.as_ref()),
+                        _ => None,
+                    }),
+                    &config.apq,
+                )
+            };
+            if operations.is_empty() {
+                return;
+            }
+
+            let count = operations.len();
+            let start = Instant::now();
+            let registration =
+                pre_register_operations(operations, &schema, &subgraph_service_factory);
+            match tokio::time::timeout(APQ_PRE_REGISTRATION_TIMEOUT, registration).await {
+                Ok(()) => tracing::info!(
+                    "registered {count} operations with the subgraphs in {:?}",
+                    start.elapsed()
+                ),
+                Err(_) => tracing::warn!(
+                    "could not register {count} operations with the subgraphs within {:?}",
+                    APQ_PRE_REGISTRATION_TIMEOUT
+                ),
+            }
+        });
+    }
+}
+
+/// Collects the query operations of the plans sent to the subgraphs configured with
+/// `apq.subgraph.pre_register`, as `(subgraph, query, operation name)`
+pub(crate) fn operations_to_pre_register<'a>(
+    plans: impl IntoIterator<Item = &'a QueryPlan>,
+    apq: &Apq,
+) -> IndexSet<(Arc<str>, String, Option<Arc<str>>)> {
+    let mut operations = IndexSet::new();
+    for plan in plans {
+        let mut fetches = Vec::new();
+        plan.root.fetch_nodes(&mut fetches);
+        for fetch in fetches {
+            // registering an operation may execute it, mutations are never sent
+            if *fetch.operation_kind() == OperationKind::Query
+                && apq.subgraph_pre_register(&fetch.service_name)
+            {
+                operations.insert((
+                    fetch.service_name.clone(),
+                    fetch.operation.as_serialized().to_string(),
+                    fetch.operation_name.clone(),
+                ));
+            }
+        }
+    }
+    operations
+}
+
+/// Sends the operations to their subgraph through the subgraph plugins, with the
+/// `ApqRegistration` marker in the context
+pub(crate) async fn pre_register_operations(
+    operations: IndexSet<(Arc<str>, String, Option<Arc<str>>)>,
+    schema: &Schema,
+    subgraph_service_factory: &SubgraphServiceFactory,
+) {
+    stream::iter(operations)
+        .for_each_concurrent(
+            APQ_PRE_REGISTRATION_CONCURRENCY,
+            |(service_name, query, operation_name)| async move {
+                let (Some(url), Some(service)) = (
+                    schema.subgraph_url(&service_name),
+                    subgraph_service_factory.create(&service_name),
+                ) else {
+                    return;
+                };
+                let context = Context::new();
+                context
+                    .extensions()
+                    .with_lock(|mut lock| lock.insert(ApqRegistration));
+                let request = SubgraphRequest::builder()
+                    .supergraph_request(Arc::new(http::Request::default()))
+                    .subgraph_request(
+                        http_ext::Request::builder()
+                            .method(http::Method::POST)
+                            .uri(url.clone())
+                            .body(
+                                graphql::Request::builder()
+                                    .query(query)
+                                    .and_operation_name(operation_name.map(|name| name.to_string()))
+                                    .build(),
+                            )
+                            .build()
+                            .expect("the subgraph url was already checked; qed"),
+                    )
+                    .subgraph_name(service_name.to_string())
+                    .operation_kind(OperationKind::Query)
+                    .context(context)
+                    .build();
+                // the subgraph usually rejects the operation for missing variables once it
+                // is registered, the response is not relevant
+                if let Err(err) = service.oneshot(request).await {
+                    tracing::warn!(
+                        subgraph = %service_name,
+                        "could not register an operation with the subgraph: {err}"
+                    );
+                }
+            },
+        )
+        .await
 }
@@ -3626,3 +3626,135 @@ async fn invalid_input_enum() {
 
     insta::assert_json_snapshot!(response);
 }
+
+fn pre_registration_plan() -> crate::services::execution::QueryPlan {
+    let root = serde_json::from_value(serde_json::json!({
+        "kind": "Sequence",
+        "nodes": [
+            {
+                "kind": "Fetch",
+                "serviceName": "user",
+                "variableUsages": [],
+                "operation": "query CurrentUser__user__0{currentUser{__typename id name}}",
+                "operationName": "CurrentUser__user__0",
+                "operationKind": "query"
+            },
+            {
+                "kind": "Flatten",
+                "path": ["currentUser"],
+                "node": {
+                    "kind": "Fetch",
+                    "serviceName": "orga",
+                    "requires": [],
+                    "variableUsages": [],
+                    "operation": "query CurrentUser__orga__1($representations:[_Any!]!){_entities(representations:$representations){...on User{activeOrganization{name}}}}",
+                    "operationName": "CurrentUser__orga__1",
+                    "operationKind": "query"
+                }
+            },
+            {
+                "kind": "Fetch",
+                "serviceName": "user",
+                "variableUsages": [],
+                "operation": "mutation{createUser{id}}",
+                "operationKind": "mutation"
+            }
+        ]
+    }))
+    .unwrap();
+    crate::services::execution::QueryPlan::fake_builder()
+        .root(root)
+        .build()
+}
+
+#[test]
+fn it_collects_the_query_operations_to_pre_register() {
+    let apq: crate::configuration::Apq = serde_json::from_value(serde_json::json!({
+        "subgraph": {
+            "all": { "enabled": true, "pre_register": true },
+            "subgraphs": { "orga": { "enabled": true } }
+        }
+    }))
+    .unwrap();
+    let plan = pre_registration_plan();
+
+    // the same operation in several plans is only registered once, mutations are never
+    // registered, and the operations of subgraphs without `pre_register` are left out
+    let operations = super::service::operations_to_pre_register([&plan, &plan], &apq);
+    assert_eq!(operations.len(), 1);
+    let (service_name, query, operation_name) = operations.first().unwrap();
+    assert_eq!(service_name.as_ref(), "user");
+    assert_eq!(
+        query,
+        "query CurrentUser__user__0{currentUser{__typename id name}}"
+    );
+    assert_eq!(operation_name.as_deref(), Some("CurrentUser__user__0"));
+
+    // nothing is registered when APQ is disabled for the subgraph
+    let apq: crate::configuration::Apq = serde_json::from_value(serde_json::json!({
+        "subgraph": { "all": { "enabled": false, "pre_register": true } }
+    }))
+    .unwrap();
+    assert!(super::service::operations_to_pre_register([&plan], &apq).is_empty());
+}
+
+#[tokio::test]
+async fn it_pre_registers_operations_through_the_subgraph_services() {
+    use crate::services::subgraph_service::ApqRegistration;
+    use crate::services::subgraph_service::MakeSubgraphService;
+    use crate::services::subgraph_service::SubgraphServiceFactory;
+
+    let received = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let subgraph = {
+        let received = received.clone();
+        tower::service_fn(move |request: subgraph::Request| {
+            let received = received.clone();
+            async move {
+                assert!(request
+                    .context
+                    .extensions()
+                    .with_lock(|lock| lock.contains_key::<ApqRegistration>()));
+                let body = request.subgraph_request.body();
+                received.lock().unwrap().push((
+                    request.subgraph_name.clone(),
+                    request.subgraph_request.uri().to_string(),
+                    body.query.clone(),
+                    body.operation_name.clone(),
+                ));
+                Ok::<_, tower::BoxError>(
+                    subgraph::Response::fake_builder()
+                        .context(request.context)
+                        .build(),
+                )
+            }
+        })
+    };
+    // there is no service for the `orga` subgraph, its operations are skipped
+    let factory = SubgraphServiceFactory::new(
+        vec![(
+            "user".to_string(),
+            Arc::new(subgraph) as Arc<dyn MakeSubgraphService>,
+        )],
+        Default::default(),
+    );
+    let schema = Schema::parse(SCHEMA, &Default::default()).unwrap();
+    let apq: crate::configuration::Apq = serde_json::from_value(serde_json::json!({
+        "subgraph": { "all": { "enabled": true, "pre_register": true } }
+    }))
+    .unwrap();
+    let plan = pre_registration_plan();
+    let operations = super::service::operations_to_pre_register([&plan], &apq);
+    assert_eq!(operations.len(), 2);
+
+    super::service::pre_register_operations(operations, &schema, &factory).await;
+
+    assert_eq!(
+        *received.lock().unwrap(),
+        [(
+            Some("user".to_string()),
+            "http://localhost:4001/graphql".to_string(),
+            Some("query CurrentUser__user__0{currentUser{__typename id name}}".to_string()),
+            Some("CurrentUser__user__0".to_string()),
+        )]
+    );
+}
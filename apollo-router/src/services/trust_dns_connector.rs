@@ -1,16 +1,72 @@
+use std::collections::HashMap;
 use std::future::Future;
 use std::io;
+use std::net::IpAddr;
 use std::net::SocketAddr;
 use std::net::ToSocketAddrs;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::Context;
 use std::task::Poll;
+use std::time::Duration;
 
 use hyper::client::connect::dns::Name;
 use hyper::client::HttpConnector;
 use hyper::service::Service;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use trust_dns_resolver::config::LookupIpStrategy;
+use trust_dns_resolver::config::NameServerConfig;
+use trust_dns_resolver::config::Protocol;
+use trust_dns_resolver::config::ResolverConfig;
+use trust_dns_resolver::system_conf::read_system_conf;
 use trust_dns_resolver::TokioAsyncResolver;
 
+/// DNS resolution configuration
+#[derive(PartialEq, Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields, default)]
+pub(crate) struct DnsConfig {
+    /// Which IP address families are used, and in which order
+    resolution_strategy: DnsResolutionStrategy,
+    /// DNS servers to query, as `ip:port`, instead of the servers of the system configuration
+    servers: Vec<SocketAddr>,
+    /// Caching duration of resolved addresses, overriding the TTL of the DNS records
+    #[serde(deserialize_with = "humantime_serde::deserialize", default)]
+    #[schemars(with = "String", default)]
+    ttl: Option<Duration>,
+    /// IP addresses of host names, used without querying DNS servers
+    hosts: HashMap<String, Vec<IpAddr>>,
+}
+
+/// Which IP address families are used, and in which order
+#[derive(PartialEq, Debug, Clone, Copy, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum DnsResolutionStrategy {
+    /// Only query for IPv4 addresses
+    Ipv4Only,
+    /// Only query for IPv6 addresses
+    Ipv6Only,
+    /// Query for both IPv4 and IPv6 addresses
+    Ipv4AndIpv6,
+    /// Query for IPv6 addresses, and for IPv4 addresses if there are none
+    Ipv6ThenIpv4,
+    /// Query for IPv4 addresses, and for IPv6 addresses if there are none
+    #[default]
+    Ipv4ThenIpv6,
+}
+
+impl From<DnsResolutionStrategy> for LookupIpStrategy {
+    fn from(strategy: DnsResolutionStrategy) -> Self {
+        match strategy {
+            DnsResolutionStrategy::Ipv4Only => LookupIpStrategy::Ipv4Only,
+            DnsResolutionStrategy::Ipv6Only => LookupIpStrategy::Ipv6Only,
+            DnsResolutionStrategy::Ipv4AndIpv6 => LookupIpStrategy::Ipv4AndIpv6,
+            DnsResolutionStrategy::Ipv6ThenIpv4 => LookupIpStrategy::Ipv6thenIpv4,
+            DnsResolutionStrategy::Ipv4ThenIpv6 => LookupIpStrategy::Ipv4thenIpv6,
+        }
+    }
+}
+
 /// Wrapper around trust-dns-resolver's
 /// [`TokioAsyncResolver`](https://docs.rs/trust-dns-resolver/0.23.2/trust_dns_resolver/type.TokioAsyncResolver.html)
 ///
@@ -18,14 +74,49 @@ use trust_dns_resolver::TokioAsyncResolver;
 /// the background task is also created, it needs to be spawned on top of an executor before using the client,
 /// or dns requests will block.
 #[derive(Debug, Clone)]
-pub(crate) struct AsyncHyperResolver(TokioAsyncResolver);
+pub(crate) struct AsyncHyperResolver {
+    resolver: TokioAsyncResolver,
+    hosts: Arc<HashMap<String, Vec<IpAddr>>>,
+}
 
 impl AsyncHyperResolver {
-    /// constructs a new resolver from default configuration, uses the corresponding method of
-    /// [`TokioAsyncResolver`](https://docs.rs/trust-dns-resolver/0.23.2/trust_dns_resolver/type.TokioAsyncResolver.html#method.new)
-    pub(crate) fn new_from_system_conf() -> Result<Self, io::Error> {
-        let resolver = TokioAsyncResolver::tokio_from_system_conf()?;
-        Ok(Self(resolver))
+    /// constructs a new resolver from the system configuration, with the overrides of the
+    /// router's DNS configuration
+    pub(crate) fn new(config: &DnsConfig) -> Result<Self, io::Error> {
+        let (mut resolver_config, mut options) = read_system_conf()?;
+        if !config.servers.is_empty() {
+            let name_servers = config
+                .servers
+                .iter()
+                .flat_map(|server| {
+                    [
+                        NameServerConfig::new(*server, Protocol::Udp),
+                        NameServerConfig::new(*server, Protocol::Tcp),
+                    ]
+                })
+                .collect::<Vec<_>>();
+            resolver_config = ResolverConfig::from_parts(
+                resolver_config.domain().cloned(),
+                resolver_config.search().to_vec(),
+                name_servers,
+            );
+        }
+        options.ip_strategy = config.resolution_strategy.into();
+        if let Some(ttl) = config.ttl {
+            options.positive_min_ttl = Some(ttl);
+            options.positive_max_ttl = Some(ttl);
+        }
+
+        Ok(Self {
+            resolver: TokioAsyncResolver::tokio(resolver_config, options),
+            hosts: Arc::new(
+                config
+                    .hosts
+                    .iter()
+                    .map(|(host, addresses)| (host.to_ascii_lowercase(), addresses.clone()))
+                    .collect(),
+            ),
+        })
     }
 }
 
@@ -39,7 +130,15 @@ impl Service<Name> for AsyncHyperResolver {
     }
 
     fn call(&mut self, name: Name) -> Self::Future {
-        let resolver = self.0.clone();
+        if let Some(addresses) = self.hosts.get(&name.as_str().to_ascii_lowercase()) {
+            let addresses: Vec<SocketAddr> = addresses
+                .iter()
+                .map(|addr| SocketAddr::new(*addr, 0))
+                .collect();
+            return Box::pin(async move { Ok(addresses.into_iter()) });
+        }
+
+        let resolver = self.resolver.clone();
 
         Box::pin(async move {
             Ok(resolver
@@ -56,8 +155,58 @@ impl Service<Name> for AsyncHyperResolver {
     }
 }
 
-/// A helper function to create an http connector and a dns task with the default configuration
-pub(crate) fn new_async_http_connector() -> Result<HttpConnector<AsyncHyperResolver>, io::Error> {
-    let resolver = AsyncHyperResolver::new_from_system_conf()?;
+/// A helper function to create an http connector and a dns task with the given configuration
+pub(crate) fn new_async_http_connector(
+    dns: &DnsConfig,
+) -> Result<HttpConnector<AsyncHyperResolver>, io::Error> {
+    let resolver = AsyncHyperResolver::new(dns)?;
     Ok(HttpConnector::new_with_resolver(resolver))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn it_resolves_static_hosts() {
+        let config: DnsConfig = serde_json::from_value(serde_json::json!({
+            "hosts": {
+                "Products.internal": ["10.0.0.1", "fd00::1"]
+            }
+        }))
+        .unwrap();
+        let mut resolver = AsyncHyperResolver::new(&config).unwrap();
+
+        let addresses: Vec<SocketAddr> = resolver
+            .call("products.internal".parse().unwrap())
+            .await
+            .unwrap()
+            .collect();
+        assert_eq!(
+            addresses,
+            vec![
+                "10.0.0.1:0".parse::<SocketAddr>().unwrap(),
+                "[fd00::1]:0".parse().unwrap()
+            ]
+        );
+    }
+
+    #[test]
+    fn it_parses_the_configuration() {
+        let config: DnsConfig = serde_yaml::from_str(
+            r#"
+            resolution_strategy: ipv6_then_ipv4
+            servers:
+              - 10.0.0.53:53
+            ttl: 30s
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.resolution_strategy,
+            DnsResolutionStrategy::Ipv6ThenIpv4
+        );
+        assert_eq!(config.ttl, Some(Duration::from_secs(30)));
+        assert_eq!(config.servers.len(), 1);
+    }
+}
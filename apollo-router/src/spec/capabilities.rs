@@ -0,0 +1,534 @@
+//! Fallback for supergraphs linking features the router does not support
+//!
+//! Composing with a newer version of federation can produce a supergraph linking a newer minor
+//! version of the join spec, or a new feature for `EXECUTION`, that the query planners refuse.
+//! With `supergraph.experimental_spec_fallback`, newer minor versions of the specs required to
+//! plan queries are handled as the latest version the router supports, as long as the supergraph
+//! only uses the directives and arguments defined by that version, and the `EXECUTION` purpose of
+//! unknown features is dropped so that they are ignored. Features for `SECURITY` are never
+//! degraded: ignoring them could expose data they protect.
+
+use std::collections::HashMap;
+
+use apollo_compiler::ast;
+use apollo_compiler::Name;
+use apollo_compiler::Node;
+use apollo_federation::link::spec::Identity;
+use apollo_federation::link::spec::Url;
+use apollo_federation::link::Link;
+use apollo_federation::link::Purpose;
+use serde::Serialize;
+
+use crate::error::SchemaError;
+
+/// Directives of a spec version, with the names of their arguments
+type SpecDirectives = &'static [(&'static str, &'static [&'static str])];
+
+const JOIN_V0_1: SpecDirectives = &[
+    ("graph", &["name", "url"]),
+    ("type", &["graph", "key"]),
+    ("field", &["graph", "requires", "provides"]),
+    ("owner", &["graph"]),
+];
+const JOIN_V0_2: SpecDirectives = &[
+    ("graph", &["name", "url"]),
+    ("type", &["graph", "key", "extension", "resolvable"]),
+    (
+        "field",
+        &["graph", "requires", "provides", "type", "external"],
+    ),
+    ("implements", &["graph", "interface"]),
+];
+const JOIN_V0_3: SpecDirectives = &[
+    ("graph", &["name", "url"]),
+    (
+        "type",
+        &[
+            "graph",
+            "key",
+            "extension",
+            "resolvable",
+            "isInterfaceObject",
+        ],
+    ),
+    (
+        "field",
+        &[
+            "graph",
+            "requires",
+            "provides",
+            "type",
+            "external",
+            "override",
+            "usedOverridden",
+        ],
+    ),
+    ("implements", &["graph", "interface"]),
+    ("unionMember", &["graph", "member"]),
+    ("enumValue", &["graph"]),
+];
+const JOIN_V0_4: SpecDirectives = &[
+    ("graph", &["name", "url"]),
+    (
+        "type",
+        &[
+            "graph",
+            "key",
+            "extension",
+            "resolvable",
+            "isInterfaceObject",
+        ],
+    ),
+    (
+        "field",
+        &[
+            "graph",
+            "requires",
+            "provides",
+            "type",
+            "external",
+            "override",
+            "usedOverridden",
+            "overrideLabel",
+        ],
+    ),
+    ("implements", &["graph", "interface"]),
+    ("unionMember", &["graph", "member"]),
+    ("enumValue", &["graph"]),
+];
+const JOIN_V0_5: SpecDirectives = &[
+    ("graph", &["name", "url"]),
+    (
+        "type",
+        &[
+            "graph",
+            "key",
+            "extension",
+            "resolvable",
+            "isInterfaceObject",
+        ],
+    ),
+    (
+        "field",
+        &[
+            "graph",
+            "requires",
+            "provides",
+            "type",
+            "external",
+            "override",
+            "usedOverridden",
+            "overrideLabel",
+            "contextArguments",
+        ],
+    ),
+    ("implements", &["graph", "interface"]),
+    ("unionMember", &["graph", "member"]),
+    ("enumValue", &["graph"]),
+    ("directive", &["graphs", "name", "args"]),
+];
+const LINK_V1_0: SpecDirectives = &[("link", &["url", "as", "for", "import"])];
+const CORE_V0_1: SpecDirectives = &[("core", &["feature"])];
+const CORE_V0_2: SpecDirectives = &[("core", &["feature", "as", "for"])];
+
+/// Returns the directives defined by a supported spec version. Versions missing here are never
+/// used as a fallback
+fn spec_directives(url: &Url) -> Option<SpecDirectives> {
+    let version = (url.version.major, url.version.minor);
+    if url.identity == Identity::join_identity() {
+        match version {
+            (0, 1) => Some(JOIN_V0_1),
+            (0, 2) => Some(JOIN_V0_2),
+            (0, 3) => Some(JOIN_V0_3),
+            (0, 4) => Some(JOIN_V0_4),
+            (0, 5) => Some(JOIN_V0_5),
+            _ => None,
+        }
+    } else if url.identity == Identity::link_identity() {
+        (version == (1, 0)).then_some(LINK_V1_0)
+    } else if url.identity == Identity::core_identity() {
+        match version {
+            (0, 1) => Some(CORE_V0_1),
+            (0, 2) => Some(CORE_V0_2),
+            _ => None,
+        }
+    } else {
+        None
+    }
+}
+
+/// A feature linked by the supergraph that the router does not support
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct CapabilityGap {
+    /// URL of the feature in the `@link` directive
+    pub(crate) url: String,
+    /// Purpose of the feature in the `for` argument of the `@link` directive
+    pub(crate) purpose: Option<String>,
+    /// URL of the version the feature is handled as, or `None` if the feature is ignored
+    pub(crate) handled_as: Option<String>,
+}
+
+impl CapabilityGap {
+    pub(crate) fn log(&self) {
+        tracing::warn!(
+            feature = %self.url,
+            purpose = self.purpose.as_deref(),
+            handled_as = self.handled_as.as_deref(),
+            "the supergraph links a feature the router does not support, {}",
+            match &self.handled_as {
+                Some(handled_as) => format!("handling it as {handled_as}"),
+                None => String::from("ignoring it"),
+            }
+        );
+    }
+}
+
+/// Degrades the features of the parsed supergraph the router does not support, returning the
+/// features that were degraded. The rewritten definitions keep their locations in the original
+/// source, so that errors are reported against it. Fails if the supergraph uses directives or
+/// arguments missing from the version a feature would be handled as
+pub(crate) fn degrade_unsupported_features(
+    document: &mut ast::Document,
+) -> Result<Vec<CapabilityGap>, SchemaError> {
+    // the links are checked against the whole document before rewriting it
+    let mut gaps = Vec::new();
+    for (index, definition) in document.definitions.iter().enumerate() {
+        if !matches!(
+            definition,
+            ast::Definition::SchemaDefinition(_) | ast::Definition::SchemaExtension(_)
+        ) {
+            continue;
+        }
+        for (position, directive) in definition.directives().iter().enumerate() {
+            if let Some(gap) = degrade(document, directive)? {
+                gaps.push((index, position, gap));
+            }
+        }
+    }
+
+    Ok(gaps
+        .into_iter()
+        .map(|(index, position, gap)| {
+            let directive = match &mut document.definitions[index] {
+                ast::Definition::SchemaDefinition(schema) => {
+                    schema.make_mut().directives[position].make_mut()
+                }
+                ast::Definition::SchemaExtension(schema) => {
+                    schema.make_mut().directives[position].make_mut()
+                }
+                _ => unreachable!("only schema definitions link features"),
+            };
+            match &gap.handled_as {
+                // `@core` links features with the `feature` argument
+                Some(handled_as) => {
+                    for argument in &mut directive.arguments {
+                        if argument.name == "url" || argument.name == "feature" {
+                            argument.make_mut().value =
+                                Node::new(ast::Value::String(handled_as.clone()));
+                        }
+                    }
+                }
+                None => directive
+                    .arguments
+                    .retain(|argument| argument.name != "for"),
+            }
+            gap
+        })
+        .collect())
+}
+
+fn degrade(
+    document: &ast::Document,
+    directive: &Node<ast::Directive>,
+) -> Result<Option<CapabilityGap>, SchemaError> {
+    let Ok(link) = Link::from_directive_application(directive) else {
+        return Ok(None);
+    };
+    if link.purpose == Some(Purpose::SECURITY) {
+        return Ok(None);
+    }
+    let url = link.url.to_string();
+    let purpose = link.purpose.as_ref().map(|purpose| purpose.to_string());
+
+    match apollo_federation::supported_supergraph_spec_versions(&link.url.identity) {
+        Some(versions) => {
+            if versions.contains(&link.url.version) {
+                return Ok(None);
+            }
+            // minor versions of the specs only add to the previous ones
+            let Some(fallback) = versions
+                .into_iter()
+                .filter(|version| {
+                    version.major == link.url.version.major
+                        && version.minor < link.url.version.minor
+                })
+                .max()
+                .map(|version| Url {
+                    identity: link.url.identity.clone(),
+                    version,
+                })
+            else {
+                return Ok(None);
+            };
+            let handled_as = fallback.to_string();
+            check_fallback(document, &link, &fallback).map_err(|reason| {
+                SchemaError::UnsupportedFeature(format!(
+                    "{url} cannot be handled as {handled_as}: {reason}"
+                ))
+            })?;
+            Ok(Some(CapabilityGap {
+                url,
+                purpose,
+                handled_as: Some(handled_as),
+            }))
+        }
+        None if link.purpose == Some(Purpose::EXECUTION) => Ok(Some(CapabilityGap {
+            url,
+            purpose,
+            handled_as: None,
+        })),
+        None => Ok(None),
+    }
+}
+
+/// Checks that every application of the directives of a linked feature only uses the directives
+/// and arguments defined by the version it falls back to
+fn check_fallback(document: &ast::Document, link: &Link, fallback: &Url) -> Result<(), String> {
+    let directives = spec_directives(fallback)
+        .ok_or_else(|| String::from("the directives of this version are not known"))?;
+    let arguments: HashMap<Name, &[&str]> = directives
+        .iter()
+        .map(|(name, arguments)| {
+            (
+                link.directive_name_in_schema(&Name::new_unchecked(name)),
+                *arguments,
+            )
+        })
+        .collect();
+    let prefix = format!("{}__", link.spec_name_in_schema());
+
+    for directive in directive_applications(document) {
+        match arguments.get(&directive.name) {
+            Some(arguments) => {
+                if let Some(argument) = directive
+                    .arguments
+                    .iter()
+                    .find(|argument| !arguments.contains(&argument.name.as_str()))
+                {
+                    return Err(format!(
+                        "the supergraph uses the argument '{}' of @{}, which is not defined",
+                        argument.name, directive.name
+                    ));
+                }
+            }
+            None if directive.name.as_str().starts_with(&prefix) => {
+                return Err(format!(
+                    "the supergraph uses @{}, which is not defined",
+                    directive.name
+                ));
+            }
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+/// Returns the directives applied in the type system definitions of the document
+fn directive_applications(document: &ast::Document) -> Vec<&Node<ast::Directive>> {
+    let mut directives = Vec::new();
+    for definition in &document.definitions {
+        directives.extend(definition.directives().iter());
+        let fields = match definition {
+            ast::Definition::ObjectTypeDefinition(object) => &object.fields[..],
+            ast::Definition::ObjectTypeExtension(object) => &object.fields[..],
+            ast::Definition::InterfaceTypeDefinition(interface) => &interface.fields[..],
+            ast::Definition::InterfaceTypeExtension(interface) => &interface.fields[..],
+            _ => &[],
+        };
+        let input_values = match definition {
+            ast::Definition::InputObjectTypeDefinition(input) => &input.fields[..],
+            ast::Definition::InputObjectTypeExtension(input) => &input.fields[..],
+            ast::Definition::DirectiveDefinition(directive) => &directive.arguments[..],
+            _ => &[],
+        };
+        let enum_values = match definition {
+            ast::Definition::EnumTypeDefinition(enum_) => &enum_.values[..],
+            ast::Definition::EnumTypeExtension(enum_) => &enum_.values[..],
+            _ => &[],
+        };
+        for field in fields {
+            directives.extend(field.directives.iter());
+            for argument in &field.arguments {
+                directives.extend(argument.directives.iter());
+            }
+        }
+        for input_value in input_values {
+            directives.extend(input_value.directives.iter());
+        }
+        for enum_value in enum_values {
+            directives.extend(enum_value.directives.iter());
+        }
+    }
+    directives
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn degrade_supergraph(links: &str) -> (String, Vec<CapabilityGap>) {
+        degrade_sdl(&supergraph(links, "type Query { me: String }")).unwrap()
+    }
+
+    fn degrade_sdl(sdl: &str) -> Result<(String, Vec<CapabilityGap>), SchemaError> {
+        let mut document = ast::Document::parse(sdl, "schema.graphql").unwrap();
+        let gaps = degrade_unsupported_features(&mut document)?;
+        Ok((document.to_string(), gaps))
+    }
+
+    fn supergraph(links: &str, types: &str) -> String {
+        format!(
+            r#"
+            schema
+              @link(url: "https://specs.apollo.dev/link/v1.0")
+              {links}
+            {{
+              query: Query
+            }}
+            directive @link(url: String, as: String, for: link__Purpose, import: [link__Import]) repeatable on SCHEMA
+            scalar link__Import
+            enum link__Purpose {{ SECURITY EXECUTION }}
+            {types}
+            "#
+        )
+    }
+
+    #[test]
+    fn it_keeps_supported_features() {
+        assert_eq!(
+            degrade_supergraph(
+                r#"@link(url: "https://specs.apollo.dev/join/v0.3", for: EXECUTION)"#
+            )
+            .1,
+            vec![]
+        );
+    }
+
+    #[test]
+    fn it_handles_newer_minor_versions_as_the_latest_supported() {
+        let (sdl, gaps) = degrade_supergraph(
+            r#"@link(url: "https://specs.apollo.dev/join/v0.99", for: EXECUTION)"#,
+        );
+        assert_eq!(
+            gaps,
+            vec![CapabilityGap {
+                url: "https://specs.apollo.dev/join/v0.99".to_string(),
+                purpose: Some("EXECUTION".to_string()),
+                handled_as: Some("https://specs.apollo.dev/join/v0.5".to_string()),
+            }]
+        );
+        assert!(sdl.contains(r#"@link(url: "https://specs.apollo.dev/join/v0.5", for: EXECUTION)"#));
+    }
+
+    #[test]
+    fn it_only_falls_back_when_the_directives_are_defined_by_the_fallback_version() {
+        let join = r#"@link(url: "https://specs.apollo.dev/join/v0.99", for: EXECUTION)"#;
+        let (_, gaps) = degrade_sdl(&supergraph(
+            join,
+            r#"
+            type Query @join__type(graph: A) {
+              me: String @join__field(graph: A, overrideLabel: "percent(50)")
+            }
+            enum join__Graph { A @join__graph(name: "a", url: "http://a") }
+            "#,
+        ))
+        .unwrap();
+        assert_eq!(gaps.len(), 1);
+
+        // an argument added by the newer version
+        let error = degrade_sdl(&supergraph(
+            join,
+            r#"
+            type Query @join__type(graph: A) {
+              me: String @join__field(graph: A, newArgument: true)
+            }
+            "#,
+        ))
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unsupported feature: https://specs.apollo.dev/join/v0.99 cannot be handled as https://specs.apollo.dev/join/v0.5: the supergraph uses the argument 'newArgument' of @join__field, which is not defined"
+        );
+
+        // a directive added by the newer version, under the name given by `as`
+        let error = degrade_sdl(&supergraph(
+            r#"@link(url: "https://specs.apollo.dev/join/v0.99", as: "j", for: EXECUTION)"#,
+            r#"
+            type Query @j__newDirective(graph: A) @join__unrelated {
+              me: String
+            }
+            "#,
+        ))
+        .unwrap_err();
+        assert!(error
+            .to_string()
+            .ends_with("the supergraph uses @j__newDirective, which is not defined"));
+    }
+
+    #[test]
+    fn it_handles_features_linked_with_core() {
+        let (sdl, gaps) = degrade_sdl(
+            r#"
+            schema
+              @core(feature: "https://specs.apollo.dev/core/v0.2")
+              @core(feature: "https://specs.apollo.dev/join/v0.99", for: EXECUTION)
+            {
+              query: Query
+            }
+            directive @core(feature: String!, as: String, for: core__Purpose) repeatable on SCHEMA
+            enum core__Purpose { SECURITY EXECUTION }
+            type Query @join__type(graph: A, key: "id") { me: String @join__field(graph: A) }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            gaps,
+            vec![CapabilityGap {
+                url: "https://specs.apollo.dev/join/v0.99".to_string(),
+                purpose: Some("EXECUTION".to_string()),
+                handled_as: Some("https://specs.apollo.dev/join/v0.5".to_string()),
+            }]
+        );
+        assert!(
+            sdl.contains(r#"@core(feature: "https://specs.apollo.dev/join/v0.5", for: EXECUTION)"#)
+        );
+    }
+
+    #[test]
+    fn it_ignores_unknown_features_for_execution() {
+        let (sdl, gaps) = degrade_supergraph(
+            r#"@link(url: "https://specs.apollo.dev/unknown/v0.1", for: EXECUTION)"#,
+        );
+        assert_eq!(gaps[0].handled_as, None);
+        assert!(sdl.contains(r#"@link(url: "https://specs.apollo.dev/unknown/v0.1")"#));
+    }
+
+    #[test]
+    fn it_does_not_degrade_features_for_security() {
+        assert_eq!(
+            degrade_supergraph(
+                r#"@link(url: "https://specs.apollo.dev/unknown/v0.1", for: SECURITY)"#
+            )
+            .1,
+            vec![]
+        );
+        // a new major version can break the previous ones
+        assert_eq!(
+            degrade_supergraph(
+                r#"@link(url: "https://specs.apollo.dev/join/v1.0", for: EXECUTION)"#
+            )
+            .1,
+            vec![]
+        );
+    }
+}
@@ -2,6 +2,7 @@
 #![cfg_attr(not(test), deny(clippy::expect_used))]
 #![cfg_attr(not(test), deny(clippy::panic))]
 
+pub(crate) mod capabilities;
 mod field_type;
 mod fragments;
 pub(crate) mod operation_limits;
@@ -48,6 +49,8 @@ pub(crate) enum SpecError {
     TransformError(String),
     /// parsing error: {0}
     ParseError(ValidationErrors),
+    /// parser {0} limit reached
+    ParserLimitExceeded(ParserLimit),
     /// validation error: {0}
     ValidationError(ValidationErrors),
     /// Unknown operation named "{0}"
@@ -58,12 +61,24 @@ pub(crate) enum SpecError {
     QueryHashing(String),
 }
 
+/// Limit of the GraphQL parser
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ParserLimit {
+    /// recursion
+    Recursion,
+    /// token
+    Token,
+}
+
 pub(crate) const GRAPHQL_VALIDATION_FAILURE_ERROR_KEY: &str = "## GraphQLValidationFailure\n";
 
 impl SpecError {
     pub(crate) const fn get_error_key(&self) -> &'static str {
         match self {
-            SpecError::TransformError(_) | SpecError::ParseError(_) => "## GraphQLParseFailure\n",
+            SpecError::TransformError(_)
+            | SpecError::ParseError(_)
+            | SpecError::ParserLimitExceeded(_) => "## GraphQLParseFailure\n",
             SpecError::UnknownOperation(_) => "## GraphQLUnknownOperationName\n",
             _ => GRAPHQL_VALIDATION_FAILURE_ERROR_KEY,
         }
@@ -82,6 +97,7 @@ impl ErrorExtension for SpecError {
             SpecError::InvalidField(_, _) => "INVALID_FIELD",
             SpecError::TransformError(_) => "PARSING_ERROR",
             SpecError::ParseError(_) => "PARSING_ERROR",
+            SpecError::ParserLimitExceeded(_) => "PARSER_LIMIT_EXCEEDED",
             SpecError::ValidationError(_) => "GRAPHQL_VALIDATION_FAILED",
             SpecError::UnknownOperation(_) => "GRAPHQL_VALIDATION_FAILED",
             SpecError::SubscriptionNotSupported => "SUBSCRIPTION_NOT_SUPPORTED",
@@ -100,6 +116,9 @@ impl ErrorExtension for SpecError {
                 obj.insert("type", ty.clone().into());
                 obj.insert("field", field.clone().into());
             }
+            SpecError::ParserLimitExceeded(limit) => {
+                obj.insert("limit", limit.to_string().into());
+            }
             _ => (),
         }
 
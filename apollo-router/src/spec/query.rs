@@ -31,6 +31,7 @@ use crate::json_ext::Path;
 use crate::json_ext::ResponsePathElement;
 use crate::json_ext::Value;
 use crate::plugins::authorization::UnauthorizedPaths;
+use crate::plugins::limits::ParserLimits;
 use crate::query_planner::fetch::OperationKind;
 use crate::query_planner::fetch::QueryHash;
 use crate::services::layers::query_analysis::ParsedDocument;
@@ -39,12 +40,14 @@ use crate::spec::schema::ApiSchema;
 use crate::spec::FieldType;
 use crate::spec::Fragments;
 use crate::spec::InvalidValue;
+use crate::spec::ParserLimit;
 use crate::spec::Schema;
 use crate::spec::Selection;
 use crate::spec::SpecError;
 use crate::Configuration;
 
 pub(crate) mod change;
+pub(crate) mod literals;
 pub(crate) mod subselections;
 pub(crate) mod transform;
 pub(crate) mod traverse;
@@ -274,13 +277,35 @@ impl Query {
         operation_name: Option<&str>,
         schema: &Schema,
         configuration: &Configuration,
+    ) -> Result<ParsedDocument, SpecError> {
+        Self::parse_document_with_limits(
+            query,
+            operation_name,
+            schema,
+            configuration.limits.parser_limits(None),
+        )
+    }
+
+    /// Parses and validates the operation, with the parser limits of the client sending it
+    pub(crate) fn parse_document_with_limits(
+        query: &str,
+        operation_name: Option<&str>,
+        schema: &Schema,
+        limits: ParserLimits,
     ) -> Result<ParsedDocument, SpecError> {
         let parser = &mut apollo_compiler::parser::Parser::new()
-            .recursion_limit(configuration.limits.parser_max_recursion)
-            .token_limit(configuration.limits.parser_max_tokens);
+            .recursion_limit(limits.max_recursion)
+            .token_limit(limits.max_tokens);
         let ast = match parser.parse_ast(query, "query.graphql") {
             Ok(ast) => ast,
             Err(errors) => {
+                // the parser aborts when a limit is reached, the other errors are irrelevant
+                if parser.recursion_reached() >= limits.max_recursion {
+                    return Err(SpecError::ParserLimitExceeded(ParserLimit::Recursion));
+                }
+                if parser.tokens_reached() >= limits.max_tokens {
+                    return Err(SpecError::ParserLimitExceeded(ParserLimit::Token));
+                }
                 return Err(SpecError::ParseError(errors.into()));
             }
         };
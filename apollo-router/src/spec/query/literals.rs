@@ -0,0 +1,112 @@
+//! Removal of the literals of a GraphQL document
+//!
+//! Literals written in a document can hold personal data, like an email passed as an argument.
+//! Stripping them keeps the shape of the operation, with its variables and enum values, so that
+//! documents can be written to samples and telemetry.
+
+use apollo_compiler::ast;
+use apollo_compiler::Node;
+
+/// Replaces the literals of the query, which can hold personal data: strings are emptied and
+/// numbers set to zero. Returns `None` if the query cannot be parsed.
+pub(crate) fn strip_literals(query: &str) -> Option<String> {
+    let mut document = ast::Document::parse(query, "query.graphql").ok()?;
+    for definition in &mut document.definitions {
+        match definition {
+            ast::Definition::OperationDefinition(operation) => {
+                let operation = operation.make_mut();
+                for variable in &mut operation.variables {
+                    let variable = variable.make_mut();
+                    if let Some(default_value) = &mut variable.default_value {
+                        strip_value(default_value);
+                    }
+                    strip_directives(&mut variable.directives);
+                }
+                strip_directives(&mut operation.directives);
+                strip_selection_set(&mut operation.selection_set);
+            }
+            ast::Definition::FragmentDefinition(fragment) => {
+                let fragment = fragment.make_mut();
+                strip_directives(&mut fragment.directives);
+                strip_selection_set(&mut fragment.selection_set);
+            }
+            _ => {}
+        }
+    }
+    Some(document.to_string())
+}
+
+fn strip_selection_set(selection_set: &mut [ast::Selection]) {
+    for selection in selection_set {
+        match selection {
+            ast::Selection::Field(field) => {
+                let field = field.make_mut();
+                strip_arguments(&mut field.arguments);
+                strip_directives(&mut field.directives);
+                strip_selection_set(&mut field.selection_set);
+            }
+            ast::Selection::FragmentSpread(spread) => {
+                strip_directives(&mut spread.make_mut().directives);
+            }
+            ast::Selection::InlineFragment(inline) => {
+                let inline = inline.make_mut();
+                strip_directives(&mut inline.directives);
+                strip_selection_set(&mut inline.selection_set);
+            }
+        }
+    }
+}
+
+fn strip_directives(directives: &mut ast::DirectiveList) {
+    for directive in directives.iter_mut() {
+        strip_arguments(&mut directive.make_mut().arguments);
+    }
+}
+
+fn strip_arguments(arguments: &mut [Node<ast::Argument>]) {
+    for argument in arguments {
+        strip_value(&mut argument.make_mut().value);
+    }
+}
+
+fn strip_value(value: &mut Node<ast::Value>) {
+    match value.make_mut() {
+        ast::Value::String(string) => string.clear(),
+        ast::Value::Int(int) => *int = 0.into(),
+        ast::Value::Float(float) => *float = 0.0.into(),
+        ast::Value::List(values) => values.iter_mut().for_each(strip_value),
+        ast::Value::Object(fields) => fields.iter_mut().for_each(|(_, value)| strip_value(value)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_strips_literals() {
+        let query = r#"query Users($limit: Int = 10) @cached(ttl: 30.5) {
+            users(filter: { email: "ada@example.com", roles: [ADMIN], age: 36 }, limit: $limit) {
+                ...UserFields @include(if: true)
+            }
+        }
+        fragment UserFields on User { name(format: "short") }"#;
+
+        let stripped = strip_literals(query).unwrap();
+        assert!(!stripped.contains("ada@example.com"));
+        assert!(!stripped.contains("short"));
+        assert!(!stripped.contains("36"));
+        assert!(!stripped.contains("30.5"));
+        assert!(stripped.contains(r#"email: """#));
+        assert!(stripped.contains("ADMIN"));
+        assert!(stripped.contains("age: 0"));
+        assert!(stripped.contains("limit: $limit"));
+        assert!(stripped.contains("@include(if: true)"));
+    }
+
+    #[test]
+    fn it_does_not_strip_invalid_documents() {
+        assert_eq!(strip_literals("query {"), None);
+    }
+}
@@ -17,6 +17,8 @@ use semver::VersionReq;
 use sha2::Digest;
 use sha2::Sha256;
 
+use super::capabilities;
+use super::capabilities::CapabilityGap;
 use crate::error::ParseErrors;
 use crate::error::SchemaError;
 use crate::query_planner::OperationKind;
@@ -25,11 +27,13 @@ use crate::Configuration;
 /// A GraphQL schema.
 pub(crate) struct Schema {
     pub(crate) raw_sdl: Arc<String>,
+    planner_sdl: Arc<String>,
     supergraph: Supergraph,
     subgraphs: HashMap<String, Uri>,
     pub(crate) implementers_map: apollo_compiler::collections::HashMap<Name, Implementers>,
     api_schema: ApiSchema,
     pub(crate) schema_id: Arc<String>,
+    capability_gaps: Vec<CapabilityGap>,
 }
 
 /// Wrapper type to distinguish from `Schema::definitions` for the supergraph schema
@@ -48,17 +52,31 @@ impl Schema {
         let start = Instant::now();
         let mut parser = apollo_compiler::parser::Parser::new();
         let result = parser.parse_ast(raw_sdl.as_ref(), "schema.graphql");
-
         // Trace log recursion limit data
         let recursion_limit = parser.recursion_reached();
         tracing::trace!(?recursion_limit, "recursion limit data");
 
-        let definitions = result
-            .map_err(|invalid| {
-                SchemaError::Parse(ParseErrors {
-                    errors: invalid.errors,
-                })
-            })?
+        let mut document = result.map_err(|invalid| {
+            SchemaError::Parse(ParseErrors {
+                errors: invalid.errors,
+            })
+        })?;
+
+        // the schema ID and the errors refer to the original supergraph, only the query planners
+        // get the rewritten one
+        let mut planner_sdl = raw_sdl.clone();
+        let mut capability_gaps = Vec::new();
+        if config.supergraph.experimental_spec_fallback {
+            capability_gaps = capabilities::degrade_unsupported_features(&mut document)?;
+            for gap in &capability_gaps {
+                gap.log();
+            }
+            if !capability_gaps.is_empty() {
+                planner_sdl = Arc::new(document.to_string());
+            }
+        }
+
+        let definitions = document
             .to_schema_validate()
             .map_err(|errors| SchemaError::Validate(errors.into()))?;
 
@@ -123,11 +141,13 @@ impl Schema {
 
         Ok(Schema {
             raw_sdl,
+            planner_sdl,
             supergraph,
             subgraphs,
             implementers_map,
             api_schema: ApiSchema(api_schema),
             schema_id,
+            capability_gaps,
         })
     }
 
@@ -201,6 +221,18 @@ impl Schema {
         self.subgraphs.get(service_name)
     }
 
+    /// Return the supergraph given to the query planners: the raw SDL, with the features the
+    /// router does not support degraded by `supergraph.experimental_spec_fallback`
+    pub(crate) fn planner_sdl(&self) -> &Arc<String> {
+        &self.planner_sdl
+    }
+
+    /// Return the features linked by the supergraph that the router does not support, degraded
+    /// with `supergraph.experimental_spec_fallback`
+    pub(crate) fn capability_gaps(&self) -> &[CapabilityGap] {
+        &self.capability_gaps
+    }
+
     /// Return the API schema for this supergraph.
     pub(crate) fn api_schema(&self) -> &ApiSchema {
         &self.api_schema
@@ -330,16 +362,19 @@ impl std::fmt::Debug for Schema {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let Self {
             raw_sdl,
+            planner_sdl: _,
             supergraph: _, // skip
             subgraphs,
             implementers_map,
             api_schema: _, // skip
             schema_id: _,
+            capability_gaps,
         } = self;
         f.debug_struct("Schema")
             .field("raw_sdl", raw_sdl)
             .field("subgraphs", subgraphs)
             .field("implementers_map", implementers_map)
+            .field("capability_gaps", capability_gaps)
             .finish()
     }
 }
@@ -558,6 +593,49 @@ mod tests {
         assert_eq!(schema.federation_version(), Some(2));
     }
 
+    #[test]
+    fn spec_fallback() {
+        let sdl = include_str!("../testdata/minimal_fed2_supergraph.graphql").replace(
+            "https://specs.apollo.dev/join/v0.3",
+            "https://specs.apollo.dev/join/v0.99",
+        );
+        assert!(Schema::parse(&sdl, &Default::default()).is_err());
+
+        let config = Configuration::fake_builder()
+            .supergraph(
+                crate::configuration::Supergraph::fake_builder()
+                    .experimental_spec_fallback(true)
+                    .build(),
+            )
+            .build()
+            .unwrap();
+        let schema = Schema::parse(&sdl, &config).unwrap();
+        assert_eq!(schema.capability_gaps().len(), 1);
+        assert_eq!(
+            schema.capability_gaps()[0].handled_as.as_deref(),
+            Some("https://specs.apollo.dev/join/v0.5")
+        );
+        // only the query planners get the rewritten supergraph
+        assert_eq!(*schema.schema_id, Schema::schema_id(&sdl));
+        assert_eq!(schema.raw_sdl.as_str(), sdl);
+        assert!(schema
+            .planner_sdl()
+            .contains("https://specs.apollo.dev/join/v0.5"));
+
+        // errors are located in the original supergraph
+        let sdl = format!("{sdl}\ntype Broken {{\n  field: Missing\n}}\n");
+        let line = sdl
+            .lines()
+            .position(|line| line.contains("field: Missing"))
+            .unwrap()
+            + 1;
+        let errors = Schema::parse(&sdl, &config)
+            .unwrap_err()
+            .located_errors(&sdl);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, line);
+    }
+
     #[test]
     fn schema_id() {
         #[cfg(not(windows))]
@@ -31,6 +31,7 @@ use crate::configuration::metrics::Metrics;
 use crate::configuration::Configuration;
 use crate::configuration::Discussed;
 use crate::configuration::ListenAddr;
+use crate::plugins::telemetry::config::Conf as TelemetryConfig;
 use crate::plugins::telemetry::reload::apollo_opentelemetry_initialized;
 use crate::router::Event::UpdateLicense;
 use crate::router_factory::RouterFactory;
@@ -317,10 +318,12 @@ impl<FA: RouterSuperServiceFactory> State<FA> {
         S: HttpServerFactory,
         FA: RouterSuperServiceFactory,
     {
-        let schema = Arc::new(
-            Schema::parse_arc(sdl.clone(), &configuration)
-                .map_err(|e| ServiceCreationError(e.to_string().into()))?,
-        );
+        let schema = Arc::new(Schema::parse_arc(sdl.clone(), &configuration).map_err(|e| {
+            for error in e.located_errors(&sdl) {
+                error.log("schema");
+            }
+            ServiceCreationError(e.to_string().into())
+        })?);
         // Check the license
         let report = LicenseEnforcementReport::build(&configuration, &schema);
 
@@ -357,6 +360,7 @@ impl<FA: RouterSuperServiceFactory> State<FA> {
             license
         };
 
+        let capability_gaps = schema.capability_gaps().to_vec();
         let router_service_factory = state_machine
             .router_configurator
             .create(
@@ -418,6 +422,15 @@ impl<FA: RouterSuperServiceFactory> State<FA> {
 
         let metrics =
             apollo_opentelemetry_initialized().then(|| Metrics::new(&configuration, &license));
+        crate::status::record_running(&configuration, &sdl, capability_gaps, license);
+        // annotates dashboards with schema and configuration releases
+        u64_counter!(
+            "apollo.router.reload",
+            "Number of times the router started serving a schema and configuration",
+            1,
+            "schema.id" = Schema::schema_id(&sdl),
+            "release" = TelemetryConfig::metrics_release(&configuration).unwrap_or_default()
+        );
 
         Ok(Running {
             configuration,
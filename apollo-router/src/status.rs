@@ -0,0 +1,233 @@
+//! Live status of the router, exposed by the admin API
+//!
+//! The state machine, the uplink streams and the caches record what they are doing here, so
+//! that fleet management tools can read it as JSON instead of scraping logs.
+
+use std::collections::BTreeMap;
+use std::collections::VecDeque;
+use std::sync::atomic::AtomicI64;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::Serialize;
+use sha2::Digest;
+use sha2::Sha256;
+
+use crate::configuration::Configuration;
+use crate::spec::capabilities::CapabilityGap;
+use crate::spec::Schema;
+use crate::uplink::license_enforcement::LicenseState;
+
+/// Number of uplink errors kept for each query
+const MAX_UPLINK_ERRORS: usize = 10;
+
+static STATUS: Lazy<Mutex<Status>> = Lazy::new(Default::default);
+
+#[derive(Default)]
+struct Status {
+    schema_id: Option<String>,
+    capability_gaps: Vec<CapabilityGap>,
+    configuration_hash: Option<String>,
+    license: Option<LicenseState>,
+    last_reload: Option<SystemTime>,
+    plugins: Vec<String>,
+    uplink: BTreeMap<&'static str, UplinkStatus>,
+    caches: BTreeMap<String, Arc<CacheCounters>>,
+}
+
+#[derive(Default)]
+struct UplinkStatus {
+    last_success: Option<SystemTime>,
+    errors: VecDeque<(SystemTime, String)>,
+}
+
+/// Statistics of an in memory cache
+#[derive(Default)]
+pub(crate) struct CacheCounters {
+    size: Arc<AtomicI64>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl CacheCounters {
+    pub(crate) fn hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn miss(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Records the configuration, schema and license the router is running with, and the features
+/// linked by the schema that the router does not support
+pub(crate) fn record_running(
+    configuration: &Configuration,
+    sdl: &str,
+    capability_gaps: Vec<CapabilityGap>,
+    license: LicenseState,
+) {
+    let mut status = STATUS.lock();
+    status.schema_id = Some(Schema::schema_id(sdl));
+    status.capability_gaps = capability_gaps;
+    status.configuration_hash = configuration.validated_yaml.as_ref().map(|yaml| {
+        let mut hasher = Sha256::new();
+        hasher.update(yaml.to_string().as_bytes());
+        format!("{:x}", hasher.finalize())
+    });
+    status.license = Some(license);
+    status.last_reload = Some(SystemTime::now());
+}
+
+/// Records the plugins of the router pipeline
+pub(crate) fn record_plugins(plugins: Vec<String>) {
+    STATUS.lock().plugins = plugins;
+}
+
+/// Records the result of an uplink poll
+pub(crate) fn record_uplink_poll(query: &'static str, error: Option<String>) {
+    let mut status = STATUS.lock();
+    let uplink = status.uplink.entry(query).or_default();
+    match error {
+        None => uplink.last_success = Some(SystemTime::now()),
+        Some(error) => {
+            if uplink.errors.len() == MAX_UPLINK_ERRORS {
+                uplink.errors.pop_front();
+            }
+            uplink.errors.push_back((SystemTime::now(), error));
+        }
+    }
+}
+
+/// Registers a cache, replacing the statistics of the previous cache of the same kind
+pub(crate) fn register_cache(kind: &str, size: Arc<AtomicI64>) -> Arc<CacheCounters> {
+    let counters = Arc::new(CacheCounters {
+        size,
+        ..Default::default()
+    });
+    STATUS
+        .lock()
+        .caches
+        .insert(kind.to_string(), counters.clone());
+    counters
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct StatusReport {
+    schema_id: Option<String>,
+    /// Features linked by the supergraph that the router does not support
+    unsupported_features: Vec<CapabilityGap>,
+    configuration_hash: Option<String>,
+    license: Option<String>,
+    last_reload: Option<String>,
+    plugins: Vec<String>,
+    uplink: BTreeMap<&'static str, UplinkReport>,
+    caches: BTreeMap<String, CacheReport>,
+}
+
+#[derive(Debug, Serialize)]
+struct UplinkReport {
+    last_success: Option<String>,
+    errors: Vec<UplinkErrorReport>,
+}
+
+#[derive(Debug, Serialize)]
+struct UplinkErrorReport {
+    time: String,
+    message: String,
+}
+
+#[derive(Debug, Serialize)]
+struct CacheReport {
+    size: i64,
+    hits: u64,
+    misses: u64,
+}
+
+fn format_time(time: &SystemTime) -> String {
+    humantime::format_rfc3339_seconds(*time).to_string()
+}
+
+/// Returns a snapshot of the router's status
+pub(crate) fn report() -> StatusReport {
+    let status = STATUS.lock();
+    StatusReport {
+        schema_id: status.schema_id.clone(),
+        unsupported_features: status.capability_gaps.clone(),
+        configuration_hash: status.configuration_hash.clone(),
+        license: status.license.map(|license| license.to_string()),
+        last_reload: status.last_reload.as_ref().map(format_time),
+        plugins: status.plugins.clone(),
+        uplink: status
+            .uplink
+            .iter()
+            .map(|(query, uplink)| {
+                (
+                    *query,
+                    UplinkReport {
+                        last_success: uplink.last_success.as_ref().map(format_time),
+                        errors: uplink
+                            .errors
+                            .iter()
+                            .map(|(time, message)| UplinkErrorReport {
+                                time: format_time(time),
+                                message: message.clone(),
+                            })
+                            .collect(),
+                    },
+                )
+            })
+            .collect(),
+        caches: status
+            .caches
+            .iter()
+            .map(|(kind, counters)| {
+                (
+                    kind.clone(),
+                    CacheReport {
+                        size: counters.size.load(Ordering::SeqCst),
+                        hits: counters.hits.load(Ordering::Relaxed),
+                        misses: counters.misses.load(Ordering::Relaxed),
+                    },
+                )
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_keeps_the_last_uplink_errors() {
+        let query = "StatusTest";
+        record_uplink_poll(query, None);
+        for i in 0..MAX_UPLINK_ERRORS + 2 {
+            record_uplink_poll(query, Some(format!("error {i}")));
+        }
+
+        let report = report();
+        let uplink = &report.uplink[query];
+        assert!(uplink.last_success.is_some());
+        assert_eq!(uplink.errors.len(), MAX_UPLINK_ERRORS);
+        assert_eq!(uplink.errors[0].message, "error 2");
+    }
+
+    #[test]
+    fn it_reports_cache_statistics() {
+        let size = Arc::new(AtomicI64::new(3));
+        let counters = register_cache("status test", size);
+        counters.hit();
+        counters.miss();
+        counters.miss();
+
+        let report = report();
+        let cache = &report.caches["status test"];
+        assert_eq!((cache.size, cache.hits, cache.misses), (3, 1, 2));
+    }
+}
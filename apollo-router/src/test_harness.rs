@@ -328,7 +328,7 @@ impl<'a> TestHarness<'a> {
     pub async fn build_router(self) -> Result<router::BoxCloneService, BoxError> {
         let (config, supergraph_creator) = self.build_common().await?;
         let router_creator = RouterCreator::new(
-            QueryAnalysisLayer::new(supergraph_creator.schema(), Arc::clone(&config)).await,
+            QueryAnalysisLayer::new(supergraph_creator.schema(), Arc::clone(&config)).await?,
             Arc::new(PersistedQueryLayer::new(&config).await.unwrap()),
             Arc::new(supergraph_creator),
             config.clone(),
@@ -356,7 +356,7 @@ impl<'a> TestHarness<'a> {
 
         let (config, supergraph_creator) = self.build_common().await?;
         let router_creator = RouterCreator::new(
-            QueryAnalysisLayer::new(supergraph_creator.schema(), Arc::clone(&config)).await,
+            QueryAnalysisLayer::new(supergraph_creator.schema(), Arc::clone(&config)).await?,
             Arc::new(PersistedQueryLayer::new(&config).await.unwrap()),
             Arc::new(supergraph_creator),
             config.clone(),
@@ -370,6 +370,7 @@ impl<'a> TestHarness<'a> {
         let routers = make_axum_router(
             live,
             ready,
+            Default::default(),
             router_creator,
             &config,
             web_endpoints,
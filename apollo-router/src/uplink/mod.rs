@@ -250,6 +250,11 @@ where
                         status = "success",
                         query
                     );
+                    let error = match &response {
+                        UplinkResponse::Error { message, .. } => Some(message.clone()),
+                        _ => None,
+                    };
+                    crate::status::record_uplink_poll(query, error);
                     match response {
                         UplinkResponse::New {
                             id,
@@ -299,6 +304,7 @@ where
                         status = "failure",
                         query
                     );
+                    crate::status::record_uplink_poll(query, Some(err.to_string()));
                     if let Err(e) = sender.send(Err(err)).await {
                         tracing::debug!("failed to send error to uplink stream. This is likely to be because the router is shutting down: {e}");
                         break;
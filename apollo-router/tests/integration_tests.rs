@@ -646,6 +646,39 @@ async fn query_just_at_recursion_limit() {
         message.contains("parser recursion limit reached"),
         "{message}"
     );
+    assert_eq!(
+        actual.errors[0].extensions["code"].as_str(),
+        Some("PARSER_LIMIT_EXCEEDED")
+    );
+    assert_eq!(registry.totals(), expected_service_hits);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn query_at_recursion_limit_with_client_override() {
+    let config = serde_json::json!({
+        "limits": {
+            "parser_max_recursion": PARSER_LIMITS_TEST_QUERY_RECURSION - 1,
+            "clients": {
+                "trusted-client": {
+                    "parser_max_recursion": PARSER_LIMITS_TEST_QUERY_RECURSION
+                }
+            }
+        }
+    });
+    let request = supergraph::Request::fake_builder()
+        .query(PARSER_LIMITS_TEST_QUERY)
+        .header("apollographql-client-name", "trusted-client")
+        .build()
+        .expect("expecting valid request");
+
+    let expected_service_hits = hashmap! {
+        "reviews".to_string() => 1,
+        "accounts".to_string() => 2,
+    };
+
+    let (actual, registry) = query_rust_with_config(request, config).await;
+
+    assert_eq!(actual.errors, []);
     assert_eq!(registry.totals(), expected_service_hits);
 }
 
@@ -700,6 +733,10 @@ async fn query_just_at_token_limit() {
 
     assert_eq!(1, actual.errors.len());
     assert!(actual.errors[0].message.contains("token limit reached"));
+    assert_eq!(
+        actual.errors[0].extensions["code"].as_str(),
+        Some("PARSER_LIMIT_EXCEEDED")
+    );
     assert_eq!(registry.totals(), expected_service_hits);
 }
 